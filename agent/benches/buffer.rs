@@ -0,0 +1,156 @@
+//! RingBuffer snapshot benchmarks
+//!
+//! Compares `Arc` snapshots against deep-cloning every buffered `Metrics`
+//! (the previous behaviour). Allocation counts per snapshot are printed
+//! before the timing runs.
+//!
+//! Run with `cargo bench --bench buffer`.
+
+#![allow(dead_code, unused_imports)]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+
+// The agent is a binary crate, so pull in just the modules under test.
+use nanolink_client::proto;
+#[path = "../src/buffer/mod.rs"]
+mod buffer;
+#[path = "../src/telemetry.rs"]
+mod telemetry;
+
+use buffer::RingBuffer;
+use proto::{CpuMetrics, DiskMetrics, Metrics, NetworkMetrics, UserSession};
+
+/// Global allocator that counts allocations
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// A sample roughly the size of a real server's full metrics
+fn sample_metrics(sequence: u64) -> Metrics {
+    Metrics {
+        timestamp: 1_700_000_000_000 + sequence * 1000,
+        sequence,
+        hostname: "bench-host.example.com".to_string(),
+        agent_id: "6f1c2b4e-8d3a-4c5b-9e7f-0a1b2c3d4e5f".to_string(),
+        cpu: Some(CpuMetrics {
+            usage_percent: 42.0,
+            core_count: 32,
+            per_core_usage: vec![12.5; 32],
+            model: "AMD EPYC 7543 32-Core Processor".to_string(),
+            vendor: "AMD".to_string(),
+            ..Default::default()
+        }),
+        disks: (0..8)
+            .map(|i| DiskMetrics {
+                mount_point: format!("/mnt/data{i}"),
+                device: format!("/dev/nvme{i}n1"),
+                fs_type: "ext4".to_string(),
+                total: 1 << 40,
+                used: 1 << 39,
+                ..Default::default()
+            })
+            .collect(),
+        networks: (0..6)
+            .map(|i| NetworkMetrics {
+                interface: format!("eth{i}"),
+                mac_address: "00:11:22:33:44:55".to_string(),
+                ip_addresses: vec![format!("10.0.0.{i}"), format!("fe80::{i}")],
+                is_up: true,
+                ..Default::default()
+            })
+            .collect(),
+        user_sessions: (0..4)
+            .map(|i| UserSession {
+                username: format!("user{i}"),
+                tty: format!("pts/{i}"),
+                remote_host: "192.168.1.10".to_string(),
+                session_type: "ssh".to_string(),
+                ..Default::default()
+            })
+            .collect(),
+        load_average: vec![1.0, 0.8, 0.5],
+        ..Default::default()
+    }
+}
+
+fn filled_buffer(capacity: usize) -> RingBuffer {
+    let buffer = RingBuffer::new(capacity);
+    for seq in 1..=capacity as u64 {
+        buffer.push(sample_metrics(seq));
+    }
+    buffer
+}
+
+/// Previous behaviour: every snapshot deep-clones each sample
+fn deep_clone_all(buffer: &RingBuffer) -> Vec<Metrics> {
+    buffer.get_all().iter().map(|m| (**m).clone()).collect()
+}
+
+fn count_allocations<T>(f: impl FnOnce() -> T) -> (usize, usize) {
+    let allocs = ALLOCATIONS.load(Ordering::Relaxed);
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    black_box(f());
+    (
+        ALLOCATIONS.load(Ordering::Relaxed) - allocs,
+        ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes,
+    )
+}
+
+fn report_allocations(capacity: usize) {
+    let buffer = filled_buffer(capacity);
+    let (arc_allocs, arc_bytes) = count_allocations(|| buffer.get_all());
+    let (deep_allocs, deep_bytes) = count_allocations(|| deep_clone_all(&buffer));
+    println!(
+        "get_all ({capacity} entries): arc = {arc_allocs} allocs / {arc_bytes} B, \
+         deep clone = {deep_allocs} allocs / {deep_bytes} B"
+    );
+}
+
+fn bench_snapshots(c: &mut Criterion) {
+    for capacity in [60, 600, 3600] {
+        report_allocations(capacity);
+    }
+
+    let mut group = c.benchmark_group("buffer_get_all");
+    for capacity in [60, 600, 3600] {
+        let buffer = filled_buffer(capacity);
+        group.bench_with_input(BenchmarkId::new("arc", capacity), &buffer, |b, buf| {
+            b.iter(|| black_box(buf.get_all()))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("deep_clone", capacity),
+            &buffer,
+            |b, buf| b.iter(|| black_box(deep_clone_all(buf))),
+        );
+    }
+    group.finish();
+
+    let buffer = filled_buffer(600);
+    c.bench_function("buffer_get_since_half", |b| {
+        b.iter(|| black_box(buffer.get_since(300)))
+    });
+    c.bench_function("buffer_latest", |b| b.iter(|| black_box(buffer.latest())));
+}
+
+criterion_group!(benches, bench_snapshots);
+criterion_main!(benches);
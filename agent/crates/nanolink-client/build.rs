@@ -10,8 +10,8 @@ fn main() -> Result<()> {
 
     // Use CARGO_MANIFEST_DIR for reliable path resolution
     let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
-    let proto_path = Path::new(&manifest_dir).join("../sdk/protocol/nanolink.proto");
-    let proto_dir = Path::new(&manifest_dir).join("../sdk/protocol");
+    let proto_path = Path::new(&manifest_dir).join("../../../sdk/protocol/nanolink.proto");
+    let proto_dir = Path::new(&manifest_dir).join("../../../sdk/protocol");
 
     // Tell cargo to rerun if proto file changes
     println!("cargo:rerun-if-changed={}", proto_path.display());
@@ -26,9 +26,19 @@ fn main() -> Result<()> {
 
     // Use tonic-prost-build to generate both protobuf messages and gRPC client/server code
     // Output goes to OUT_DIR by default
-    tonic_prost_build::configure()
-        .build_server(false) // Agent only needs client
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+
+    let mut builder = tonic_prost_build::configure()
+        // Server stubs are only needed by the mock server
+        .build_server(std::env::var("CARGO_FEATURE_SERVER").is_ok())
         .build_client(true)
+        // Served at GET /api/proto/descriptor for grpcurl and other clients
+        .file_descriptor_set_path(Path::new(&out_dir).join("nanolink_descriptor.bin"));
+    if std::env::var("CARGO_FEATURE_SERDE").is_ok() {
+        // JSON export of collected metrics (`nanolink-agent snapshot`)
+        builder = builder.type_attribute(".nanolink", "#[derive(serde::Serialize)]");
+    }
+    builder
         // Suppress clippy::large_enum_variant on generated Payload enums
         .type_attribute(
             "nanolink.Message.Payload",
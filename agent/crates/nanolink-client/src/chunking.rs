@@ -0,0 +1,235 @@
+//! Splitting of oversized protobuf messages
+//!
+//! Hosts with hundreds of disks or interfaces can produce `StaticInfo` and
+//! full `Metrics` messages larger than the negotiated gRPC message limit,
+//! which kills the stream. Such messages are split into several partial
+//! messages: the first carries all scalar fields, and every chunk carries a
+//! share of the repeated entries plus a `ChunkInfo` so the server can
+//! reassemble them. Messages that fit are sent unchanged without `ChunkInfo`.
+
+use prost::Message;
+use tracing::warn;
+
+use crate::proto::{ChunkInfo, Metrics, StaticInfo};
+
+/// Default gRPC message limit (matches tonic and most server defaults)
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Room reserved for the stream envelope and the `ChunkInfo` field
+const ENVELOPE_OVERHEAD: usize = 64;
+
+/// Effective message limit after negotiation.
+///
+/// `server_max` of 0 means the server did not advertise a limit.
+pub fn negotiate_max_message_size(local_max: usize, server_max: u32) -> usize {
+    match server_max as usize {
+        0 => local_max,
+        server_max => local_max.min(server_max),
+    }
+}
+
+/// Accumulates repeated entries into chunks that fit the size budget
+struct Chunker<M> {
+    chunks: Vec<M>,
+    current: M,
+    current_len: usize,
+    current_items: usize,
+    header: M,
+    budget: usize,
+}
+
+impl<M: Message + Clone> Chunker<M> {
+    fn new(first: M, header: M, max_size: usize) -> Self {
+        Self {
+            current_len: first.encoded_len(),
+            chunks: Vec::new(),
+            current: first,
+            current_items: 0,
+            header,
+            budget: max_size.saturating_sub(ENVELOPE_OVERHEAD),
+        }
+    }
+
+    fn push<T: Message>(&mut self, items: Vec<T>, field: fn(&mut M) -> &mut Vec<T>) {
+        for item in items {
+            let len = item.encoded_len();
+            // Field tag plus length prefix
+            let needed = len + prost::length_delimiter_len(len) + 1;
+
+            if self.current_items > 0 && self.current_len + needed > self.budget {
+                let next = self.header.clone();
+                self.current_len = next.encoded_len();
+                self.current_items = 0;
+                self.chunks.push(std::mem::replace(&mut self.current, next));
+            }
+
+            if self.current_items == 0 && self.current_len + needed > self.budget {
+                warn!(
+                    "Single entry of {} bytes exceeds the message budget of {} bytes",
+                    len, self.budget
+                );
+            }
+
+            field(&mut self.current).push(item);
+            self.current_len += needed;
+            self.current_items += 1;
+        }
+    }
+
+    fn finish(mut self, set_chunk: fn(&mut M, ChunkInfo)) -> Vec<M> {
+        self.chunks.push(self.current);
+        let count = self.chunks.len() as u32;
+        for (index, chunk) in self.chunks.iter_mut().enumerate() {
+            set_chunk(
+                chunk,
+                ChunkInfo {
+                    index: index as u32,
+                    count,
+                    complete: index as u32 + 1 == count,
+                },
+            );
+        }
+        self.chunks
+    }
+}
+
+/// Split a full metrics message so every part fits `max_size`
+pub fn split_metrics(mut metrics: Metrics, max_size: usize) -> Vec<Metrics> {
+    if metrics.encoded_len() + ENVELOPE_OVERHEAD <= max_size {
+        return vec![metrics];
+    }
+
+    let disks = std::mem::take(&mut metrics.disks);
+    let networks = std::mem::take(&mut metrics.networks);
+    let gpus = std::mem::take(&mut metrics.gpus);
+    let npus = std::mem::take(&mut metrics.npus);
+    let user_sessions = std::mem::take(&mut metrics.user_sessions);
+
+    let header = Metrics {
+        timestamp: metrics.timestamp,
+        sequence: metrics.sequence,
+        hostname: metrics.hostname.clone(),
+        agent_id: metrics.agent_id.clone(),
+        metrics_type: metrics.metrics_type,
+        is_initial: metrics.is_initial,
+        ..Default::default()
+    };
+
+    let mut chunker = Chunker::new(metrics, header, max_size);
+    chunker.push(disks, |m| &mut m.disks);
+    chunker.push(networks, |m| &mut m.networks);
+    chunker.push(gpus, |m| &mut m.gpus);
+    chunker.push(npus, |m| &mut m.npus);
+    chunker.push(user_sessions, |m| &mut m.user_sessions);
+    chunker.finish(|m, chunk| m.chunk = Some(chunk))
+}
+
+/// Split a static info message so every part fits `max_size`
+pub fn split_static_info(mut info: StaticInfo, max_size: usize) -> Vec<StaticInfo> {
+    if info.encoded_len() + ENVELOPE_OVERHEAD <= max_size {
+        return vec![info];
+    }
+
+    let disks = std::mem::take(&mut info.disks);
+    let networks = std::mem::take(&mut info.networks);
+    let gpus = std::mem::take(&mut info.gpus);
+    let npus = std::mem::take(&mut info.npus);
+
+    let header = StaticInfo {
+        timestamp: info.timestamp,
+        agent_version: info.agent_version.clone(),
+        agent_id: info.agent_id.clone(),
+        ..Default::default()
+    };
+
+    let mut chunker = Chunker::new(info, header, max_size);
+    chunker.push(disks, |m| &mut m.disks);
+    chunker.push(networks, |m| &mut m.networks);
+    chunker.push(gpus, |m| &mut m.gpus);
+    chunker.push(npus, |m| &mut m.npus);
+    chunker.finish(|m, chunk| m.chunk = Some(chunk))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::{DiskMetrics, NetworkStaticInfo};
+
+    fn big_metrics(disks: usize) -> Metrics {
+        Metrics {
+            timestamp: 1,
+            sequence: 7,
+            hostname: "host".to_string(),
+            disks: (0..disks)
+                .map(|i| DiskMetrics {
+                    mount_point: format!("/mnt/disk{i:04}"),
+                    device: format!("/dev/sd{i:04}"),
+                    fs_type: "xfs".to_string(),
+                    total: u64::MAX,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_small_message_is_unchanged() {
+        let chunks = split_metrics(big_metrics(2), DEFAULT_MAX_MESSAGE_SIZE);
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].chunk.is_none());
+    }
+
+    #[test]
+    fn test_split_metrics_fits_and_preserves_entries() {
+        let max_size = 2048;
+        let chunks = split_metrics(big_metrics(200), max_size);
+
+        assert!(chunks.len() > 1);
+        let total: usize = chunks.iter().map(|c| c.disks.len()).sum();
+        assert_eq!(total, 200);
+
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert!(chunk.encoded_len() <= max_size);
+            assert_eq!(chunk.sequence, 7);
+            let info = chunk.chunk.as_ref().unwrap();
+            assert_eq!(info.index as usize, i);
+            assert_eq!(info.count as usize, chunks.len());
+            assert_eq!(info.complete, i + 1 == chunks.len());
+        }
+
+        // Entry order is preserved across chunks
+        assert_eq!(chunks[0].disks[0].mount_point, "/mnt/disk0000");
+        assert_eq!(
+            chunks.last().unwrap().disks.last().unwrap().mount_point,
+            "/mnt/disk0199"
+        );
+    }
+
+    #[test]
+    fn test_split_static_info() {
+        let info = StaticInfo {
+            agent_id: "id".to_string(),
+            networks: (0..300)
+                .map(|i| NetworkStaticInfo {
+                    interface: format!("veth{i:05}"),
+                    mac_address: "00:11:22:33:44:55".to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        let chunks = split_static_info(info, 4096);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.agent_id == "id"));
+        assert_eq!(chunks.iter().map(|c| c.networks.len()).sum::<usize>(), 300);
+    }
+
+    #[test]
+    fn test_negotiate_max_message_size() {
+        assert_eq!(negotiate_max_message_size(4096, 0), 4096);
+        assert_eq!(negotiate_max_message_size(4096, 1024), 1024);
+        assert_eq!(negotiate_max_message_size(1024, 4096), 1024);
+    }
+}
@@ -0,0 +1,349 @@
+//! gRPC connection to a NanoLink server
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio_stream::Stream;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Request, Streaming};
+
+use crate::proto::{
+    AgentInit, AuthRequest, AuthResponse, ConfigProfile, ConfigProfileRequest, EnrollRequest,
+    EnrollResponse, Metrics, MetricsStreamRequest, MetricsStreamResponse,
+    nano_link_service_client::NanoLinkServiceClient,
+};
+use crate::protocol::{PROTOCOL_VERSION, SESSION_METADATA, negotiate_protocol};
+use crate::{Server, chunking, tls};
+
+/// How a connection is established
+#[derive(Debug, Clone)]
+pub struct ConnectOptions {
+    /// Time allowed for TCP, TLS and HTTP/2 setup
+    pub connect_timeout: Duration,
+    /// HTTP/2 keepalive pings, for long-lived streams through NAT and
+    /// firewalls
+    pub keepalive: bool,
+    /// Local message size limit in bytes, lowered to the server's during
+    /// authentication
+    pub max_message_size: usize,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(15),
+            keepalive: true,
+            max_message_size: chunking::DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+}
+
+/// Who is connecting; sent with authentication, enrollment and `AgentInit`
+#[derive(Debug, Clone, Default)]
+pub struct AgentIdentity {
+    /// Stable ID the server keys the host by
+    pub agent_id: String,
+    pub hostname: String,
+    pub agent_version: String,
+    pub os: String,
+    pub arch: String,
+    /// Capabilities requested from the server
+    pub capabilities: Vec<String>,
+}
+
+impl AgentIdentity {
+    /// An identity for this machine's OS and architecture
+    pub fn new(
+        agent_id: impl Into<String>,
+        hostname: impl Into<String>,
+        agent_version: impl Into<String>,
+    ) -> Self {
+        Self {
+            agent_id: agent_id.into(),
+            hostname: hostname.into(),
+            agent_version: agent_version.into(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            capabilities: Vec::new(),
+        }
+    }
+
+    pub fn auth_request(&self, token: impl Into<String>) -> AuthRequest {
+        AuthRequest {
+            token: token.into(),
+            hostname: self.hostname.clone(),
+            agent_version: self.agent_version.clone(),
+            os: self.os.clone(),
+            arch: self.arch.clone(),
+            capabilities: self.capabilities.clone(),
+            agent_id: self.agent_id.clone(),
+            protocol_version: PROTOCOL_VERSION,
+        }
+    }
+
+    pub fn enroll_request(&self, code: impl Into<String>) -> EnrollRequest {
+        EnrollRequest {
+            code: code.into(),
+            hostname: self.hostname.clone(),
+            os: self.os.clone(),
+            arch: self.arch.clone(),
+            agent_version: self.agent_version.clone(),
+            agent_id: self.agent_id.clone(),
+        }
+    }
+
+    /// First message of a metrics stream
+    pub fn agent_init(&self) -> AgentInit {
+        AgentInit {
+            agent_id: self.agent_id.clone(),
+            hostname: self.hostname.clone(),
+            os: self.os.clone(),
+            arch: self.arch.clone(),
+            agent_version: self.agent_version.clone(),
+        }
+    }
+}
+
+/// Connection to a NanoLink server
+#[derive(Clone)]
+pub struct Client {
+    service: NanoLinkServiceClient<Channel>,
+    tls: Option<tls::Handshake>,
+    local_max_message_size: usize,
+    /// Negotiated maximum message size in bytes
+    max_message_size: usize,
+    /// Negotiated stream protocol version
+    protocol_version: u32,
+    permission_level: i32,
+    /// Issued by the server during authentication, presented on streams
+    session_token: String,
+}
+
+impl Client {
+    /// Connect to a server. TLS, when enabled, goes through the pinning
+    /// verifier in [`tls`].
+    pub async fn connect(server: &Server, options: &ConnectOptions) -> Result<Self> {
+        let mut endpoint = Endpoint::from_shared(tls::endpoint_uri(server))
+            .context("Invalid server URL")?
+            // Note: Don't set .timeout() here - it kills streaming RPCs
+            // TCP keepalive is set when dialing, see resolve::dial
+            .connect_timeout(options.connect_timeout);
+        if options.keepalive {
+            // HTTP/2 keepalive - gRPC level (must match server settings)
+            // Server: keepAliveTime=30s, keepAliveTimeout=10s
+            endpoint = endpoint
+                .http2_keep_alive_interval(Duration::from_secs(20))
+                .keep_alive_timeout(Duration::from_secs(10))
+                .keep_alive_while_idle(true);
+        }
+
+        let (channel, handshake) = tls::connect(endpoint, server)
+            .await
+            .context("Failed to connect to server")?;
+
+        let max_message_size = options.max_message_size;
+        let service = NanoLinkServiceClient::new(channel)
+            .max_encoding_message_size(max_message_size)
+            .max_decoding_message_size(max_message_size);
+
+        Ok(Self {
+            service,
+            tls: handshake,
+            local_max_message_size: max_message_size,
+            max_message_size,
+            protocol_version: PROTOCOL_VERSION,
+            permission_level: 0,
+            session_token: String::new(),
+        })
+    }
+
+    /// Authenticate. On success the message size limit and protocol version
+    /// are negotiated with the server.
+    pub async fn authenticate(&mut self, request: AuthRequest) -> Result<AuthResponse> {
+        let response = self
+            .service
+            .authenticate(Request::new(request))
+            .await
+            .context("Authentication failed")?
+            .into_inner();
+
+        if response.success {
+            self.permission_level = response.permission_level;
+            self.max_message_size = chunking::negotiate_max_message_size(
+                self.local_max_message_size,
+                response.max_message_size,
+            );
+            self.service = self
+                .service
+                .clone()
+                .max_encoding_message_size(self.max_message_size);
+            self.protocol_version = negotiate_protocol(response.protocol_version);
+            self.session_token = response.session_token.clone();
+        }
+        Ok(response)
+    }
+
+    /// A stream request, with the session token if the server issued one
+    fn stream_request<T>(&self, requests: T) -> Request<T> {
+        let mut request = Request::new(requests);
+        if !self.session_token.is_empty()
+            && let Ok(token) = self.session_token.parse()
+        {
+            request.metadata_mut().insert(SESSION_METADATA, token);
+        }
+        request
+    }
+
+    /// Exchange an enrollment code for a token. TLS is required, as the code
+    /// alone grants access. Returns the response and the server certificate
+    /// fingerprint.
+    pub async fn enroll(
+        server: &Server,
+        options: &ConnectOptions,
+        request: EnrollRequest,
+    ) -> Result<(EnrollResponse, String)> {
+        if !server.tls_enabled {
+            anyhow::bail!("Enrollment requires TLS");
+        }
+        let mut client = Self::connect(server, options).await?;
+        let tls_fingerprint = client
+            .tls
+            .take()
+            .map(|tls| tls.fingerprint)
+            .ok_or_else(|| anyhow::anyhow!("Server did not present a certificate"))?;
+
+        let response = client
+            .service
+            .enroll(Request::new(request))
+            .await
+            .context("Enrollment failed")?;
+
+        Ok((response.into_inner(), tls_fingerprint))
+    }
+
+    /// Open the bidirectional metrics stream. Messages for it should be
+    /// passed through [`crate::protocol::downgrade`] and split with
+    /// [`chunking`] first.
+    pub async fn stream_metrics(
+        &mut self,
+        requests: impl Stream<Item = MetricsStreamRequest> + Send + 'static,
+    ) -> Result<Streaming<MetricsStreamResponse>> {
+        let response = self
+            .service
+            .stream_metrics(self.stream_request(requests))
+            .await
+            .context("Failed to start metrics stream")?;
+        Ok(response.into_inner())
+    }
+
+    /// Open the dedicated command stream. `None` when the server doesn't
+    /// support it, in which case commands arrive on the metrics stream.
+    pub async fn stream_commands(
+        &mut self,
+        requests: impl Stream<Item = MetricsStreamRequest> + Send + 'static,
+    ) -> Result<Option<Streaming<MetricsStreamResponse>>> {
+        let request = self.stream_request(requests);
+        match self.service.stream_commands(request).await {
+            Ok(response) => Ok(Some(response.into_inner())),
+            Err(status) if status.code() == tonic::Code::Unimplemented => Ok(None),
+            Err(status) => Err(status).context("Failed to start command stream"),
+        }
+    }
+
+    /// Report metrics with the unary RPC, split into chunks if needed.
+    /// Returns whether every chunk was acknowledged.
+    pub async fn report_metrics(&mut self, metrics: Metrics) -> Result<bool> {
+        let mut acknowledged = true;
+        for chunk in chunking::split_metrics(metrics, self.max_message_size) {
+            let response = self
+                .service
+                .report_metrics(Request::new(chunk))
+                .await
+                .context("Failed to report metrics")?;
+            acknowledged &= response.into_inner().success;
+        }
+        Ok(acknowledged)
+    }
+
+    /// Request the central config profile
+    pub async fn get_config_profile(
+        &mut self,
+        request: ConfigProfileRequest,
+    ) -> Result<ConfigProfile> {
+        let response = self
+            .service
+            .get_config_profile(Request::new(request))
+            .await
+            .context("Failed to get config profile")?;
+        Ok(response.into_inner())
+    }
+
+    /// The generated client, for RPCs without a wrapper here
+    pub fn service(&mut self) -> &mut NanoLinkServiceClient<Channel> {
+        &mut self.service
+    }
+
+    /// SHA-256 fingerprint of the server certificate seen during the TLS handshake
+    pub fn tls_fingerprint(&self) -> Option<&str> {
+        self.tls.as_ref().map(|tls| tls.fingerprint.as_str())
+    }
+
+    /// Protocol version, cipher suite and certificate of the TLS handshake
+    pub fn tls_handshake(&self) -> Option<&tls::Handshake> {
+        self.tls.as_ref()
+    }
+
+    /// Permission level granted by the last successful authentication
+    pub fn permission_level(&self) -> i32 {
+        self.permission_level
+    }
+
+    /// Message size limit in bytes, negotiated during authentication
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    /// Stream protocol version, negotiated during authentication
+    pub fn protocol_version(&self) -> u32 {
+        self.protocol_version
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_requests() {
+        let mut identity = AgentIdentity::new("id-1", "web-01", "0.4.1");
+        identity.capabilities = vec!["metrics".to_string()];
+
+        let auth = identity.auth_request("secret");
+        assert_eq!(auth.token, "secret");
+        assert_eq!(auth.agent_id, "id-1");
+        assert_eq!(auth.os, std::env::consts::OS);
+        assert_eq!(auth.capabilities, ["metrics"]);
+        assert_eq!(auth.protocol_version, PROTOCOL_VERSION);
+
+        let enroll = identity.enroll_request("ABC123");
+        assert_eq!(
+            (enroll.code.as_str(), enroll.hostname.as_str()),
+            ("ABC123", "web-01")
+        );
+        assert_eq!(identity.agent_init().agent_version, "0.4.1");
+    }
+
+    #[tokio::test]
+    async fn test_enroll_requires_tls() {
+        let server = Server {
+            host: "127.0.0.1".to_string(),
+            port: 1,
+            ..Default::default()
+        };
+        let request = AgentIdentity::default().enroll_request("ABC123");
+        let err = Client::enroll(&server, &ConnectOptions::default(), request)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("requires TLS"));
+    }
+}
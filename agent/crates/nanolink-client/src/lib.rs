@@ -0,0 +1,91 @@
+//! Client library for NanoLink servers
+//!
+//! The protocol types generated from `nanolink.proto`, a gRPC [`Client`]
+//! with certificate pinning, authentication and enrollment, and the pieces
+//! of the stream protocol the agent uses: message chunking and version
+//! negotiation. Other Rust services can use it to talk to a NanoLink server
+//! or to embed a small agent.
+//!
+//! ```no_run
+//! use nanolink_client::{AgentIdentity, Client, ConnectOptions, Server};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let server = Server {
+//!     host: "monitor.example.com".to_string(),
+//!     port: 39100,
+//!     tls_enabled: true,
+//!     tls_verify: true,
+//!     tls_pin: None,
+//!     srv: None,
+//! };
+//! let identity = AgentIdentity::new("inventory-sync", "build-01", "1.0.0");
+//! let mut client = Client::connect(&server, &ConnectOptions::default()).await?;
+//! let auth = client.authenticate(identity.auth_request("token")).await?;
+//! println!("Granted permission level {}", auth.permission_level);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod chunking;
+mod client;
+pub mod protocol;
+pub mod resolve;
+pub mod tls;
+pub mod unix;
+
+#[allow(clippy::large_enum_variant)]
+pub mod proto {
+    include!(concat!(env!("OUT_DIR"), "/nanolink.rs"));
+
+    /// Encoded `FileDescriptorSet` of nanolink.proto
+    pub const FILE_DESCRIPTOR_SET: &[u8] =
+        include_bytes!(concat!(env!("OUT_DIR"), "/nanolink_descriptor.bin"));
+}
+
+pub use client::{AgentIdentity, Client, ConnectOptions};
+
+/// Address and TLS settings of a NanoLink server
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Server {
+    pub host: String,
+    /// gRPC port (39100 by default)
+    pub port: u16,
+    pub tls_enabled: bool,
+    /// Verify the certificate chain against the system and webpki roots
+    pub tls_verify: bool,
+    /// Expected leaf certificate fingerprint (`sha256:<hex>`). Without one,
+    /// any certificate that passes verification is accepted and its
+    /// fingerprint is reported by [`Client::tls_fingerprint`].
+    pub tls_pin: Option<String>,
+    /// SRV record to discover the endpoint from, e.g.
+    /// `_nanolink._tcp.example.com`. `host` and `port` are the fallback.
+    pub srv: Option<String>,
+}
+
+impl Server {
+    /// `host:port`, or the host of a Unix socket
+    pub fn address(&self) -> String {
+        if self.unix_socket().is_some() {
+            return self.host.clone();
+        }
+        format!("{}:{}", self.host, self.port)
+    }
+
+    /// Socket path when `host` is a `unix://` URI
+    pub fn unix_socket(&self) -> Option<&std::path::Path> {
+        unix::socket_path(&self.host)
+    }
+}
+
+impl From<&nanolink_core::ServerConfig> for Server {
+    fn from(config: &nanolink_core::ServerConfig) -> Self {
+        Self {
+            host: config.host.clone(),
+            port: config.port,
+            tls_enabled: config.tls_enabled,
+            tls_verify: config.tls_verify,
+            tls_pin: config.tls_pin.clone(),
+            srv: config.srv.clone(),
+        }
+    }
+}
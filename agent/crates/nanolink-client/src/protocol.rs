@@ -0,0 +1,60 @@
+//! Stream protocol versions
+//!
+//! Both sides report the version of the `StreamMetrics` protocol they speak
+//! during authentication (history in nanolink.proto). Messages a peer's
+//! version doesn't know are dropped instead of breaking its stream.
+
+use crate::proto::metrics_stream_request;
+
+/// Stream protocol version spoken by this client
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Metadata key carrying `AuthResponse.session_token` on streams
+pub const SESSION_METADATA: &str = "x-nanolink-session";
+
+/// Version assumed for servers that don't report one
+const LEGACY_PROTOCOL_VERSION: u32 = 1;
+
+/// Version both sides understand, given the one the server reported
+pub fn negotiate_protocol(server_version: u32) -> u32 {
+    match server_version {
+        0 => LEGACY_PROTOCOL_VERSION,
+        v => v.min(PROTOCOL_VERSION),
+    }
+}
+
+/// Prepare a stream message for a peer speaking `version`. Returns `None` for
+/// message types the peer doesn't know, which are dropped instead of
+/// breaking its stream.
+pub fn downgrade(
+    request: metrics_stream_request::Request,
+    version: u32,
+) -> Option<metrics_stream_request::Request> {
+    use metrics_stream_request::Request;
+
+    let introduced = match &request {
+        Request::Telemetry(_) | Request::LogBatch(_) => 2,
+        _ => 1,
+    };
+    (version >= introduced).then_some(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::{AgentTelemetry, Heartbeat, LogBatch};
+    use metrics_stream_request::Request;
+
+    #[test]
+    fn test_protocol_negotiation() {
+        assert_eq!(negotiate_protocol(0), 1);
+        assert_eq!(negotiate_protocol(1), 1);
+        assert_eq!(negotiate_protocol(PROTOCOL_VERSION + 5), PROTOCOL_VERSION);
+
+        // Legacy servers don't get message types added later
+        assert!(downgrade(Request::Heartbeat(Heartbeat::default()), 1).is_some());
+        assert!(downgrade(Request::LogBatch(LogBatch::default()), 1).is_none());
+        assert!(downgrade(Request::Telemetry(AgentTelemetry::default()), 1).is_none());
+        assert!(downgrade(Request::LogBatch(LogBatch::default()), 2).is_some());
+    }
+}
@@ -0,0 +1,206 @@
+//! Endpoint discovery and dialing
+//!
+//! The server address is resolved again on every dial instead of once per
+//! process, so an agent follows a DNS failover to a standby on its next
+//! reconnect. With an SRV record configured, the record is looked up first
+//! and its targets are tried in priority order, falling back to the
+//! configured `host:port` when the lookup fails or returns nothing. Every
+//! address a name resolves to is tried before the dial fails. SRV lookups
+//! need the `srv` feature; without it the configured `host:port` is used.
+
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+#[cfg(feature = "srv")]
+use hickory_resolver::TokioResolver;
+use socket2::{SockRef, TcpKeepalive};
+use tokio::net::TcpStream;
+use tracing::{debug, warn};
+
+use crate::Server;
+
+/// OS-level keepalive for long-lived streams through NAT and firewalls
+const TCP_KEEPALIVE: Duration = Duration::from_secs(20);
+
+/// A host and port to dial
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub host: String,
+    pub port: u16,
+}
+
+/// Targets for a server, most preferred first
+pub async fn targets(server: &Server) -> Vec<Target> {
+    let fallback = Target {
+        host: server.host.clone(),
+        port: server.port,
+    };
+    let Some(name) = &server.srv else {
+        return vec![fallback];
+    };
+    match lookup_srv(name).await {
+        Ok(records) if !records.is_empty() => {
+            let mut targets = order_srv(records);
+            targets.push(fallback);
+            targets
+        }
+        Ok(_) => {
+            warn!(
+                "SRV record {} has no targets, using {}",
+                name,
+                server.address()
+            );
+            vec![fallback]
+        }
+        Err(e) => {
+            warn!(
+                "SRV lookup of {} failed, using {}: {}",
+                name,
+                server.address(),
+                e
+            );
+            vec![fallback]
+        }
+    }
+}
+
+/// SRV records of `name` as (priority, weight, target)
+#[cfg(not(feature = "srv"))]
+async fn lookup_srv(_name: &str) -> Result<Vec<(u16, u16, Target)>, String> {
+    Err("built without SRV support".to_string())
+}
+
+/// SRV records of `name` as (priority, weight, target)
+#[cfg(feature = "srv")]
+async fn lookup_srv(name: &str) -> Result<Vec<(u16, u16, Target)>, String> {
+    // A new resolver per lookup, so nothing is served from a stale cache
+    let resolver = TokioResolver::builder_tokio()
+        .map_err(|e| e.to_string())?
+        .build();
+    let lookup = resolver.srv_lookup(name).await.map_err(|e| e.to_string())?;
+    Ok(lookup
+        .iter()
+        .map(|srv| {
+            let host = srv.target().to_utf8();
+            let target = Target {
+                host: host.trim_end_matches('.').to_string(),
+                port: srv.port(),
+            };
+            (srv.priority(), srv.weight(), target)
+        })
+        // "." means the service is not available at this domain
+        .filter(|(_, _, target)| !target.host.is_empty())
+        .collect())
+}
+
+/// Lowest priority first, then heaviest weight first
+fn order_srv(mut records: Vec<(u16, u16, Target)>) -> Vec<Target> {
+    records.sort_by_key(|(priority, weight, _)| (*priority, std::cmp::Reverse(*weight)));
+    let mut targets: Vec<Target> = Vec::with_capacity(records.len());
+    for (_, _, target) in records {
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+    targets
+}
+
+/// Open a TCP connection to the first reachable address of the server.
+/// Returns the stream and the host name it was reached by, for TLS.
+pub async fn dial(server: &Server) -> io::Result<(TcpStream, String)> {
+    let mut last_error = None;
+    for target in targets(server).await {
+        let addrs = match tokio::net::lookup_host((target.host.as_str(), target.port)).await {
+            Ok(addrs) => addrs.collect::<Vec<SocketAddr>>(),
+            Err(e) => {
+                debug!("Failed to resolve {}:{}: {}", target.host, target.port, e);
+                last_error = Some(e);
+                continue;
+            }
+        };
+        for addr in addrs {
+            debug!("Dialing {}:{} at {}", target.host, target.port, addr);
+            match TcpStream::connect(addr).await {
+                Ok(tcp) => {
+                    configure(&tcp);
+                    return Ok((tcp, target.host));
+                }
+                Err(e) => {
+                    debug!("Failed to connect to {}: {}", addr, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} resolved to no addresses", server.address()),
+        )
+    }))
+}
+
+fn configure(tcp: &TcpStream) {
+    let _ = tcp.set_nodelay(true);
+    let keepalive = TcpKeepalive::new().with_time(TCP_KEEPALIVE);
+    if let Err(e) = SockRef::from(tcp).set_tcp_keepalive(&keepalive) {
+        debug!("Failed to enable TCP keepalive: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(host: &str, port: u16) -> Target {
+        Target {
+            host: host.to_string(),
+            port,
+        }
+    }
+
+    #[test]
+    fn test_srv_order() {
+        let records = vec![
+            (20, 0, target("standby.example.com", 39100)),
+            (10, 10, target("b.example.com", 39101)),
+            (10, 60, target("a.example.com", 39100)),
+            (10, 60, target("a.example.com", 39100)),
+        ];
+        assert_eq!(
+            order_srv(records),
+            vec![
+                target("a.example.com", 39100),
+                target("b.example.com", 39101),
+                target("standby.example.com", 39100),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dial_resolves_each_time() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = Server {
+            host: "localhost".to_string(),
+            port: listener.local_addr().unwrap().port(),
+            ..Default::default()
+        };
+        assert_eq!(
+            targets(&server).await,
+            vec![target("localhost", server.port)]
+        );
+        for _ in 0..2 {
+            let (tcp, host) = dial(&server).await.unwrap();
+            assert_eq!(host, "localhost");
+            assert!(tcp.nodelay().unwrap());
+        }
+
+        drop(listener);
+        let refused = Server {
+            host: "127.0.0.1".to_string(),
+            ..server
+        };
+        assert!(dial(&refused).await.is_err());
+    }
+}
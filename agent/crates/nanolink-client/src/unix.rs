@@ -0,0 +1,144 @@
+//! Unix domain socket transport for servers on the same host
+//!
+//! A server host of `unix:///run/nanolink.sock` is reached over that socket
+//! instead of TCP loopback, without TLS: the traffic never leaves the host.
+//! Before connecting, the socket must be owned by root or by the connecting
+//! user, so another local user can't stand in for the server.
+//!
+//! [`bind`] creates a server socket: a stale socket left by a previous run
+//! is replaced, anything else at the path is refused, and the file is made
+//! accessible to the given mode only.
+
+use std::io;
+use std::path::Path;
+
+/// Host prefix selecting the Unix socket transport
+pub const SCHEME: &str = "unix://";
+
+/// Socket path of a `unix://` host
+pub fn socket_path(host: &str) -> Option<&Path> {
+    host.strip_prefix(SCHEME).map(Path::new)
+}
+
+/// Connect to a server socket after checking its owner
+#[cfg(unix)]
+pub async fn dial(path: &Path) -> io::Result<tokio::net::UnixStream> {
+    check_owner(path)?;
+    tokio::net::UnixStream::connect(path).await
+}
+
+#[cfg(not(unix))]
+pub async fn dial(path: &Path) -> io::Result<tokio::net::TcpStream> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "Unix sockets are not supported on this platform: {}",
+            path.display()
+        ),
+    ))
+}
+
+#[cfg(unix)]
+fn check_owner(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    let metadata = std::fs::metadata(path)?;
+    if !metadata.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a socket", path.display()),
+        ));
+    }
+    // SAFETY: geteuid has no preconditions and cannot fail
+    let euid = unsafe { libc::geteuid() };
+    if metadata.uid() != 0 && metadata.uid() != euid {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "{} is owned by uid {}, not root or this user",
+                path.display(),
+                metadata.uid()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Create a server socket at `path` with permissions `mode` (e.g. `0o660`)
+#[cfg(unix)]
+pub fn bind(path: &Path, mode: u32) -> io::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("{} is in use by another server", path.display()),
+                ));
+            }
+            // Left behind by a server that didn't shut down cleanly
+            std::fs::remove_file(path)?;
+        }
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ));
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let listener = tokio::net::UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    Ok(listener)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_socket_path() {
+        assert_eq!(
+            socket_path("unix:///run/nanolink.sock"),
+            Some(Path::new("/run/nanolink.sock"))
+        );
+        assert_eq!(socket_path("monitor.example.com"), None);
+    }
+
+    #[tokio::test]
+    async fn test_bind_and_dial() {
+        let dir = std::env::temp_dir().join(format!("nanolink-unix-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("server.sock");
+
+        let listener = bind(&path, 0o660).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o660);
+        assert!(dial(&path).await.is_ok());
+
+        // A live socket is not taken over, a stale one is
+        assert_eq!(
+            bind(&path, 0o660).unwrap_err().kind(),
+            io::ErrorKind::AddrInUse
+        );
+        drop(listener);
+        let _listener = bind(&path, 0o600).unwrap();
+
+        // Regular files are neither replaced nor dialed
+        let file = dir.join("not-a-socket");
+        std::fs::write(&file, b"").unwrap();
+        assert_eq!(
+            bind(&file, 0o660).unwrap_err().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+        assert_eq!(
+            dial(&file).await.unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,15 @@
+//! Shared NanoLink models
+//!
+//! Types used by the agent and by tools that manage agents, kept in one
+//! place so they don't drift: the server connection settings from the
+//! agent config, permission levels, and the host snapshot served by the
+//! management API. Only depends on serde.
+
+mod permission;
+mod server;
+pub mod snapshot;
+
+pub use permission::PermissionLevel;
+pub use server::{
+    BandwidthCap, DEFAULT_GRPC_PORT, ReportingSchedule, ReportingWindow, ServerConfig, ServerRole,
+};
@@ -0,0 +1,72 @@
+//! Permission levels
+//!
+//! Each server connection has a level from 0 to 3, granted by the server
+//! during authentication and capped by the agent's config. Every command
+//! requires a minimum level.
+
+/// Permission levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PermissionLevel {
+    /// Read-only access - can only view metrics
+    ReadOnly = 0,
+    /// Basic write - can download logs, clear temp files
+    BasicWrite = 1,
+    /// Service control - can restart services, containers, kill processes
+    ServiceControl = 2,
+    /// System admin - full access including reboot and shell commands
+    SystemAdmin = 3,
+}
+
+impl PermissionLevel {
+    /// The level for `level`, `None` above 3
+    pub fn new(level: u8) -> Option<Self> {
+        match level {
+            0 => Some(PermissionLevel::ReadOnly),
+            1 => Some(PermissionLevel::BasicWrite),
+            2 => Some(PermissionLevel::ServiceControl),
+            3 => Some(PermissionLevel::SystemAdmin),
+            _ => None,
+        }
+    }
+
+    /// Name as used in the protocol and the CLI, e.g. `SERVICE_CONTROL`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PermissionLevel::ReadOnly => "READ_ONLY",
+            PermissionLevel::BasicWrite => "BASIC_WRITE",
+            PermissionLevel::ServiceControl => "SERVICE_CONTROL",
+            PermissionLevel::SystemAdmin => "SYSTEM_ADMIN",
+        }
+    }
+}
+
+/// Unknown levels fall back to read-only
+impl From<u8> for PermissionLevel {
+    fn from(level: u8) -> Self {
+        Self::new(level).unwrap_or(PermissionLevel::ReadOnly)
+    }
+}
+
+impl From<i32> for PermissionLevel {
+    fn from(level: i32) -> Self {
+        Self::from(level.clamp(0, 3) as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permission_levels() {
+        assert_eq!(
+            PermissionLevel::new(2),
+            Some(PermissionLevel::ServiceControl)
+        );
+        assert_eq!(PermissionLevel::new(4), None);
+        assert_eq!(PermissionLevel::from(9u8), PermissionLevel::ReadOnly);
+        assert_eq!(PermissionLevel::from(9i32), PermissionLevel::SystemAdmin);
+        assert!(PermissionLevel::BasicWrite < PermissionLevel::SystemAdmin);
+        assert_eq!(PermissionLevel::SystemAdmin.as_str(), "SYSTEM_ADMIN");
+    }
+}
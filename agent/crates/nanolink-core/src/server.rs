@@ -0,0 +1,354 @@
+//! Server connection settings
+//!
+//! The `servers` entries of the agent config, also used by tools that
+//! manage agents to describe where an agent reports to.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::PermissionLevel;
+
+/// Default gRPC port for NanoLink
+pub const DEFAULT_GRPC_PORT: u16 = 39100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerConfig {
+    /// Server hostname or IP address, or a Unix socket for a server on the same host
+    /// Examples: "localhost", "192.168.1.100", "monitor.example.com", "unix:///run/nanolink.sock"
+    pub host: String,
+
+    /// gRPC port (default: 39100)
+    #[serde(default = "default_grpc_port")]
+    pub port: u16,
+
+    /// Authentication token. Supports multiple formats:
+    /// 1. Direct value: "my_token"
+    /// 2. Environment variable reference: "${ENV_VAR_NAME}"
+    /// 3. File reference: "file:///path/to/token"
+    pub token: String,
+
+    /// Management API token for this server to call Agent remotely
+    /// Only valid when permission >= 1, bound to server's IP address
+    #[serde(default)]
+    pub management_token: Option<String>,
+
+    /// Permission level for this connection
+    /// 0 = READ_ONLY, 1 = BASIC_WRITE, 2 = SERVICE_CONTROL, 3 = SYSTEM_ADMIN
+    #[serde(default)]
+    pub permission: u8,
+
+    /// Enable TLS (grpcs://)
+    #[serde(default)]
+    pub tls_enabled: bool,
+
+    /// Enable TLS certificate verification
+    #[serde(default = "default_true")]
+    pub tls_verify: bool,
+
+    /// Pinned server certificate fingerprint ("sha256:<hex>")
+    /// Recorded automatically on the first successful TLS connection (trust-on-first-use);
+    /// connections presenting a different certificate are refused until re-pinned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_pin: Option<String>,
+
+    /// SRV record to discover the server from, e.g. "_nanolink._tcp.example.com"
+    /// Looked up on every reconnect; targets are tried in priority order and
+    /// host:port is used when the lookup fails
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub srv: Option<String>,
+
+    /// Role when reporting to several servers: "primary", "standby" or "mirror"
+    /// Metrics go to the primary, or to the standby while the primary is down;
+    /// a mirror only receives alerts and events. Servers without a role each
+    /// get the full stream.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<ServerRole>,
+
+    /// Daily and monthly traffic caps for this server, e.g. on a metered link
+    /// Reporting drops to periodic data when a cap is nearly used up, and to
+    /// alerts, events and command results once it is reached
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bandwidth_cap: Option<BandwidthCap>,
+
+    /// When this server gets every realtime sample and when only one now
+    /// and then, e.g. full reporting during office hours only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reporting_schedule: Option<ReportingSchedule>,
+}
+
+/// Traffic caps of a server connection (`servers[].bandwidth_cap`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BandwidthCap {
+    /// Megabytes per day, counted from local midnight
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_mb: Option<u64>,
+
+    /// Megabytes per month, counted from `reset_day`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_mb: Option<u64>,
+
+    /// Day of the month the monthly count starts over (1-28)
+    #[serde(default = "default_reset_day")]
+    pub reset_day: u8,
+
+    /// Share of a cap, in percent, from which only periodic data is sent
+    #[serde(default = "default_reduce_at_percent")]
+    pub reduce_at_percent: u8,
+}
+
+/// Role of a server among several (`servers[].role`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerRole {
+    /// Receives the full stream whenever it is reachable
+    Primary,
+    /// Receives the full stream while the primary is down
+    Standby,
+    /// Receives alerts and events only
+    Mirror,
+}
+
+impl ServerRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Primary => "primary",
+            Self::Standby => "standby",
+            Self::Mirror => "mirror",
+        }
+    }
+}
+
+/// Realtime reporting pace by time of day (`servers[].reporting_schedule`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportingSchedule {
+    /// Windows with their own pace; the first open one applies
+    #[serde(default)]
+    pub windows: Vec<ReportingWindow>,
+
+    /// Seconds between realtime samples outside every window, 0 = all
+    #[serde(default = "default_reporting_interval")]
+    pub interval_secs: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportingWindow {
+    /// Cron expression (local time) for when the window opens, e.g.
+    /// "0 8 * * mon-fri"
+    pub schedule: String,
+
+    /// How long the window stays open
+    #[serde(default = "default_reporting_window_duration")]
+    pub duration_minutes: u32,
+
+    /// Seconds between realtime samples while open, 0 = all
+    #[serde(default)]
+    pub interval_secs: u64,
+}
+
+impl ServerConfig {
+    /// Server address as `host:port`, or the host of a Unix socket
+    pub fn address(&self) -> String {
+        if self.unix_socket().is_some() {
+            return self.host.clone();
+        }
+        format!("{}:{}", self.host, self.port)
+    }
+
+    /// Socket path when `host` is a `unix://` URI
+    pub fn unix_socket(&self) -> Option<&Path> {
+        self.host.strip_prefix("unix://").map(Path::new)
+    }
+
+    /// Get the gRPC connection URL
+    pub fn get_grpc_url(&self) -> String {
+        if self.unix_socket().is_some() {
+            self.host.clone()
+        } else if self.tls_enabled {
+            format!("https://{}:{}", self.host, self.port)
+        } else {
+            format!("http://{}:{}", self.host, self.port)
+        }
+    }
+
+    /// Resolve token value, supporting environment variables and file references
+    /// Returns the actual token value, or an error if resolution fails
+    pub fn resolve_token(&self) -> Result<String, String> {
+        let token = &self.token;
+
+        // Environment variable format: ${VAR_NAME}
+        if token.starts_with("${") && token.ends_with("}") {
+            let var_name = &token[2..token.len() - 1];
+            return std::env::var(var_name).map_err(|_| {
+                format!(
+                    "Environment variable '{var_name}' not found. \
+                    Make sure it is set before starting the agent."
+                )
+            });
+        }
+
+        // File reference format: file:///path/to/token
+        if let Some(path) = token.strip_prefix("file://") {
+            return std::fs::read_to_string(path)
+                .map(|s| s.trim().to_string())
+                .map_err(|e| format!("Failed to read token file '{path}': {e}"));
+        }
+
+        // Direct value
+        Ok(token.clone())
+    }
+
+    /// Check the fields a connection needs
+    pub fn validate(&self) -> Result<(), String> {
+        if self.host.is_empty() {
+            return Err("host cannot be empty".to_string());
+        }
+        if self.token.is_empty() {
+            return Err("token cannot be empty".to_string());
+        }
+        if let Some(path) = self.unix_socket() {
+            if !path.is_absolute() {
+                return Err("unix socket path must be absolute".to_string());
+            }
+            if self.tls_enabled {
+                return Err("tls_enabled is not supported for unix sockets".to_string());
+            }
+        }
+        if self.srv.as_deref().is_some_and(|srv| srv.trim().is_empty()) {
+            return Err("srv cannot be empty".to_string());
+        }
+        if PermissionLevel::new(self.permission).is_none() {
+            return Err("permission must be 0-3".to_string());
+        }
+        if let Some(schedule) = &self.reporting_schedule
+            && schedule
+                .windows
+                .iter()
+                .any(|window| window.duration_minutes == 0)
+        {
+            return Err("reporting_schedule windows need a duration".to_string());
+        }
+        if let Some(cap) = &self.bandwidth_cap {
+            if cap.daily_mb.is_none() && cap.monthly_mb.is_none() {
+                return Err("bandwidth_cap needs daily_mb or monthly_mb".to_string());
+            }
+            if cap.daily_mb == Some(0) || cap.monthly_mb == Some(0) {
+                return Err("bandwidth_cap cannot be 0".to_string());
+            }
+            if !(1..=28).contains(&cap.reset_day) {
+                return Err("bandwidth_cap.reset_day must be 1-28".to_string());
+            }
+            if !(1..=100).contains(&cap.reduce_at_percent) {
+                return Err("bandwidth_cap.reduce_at_percent must be 1-100".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn default_grpc_port() -> u16 {
+    DEFAULT_GRPC_PORT
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_reset_day() -> u8 {
+    1
+}
+
+fn default_reduce_at_percent() -> u8 {
+    80
+}
+
+fn default_reporting_interval() -> u64 {
+    60
+}
+
+fn default_reporting_window_duration() -> u32 {
+    60
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_config_defaults_and_validation() {
+        let server: ServerConfig =
+            serde_json::from_str(r#"{"host": "monitor.example.com", "token": "t"}"#).unwrap();
+        assert_eq!(server.port, DEFAULT_GRPC_PORT);
+        assert!(server.tls_verify && !server.tls_enabled);
+        assert_eq!(server.address(), "monitor.example.com:39100");
+        assert!(server.validate().is_ok());
+        assert_eq!(server.role, None);
+
+        let standby: ServerConfig =
+            serde_json::from_str(r#"{"host": "h", "token": "t", "role": "standby"}"#).unwrap();
+        assert_eq!(standby.role, Some(ServerRole::Standby));
+
+        let metered: ServerConfig = serde_json::from_str(
+            r#"{"host": "h", "token": "t", "bandwidth_cap": {"monthly_mb": 500}}"#,
+        )
+        .unwrap();
+        let cap = metered.bandwidth_cap.as_ref().unwrap();
+        assert_eq!((cap.daily_mb, cap.monthly_mb), (None, Some(500)));
+        assert_eq!((cap.reset_day, cap.reduce_at_percent), (1, 80));
+        assert!(metered.validate().is_ok());
+
+        let scheduled: ServerConfig = serde_json::from_str(
+            r#"{"host": "h", "token": "t", "reporting_schedule": {"windows": [{"schedule": "0 8 * * *"}]}}"#,
+        )
+        .unwrap();
+        let schedule = scheduled.reporting_schedule.as_ref().unwrap();
+        assert_eq!(schedule.interval_secs, 60);
+        assert_eq!(schedule.windows[0].duration_minutes, 60);
+        assert_eq!(schedule.windows[0].interval_secs, 0);
+
+        let invalid = |f: fn(&mut ServerConfig)| {
+            let mut server = server.clone();
+            f(&mut server);
+            server.validate().unwrap_err()
+        };
+        assert_eq!(invalid(|s| s.host.clear()), "host cannot be empty");
+        assert_eq!(invalid(|s| s.token.clear()), "token cannot be empty");
+        assert_eq!(
+            invalid(|s| s.srv = Some(String::new())),
+            "srv cannot be empty"
+        );
+        assert_eq!(invalid(|s| s.permission = 4), "permission must be 0-3");
+        fn daily_cap(daily_mb: Option<u64>, reset_day: u8) -> Option<BandwidthCap> {
+            Some(BandwidthCap {
+                daily_mb,
+                monthly_mb: None,
+                reset_day,
+                reduce_at_percent: 80,
+            })
+        }
+        assert_eq!(
+            invalid(|s| s.bandwidth_cap = daily_cap(None, 1)),
+            "bandwidth_cap needs daily_mb or monthly_mb"
+        );
+        assert_eq!(
+            invalid(|s| s.bandwidth_cap = daily_cap(Some(50), 31)),
+            "bandwidth_cap.reset_day must be 1-28"
+        );
+
+        let mut local = server.clone();
+        local.host = "unix:///run/nanolink.sock".to_string();
+        assert_eq!(local.address(), "unix:///run/nanolink.sock");
+        assert_eq!(local.unix_socket(), Some(Path::new("/run/nanolink.sock")));
+        assert!(local.validate().is_ok());
+        local.tls_enabled = true;
+        assert_eq!(
+            local.validate().unwrap_err(),
+            "tls_enabled is not supported for unix sockets"
+        );
+        local.host = "unix://run/nanolink.sock".to_string();
+        assert_eq!(
+            local.validate().unwrap_err(),
+            "unix socket path must be absolute"
+        );
+    }
+}
@@ -0,0 +1,70 @@
+//! Host snapshot shown by the metrics viewer
+//!
+//! One collection of the headline metrics, as served by the agent's
+//! management API at `GET /api/snapshot`: usage per core, memory, disks,
+//! network rates, GPUs, the busiest processes and listening ports.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub hostname: String,
+    pub cpu_usage: f64,
+    /// Usage of each logical core in percent
+    pub cores: Vec<f32>,
+    /// 1, 5 and 15 minute load averages (Unix)
+    pub load_average: Option<[f64; 3]>,
+    pub memory_total: u64,
+    pub memory_used: u64,
+    pub swap_total: u64,
+    pub swap_used: u64,
+    pub disks: Vec<DiskRow>,
+    pub networks: Vec<NetworkRow>,
+    pub gpus: Vec<GpuRow>,
+    pub processes: Vec<ProcessRow>,
+    pub ports: Vec<PortRow>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiskRow {
+    pub mount_point: String,
+    pub file_system: String,
+    pub used: u64,
+    pub total: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NetworkRow {
+    pub name: String,
+    /// Bytes since the previous snapshot
+    pub received: u64,
+    pub transmitted: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GpuRow {
+    pub index: u32,
+    pub name: String,
+    pub usage_percent: f64,
+    pub memory_total: u64,
+    pub memory_used: u64,
+    pub temperature: f64,
+    pub power_watts: u32,
+    pub power_limit_watts: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProcessRow {
+    pub pid: u32,
+    pub cpu_usage: f32,
+    pub memory: u64,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PortRow {
+    pub pid: String,
+    pub protocol: String,
+    pub address: String,
+    pub process: String,
+}
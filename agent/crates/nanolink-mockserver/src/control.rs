@@ -0,0 +1,220 @@
+//! HTTP control plane: inspect connected agents and inject commands
+//!
+//! - `GET  /api/health`
+//! - `GET  /api/agents`: agents that opened a stream, with message counts
+//! - `GET  /api/agents/{id}`: one agent and the last message of each kind
+//! - `POST /api/agents/{id}/commands`: send a command; with `wait_seconds`
+//!   the response is the agent's `CommandResult`
+//! - `POST /api/agents/{id}/data-requests`: send a `DataRequest`
+//! - `POST /api/agents/{id}/config-baseline`: send a `ConfigBaseline`
+//! - `GET  /api/commands/{id}`: result of an earlier command
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use nanolink_client::proto::{
+    Command, CommandType, ConfigBaseline, DataRequest, DataRequestType, MetricsStreamResponse,
+    metrics_stream_response,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::state::{MockState, SendError};
+
+/// Longest a command request may wait for its result
+const MAX_WAIT: Duration = Duration::from_secs(300);
+
+pub fn router(state: Arc<MockState>) -> Router {
+    Router::new()
+        .route("/api/health", get(health))
+        .route("/api/agents", get(list_agents))
+        .route("/api/agents/{id}", get(get_agent))
+        .route("/api/agents/{id}/commands", post(send_command))
+        .route("/api/agents/{id}/data-requests", post(send_data_request))
+        .route(
+            "/api/agents/{id}/config-baseline",
+            post(send_config_baseline),
+        )
+        .route("/api/commands/{id}", get(get_result))
+        .with_state(state)
+}
+
+fn error(status: StatusCode, message: impl Into<String>) -> Response {
+    (
+        status,
+        Json(json!({ "success": false, "message": message.into() })),
+    )
+        .into_response()
+}
+
+fn send_error(e: SendError) -> Response {
+    let status = match e {
+        SendError::UnknownAgent => StatusCode::NOT_FOUND,
+        SendError::Disconnected => StatusCode::CONFLICT,
+    };
+    error(status, e.to_string())
+}
+
+async fn health(State(state): State<Arc<MockState>>) -> Json<serde_json::Value> {
+    let agents = state.agents();
+    Json(json!({
+        "status": "ok",
+        "agents": agents.len(),
+        "connected": agents.iter().filter(|a| a.connected).count(),
+        "reports": state.reports.load(Ordering::Relaxed),
+    }))
+}
+
+async fn list_agents(State(state): State<Arc<MockState>>) -> Response {
+    Json(state.agents()).into_response()
+}
+
+async fn get_agent(State(state): State<Arc<MockState>>, Path(id): Path<String>) -> Response {
+    match state.agent(&id) {
+        Some(agent) => Json(json!({ "agent": agent, "last": agent.last })).into_response(),
+        None => error(StatusCode::NOT_FOUND, "Unknown agent"),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CommandRequest {
+    /// `CommandType` name, e.g. `PROCESS_LIST`
+    #[serde(rename = "type")]
+    command_type: String,
+    #[serde(default)]
+    command_id: String,
+    #[serde(default)]
+    target: String,
+    #[serde(default)]
+    params: HashMap<String, String>,
+    #[serde(default)]
+    super_token: String,
+    /// Wait up to this long for the result instead of returning the ID
+    #[serde(default)]
+    wait_seconds: u64,
+}
+
+async fn send_command(
+    State(state): State<Arc<MockState>>,
+    Path(id): Path<String>,
+    Json(req): Json<CommandRequest>,
+) -> Response {
+    let Some(command_type) = CommandType::from_str_name(&req.command_type) else {
+        return error(
+            StatusCode::BAD_REQUEST,
+            format!("Unknown command type: {}", req.command_type),
+        );
+    };
+    let command = Command {
+        command_id: req.command_id,
+        r#type: command_type as i32,
+        target: req.target,
+        params: req.params,
+        super_token: req.super_token,
+    };
+
+    let events = state.subscribe_results();
+    let command_id = match state.send_command(&id, command).await {
+        Ok(command_id) => command_id,
+        Err(e) => return send_error(e),
+    };
+    if req.wait_seconds == 0 {
+        return (
+            StatusCode::ACCEPTED,
+            Json(json!({ "command_id": command_id })),
+        )
+            .into_response();
+    }
+
+    let timeout = Duration::from_secs(req.wait_seconds).min(MAX_WAIT);
+    match state.wait_result(events, &command_id, timeout).await {
+        Some(result) => Json(result).into_response(),
+        None => (
+            StatusCode::GATEWAY_TIMEOUT,
+            Json(json!({
+                "success": false,
+                "message": "No result before the timeout",
+                "command_id": command_id,
+            })),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct DataRequestBody {
+    /// `DataRequestType` name, e.g. `DATA_REQUEST_STATIC`
+    #[serde(rename = "type")]
+    request_type: String,
+    #[serde(default)]
+    target: String,
+}
+
+async fn send_data_request(
+    State(state): State<Arc<MockState>>,
+    Path(id): Path<String>,
+    Json(req): Json<DataRequestBody>,
+) -> Response {
+    let Some(request_type) = DataRequestType::from_str_name(&req.request_type) else {
+        return error(
+            StatusCode::BAD_REQUEST,
+            format!("Unknown data request type: {}", req.request_type),
+        );
+    };
+    let response = MetricsStreamResponse {
+        response: Some(metrics_stream_response::Response::DataRequest(
+            DataRequest {
+                request_type: request_type as i32,
+                target: req.target,
+            },
+        )),
+    };
+    match state.send(&id, response).await {
+        Ok(()) => (StatusCode::ACCEPTED, Json(json!({ "success": true }))).into_response(),
+        Err(e) => send_error(e),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ConfigBaselineBody {
+    #[serde(default)]
+    baseline_id: String,
+    /// Section name -> `sha256:<hex>` digest
+    sections: HashMap<String, String>,
+    #[serde(default)]
+    check_interval_seconds: u64,
+}
+
+async fn send_config_baseline(
+    State(state): State<Arc<MockState>>,
+    Path(id): Path<String>,
+    Json(req): Json<ConfigBaselineBody>,
+) -> Response {
+    let response = MetricsStreamResponse {
+        response: Some(metrics_stream_response::Response::ConfigBaseline(
+            ConfigBaseline {
+                baseline_id: req.baseline_id,
+                sections: req.sections,
+                check_interval_seconds: req.check_interval_seconds,
+            },
+        )),
+    };
+    match state.send(&id, response).await {
+        Ok(()) => (StatusCode::ACCEPTED, Json(json!({ "success": true }))).into_response(),
+        Err(e) => send_error(e),
+    }
+}
+
+async fn get_result(State(state): State<Arc<MockState>>, Path(id): Path<String>) -> Response {
+    match state.result(&id) {
+        Some(result) => Json(result).into_response(),
+        None => error(StatusCode::NOT_FOUND, "No result for this command yet"),
+    }
+}
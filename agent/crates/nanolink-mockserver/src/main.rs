@@ -0,0 +1,493 @@
+//! Mock NanoLink server for agent integration tests
+//!
+//! Serves the agent side of `NanoLinkService` over plaintext gRPC:
+//! authentication, a metrics sink that counts and keeps the last message of
+//! each kind, and heartbeat acknowledgements. Commands and data requests are
+//! injected through an HTTP control plane (see [`control`]), so tests and CI
+//! can run the connect/auth/stream/command loop without a production server.
+//!
+//! ```text
+//! nanolink-mockserver --token test-token &
+//! nanolink-agent -c agent.yaml &     # servers: [{host: 127.0.0.1, port: 39100, token: test-token, tls_enabled: false}]
+//! curl -s localhost:39101/api/agents
+//! curl -s -XPOST localhost:39101/api/agents/<agent_id>/commands \
+//!      -d '{"type": "PROCESS_LIST", "wait_seconds": 10}' -H 'content-type: application/json'
+//! ```
+
+mod control;
+mod service;
+mod state;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use nanolink_client::chunking::DEFAULT_MAX_MESSAGE_SIZE;
+use nanolink_client::proto::nano_link_service_server::NanoLinkServiceServer;
+use nanolink_client::protocol::PROTOCOL_VERSION;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tracing::info;
+
+use crate::service::MockService;
+use crate::state::{MockState, Settings};
+
+#[derive(Parser, Debug)]
+#[command(name = "nanolink-mockserver", version, about)]
+struct Args {
+    /// Address agents connect to
+    #[arg(long, default_value = "127.0.0.1:39100")]
+    grpc: SocketAddr,
+
+    /// Also serve gRPC on this Unix socket, for agents with a
+    /// `unix://` server host
+    #[arg(long)]
+    unix: Option<std::path::PathBuf>,
+
+    /// Address of the HTTP control plane
+    #[arg(long, default_value = "127.0.0.1:39101")]
+    http: SocketAddr,
+
+    /// Token agents must authenticate with; any token is accepted if unset
+    #[arg(long)]
+    token: Option<String>,
+
+    /// Permission level granted to agents (0-3)
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(i32).range(0..=3))]
+    permission: i32,
+
+    /// Stream protocol version to announce; 0 behaves like a server that
+    /// predates negotiation
+    #[arg(long, default_value_t = PROTOCOL_VERSION)]
+    protocol_version: u32,
+
+    /// Largest message accepted, in bytes
+    #[arg(long, default_value_t = DEFAULT_MAX_MESSAGE_SIZE as u32)]
+    max_message_size: u32,
+
+    /// Answer the dedicated command stream with UNIMPLEMENTED, like a
+    /// server that only has the metrics stream
+    #[arg(long)]
+    no_command_stream: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_target(false)
+        .compact()
+        .init();
+    let args = Args::parse();
+
+    let state = Arc::new(MockState::new(Settings {
+        token: args.token,
+        permission_level: args.permission,
+        protocol_version: args.protocol_version,
+        max_message_size: args.max_message_size,
+        command_stream: !args.no_command_stream,
+    }));
+
+    let grpc = TcpListener::bind(args.grpc)
+        .await
+        .with_context(|| format!("Failed to bind {}", args.grpc))?;
+    let http = TcpListener::bind(args.http)
+        .await
+        .with_context(|| format!("Failed to bind {}", args.http))?;
+    info!("gRPC listening on {}", grpc.local_addr()?);
+    info!("Control plane listening on http://{}", http.local_addr()?);
+    let unix = serve_unix(state.clone(), args.unix.as_deref())?;
+
+    tokio::select! {
+        result = serve_grpc(state.clone(), grpc) => result,
+        result = unix => result,
+        result = axum::serve(http, control::router(state)) => {
+            result.context("Control plane failed")
+        }
+        _ = tokio::signal::ctrl_c() => {
+            info!("Shutting down");
+            Ok(())
+        }
+    }
+}
+
+fn grpc_service(state: Arc<MockState>) -> NanoLinkServiceServer<MockService> {
+    let max_message_size = state.settings.max_message_size as usize;
+    NanoLinkServiceServer::new(MockService::new(state))
+        .max_decoding_message_size(max_message_size)
+        .max_encoding_message_size(max_message_size)
+}
+
+async fn serve_grpc(state: Arc<MockState>, listener: TcpListener) -> Result<()> {
+    tonic::transport::Server::builder()
+        .add_service(grpc_service(state))
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await
+        .context("gRPC server failed")
+}
+
+/// Serve gRPC on a Unix socket, pending forever without one. Agents on a
+/// socket have no peer address, so they are known by their AgentInit.
+#[cfg(unix)]
+fn serve_unix(
+    state: Arc<MockState>,
+    path: Option<&std::path::Path>,
+) -> Result<impl std::future::Future<Output = Result<()>> + use<>> {
+    let listener = path
+        .map(|path| {
+            let listener = nanolink_client::unix::bind(path, 0o660)
+                .with_context(|| format!("Failed to bind {}", path.display()))?;
+            info!("gRPC listening on unix://{}", path.display());
+            anyhow::Ok(listener)
+        })
+        .transpose()?;
+    Ok(async move {
+        let Some(listener) = listener else {
+            return std::future::pending().await;
+        };
+        tonic::transport::Server::builder()
+            .add_service(grpc_service(state))
+            .serve_with_incoming(tokio_stream::wrappers::UnixListenerStream::new(listener))
+            .await
+            .context("gRPC server failed")
+    })
+}
+
+#[cfg(not(unix))]
+fn serve_unix(
+    _state: Arc<MockState>,
+    path: Option<&std::path::Path>,
+) -> Result<impl std::future::Future<Output = Result<()>> + use<>> {
+    if path.is_some() {
+        anyhow::bail!("Unix sockets are not supported on this platform");
+    }
+    Ok(std::future::pending())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use nanolink_client::proto::{
+        Command, CommandResult, Heartbeat, MetricsStreamRequest, metrics_stream_request,
+        metrics_stream_response,
+    };
+    use nanolink_client::{AgentIdentity, Client, ConnectOptions, Server};
+    use tokio::sync::mpsc;
+    use tokio_stream::wrappers::ReceiverStream;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    async fn start(token: Option<&str>) -> (Arc<MockState>, Server) {
+        start_with(token, true).await
+    }
+
+    async fn start_with(token: Option<&str>, command_stream: bool) -> (Arc<MockState>, Server) {
+        let state = Arc::new(MockState::new(Settings {
+            token: token.map(String::from),
+            permission_level: 2,
+            protocol_version: PROTOCOL_VERSION,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE as u32,
+            command_stream,
+        }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = Server {
+            host: "127.0.0.1".to_string(),
+            port: listener.local_addr().unwrap().port(),
+            ..Default::default()
+        };
+        tokio::spawn(serve_grpc(state.clone(), listener));
+        (state, server)
+    }
+
+    fn stream_message(request: metrics_stream_request::Request) -> MetricsStreamRequest {
+        MetricsStreamRequest {
+            request: Some(request),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejects_wrong_token() {
+        let (_state, server) = start(Some("secret")).await;
+        let mut client = Client::connect(&server, &ConnectOptions::default())
+            .await
+            .unwrap();
+        let identity = AgentIdentity::new("agent-1", "web-01", "0.4.1");
+        let response = client
+            .authenticate(identity.auth_request("wrong"))
+            .await
+            .unwrap();
+        assert!(!response.success);
+
+        // Streams need a successful authentication first
+        let (tx, rx) = mpsc::channel(4);
+        tx.send(stream_message(metrics_stream_request::Request::AgentInit(
+            identity.agent_init(),
+        )))
+        .await
+        .unwrap();
+        let mut responses = client
+            .stream_metrics(ReceiverStream::new(rx))
+            .await
+            .unwrap();
+        let status = responses.message().await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+    }
+
+    #[tokio::test]
+    async fn test_stream_and_command_loop() {
+        let (state, server) = start(Some("secret")).await;
+        let mut client = Client::connect(&server, &ConnectOptions::default())
+            .await
+            .unwrap();
+        let identity = AgentIdentity::new("agent-1", "web-01", "0.4.1");
+        let auth = client
+            .authenticate(identity.auth_request("secret"))
+            .await
+            .unwrap();
+        assert!(auth.success);
+        assert_eq!(auth.permission_level, 2);
+
+        let (tx, rx) = mpsc::channel(16);
+        tx.send(stream_message(metrics_stream_request::Request::AgentInit(
+            identity.agent_init(),
+        )))
+        .await
+        .unwrap();
+        tx.send(stream_message(metrics_stream_request::Request::Heartbeat(
+            Heartbeat::default(),
+        )))
+        .await
+        .unwrap();
+        let mut responses = client
+            .stream_metrics(ReceiverStream::new(rx))
+            .await
+            .unwrap();
+
+        let ack = responses.message().await.unwrap().unwrap();
+        assert!(matches!(
+            ack.response,
+            Some(metrics_stream_response::Response::HeartbeatAck(_))
+        ));
+
+        // Act as the agent: answer the command that arrives next
+        tokio::spawn(async move {
+            while let Ok(Some(response)) = responses.message().await {
+                if let Some(metrics_stream_response::Response::Command(command)) = response.response
+                {
+                    let result = CommandResult {
+                        command_id: command.command_id,
+                        success: true,
+                        output: format!("ran {}", command.target),
+                        ..Default::default()
+                    };
+                    let message =
+                        stream_message(metrics_stream_request::Request::CommandResult(result));
+                    if tx.send(message).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        let request = Request::post("/api/agents/agent-1/commands")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"type": "SERVICE_STATUS", "target": "nginx", "wait_seconds": 5}"#,
+            ))
+            .unwrap();
+        let response = control::router(state.clone())
+            .oneshot(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let result: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(result["success"], true);
+        assert_eq!(result["output"], "ran nginx");
+
+        let agent = state.agent("agent-1").unwrap();
+        assert!(agent.connected);
+        assert_eq!(agent.init.hostname, "web-01");
+        assert_eq!(agent.counts.heartbeats, 1);
+        assert_eq!(agent.counts.command_results, 1);
+        let command_id = result["command_id"].as_str().unwrap();
+        assert!(state.result(command_id).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_command_stream() {
+        let (state, server) = start(None).await;
+        let mut client = Client::connect(&server, &ConnectOptions::default())
+            .await
+            .unwrap();
+        let identity = AgentIdentity::new("agent-3", "app-01", "0.4.1");
+        client
+            .authenticate(identity.auth_request(""))
+            .await
+            .unwrap();
+
+        let (metrics_tx, metrics_rx) = mpsc::channel(4);
+        metrics_tx
+            .send(stream_message(metrics_stream_request::Request::AgentInit(
+                identity.agent_init(),
+            )))
+            .await
+            .unwrap();
+        let _metrics = client
+            .stream_metrics(ReceiverStream::new(metrics_rx))
+            .await
+            .unwrap();
+        let (tx, rx) = mpsc::channel(4);
+        tx.send(stream_message(metrics_stream_request::Request::AgentInit(
+            identity.agent_init(),
+        )))
+        .await
+        .unwrap();
+        let mut commands = client
+            .stream_commands(ReceiverStream::new(rx))
+            .await
+            .unwrap()
+            .expect("command stream is served");
+
+        // The command goes to the command stream once it is registered
+        let command_id = loop {
+            if state
+                .agent("agent-3")
+                .is_some_and(|a| a.command_stream && a.connected)
+            {
+                break state
+                    .send_command("agent-3", Command::default())
+                    .await
+                    .unwrap();
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        };
+        let response = commands.message().await.unwrap().unwrap();
+        let Some(metrics_stream_response::Response::Command(command)) = response.response else {
+            panic!("expected a command");
+        };
+        assert_eq!(command.command_id, command_id);
+
+        let events = state.subscribe_results();
+        let result = CommandResult {
+            command_id: command_id.clone(),
+            success: true,
+            ..Default::default()
+        };
+        tx.send(stream_message(
+            metrics_stream_request::Request::CommandResult(result),
+        ))
+        .await
+        .unwrap();
+        let result = state
+            .wait_result(events, &command_id, std::time::Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert!(result.success);
+
+        // Servers without it refuse the command stream
+        let (_state, server) = start_with(None, false).await;
+        let mut client = Client::connect(&server, &ConnectOptions::default())
+            .await
+            .unwrap();
+        let (_tx, rx) = mpsc::channel(1);
+        assert!(
+            client
+                .stream_commands(ReceiverStream::new(rx))
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket() {
+        let (state, _) = start(None).await;
+        let dir = std::env::temp_dir().join(format!("nanolink-mock-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("grpc.sock");
+        tokio::spawn(serve_unix(state, Some(&path)).unwrap());
+
+        let server = Server {
+            host: format!("unix://{}", path.display()),
+            ..Default::default()
+        };
+        let mut client = Client::connect(&server, &ConnectOptions::default())
+            .await
+            .unwrap();
+        let identity = AgentIdentity::new("agent-4", "local-01", "0.4.1");
+        let auth = client
+            .authenticate(identity.auth_request(""))
+            .await
+            .unwrap();
+        assert!(auth.success);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stream_without_agent_init() {
+        let (state, server) = start(Some("secret")).await;
+        let mut client = Client::connect(&server, &ConnectOptions::default())
+            .await
+            .unwrap();
+        let identity = AgentIdentity::new("agent-2", "db-01", "0.4.1");
+        client
+            .authenticate(identity.auth_request("secret"))
+            .await
+            .unwrap();
+
+        // The legacy stream starts with metrics; the authenticated
+        // connection identifies the agent
+        let (tx, rx) = mpsc::channel(4);
+        tx.send(stream_message(metrics_stream_request::Request::Heartbeat(
+            Heartbeat::default(),
+        )))
+        .await
+        .unwrap();
+        let mut responses = client
+            .stream_metrics(ReceiverStream::new(rx))
+            .await
+            .unwrap();
+        responses.message().await.unwrap().unwrap();
+
+        let agent = state.agent("agent-2").unwrap();
+        assert_eq!(agent.init.hostname, "db-01");
+        assert_eq!(agent.counts.heartbeats, 1);
+    }
+
+    #[tokio::test]
+    async fn test_control_plane_errors() {
+        let (state, _server) = start(None).await;
+        let router = control::router(state);
+
+        let send = |body: &'static str| {
+            Request::post("/api/agents/missing/commands")
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+        let response = router
+            .clone()
+            .oneshot(send(r#"{"type": "NOT_A_COMMAND"}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = router
+            .clone()
+            .oneshot(send(r#"{"type": "PROCESS_LIST"}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let request = Request::get("/api/commands/nope")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
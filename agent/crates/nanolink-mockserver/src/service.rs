@@ -0,0 +1,247 @@
+//! Server side of `NanoLinkService`
+
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+
+use nanolink_client::proto::nano_link_service_server::NanoLinkService;
+use nanolink_client::proto::{
+    AgentInfoRequest, AgentInfoResponse, AuthRequest, AuthResponse, Command, CommandResult,
+    ConfigProfile, ConfigProfileRequest, EnrollRequest, EnrollResponse, HeartbeatRequest,
+    HeartbeatResponse, Metrics, MetricsAck, MetricsStreamRequest, MetricsStreamResponse,
+    MetricsSyncRequest, MetricsSyncResponse, metrics_stream_request,
+};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+use tracing::{debug, info, warn};
+
+use crate::state::MockState;
+
+/// Responses buffered per stream before commands are refused
+const STREAM_BUFFER: usize = 64;
+
+pub struct MockService {
+    state: Arc<MockState>,
+}
+
+impl MockService {
+    pub fn new(state: Arc<MockState>) -> Self {
+        Self { state }
+    }
+}
+
+#[tonic::async_trait]
+impl NanoLinkService for MockService {
+    async fn authenticate(
+        &self,
+        request: Request<AuthRequest>,
+    ) -> Result<Response<AuthResponse>, Status> {
+        let peer = request.remote_addr();
+        let request = request.into_inner();
+        let response = self.state.authenticate(&request, peer);
+        if response.success {
+            info!(
+                "Authenticated {} ({}, protocol {})",
+                request.hostname, request.agent_id, request.protocol_version
+            );
+        } else {
+            warn!("Rejected token from {}", request.hostname);
+        }
+        Ok(Response::new(response))
+    }
+
+    type StreamMetricsStream = ReceiverStream<Result<MetricsStreamResponse, Status>>;
+
+    async fn stream_metrics(
+        &self,
+        request: Request<Streaming<MetricsStreamRequest>>,
+    ) -> Result<Response<Self::StreamMetricsStream>, Status> {
+        let peer = request.remote_addr();
+        let mut inbound = request.into_inner();
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        let state = self.state.clone();
+
+        // Agents send AgentInit once the response headers arrive, so the
+        // stream is answered first. Agents using the legacy stream send none
+        // and are known by the connection they authenticated on.
+        tokio::spawn(async move {
+            let mut session = state.peer_identity(peer).map(|init| {
+                info!("Stream opened by {} ({})", init.hostname, init.agent_id);
+                (init.agent_id.clone(), state.connect(init, tx.clone()))
+            });
+
+            loop {
+                let message = match inbound.message().await {
+                    Ok(Some(message)) => message,
+                    Ok(None) => break,
+                    Err(e) => {
+                        debug!("Stream failed: {e}");
+                        break;
+                    }
+                };
+                if let (None, Some(metrics_stream_request::Request::AgentInit(init))) =
+                    (&session, &message.request)
+                {
+                    if !state.may_stream(&init.agent_id) {
+                        let status = Status::unauthenticated("Agent has not authenticated");
+                        let _ = tx.send(Err(status)).await;
+                        break;
+                    }
+                    info!("Stream opened by {} ({})", init.hostname, init.agent_id);
+                    session = Some((
+                        init.agent_id.clone(),
+                        state.connect(init.clone(), tx.clone()),
+                    ));
+                    continue;
+                }
+                let Some((agent_id, _)) = &session else {
+                    let status = Status::failed_precondition("AgentInit must be the first message");
+                    let _ = tx.send(Err(status)).await;
+                    break;
+                };
+                if let Some(reply) = state.record(agent_id, message) {
+                    if tx.send(Ok(reply)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+
+            if let Some((agent_id, session)) = session {
+                state.disconnect(&agent_id, session);
+                info!("Stream closed by {agent_id}");
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    type StreamCommandsStream = ReceiverStream<Result<MetricsStreamResponse, Status>>;
+
+    async fn stream_commands(
+        &self,
+        request: Request<Streaming<MetricsStreamRequest>>,
+    ) -> Result<Response<Self::StreamCommandsStream>, Status> {
+        if !self.state.settings.command_stream {
+            return Err(Status::unimplemented("No command stream"));
+        }
+        let mut inbound = request.into_inner();
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        let state = self.state.clone();
+
+        tokio::spawn(async move {
+            let init = match inbound.message().await {
+                Ok(Some(MetricsStreamRequest {
+                    request: Some(metrics_stream_request::Request::AgentInit(init)),
+                })) => init,
+                Ok(_) => {
+                    let status = Status::failed_precondition("AgentInit must be the first message");
+                    let _ = tx.send(Err(status)).await;
+                    return;
+                }
+                Err(e) => {
+                    debug!("Command stream failed: {e}");
+                    return;
+                }
+            };
+            if !state.may_stream(&init.agent_id) {
+                let status = Status::unauthenticated("Agent has not authenticated");
+                let _ = tx.send(Err(status)).await;
+                return;
+            }
+            info!(
+                "Command stream opened by {} ({})",
+                init.hostname, init.agent_id
+            );
+            let session = state.connect_commands(&init, tx.clone());
+
+            while let Ok(Some(message)) = inbound.message().await {
+                if let Some(reply) = state.record(&init.agent_id, message) {
+                    if tx.send(Ok(reply)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+
+            state.disconnect_commands(&init.agent_id, session);
+            info!("Command stream closed by {}", init.agent_id);
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn report_metrics(
+        &self,
+        _request: Request<Metrics>,
+    ) -> Result<Response<MetricsAck>, Status> {
+        self.state.reports.fetch_add(1, Ordering::Relaxed);
+        Ok(Response::new(MetricsAck {
+            success: true,
+            timestamp: 0,
+        }))
+    }
+
+    async fn execute_command(
+        &self,
+        _request: Request<Command>,
+    ) -> Result<Response<CommandResult>, Status> {
+        Err(Status::unimplemented(
+            "Commands are sent on the metrics stream; use the control plane",
+        ))
+    }
+
+    async fn heartbeat(
+        &self,
+        _request: Request<HeartbeatRequest>,
+    ) -> Result<Response<HeartbeatResponse>, Status> {
+        Ok(Response::new(HeartbeatResponse {
+            server_timestamp: 0,
+            config_changed: false,
+        }))
+    }
+
+    async fn sync_metrics(
+        &self,
+        _request: Request<MetricsSyncRequest>,
+    ) -> Result<Response<MetricsSyncResponse>, Status> {
+        Ok(Response::new(MetricsSyncResponse {
+            success: true,
+            ..Default::default()
+        }))
+    }
+
+    async fn get_agent_info(
+        &self,
+        request: Request<AgentInfoRequest>,
+    ) -> Result<Response<AgentInfoResponse>, Status> {
+        let agent_id = request.into_inner().agent_id;
+        let agent = self
+            .state
+            .agent(&agent_id)
+            .ok_or_else(|| Status::not_found("Unknown agent"))?;
+        Ok(Response::new(AgentInfoResponse {
+            agent_id: agent.init.agent_id,
+            hostname: agent.init.hostname,
+            os: agent.init.os,
+            arch: agent.init.arch,
+            version: agent.init.agent_version,
+            permission_level: self.state.settings.permission_level,
+            connected_at: agent.connected_at,
+            ..Default::default()
+        }))
+    }
+
+    async fn get_config_profile(
+        &self,
+        _request: Request<ConfigProfileRequest>,
+    ) -> Result<Response<ConfigProfile>, Status> {
+        Err(Status::not_found("The mock server has no config profile"))
+    }
+
+    async fn enroll(
+        &self,
+        _request: Request<EnrollRequest>,
+    ) -> Result<Response<EnrollResponse>, Status> {
+        // Agents only enroll over TLS, which the mock server doesn't serve
+        Err(Status::unimplemented("Enrollment needs TLS"))
+    }
+}
@@ -0,0 +1,411 @@
+//! What the mock server has seen, shared by the gRPC service and the
+//! control plane
+
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use nanolink_client::proto::{
+    AgentInit, AgentTelemetry, AuthRequest, AuthResponse, Command, CommandResult, ConfigDrift,
+    HeartbeatAck, Metrics, MetricsStreamRequest, MetricsStreamResponse, PeriodicData,
+    RealtimeMetrics, StaticInfo, metrics_stream_request, metrics_stream_response,
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::sync::{broadcast, mpsc};
+use tonic::Status;
+
+/// Responses queued for one agent's stream
+pub type StreamSender = mpsc::Sender<Result<MetricsStreamResponse, Status>>;
+
+/// How the server answers agents
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Token agents must present; any token is accepted when unset
+    pub token: Option<String>,
+    pub permission_level: i32,
+    /// Stream protocol version announced during authentication
+    pub protocol_version: u32,
+    /// Largest message accepted, in bytes
+    pub max_message_size: u32,
+    /// Serve the dedicated command stream
+    pub command_stream: bool,
+}
+
+/// Stream messages received from one agent, by kind
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MessageCounts {
+    pub metrics: u64,
+    pub realtime: u64,
+    pub static_info: u64,
+    pub periodic: u64,
+    pub heartbeats: u64,
+    pub command_results: u64,
+    pub telemetry: u64,
+    pub log_batches: u64,
+    pub config_drift: u64,
+}
+
+/// Most recent message of each kind
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LastMessages {
+    pub metrics: Option<Metrics>,
+    pub realtime: Option<RealtimeMetrics>,
+    pub static_info: Option<StaticInfo>,
+    pub periodic: Option<PeriodicData>,
+    pub telemetry: Option<AgentTelemetry>,
+    pub config_drift: Option<ConfigDrift>,
+}
+
+/// An agent that opened a metrics stream
+#[derive(Debug, Clone, Serialize)]
+pub struct Agent {
+    #[serde(flatten)]
+    pub init: AgentInit,
+    /// Unix time of the latest stream, in seconds
+    pub connected_at: u64,
+    pub connected: bool,
+    /// Whether commands go to the dedicated command stream
+    pub command_stream: bool,
+    pub counts: MessageCounts,
+    #[serde(skip)]
+    pub last: LastMessages,
+    #[serde(skip)]
+    session: u64,
+    #[serde(skip)]
+    sender: Option<StreamSender>,
+    #[serde(skip)]
+    command_session: u64,
+    #[serde(skip)]
+    command_sender: Option<StreamSender>,
+}
+
+/// Why a message could not be delivered to an agent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendError {
+    UnknownAgent,
+    Disconnected,
+}
+
+impl std::fmt::Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendError::UnknownAgent => f.write_str("Agent has never connected"),
+            SendError::Disconnected => f.write_str("Agent is not connected"),
+        }
+    }
+}
+
+pub struct MockState {
+    pub settings: Settings,
+    agents: Mutex<HashMap<String, Agent>>,
+    /// Agent IDs that passed `Authenticate`; streams from others are refused
+    authenticated: Mutex<HashSet<String>>,
+    /// Identity presented on each authenticated connection
+    peers: Mutex<HashMap<SocketAddr, AgentInit>>,
+    results: Mutex<HashMap<String, CommandResult>>,
+    result_events: broadcast::Sender<CommandResult>,
+    next_id: AtomicU64,
+    /// Metrics received with the unary `ReportMetrics`
+    pub reports: AtomicU64,
+}
+
+impl MockState {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            settings,
+            agents: Mutex::new(HashMap::new()),
+            authenticated: Mutex::new(HashSet::new()),
+            peers: Mutex::new(HashMap::new()),
+            results: Mutex::new(HashMap::new()),
+            result_events: broadcast::channel(64).0,
+            next_id: AtomicU64::new(1),
+            reports: AtomicU64::new(0),
+        }
+    }
+
+    pub fn authenticate(&self, request: &AuthRequest, peer: Option<SocketAddr>) -> AuthResponse {
+        if self
+            .settings
+            .token
+            .as_ref()
+            .is_some_and(|token| *token != request.token)
+        {
+            return AuthResponse {
+                success: false,
+                error_message: "Invalid token".to_string(),
+                ..Default::default()
+            };
+        }
+
+        self.authenticated.lock().insert(request.agent_id.clone());
+        if let Some(peer) = peer {
+            self.peers.lock().insert(
+                peer,
+                AgentInit {
+                    agent_id: request.agent_id.clone(),
+                    hostname: request.hostname.clone(),
+                    os: request.os.clone(),
+                    arch: request.arch.clone(),
+                    agent_version: request.agent_version.clone(),
+                },
+            );
+        }
+        AuthResponse {
+            success: true,
+            permission_level: self.settings.permission_level,
+            max_message_size: self.settings.max_message_size,
+            protocol_version: self.settings.protocol_version,
+            ..Default::default()
+        }
+    }
+
+    /// Whether a stream from `agent_id` is accepted
+    pub fn may_stream(&self, agent_id: &str) -> bool {
+        self.settings.token.is_none() || self.authenticated.lock().contains(agent_id)
+    }
+
+    /// Identity presented by `Authenticate` on the connection from `peer`
+    pub fn peer_identity(&self, peer: Option<SocketAddr>) -> Option<AgentInit> {
+        self.peers.lock().get(&peer?).cloned()
+    }
+
+    /// Register a stream. Returns a session number for [`Self::disconnect`],
+    /// so a reconnect isn't undone by the old stream closing.
+    pub fn connect(&self, init: AgentInit, sender: StreamSender) -> u64 {
+        let session = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut agents = self.agents.lock();
+        let agent = Self::entry(&mut agents, &init);
+        agent.init = init;
+        agent.connected_at = unix_now();
+        agent.connected = true;
+        agent.session = session;
+        agent.sender = Some(sender);
+        session
+    }
+
+    pub fn disconnect(&self, agent_id: &str, session: u64) {
+        if let Some(agent) = self.agents.lock().get_mut(agent_id) {
+            if agent.session == session {
+                agent.connected = false;
+                agent.sender = None;
+            }
+        }
+    }
+
+    /// Register a command stream; commands go to it from now on. Returns a
+    /// session number for [`Self::disconnect_commands`].
+    pub fn connect_commands(&self, init: &AgentInit, sender: StreamSender) -> u64 {
+        let session = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut agents = self.agents.lock();
+        let agent = Self::entry(&mut agents, init);
+        agent.command_stream = true;
+        agent.command_session = session;
+        agent.command_sender = Some(sender);
+        session
+    }
+
+    pub fn disconnect_commands(&self, agent_id: &str, session: u64) {
+        if let Some(agent) = self.agents.lock().get_mut(agent_id) {
+            if agent.command_session == session {
+                agent.command_stream = false;
+                agent.command_sender = None;
+            }
+        }
+    }
+
+    fn entry<'a>(agents: &'a mut HashMap<String, Agent>, init: &AgentInit) -> &'a mut Agent {
+        agents
+            .entry(init.agent_id.clone())
+            .or_insert_with(|| Agent {
+                init: init.clone(),
+                connected_at: 0,
+                connected: false,
+                command_stream: false,
+                counts: MessageCounts::default(),
+                last: LastMessages::default(),
+                session: 0,
+                sender: None,
+                command_session: 0,
+                command_sender: None,
+            })
+    }
+
+    /// Account for a stream message. Returns the reply owed to it, if any.
+    pub fn record(
+        &self,
+        agent_id: &str,
+        message: MetricsStreamRequest,
+    ) -> Option<MetricsStreamResponse> {
+        use metrics_stream_request::Request;
+
+        let mut reply = None;
+        let mut result = None;
+        {
+            let mut agents = self.agents.lock();
+            let agent = agents.get_mut(agent_id)?;
+            let (counts, last) = (&mut agent.counts, &mut agent.last);
+            match message.request? {
+                Request::Metrics(metrics) => {
+                    counts.metrics += 1;
+                    last.metrics = Some(metrics);
+                }
+                Request::Realtime(realtime) => {
+                    counts.realtime += 1;
+                    last.realtime = Some(realtime);
+                }
+                Request::StaticInfo(info) => {
+                    counts.static_info += 1;
+                    last.static_info = Some(info);
+                }
+                Request::Periodic(periodic) => {
+                    counts.periodic += 1;
+                    last.periodic = Some(periodic);
+                }
+                Request::Telemetry(telemetry) => {
+                    counts.telemetry += 1;
+                    last.telemetry = Some(telemetry);
+                }
+                Request::LogBatch(_) => counts.log_batches += 1,
+                Request::ConfigDrift(drift) => {
+                    counts.config_drift += 1;
+                    last.config_drift = Some(drift);
+                }
+                Request::Heartbeat(_) => {
+                    counts.heartbeats += 1;
+                    reply = Some(MetricsStreamResponse {
+                        response: Some(metrics_stream_response::Response::HeartbeatAck(
+                            HeartbeatAck {
+                                timestamp: unix_now_ms(),
+                            },
+                        )),
+                    });
+                }
+                Request::CommandResult(command_result) => {
+                    counts.command_results += 1;
+                    result = Some(command_result);
+                }
+                // A repeated AgentInit only refreshes the identity
+                Request::AgentInit(init) => agent.init = init,
+            }
+        }
+
+        if let Some(result) = result {
+            self.results
+                .lock()
+                .insert(result.command_id.clone(), result.clone());
+            let _ = self.result_events.send(result);
+        }
+        reply
+    }
+
+    pub fn agents(&self) -> Vec<Agent> {
+        let mut agents: Vec<Agent> = self.agents.lock().values().cloned().collect();
+        agents.sort_by(|a, b| a.init.agent_id.cmp(&b.init.agent_id));
+        agents
+    }
+
+    pub fn agent(&self, agent_id: &str) -> Option<Agent> {
+        self.agents.lock().get(agent_id).cloned()
+    }
+
+    /// Queue a response on an agent's stream
+    pub async fn send(
+        &self,
+        agent_id: &str,
+        response: MetricsStreamResponse,
+    ) -> Result<(), SendError> {
+        let sender = {
+            let agents = self.agents.lock();
+            let agent = agents.get(agent_id).ok_or(SendError::UnknownAgent)?;
+            agent.sender.clone().ok_or(SendError::Disconnected)?
+        };
+        sender
+            .send(Ok(response))
+            .await
+            .map_err(|_| SendError::Disconnected)
+    }
+
+    /// Send a command, on the command stream if the agent opened one,
+    /// giving it an ID if it has none. Returns the ID.
+    pub async fn send_command(
+        &self,
+        agent_id: &str,
+        mut command: Command,
+    ) -> Result<String, SendError> {
+        if command.command_id.is_empty() {
+            command.command_id = format!("mock-{}", self.next_id.fetch_add(1, Ordering::Relaxed));
+        }
+        let command_id = command.command_id.clone();
+        let response = MetricsStreamResponse {
+            response: Some(metrics_stream_response::Response::Command(command)),
+        };
+        let command_sender = {
+            let agents = self.agents.lock();
+            let agent = agents.get(agent_id).ok_or(SendError::UnknownAgent)?;
+            agent.command_sender.clone()
+        };
+        match command_sender {
+            Some(sender) => sender
+                .send(Ok(response))
+                .await
+                .map_err(|_| SendError::Disconnected)?,
+            None => self.send(agent_id, response).await?,
+        }
+        Ok(command_id)
+    }
+
+    pub fn result(&self, command_id: &str) -> Option<CommandResult> {
+        self.results.lock().get(command_id).cloned()
+    }
+
+    /// Subscribe before sending a command whose result will be awaited
+    pub fn subscribe_results(&self) -> broadcast::Receiver<CommandResult> {
+        self.result_events.subscribe()
+    }
+
+    /// Wait for the result of `command_id`, from a receiver subscribed
+    /// before the command was sent
+    pub async fn wait_result(
+        &self,
+        mut events: broadcast::Receiver<CommandResult>,
+        command_id: &str,
+        timeout: Duration,
+    ) -> Option<CommandResult> {
+        let wait = async {
+            loop {
+                match events.recv().await {
+                    Ok(result) if result.command_id == command_id => return Some(result),
+                    Ok(_) => {}
+                    // Missed events: the result may already be stored
+                    Err(broadcast::error::RecvError::Lagged(_)) => {
+                        if let Some(result) = self.result(command_id) {
+                            return Some(result);
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        };
+        tokio::time::timeout(timeout, wait)
+            .await
+            .ok()
+            .flatten()
+            .or_else(|| self.result(command_id))
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn unix_now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nanolink_parse::dpkg;
+
+fuzz_target!(|data: &[u8]| {
+    let output = String::from_utf8_lossy(data);
+    for package in dpkg::packages(&output) {
+        assert!(!package.name.is_empty() && !package.name.contains(char::is_whitespace));
+        assert!(!package.version.is_empty() && !package.version.contains(char::is_whitespace));
+    }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nanolink_parse::netstat;
+
+fuzz_target!(|data: &[u8]| {
+    let output = String::from_utf8_lossy(data);
+    for port in netstat::listening_ports(&output) {
+        assert_eq!(port.protocol, "tcp");
+        assert!(port.pid.is_some());
+    }
+});
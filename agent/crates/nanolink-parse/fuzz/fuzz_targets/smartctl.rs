@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nanolink_parse::smartctl;
+
+fuzz_target!(|data: &[u8]| {
+    let output = String::from_utf8_lossy(data);
+    smartctl::health(&output);
+    if let Some(celsius) = smartctl::temperature(&output) {
+        assert!((1.0..=150.0).contains(&celsius));
+    }
+});
@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nanolink_parse::ss;
+
+fuzz_target!(|data: &[u8]| {
+    let output = String::from_utf8_lossy(data);
+    for port in ss::listening_ports(&output) {
+        assert!(port.protocol == "tcp" || port.protocol == "udp");
+        assert_eq!(port.pid.is_some(), port.process.is_some());
+    }
+    for socket in ss::socket_bytes(&output) {
+        assert_ne!(socket.inode, 0);
+    }
+});
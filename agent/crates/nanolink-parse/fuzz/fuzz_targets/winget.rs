@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nanolink_parse::winget;
+
+fuzz_target!(|data: &[u8]| {
+    let output = String::from_utf8_lossy(data);
+    for package in winget::packages(&output) {
+        assert!(!package.name.is_empty());
+        assert!(!package.id.is_empty() && !package.id.contains(char::is_whitespace));
+        assert!(!package.version.is_empty());
+    }
+});
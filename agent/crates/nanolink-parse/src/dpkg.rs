@@ -0,0 +1,144 @@
+//! `dpkg-query` (Debian/Ubuntu)
+
+/// Output format to run `dpkg-query -W -f` with
+pub const QUERY_FORMAT: &str = "${Package}\t${Version}\t${Status}\n";
+
+/// One row of `dpkg-query -W -f` output in [`QUERY_FORMAT`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    /// Selection, flag and state, e.g. `install ok installed`
+    pub status: String,
+}
+
+impl Package {
+    /// Whether the package is unpacked and configured. Removed packages
+    /// that left config files behind are listed too, as `config-files`.
+    pub fn is_installed(&self) -> bool {
+        self.status.split_whitespace().nth(2) == Some("installed")
+    }
+}
+
+/// Parse `dpkg-query -W -f` output in [`QUERY_FORMAT`].
+///
+/// Rows whose name or version break Debian policy are skipped.
+pub fn packages(output: &str) -> Vec<Package> {
+    output.lines().filter_map(package).collect()
+}
+
+fn package(line: &str) -> Option<Package> {
+    let mut fields = line.split('\t');
+    let name = fields.next()?;
+    let version = fields.next()?;
+    let status = fields.next().unwrap_or("").trim();
+    if fields.next().is_some() || !valid_name(name) || !valid_version(version) {
+        return None;
+    }
+
+    Some(Package {
+        name: name.to_string(),
+        version: version.to_string(),
+        status: status.to_string(),
+    })
+}
+
+/// Lowercase letters, digits, `+`, `-` and `.`, starting with a letter or
+/// digit and at least two characters long
+fn valid_name(name: &str) -> bool {
+    name.len() >= 2
+        && name.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b"+-.".contains(&b))
+}
+
+/// `[epoch:]upstream[-revision]`, with the upstream version starting with a
+/// digit
+fn valid_version(version: &str) -> bool {
+    let upstream = match version.split_once(':') {
+        Some((epoch, rest)) if epoch.bytes().all(|b| b.is_ascii_digit()) && !epoch.is_empty() => {
+            rest
+        }
+        Some(_) => return false,
+        None => version,
+    };
+    upstream.starts_with(|c: char| c.is_ascii_digit())
+        && upstream
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"+-.~:".contains(&b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const OUTPUT: &str = "\
+adduser\t3.134\tinstall ok installed
+libc6\t2.39-0ubuntu8.3\tinstall ok installed
+linux-image-6.8.0-31-generic\t6.8.0-31.31\tdeinstall ok config-files
+openssh-server\t1:9.6p1-3ubuntu13.5\tinstall ok installed
+dpkg-query: warning: parsing file '/var/lib/dpkg/status' near line 12
+Broken Name\t1.0\tinstall ok installed
+python3\t\tunknown ok not-installed
+";
+
+    #[test]
+    fn test_packages() {
+        let packages = packages(OUTPUT);
+        assert_eq!(packages.len(), 4);
+        assert_eq!(packages[1].name, "libc6");
+        assert_eq!(packages[1].version, "2.39-0ubuntu8.3");
+        assert!(!packages[2].is_installed());
+        assert_eq!(packages[3].version, "1:9.6p1-3ubuntu13.5");
+        assert!(packages[3].is_installed());
+    }
+
+    #[test]
+    fn test_valid_version() {
+        assert!(valid_version("2:1.0~rc1+dfsg-1"));
+        assert!(!valid_version(""));
+        assert!(!valid_version("a1.0"));
+        assert!(!valid_version(":1.0"));
+        assert!(!valid_version("1.0 beta"));
+    }
+
+    proptest! {
+        #[test]
+        fn never_panics(output in "\\PC*") {
+            packages(&output);
+        }
+
+        #[test]
+        fn damaged_rows_are_valid_or_dropped(
+            line in 0..7usize,
+            cut in 0..60usize,
+            junk in "\\PC{0,12}",
+        ) {
+            let mut lines: Vec<String> = OUTPUT.lines().map(String::from).collect();
+            let target = &mut lines[line];
+            let cut = target.char_indices().nth(cut).map_or(target.len(), |(i, _)| i);
+            target.insert_str(cut, &junk);
+
+            for package in packages(&lines.join("\n")) {
+                prop_assert!(valid_name(&package.name));
+                prop_assert!(valid_version(&package.version));
+                prop_assert!(!package.status.contains('\t'));
+            }
+        }
+
+        #[test]
+        fn well_formed_rows_round_trip(
+            name in "[a-z0-9][a-z0-9+.-]{1,30}",
+            version in "([0-9]:)?[0-9][a-z0-9.+~]{0,10}(-[0-9a-z.]{1,6})?",
+        ) {
+            let line = format!("{name}\t{version}\tinstall ok installed");
+            let packages = packages(&line);
+            prop_assert_eq!(packages.len(), 1);
+            prop_assert_eq!(&packages[0].name, &name);
+            prop_assert_eq!(&packages[0].version, &version);
+            prop_assert!(packages[0].is_installed());
+        }
+    }
+}
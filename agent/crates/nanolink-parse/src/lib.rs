@@ -0,0 +1,88 @@
+//! Typed parsers for the command output the NanoLink agent collects from
+//!
+//! Tools like `ss`, `netstat`, `dpkg-query`, `winget` and `smartctl` print
+//! text meant for people, and the text changes with versions, locales and
+//! terminal widths. Each parser here validates every row it returns and skips
+//! the rest, so malformed output means fewer rows rather than wrong metrics.
+//! None of them panic, whatever the input; this is checked with property
+//! tests and with the cargo-fuzz targets in `fuzz/`.
+//!
+//! ```
+//! let output = "\
+//! Netid State  Recv-Q Send-Q Local Address:Port Peer Address:Port Process
+//! tcp   LISTEN 0      128    0.0.0.0:22         0.0.0.0:*         users:((\"sshd\",pid=812,fd=3))
+//! ";
+//! let ports = nanolink_parse::ss::listening_ports(output);
+//! assert_eq!(ports[0].port, 22);
+//! assert_eq!(ports[0].pid, Some(812));
+//! ```
+
+pub mod dpkg;
+pub mod netstat;
+pub mod smartctl;
+pub mod ss;
+pub mod winget;
+
+/// A socket accepting connections or datagrams
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListeningPort {
+    /// `tcp` or `udp` as printed by the tool, lowercased
+    pub protocol: String,
+    /// Local address including the port, e.g. `0.0.0.0:22` or `[::]:443`
+    pub address: String,
+    pub port: u16,
+    pub pid: Option<u32>,
+    pub process: Option<String>,
+}
+
+/// Port of a `host:port` address. `None` for wildcards and anything that
+/// isn't a number in range.
+fn address_port(address: &str) -> Option<u16> {
+    let (host, port) = address.rsplit_once(':')?;
+    if host.is_empty() || !port.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    port.parse().ok()
+}
+
+/// The first `n` whitespace-separated fields of `line`, and whatever
+/// follows them with surrounding whitespace trimmed
+fn split_fields(line: &str, n: usize) -> Option<(Vec<&str>, &str)> {
+    let mut fields = Vec::with_capacity(n);
+    let mut rest = line.trim_start();
+    while fields.len() < n {
+        if rest.is_empty() {
+            return None;
+        }
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        fields.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    Some((fields, rest.trim_end()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address_port() {
+        assert_eq!(address_port("0.0.0.0:22"), Some(22));
+        assert_eq!(address_port("[::]:443"), Some(443));
+        assert_eq!(address_port("127.0.0.53%lo:53"), Some(53));
+        assert_eq!(address_port("*:*"), None);
+        assert_eq!(address_port(":80"), None);
+        assert_eq!(address_port("0.0.0.0:70000"), None);
+        assert_eq!(address_port("0.0.0.0:+22"), None);
+        assert_eq!(address_port("Address:Port"), None);
+    }
+
+    #[test]
+    fn test_split_fields() {
+        let (fields, rest) = split_fields("  a  b\tc d e  ", 2).unwrap();
+        assert_eq!(fields, ["a", "b"]);
+        assert_eq!(rest, "c d e");
+        assert_eq!(split_fields("a b", 2).unwrap().1, "");
+        assert!(split_fields("a ", 2).is_none());
+    }
+}
@@ -0,0 +1,80 @@
+//! `netstat -ano` (Windows)
+
+use crate::{ListeningPort, address_port, split_fields};
+
+/// Parse `netstat -ano` output into listening TCP sockets.
+///
+/// Only rows in the `LISTENING` state are returned; netstat translates the
+/// state on non-English Windows, where this finds nothing. The owning
+/// process name is not part of the output.
+pub fn listening_ports(output: &str) -> Vec<ListeningPort> {
+    output.lines().filter_map(listening_port).collect()
+}
+
+fn listening_port(line: &str) -> Option<ListeningPort> {
+    let (fields, rest) = split_fields(line, 5)?;
+    if !fields[0].eq_ignore_ascii_case("tcp") || fields[3] != "LISTENING" || !rest.is_empty() {
+        return None;
+    }
+    let port = address_port(fields[1])?;
+    let pid = fields[4].parse().ok()?;
+
+    Some(ListeningPort {
+        protocol: "tcp".to_string(),
+        address: fields[1].to_string(),
+        port,
+        pid: Some(pid),
+        process: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const OUTPUT: &str = "\r
+Active Connections\r
+\r
+  Proto  Local Address          Foreign Address        State           PID\r
+  TCP    0.0.0.0:135            0.0.0.0:0              LISTENING       1104\r
+  TCP    127.0.0.1:5939         0.0.0.0:0              LISTENING       4\r
+  TCP    10.0.0.5:49702         20.42.65.90:443        ESTABLISHED     5120\r
+  TCP    [::]:445               [::]:0                 LISTENING       System\r
+  TCP    [::]:3389              [::]:0                 LISTENING       996\r
+  UDP    0.0.0.0:500            *:*                                    3312\r
+";
+
+    #[test]
+    fn test_listening_ports() {
+        let ports = listening_ports(OUTPUT);
+        assert_eq!(ports.len(), 3);
+        assert_eq!((ports[0].port, ports[0].pid), (135, Some(1104)));
+        assert_eq!(ports[2].address, "[::]:3389");
+        assert!(ports.iter().all(|p| p.protocol == "tcp"));
+    }
+
+    proptest! {
+        #[test]
+        fn never_panics(output in "\\PC*") {
+            listening_ports(&output);
+        }
+
+        #[test]
+        fn damaged_rows_are_valid_or_dropped(
+            line in 0..10usize,
+            cut in 0..90usize,
+            junk in "\\PC{0,12}",
+        ) {
+            let mut lines: Vec<String> = OUTPUT.lines().map(String::from).collect();
+            let target = &mut lines[line];
+            let cut = target.char_indices().nth(cut).map_or(target.len(), |(i, _)| i);
+            target.insert_str(cut, &junk);
+
+            for port in listening_ports(&lines.join("\n")) {
+                prop_assert_eq!(address_port(&port.address), Some(port.port));
+                prop_assert!(port.pid.is_some());
+            }
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! `smartctl` from smartmontools
+
+use std::fmt;
+
+/// Highest temperature in °C taken as a real reading; anything above is a
+/// misparsed raw value
+const MAX_TEMPERATURE: f64 = 150.0;
+
+/// Result of the S.M.A.R.T. overall health self-assessment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Health {
+    Passed,
+    Failed,
+    Unknown,
+}
+
+impl Health {
+    /// The name reported in disk metrics
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Health::Passed => "PASSED",
+            Health::Failed => "FAILED",
+            Health::Unknown => "Unknown",
+        }
+    }
+}
+
+impl fmt::Display for Health {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Parse `smartctl -H` output.
+///
+/// ATA and NVMe devices print `...self-assessment test result: PASSED`,
+/// SCSI devices `SMART Health Status: OK`. Only that line is looked at, so
+/// a model name containing `FAILED` is not a failure.
+pub fn health(output: &str) -> Health {
+    for line in output.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if key.trim() == "SMART overall-health self-assessment test result" {
+            if value.starts_with("PASSED") {
+                return Health::Passed;
+            }
+            if value.starts_with("FAILED") {
+                return Health::Failed;
+            }
+        } else if key.trim() == "SMART Health Status" {
+            return if value == "OK" {
+                Health::Passed
+            } else {
+                Health::Failed
+            };
+        }
+    }
+    Health::Unknown
+}
+
+/// Parse the drive temperature in °C from `smartctl -A` output.
+///
+/// ATA devices report it in the attribute table, preferably as
+/// `Temperature_Celsius` (194), otherwise as `Airflow_Temperature_Cel`
+/// (190); NVMe devices as `Temperature: 38 Celsius` and SCSI devices as
+/// `Current Drive Temperature: 30 C`. Readings outside 1–150 °C are
+/// treated as missing.
+pub fn temperature(output: &str) -> Option<f64> {
+    let mut airflow = None;
+    for line in output.lines() {
+        if let Some((key, value)) = line.split_once(':') {
+            if matches!(key.trim(), "Temperature" | "Current Drive Temperature") {
+                let reading = value.split_whitespace().next().and_then(plausible);
+                if reading.is_some() {
+                    return reading;
+                }
+            }
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 10 || fields[0].parse::<u8>().is_err() {
+            continue;
+        }
+        // The raw value may carry extra detail, e.g. `35 (Min/Max 18/45)`
+        let reading = plausible(fields[9]);
+        match fields[1] {
+            "Temperature_Celsius" if reading.is_some() => return reading,
+            "Airflow_Temperature_Cel" => airflow = airflow.or(reading),
+            _ => {}
+        }
+    }
+    airflow
+}
+
+fn plausible(value: &str) -> Option<f64> {
+    let value: f64 = value.parse().ok()?;
+    (1.0..=MAX_TEMPERATURE).contains(&value).then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const ATA_ATTRIBUTES: &str = "\
+smartctl 7.4 2023-08-01 r5530 [x86_64-linux-6.8.0-31-generic] (local build)
+=== START OF READ SMART DATA SECTION ===
+SMART Attributes Data Structure revision number: 16
+Vendor Specific SMART Attributes with Thresholds:
+ID# ATTRIBUTE_NAME          FLAG     VALUE WORST THRESH TYPE      UPDATED  WHEN_FAILED RAW_VALUE
+  5 Reallocated_Sector_Ct   0x0033   100   100   010    Pre-fail  Always       -       0
+  9 Power_On_Hours          0x0032   095   095   000    Old_age   Always       -       21543
+190 Airflow_Temperature_Cel 0x0032   062   048   000    Old_age   Always       -       38
+194 Temperature_Celsius     0x0022   036   052   000    Old_age   Always       -       36 (Min/Max 18/52)
+";
+
+    const NVME_ATTRIBUTES: &str = "\
+=== START OF SMART DATA SECTION ===
+SMART/Health Information (NVMe Log 0x02)
+Critical Warning:                   0x00
+Temperature:                        41 Celsius
+Available Spare:                    100%
+";
+
+    #[test]
+    fn test_temperature() {
+        assert_eq!(temperature(ATA_ATTRIBUTES), Some(36.0));
+        assert_eq!(temperature(NVME_ATTRIBUTES), Some(41.0));
+        assert_eq!(
+            temperature("Current Drive Temperature:     30 C\n"),
+            Some(30.0)
+        );
+        // Airflow only, and a bogus 194 raw value
+        let airflow_only = ATA_ATTRIBUTES.replace("36 (Min/Max 18/52)", "4294967295");
+        assert_eq!(temperature(&airflow_only), Some(38.0));
+        assert_eq!(temperature("Temperature:  0 Celsius\n"), None);
+        assert_eq!(temperature(""), None);
+    }
+
+    #[test]
+    fn test_health() {
+        let passed = "=== START OF READ SMART DATA SECTION ===\n\
+                      SMART overall-health self-assessment test result: PASSED\n";
+        assert_eq!(health(passed), Health::Passed);
+        let failed = "Device Model: FAILED-ARRAY-01\n\
+                      SMART overall-health self-assessment test result: FAILED!\n\
+                      Drive failure expected in less than 24 hours. SAVE ALL DATA.\n";
+        assert_eq!(health(failed), Health::Failed);
+        assert_eq!(health("SMART Health Status: OK\n"), Health::Passed);
+        assert_eq!(
+            health("SMART Health Status: FIRMWARE IMPENDING FAILURE\n"),
+            Health::Failed
+        );
+        assert_eq!(health("Device Model: FAILED-ARRAY-01\n"), Health::Unknown);
+        assert_eq!(Health::Unknown.to_string(), "Unknown");
+    }
+
+    proptest! {
+        #[test]
+        fn never_panics(output in "\\PC*") {
+            health(&output);
+            temperature(&output);
+        }
+
+        #[test]
+        fn damaged_output_gives_plausible_temperatures(
+            line in 0..9usize,
+            cut in 0..100usize,
+            junk in "\\PC{0,12}",
+        ) {
+            let mut lines: Vec<String> = ATA_ATTRIBUTES.lines().map(String::from).collect();
+            let target = &mut lines[line];
+            let cut = target.char_indices().nth(cut).map_or(target.len(), |(i, _)| i);
+            target.insert_str(cut, &junk);
+
+            if let Some(celsius) = temperature(&lines.join("\n")) {
+                prop_assert!((1.0..=MAX_TEMPERATURE).contains(&celsius));
+            }
+        }
+    }
+}
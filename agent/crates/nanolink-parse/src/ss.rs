@@ -0,0 +1,185 @@
+//! `ss` from iproute2 (Linux)
+
+use crate::{ListeningPort, address_port, split_fields};
+
+/// Cumulative TCP counters of one socket
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SocketBytes {
+    pub inode: u64,
+    pub sent: u64,
+    pub received: u64,
+}
+
+/// Parse `ss -tulnp` output.
+///
+/// Rows need a `tcp`/`udp` netid, a listening state, numeric queues and a
+/// local address with a port. The process column is optional: without
+/// root, sockets of other users have none.
+pub fn listening_ports(output: &str) -> Vec<ListeningPort> {
+    output.lines().filter_map(listening_port).collect()
+}
+
+fn listening_port(line: &str) -> Option<ListeningPort> {
+    let (fields, process) = split_fields(line, 6)?;
+    let protocol = fields[0].to_ascii_lowercase();
+    if !matches!(protocol.as_str(), "tcp" | "udp")
+        || !matches!(fields[1], "LISTEN" | "UNCONN")
+        || fields[2].parse::<u64>().is_err()
+        || fields[3].parse::<u64>().is_err()
+    {
+        return None;
+    }
+    let port = address_port(fields[4])?;
+    let (pid, process) = users(process).unzip();
+
+    Some(ListeningPort {
+        protocol,
+        address: fields[4].to_string(),
+        port,
+        pid,
+        process,
+    })
+}
+
+/// First `(pid, name)` of a `users:(("name",pid=123,fd=4),...)` column
+fn users(column: &str) -> Option<(u32, String)> {
+    let entry = column.strip_prefix("users:((\"")?;
+    let (name, rest) = entry.split_once("\",pid=")?;
+    let digits = rest.split(|c: char| !c.is_ascii_digit()).next()?;
+    let pid = digits.parse().ok()?;
+    (!name.is_empty()).then(|| (pid, name.to_string()))
+}
+
+/// Parse `ss -tinHe` output into per-socket cumulative counters.
+///
+/// Each socket spans two lines: the connection line carrying `ino:<inode>`
+/// and an indented `tcp_info` line with `bytes_acked`/`bytes_received`.
+/// Sockets without an inode (owned by another network namespace) are
+/// dropped.
+pub fn socket_bytes(output: &str) -> Vec<SocketBytes> {
+    let field = |line: &str, key: &str| -> Option<u64> {
+        line.split_whitespace()
+            .find_map(|token| token.strip_prefix(key))
+            .and_then(|v| v.parse().ok())
+    };
+
+    let mut sockets = Vec::new();
+    let mut current: Option<SocketBytes> = None;
+
+    for line in output.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            sockets.extend(current.take());
+            current = field(line, "ino:")
+                .filter(|&inode| inode != 0)
+                .map(|inode| SocketBytes {
+                    inode,
+                    ..Default::default()
+                });
+        } else if let Some(socket) = current.as_mut() {
+            socket.sent = field(line, "bytes_acked:")
+                .or_else(|| field(line, "bytes_sent:"))
+                .unwrap_or(0);
+            socket.received = field(line, "bytes_received:").unwrap_or(0);
+        }
+    }
+    sockets.extend(current);
+
+    sockets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const LISTEN_OUTPUT: &str = "\
+Netid State  Recv-Q Send-Q      Local Address:Port  Peer Address:Port Process
+udp   UNCONN 0      0       127.0.0.53%lo:53         0.0.0.0:*     users:((\"systemd-resolve\",pid=600,fd=13))
+tcp   LISTEN 0      4096          0.0.0.0:22         0.0.0.0:*     users:((\"sshd\",pid=812,fd=3),(\"sshd\",pid=1,fd=3))
+tcp   LISTEN 0      511              [::]:80            [::]:*     users:((\"Web Server\",pid=77,fd=6))
+tcp   LISTEN 0      128         127.0.0.1:631        0.0.0.0:*
+tcp   LISTEN 0      128         127.0.0.1:???        0.0.0.0:*
+";
+
+    const TCP_INFO_OUTPUT: &str = "\
+ESTAB 0      0      127.0.0.1:45236 127.0.0.1:48271 timer:(keepalive,45sec,0) ino:82532 sk:1 cgroup:/ <->
+\t ts sack bbr wscale:10,10 rto:204 bytes_sent:20867505 bytes_acked:20867506 bytes_received:2400595 segs_out:1134
+ESTAB 0      0      10.0.0.2:22 10.0.0.1:5555 ino:0 sk:2 <->
+\t ts sack cubic bytes_acked:10 bytes_received:20
+ESTAB 0      0      10.0.0.2:443 10.0.0.9:6000 ino:900 sk:3 <->
+\t ts sack cubic bytes_acked:1000 bytes_received:500
+";
+
+    #[test]
+    fn test_listening_ports() {
+        let ports = listening_ports(LISTEN_OUTPUT);
+        assert_eq!(ports.len(), 4);
+        assert_eq!(ports[0].protocol, "udp");
+        assert_eq!(ports[0].address, "127.0.0.53%lo:53");
+        assert_eq!(ports[0].process.as_deref(), Some("systemd-resolve"));
+        assert_eq!((ports[1].port, ports[1].pid), (22, Some(812)));
+        // Names with spaces survive, unlike with whitespace splitting
+        assert_eq!(ports[2].process.as_deref(), Some("Web Server"));
+        assert_eq!((ports[3].pid, ports[3].process.as_deref()), (None, None));
+    }
+
+    #[test]
+    fn test_socket_bytes() {
+        let sockets = socket_bytes(TCP_INFO_OUTPUT);
+        assert_eq!(sockets.len(), 2);
+        assert_eq!(sockets[0].inode, 82532);
+        assert_eq!(sockets[0].sent, 20867506);
+        assert_eq!(sockets[0].received, 2400595);
+        assert_eq!(sockets[1].inode, 900);
+    }
+
+    proptest! {
+        #[test]
+        fn listening_ports_never_panics(output in "\\PC*") {
+            listening_ports(&output);
+        }
+
+        #[test]
+        fn socket_bytes_never_panics(output in "[ \\ta-z0-9:_.\\n]*") {
+            socket_bytes(&output);
+        }
+
+        /// Every row that survives a damaged line is still a valid row
+        #[test]
+        fn damaged_rows_are_valid_or_dropped(
+            line in 0..6usize,
+            cut in 0..120usize,
+            junk in "\\PC{0,12}",
+        ) {
+            let mut lines: Vec<String> = LISTEN_OUTPUT.lines().map(String::from).collect();
+            let target = &mut lines[line];
+            let cut = target.char_indices().nth(cut).map_or(target.len(), |(i, _)| i);
+            target.insert_str(cut, &junk);
+
+            for port in listening_ports(&lines.join("\n")) {
+                prop_assert!(port.protocol == "tcp" || port.protocol == "udp");
+                prop_assert_eq!(address_port(&port.address), Some(port.port));
+                prop_assert_eq!(port.pid.is_some(), port.process.is_some());
+            }
+        }
+
+        #[test]
+        fn well_formed_rows_round_trip(
+            tcp in any::<bool>(),
+            port in 1..=u16::MAX,
+            pid in any::<u32>(),
+            name in "[A-Za-z][A-Za-z0-9 ._-]{0,14}",
+        ) {
+            let protocol = if tcp { "tcp" } else { "udp" };
+            let state = if tcp { "LISTEN" } else { "UNCONN" };
+            let line = format!(
+                "{protocol} {state} 0 128 0.0.0.0:{port} 0.0.0.0:* users:((\"{name}\",pid={pid},fd=3))"
+            );
+            let ports = listening_ports(&line);
+            prop_assert_eq!(ports.len(), 1);
+            prop_assert_eq!(ports[0].port, port);
+            prop_assert_eq!(ports[0].pid, Some(pid));
+            prop_assert_eq!(ports[0].process.as_deref(), Some(name.as_str()));
+        }
+    }
+}
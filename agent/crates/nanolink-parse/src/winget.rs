@@ -0,0 +1,238 @@
+//! `winget list` and `winget upgrade` (Windows)
+
+/// One row of a winget package table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Package {
+    /// Display name, possibly truncated with `…`
+    pub name: String,
+    /// Package identifier, as taken by `winget upgrade --id`
+    pub id: String,
+    pub version: String,
+    /// Newer version, in `winget upgrade` output and for `winget list` rows
+    /// with an update
+    pub available: Option<String>,
+    pub source: Option<String>,
+}
+
+/// Parse the package tables of `winget list` or `winget upgrade`.
+///
+/// winget prints fixed-width columns sized to the header, so rows are cut
+/// at the header's column positions rather than split on whitespace, which
+/// would break names with spaces. A table starts at each header followed by
+/// a dashed line; `winget upgrade` can print a second one for packages that
+/// need explicit targeting. Rows without an ID or version are skipped,
+/// which also drops progress output and the summary lines.
+pub fn packages(output: &str) -> Vec<Package> {
+    // Progress spinners redraw the line with carriage returns
+    let lines: Vec<&str> = output
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .map(|line| line.rsplit('\r').next().unwrap_or(line))
+        .collect();
+
+    let mut packages = Vec::new();
+    let mut columns: Option<Columns> = None;
+    for (i, line) in lines.iter().enumerate() {
+        if is_separator(line) {
+            columns = i
+                .checked_sub(1)
+                .and_then(|h| Columns::from_header(lines[h]));
+            continue;
+        }
+        // A header line is only recognised by the separator after it
+        if lines.get(i + 1).is_some_and(|next| is_separator(next)) {
+            continue;
+        }
+        if let Some(package) = columns.as_ref().and_then(|c| c.package(line)) {
+            packages.push(package);
+        }
+    }
+    packages
+}
+
+fn is_separator(line: &str) -> bool {
+    let line = line.trim();
+    line.chars().count() >= 3 && line.chars().all(|c| c == '-' || c == '─')
+}
+
+/// Character offsets of a table's columns
+struct Columns {
+    starts: Vec<usize>,
+    name: usize,
+    id: usize,
+    version: usize,
+    available: Option<usize>,
+    source: Option<usize>,
+}
+
+impl Columns {
+    fn from_header(header: &str) -> Option<Self> {
+        let mut starts = Vec::new();
+        let mut titles = Vec::new();
+        let mut previous = ' ';
+        for (offset, c) in header.chars().enumerate() {
+            if previous == ' ' && c != ' ' {
+                starts.push(offset);
+                titles.push(String::new());
+            }
+            if c != ' ' {
+                if let Some(title) = titles.last_mut() {
+                    title.push(c.to_ascii_lowercase());
+                }
+            }
+            previous = c;
+        }
+        if starts.len() < 3 {
+            return None;
+        }
+
+        let find = |title: &str| titles.iter().position(|t| t == title);
+        let columns = match (find("name"), find("id"), find("version")) {
+            (Some(name), Some(id), Some(version)) => Columns {
+                name,
+                id,
+                version,
+                available: find("available"),
+                source: find("source"),
+                starts,
+            },
+            // Localized headers: winget's column order
+            _ => Columns {
+                name: 0,
+                id: 1,
+                version: 2,
+                available: (starts.len() >= 5).then_some(3),
+                source: (starts.len() >= 4).then_some(starts.len() - 1),
+                starts,
+            },
+        };
+        Some(columns)
+    }
+
+    fn cell(&self, row: &[char], column: usize) -> String {
+        let start = self.starts[column].min(row.len());
+        let end = self
+            .starts
+            .get(column + 1)
+            .map_or(row.len(), |&end| end.min(row.len()));
+        row[start..end]
+            .iter()
+            .collect::<String>()
+            .trim()
+            .to_string()
+    }
+
+    fn package(&self, line: &str) -> Option<Package> {
+        let row: Vec<char> = line.chars().collect();
+        let optional = |column: Option<usize>| {
+            column
+                .map(|c| self.cell(&row, c))
+                .filter(|value| !value.is_empty())
+        };
+
+        let package = Package {
+            name: self.cell(&row, self.name),
+            id: self.cell(&row, self.id),
+            version: self.cell(&row, self.version),
+            available: optional(self.available),
+            source: optional(self.source),
+        };
+        let valid = !package.name.is_empty()
+            && !package.id.is_empty()
+            && !package.id.contains(char::is_whitespace)
+            && !package.version.is_empty()
+            && package
+                .available
+                .as_ref()
+                .is_none_or(|v| !v.contains(char::is_whitespace))
+            && !line.contains(char::is_control);
+        valid.then_some(package)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const LIST_OUTPUT: &str = "   - \r   \\ \r\
+Name                             Id                             Version        Available Source
+---------------------------------------------------------------------------------------------------
+Microsoft Visual Studio Code     Microsoft.VisualStudioCode     1.94.2         1.95.0    winget
+Git                              Git.Git                        2.47.0                   winget
+Microsoft Edge                   Microsoft.Edge                 130.0.2849.46            winget
+Contoso Legacy Tool              ARP\\Machine\\X64\\ContosoTool    < 4.1
+";
+
+    const UPGRADE_OUTPUT: &str = "\
+Name                 Id                   Version   Available Source
+----------------------------------------------------------------------
+7-Zip 24.07 (x64)    7zip.7zip            24.07     24.08     winget
+Node.js              OpenJS.NodeJS.LTS    20.18.0   20.18.1   winget
+2 upgrades available.
+
+The following packages have an upgrade available, but require explicit targeting for upgrade:
+Name     Id              Version Available Source
+---------------------------------------------------
+Discord  Discord.Discord 1.0.9   1.0.9166  winget
+";
+
+    #[test]
+    fn test_list() {
+        let packages = packages(LIST_OUTPUT);
+        assert_eq!(packages.len(), 4);
+        assert_eq!(packages[0].name, "Microsoft Visual Studio Code");
+        assert_eq!(packages[0].id, "Microsoft.VisualStudioCode");
+        assert_eq!(packages[0].available.as_deref(), Some("1.95.0"));
+        assert_eq!(packages[1].available, None);
+        assert_eq!(packages[2].source.as_deref(), Some("winget"));
+        assert_eq!(packages[3].version, "< 4.1");
+        assert_eq!(packages[3].source, None);
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let packages = packages(UPGRADE_OUTPUT);
+        let ids: Vec<&str> = packages.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["7zip.7zip", "OpenJS.NodeJS.LTS", "Discord.Discord"]);
+        assert_eq!(packages[2].available.as_deref(), Some("1.0.9166"));
+        assert!(packages.iter().all(|p| p.available.is_some()));
+    }
+
+    #[test]
+    fn test_localized_header() {
+        let output = "\
+Nom      ID            Version Disponible Source
+------------------------------------------------
+Git      Git.Git       2.46.0  2.47.0     winget
+";
+        let packages = packages(output);
+        assert_eq!(packages[0].id, "Git.Git");
+        assert_eq!(packages[0].available.as_deref(), Some("2.47.0"));
+    }
+
+    proptest! {
+        #[test]
+        fn never_panics(output in "(\\PC|[\\r\\n-]){0,400}") {
+            packages(&output);
+        }
+
+        #[test]
+        fn damaged_rows_are_valid_or_dropped(
+            line in 0..10usize,
+            cut in 0..80usize,
+            junk in "\\PC{0,12}",
+        ) {
+            let mut lines: Vec<String> = UPGRADE_OUTPUT.lines().map(String::from).collect();
+            let target = &mut lines[line];
+            let cut = target.char_indices().nth(cut).map_or(target.len(), |(i, _)| i);
+            target.insert_str(cut, &junk);
+
+            for package in packages(&lines.join("\n")) {
+                prop_assert!(!package.name.is_empty());
+                prop_assert!(!package.id.is_empty() && !package.id.contains(' '));
+                prop_assert!(!package.version.is_empty());
+            }
+        }
+    }
+}
@@ -751,7 +751,13 @@ function New-Directories {
 function Get-Binary {
     Write-Step "Downloading NanoLink Agent"
 
-    $arch = if ([Environment]::Is64BitOperatingSystem) { "x86_64" } else { "x86" }
+    $arch = if ($env:PROCESSOR_ARCHITECTURE -eq "ARM64" -or $env:PROCESSOR_ARCHITEW6432 -eq "ARM64") {
+        "aarch64"
+    } elseif ([Environment]::Is64BitOperatingSystem) {
+        "x86_64"
+    } else {
+        "x86"
+    }
     $downloadUrl = "https://github.com/$Script:GitHubRepo/releases/latest/download/nanolink-agent-windows-$arch.exe"
     $binaryPath = Join-Path $Script:InstallDir $Script:BinaryName
 
@@ -1,8 +1,11 @@
 use parking_lot::RwLock;
+use prost::Message;
 use std::collections::VecDeque;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 
 use crate::proto::Metrics;
+use crate::telemetry::telemetry;
 
 /// Thread-safe Ring Buffer for caching metrics data
 ///
@@ -10,11 +13,24 @@ use crate::proto::Metrics;
 /// When the network is disconnected, data continues to be collected
 /// and stored in this buffer. Upon reconnection, buffered data can
 /// be synced to the server.
+///
+/// Entries are ordered and synced by their monotonic `sequence`; wall-clock
+/// timestamps can jump when NTP steps the clock and are for display only.
+///
+/// Entries are stored as `Arc<Metrics>` so snapshots only bump reference
+/// counts instead of deep-cloning every sample.
+///
+/// Capacity is counted in entries; an optional byte budget additionally
+/// bounds memory by the approximate (encoded) size of the buffered samples.
 pub struct RingBuffer {
-    buffer: RwLock<VecDeque<Metrics>>,
+    buffer: RwLock<VecDeque<Arc<Metrics>>>,
     capacity: usize,
-    /// Timestamp of the last successfully synced metrics
-    last_sync_timestamp: AtomicU64,
+    /// Optional budget for the encoded size of all entries
+    max_bytes: Option<usize>,
+    /// Encoded size of all buffered entries
+    current_bytes: AtomicUsize,
+    /// Sequence number of the last successfully synced metrics
+    last_sync_sequence: AtomicU64,
 }
 
 #[allow(dead_code)]
@@ -24,37 +40,58 @@ impl RingBuffer {
         Self {
             buffer: RwLock::new(VecDeque::with_capacity(capacity)),
             capacity,
-            last_sync_timestamp: AtomicU64::new(0),
+            max_bytes: None,
+            current_bytes: AtomicUsize::new(0),
+            last_sync_sequence: AtomicU64::new(0),
         }
     }
 
+    /// Limit the buffer to an approximate byte budget (`None` = unlimited)
+    pub fn with_max_bytes(mut self, max_bytes: Option<usize>) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
     /// Push a new metrics entry into the buffer
-    /// If the buffer is full, the oldest entry will be removed
+    /// If the buffer is full (by entries or bytes), the oldest entries are removed.
+    /// The newest entry is always kept, even if it alone exceeds the byte budget.
     pub fn push(&self, metrics: Metrics) {
+        let size = metrics.encoded_len();
+        let metrics = Arc::new(metrics);
         let mut buffer = self.buffer.write();
-        if buffer.len() >= self.capacity {
-            buffer.pop_front();
+        let mut bytes = self.current_bytes.load(Ordering::Relaxed);
+
+        while !buffer.is_empty()
+            && (buffer.len() >= self.capacity
+                || self.max_bytes.is_some_and(|max| bytes + size > max))
+        {
+            if let Some(evicted) = buffer.pop_front() {
+                bytes -= evicted.encoded_len();
+                telemetry().record_eviction();
+            }
         }
+
         buffer.push_back(metrics);
+        self.current_bytes.store(bytes + size, Ordering::Relaxed);
     }
 
     /// Get the latest metrics entry
-    pub fn latest(&self) -> Option<Metrics> {
+    pub fn latest(&self) -> Option<Arc<Metrics>> {
         self.buffer.read().back().cloned()
     }
 
-    /// Get all metrics since the given timestamp
-    pub fn get_since(&self, timestamp: u64) -> Vec<Metrics> {
+    /// Get all metrics after the given sequence number
+    pub fn get_since(&self, sequence: u64) -> Vec<Arc<Metrics>> {
         self.buffer
             .read()
             .iter()
-            .filter(|m| m.timestamp > timestamp)
+            .filter(|m| m.sequence > sequence)
             .cloned()
             .collect()
     }
 
     /// Get all buffered metrics
-    pub fn get_all(&self) -> Vec<Metrics> {
+    pub fn get_all(&self) -> Vec<Arc<Metrics>> {
         self.buffer.read().iter().cloned().collect()
     }
 
@@ -71,6 +108,7 @@ impl RingBuffer {
     /// Clear all buffered data
     pub fn clear(&self) {
         self.buffer.write().clear();
+        self.current_bytes.store(0, Ordering::Relaxed);
     }
 
     /// Get the oldest timestamp in the buffer
@@ -83,52 +121,70 @@ impl RingBuffer {
         self.buffer.read().back().map(|m| m.timestamp)
     }
 
+    /// Get the oldest sequence number in the buffer
+    pub fn oldest_sequence(&self) -> Option<u64> {
+        self.buffer.read().front().map(|m| m.sequence)
+    }
+
+    /// Get the newest sequence number in the buffer
+    pub fn newest_sequence(&self) -> Option<u64> {
+        self.buffer.read().back().map(|m| m.sequence)
+    }
+
     /// Get buffer capacity
     pub fn capacity(&self) -> usize {
         self.capacity
     }
 
-    /// Get buffer usage as percentage
+    /// Get the byte budget, if configured
+    pub fn max_bytes(&self) -> Option<usize> {
+        self.max_bytes
+    }
+
+    /// Get the approximate encoded size of all buffered entries
+    pub fn current_bytes(&self) -> usize {
+        self.current_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Get buffer usage as percentage (the higher of entry and byte usage)
     pub fn usage_percent(&self) -> f64 {
         let len = self.buffer.read().len();
-        (len as f64 / self.capacity as f64) * 100.0
+        let entries = (len as f64 / self.capacity as f64) * 100.0;
+        match self.max_bytes {
+            Some(max) if max > 0 => entries.max((self.current_bytes() as f64 / max as f64) * 100.0),
+            _ => entries,
+        }
     }
 
-    /// Get the last sync timestamp
-    pub fn get_last_sync_timestamp(&self) -> u64 {
-        self.last_sync_timestamp.load(Ordering::Relaxed)
+    /// Get the last synced sequence number
+    pub fn get_last_sync_sequence(&self) -> u64 {
+        self.last_sync_sequence.load(Ordering::Relaxed)
     }
 
-    /// Update the last sync timestamp
-    pub fn set_last_sync_timestamp(&self, timestamp: u64) {
-        self.last_sync_timestamp.store(timestamp, Ordering::Relaxed);
+    /// Update the last synced sequence number
+    pub fn set_last_sync_sequence(&self, sequence: u64) {
+        self.last_sync_sequence.store(sequence, Ordering::Relaxed);
     }
 
-    /// Get all unsynced metrics (metrics with timestamp > last_sync_timestamp)
-    pub fn get_unsynced(&self) -> Vec<Metrics> {
-        let last_sync = self.last_sync_timestamp.load(Ordering::Relaxed);
-        self.buffer
-            .read()
-            .iter()
-            .filter(|m| m.timestamp > last_sync)
-            .cloned()
-            .collect()
+    /// Get all unsynced metrics (metrics with sequence > last_sync_sequence)
+    pub fn get_unsynced(&self) -> Vec<Arc<Metrics>> {
+        self.get_since(self.get_last_sync_sequence())
     }
 
     /// Get unsynced metrics count
     pub fn unsynced_count(&self) -> usize {
-        let last_sync = self.last_sync_timestamp.load(Ordering::Relaxed);
+        let last_sync = self.get_last_sync_sequence();
         self.buffer
             .read()
             .iter()
-            .filter(|m| m.timestamp > last_sync)
+            .filter(|m| m.sequence > last_sync)
             .count()
     }
 
-    /// Mark all current data as synced (set last_sync_timestamp to newest)
+    /// Mark all current data as synced (set last_sync_sequence to newest)
     pub fn mark_all_synced(&self) {
-        if let Some(ts) = self.newest_timestamp() {
-            self.last_sync_timestamp.store(ts, Ordering::Relaxed);
+        if let Some(seq) = self.newest_sequence() {
+            self.last_sync_sequence.store(seq, Ordering::Relaxed);
         }
     }
 }
@@ -138,8 +194,13 @@ mod tests {
     use super::*;
 
     fn create_test_metrics(timestamp: u64) -> Metrics {
+        create_test_metrics_at(timestamp, timestamp)
+    }
+
+    fn create_test_metrics_at(sequence: u64, timestamp: u64) -> Metrics {
         Metrics {
             timestamp,
+            sequence,
             cpu: None,
             memory: None,
             disks: vec![],
@@ -152,6 +213,18 @@ mod tests {
             is_initial: false,
             metrics_type: 0,
             user_sessions: vec![],
+            agent_id: String::new(),
+            chunk: None,
+            custom_metrics: vec![],
+            sensors: vec![],
+            routing: None,
+            process_network: vec![],
+            public_ip: String::new(),
+            failed_logins: None,
+            cgroups: vec![],
+            printers: vec![],
+            ssh_keys: vec![],
+            kernel_modules: None,
         }
     }
 
@@ -184,8 +257,26 @@ mod tests {
 
         let since_3 = buffer.get_since(3);
         assert_eq!(since_3.len(), 2);
-        assert_eq!(since_3[0].timestamp, 4);
-        assert_eq!(since_3[1].timestamp, 5);
+        assert_eq!(since_3[0].sequence, 4);
+        assert_eq!(since_3[1].sequence, 5);
+    }
+
+    #[test]
+    fn test_unsynced_survives_clock_step_back() {
+        let buffer = RingBuffer::new(5);
+
+        buffer.push(create_test_metrics_at(1, 10_000));
+        buffer.push(create_test_metrics_at(2, 11_000));
+        buffer.mark_all_synced();
+
+        // Wall clock stepped back by NTP after the last sync
+        buffer.push(create_test_metrics_at(3, 5_000));
+        buffer.push(create_test_metrics_at(4, 6_000));
+
+        let unsynced = buffer.get_unsynced();
+        assert_eq!(buffer.unsynced_count(), 2);
+        assert_eq!(unsynced[0].sequence, 3);
+        assert_eq!(unsynced[1].sequence, 4);
     }
 
     #[test]
@@ -199,4 +290,42 @@ mod tests {
 
         assert_eq!(buffer.latest().unwrap().timestamp, 2);
     }
+
+    #[test]
+    fn test_byte_budget_evicts_oldest() {
+        let entry_size = create_test_metrics(1).encoded_len();
+        let buffer = RingBuffer::new(100).with_max_bytes(Some(entry_size * 3));
+
+        for i in 1..=5 {
+            buffer.push(create_test_metrics(i));
+        }
+
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.oldest_sequence(), Some(3));
+        assert_eq!(buffer.current_bytes(), entry_size * 3);
+
+        buffer.clear();
+        assert_eq!(buffer.current_bytes(), 0);
+    }
+
+    #[test]
+    fn test_byte_budget_keeps_newest() {
+        let buffer = RingBuffer::new(100).with_max_bytes(Some(1));
+
+        buffer.push(create_test_metrics(1));
+        buffer.push(create_test_metrics(2));
+
+        assert_eq!(buffer.len(), 1);
+        assert_eq!(buffer.latest().unwrap().sequence, 2);
+    }
+
+    #[test]
+    fn test_snapshots_share_entries() {
+        let buffer = RingBuffer::new(3);
+        buffer.push(create_test_metrics(1));
+
+        let a = buffer.get_all();
+        let b = buffer.get_all();
+        assert!(Arc::ptr_eq(&a[0], &b[0]));
+    }
 }
@@ -0,0 +1,366 @@
+//! Agent-side anomaly detection
+//!
+//! With `collector.anomaly.enabled`, CPU usage, memory usage, disk
+//! throughput and network throughput are compared against an exponentially
+//! weighted baseline (mean and variance) on every run. With `seasonal`, each
+//! hour of the day keeps its own baseline once it has seen `warmup_samples`,
+//! so a nightly backup doesn't look unusual every night.
+//!
+//! A metric whose z-score stays above `threshold` for `consecutive` runs
+//! starts an anomaly; it ends once the score drops below half the threshold.
+//! Both transitions are published as `anomaly` log events carrying the
+//! observed value and the baseline. Scores are also sent as custom metrics
+//! (`anomaly.<metric>`, collector "anomaly").
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use chrono::Timelike;
+use sysinfo::{Disks, Networks};
+use tracing::info;
+
+use crate::config::{AnomalyConfig, CollectorConfig};
+use crate::proto::{CustomMetric, LogBatch, LogEntry};
+
+use super::registry::{CollectContext, Collector, Fragment, IntervalClass};
+use super::syslog;
+
+/// Metrics watched, with the smallest standard deviation assumed for each so
+/// an idle, flat baseline doesn't turn every blip into an anomaly
+const METRICS: &[(&str, f64)] = &[
+    ("cpu_percent", 2.0),
+    ("memory_percent", 1.0),
+    ("disk_read_bytes_sec", 256.0 * 1024.0),
+    ("disk_write_bytes_sec", 256.0 * 1024.0),
+    ("net_rx_bytes_sec", 64.0 * 1024.0),
+    ("net_tx_bytes_sec", 64.0 * 1024.0),
+];
+
+/// Exponentially weighted mean and variance
+#[derive(Debug, Clone, Copy, Default)]
+struct Baseline {
+    mean: f64,
+    variance: f64,
+    samples: u64,
+}
+
+impl Baseline {
+    fn update(&mut self, value: f64, alpha: f64) {
+        if self.samples == 0 {
+            self.mean = value;
+        } else {
+            let diff = value - self.mean;
+            let step = alpha * diff;
+            self.mean += step;
+            self.variance = (1.0 - alpha) * (self.variance + diff * step);
+        }
+        self.samples += 1;
+    }
+
+    fn std_dev(&self, floor: f64) -> f64 {
+        self.variance.sqrt().max(floor)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Detector {
+    global: Baseline,
+    hourly: [Baseline; 24],
+    /// Consecutive runs above the threshold
+    over: u32,
+    firing: bool,
+}
+
+/// Outcome of one observation
+#[derive(Debug, PartialEq)]
+enum Transition {
+    None,
+    Started,
+    Ended,
+}
+
+impl Detector {
+    /// Baseline to compare against at `hour`
+    fn baseline(&self, hour: usize, seasonal: bool, warmup: u64) -> Baseline {
+        if seasonal && self.hourly[hour].samples >= warmup {
+            self.hourly[hour]
+        } else {
+            self.global
+        }
+    }
+
+    /// Score `value`, then fold it into the baselines
+    fn observe(
+        &mut self,
+        value: f64,
+        hour: usize,
+        floor: f64,
+        config: &AnomalyConfig,
+    ) -> (f64, Baseline, Transition) {
+        let baseline = self.baseline(hour, config.seasonal, config.warmup_samples);
+        let score = if baseline.samples >= config.warmup_samples {
+            (value - baseline.mean) / baseline.std_dev(floor)
+        } else {
+            0.0
+        };
+
+        let mut transition = Transition::None;
+        if score.abs() > config.threshold {
+            self.over += 1;
+            if !self.firing && self.over >= config.consecutive {
+                self.firing = true;
+                transition = Transition::Started;
+            }
+        } else {
+            self.over = 0;
+            if self.firing && score.abs() < config.threshold / 2.0 {
+                self.firing = false;
+                transition = Transition::Ended;
+            }
+        }
+
+        // Learn slowly while anomalous so a lasting shift is eventually
+        // accepted without a spike dragging the baseline along
+        let alpha = if self.firing || self.over > 0 {
+            config.alpha / 4.0
+        } else {
+            config.alpha
+        };
+        self.global.update(value, alpha);
+        self.hourly[hour].update(value, alpha);
+        (score, baseline, transition)
+    }
+}
+
+pub struct AnomalyCollector {
+    config: AnomalyConfig,
+    disks: Disks,
+    networks: Networks,
+    /// Cumulative disk read/write and network rx/tx bytes
+    prev_totals: Option<(Instant, [u64; 4])>,
+    detectors: HashMap<&'static str, Detector>,
+}
+
+impl AnomalyCollector {
+    pub fn new(config: AnomalyConfig) -> Self {
+        Self {
+            config,
+            disks: Disks::new_with_refreshed_list(),
+            networks: Networks::new_with_refreshed_list(),
+            prev_totals: None,
+            detectors: HashMap::new(),
+        }
+    }
+
+    fn totals(&mut self) -> [u64; 4] {
+        self.disks.refresh(true);
+        self.networks.refresh(true);
+        let (read, written) = self.disks.list().iter().fold((0, 0), |(r, w), d| {
+            let usage = d.usage();
+            (r + usage.total_read_bytes, w + usage.total_written_bytes)
+        });
+        let (rx, tx) = self
+            .networks
+            .iter()
+            .filter(|(name, _)| !name.starts_with("lo"))
+            .fold((0, 0), |(rx, tx), (_, data)| {
+                (rx + data.total_received(), tx + data.total_transmitted())
+            });
+        [read, written, rx, tx]
+    }
+
+    /// Throughput rates since the previous run, `None` on the first run
+    fn rates(&mut self) -> Option<[f64; 4]> {
+        let now = Instant::now();
+        let totals = self.totals();
+        let prev = self.prev_totals.replace((now, totals));
+        let (then, before) = prev?;
+        let secs = now.duration_since(then).as_secs_f64();
+        if secs <= 0.0 {
+            return None;
+        }
+        let mut rates = [0.0; 4];
+        for (rate, (now, before)) in rates.iter_mut().zip(totals.iter().zip(before)) {
+            *rate = now.saturating_sub(before) as f64 / secs;
+        }
+        Some(rates)
+    }
+
+    fn evaluate(
+        &mut self,
+        values: &[(&'static str, f64)],
+        hour: usize,
+    ) -> (Vec<CustomMetric>, Vec<LogEntry>) {
+        let mut metrics = Vec::new();
+        let mut events = Vec::new();
+        for &(name, value) in values {
+            let floor = METRICS
+                .iter()
+                .find(|(m, _)| *m == name)
+                .map_or(1.0, |(_, f)| *f);
+            let detector = self.detectors.entry(name).or_default();
+            let (score, baseline, transition) = detector.observe(value, hour, floor, &self.config);
+
+            metrics.push(CustomMetric {
+                collector: "anomaly".to_string(),
+                name: format!("anomaly.{name}"),
+                value: score,
+                ..Default::default()
+            });
+            if transition != Transition::None {
+                events.push(anomaly_event(
+                    name,
+                    value,
+                    &baseline,
+                    floor,
+                    score,
+                    &transition,
+                ));
+            }
+        }
+        (metrics, events)
+    }
+}
+
+impl Collector for AnomalyCollector {
+    fn name(&self) -> &'static str {
+        "anomaly"
+    }
+
+    fn interval(&self, _config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Periodic(Duration::from_millis(self.config.interval_ms))
+    }
+
+    fn collect(&mut self, ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        let Some([read, write, rx, tx]) = self.rates() else {
+            return Ok(Fragment::Custom(Vec::new()));
+        };
+        let system = ctx.system;
+        let memory_percent = if system.total_memory() > 0 {
+            system.used_memory() as f64 / system.total_memory() as f64 * 100.0
+        } else {
+            0.0
+        };
+        let values = [
+            ("cpu_percent", f64::from(system.global_cpu_usage())),
+            ("memory_percent", memory_percent),
+            ("disk_read_bytes_sec", read),
+            ("disk_write_bytes_sec", write),
+            ("net_rx_bytes_sec", rx),
+            ("net_tx_bytes_sec", tx),
+        ];
+
+        let hour = chrono::Local::now().hour() as usize;
+        let (metrics, events) = self.evaluate(&values, hour);
+        if !events.is_empty() {
+            syslog::publish(LogBatch {
+                source: "anomaly".to_string(),
+                entries: events,
+                dropped: 0,
+            });
+        }
+        Ok(Fragment::Custom(metrics))
+    }
+}
+
+fn anomaly_event(
+    metric: &str,
+    observed: f64,
+    baseline: &Baseline,
+    floor: f64,
+    score: f64,
+    transition: &Transition,
+) -> LogEntry {
+    let state = match transition {
+        Transition::Ended => "resolved",
+        _ => "firing",
+    };
+    let direction = if score >= 0.0 { "high" } else { "low" };
+    let message = match transition {
+        Transition::Ended => format!("{metric} back to normal: {observed:.1}"),
+        _ => format!(
+            "{metric} unusually {direction}: {observed:.1} (baseline {:.1} ± {:.1})",
+            baseline.mean,
+            baseline.std_dev(floor)
+        ),
+    };
+    info!("Anomaly {}: {}", state, message);
+
+    LogEntry {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        level: if state == "firing" { "warning" } else { "info" }.to_string(),
+        source: "anomaly".to_string(),
+        message,
+        metadata: HashMap::from([
+            ("metric".to_string(), metric.to_string()),
+            ("state".to_string(), state.to_string()),
+            ("observed".to_string(), format!("{observed:.3}")),
+            ("baseline".to_string(), format!("{:.3}", baseline.mean)),
+            (
+                "stddev".to_string(),
+                format!("{:.3}", baseline.std_dev(floor)),
+            ),
+            ("score".to_string(), format!("{score:.2}")),
+        ]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AnomalyConfig {
+        AnomalyConfig {
+            enabled: true,
+            warmup_samples: 10,
+            consecutive: 2,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_spike_starts_and_ends_anomaly() {
+        let config = config();
+        let mut detector = Detector::default();
+        for i in 0..50 {
+            let value = 20.0 + (i % 3) as f64;
+            assert_eq!(detector.observe(value, 0, 2.0, &config).2, Transition::None);
+        }
+
+        // One outlier isn't enough, a sustained one is
+        assert_eq!(detector.observe(95.0, 0, 2.0, &config).2, Transition::None);
+        let (score, baseline, transition) = detector.observe(95.0, 0, 2.0, &config);
+        assert_eq!(transition, Transition::Started);
+        assert!(score > config.threshold);
+        assert!((baseline.mean - 21.0).abs() < 2.0);
+
+        assert_eq!(detector.observe(21.0, 0, 2.0, &config).2, Transition::Ended);
+    }
+
+    #[test]
+    fn test_seasonal_baseline() {
+        let config = AnomalyConfig {
+            seasonal: true,
+            ..config()
+        };
+        let mut detector = Detector::default();
+        // Busy at 02:00 every night, quiet otherwise
+        for _ in 0..30 {
+            detector.observe(10.0, 1, 2.0, &config);
+            detector.observe(90.0, 2, 2.0, &config);
+        }
+        assert_eq!(detector.observe(90.0, 2, 2.0, &config).2, Transition::None);
+        assert_eq!(detector.observe(90.0, 2, 2.0, &config).2, Transition::None);
+
+        let mut collector = AnomalyCollector {
+            config: config.clone(),
+            disks: Disks::new(),
+            networks: Networks::new(),
+            prev_totals: None,
+            detectors: HashMap::from([("cpu_percent", detector)]),
+        };
+        let (metrics, _) = collector.evaluate(&[("cpu_percent", 90.0)], 1);
+        assert_eq!(metrics[0].name, "anomaly.cpu_percent");
+        assert!(metrics[0].value > config.threshold);
+    }
+}
@@ -0,0 +1,393 @@
+//! Boot session tracking
+//!
+//! Detects once per agent process how the previous boot ended, so hosts that
+//! silently crashed and came back can be told apart from planned reboots.
+//!
+//! - Linux: pstore crash dumps (kernel panic), then the tail of the previous
+//!   boot's journal, then the wtmp reboot/shutdown records (`last -x`)
+//! - Windows: System log events 1001 (BugCheck), 41 (Kernel-Power) and 6008
+//!   (unexpected shutdown) logged since this boot
+//! - macOS: panic reports in `/Library/Logs/DiagnosticReports`; clean and
+//!   unclean shutdowns are not distinguished
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+#[cfg(not(target_os = "macos"))]
+use std::time::Duration;
+
+use tracing::{debug, info, warn};
+
+use crate::proto::{BootInfo, ShutdownKind};
+#[cfg(not(target_os = "macos"))]
+use crate::utils::safe_command::exec_with_timeout;
+
+#[cfg(not(target_os = "macos"))]
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Journal messages that only appear when systemd shuts the host down
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+const CLEAN_SHUTDOWN_MARKERS: &[&str] = &[
+    "Reached target System Power Off",
+    "Reached target System Reboot",
+    "Reached target System Halt",
+    "Reached target Power-Off",
+    "Reached target Reboot",
+    "systemd-shutdown",
+    "Journal stopped",
+];
+
+struct BootState {
+    info: BootInfo,
+    /// First agent start since the host booted
+    first_start: bool,
+}
+
+static STATE: OnceLock<BootState> = OnceLock::new();
+
+fn state() -> &'static BootState {
+    STATE.get_or_init(|| {
+        let info = detect();
+        let first_start = record_boot(&info.boot_id);
+        debug!(
+            "Boot {} (previous shutdown: {:?}, first agent start: {})",
+            info.boot_id,
+            info.previous_shutdown(),
+            first_start
+        );
+        BootState { info, first_start }
+    })
+}
+
+/// Current boot session and how the previous one ended
+pub fn info() -> BootInfo {
+    state().info.clone()
+}
+
+/// The boot info if this is the first agent start after an unclean shutdown.
+///
+/// Layered collectors send it once per connection; servers deduplicate by
+/// `boot_id`.
+pub fn unclean_boot() -> Option<BootInfo> {
+    let state = state();
+    let unclean = matches!(
+        state.info.previous_shutdown(),
+        ShutdownKind::ShutdownUnclean | ShutdownKind::ShutdownPanic
+    );
+    (state.first_start && unclean).then(|| state.info.clone())
+}
+
+fn state_file() -> PathBuf {
+    if cfg!(test) {
+        return std::env::temp_dir().join("nanolink-test-last_boot");
+    }
+    #[cfg(windows)]
+    {
+        let base = std::env::var("ProgramData").unwrap_or_else(|_| "C:\\ProgramData".to_string());
+        PathBuf::from(base).join("nanolink").join("last_boot")
+    }
+    #[cfg(unix)]
+    {
+        PathBuf::from("/var/lib/nanolink/last_boot")
+    }
+}
+
+/// Store the current boot ID; returns true if it differs from the stored one
+fn record_boot(boot_id: &str) -> bool {
+    let path = state_file();
+    let previous = std::fs::read_to_string(&path).unwrap_or_default();
+    if previous.trim() == boot_id {
+        return false;
+    }
+
+    let written = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&path, boot_id));
+    if let Err(e) = written {
+        warn!("Failed to record boot ID in {:?}: {}", path, e);
+    }
+    true
+}
+
+fn detect() -> BootInfo {
+    let boot_time = sysinfo::System::boot_time();
+    let mut info = BootInfo {
+        boot_id: boot_id().unwrap_or_else(|| boot_time.to_string()),
+        boot_time,
+        ..Default::default()
+    };
+    detect_previous_shutdown(&mut info);
+
+    if info.previous_shutdown() != ShutdownKind::ShutdownClean
+        && info.previous_shutdown() != ShutdownKind::ShutdownUnknown
+    {
+        info!(
+            "Previous shutdown was not clean: {:?} {}",
+            info.previous_shutdown(),
+            info.last_crash_reason
+        );
+    }
+    info
+}
+
+#[cfg(target_os = "linux")]
+fn boot_id() -> Option<String> {
+    std::fs::read_to_string("/proc/sys/kernel/random/boot_id")
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn boot_id() -> Option<String> {
+    crate::utils::safe_command::run_command("sysctl", &["-n", "kern.bootsessionuuid"])
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn boot_id() -> Option<String> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn detect_previous_shutdown(info: &mut BootInfo) {
+    use std::process::Command;
+
+    // Crash dumps saved by the kernel before the panic
+    if let Some((time, reason)) = newest_crash_file("/sys/fs/pstore", |_| true, "Kernel panic") {
+        info.set_previous_shutdown(ShutdownKind::ShutdownPanic);
+        info.last_crash_time = time;
+        info.last_crash_reason = reason;
+        return;
+    }
+
+    // Requires a persistent journal
+    let mut cmd = Command::new("journalctl");
+    cmd.args([
+        "-b",
+        "-1",
+        "-n",
+        "30",
+        "-o",
+        "short-unix",
+        "-q",
+        "--no-pager",
+    ]);
+    if let Some(output) = exec_with_timeout(cmd, COMMAND_TIMEOUT)
+        && output.status.success()
+        && let Some((kind, last)) = classify_journal(&String::from_utf8_lossy(&output.stdout))
+    {
+        info.set_previous_shutdown(kind);
+        if kind == ShutdownKind::ShutdownUnclean {
+            info.last_crash_time = last;
+            info.last_crash_reason = "journal ends without a shutdown".to_string();
+        }
+        return;
+    }
+
+    let mut cmd = Command::new("last");
+    cmd.args(["-x", "-n", "4", "reboot", "shutdown"]);
+    if let Some(output) = exec_with_timeout(cmd, COMMAND_TIMEOUT) {
+        let kind = classify_wtmp(&String::from_utf8_lossy(&output.stdout));
+        info.set_previous_shutdown(kind);
+        if kind == ShutdownKind::ShutdownUnclean {
+            info.last_crash_reason = "no shutdown record in wtmp".to_string();
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn detect_previous_shutdown(info: &mut BootInfo) {
+    // Panic reports are written on the first boot after the panic
+    if let Some((time, reason)) = newest_crash_file(
+        "/Library/Logs/DiagnosticReports",
+        |name| name.contains("panic"),
+        "panic(",
+    ) && time + 300 >= info.boot_time
+    {
+        info.set_previous_shutdown(ShutdownKind::ShutdownPanic);
+        info.last_crash_time = time;
+        info.last_crash_reason = reason;
+    }
+}
+
+#[cfg(windows)]
+fn detect_previous_shutdown(info: &mut BootInfo) {
+    use std::process::Command;
+
+    let script = format!(
+        "Get-WinEvent -FilterHashtable @{{LogName='System';Id=1001,41,6008;StartTime=[DateTimeOffset]::FromUnixTimeSeconds({}).LocalDateTime}} -ErrorAction SilentlyContinue \
+         | ForEach-Object {{ \"$($_.Id)|$([DateTimeOffset]::new($_.TimeCreated).ToUnixTimeSeconds())|$(($_.Message -split \"`n\")[0])\" }}",
+        info.boot_time
+    );
+    let mut cmd = Command::new("powershell");
+    cmd.args(["-NoProfile", "-Command", &script]);
+    if let Some(output) = exec_with_timeout(cmd, COMMAND_TIMEOUT) {
+        let (kind, time, reason) =
+            classify_windows_events(&String::from_utf8_lossy(&output.stdout));
+        info.set_previous_shutdown(kind);
+        info.last_crash_time = time;
+        info.last_crash_reason = reason;
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn detect_previous_shutdown(_info: &mut BootInfo) {}
+
+/// Newest crash file in `dir` and the first line containing `marker`
+#[cfg(unix)]
+fn newest_crash_file(
+    dir: &str,
+    filter: impl Fn(&str) -> bool,
+    marker: &str,
+) -> Option<(u64, String)> {
+    let (path, modified) = std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|e| e.file_name().to_str().is_some_and(&filter))
+        .filter_map(|e| Some((e.path(), e.metadata().ok()?.modified().ok()?)))
+        .max_by_key(|(_, modified)| *modified)?;
+
+    let time = modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let reason = std::fs::read(&path)
+        .ok()
+        .and_then(|content| {
+            String::from_utf8_lossy(&content)
+                .lines()
+                .find(|l| l.contains(marker))
+                .map(|l| l.trim().chars().take(200).collect())
+        })
+        .unwrap_or_default();
+    Some((time, reason))
+}
+
+/// Classify the tail of the previous boot's journal (`-o short-unix`).
+///
+/// Returns the shutdown kind and the timestamp of the last entry, or None
+/// if the previous boot is not in the journal.
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn classify_journal(output: &str) -> Option<(ShutdownKind, u64)> {
+    let last = output.lines().rev().find(|l| !l.trim().is_empty())?;
+    let last_time = last
+        .split_whitespace()
+        .next()
+        .and_then(|t| t.parse::<f64>().ok())
+        .map(|t| t as u64)
+        .unwrap_or(0);
+
+    let clean = output
+        .lines()
+        .any(|l| CLEAN_SHUTDOWN_MARKERS.iter().any(|m| l.contains(m)));
+    let kind = if clean {
+        ShutdownKind::ShutdownClean
+    } else {
+        ShutdownKind::ShutdownUnclean
+    };
+    Some((kind, last_time))
+}
+
+/// Classify `last -x reboot shutdown` output (newest first): the current
+/// boot's record must be preceded by a shutdown record
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn classify_wtmp(output: &str) -> ShutdownKind {
+    let mut records = output
+        .lines()
+        .filter_map(|l| l.split_whitespace().next())
+        .filter(|w| *w == "reboot" || *w == "shutdown");
+
+    match (records.next(), records.next()) {
+        (Some("reboot"), Some("shutdown")) => ShutdownKind::ShutdownClean,
+        (Some("reboot"), Some("reboot")) => ShutdownKind::ShutdownUnclean,
+        _ => ShutdownKind::ShutdownUnknown,
+    }
+}
+
+/// Classify `id|unix time|message` lines of System log events since boot
+#[cfg_attr(not(any(test, windows)), allow(dead_code))]
+fn classify_windows_events(output: &str) -> (ShutdownKind, u64, String) {
+    let mut result = (ShutdownKind::ShutdownClean, 0, String::new());
+    for line in output.lines() {
+        let mut fields = line.trim().splitn(3, '|');
+        let (Some(id), Some(time), message) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        let time = time.parse().unwrap_or(0);
+        let message = message.unwrap_or_default().trim().to_string();
+
+        match id {
+            // BugCheck wins over the generic power-loss events
+            "1001" => return (ShutdownKind::ShutdownPanic, time, message),
+            "41" | "6008" if result.0 == ShutdownKind::ShutdownClean => {
+                result = (ShutdownKind::ShutdownUnclean, time, message);
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_journal() {
+        let clean = "1717000000.100000 web1 systemd[1]: Stopping nginx.service...\n\
+                     1717000002.500000 web1 systemd[1]: Reached target System Reboot.\n\
+                     1717000002.900000 web1 systemd-journald[300]: Journal stopped\n";
+        assert_eq!(
+            classify_journal(clean),
+            Some((ShutdownKind::ShutdownClean, 1717000002))
+        );
+
+        let crashed = "1717000000.100000 web1 kernel: nvme0: I/O timeout\n\
+                       1717000010.700000 web1 CRON[999]: (root) CMD (backup)\n";
+        assert_eq!(
+            classify_journal(crashed),
+            Some((ShutdownKind::ShutdownUnclean, 1717000010))
+        );
+
+        assert_eq!(classify_journal(""), None);
+    }
+
+    #[test]
+    fn test_classify_wtmp() {
+        let clean = "reboot   system boot  6.8.0-45-generic Mon Jun  3 08:00   still running\n\
+                     shutdown system down  6.8.0-45-generic Mon Jun  3 07:59 - 08:00  (00:00)\n\
+                     reboot   system boot  6.8.0-45-generic Sat Jun  1 10:00 - 07:59 (1+21:59)\n\
+                     \n\
+                     wtmp begins Sat Jun  1 09:00:00 2024\n";
+        assert_eq!(classify_wtmp(clean), ShutdownKind::ShutdownClean);
+
+        let crashed = "reboot   system boot  6.8.0-45-generic Mon Jun  3 08:00   still running\n\
+                       reboot   system boot  6.8.0-45-generic Sat Jun  1 10:00 - 08:00 (1+22:00)\n";
+        assert_eq!(classify_wtmp(crashed), ShutdownKind::ShutdownUnclean);
+
+        assert_eq!(
+            classify_wtmp("\nwtmp begins Sat Jun  1\n"),
+            ShutdownKind::ShutdownUnknown
+        );
+    }
+
+    #[test]
+    fn test_classify_windows_events() {
+        let (kind, time, reason) = classify_windows_events(
+            "6008|1717000100|The previous system shutdown at 3:02:11 AM was unexpected.\r\n\
+             41|1717000090|The system has rebooted without cleanly shutting down first.\r\n",
+        );
+        assert_eq!(kind, ShutdownKind::ShutdownUnclean);
+        assert_eq!(time, 1717000100);
+        assert!(reason.contains("unexpected"));
+
+        let (kind, _, reason) = classify_windows_events(
+            "41|1717000090|Kernel-Power\r\n1001|1717000095|The computer has rebooted from a bugcheck.\r\n",
+        );
+        assert_eq!(kind, ShutdownKind::ShutdownPanic);
+        assert!(reason.contains("bugcheck"));
+
+        assert_eq!(classify_windows_events("").0, ShutdownKind::ShutdownClean);
+    }
+}
@@ -0,0 +1,436 @@
+//! Camera and microphone usage detection
+//!
+//! Off by default (`collector.capture_devices.enabled`). Every run lists the
+//! processes that have a camera or microphone open. A process that starts or
+//! stops using one is published as a `capture_devices` log event, at warning
+//! level unless it is one of `expected_processes`. The number of processes
+//! using each device is also sent as custom metrics (`camera_in_use`,
+//! `microphone_in_use`, collector "capture_devices"), which works as a
+//! privacy indicator on a dashboard.
+//!
+//! - Linux: PipeWire capture streams from `pw-dump` in every user's session,
+//!   plus `/dev/video*` and ALSA capture devices (`/dev/snd/pcm*c`) held open
+//!   in `/proc/<pid>/fd`. Sound servers are skipped there, since they hold
+//!   the microphone on behalf of their clients.
+//! - Windows: the capability access manager's consent store, which marks an
+//!   app as using the device until `LastUsedTimeStop` is set. It has no PIDs.
+//! - macOS: not supported.
+
+use std::collections::{BTreeSet, HashMap};
+#[cfg(any(target_os = "linux", target_os = "windows"))]
+use std::process::Command;
+use std::time::Duration;
+
+use serde_json::Value;
+use tracing::info;
+
+use crate::config::{CaptureDeviceConfig, CollectorConfig};
+use crate::proto::{CustomMetric, LogBatch, LogEntry};
+#[cfg(any(target_os = "linux", target_os = "windows"))]
+use crate::utils::safe_command::exec_with_timeout;
+
+use super::registry::{CollectContext, Collector, Fragment, IntervalClass};
+use super::syslog;
+
+#[cfg(any(target_os = "linux", target_os = "windows"))]
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Processes that open capture devices for their clients
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+const SOUND_SERVERS: &[&str] = &["pipewire", "pipewire-pulse", "wireplumber", "pulseaudio"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Device {
+    Camera,
+    Microphone,
+}
+
+impl Device {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Camera => "camera",
+            Self::Microphone => "microphone",
+        }
+    }
+}
+
+/// One process using one device
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Usage {
+    device: Device,
+    process: String,
+    /// 0 where the platform doesn't say (Windows)
+    pid: u32,
+    user: String,
+}
+
+pub struct CaptureCollector {
+    config: CaptureDeviceConfig,
+    in_use: BTreeSet<Usage>,
+}
+
+impl CaptureCollector {
+    pub fn new(config: CaptureDeviceConfig) -> Self {
+        Self {
+            config,
+            in_use: BTreeSet::new(),
+        }
+    }
+
+    fn is_expected(&self, process: &str) -> bool {
+        let name = |s: &str| s.trim_end_matches(".exe").to_lowercase();
+        self.config
+            .expected_processes
+            .iter()
+            .any(|p| name(p) == name(process))
+    }
+
+    /// Record the current usage and return the processes that started and
+    /// stopped using a device since the previous run
+    fn update(&mut self, current: BTreeSet<Usage>) -> (Vec<Usage>, Vec<Usage>) {
+        let started = current.difference(&self.in_use).cloned().collect();
+        let stopped = self.in_use.difference(&current).cloned().collect();
+        self.in_use = current;
+        (started, stopped)
+    }
+
+    fn event(&self, usage: &Usage, started: bool) -> LogEntry {
+        let state = if started { "started" } else { "stopped" };
+        let level = if started && !self.is_expected(&usage.process) {
+            "warning"
+        } else {
+            "info"
+        };
+        let process = match usage.pid {
+            0 => usage.process.clone(),
+            pid => format!("{} (pid {})", usage.process, pid),
+        };
+        LogEntry {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            level: level.to_string(),
+            source: "capture_devices".to_string(),
+            message: format!("{process} {state} using the {}", usage.device.as_str()),
+            metadata: HashMap::from([
+                ("device".to_string(), usage.device.as_str().to_string()),
+                ("process".to_string(), usage.process.clone()),
+                ("pid".to_string(), usage.pid.to_string()),
+                ("user".to_string(), usage.user.clone()),
+                ("state".to_string(), state.to_string()),
+            ]),
+        }
+    }
+}
+
+impl Collector for CaptureCollector {
+    fn name(&self) -> &'static str {
+        "capture_devices"
+    }
+
+    fn interval(&self, _config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Periodic(Duration::from_millis(self.config.interval_ms))
+    }
+
+    fn collect(&mut self, _ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        let (started, stopped) = self.update(scan());
+        let entries: Vec<LogEntry> = started
+            .iter()
+            .map(|usage| self.event(usage, true))
+            .chain(stopped.iter().map(|usage| self.event(usage, false)))
+            .collect();
+        for entry in &entries {
+            info!("Capture device: {}", entry.message);
+        }
+        if !entries.is_empty() {
+            syslog::publish(LogBatch {
+                source: "capture_devices".to_string(),
+                entries,
+                dropped: 0,
+            });
+        }
+
+        let count = |device| self.in_use.iter().filter(|u| u.device == device).count();
+        Ok(Fragment::Custom(
+            [Device::Camera, Device::Microphone]
+                .into_iter()
+                .map(|device| CustomMetric {
+                    collector: "capture_devices".to_string(),
+                    name: format!("{}_in_use", device.as_str()),
+                    value: count(device) as f64,
+                    ..Default::default()
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// Capture device behind an `/proc/<pid>/fd` link target
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn device_of(target: &str) -> Option<Device> {
+    if target.starts_with("/dev/video") {
+        Some(Device::Camera)
+    } else if let Some(pcm) = target.strip_prefix("/dev/snd/pcmC") {
+        // pcmC<card>D<device>c is capture, ...p playback
+        pcm.ends_with('c').then_some(Device::Microphone)
+    } else {
+        None
+    }
+}
+
+/// Capture streams in `pw-dump` output
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn parse_pw_dump(json: &Value, user: &str) -> Vec<Usage> {
+    let Some(objects) = json.as_array() else {
+        return Vec::new();
+    };
+    objects
+        .iter()
+        .filter(|o| o["type"] == "PipeWire:Interface:Node" && o["info"]["state"] == "running")
+        .filter_map(|o| {
+            let props = &o["info"]["props"];
+            let device = match props["media.class"].as_str()? {
+                "Stream/Input/Audio" => Device::Microphone,
+                "Stream/Input/Video" => Device::Camera,
+                _ => return None,
+            };
+            let text = |key: &str| props[key].as_str().filter(|s| !s.is_empty());
+            // Numbers in recent versions, strings in older ones
+            let pid = match &props["application.process.id"] {
+                Value::Number(n) => n.as_u64().unwrap_or(0) as u32,
+                Value::String(s) => s.parse().unwrap_or(0),
+                _ => 0,
+            };
+            Some(Usage {
+                device,
+                process: text("application.process.binary")
+                    .or_else(|| text("application.name"))
+                    .or_else(|| text("node.name"))
+                    .unwrap_or("unknown")
+                    .to_string(),
+                pid,
+                user: text("application.process.user").unwrap_or(user).to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn scan() -> BTreeSet<Usage> {
+    use std::fs;
+
+    let user_name = |uid: u32| {
+        nix::unistd::User::from_uid(uid.into())
+            .ok()
+            .flatten()
+            .map_or_else(|| uid.to_string(), |u| u.name)
+    };
+    let mut in_use = BTreeSet::new();
+
+    // PipeWire runs per user session
+    if let Ok(sessions) = fs::read_dir("/run/user") {
+        for session in sessions.flatten() {
+            let runtime_dir = session.path();
+            if !runtime_dir.join("pipewire-0").exists() {
+                continue;
+            }
+            let Some(uid) = session.file_name().to_str().and_then(|s| s.parse().ok()) else {
+                continue;
+            };
+            let mut cmd = Command::new("pw-dump");
+            cmd.env("XDG_RUNTIME_DIR", &runtime_dir);
+            if let Some(json) = exec_with_timeout(cmd, COMMAND_TIMEOUT)
+                .and_then(|o| serde_json::from_slice::<Value>(&o.stdout).ok())
+            {
+                in_use.extend(parse_pw_dump(&json, &user_name(uid)));
+            }
+        }
+    }
+
+    // Direct V4L2 and ALSA users
+    let Ok(procs) = fs::read_dir("/proc") else {
+        return in_use;
+    };
+    for entry in procs.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        let devices: BTreeSet<Device> = fds
+            .flatten()
+            .filter_map(|fd| fs::read_link(fd.path()).ok())
+            .filter_map(|target| device_of(target.to_str()?))
+            .collect();
+        if devices.is_empty() {
+            continue;
+        }
+        let name = fs::read_to_string(entry.path().join("comm"))
+            .map(|c| c.trim().to_string())
+            .unwrap_or_default();
+        if SOUND_SERVERS.contains(&name.as_str()) {
+            continue;
+        }
+        let uid = fs::read_to_string(entry.path().join("status"))
+            .ok()
+            .and_then(|status| {
+                status
+                    .lines()
+                    .find_map(|l| l.strip_prefix("Uid:"))
+                    .and_then(|ids| ids.split_whitespace().next()?.parse().ok())
+            })
+            .unwrap_or(0);
+        let user = user_name(uid);
+        for device in devices {
+            in_use.insert(Usage {
+                device,
+                process: name.clone(),
+                pid,
+                user: user.clone(),
+            });
+        }
+    }
+    in_use
+}
+
+#[cfg(target_os = "windows")]
+fn scan() -> BTreeSet<Usage> {
+    const SCRIPT: &str = r#"
+$ErrorActionPreference = 'SilentlyContinue'
+$store = 'Software\Microsoft\Windows\CurrentVersion\CapabilityAccessManager\ConsentStore'
+@(Get-ChildItem 'Registry::HKEY_USERS' | Where-Object { $_.PSChildName -match '^S-1-5-21-[\d-]+$' } | ForEach-Object {
+  $sid = $_.PSChildName
+  $user = try { (New-Object System.Security.Principal.SecurityIdentifier($sid)).Translate([System.Security.Principal.NTAccount]).Value } catch { $sid }
+  foreach ($device in 'webcam', 'microphone') {
+    Get-ChildItem "Registry::HKEY_USERS\$sid\$store\$device" -Recurse | ForEach-Object {
+      $p = Get-ItemProperty -Path $_.PSPath
+      if ($p.LastUsedTimeStart -and $p.LastUsedTimeStop -eq 0) {
+        [pscustomobject]@{ device = $device; app = $_.PSChildName; user = $user }
+      }
+    }
+  }
+}) | ConvertTo-Json -Compress
+"#;
+    let mut cmd = Command::new("powershell");
+    cmd.args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT]);
+    let Some(json) = exec_with_timeout(cmd, COMMAND_TIMEOUT)
+        .and_then(|o| serde_json::from_slice::<Value>(&o.stdout).ok())
+    else {
+        return BTreeSet::new();
+    };
+    // ConvertTo-Json unwraps single-item arrays
+    let items = match json {
+        Value::Array(items) => items,
+        Value::Null => Vec::new(),
+        item => vec![item],
+    };
+    items
+        .iter()
+        .filter_map(|item| {
+            let device = match item["device"].as_str()? {
+                "webcam" => Device::Camera,
+                _ => Device::Microphone,
+            };
+            // Desktop apps are keyed by path with '#' for '\', store apps
+            // by package family name
+            let app = item["app"].as_str()?;
+            let process = app.rsplit('#').next().unwrap_or(app).to_string();
+            Some(Usage {
+                device,
+                process,
+                pid: 0,
+                user: item["user"].as_str().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn scan() -> BTreeSet<Usage> {
+    BTreeSet::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(device: Device, process: &str, pid: u32) -> Usage {
+        Usage {
+            device,
+            process: process.to_string(),
+            pid,
+            user: "alice".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_device_of() {
+        assert_eq!(device_of("/dev/video0"), Some(Device::Camera));
+        assert_eq!(device_of("/dev/snd/pcmC0D0c"), Some(Device::Microphone));
+        assert_eq!(device_of("/dev/snd/pcmC0D3p"), None);
+        assert_eq!(device_of("/dev/snd/controlC0"), None);
+        assert_eq!(device_of("socket:[1234]"), None);
+    }
+
+    #[test]
+    fn test_parse_pw_dump() {
+        let json: Value = serde_json::from_str(
+            r#"[
+              {"id": 30, "type": "PipeWire:Interface:Node", "info": {"state": "running",
+                "props": {"media.class": "Audio/Source", "node.name": "alsa_input.pci"}}},
+              {"id": 81, "type": "PipeWire:Interface:Node", "info": {"state": "running",
+                "props": {"media.class": "Stream/Input/Audio", "application.name": "Firefox",
+                  "application.process.binary": "firefox", "application.process.id": 4242}}},
+              {"id": 82, "type": "PipeWire:Interface:Node", "info": {"state": "idle",
+                "props": {"media.class": "Stream/Input/Video", "application.process.binary": "obs"}}},
+              {"id": 83, "type": "PipeWire:Interface:Node", "info": {"state": "running",
+                "props": {"media.class": "Stream/Input/Video", "application.name": "Cheese",
+                  "application.process.id": "5151", "application.process.user": "bob"}}},
+              {"id": 84, "type": "PipeWire:Interface:Node", "info": {"state": "running",
+                "props": {"media.class": "Stream/Output/Audio", "application.process.binary": "mpv"}}}
+            ]"#,
+        )
+        .unwrap();
+        let streams = parse_pw_dump(&json, "alice");
+        assert_eq!(
+            streams,
+            vec![
+                usage(Device::Microphone, "firefox", 4242),
+                Usage {
+                    user: "bob".to_string(),
+                    ..usage(Device::Camera, "Cheese", 5151)
+                },
+            ]
+        );
+        assert!(parse_pw_dump(&Value::Null, "alice").is_empty());
+    }
+
+    #[test]
+    fn test_start_and_stop_events() {
+        let mut collector = CaptureCollector::new(CaptureDeviceConfig {
+            expected_processes: vec!["Zoom.exe".to_string()],
+            ..Default::default()
+        });
+        let zoom = usage(Device::Camera, "zoom", 100);
+        let spy = usage(Device::Microphone, "updater", 200);
+
+        let (started, stopped) = collector.update(BTreeSet::from([zoom.clone()]));
+        assert_eq!((started, stopped), (vec![zoom.clone()], vec![]));
+        assert_eq!(collector.event(&zoom, true).level, "info");
+
+        let (started, stopped) = collector.update(BTreeSet::from([zoom.clone(), spy.clone()]));
+        assert_eq!((started, stopped), (vec![spy.clone()], vec![]));
+        let event = collector.event(&spy, true);
+        assert_eq!(event.level, "warning");
+        assert_eq!(
+            event.message,
+            "updater (pid 200) started using the microphone"
+        );
+
+        let (started, stopped) = collector.update(BTreeSet::new());
+        assert_eq!((started, stopped), (vec![], vec![zoom, spy]));
+    }
+}
@@ -0,0 +1,293 @@
+//! Per-cgroup resource usage (Linux cgroup v2)
+//!
+//! Off by default (`collector.cgroups.enabled`). Walks the unified hierarchy
+//! under `/sys/fs/cgroup` down to `depth` levels, so with the default of 2
+//! systemd hosts report `system.slice/*.service`, `user.slice/user-*.slice`
+//! and the like. That is usually enough to attribute host load to services
+//! without per-process collection. For every cgroup:
+//!
+//! - CPU from `cpu.stat` `usage_usec`, in percent of the whole host (the
+//!   cgroups of one level add up to the host's CPU usage)
+//! - memory from `memory.current`
+//! - read/write throughput from `io.stat`
+//! - task count from `pids.current`
+//!
+//! `include`/`exclude` are glob patterns on the path relative to the root,
+//! e.g. `system.slice/*.service`. Hosts still on cgroup v1 report nothing.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use glob::Pattern;
+use tracing::warn;
+
+use crate::config::{CgroupConfig, CollectorConfig};
+use crate::proto::CgroupUsage;
+
+use super::registry::{CollectContext, Collector, Fragment, IntervalClass};
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Most cgroups reported, busiest CPU users first
+const MAX_CGROUPS: usize = 200;
+
+/// Cumulative counters of one cgroup
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Counters {
+    cpu_usec: u64,
+    read_bytes: u64,
+    write_bytes: u64,
+}
+
+pub struct CgroupCollector {
+    root: PathBuf,
+    config: CgroupConfig,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    prev: HashMap<String, Counters>,
+    prev_time: Option<Instant>,
+    cpus: f64,
+}
+
+impl CgroupCollector {
+    pub fn new(config: CgroupConfig) -> Self {
+        Self::with_root(Path::new(CGROUP_ROOT), config)
+    }
+
+    fn with_root(root: &Path, config: CgroupConfig) -> Self {
+        let patterns = |globs: &[String]| {
+            globs
+                .iter()
+                .filter_map(|g| match Pattern::new(g) {
+                    Ok(p) => Some(p),
+                    Err(e) => {
+                        warn!("Invalid cgroup pattern '{}': {}", g, e);
+                        None
+                    }
+                })
+                .collect()
+        };
+        if !root.join("cgroup.controllers").exists() {
+            warn!(
+                "{} is not a cgroup v2 hierarchy; cgroup usage is not reported",
+                root.display()
+            );
+        }
+        Self {
+            root: root.to_path_buf(),
+            include: patterns(&config.include),
+            exclude: patterns(&config.exclude),
+            config,
+            prev: HashMap::new(),
+            prev_time: None,
+            cpus: std::thread::available_parallelism().map_or(1, |n| n.get()) as f64,
+        }
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| p.matches(path)))
+            && !self.exclude.iter().any(|p| p.matches(path))
+    }
+
+    /// Relative paths of the cgroups to report
+    fn walk(&self, dir: &Path, relative: &str, depth: u32, out: &mut Vec<String>) {
+        if depth == self.config.depth {
+            return;
+        }
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            if !entry.file_type().is_ok_and(|t| t.is_dir()) {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let path = if relative.is_empty() {
+                name
+            } else {
+                format!("{relative}/{name}")
+            };
+            if self.matches(&path) {
+                out.push(path.clone());
+            }
+            self.walk(&entry.path(), &path, depth + 1, out);
+        }
+    }
+
+    fn collect(&mut self) -> Vec<CgroupUsage> {
+        if !self.root.join("cgroup.controllers").exists() {
+            return Vec::new();
+        }
+        let now = Instant::now();
+        let elapsed = self
+            .prev_time
+            .map(|t| now.duration_since(t).as_secs_f64())
+            .unwrap_or(0.0);
+
+        let mut paths = Vec::new();
+        self.walk(&self.root, "", 0, &mut paths);
+
+        let mut current = HashMap::with_capacity(paths.len());
+        let mut usage = Vec::with_capacity(paths.len());
+        for path in paths {
+            let dir = self.root.join(&path);
+            let read = |file: &str| std::fs::read_to_string(dir.join(file)).unwrap_or_default();
+
+            let (read_bytes, write_bytes) = parse_io_stat(&read("io.stat"));
+            let counters = Counters {
+                cpu_usec: parse_keyed(&read("cpu.stat"), "usage_usec").unwrap_or(0),
+                read_bytes,
+                write_bytes,
+            };
+            let rate = |now: u64, prev: u64| {
+                if elapsed > 0.0 {
+                    now.saturating_sub(prev) as f64 / elapsed
+                } else {
+                    0.0
+                }
+            };
+            let prev = self.prev.get(&path).copied();
+            let (cpu_percent, read_sec, write_sec) = match prev {
+                Some(prev) => (
+                    rate(counters.cpu_usec, prev.cpu_usec) / 1_000_000.0 / self.cpus * 100.0,
+                    rate(counters.read_bytes, prev.read_bytes),
+                    rate(counters.write_bytes, prev.write_bytes),
+                ),
+                None => (0.0, 0.0, 0.0),
+            };
+
+            usage.push(CgroupUsage {
+                path: path.clone(),
+                cpu_percent,
+                memory_bytes: read("memory.current").trim().parse().unwrap_or(0),
+                io_read_bytes_sec: read_sec as u64,
+                io_write_bytes_sec: write_sec as u64,
+                pids: read("pids.current").trim().parse().unwrap_or(0),
+            });
+            current.insert(path, counters);
+        }
+
+        self.prev = current;
+        self.prev_time = Some(now);
+
+        usage.sort_by(|a, b| b.cpu_percent.total_cmp(&a.cpu_percent));
+        usage.truncate(MAX_CGROUPS);
+        usage
+    }
+}
+
+impl Collector for CgroupCollector {
+    fn name(&self) -> &'static str {
+        "cgroups"
+    }
+
+    fn interval(&self, _config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Periodic(Duration::from_millis(self.config.interval_ms))
+    }
+
+    fn collect(&mut self, _ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        Ok(Fragment::Cgroups(CgroupCollector::collect(self)))
+    }
+}
+
+/// Value of `key` in a flat keyed file such as `cpu.stat`
+fn parse_keyed(text: &str, key: &str) -> Option<u64> {
+    text.lines().find_map(|line| {
+        let (k, v) = line.split_once(' ')?;
+        (k == key).then(|| v.trim().parse().ok())?
+    })
+}
+
+/// Bytes read and written, summed over devices:
+/// `8:0 rbytes=1 wbytes=2 rios=3 wios=4 dbytes=0 dios=0`
+fn parse_io_stat(text: &str) -> (u64, u64) {
+    let mut read = 0u64;
+    let mut write = 0u64;
+    for field in text.split_whitespace() {
+        if let Some(v) = field.strip_prefix("rbytes=") {
+            read += v.parse::<u64>().unwrap_or(0);
+        } else if let Some(v) = field.strip_prefix("wbytes=") {
+            write += v.parse::<u64>().unwrap_or(0);
+        }
+    }
+    (read, write)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cgroup(root: &Path, path: &str, cpu_usec: u64, memory: u64) {
+        let dir = root.join(path);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("cpu.stat"),
+            format!("usage_usec {cpu_usec}\nuser_usec 0\n"),
+        )
+        .unwrap();
+        std::fs::write(dir.join("memory.current"), format!("{memory}\n")).unwrap();
+        std::fs::write(
+            dir.join("io.stat"),
+            "8:0 rbytes=4096 wbytes=8192 rios=1 wios=2\n259:0 rbytes=1024 wbytes=0\n",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_parsers() {
+        assert_eq!(
+            parse_keyed("usage_usec 123\nuser_usec 100\n", "usage_usec"),
+            Some(123)
+        );
+        assert_eq!(parse_keyed("user_usec 100\n", "usage_usec"), None);
+        assert_eq!(
+            parse_io_stat("8:0 rbytes=4096 wbytes=8192 rios=1\n259:0 rbytes=1024 wbytes=0"),
+            (5120, 8192)
+        );
+    }
+
+    #[test]
+    fn test_walk_and_filters() {
+        let root = std::env::temp_dir().join(format!("nanolink-cgroup-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("cgroup.controllers"), "cpu io memory pids\n").unwrap();
+        cgroup(&root, "system.slice", 0, 300);
+        cgroup(&root, "system.slice/nginx.service", 1_000, 100);
+        cgroup(&root, "system.slice/cron.service", 0, 200);
+        cgroup(&root, "system.slice/nginx.service/worker", 0, 50);
+        cgroup(&root, "user.slice", 0, 400);
+
+        let mut collector = CgroupCollector::with_root(
+            &root,
+            CgroupConfig {
+                enabled: true,
+                exclude: vec!["system.slice/cron.*".to_string()],
+                ..Default::default()
+            },
+        );
+        let first = collector.collect();
+        let mut paths: Vec<_> = first.iter().map(|u| u.path.as_str()).collect();
+        paths.sort();
+        // Depth 2: the worker sub-cgroup is not reported
+        assert_eq!(
+            paths,
+            ["system.slice", "system.slice/nginx.service", "user.slice"]
+        );
+        let nginx = first
+            .iter()
+            .find(|u| u.path == "system.slice/nginx.service")
+            .unwrap();
+        assert_eq!(nginx.memory_bytes, 100);
+        assert_eq!(nginx.cpu_percent, 0.0);
+
+        // CPU is a rate between samples
+        collector.prev_time = Some(Instant::now() - Duration::from_secs(1));
+        cgroup(&root, "system.slice/nginx.service", 1_000_000, 100);
+        let second = collector.collect();
+        let _ = std::fs::remove_dir_all(&root);
+        assert_eq!(second[0].path, "system.slice/nginx.service");
+        assert!(second[0].cpu_percent > 0.0);
+    }
+}
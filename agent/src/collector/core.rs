@@ -0,0 +1,380 @@
+//! Shared collection core
+//!
+//! Owns the sysinfo handles and all collectors, and builds every message
+//! shape (full, static, realtime, disk usage). `MetricsCollector` and
+//! `LayeredCollector` are thin schedulers on top of it, so a new metric
+//! field only has to be added here.
+
+use std::sync::Arc;
+
+use sysinfo::{Disks, Networks, System};
+
+use crate::config::Config;
+use crate::proto::{
+    CpuStaticInfo, DiskIo, DiskStaticInfo, DiskUsage, MemoryStaticInfo, Metrics, MetricsType,
+    NetworkIo, NetworkMetrics, NetworkStaticInfo, PeriodicData, RealtimeMetrics, StaticInfo,
+};
+use crate::utils::clock;
+
+use super::forecast::DiskForecaster;
+use super::rapl::PowerMeter;
+use super::registry::{CollectContext, CollectorRegistry};
+use super::statsd::StatsdCollector;
+use super::{CpuCollector, DiskCollector, MemoryCollector, NetworkCollector, SystemInfoCollector};
+use super::{boot, encryption, environment};
+
+/// Collection core shared by the legacy and layered pipelines
+pub struct CollectionCore {
+    config: Arc<Config>,
+    system: System,
+    disks: Disks,
+    networks: Networks,
+    hostname: String,
+    agent_id: String,
+
+    cpu_collector: CpuCollector,
+    memory_collector: MemoryCollector,
+    disk_collector: DiskCollector,
+    network_collector: NetworkCollector,
+    system_info_collector: SystemInfoCollector,
+    power_meter: PowerMeter,
+    disk_forecaster: DiskForecaster,
+
+    // GPU, NPU, user sessions and pluggable collectors
+    registry: CollectorRegistry,
+}
+
+impl CollectionCore {
+    pub fn new(config: Arc<Config>) -> Self {
+        let mut system = System::new_all();
+        system.refresh_all();
+
+        let mut registry = CollectorRegistry::with_builtin(&config.collector);
+        if config.statsd.enabled {
+            registry.register(
+                Box::new(StatsdCollector::new(config.statsd.flush_interval_ms)),
+                &config.collector,
+            );
+        }
+
+        Self {
+            hostname: config.get_hostname(),
+            agent_id: config.agent.agent_id.clone().unwrap_or_default(),
+            system,
+            disks: Disks::new_with_refreshed_list(),
+            networks: Networks::new_with_refreshed_list(),
+            cpu_collector: CpuCollector::new(),
+            memory_collector: MemoryCollector::new(),
+            disk_collector: DiskCollector::new(),
+            network_collector: NetworkCollector::new(),
+            system_info_collector: SystemInfoCollector::with_hostname(
+                config.agent.hostname.clone(),
+            ),
+            power_meter: PowerMeter::new(),
+            disk_forecaster: DiskForecaster::new(config.collector.disk_forecast.clone()),
+            registry,
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn agent_id(&self) -> &str {
+        &self.agent_id
+    }
+
+    pub fn registry(&self) -> &CollectorRegistry {
+        &self.registry
+    }
+
+    fn refresh(&mut self) {
+        self.system.refresh_cpu_all();
+        self.system.refresh_memory();
+        self.disks.refresh(true);
+        self.networks.refresh(false);
+    }
+
+    /// Collect full metrics (all data)
+    pub fn collect_full(&mut self, is_initial: bool) -> Metrics {
+        self.refresh();
+
+        let timestamp = clock::now_millis();
+        let collector_config = &self.config.collector;
+
+        let mut metrics = Metrics {
+            timestamp,
+            cpu: Some(self.cpu_collector.collect(&self.system, collector_config)),
+            memory: Some(self.memory_collector.collect(&self.system)),
+            disks: self.disk_collector.collect(&self.disks, collector_config),
+            networks: self
+                .network_collector
+                .collect(&self.networks, collector_config),
+            load_average: load_average(),
+            hostname: self.hostname.clone(),
+            system_info: Some(self.system_info_collector.collect()),
+            metrics_type: MetricsType::MetricsFull as i32,
+            is_initial,
+            agent_id: self.agent_id.clone(),
+            sequence: clock::next_sequence(),
+            ..Default::default()
+        };
+
+        let ctx = CollectContext {
+            system: &self.system,
+            config: &self.config.collector,
+        };
+        self.registry.collect_full(&ctx, &mut metrics);
+
+        metrics
+    }
+
+    /// Collect static hardware information
+    pub fn collect_static(&mut self) -> StaticInfo {
+        self.system.refresh_all();
+        self.disks.refresh(true);
+        self.networks.refresh(false);
+
+        let timestamp = clock::now_millis();
+        let collector_config = &self.config.collector;
+
+        // CPU static info
+        let cpu_info = self.cpu_collector.collect(&self.system, collector_config);
+        let cpu_static = CpuStaticInfo {
+            model: cpu_info.model,
+            vendor: cpu_info.vendor,
+            physical_cores: cpu_info.physical_cores,
+            logical_cores: cpu_info.logical_cores,
+            architecture: cpu_info.architecture,
+            frequency_max_mhz: cpu_info.frequency_max_mhz,
+            l1_cache_kb: 0, // TODO: implement cache detection
+            l2_cache_kb: 0,
+            l3_cache_kb: 0,
+        };
+
+        // Memory static info
+        let mem_info = self.memory_collector.collect(&self.system);
+        let memory_static = MemoryStaticInfo {
+            total: mem_info.total,
+            swap_total: mem_info.swap_total,
+            memory_type: mem_info.memory_type,
+            memory_speed_mhz: mem_info.memory_speed_mhz,
+            memory_slots: 0, // TODO: implement slot detection
+        };
+
+        // Disk static info
+        let disks_static: Vec<DiskStaticInfo> = self
+            .disk_collector
+            .collect(&self.disks, collector_config)
+            .into_iter()
+            .map(|d| {
+                let encryption = encryption::detect(&d.device, &d.mount_point, &d.fs_type);
+                DiskStaticInfo {
+                    device: d.device,
+                    mount_point: d.mount_point,
+                    fs_type: d.fs_type,
+                    model: d.model,
+                    serial: d.serial,
+                    disk_type: d.disk_type,
+                    total_bytes: d.total,
+                    health_status: d.health_status,
+                    encryption: encryption.status.to_string(),
+                    encryption_method: encryption.method,
+                }
+            })
+            .collect();
+
+        // Network static info
+        let networks_static: Vec<NetworkStaticInfo> = self
+            .network_collector
+            .collect(&self.networks, collector_config)
+            .into_iter()
+            .map(|n| NetworkStaticInfo {
+                is_virtual: n.interface.starts_with("docker")
+                    || n.interface.starts_with("veth")
+                    || n.interface.starts_with("br-"),
+                interface: n.interface,
+                mac_address: n.mac_address,
+                ip_addresses: n.ip_addresses,
+                speed_mbps: n.speed_mbps,
+                interface_type: n.interface_type,
+            })
+            .collect();
+
+        let mut static_info = StaticInfo {
+            timestamp,
+            cpu: Some(cpu_static),
+            memory: Some(memory_static),
+            disks: disks_static,
+            networks: networks_static,
+            system_info: Some(self.system_info_collector.collect()),
+            agent_version: env!("CARGO_PKG_VERSION").to_string(),
+            agent_id: self.agent_id.clone(),
+            boot: Some(boot::info()),
+            environment: Some(environment::detect(
+                self.config.collector.enable_cloud_metadata,
+            )),
+            ..Default::default()
+        };
+
+        let ctx = CollectContext {
+            system: &self.system,
+            config: &self.config.collector,
+        };
+        self.registry.collect_static(&ctx, &mut static_info);
+
+        static_info
+    }
+
+    /// Collect realtime metrics (lightweight, for frequent sending)
+    pub fn collect_realtime(&mut self) -> RealtimeMetrics {
+        self.refresh();
+
+        let timestamp = clock::now_millis();
+        let collector_config = &self.config.collector;
+
+        let cpu = self.cpu_collector.collect(&self.system, collector_config);
+        let mem = self.memory_collector.collect(&self.system);
+
+        // Disk IO (not usage)
+        let disk_io: Vec<DiskIo> = self
+            .disk_collector
+            .collect(&self.disks, collector_config)
+            .into_iter()
+            .map(|d| DiskIo {
+                device: d.device,
+                read_bytes_sec: d.read_bytes_sec,
+                write_bytes_sec: d.write_bytes_sec,
+                read_iops: d.read_iops,
+                write_iops: d.write_iops,
+            })
+            .collect();
+
+        // Network IO (not addresses)
+        let network_io: Vec<NetworkIo> = self
+            .network_collector
+            .collect(&self.networks, collector_config)
+            .into_iter()
+            .map(|n| NetworkIo {
+                interface: n.interface,
+                rx_bytes_sec: n.rx_bytes_sec,
+                tx_bytes_sec: n.tx_bytes_sec,
+                rx_packets_sec: n.rx_packets_sec,
+                tx_packets_sec: n.tx_packets_sec,
+                is_up: n.is_up,
+            })
+            .collect();
+
+        let mut realtime = RealtimeMetrics {
+            timestamp,
+            cpu_usage_percent: cpu.usage_percent,
+            cpu_per_core: cpu.per_core_usage,
+            cpu_temperature: cpu.temperature,
+            cpu_frequency_mhz: cpu.frequency_mhz,
+            memory_used: mem.used,
+            memory_cached: mem.cached,
+            swap_used: mem.swap_used,
+            disk_io,
+            network_io,
+            load_average: load_average(),
+            agent_id: self.agent_id.clone(),
+            sequence: clock::next_sequence(),
+            cpu_power: self.power_meter.sample(),
+            ..Default::default()
+        };
+
+        let ctx = CollectContext {
+            system: &self.system,
+            config: &self.config.collector,
+        };
+        self.registry.collect_realtime(&ctx, &mut realtime);
+
+        realtime
+    }
+
+    /// Collect disk capacity/usage, with days-until-full forecasts
+    pub fn collect_disk_usage(&mut self) -> Vec<DiskUsage> {
+        self.disks.refresh(true);
+        let mut usage: Vec<DiskUsage> = self
+            .disk_collector
+            .collect(&self.disks, &self.config.collector)
+            .into_iter()
+            .map(|d| DiskUsage {
+                device: d.device,
+                mount_point: d.mount_point,
+                total: d.total,
+                used: d.used,
+                available: d.available,
+                temperature: d.temperature,
+                inodes_total: d.inodes_total,
+                inodes_used: d.inodes_used,
+                inodes_supported: d.inodes_supported,
+                ..Default::default()
+            })
+            .collect();
+        self.disk_forecaster
+            .update(&mut usage, clock::now_millis() / 1000);
+        usage
+    }
+
+    /// Collect network interfaces (including addresses)
+    pub fn collect_networks(&mut self) -> Vec<NetworkMetrics> {
+        self.networks.refresh(false);
+        self.network_collector
+            .collect(&self.networks, &self.config.collector)
+    }
+
+    /// Run periodic pluggable collectors that are due
+    pub fn collect_periodic(&mut self, periodic: &mut PeriodicData) -> bool {
+        let ctx = CollectContext {
+            system: &self.system,
+            config: &self.config.collector,
+        };
+        self.registry.collect_periodic(&ctx, periodic)
+    }
+
+    /// Run a single pluggable collector into a periodic message
+    pub fn collect_one_periodic(&mut self, name: &str, periodic: &mut PeriodicData) {
+        let ctx = CollectContext {
+            system: &self.system,
+            config: &self.config.collector,
+        };
+        if let Some(fragment) = self.registry.collect_one(name, &ctx) {
+            fragment.apply_periodic(periodic);
+        }
+    }
+}
+
+/// System load average (Unix only)
+#[cfg(unix)]
+fn load_average() -> Vec<f64> {
+    let load = System::load_average();
+    vec![load.one, load.five, load.fifteen]
+}
+
+#[cfg(windows)]
+fn load_average() -> Vec<f64> {
+    // Windows doesn't have load average
+    vec![]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layers_share_one_source() {
+        let mut core = CollectionCore::new(Arc::new(Config::sample()));
+
+        let full = core.collect_full(true);
+        let realtime = core.collect_realtime();
+        let static_info = core.collect_static();
+
+        assert!(full.is_initial);
+        assert!(realtime.sequence > full.sequence);
+        assert_eq!(full.disks.len(), static_info.disks.len());
+        assert_eq!(full.networks.len(), realtime.network_io.len());
+        assert_eq!(full.load_average.len(), realtime.load_average.len());
+    }
+}
@@ -4,6 +4,8 @@ use sysinfo::System;
 use crate::config::CollectorConfig;
 use crate::proto::CpuMetrics;
 
+use super::temperature::CpuTemperature;
+
 /// Static CPU info that doesn't change
 static CPU_INFO: OnceLock<CpuStaticInfo> = OnceLock::new();
 
@@ -19,14 +21,16 @@ struct CpuStaticInfo {
 
 /// CPU metrics collector
 pub struct CpuCollector {
-    _marker: (),
+    temperature: CpuTemperature,
 }
 
 impl CpuCollector {
     pub fn new() -> Self {
         // Initialize static CPU info once
         CPU_INFO.get_or_init(Self::collect_static_info);
-        Self { _marker: () }
+        Self {
+            temperature: CpuTemperature::new(),
+        }
     }
 
     #[allow(unused_assignments)]
@@ -323,49 +327,6 @@ impl CpuCollector {
             .unwrap_or(0)
     }
 
-    /// Get CPU temperature (platform-specific)
-    fn get_temperature() -> f64 {
-        #[cfg(target_os = "linux")]
-        {
-            use std::fs;
-            // Try hwmon thermal zones
-            let hwmon_path = "/sys/class/hwmon";
-            if let Ok(entries) = fs::read_dir(hwmon_path) {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    // Check if this is a CPU thermal sensor
-                    if let Ok(name) = fs::read_to_string(path.join("name")) {
-                        let name = name.trim();
-                        if name.contains("coretemp")
-                            || name.contains("k10temp")
-                            || name.contains("cpu")
-                        {
-                            // Read temp1_input (in millidegrees)
-                            if let Ok(temp) = fs::read_to_string(path.join("temp1_input")) {
-                                if let Ok(temp_mc) = temp.trim().parse::<i64>() {
-                                    return temp_mc as f64 / 1000.0;
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-
-            // Fallback to thermal zones
-            if let Ok(temp) = fs::read_to_string("/sys/class/thermal/thermal_zone0/temp") {
-                if let Ok(temp_mc) = temp.trim().parse::<i64>() {
-                    return temp_mc as f64 / 1000.0;
-                }
-            }
-
-            0.0
-        }
-        #[cfg(not(target_os = "linux"))]
-        {
-            0.0 // Temperature reading not easily available on macOS/Windows without admin
-        }
-    }
-
     /// Collect CPU metrics
     pub fn collect(&mut self, system: &System, config: &CollectorConfig) -> CpuMetrics {
         let global_cpu = system.global_cpu_usage();
@@ -393,7 +354,7 @@ impl CpuCollector {
             physical_cores: cpu_info.physical_cores,
             logical_cores: cpu_info.logical_cores,
             architecture: cpu_info.architecture.clone(),
-            temperature: Self::get_temperature(),
+            temperature: self.temperature.read(),
         }
     }
 }
@@ -405,6 +366,7 @@ impl Default for CpuCollector {
 }
 
 /// Helper to extract string from JSON line
+#[cfg(target_os = "windows")]
 fn extract_json_string(line: &str) -> Option<String> {
     let parts: Vec<&str> = line.split(':').collect();
     if parts.len() >= 2 {
@@ -421,6 +383,7 @@ fn extract_json_string(line: &str) -> Option<String> {
 }
 
 /// Helper to extract number from JSON line
+#[cfg(target_os = "windows")]
 fn extract_json_number(line: &str) -> Option<u32> {
     let parts: Vec<&str> = line.split(':').collect();
     if parts.len() >= 2 {
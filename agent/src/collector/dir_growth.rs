@@ -0,0 +1,322 @@
+//! Directory growth tracking
+//!
+//! With `collector.dir_growth.enabled`, the configured directories and their
+//! subdirectories down to `depth` levels are sized once per
+//! `snapshot_interval_hours`, daily by default. Each snapshot is compared
+//! with the previous one, which is kept in
+//! `/var/lib/nanolink/dir_sizes.json` across restarts. The `top_n`
+//! fastest-growing directories are reported as custom metrics (collector
+//! "dir_growth", label `path`):
+//!
+//! - `dir.size_bytes`: current size
+//! - `dir.growth_bytes_per_day`: growth since the previous snapshot
+//!
+//! Walks never follow symlinks or cross into other filesystems, and stop
+//! after `max_files` entries. They run on a background thread, so a large
+//! tree doesn't hold up other collectors.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::config::{CollectorConfig, DirGrowthConfig};
+use crate::proto::CustomMetric;
+use crate::utils::clock;
+
+use super::registry::{CollectContext, Collector, Fragment, IntervalClass};
+
+/// How often to check whether a snapshot is due or finished
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+const SECS_PER_DAY: f64 = 86_400.0;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Snapshot {
+    /// Unix time in seconds
+    time: u64,
+    sizes: HashMap<String, u64>,
+}
+
+pub struct DirGrowthCollector {
+    config: DirGrowthConfig,
+    path: Option<PathBuf>,
+    previous: Option<Snapshot>,
+    scan: Option<JoinHandle<Snapshot>>,
+}
+
+impl DirGrowthCollector {
+    pub fn new(config: DirGrowthConfig) -> Self {
+        let path = (!cfg!(test)).then(state_file);
+        let previous = path.as_deref().and_then(|path| {
+            let json = std::fs::read(path).ok()?;
+            serde_json::from_slice(&json)
+                .inspect_err(|e| warn!("Ignoring unreadable {:?}: {}", path, e))
+                .ok()
+        });
+        Self {
+            config,
+            path,
+            previous,
+            scan: None,
+        }
+    }
+
+    fn snapshot_due(&self, now: u64) -> bool {
+        let interval = self.config.snapshot_interval_hours * 3600;
+        self.previous
+            .as_ref()
+            .is_none_or(|prev| now.saturating_sub(prev.time) >= interval)
+    }
+
+    fn start_scan(&mut self, now: u64) {
+        let config = self.config.clone();
+        let spawned = std::thread::Builder::new()
+            .name("dir-growth-scan".to_string())
+            .spawn(move || scan(&config, now));
+        match spawned {
+            Ok(handle) => self.scan = Some(handle),
+            Err(e) => warn!("Failed to start directory scan: {}", e),
+        }
+    }
+
+    /// Compare `current` with the previous snapshot and keep it
+    fn finish(&mut self, current: Snapshot) -> Vec<CustomMetric> {
+        let metrics = match &self.previous {
+            Some(prev) if current.time > prev.time => growth(prev, &current, self.config.top_n),
+            _ => Vec::new(),
+        };
+        if let Some(path) = &self.path {
+            let written = serde_json::to_vec(&current)
+                .map_err(std::io::Error::other)
+                .and_then(|json| {
+                    path.parent()
+                        .map_or(Ok(()), std::fs::create_dir_all)
+                        .and_then(|_| std::fs::write(path, json))
+                });
+            if let Err(e) = written {
+                debug!("Failed to save directory sizes to {:?}: {}", path, e);
+            }
+        }
+        self.previous = Some(current);
+        metrics
+    }
+}
+
+impl Collector for DirGrowthCollector {
+    fn name(&self) -> &'static str {
+        "dir_growth"
+    }
+
+    fn interval(&self, _config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Periodic(POLL_INTERVAL)
+    }
+
+    fn collect(&mut self, _ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        if self.scan.as_ref().is_some_and(|scan| scan.is_finished()) {
+            let handle = self.scan.take().expect("checked above");
+            let metrics = match handle.join() {
+                Ok(snapshot) => self.finish(snapshot),
+                Err(_) => {
+                    warn!("Directory scan panicked");
+                    Vec::new()
+                }
+            };
+            return Ok(Fragment::Custom(metrics));
+        }
+
+        let now = clock::now_millis() / 1000;
+        if self.scan.is_none() && self.snapshot_due(now) {
+            self.start_scan(now);
+        }
+        Ok(Fragment::Custom(Vec::new()))
+    }
+}
+
+fn state_file() -> PathBuf {
+    #[cfg(windows)]
+    {
+        let base = std::env::var("ProgramData").unwrap_or_else(|_| "C:\\ProgramData".to_string());
+        PathBuf::from(base).join("nanolink").join("dir_sizes.json")
+    }
+    #[cfg(unix)]
+    {
+        PathBuf::from("/var/lib/nanolink/dir_sizes.json")
+    }
+}
+
+/// Size every configured directory and its subdirectories down to `depth`
+fn scan(config: &DirGrowthConfig, time: u64) -> Snapshot {
+    let mut sizes = HashMap::new();
+    let mut budget = config.max_files;
+    for root in &config.paths {
+        let root = Path::new(root);
+        let Ok(meta) = std::fs::symlink_metadata(root) else {
+            debug!("Skipping missing directory {:?}", root);
+            continue;
+        };
+        size_dir(
+            root,
+            device(&meta),
+            0,
+            config.depth,
+            &mut budget,
+            &mut sizes,
+        );
+    }
+    if budget == 0 {
+        warn!(
+            "Directory scan stopped after {} entries; sizes are incomplete",
+            config.max_files
+        );
+    }
+    Snapshot { time, sizes }
+}
+
+/// Total size of `dir`, recording directories up to `max_depth` in `sizes`
+fn size_dir(
+    dir: &Path,
+    dev: u64,
+    depth: u32,
+    max_depth: u32,
+    budget: &mut u64,
+    sizes: &mut HashMap<String, u64>,
+) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    let mut total = 0;
+    for entry in entries.flatten() {
+        if *budget == 0 {
+            break;
+        }
+        *budget -= 1;
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if meta.is_dir() {
+            if device(&meta) == dev {
+                total += size_dir(&entry.path(), dev, depth + 1, max_depth, budget, sizes);
+            }
+        } else if meta.is_file() {
+            total += meta.len();
+        }
+    }
+    if depth <= max_depth {
+        sizes.insert(dir.to_string_lossy().into_owned(), total);
+    }
+    total
+}
+
+#[cfg(unix)]
+fn device(meta: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.dev()
+}
+
+#[cfg(not(unix))]
+fn device(_meta: &std::fs::Metadata) -> u64 {
+    0
+}
+
+/// The `top_n` fastest-growing directories between two snapshots
+fn growth(prev: &Snapshot, current: &Snapshot, top_n: usize) -> Vec<CustomMetric> {
+    let days = (current.time - prev.time) as f64 / SECS_PER_DAY;
+    let mut rates: Vec<(&String, u64, f64)> = current
+        .sizes
+        .iter()
+        .filter_map(|(path, &size)| {
+            let before = *prev.sizes.get(path)?;
+            Some((path, size, (size as f64 - before as f64) / days))
+        })
+        .collect();
+    rates.sort_by(|a, b| b.2.total_cmp(&a.2));
+    rates.truncate(top_n);
+
+    let metric = |name: &str, path: &str, value: f64, unit: &str| CustomMetric {
+        collector: "dir_growth".to_string(),
+        name: name.to_string(),
+        value,
+        unit: unit.to_string(),
+        labels: HashMap::from([("path".to_string(), path.to_string())]),
+    };
+    rates
+        .into_iter()
+        .flat_map(|(path, size, rate)| {
+            [
+                metric("dir.size_bytes", path, size as f64, "bytes"),
+                metric("dir.growth_bytes_per_day", path, rate, "bytes/day"),
+            ]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_depth() {
+        let root = std::env::temp_dir().join(format!("nanolink-dirs-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("app/cache/deep")).unwrap();
+        std::fs::create_dir_all(root.join("logs")).unwrap();
+        std::fs::write(root.join("logs/app.log"), vec![0u8; 1000]).unwrap();
+        std::fs::write(root.join("app/cache/deep/blob"), vec![0u8; 500]).unwrap();
+        std::fs::write(root.join("top.txt"), vec![0u8; 10]).unwrap();
+
+        let config = DirGrowthConfig {
+            enabled: true,
+            paths: vec![root.to_string_lossy().into_owned()],
+            depth: 1,
+            ..Default::default()
+        };
+        let snapshot = scan(&config, 0);
+        let _ = std::fs::remove_dir_all(&root);
+
+        let size = |rel: &str| {
+            snapshot
+                .sizes
+                .get(&root.join(rel).to_string_lossy().into_owned())
+                .copied()
+        };
+        assert_eq!(snapshot.sizes[&root.to_string_lossy().into_owned()], 1510);
+        assert_eq!(size("logs"), Some(1000));
+        assert_eq!(size("app"), Some(500));
+        // Below the configured depth: counted, not listed
+        assert_eq!(size("app/cache"), None);
+    }
+
+    #[test]
+    fn test_growth_top_n() {
+        let snapshot = |time, sizes: &[(&str, u64)]| Snapshot {
+            time,
+            sizes: sizes.iter().map(|(p, s)| (p.to_string(), *s)).collect(),
+        };
+        let prev = snapshot(
+            0,
+            &[("/var/log", 1000), ("/var/cache", 5000), ("/tmp", 100)],
+        );
+        let current = snapshot(
+            2 * 86_400,
+            &[
+                ("/var/log", 9000),
+                ("/var/cache", 5000),
+                ("/tmp", 300),
+                ("/new", 1),
+            ],
+        );
+
+        let metrics = growth(&prev, &current, 2);
+        let rates: Vec<_> = metrics
+            .iter()
+            .filter(|m| m.name == "dir.growth_bytes_per_day")
+            .map(|m| (m.labels["path"].as_str(), m.value))
+            .collect();
+        assert_eq!(rates, [("/var/log", 4000.0), ("/tmp", 100.0)]);
+        assert_eq!(metrics[0].name, "dir.size_bytes");
+        assert_eq!(metrics[0].value, 9000.0);
+    }
+}
@@ -348,22 +348,13 @@ impl DiskCollector {
         {
             use std::process::Command;
 
-            // Try smartctl (requires smartmontools)
+            // Try smartctl (requires smartmontools). Its exit status is a
+            // bit mask that also flags disk problems, so the output is
+            // parsed whatever the status.
             if let Ok(output) = Command::new("smartctl").args(["-A", device]).output() {
-                if output.status.success() {
-                    let stdout = String::from_utf8_lossy(&output.stdout);
-                    for line in stdout.lines() {
-                        if line.contains("Temperature_Celsius")
-                            || line.contains("Airflow_Temperature")
-                        {
-                            let parts: Vec<&str> = line.split_whitespace().collect();
-                            if parts.len() >= 10 {
-                                if let Ok(temp) = parts[9].parse::<f64>() {
-                                    return temp;
-                                }
-                            }
-                        }
-                    }
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                if let Some(temp) = nanolink_parse::smartctl::temperature(&stdout) {
+                    return temp;
                 }
             }
 
@@ -398,15 +389,11 @@ impl DiskCollector {
         {
             use std::process::Command;
 
+            // A failing disk sets bit 3 of the exit status, so the status
+            // can't be used to discard the output
             if let Ok(output) = Command::new("smartctl").args(["-H", device]).output() {
-                if output.status.success() {
-                    let stdout = String::from_utf8_lossy(&output.stdout);
-                    if stdout.contains("PASSED") {
-                        return "PASSED".to_string();
-                    } else if stdout.contains("FAILED") {
-                        return "FAILED".to_string();
-                    }
-                }
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                return nanolink_parse::smartctl::health(&stdout).to_string();
             }
         }
 
@@ -483,6 +470,7 @@ impl DiskCollector {
 
             let temperature = Self::get_disk_temperature(&format!("/dev/{base_device}"));
             let health_status = Self::get_smart_health(&format!("/dev/{base_device}"));
+            let inodes = Self::get_inode_usage(&mount_point);
 
             metrics.push(DiskMetrics {
                 mount_point,
@@ -500,6 +488,9 @@ impl DiskCollector {
                 write_iops,
                 temperature,
                 health_status,
+                inodes_total: inodes.map_or(0, |(total, _)| total),
+                inodes_used: inodes.map_or(0, |(_, used)| used),
+                inodes_supported: inodes.is_some(),
             });
         }
 
@@ -510,6 +501,26 @@ impl DiskCollector {
         metrics
     }
 
+    /// Get inode usage `(total, used)` of the filesystem mounted at `mount_point`.
+    ///
+    /// Returns `None` where inodes are not a meaningful limit: Windows, and
+    /// filesystems that allocate inodes dynamically (btrfs, ZFS report 0).
+    #[cfg(unix)]
+    fn get_inode_usage(mount_point: &str) -> Option<(u64, u64)> {
+        let stat = nix::sys::statvfs::statvfs(mount_point).ok()?;
+        let total = stat.files() as u64;
+        if total == 0 {
+            return None;
+        }
+        let free = stat.files_free() as u64;
+        Some((total, total.saturating_sub(free)))
+    }
+
+    #[cfg(windows)]
+    fn get_inode_usage(_mount_point: &str) -> Option<(u64, u64)> {
+        None
+    }
+
     /// Check if a filesystem should be skipped (virtual/pseudo filesystems)
     fn should_skip_filesystem(mount_point: &str, device: &str, fs_type: &str) -> bool {
         // Skip by filesystem type (virtual/pseudo filesystems)
@@ -599,6 +610,7 @@ impl Default for DiskCollector {
 }
 
 /// Helper to extract value from JSON-like line
+#[cfg(target_os = "windows")]
 fn extract_json_value(line: &str) -> Option<String> {
     let parts: Vec<&str> = line.split(':').collect();
     if parts.len() >= 2 {
@@ -613,3 +625,19 @@ fn extract_json_value(line: &str) -> Option<String> {
         None
     }
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inode_usage() {
+        // The root filesystem may not track inodes (btrfs), but never reports
+        // more used than total
+        if let Some((total, used)) = DiskCollector::get_inode_usage("/") {
+            assert!(total > 0);
+            assert!(used <= total);
+        }
+        assert!(DiskCollector::get_inode_usage("/nonexistent/mount").is_none());
+    }
+}
@@ -0,0 +1,340 @@
+//! Volume encryption detection
+//!
+//! Reports whether each mounted volume is encrypted at rest, for
+//! `DiskStaticInfo.encryption`:
+//!
+//! - Linux: device-mapper targets are followed down to their slaves; a
+//!   `CRYPT-LUKS*` dm UUID or a LUKS header on the backing device means LUKS,
+//!   other `CRYPT-*` targets are plain dm-crypt. eCryptfs mounts count too.
+//! - Windows: `manage-bde -status`, falling back to the
+//!   `Win32_EncryptableVolume` WMI class
+//! - macOS: `diskutil info` for the volume, `fdesetup status` for the root
+//!   volume when diskutil doesn't say
+//!
+//! Anything that can't be determined (e.g. missing privileges) is "unknown".
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+use std::process::Command;
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+use std::time::Duration;
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+use crate::utils::safe_command::exec_with_timeout;
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// LUKS1 and LUKS2 header magic
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+const LUKS_MAGIC: &[u8] = b"LUKS\xba\xbe";
+
+/// Encryption state of one volume
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Encryption {
+    /// "encrypted", "unencrypted" or "unknown"
+    pub status: &'static str,
+    /// LUKS, dm-crypt, BitLocker, FileVault or eCryptfs; empty if unencrypted
+    pub method: String,
+}
+
+impl Encryption {
+    fn encrypted(method: &str) -> Self {
+        Self {
+            status: "encrypted",
+            method: method.to_string(),
+        }
+    }
+
+    fn unencrypted() -> Self {
+        Self {
+            status: "unencrypted",
+            method: String::new(),
+        }
+    }
+
+    fn unknown() -> Self {
+        Self {
+            status: "unknown",
+            method: String::new(),
+        }
+    }
+}
+
+/// Detect the encryption of the volume `device` mounted at `mount_point`
+#[allow(unused_variables)]
+pub fn detect(device: &str, mount_point: &str, fs_type: &str) -> Encryption {
+    #[cfg(target_os = "linux")]
+    return linux::detect(device, fs_type);
+
+    #[cfg(target_os = "windows")]
+    return windows::detect(mount_point);
+
+    #[cfg(target_os = "macos")]
+    return macos::detect(mount_point);
+
+    #[allow(unreachable_code)]
+    Encryption::unknown()
+}
+
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn is_luks_header(header: &[u8]) -> bool {
+    header.starts_with(LUKS_MAGIC)
+}
+
+/// Encryption method named by a device-mapper UUID, e.g.
+/// `CRYPT-LUKS2-<uuid>-cryptroot`
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn dm_crypt_method(dm_uuid: &str) -> Option<&'static str> {
+    if dm_uuid.starts_with("CRYPT-LUKS") {
+        Some("LUKS")
+    } else if dm_uuid.starts_with("CRYPT-") {
+        Some("dm-crypt")
+    } else {
+        None
+    }
+}
+
+/// `manage-bde -status <volume>` output
+#[cfg_attr(not(any(test, target_os = "windows")), allow(dead_code))]
+fn parse_manage_bde(output: &str) -> Option<Encryption> {
+    let field = |name: &str| {
+        output.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            (key.trim() == name).then(|| value.trim().to_string())
+        })
+    };
+    let conversion = field("Conversion Status")?;
+    let protection = field("Protection Status").unwrap_or_default();
+
+    // Suspended protection still leaves the data encrypted on disk
+    Some(
+        if conversion == "Fully Encrypted" || protection.starts_with("Protection On") {
+            Encryption::encrypted("BitLocker")
+        } else if conversion == "Fully Decrypted" {
+            Encryption::unencrypted()
+        } else {
+            // Encryption or decryption in progress
+            Encryption {
+                status: "unknown",
+                method: "BitLocker".to_string(),
+            }
+        },
+    )
+}
+
+/// `diskutil info <mount point>` output
+#[cfg_attr(not(any(test, target_os = "macos")), allow(dead_code))]
+fn parse_diskutil(output: &str) -> Option<Encryption> {
+    output.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        if key.trim() != "FileVault" {
+            return None;
+        }
+        // "Yes", or "Yes (Unlocked)" on APFS data volumes
+        match value.trim() {
+            v if v.starts_with("Yes") => Some(Encryption::encrypted("FileVault")),
+            v if v.starts_with("No") => Some(Encryption::unencrypted()),
+            _ => None,
+        }
+    })
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::fs;
+    use std::io::Read;
+    use std::path::{Path, PathBuf};
+
+    use super::{Encryption, dm_crypt_method, is_luks_header};
+
+    /// Device-mapper stacks are rarely deeper than LVM on LUKS on RAID
+    const MAX_DEPTH: usize = 8;
+
+    pub fn detect(device: &str, fs_type: &str) -> Encryption {
+        if fs_type == "ecryptfs" {
+            return Encryption::encrypted("eCryptfs");
+        }
+        let Some(name) = block_name(device) else {
+            return Encryption::unknown();
+        };
+        match crypt_method(&name, 0) {
+            Some(method) => Encryption::encrypted(method),
+            None => Encryption::unencrypted(),
+        }
+    }
+
+    /// Kernel block device name, e.g. `/dev/mapper/root` -> `dm-0`
+    fn block_name(device: &str) -> Option<String> {
+        if !device.starts_with("/dev/") {
+            return None;
+        }
+        let path = fs::canonicalize(device).ok()?;
+        Some(path.file_name()?.to_string_lossy().into_owned())
+    }
+
+    /// sysfs directory of a disk or partition
+    fn sys_dir(name: &str) -> PathBuf {
+        Path::new("/sys/class/block").join(name)
+    }
+
+    fn crypt_method(name: &str, depth: usize) -> Option<&'static str> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let dir = sys_dir(name);
+        if let Ok(uuid) = fs::read_to_string(dir.join("dm/uuid"))
+            && let Some(method) = dm_crypt_method(uuid.trim())
+        {
+            return Some(method);
+        }
+
+        let slaves: Vec<String> = fs::read_dir(dir.join("slaves"))
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|e| e.file_name().to_string_lossy().into_owned())
+                    .collect()
+            })
+            .unwrap_or_default();
+        if slaves.is_empty() {
+            // A bare device only holds a LUKS header if it is opened elsewhere,
+            // but check anyway in case the mapping hides its dm UUID
+            return has_luks_header(name).then_some("LUKS");
+        }
+        // Encrypted only if every leg is (e.g. LVM spanning several PVs)
+        let methods = slaves
+            .iter()
+            .map(|slave| crypt_method(slave, depth + 1))
+            .collect::<Option<Vec<_>>>()?;
+        methods.first().copied()
+    }
+
+    /// Reading the header needs root; unreadable devices count as no header
+    fn has_luks_header(name: &str) -> bool {
+        let mut header = [0u8; 6];
+        fs::File::open(Path::new("/dev").join(name))
+            .and_then(|mut file| file.read_exact(&mut header))
+            .is_ok_and(|_| is_luks_header(&header))
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use super::*;
+
+    pub fn detect(mount_point: &str) -> Encryption {
+        let volume = mount_point.trim_end_matches('\\');
+        if volume.is_empty() {
+            return Encryption::unknown();
+        }
+
+        let mut cmd = Command::new("manage-bde");
+        cmd.args(["-status", volume]);
+        if let Some(output) = exec_with_timeout(cmd, COMMAND_TIMEOUT)
+            && output.status.success()
+            && let Some(encryption) = parse_manage_bde(&String::from_utf8_lossy(&output.stdout))
+        {
+            return encryption;
+        }
+
+        wmi_protection_status(volume)
+    }
+
+    /// 0 = unprotected, 1 = protected, 2 = unknown (e.g. locked)
+    fn wmi_protection_status(volume: &str) -> Encryption {
+        let script = format!(
+            "(Get-CimInstance -Namespace root\\cimv2\\security\\microsoftvolumeencryption \
+             -ClassName Win32_EncryptableVolume -Filter \"DriveLetter='{volume}'\").ProtectionStatus"
+        );
+        let mut cmd = Command::new("powershell");
+        cmd.args(["-NoProfile", "-Command", &script]);
+        let status = exec_with_timeout(cmd, COMMAND_TIMEOUT)
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+        match status.as_deref() {
+            Some("1") => Encryption::encrypted("BitLocker"),
+            Some("0") => Encryption::unencrypted(),
+            _ => Encryption::unknown(),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::*;
+
+    pub fn detect(mount_point: &str) -> Encryption {
+        let mut cmd = Command::new("diskutil");
+        cmd.args(["info", mount_point]);
+        if let Some(output) = exec_with_timeout(cmd, COMMAND_TIMEOUT)
+            && output.status.success()
+            && let Some(encryption) = parse_diskutil(&String::from_utf8_lossy(&output.stdout))
+        {
+            return encryption;
+        }
+
+        if mount_point != "/" {
+            return Encryption::unknown();
+        }
+        let mut cmd = Command::new("fdesetup");
+        cmd.arg("status");
+        match exec_with_timeout(cmd, COMMAND_TIMEOUT)
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+        {
+            Some(status) if status.contains("FileVault is On") => {
+                Encryption::encrypted("FileVault")
+            }
+            Some(status) if status.contains("FileVault is Off") => Encryption::unencrypted(),
+            _ => Encryption::unknown(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linux_markers() {
+        assert!(is_luks_header(b"LUKS\xba\xbe\x00\x02"));
+        assert!(!is_luks_header(b"\x00\x00\x00\x00\x00\x00"));
+        assert_eq!(
+            dm_crypt_method("CRYPT-LUKS2-3f1c0e2a9b7d4c55a1e2b3c4d5e6f708-cryptroot"),
+            Some("LUKS")
+        );
+        assert_eq!(dm_crypt_method("CRYPT-PLAIN-swap"), Some("dm-crypt"));
+        assert_eq!(dm_crypt_method("LVM-abcdef"), None);
+    }
+
+    #[test]
+    fn test_parse_manage_bde() {
+        let on = "Volume C: [OS]\n\
+                  \x20   Size:                 237.85 GB\n\
+                  \x20   Conversion Status:    Fully Encrypted\n\
+                  \x20   Percentage Encrypted: 100.0%\n\
+                  \x20   Protection Status:    Protection On\n";
+        assert_eq!(
+            parse_manage_bde(on),
+            Some(Encryption::encrypted("BitLocker"))
+        );
+
+        let off = "    Conversion Status:    Fully Decrypted\n\
+                   \x20   Protection Status:    Protection Off\n";
+        assert_eq!(parse_manage_bde(off), Some(Encryption::unencrypted()));
+        assert_eq!(parse_manage_bde("ERROR: not a volume"), None);
+    }
+
+    #[test]
+    fn test_parse_diskutil() {
+        let output = "   Volume Name:               Macintosh HD - Data\n\
+                      \x20  FileVault:                 Yes (Unlocked)\n";
+        assert_eq!(
+            parse_diskutil(output),
+            Some(Encryption::encrypted("FileVault"))
+        );
+        assert_eq!(
+            parse_diskutil("   FileVault:                 No\n"),
+            Some(Encryption::unencrypted())
+        );
+    }
+}
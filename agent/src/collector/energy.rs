@@ -0,0 +1,200 @@
+//! Energy consumption and cost estimation
+//!
+//! With `collector.energy.enabled`, power draw is integrated over time and
+//! reported as custom metrics (collector "energy"):
+//!
+//! - `energy.cpu_watts`: RAPL package + DRAM power; without readable RAPL
+//!   counters, a whole-host estimate interpolated between `idle_watts` and
+//!   `max_watts` by CPU usage (label `source`: rapl or estimate)
+//! - `energy.gpu_watts`: GPU board power as reported by the GPU collector
+//! - `energy.watts`: the sum, averaged over the interval
+//! - `energy.kwh` and `energy.cost`: totals since the agent started
+//! - `energy.cost_per_hour`: cost rate at the current draw
+//!
+//! Cost uses the configured `price_per_kwh` and is labelled with `currency`.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::config::{CollectorConfig, EnergyConfig};
+use crate::proto::CustomMetric;
+
+use super::rapl::{self, Domain};
+use super::registry::{CollectContext, Collector, Fragment, IntervalClass};
+
+/// Latest total GPU power in watts, recorded by the GPU collector
+static GPU_WATTS: AtomicU32 = AtomicU32::new(0);
+
+const JOULES_PER_KWH: f64 = 3_600_000.0;
+
+/// Record the combined power draw of all GPUs
+pub fn record_gpu_power(watts: u32) {
+    GPU_WATTS.store(watts, Ordering::Relaxed);
+}
+
+pub struct EnergyCollector {
+    config: EnergyConfig,
+    /// RAPL domains with their last counter reading
+    rapl: Vec<(Domain, Option<u64>)>,
+    last_run: Option<Instant>,
+    kwh: f64,
+    cost: f64,
+}
+
+impl EnergyCollector {
+    pub fn new(config: EnergyConfig) -> Self {
+        let rapl = rapl::domains()
+            .into_iter()
+            .map(|d| {
+                let reading = d.energy_uj();
+                (d, reading)
+            })
+            .collect();
+        Self {
+            config,
+            rapl,
+            last_run: None,
+            kwh: 0.0,
+            cost: 0.0,
+        }
+    }
+
+    /// CPU energy in joules from RAPL since the previous reading; `None` if
+    /// no counter is readable
+    fn rapl_joules(&mut self) -> Option<f64> {
+        let mut total_uj = 0;
+        let mut readable = false;
+        for (domain, previous) in &mut self.rapl {
+            let current = domain.energy_uj();
+            if let (Some(prev), Some(cur)) = (*previous, current) {
+                total_uj += domain.delta_uj(prev, cur);
+                readable = true;
+            }
+            *previous = current;
+        }
+        readable.then(|| total_uj as f64 / 1_000_000.0)
+    }
+
+    /// Whole-host power estimate from CPU usage
+    fn estimated_watts(&self, cpu_percent: f64) -> Option<f64> {
+        let (idle, max) = (self.config.idle_watts, self.config.max_watts);
+        (max > 0.0).then(|| idle + (max - idle).max(0.0) * cpu_percent.clamp(0.0, 100.0) / 100.0)
+    }
+
+    fn sample(&mut self, elapsed: Duration, cpu_percent: f64) -> Vec<CustomMetric> {
+        let secs = elapsed.as_secs_f64();
+        if secs <= 0.0 {
+            return Vec::new();
+        }
+
+        let (cpu_watts, source) = match self.rapl_joules() {
+            Some(joules) => (joules / secs, "rapl"),
+            None => match self.estimated_watts(cpu_percent) {
+                Some(watts) => (watts, "estimate"),
+                None => (0.0, "none"),
+            },
+        };
+        let gpu_watts = f64::from(GPU_WATTS.load(Ordering::Relaxed));
+        let watts = cpu_watts + gpu_watts;
+
+        let kwh = watts * secs / JOULES_PER_KWH;
+        self.kwh += kwh;
+        self.cost += kwh * self.config.price_per_kwh;
+
+        let metric = |name: &str, value: f64, unit: &str| CustomMetric {
+            collector: "energy".to_string(),
+            name: name.to_string(),
+            value,
+            unit: unit.to_string(),
+            labels: HashMap::new(),
+        };
+        let currency = &self.config.currency;
+        let mut cpu = metric("energy.cpu_watts", cpu_watts, "W");
+        cpu.labels.insert("source".to_string(), source.to_string());
+        let mut metrics = vec![
+            cpu,
+            metric("energy.gpu_watts", gpu_watts, "W"),
+            metric("energy.watts", watts, "W"),
+            metric("energy.kwh", self.kwh, "kWh"),
+            metric("energy.cost", self.cost, currency),
+            metric(
+                "energy.cost_per_hour",
+                watts / 1000.0 * self.config.price_per_kwh,
+                currency,
+            ),
+        ];
+        for m in &mut metrics[4..] {
+            m.labels.insert("currency".to_string(), currency.clone());
+        }
+        metrics
+    }
+}
+
+impl Collector for EnergyCollector {
+    fn name(&self) -> &'static str {
+        "energy"
+    }
+
+    fn interval(&self, _config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Periodic(Duration::from_millis(self.config.interval_ms))
+    }
+
+    fn collect(&mut self, ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        let now = Instant::now();
+        // The first run only takes the baseline readings
+        let metrics = match self.last_run {
+            Some(last) => self.sample(
+                now.duration_since(last),
+                f64::from(ctx.system.global_cpu_usage()),
+            ),
+            None => Vec::new(),
+        };
+        self.last_run = Some(now);
+        Ok(Fragment::Custom(metrics))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_and_cost() {
+        let mut collector = EnergyCollector {
+            config: EnergyConfig {
+                enabled: true,
+                price_per_kwh: 0.30,
+                currency: "EUR".to_string(),
+                idle_watts: 50.0,
+                max_watts: 250.0,
+                ..Default::default()
+            },
+            rapl: Vec::new(),
+            last_run: None,
+            kwh: 0.0,
+            cost: 0.0,
+        };
+
+        // 50% CPU for an hour: 150 W
+        let metrics = collector.sample(Duration::from_secs(3600), 50.0);
+        let find = |metrics: &[CustomMetric], name: &str| {
+            metrics.iter().find(|m| m.name == name).unwrap().clone()
+        };
+        let cpu = find(&metrics, "energy.cpu_watts");
+        assert_eq!(cpu.value, 150.0);
+        assert_eq!(cpu.labels["source"], "estimate");
+        let watts = find(&metrics, "energy.watts").value;
+        let kwh = find(&metrics, "energy.kwh").value;
+        assert!((kwh - watts / 1000.0).abs() < 1e-9);
+        let cost = find(&metrics, "energy.cost");
+        assert!((cost.value - kwh * 0.30).abs() < 1e-9);
+        assert_eq!(cost.labels["currency"], "EUR");
+
+        // Totals accumulate across intervals
+        let metrics = collector.sample(Duration::from_secs(3600), 50.0);
+        let watts_2 = find(&metrics, "energy.watts").value;
+        let total = find(&metrics, "energy.kwh").value;
+        assert!((total - (watts + watts_2) / 1000.0).abs() < 1e-9);
+    }
+}
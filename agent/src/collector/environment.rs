@@ -0,0 +1,368 @@
+//! Virtualization and cloud environment detection
+//!
+//! Classifies the host as bare metal or a VM from its SMBIOS/DMI identity
+//! (`/sys/class/dmi/id` on Linux, WMI on Windows, sysctl on macOS) and
+//! recognises AWS, GCP and Azure instances. With
+//! `collector.enable_cloud_metadata` the provider's instance metadata
+//! service (IMDS) is asked for the instance ID, type, region and account.
+//! Only the detected provider's IMDS is contacted, so bare metal and
+//! on-premises VMs never wait for 169.254.169.254 to time out.
+//!
+//! The environment can't change while the agent runs, so it is detected once.
+
+use std::net::SocketAddr;
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use std::process::Command;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use serde_json::Value;
+use tracing::debug;
+
+use crate::proto::HostEnvironment;
+use crate::utils::http;
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use crate::utils::safe_command::exec_with_timeout;
+
+/// Link-local address shared by the AWS, GCP and Azure metadata services
+const IMDS_ADDR: &str = "169.254.169.254:80";
+
+/// IMDS answers within milliseconds when it exists at all
+const IMDS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// SMBIOS asset tag of every Azure VM
+const AZURE_ASSET_TAG: &str = "7783-7084-3265-9085-8269-3286-77";
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+static ENVIRONMENT: OnceLock<HostEnvironment> = OnceLock::new();
+
+/// SMBIOS identity strings
+#[derive(Debug, Default)]
+struct Dmi {
+    sys_vendor: String,
+    product_name: String,
+    bios_vendor: String,
+    bios_version: String,
+    asset_tag: String,
+    /// CPUID hypervisor bit
+    hypervisor_flag: bool,
+}
+
+/// Host environment, detected on first use
+pub fn detect(fetch_metadata: bool) -> HostEnvironment {
+    ENVIRONMENT
+        .get_or_init(|| {
+            let mut env = classify(&read_dmi());
+            if fetch_metadata && !env.cloud_provider.is_empty() {
+                match fetch_instance_metadata(&env.cloud_provider) {
+                    Some(metadata) => merge(&mut env, metadata),
+                    None => debug!("No instance metadata from {}", env.cloud_provider),
+                }
+            }
+            debug!(
+                "Host environment: {} {} {}",
+                env.kind, env.hypervisor, env.cloud_provider
+            );
+            env
+        })
+        .clone()
+}
+
+/// Map SMBIOS identity to hypervisor and cloud provider
+fn classify(dmi: &Dmi) -> HostEnvironment {
+    let haystack = format!(
+        "{}|{}|{}|{}",
+        dmi.sys_vendor, dmi.product_name, dmi.bios_vendor, dmi.bios_version
+    )
+    .to_lowercase();
+    let has = |needle: &str| haystack.contains(needle);
+
+    let (hypervisor, cloud) = if has("amazon ec2") || (has("amazon") && has("xen")) {
+        // Nitro instances report "Amazon EC2", older Xen ones "amazon" in the BIOS version
+        (if has("xen") { "xen" } else { "kvm" }, "aws")
+    } else if has("google") {
+        ("kvm", "gcp")
+    } else if dmi.asset_tag == AZURE_ASSET_TAG {
+        ("hyper-v", "azure")
+    } else if has("microsoft corporation") && has("virtual machine") {
+        ("hyper-v", "")
+    } else if has("vmware") {
+        ("vmware", "")
+    } else if has("virtualbox") || has("innotek") {
+        ("virtualbox", "")
+    } else if has("parallels") {
+        ("parallels", "")
+    } else if has("xen") {
+        ("xen", "")
+    } else if has("qemu") || has("kvm") || has("bochs") {
+        ("kvm", "")
+    } else if dmi.hypervisor_flag {
+        ("unknown", "")
+    } else {
+        ("", "")
+    };
+
+    HostEnvironment {
+        kind: if hypervisor.is_empty() {
+            "bare-metal"
+        } else {
+            "vm"
+        }
+        .to_string(),
+        hypervisor: hypervisor.to_string(),
+        cloud_provider: cloud.to_string(),
+        ..Default::default()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn read_dmi() -> Dmi {
+    let read = |name: &str| {
+        std::fs::read_to_string(format!("/sys/class/dmi/id/{name}"))
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default()
+    };
+    let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+    Dmi {
+        sys_vendor: read("sys_vendor"),
+        product_name: read("product_name"),
+        bios_vendor: read("bios_vendor"),
+        bios_version: read("bios_version"),
+        // Root-only on some distributions
+        asset_tag: read("chassis_asset_tag"),
+        hypervisor_flag: cpuinfo
+            .lines()
+            .filter(|l| l.starts_with("flags"))
+            .any(|l| l.split_whitespace().any(|f| f == "hypervisor")),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn read_dmi() -> Dmi {
+    const SCRIPT: &str = r#"
+$cs = Get-CimInstance Win32_ComputerSystem
+$bios = Get-CimInstance Win32_BIOS
+$enc = Get-CimInstance Win32_SystemEnclosure
+"sys_vendor=" + $cs.Manufacturer
+"product_name=" + $cs.Model
+"bios_vendor=" + $bios.Manufacturer
+"bios_version=" + $bios.SMBIOSBIOSVersion
+"asset_tag=" + $enc.SMBIOSAssetTag
+"#;
+    let mut cmd = Command::new("powershell");
+    cmd.args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT]);
+    let output = exec_with_timeout(cmd, COMMAND_TIMEOUT)
+        .map(|o| String::from_utf8_lossy(&o.stdout).into_owned())
+        .unwrap_or_default();
+
+    let mut dmi = Dmi::default();
+    for (key, value) in output.lines().filter_map(|l| l.trim().split_once('=')) {
+        let value = value.trim().to_string();
+        match key {
+            "sys_vendor" => dmi.sys_vendor = value,
+            "product_name" => dmi.product_name = value,
+            "bios_vendor" => dmi.bios_vendor = value,
+            "bios_version" => dmi.bios_version = value,
+            "asset_tag" => dmi.asset_tag = value,
+            _ => {}
+        }
+    }
+    dmi
+}
+
+#[cfg(target_os = "macos")]
+fn read_dmi() -> Dmi {
+    let sysctl = |name: &str| {
+        let mut cmd = Command::new("sysctl");
+        cmd.args(["-n", name]);
+        exec_with_timeout(cmd, COMMAND_TIMEOUT)
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+            .unwrap_or_default()
+    };
+    Dmi {
+        product_name: sysctl("hw.model"),
+        hypervisor_flag: sysctl("kern.hv_vmm_present") == "1",
+        ..Default::default()
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+fn read_dmi() -> Dmi {
+    Dmi::default()
+}
+
+/// Query the provider's IMDS
+fn fetch_instance_metadata(provider: &str) -> Option<HostEnvironment> {
+    match provider {
+        "aws" => {
+            // IMDSv2: session token first
+            let token = imds_request(
+                "PUT",
+                "/latest/api/token",
+                &[("X-aws-ec2-metadata-token-ttl-seconds", "60")],
+            )?;
+            let document = imds_request(
+                "GET",
+                "/latest/dynamic/instance-identity/document",
+                &[("X-aws-ec2-metadata-token", token.trim())],
+            )?;
+            parse_aws(&document)
+        }
+        "gcp" => imds_request(
+            "GET",
+            "/computeMetadata/v1/instance/?recursive=true",
+            &[("Metadata-Flavor", "Google")],
+        )
+        .and_then(|body| parse_gcp(&body)),
+        "azure" => imds_request(
+            "GET",
+            "/metadata/instance/compute?api-version=2021-02-01",
+            &[("Metadata", "true")],
+        )
+        .and_then(|body| parse_azure(&body)),
+        _ => None,
+    }
+}
+
+fn merge(env: &mut HostEnvironment, metadata: HostEnvironment) {
+    env.instance_id = metadata.instance_id;
+    env.instance_type = metadata.instance_type;
+    env.region = metadata.region;
+    env.zone = metadata.zone;
+    env.account_id = metadata.account_id;
+}
+
+/// IMDS request; returns the body of a 200 response
+fn imds_request(method: &str, path: &str, headers: &[(&str, &str)]) -> Option<String> {
+    let addr: SocketAddr = IMDS_ADDR.parse().ok()?;
+    let response =
+        http::request(method, addr, "169.254.169.254", path, headers, IMDS_TIMEOUT).ok()?;
+    (response.status == 200).then(|| response.text())
+}
+
+fn json_str(value: &Value, key: &str) -> String {
+    match &value[key] {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Last segment of a GCP resource path, e.g. `projects/1/zones/us-east1-b`
+fn last_segment(path: &str) -> String {
+    path.rsplit('/').next().unwrap_or_default().to_string()
+}
+
+fn parse_aws(document: &str) -> Option<HostEnvironment> {
+    let doc: Value = serde_json::from_str(document).ok()?;
+    Some(HostEnvironment {
+        instance_id: json_str(&doc, "instanceId"),
+        instance_type: json_str(&doc, "instanceType"),
+        region: json_str(&doc, "region"),
+        zone: json_str(&doc, "availabilityZone"),
+        account_id: json_str(&doc, "accountId"),
+        ..Default::default()
+    })
+}
+
+fn parse_gcp(body: &str) -> Option<HostEnvironment> {
+    let doc: Value = serde_json::from_str(body).ok()?;
+    let zone_path = json_str(&doc, "zone");
+    let zone = last_segment(&zone_path);
+    // us-central1-a -> us-central1
+    let region = zone
+        .rsplit_once('-')
+        .map(|(region, _)| region.to_string())
+        .unwrap_or_default();
+    // projects/<project number>/zones/<zone>
+    let project = zone_path.split('/').nth(1).unwrap_or_default().to_string();
+    Some(HostEnvironment {
+        instance_id: json_str(&doc, "id"),
+        instance_type: last_segment(&json_str(&doc, "machineType")),
+        region,
+        zone,
+        account_id: project,
+        ..Default::default()
+    })
+}
+
+fn parse_azure(body: &str) -> Option<HostEnvironment> {
+    let doc: Value = serde_json::from_str(body).ok()?;
+    Some(HostEnvironment {
+        instance_id: json_str(&doc, "vmId"),
+        instance_type: json_str(&doc, "vmSize"),
+        region: json_str(&doc, "location"),
+        zone: json_str(&doc, "zone"),
+        account_id: json_str(&doc, "subscriptionId"),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dmi(sys_vendor: &str, product_name: &str) -> Dmi {
+        Dmi {
+            sys_vendor: sys_vendor.to_string(),
+            product_name: product_name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        let env = classify(&dmi("Amazon EC2", "m6i.large"));
+        assert_eq!(
+            (env.hypervisor.as_str(), env.cloud_provider.as_str()),
+            ("kvm", "aws")
+        );
+        let env = classify(&dmi("Google", "Google Compute Engine"));
+        assert_eq!(env.cloud_provider, "gcp");
+        let env = classify(&dmi("VMware, Inc.", "VMware Virtual Platform"));
+        assert_eq!(
+            (env.kind.as_str(), env.hypervisor.as_str()),
+            ("vm", "vmware")
+        );
+        let env = classify(&dmi("QEMU", "Standard PC (Q35 + ICH9, 2009)"));
+        assert_eq!(env.hypervisor, "kvm");
+
+        let mut azure = dmi("Microsoft Corporation", "Virtual Machine");
+        assert_eq!(classify(&azure).cloud_provider, "");
+        azure.asset_tag = AZURE_ASSET_TAG.to_string();
+        assert_eq!(classify(&azure).cloud_provider, "azure");
+
+        let env = classify(&dmi("Dell Inc.", "PowerEdge R740"));
+        assert_eq!(env.kind, "bare-metal");
+        let mut unknown = dmi("Dell Inc.", "PowerEdge R740");
+        unknown.hypervisor_flag = true;
+        assert_eq!(classify(&unknown).hypervisor, "unknown");
+    }
+
+    #[test]
+    fn test_parse_metadata() {
+        let aws = r#"{"accountId":"123456789012","availabilityZone":"eu-west-1a",
+            "instanceId":"i-0abc","instanceType":"t3.micro","region":"eu-west-1"}"#;
+        let env = parse_aws(aws).unwrap();
+        assert_eq!(env.instance_id, "i-0abc");
+        assert_eq!(env.zone, "eu-west-1a");
+        assert_eq!(env.account_id, "123456789012");
+
+        let gcp = r#"{"id":4520031799277581759,
+            "machineType":"projects/998877/machineTypes/e2-medium",
+            "zone":"projects/998877/zones/us-central1-a"}"#;
+        let env = parse_gcp(gcp).unwrap();
+        assert_eq!(env.instance_id, "4520031799277581759");
+        assert_eq!(env.instance_type, "e2-medium");
+        assert_eq!(env.region, "us-central1");
+        assert_eq!(env.account_id, "998877");
+
+        let azure = r#"{"vmId":"02aab8a4","vmSize":"Standard_D2s_v3",
+            "location":"westeurope","zone":"1","subscriptionId":"8d10da13"}"#;
+        let env = parse_azure(azure).unwrap();
+        assert_eq!(env.instance_type, "Standard_D2s_v3");
+        assert_eq!(env.region, "westeurope");
+    }
+}
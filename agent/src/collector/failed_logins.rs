@@ -0,0 +1,331 @@
+//! Failed login collector
+//!
+//! Summarizes failed SSH/RDP login attempts since the previous collection so
+//! the server can alert on brute-force activity.
+//!
+//! - Linux: new lines of `/var/log/auth.log` or `/var/log/secure`, else the
+//!   sshd entries of the systemd journal
+//! - macOS: sshd entries of the unified log
+//! - Windows: Security event log, event 4625 (an account failed to log on)
+
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+
+use crate::proto::{FailedLoginSource, FailedLoginSummary};
+use crate::utils::safe_command::exec_with_timeout;
+
+/// Number of source addresses reported, ordered by attempts
+const TOP_SOURCES: usize = 10;
+
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A single failed attempt
+#[derive(Debug, Clone, PartialEq)]
+struct FailedLogin {
+    ip: String,
+    user: String,
+    service: &'static str,
+    count: u32,
+}
+
+/// Failed login collector
+pub struct FailedLoginCollector {
+    last_run: Instant,
+    #[cfg(target_os = "linux")]
+    log_offset: Option<(std::path::PathBuf, u64)>,
+    #[cfg(target_os = "linux")]
+    journal_cursor: Option<String>,
+    #[cfg(windows)]
+    last_run_utc: chrono::DateTime<chrono::Utc>,
+}
+
+impl FailedLoginCollector {
+    pub fn new() -> Self {
+        let mut collector = Self {
+            last_run: Instant::now(),
+            #[cfg(target_os = "linux")]
+            log_offset: None,
+            #[cfg(target_os = "linux")]
+            journal_cursor: None,
+            #[cfg(windows)]
+            last_run_utc: chrono::Utc::now(),
+        };
+        // Start counting from now, not from the beginning of the log
+        #[cfg(target_os = "linux")]
+        collector.read_linux();
+        collector
+    }
+
+    pub fn collect(&mut self) -> FailedLoginSummary {
+        let window = self.last_run.elapsed();
+        let attempts = self.read_attempts(window);
+        self.last_run = Instant::now();
+        summarize(&attempts, window)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn read_attempts(&mut self, _window: Duration) -> Vec<FailedLogin> {
+        self.read_linux()
+    }
+
+    /// Read new sshd lines from the auth log, or from the journal when the
+    /// distribution has no syslog daemon
+    #[cfg(target_os = "linux")]
+    fn read_linux(&mut self) -> Vec<FailedLogin> {
+        use std::io::{Read, Seek, SeekFrom};
+
+        // Upper bound for one read, so a flood cannot exhaust memory
+        const MAX_READ: u64 = 8 * 1024 * 1024;
+
+        let log = ["/var/log/auth.log", "/var/log/secure"]
+            .iter()
+            .map(std::path::Path::new)
+            .find(|p| p.exists());
+
+        if let Some(path) = log {
+            let Ok(mut file) = std::fs::File::open(path) else {
+                return Vec::new();
+            };
+            let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+            let offset = match &self.log_offset {
+                // Truncated or rotated: start over
+                Some((known, offset)) if known == path && *offset <= len => *offset,
+                Some(_) => 0,
+                None => len,
+            };
+
+            let mut buf = Vec::new();
+            if file.seek(SeekFrom::Start(offset)).is_ok() {
+                let _ = file.take(MAX_READ).read_to_end(&mut buf);
+            }
+            // A partially written last line is read again next time
+            let complete = buf.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+            self.log_offset = Some((path.to_path_buf(), offset + complete as u64));
+            return String::from_utf8_lossy(&buf[..complete])
+                .lines()
+                .filter_map(parse_sshd_line)
+                .collect();
+        }
+
+        let mut cmd = Command::new("journalctl");
+        cmd.args(["-q", "--no-pager", "-o", "cat", "--show-cursor"]);
+        match &self.journal_cursor {
+            Some(cursor) => cmd.arg(format!("--after-cursor={cursor}")),
+            None => cmd.args(["-n", "1"]),
+        };
+        cmd.args(["_COMM=sshd", "_COMM=sshd-session"]);
+
+        let Some(output) = exec_with_timeout(cmd, COMMAND_TIMEOUT) else {
+            return Vec::new();
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut attempts = Vec::new();
+        for line in stdout.lines() {
+            if let Some(cursor) = line.strip_prefix("-- cursor: ") {
+                self.journal_cursor = Some(cursor.to_string());
+            } else if self.journal_cursor.is_some() {
+                attempts.extend(parse_sshd_line(line));
+            }
+        }
+        attempts
+    }
+
+    #[cfg(target_os = "macos")]
+    fn read_attempts(&mut self, window: Duration) -> Vec<FailedLogin> {
+        let mut cmd = Command::new("log");
+        cmd.args([
+            "show",
+            "--style",
+            "compact",
+            "--last",
+            &format!("{}s", window.as_secs().max(1)),
+            "--predicate",
+            "process == \"sshd\" OR process == \"sshd-session\"",
+        ]);
+        exec_with_timeout(cmd, COMMAND_TIMEOUT)
+            .map(|output| {
+                String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .filter_map(parse_sshd_line)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    #[cfg(windows)]
+    fn read_attempts(&mut self, _window: Duration) -> Vec<FailedLogin> {
+        let since = self.last_run_utc;
+        self.last_run_utc = chrono::Utc::now();
+
+        // Properties: 5 = TargetUserName, 10 = LogonType, 19 = IpAddress
+        let script = format!(
+            "Get-WinEvent -FilterHashtable @{{LogName='Security';Id=4625;StartTime=[datetime]::Parse('{}').ToLocalTime()}} -ErrorAction SilentlyContinue \
+             | ForEach-Object {{ \"$($_.Properties[5].Value),$($_.Properties[19].Value),$($_.Properties[10].Value)\" }}",
+            since.to_rfc3339()
+        );
+        let mut cmd = Command::new("powershell");
+        cmd.args(["-NoProfile", "-Command", &script]);
+        exec_with_timeout(cmd, COMMAND_TIMEOUT)
+            .map(|output| parse_security_events(&String::from_utf8_lossy(&output.stdout)))
+            .unwrap_or_default()
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+    fn read_attempts(&mut self, _window: Duration) -> Vec<FailedLogin> {
+        Vec::new()
+    }
+}
+
+impl Default for FailedLoginCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parse one sshd log message
+#[cfg_attr(windows, allow(dead_code))]
+fn parse_sshd_line(line: &str) -> Option<FailedLogin> {
+    static FAILED: OnceLock<Regex> = OnceLock::new();
+    static REPEATED: OnceLock<Regex> = OnceLock::new();
+
+    let failed = FAILED.get_or_init(|| {
+        Regex::new(
+            r"Failed (?:password|keyboard-interactive/pam|none) for (?:invalid user )?(\S*) from (\S+) port",
+        )
+        .unwrap()
+    });
+    let caps = failed.captures(line)?;
+
+    // rsyslog folds duplicates into "message repeated N times: [ ... ]"
+    let repeated = REPEATED.get_or_init(|| Regex::new(r"message repeated (\d+) times").unwrap());
+    let count = repeated
+        .captures(line)
+        .and_then(|c| c[1].parse().ok())
+        .unwrap_or(1);
+
+    Some(FailedLogin {
+        user: caps[1].to_string(),
+        ip: caps[2].to_string(),
+        service: "ssh",
+        count,
+    })
+}
+
+/// Parse `user,ip,logon type` lines of event 4625
+#[cfg_attr(not(any(test, windows)), allow(dead_code))]
+fn parse_security_events(output: &str) -> Vec<FailedLogin> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.trim().rsplitn(3, ',');
+            let logon_type = fields.next()?;
+            let ip = fields.next()?;
+            let user = fields.next()?;
+            let service = match logon_type {
+                "10" => "rdp",
+                "3" => "network",
+                "2" => "local",
+                _ => "other",
+            };
+            Some(FailedLogin {
+                ip: if ip == "-" {
+                    String::new()
+                } else {
+                    ip.to_string()
+                },
+                user: user.to_string(),
+                service,
+                count: 1,
+            })
+        })
+        .collect()
+}
+
+/// Count attempts per source address, keeping the top offenders
+fn summarize(attempts: &[FailedLogin], window: Duration) -> FailedLoginSummary {
+    let mut sources: HashMap<&str, FailedLoginSource> = HashMap::new();
+    for attempt in attempts {
+        let source = sources
+            .entry(&attempt.ip)
+            .or_insert_with(|| FailedLoginSource {
+                ip: attempt.ip.clone(),
+                service: attempt.service.to_string(),
+                ..Default::default()
+            });
+        source.count += attempt.count;
+        source.last_user = attempt.user.clone();
+    }
+
+    let unique_sources = sources.len() as u32;
+    let mut top_sources: Vec<_> = sources.into_values().collect();
+    top_sources.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.ip.cmp(&b.ip)));
+    top_sources.truncate(TOP_SOURCES);
+
+    FailedLoginSummary {
+        window_seconds: window.as_secs() as u32,
+        total: attempts.iter().map(|a| a.count).sum(),
+        unique_sources,
+        top_sources,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sshd_line() {
+        let line = "Mar  3 10:12:01 web1 sshd[4242]: Failed password for invalid user admin from 203.0.113.9 port 52514 ssh2";
+        let attempt = parse_sshd_line(line).unwrap();
+        assert_eq!(attempt.user, "admin");
+        assert_eq!(attempt.ip, "203.0.113.9");
+        assert_eq!(attempt.count, 1);
+
+        let line = "sshd[77]: message repeated 5 times: [ Failed password for root from 2001:db8::7 port 40022 ssh2]";
+        let attempt = parse_sshd_line(line).unwrap();
+        assert_eq!(attempt.user, "root");
+        assert_eq!(attempt.ip, "2001:db8::7");
+        assert_eq!(attempt.count, 5);
+
+        assert!(
+            parse_sshd_line("Accepted publickey for deploy from 10.0.0.5 port 4000 ssh2").is_none()
+        );
+    }
+
+    #[test]
+    fn test_parse_security_events() {
+        let attempts = parse_security_events("Administrator,198.51.100.4,10\r\nguest,-,2\r\n");
+        assert_eq!(attempts.len(), 2);
+        assert_eq!(attempts[0].service, "rdp");
+        assert_eq!(attempts[0].ip, "198.51.100.4");
+        assert_eq!(attempts[1].ip, "");
+        assert_eq!(attempts[1].service, "local");
+    }
+
+    #[test]
+    fn test_summarize() {
+        let attempt = |ip: &str, user: &str, count| FailedLogin {
+            ip: ip.to_string(),
+            user: user.to_string(),
+            service: "ssh",
+            count,
+        };
+        let attempts = [
+            attempt("203.0.113.9", "root", 3),
+            attempt("198.51.100.4", "admin", 1),
+            attempt("203.0.113.9", "oracle", 1),
+        ];
+
+        let summary = summarize(&attempts, Duration::from_secs(60));
+        assert_eq!(summary.window_seconds, 60);
+        assert_eq!(summary.total, 5);
+        assert_eq!(summary.unique_sources, 2);
+        assert_eq!(summary.top_sources[0].ip, "203.0.113.9");
+        assert_eq!(summary.top_sources[0].count, 4);
+        assert_eq!(summary.top_sources[0].last_user, "oracle");
+    }
+}
@@ -0,0 +1,358 @@
+//! Disk-full forecasting
+//!
+//! Every disk usage collection appends the used bytes of each filesystem to
+//! a local history covering `collector.disk_forecast.window_hours`. Samples
+//! are spaced evenly over the window, and the history is kept in
+//! `/var/lib/nanolink/disk_history.json` (`%ProgramData%\nanolink` on
+//! Windows), so restarts don't throw the trend away.
+//!
+//! A linear and an exponential trend are fitted to each history. The one that
+//! matches it better gives `DiskUsage.days_until_full`. When the estimate
+//! drops below `alert_days`, a `disk_forecast` warning event is published.
+//! A resolution event follows once the estimate recovers.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::config::DiskForecastConfig;
+use crate::proto::{DiskUsage, LogBatch, LogEntry};
+
+use super::syslog;
+
+/// Samples kept per filesystem over the window
+const MAX_SAMPLES: u64 = 288;
+
+const SECS_PER_DAY: f64 = 86_400.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Sample {
+    /// Unix time in seconds
+    time: u64,
+    used: u64,
+}
+
+/// Trend fitted to a filesystem's history
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Forecast {
+    days_until_full: f64,
+    bytes_per_day: f64,
+    model: &'static str,
+}
+
+pub struct DiskForecaster {
+    config: DiskForecastConfig,
+    path: Option<PathBuf>,
+    history: HashMap<String, VecDeque<Sample>>,
+    /// Filesystems currently under `alert_days`
+    alerting: HashMap<String, bool>,
+}
+
+impl DiskForecaster {
+    pub fn new(config: DiskForecastConfig) -> Self {
+        let path = (!cfg!(test)).then(history_file);
+        let history = path.as_ref().map(load).unwrap_or_default();
+        Self {
+            config,
+            path,
+            history,
+            alerting: HashMap::new(),
+        }
+    }
+
+    fn window_secs(&self) -> u64 {
+        self.config.window_hours * 3600
+    }
+
+    /// Record `disks` at `now` and fill in their forecasts
+    pub fn update(&mut self, disks: &mut [DiskUsage], now: u64) {
+        if !self.config.enabled {
+            return;
+        }
+        let window = self.window_secs();
+        let spacing = window / MAX_SAMPLES;
+        let mut changed = false;
+        for disk in disks.iter_mut() {
+            let samples = self.history.entry(disk.mount_point.clone()).or_default();
+            if samples.back().is_none_or(|last| now >= last.time + spacing) {
+                samples.push_back(Sample {
+                    time: now,
+                    used: disk.used,
+                });
+                changed = true;
+            }
+            while samples
+                .front()
+                .is_some_and(|s| now.saturating_sub(s.time) > window)
+            {
+                samples.pop_front();
+            }
+
+            let forecast = (samples.len() >= self.config.min_samples)
+                .then(|| fit(samples, disk.used, disk.total))
+                .flatten();
+            if let Some(forecast) = forecast {
+                disk.days_until_full = forecast.days_until_full;
+                disk.growth_bytes_per_day = forecast.bytes_per_day;
+                disk.forecast_model = forecast.model.to_string();
+            }
+            self.check_alert(disk, forecast);
+        }
+
+        // Forget filesystems that have been gone for a whole window
+        let before = self.history.len();
+        self.history.retain(|_, samples| {
+            samples
+                .back()
+                .is_some_and(|s| now.saturating_sub(s.time) <= window)
+        });
+        if changed || self.history.len() != before {
+            self.save();
+        }
+    }
+
+    fn check_alert(&mut self, disk: &DiskUsage, forecast: Option<Forecast>) {
+        let alerting = self
+            .alerting
+            .entry(disk.mount_point.clone())
+            .or_insert(false);
+        let days = forecast.map(|f| f.days_until_full);
+        let (state, message) = match days {
+            Some(days) if !*alerting && days < self.config.alert_days => {
+                *alerting = true;
+                (
+                    "firing",
+                    format!(
+                        "{} expected to be full in {:.1} days",
+                        disk.mount_point, days
+                    ),
+                )
+            }
+            // Some headroom so an estimate hovering at the threshold doesn't flap
+            _ if *alerting && days.is_none_or(|d| d > self.config.alert_days * 1.5) => {
+                *alerting = false;
+                (
+                    "resolved",
+                    format!("{} no longer expected to fill up soon", disk.mount_point),
+                )
+            }
+            _ => return,
+        };
+        info!("Disk forecast {}: {}", state, message);
+
+        let mut metadata = HashMap::from([
+            ("mount_point".to_string(), disk.mount_point.clone()),
+            ("device".to_string(), disk.device.clone()),
+            ("state".to_string(), state.to_string()),
+            ("used".to_string(), disk.used.to_string()),
+            ("total".to_string(), disk.total.to_string()),
+        ]);
+        if let Some(forecast) = forecast {
+            metadata.insert(
+                "days_until_full".to_string(),
+                format!("{:.2}", forecast.days_until_full),
+            );
+            metadata.insert("model".to_string(), forecast.model.to_string());
+        }
+        syslog::publish(LogBatch {
+            source: "disk_forecast".to_string(),
+            entries: vec![LogEntry {
+                timestamp: chrono::Utc::now().to_rfc3339(),
+                level: if state == "firing" { "warning" } else { "info" }.to_string(),
+                source: "disk_forecast".to_string(),
+                message,
+                metadata,
+            }],
+            dropped: 0,
+        });
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let written = serde_json::to_vec(&self.history)
+            .map_err(std::io::Error::other)
+            .and_then(|json| {
+                path.parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .and_then(|_| std::fs::write(path, json))
+            });
+        if let Err(e) = written {
+            debug!("Failed to save disk history to {:?}: {}", path, e);
+        }
+    }
+}
+
+fn history_file() -> PathBuf {
+    #[cfg(windows)]
+    {
+        let base = std::env::var("ProgramData").unwrap_or_else(|_| "C:\\ProgramData".to_string());
+        PathBuf::from(base)
+            .join("nanolink")
+            .join("disk_history.json")
+    }
+    #[cfg(unix)]
+    {
+        PathBuf::from("/var/lib/nanolink/disk_history.json")
+    }
+}
+
+fn load(path: &PathBuf) -> HashMap<String, VecDeque<Sample>> {
+    let Ok(json) = std::fs::read(path) else {
+        return HashMap::new();
+    };
+    serde_json::from_slice(&json).unwrap_or_else(|e| {
+        warn!("Ignoring unreadable disk history {:?}: {}", path, e);
+        HashMap::new()
+    })
+}
+
+/// Least-squares slope and intercept of `y` over `x`
+fn regression(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let (mut sxy, mut sxx) = (0.0, 0.0);
+    for (x, y) in points {
+        sxy += (x - mean_x) * (y - mean_y);
+        sxx += (x - mean_x) * (x - mean_x);
+    }
+    (sxx > 0.0).then(|| {
+        let slope = sxy / sxx;
+        (slope, mean_y - slope * mean_x)
+    })
+}
+
+/// Fit both trends and extrapolate the better one to `total`; `None` if usage
+/// isn't growing
+fn fit(samples: &VecDeque<Sample>, used: u64, total: u64) -> Option<Forecast> {
+    let start = samples.front()?.time;
+    let days = |s: &Sample| (s.time - start) as f64 / SECS_PER_DAY;
+    let linear_points: Vec<_> = samples.iter().map(|s| (days(s), s.used as f64)).collect();
+    let (slope, intercept) = regression(&linear_points)?;
+    if slope <= 0.0 {
+        return None;
+    }
+    let remaining = total.saturating_sub(used) as f64;
+    let sse = |predict: &dyn Fn(f64) -> f64| {
+        linear_points
+            .iter()
+            .map(|(x, y)| (y - predict(*x)).powi(2))
+            .sum::<f64>()
+    };
+    let linear = Forecast {
+        days_until_full: remaining / slope,
+        bytes_per_day: slope,
+        model: "linear",
+    };
+    let linear_sse = sse(&|x| intercept + slope * x);
+
+    if samples.iter().any(|s| s.used == 0) || used == 0 {
+        return Some(linear);
+    }
+    let log_points: Vec<_> = samples
+        .iter()
+        .map(|s| (days(s), (s.used as f64).ln()))
+        .collect();
+    let exponential =
+        regression(&log_points)
+            .filter(|(rate, _)| *rate > 0.0)
+            .map(|(rate, log_intercept)| {
+                let forecast = Forecast {
+                    days_until_full: (total as f64 / used as f64).ln().max(0.0) / rate,
+                    bytes_per_day: used as f64 * rate,
+                    model: "exponential",
+                };
+                (forecast, sse(&|x| (log_intercept + rate * x).exp()))
+            });
+    match exponential {
+        Some((forecast, exp_sse)) if exp_sse < linear_sse => Some(forecast),
+        _ => Some(linear),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GB: u64 = 1 << 30;
+
+    fn disk(used: u64) -> DiskUsage {
+        DiskUsage {
+            device: "/dev/sda1".to_string(),
+            mount_point: "/".to_string(),
+            total: 100 * GB,
+            used,
+            available: 100 * GB - used,
+            ..Default::default()
+        }
+    }
+
+    fn samples(points: impl Iterator<Item = (u64, u64)>) -> VecDeque<Sample> {
+        points.map(|(time, used)| Sample { time, used }).collect()
+    }
+
+    #[test]
+    fn test_linear_fit() {
+        // 1 GB a day, 50 GB used of 100
+        let history = samples((0..10).map(|d| (d * 86_400, (40 + d) * GB)));
+        let forecast = fit(&history, 49 * GB, 100 * GB).unwrap();
+        assert_eq!(forecast.model, "linear");
+        assert!((forecast.bytes_per_day - GB as f64).abs() < 1.0);
+        assert!((forecast.days_until_full - 51.0).abs() < 0.01);
+
+        // Shrinking or flat usage has no estimate
+        let history = samples((0..10).map(|d| (d * 86_400, (40 - d) * GB)));
+        assert_eq!(fit(&history, 31 * GB, 100 * GB), None);
+    }
+
+    #[test]
+    fn test_exponential_fit() {
+        // Doubling every 10 days from 1 GB
+        let history = samples((0..30).map(|d| {
+            let used = GB as f64 * 2f64.powf(d as f64 / 10.0);
+            (d * 86_400, used as u64)
+        }));
+        let used = history.back().unwrap().used;
+        let forecast = fit(&history, used, 100 * GB).unwrap();
+        assert_eq!(forecast.model, "exponential");
+        // ~7.4 GB used, 100 GB reached after log2(100 / 7.4) * 10 days
+        let expected = (100.0 * GB as f64 / used as f64).log2() * 10.0;
+        assert!((forecast.days_until_full - expected).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_update_spacing_and_alert() {
+        let mut forecaster = DiskForecaster::new(DiskForecastConfig {
+            min_samples: 4,
+            alert_days: 7.0,
+            ..Default::default()
+        });
+        let spacing = forecaster.window_secs() / MAX_SAMPLES;
+
+        // Collections closer than the spacing don't add samples
+        let mut disks = [disk(90 * GB)];
+        forecaster.update(&mut disks, 1_000);
+        forecaster.update(&mut disks, 1_001);
+        assert_eq!(forecaster.history["/"].len(), 1);
+        assert_eq!(disks[0].days_until_full, 0.0);
+
+        // 1 GB per sample: full in a few hours
+        for i in 1..4 {
+            disks = [disk((90 + i) * GB)];
+            forecaster.update(&mut disks, 1_000 + i * spacing);
+        }
+        assert_eq!(forecaster.history["/"].len(), 4);
+        assert!(disks[0].days_until_full > 0.0 && disks[0].days_until_full < 1.0);
+        assert_eq!(disks[0].forecast_model, "linear");
+        assert!(forecaster.alerting["/"]);
+
+        // Usage drops back: the alert resolves
+        disks = [disk(10 * GB)];
+        forecaster.update(&mut disks, 1_000 + 4 * spacing);
+        assert!(!forecaster.alerting["/"]);
+    }
+}
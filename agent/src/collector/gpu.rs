@@ -50,6 +50,18 @@ pub struct GpuCollector {
 
 impl GpuCollector {
     pub fn new() -> Self {
+        // Built without the `gpu` feature: no vendor tools are probed
+        if !cfg!(feature = "gpu") {
+            return Self {
+                nvidia_available: false,
+                amd_available: false,
+                intel_gpu_top_available: false,
+                xpu_smi_available: false,
+                driver_version: String::new(),
+                cached_metrics: RwLock::new(None),
+            };
+        }
+
         let nvidia_available = Self::check_nvidia_available();
         let amd_available = Self::check_amd_available();
         let driver_version = if nvidia_available {
@@ -158,6 +170,10 @@ impl GpuCollector {
     }
 
     pub fn collect(&self) -> Vec<GpuMetrics> {
+        if !cfg!(feature = "gpu") {
+            return Vec::new();
+        }
+
         // Check cache first to avoid expensive nvidia-smi calls
         {
             let cache = self.cached_metrics.read();
@@ -478,8 +494,6 @@ impl GpuCollector {
     /// Collect Intel GPU metrics using xpu-smi (for Arc/Data Center GPUs)
     #[cfg(target_os = "linux")]
     fn collect_intel_xpu_smi(&self) -> Option<Vec<GpuMetrics>> {
-        use std::collections::HashMap;
-
         // Get device list first
         let mut cmd = Command::new("xpu-smi");
         cmd.args(["discovery", "-j"]);
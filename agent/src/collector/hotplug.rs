@@ -0,0 +1,135 @@
+//! Mount/unmount detection
+//!
+//! Disks are otherwise only re-enumerated on the periodic refresh, so a newly
+//! attached USB or iSCSI volume could take minutes to show up. The watcher
+//! signals every mount table change so the layered collector can refresh
+//! static info immediately and report a [`DiskChange`].
+//!
+//! - Linux: `poll()` on `/proc/self/mounts`, which the kernel wakes with
+//!   `POLLPRI` on every mount/unmount (the same mechanism udev and systemd use)
+//! - Windows/macOS: the mount point list is compared every
+//!   `POLL_INTERVAL`; this avoids a window message loop for
+//!   `WM_DEVICECHANGE` and DiskArbitration bindings
+
+#[cfg(not(target_os = "linux"))]
+use std::time::Duration;
+
+use tokio::sync::watch;
+
+use crate::proto::{DiskChange, DiskChangeKind, DiskStaticInfo};
+
+use super::watcher::ChangeWatcher;
+
+/// Mount list polling interval where no change notification is available
+#[cfg(not(target_os = "linux"))]
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+static WATCHER: ChangeWatcher = ChangeWatcher::new("mount-watcher", run);
+
+/// Subscribe to mount table changes
+pub fn subscribe() -> watch::Receiver<u64> {
+    WATCHER.subscribe()
+}
+
+#[cfg(target_os = "linux")]
+fn run(watcher: &ChangeWatcher) -> anyhow::Result<()> {
+    use nix::poll::{PollFd, PollFlags, PollTimeout, poll};
+    use std::os::fd::AsFd;
+
+    let mounts = std::fs::File::open("/proc/self/mounts")?;
+
+    loop {
+        let mut fds = [PollFd::new(mounts.as_fd(), PollFlags::POLLPRI)];
+        match poll(&mut fds, PollTimeout::NONE) {
+            Ok(_) => {}
+            Err(nix::errno::Errno::EINTR) => continue,
+            Err(e) => return Err(e.into()),
+        }
+
+        let changed = fds[0]
+            .revents()
+            .is_some_and(|r| r.intersects(PollFlags::POLLPRI | PollFlags::POLLERR));
+        if changed {
+            watcher.settle();
+            watcher.notify();
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn run(watcher: &ChangeWatcher) -> anyhow::Result<()> {
+    fn mount_points(disks: &sysinfo::Disks) -> Vec<std::path::PathBuf> {
+        let mut points: Vec<_> = disks
+            .list()
+            .iter()
+            .map(|d| d.mount_point().to_path_buf())
+            .collect();
+        points.sort();
+        points
+    }
+
+    let mut disks =
+        sysinfo::Disks::new_with_refreshed_list_specifics(sysinfo::DiskRefreshKind::nothing());
+    let mut known = mount_points(&disks);
+
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        disks.refresh_specifics(true, sysinfo::DiskRefreshKind::nothing());
+        let current = mount_points(&disks);
+        if current != known {
+            known = current;
+            watcher.settle();
+            watcher.notify();
+        }
+    }
+}
+
+/// Compare two disk lists by mount point
+pub fn diff(old: &[DiskStaticInfo], new: &[DiskStaticInfo]) -> Vec<DiskChange> {
+    let change = |kind: DiskChangeKind, d: &DiskStaticInfo| DiskChange {
+        kind: kind as i32,
+        device: d.device.clone(),
+        mount_point: d.mount_point.clone(),
+        fs_type: d.fs_type.clone(),
+        total_bytes: d.total_bytes,
+    };
+
+    let mounted = new
+        .iter()
+        .filter(|d| !old.iter().any(|o| o.mount_point == d.mount_point))
+        .map(|d| change(DiskChangeKind::DiskMounted, d));
+    let unmounted = old
+        .iter()
+        .filter(|o| !new.iter().any(|d| d.mount_point == o.mount_point))
+        .map(|o| change(DiskChangeKind::DiskUnmounted, o));
+
+    mounted.chain(unmounted).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn disk(device: &str, mount_point: &str) -> DiskStaticInfo {
+        DiskStaticInfo {
+            device: device.to_string(),
+            mount_point: mount_point.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = vec![disk("/dev/sda1", "/"), disk("/dev/sdb1", "/mnt/backup")];
+        let new = vec![disk("/dev/sda1", "/"), disk("/dev/sdc1", "/media/usb")];
+
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].kind, DiskChangeKind::DiskMounted as i32);
+        assert_eq!(changes[0].mount_point, "/media/usb");
+        assert_eq!(changes[1].kind, DiskChangeKind::DiskUnmounted as i32);
+        assert_eq!(changes[1].device, "/dev/sdb1");
+
+        assert!(diff(&new, &new).is_empty());
+    }
+}
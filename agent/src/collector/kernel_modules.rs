@@ -0,0 +1,306 @@
+//! Kernel module and driver inventory
+//!
+//! Off by default (`collector.kernel_modules.enabled`). Lists what runs in
+//! the kernel, for security posture reporting:
+//!
+//! - Linux: modules from `/proc/modules` with size, the taint flags each
+//!   one caused and `MODULE_VERSION`. A module is unsigned when it tainted
+//!   the kernel with `E`; on kernels without module signing the signature
+//!   status is unknown. The kernel's taint mask comes from
+//!   `/proc/sys/kernel/tainted`, decoded into flags.
+//! - Windows: running kernel drivers with file version and Authenticode
+//!   status (catalog signatures included) and signer. Signature enforcement
+//!   is off when booted with test signing or without integrity checks.
+//! - macOS: not supported.
+
+use std::time::Duration;
+
+use crate::config::{CollectorConfig, KernelModuleConfig};
+use crate::proto::{KernelModule, KernelModules};
+
+use super::registry::{CollectContext, Collector, Fragment, IntervalClass};
+
+/// Kernel taint bits, see Documentation/admin-guide/tainted-kernels.rst
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+const TAINT_FLAGS: &[(char, &str)] = &[
+    ('P', "proprietary module loaded"),
+    ('F', "module force loaded"),
+    ('S', "kernel running on an out of specification system"),
+    ('R', "module force unloaded"),
+    ('M', "processor reported a machine check exception"),
+    ('B', "bad page referenced or unexpected page flags"),
+    ('U', "taint requested by userspace"),
+    ('D', "kernel died recently (oops or BUG)"),
+    ('A', "ACPI table overridden by user"),
+    ('W', "kernel issued a warning"),
+    ('C', "staging driver loaded"),
+    ('I', "workaround for a platform firmware bug applied"),
+    ('O', "externally built (out-of-tree) module loaded"),
+    ('E', "unsigned module loaded"),
+    ('L', "soft lockup occurred"),
+    ('K', "kernel live patched"),
+    ('X', "auxiliary taint (distribution defined)"),
+    ('T', "kernel built with the struct randomization plugin"),
+    ('N', "in-kernel test run"),
+];
+
+pub struct KernelModuleCollector {
+    config: KernelModuleConfig,
+}
+
+impl KernelModuleCollector {
+    pub fn new(config: KernelModuleConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Collector for KernelModuleCollector {
+    fn name(&self) -> &'static str {
+        "kernel_modules"
+    }
+
+    fn interval(&self, _config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Periodic(Duration::from_millis(self.config.interval_ms))
+    }
+
+    fn collect(&mut self, _ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        Ok(Fragment::KernelModules(scan()))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn scan() -> KernelModules {
+    use std::fs;
+
+    let read = |path: &str| fs::read_to_string(path).map(|s| s.trim().to_string());
+    // The parameter only exists on kernels with module signing
+    let sig_enforce = read("/sys/module/module/parameters/sig_enforce").ok();
+    let mut modules = parse_proc_modules(&read("/proc/modules").unwrap_or_default());
+    for module in &mut modules {
+        module.version = read(&format!("/sys/module/{}/version", module.name)).unwrap_or_default();
+        module.signature = match (&sig_enforce, module.taint.contains('E')) {
+            (None, _) => "unknown",
+            (Some(_), true) => "unsigned",
+            (Some(_), false) => "signed",
+        }
+        .to_string();
+    }
+
+    let taint = read("/proc/sys/kernel/tainted")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0);
+    KernelModules {
+        modules,
+        taint,
+        taint_flags: taint_flags(taint),
+        signature_enforced: sig_enforce.as_deref() == Some("Y"),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn scan() -> KernelModules {
+    use std::process::Command;
+
+    use crate::utils::safe_command::exec_with_timeout;
+
+    const SCRIPT: &str = r#"
+$ErrorActionPreference = 'SilentlyContinue'
+$drivers = @(Get-CimInstance Win32_SystemDriver -Filter "State='Running'" | ForEach-Object {
+  $path = $_.PathName -replace '^\\\?\?\\', '' -replace '^\\SystemRoot', $env:SystemRoot
+  if ($path -and -not [IO.Path]::IsPathRooted($path)) { $path = Join-Path $env:SystemRoot $path }
+  $sig = Get-AuthenticodeSignature -FilePath $path
+  [pscustomobject]@{ name = $_.Name; path = $path; version = [string](Get-Item $path).VersionInfo.FileVersion
+    status = [string]$sig.Status; signer = [string]$sig.SignerCertificate.Subject }
+})
+[pscustomobject]@{
+  drivers = $drivers
+  start_options = [string](Get-ItemProperty 'HKLM:\SYSTEM\CurrentControlSet\Control').SystemStartOptions
+} | ConvertTo-Json -Depth 3 -Compress
+"#;
+    let mut cmd = Command::new("powershell");
+    cmd.args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT]);
+    exec_with_timeout(cmd, Duration::from_secs(180))
+        .and_then(|o| serde_json::from_slice(&o.stdout).ok())
+        .map(|json| parse_windows_drivers(&json))
+        .unwrap_or_default()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn scan() -> KernelModules {
+    KernelModules::default()
+}
+
+/// Modules in `/proc/modules`: `name size refs deps state address [(taint)]`
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn parse_proc_modules(text: &str) -> Vec<KernelModule> {
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (name, size) = (fields.first()?, fields.get(1)?.parse().ok()?);
+            let taint = fields
+                .get(6)
+                .and_then(|t| t.strip_prefix('(')?.strip_suffix(')'))
+                .unwrap_or_default();
+            Some(KernelModule {
+                name: name.to_string(),
+                size,
+                taint: taint.to_string(),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Set bits of the kernel taint mask as "<flag>: <meaning>"
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn taint_flags(taint: u64) -> Vec<String> {
+    (0..64)
+        .filter(|bit| taint & (1 << bit) != 0)
+        .map(|bit| match TAINT_FLAGS.get(bit) {
+            Some((flag, meaning)) => format!("{flag}: {meaning}"),
+            None => format!("bit {bit}"),
+        })
+        .collect()
+}
+
+/// Running drivers and boot options from the driver script
+#[cfg_attr(not(any(test, target_os = "windows")), allow(dead_code))]
+fn parse_windows_drivers(json: &serde_json::Value) -> KernelModules {
+    use serde_json::Value;
+
+    // ConvertTo-Json unwraps single-item arrays
+    let drivers = match &json["drivers"] {
+        Value::Array(drivers) => drivers.iter().collect(),
+        Value::Null => Vec::new(),
+        driver => vec![driver],
+    };
+    let text = |value: &Value| value.as_str().unwrap_or_default().trim().to_string();
+
+    let modules = drivers
+        .into_iter()
+        .map(|driver| {
+            let signature = match driver["status"].as_str().unwrap_or_default() {
+                "Valid" => "signed",
+                "NotSigned" => "unsigned",
+                "" | "UnknownError" => "unknown",
+                // HashMismatch, NotTrusted, Incompatible
+                _ => "invalid",
+            };
+            // "CN=Microsoft Windows, O=Microsoft Corporation, ...", values
+            // with commas are quoted
+            let subject = text(&driver["signer"]);
+            let signer = subject
+                .find("CN=")
+                .map(|i| {
+                    let cn = &subject[i + 3..];
+                    match cn.strip_prefix('"') {
+                        Some(quoted) => quoted.split('"').next().unwrap_or_default(),
+                        None => cn.split(',').next().unwrap_or_default(),
+                    }
+                })
+                .unwrap_or_default();
+            KernelModule {
+                name: text(&driver["name"]),
+                version: text(&driver["version"]),
+                signature: signature.to_string(),
+                signer: signer.to_string(),
+                path: text(&driver["path"]),
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    let options = text(&json["start_options"]).to_uppercase();
+    KernelModules {
+        modules,
+        signature_enforced: !options.contains("TESTSIGNING")
+            && !options.contains("DISABLE_INTEGRITY_CHECKS"),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_modules() {
+        let text = "\
+nvidia_uvm 1531904 0 - Live 0x0000000000000000 (POE)
+nvidia 56823808 1 nvidia_uvm, Live 0x0000000000000000 (POE)
+ext4 1081344 2 - Live 0x0000000000000000
+vboxdrv 696320 0 - Loading 0x0000000000000000 (OE)
+";
+        let modules = parse_proc_modules(text);
+        assert_eq!(modules.len(), 4);
+        assert_eq!(
+            (
+                modules[1].name.as_str(),
+                modules[1].size,
+                modules[1].taint.as_str()
+            ),
+            ("nvidia", 56823808, "POE")
+        );
+        assert!(modules[2].taint.is_empty());
+        assert_eq!(modules[3].taint, "OE");
+    }
+
+    #[test]
+    fn test_taint_flags() {
+        assert!(taint_flags(0).is_empty());
+        // P, O and E: a proprietary out-of-tree unsigned module
+        assert_eq!(
+            taint_flags(4097 | 8192),
+            [
+                "P: proprietary module loaded",
+                "O: externally built (out-of-tree) module loaded",
+                "E: unsigned module loaded"
+            ]
+        );
+        assert_eq!(taint_flags(1 << 40), ["bit 40"]);
+    }
+
+    #[test]
+    fn test_parse_windows_drivers() {
+        let json: serde_json::Value = serde_json::from_str(
+            r#"{
+              "drivers": [
+                {"name": "ACPI", "path": "C:\\Windows\\system32\\drivers\\ACPI.sys", "version": "10.0.19041.1",
+                 "status": "Valid", "signer": "CN=Microsoft Windows, O=Microsoft Corporation, L=Redmond, C=US"},
+                {"name": "cheatdrv", "path": "C:\\tools\\cheatdrv.sys", "version": "",
+                 "status": "NotSigned", "signer": ""},
+                {"name": "patched", "path": "C:\\Windows\\system32\\drivers\\patched.sys", "version": "1.0",
+                 "status": "HashMismatch", "signer": "CN=\"Vendor, Inc.\""}
+              ],
+              "start_options": " NOEXECUTE=OPTIN  TESTSIGNING"
+            }"#,
+        )
+        .unwrap();
+        let inventory = parse_windows_drivers(&json);
+        assert!(!inventory.signature_enforced);
+
+        let acpi = &inventory.modules[0];
+        assert_eq!(
+            (acpi.signature.as_str(), acpi.signer.as_str()),
+            ("signed", "Microsoft Windows")
+        );
+        assert_eq!(acpi.version, "10.0.19041.1");
+        assert_eq!(inventory.modules[1].signature, "unsigned");
+        assert_eq!(
+            (
+                inventory.modules[2].signature.as_str(),
+                inventory.modules[2].signer.as_str()
+            ),
+            ("invalid", "Vendor, Inc.")
+        );
+
+        let single: serde_json::Value = serde_json::from_str(
+            r#"{"drivers": {"name": "ACPI", "status": "Valid"}, "start_options": "NOEXECUTE=OPTIN"}"#,
+        )
+        .unwrap();
+        let inventory = parse_windows_drivers(&single);
+        assert!(inventory.signature_enforced);
+        assert_eq!(inventory.modules.len(), 1);
+    }
+}
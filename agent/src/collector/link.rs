@@ -0,0 +1,99 @@
+//! Network link-state change detection
+//!
+//! Signals interface up/down, address and speed changes as they happen so
+//! the layered collector can push a `NetworkAddressUpdate` without waiting
+//! for the `ip_check_interval_ms` polling cycle.
+//!
+//! - Linux: rtnetlink multicast groups for links and IPv4/IPv6 addresses
+//! - Windows/macOS: the interface list and addresses are compared every
+//!   `POLL_INTERVAL` (no `NotifyIpInterfaceChange`/SCDynamicStore bindings)
+
+#[cfg(not(target_os = "linux"))]
+use std::time::Duration;
+
+use tokio::sync::watch;
+
+use super::watcher::ChangeWatcher;
+
+/// Interface list polling interval where no change notification is available
+#[cfg(not(target_os = "linux"))]
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+static WATCHER: ChangeWatcher = ChangeWatcher::new("link-watcher", run);
+
+/// Subscribe to network link changes
+pub fn subscribe() -> watch::Receiver<u64> {
+    WATCHER.subscribe()
+}
+
+#[cfg(target_os = "linux")]
+fn run(watcher: &ChangeWatcher) -> anyhow::Result<()> {
+    use nix::errno::Errno;
+    use nix::sys::socket::{
+        AddressFamily, MsgFlags, NetlinkAddr, SockFlag, SockProtocol, SockType, bind, recv, socket,
+    };
+    use std::os::fd::AsRawFd;
+
+    // RTMGRP_LINK | RTMGRP_IPV4_IFADDR | RTMGRP_IPV6_IFADDR
+    const GROUPS: u32 = 0x1 | 0x10 | 0x100;
+
+    let sock = socket(
+        AddressFamily::Netlink,
+        SockType::Raw,
+        SockFlag::SOCK_CLOEXEC,
+        SockProtocol::NetlinkRoute,
+    )?;
+    bind(sock.as_raw_fd(), &NetlinkAddr::new(0, GROUPS))?;
+
+    let mut buf = vec![0u8; 16 * 1024];
+    loop {
+        match recv(sock.as_raw_fd(), &mut buf, MsgFlags::empty()) {
+            Ok(_) => {}
+            Err(Errno::EINTR) => continue,
+            // Kernel buffer overrun: events were lost, so state changed
+            Err(Errno::ENOBUFS) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        // The message content is not needed: the collector re-reads the
+        // interface state. Drain whatever arrived while settling.
+        watcher.settle();
+        while recv(sock.as_raw_fd(), &mut buf, MsgFlags::MSG_DONTWAIT).is_ok() {}
+        watcher.notify();
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn run(watcher: &ChangeWatcher) -> anyhow::Result<()> {
+    fn snapshot(networks: &sysinfo::Networks) -> Vec<(String, Vec<String>)> {
+        let mut interfaces: Vec<_> = networks
+            .list()
+            .iter()
+            .map(|(name, data)| {
+                let mut addrs: Vec<String> = data
+                    .ip_networks()
+                    .iter()
+                    .map(|ip| ip.addr.to_string())
+                    .collect();
+                addrs.sort();
+                (name.clone(), addrs)
+            })
+            .collect();
+        interfaces.sort();
+        interfaces
+    }
+
+    let mut networks = sysinfo::Networks::new_with_refreshed_list();
+    let mut known = snapshot(&networks);
+
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        networks.refresh(true);
+        let current = snapshot(&networks);
+        if current != known {
+            known = current;
+            watcher.settle();
+            watcher.notify();
+        }
+    }
+}
@@ -1,94 +1,110 @@
+mod anomaly;
+mod boot;
+mod capture;
+mod cgroups;
+mod core;
 mod cpu;
+mod dir_growth;
 mod disk;
+mod encryption;
+mod energy;
+mod environment;
+mod failed_logins;
+mod forecast;
 mod gpu;
+mod hotplug;
+mod kernel_modules;
 pub mod layered;
+mod link;
 mod memory;
 mod network;
 mod npu;
+mod peripherals;
+mod printers;
+mod process_net;
+mod public_ip;
+mod rapl;
+pub mod registry;
+mod routes;
+#[cfg(feature = "rules")]
+mod rules;
+mod sensors;
+mod session_watch;
 mod sessions;
+mod ssh_keys;
+pub mod statsd;
+pub mod syslog;
 mod system;
+mod temperature;
+mod textfile;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod watcher;
 
 use std::sync::Arc;
 use std::time::Duration;
-use sysinfo::{Disks, Networks, System};
+use tokio::sync::watch;
 use tokio::time;
-use tracing::{debug, error, info};
+use tracing::{debug, info};
 
 use crate::buffer::RingBuffer;
 use crate::config::Config;
-use crate::proto::Metrics;
+use crate::proto::{Metrics, PeriodicData, UserSession};
+use crate::telemetry::telemetry;
+use crate::utils::clock;
 
 pub use cpu::CpuCollector;
 pub use disk::DiskCollector;
 pub use gpu::GpuCollector;
 pub use memory::MemoryCollector;
 pub use network::NetworkCollector;
-pub use npu::NpuCollector;
-pub use sessions::SessionCollector;
 pub use system::SystemInfoCollector;
 
+use self::core::CollectionCore;
+
 /// System metrics collector
 ///
 /// Collects CPU, memory, disk, network, GPU, NPU, and user session metrics at configurable intervals.
+/// This is the legacy (non-layered) pipeline: a thin scheduler over [`CollectionCore`] that pushes
+/// full metrics into the ring buffer.
 pub struct MetricsCollector {
-    config: Arc<Config>,
+    core: CollectionCore,
     buffer: Arc<RingBuffer>,
-    system: System,
-    disks: Disks,
-    networks: Networks,
-    hostname: String,
-    cpu_collector: CpuCollector,
-    memory_collector: MemoryCollector,
-    disk_collector: DiskCollector,
-    network_collector: NetworkCollector,
-    gpu_collector: GpuCollector,
-    npu_collector: NpuCollector,
-    session_collector: SessionCollector,
-    system_info_collector: SystemInfoCollector,
 }
 
 impl MetricsCollector {
     /// Create a new metrics collector
     pub fn new(config: Arc<Config>, buffer: Arc<RingBuffer>) -> Self {
-        let hostname = config.get_hostname();
-        let mut system = System::new_all();
-        system.refresh_all();
-
         Self {
-            config: config.clone(),
+            core: CollectionCore::new(config),
             buffer,
-            system,
-            disks: Disks::new_with_refreshed_list(),
-            networks: Networks::new_with_refreshed_list(),
-            hostname: hostname.clone(),
-            cpu_collector: CpuCollector::new(),
-            memory_collector: MemoryCollector::new(),
-            disk_collector: DiskCollector::new(),
-            network_collector: NetworkCollector::new(),
-            gpu_collector: GpuCollector::new(),
-            npu_collector: NpuCollector::new(),
-            session_collector: SessionCollector::new(),
-            system_info_collector: SystemInfoCollector::with_hostname(
-                config.agent.hostname.clone(),
-            ),
         }
     }
 
     /// Run the metrics collector loop
     pub async fn run(mut self) {
-        let interval = Duration::from_millis(self.config.collector.cpu_interval_ms);
-        let mut ticker = time::interval(interval);
+        let interval_ms = self.core.config().collector.cpu_interval_ms;
+        let mut ticker = time::interval(Duration::from_millis(interval_ms));
 
         info!(
-            "Metrics collector started (interval: {}ms)",
-            self.config.collector.cpu_interval_ms
+            "Metrics collector started (interval: {}ms, collectors: [{}])",
+            interval_ms,
+            self.core.registry().names().collect::<Vec<_>>().join(", ")
         );
 
-        loop {
-            ticker.tick().await;
+        // Session event hooks run here: this pipeline exists once per
+        // process, while layered collectors exist once per connection
+        let hook = self.core.config().collector.session_event_hook.clone();
+        let mut session_changes = hook.as_ref().map(|_| session_watch::subscribe());
+        let mut sessions = match hook {
+            Some(_) => self.collect_sessions(),
+            None => Vec::new(),
+        };
 
-            match self.collect_metrics() {
-                Ok(metrics) => {
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let metrics = self.core.collect_full(false);
                     debug!(
                         "Collected metrics: CPU={:.1}%, MEM={:.1}%, GPUs={}, NPUs={}, Sessions={}",
                         metrics.cpu.as_ref().map(|c| c.usage_percent).unwrap_or(0.0),
@@ -107,133 +123,46 @@ impl MetricsCollector {
                         metrics.npus.len(),
                         metrics.user_sessions.len()
                     );
+                    telemetry().record_sample();
                     self.buffer.push(metrics);
                 }
-                Err(e) => {
-                    error!("Failed to collect metrics: {}", e);
+
+                Some(()) = changed(&mut session_changes) => {
+                    let current = self.collect_sessions();
+                    let events = session_watch::diff(&sessions, &current, clock::now_millis());
+                    sessions = current;
+                    if let Some(hook) = &hook {
+                        for event in &events {
+                            session_watch::run_hook(hook, event);
+                        }
+                    }
                 }
             }
         }
     }
 
-    /// Collect all metrics
-    fn collect_metrics(&mut self) -> anyhow::Result<Metrics> {
-        // Refresh system info
-        self.system.refresh_cpu_all();
-        self.system.refresh_memory();
-
-        let timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)?
-            .as_millis() as u64;
-
-        // Collect CPU metrics
-        let cpu = self
-            .cpu_collector
-            .collect(&self.system, &self.config.collector);
-
-        // Collect memory metrics
-        let memory = self.memory_collector.collect(&self.system);
-
-        // Collect disk metrics
-        self.disks.refresh(false);
-        let disks = self
-            .disk_collector
-            .collect(&self.disks, &self.config.collector);
-
-        // Collect network metrics
-        self.networks.refresh(false);
-        let networks = self
-            .network_collector
-            .collect(&self.networks, &self.config.collector);
-
-        // Collect GPU metrics
-        let gpu_metrics = self.gpu_collector.collect();
-        let gpus: Vec<_> = gpu_metrics
-            .into_iter()
-            .map(|g| crate::proto::GpuMetrics {
-                index: g.index,
-                name: g.name,
-                vendor: g.vendor,
-                usage_percent: g.usage_percent,
-                memory_total: g.memory_total,
-                memory_used: g.memory_used,
-                temperature: g.temperature,
-                fan_speed_percent: g.fan_speed_percent,
-                power_watts: g.power_watts,
-                power_limit_watts: g.power_limit_watts,
-                clock_core_mhz: g.clock_core_mhz,
-                clock_memory_mhz: g.clock_memory_mhz,
-                driver_version: g.driver_version,
-                pcie_generation: g.pcie_generation,
-                encoder_usage: g.encoder_usage,
-                decoder_usage: g.decoder_usage,
-            })
-            .collect();
-
-        // Collect NPU metrics
-        let npu_metrics = self.npu_collector.collect();
-        let npus: Vec<_> = npu_metrics
-            .into_iter()
-            .map(|n| crate::proto::NpuMetrics {
-                index: n.index,
-                name: n.name,
-                vendor: n.vendor,
-                usage_percent: n.usage_percent,
-                memory_total: n.memory_total,
-                memory_used: n.memory_used,
-                temperature: n.temperature,
-                power_watts: n.power_watts,
-                driver_version: n.driver_version,
-            })
-            .collect();
-
-        // Collect user sessions
-        let session_data = self.session_collector.collect();
-        let user_sessions: Vec<_> = session_data
-            .into_iter()
-            .map(|s| crate::proto::UserSession {
-                username: s.username,
-                tty: s.tty,
-                login_time: s.login_time,
-                remote_host: s.remote_host,
-                idle_seconds: s.idle_seconds,
-                session_type: s.session_type,
-            })
-            .collect();
-
-        // Collect system info
-        let system_info = self.system_info_collector.collect();
-
-        // Get load average (Unix only)
-        let load_average = self.get_load_average();
-
-        Ok(Metrics {
-            timestamp,
-            cpu: Some(cpu),
-            memory: Some(memory),
-            disks,
-            networks,
-            load_average,
-            hostname: self.hostname.clone(),
-            gpus,
-            system_info: Some(system_info),
-            user_sessions,
-            npus,
-            metrics_type: crate::proto::MetricsType::MetricsFull as i32,
-            is_initial: false,
-        })
+    fn collect_sessions(&mut self) -> Vec<UserSession> {
+        let mut periodic = PeriodicData::default();
+        self.core.collect_one_periodic("sessions", &mut periodic);
+        periodic.user_sessions
     }
+}
 
-    /// Get system load average (Unix only)
-    #[cfg(unix)]
-    fn get_load_average(&self) -> Vec<f64> {
-        let load = System::load_average();
-        vec![load.one, load.five, load.fifteen]
-    }
+/// One full collection, as answered to `DataRequest::Full`
+///
+/// For one-off use outside the agent loop (`nanolink-agent snapshot`). Rates
+/// and CPU usage need two samples, so this takes one interval to return.
+pub fn collect_once(config: Arc<Config>) -> Metrics {
+    let mut core = CollectionCore::new(config);
+    core.collect_full(true);
+    std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL.max(Duration::from_secs(1)));
+    core.collect_full(true)
+}
 
-    #[cfg(windows)]
-    fn get_load_average(&self) -> Vec<f64> {
-        // Windows doesn't have load average, return empty
-        vec![]
+/// Wait for a change on an optional watch channel (never resolves if `None`)
+async fn changed(rx: &mut Option<watch::Receiver<u64>>) -> Option<()> {
+    match rx {
+        Some(rx) => rx.changed().await.ok(),
+        None => std::future::pending().await,
     }
 }
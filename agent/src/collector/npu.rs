@@ -37,6 +37,13 @@ pub struct NpuCollector {
 
 impl NpuCollector {
     pub fn new() -> Self {
+        // Built without the `npu` feature: no vendor tools are probed
+        if !cfg!(feature = "npu") {
+            return Self {
+                intel_available: false,
+                huawei_available: false,
+            };
+        }
         Self {
             intel_available: Self::check_intel_npu_available(),
             huawei_available: Self::check_huawei_npu_available(),
@@ -0,0 +1,790 @@
+//! Monitor, input device and camera inventory
+//!
+//! Part of static info for IT asset management on desktop fleets, enabled
+//! with `collector.enable_peripherals`.
+//!
+//! - Linux: connected DRM connectors and their EDID, `/proc/bus/input/devices`
+//!   and video4linux devices. A kernel uevent socket signals hotplug of
+//!   monitors, input devices and cameras.
+//! - Windows: WMI (`WmiMonitorID` and friends, `Win32_Keyboard`,
+//!   `Win32_PointingDevice`, camera `Win32_PnPEntity`)
+//! - macOS: `system_profiler`
+//!
+//! Windows and macOS have no notification bindings here, so the inventory is
+//! compared every `POLL_INTERVAL` instead.
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+use std::process::Command;
+#[cfg(not(target_os = "linux"))]
+use std::time::Duration;
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+use serde_json::Value;
+use tokio::sync::watch;
+
+use crate::config::CollectorConfig;
+use crate::proto::{CameraInfo, DisplayInfo, InputDeviceInfo, Peripherals};
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+use crate::utils::safe_command::exec_with_timeout;
+
+use super::registry::{CollectContext, Collector, Fragment, IntervalClass};
+use super::watcher::ChangeWatcher;
+
+/// Inventory polling interval where no change notification is available
+#[cfg(not(target_os = "linux"))]
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(20);
+
+static WATCHER: ChangeWatcher = ChangeWatcher::new("device-watcher", run);
+
+/// Subscribe to monitor, input device and camera hotplug
+pub fn subscribe() -> watch::Receiver<u64> {
+    WATCHER.subscribe()
+}
+
+/// Rescans only after a hotplug, since the scan spawns processes on
+/// Windows and macOS and full metrics are collected every interval
+pub struct PeripheralCollector {
+    changes: watch::Receiver<u64>,
+    inventory: Option<Peripherals>,
+}
+
+impl PeripheralCollector {
+    pub fn new() -> Self {
+        Self {
+            changes: subscribe(),
+            inventory: None,
+        }
+    }
+}
+
+impl Collector for PeripheralCollector {
+    fn name(&self) -> &'static str {
+        "peripherals"
+    }
+
+    fn interval(&self, _config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Static
+    }
+
+    fn collect(&mut self, _ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        let changed = self.changes.has_changed().unwrap_or(false);
+        if changed || self.inventory.is_none() {
+            self.changes.mark_unchanged();
+            self.inventory = Some(scan());
+        }
+        Ok(Fragment::Peripherals(
+            self.inventory.clone().unwrap_or_default(),
+        ))
+    }
+}
+
+/// Fields of an EDID base block
+#[derive(Debug, Default, PartialEq)]
+struct Edid {
+    manufacturer: String,
+    model: String,
+    serial: String,
+    width_px: u32,
+    height_px: u32,
+    diagonal_inches: f32,
+    year: u32,
+}
+
+/// Parse the 128-byte EDID base block
+fn parse_edid(data: &[u8]) -> Option<Edid> {
+    const HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+    if data.len() < 128 || data[..8] != HEADER {
+        return None;
+    }
+
+    // Three 5-bit letters, 1 = 'A'
+    let id = u16::from_be_bytes([data[8], data[9]]);
+    let manufacturer: String = [10, 5, 0]
+        .iter()
+        .map(|shift| char::from(b'A' - 1 + ((id >> shift) & 0x1f) as u8))
+        .collect();
+    let mut edid = Edid {
+        manufacturer,
+        serial: match u32::from_le_bytes([data[12], data[13], data[14], data[15]]) {
+            0 => String::new(),
+            serial => serial.to_string(),
+        },
+        year: if data[17] > 0 {
+            1990 + u32::from(data[17])
+        } else {
+            0
+        },
+        ..Default::default()
+    };
+    let (width_cm, height_cm) = (f32::from(data[21]), f32::from(data[22]));
+    if width_cm > 0.0 && height_cm > 0.0 {
+        edid.diagonal_inches =
+            ((width_cm.powi(2) + height_cm.powi(2)).sqrt() / 2.54 * 10.0).round() / 10.0;
+    }
+
+    for (i, block) in data[54..126].chunks_exact(18).enumerate() {
+        if block[0] != 0 || block[1] != 0 {
+            // The first detailed timing is the preferred mode
+            if i == 0 {
+                edid.width_px = u32::from(block[2]) | (u32::from(block[4] & 0xf0) << 4);
+                edid.height_px = u32::from(block[5]) | (u32::from(block[7] & 0xf0) << 4);
+            }
+            continue;
+        }
+        let text = || {
+            let text = &block[5..];
+            let end = text.iter().position(|&b| b == b'\n').unwrap_or(text.len());
+            String::from_utf8_lossy(&text[..end]).trim().to_string()
+        };
+        match block[3] {
+            0xfc => edid.model = text(),
+            0xff => edid.serial = text(),
+            _ => {}
+        }
+    }
+    Some(edid)
+}
+
+/// Kind and bus of one `/proc/bus/input/devices` entry, `None` for virtual
+/// devices, power buttons and media keys
+#[cfg(target_os = "linux")]
+fn classify_input(
+    bus: u16,
+    name: &str,
+    handlers: &[&str],
+    ev: u64,
+    prop: u64,
+) -> Option<(&'static str, &'static str)> {
+    const EV_ABS: u64 = 1 << 3;
+    const EV_REP: u64 = 1 << 20;
+    const INPUT_PROP_POINTER: u64 = 1 << 0;
+    const INPUT_PROP_DIRECT: u64 = 1 << 1;
+
+    let bus = match bus {
+        0x03 => "usb",
+        0x05 => "bluetooth",
+        // i8042 and I2C: built-in keyboards, touchpads and touchscreens
+        0x11 | 0x18 => "internal",
+        _ => return None,
+    };
+    let lower = name.to_lowercase();
+    let kind = if ["pen", "stylus", "wacom"].iter().any(|w| lower.contains(w)) {
+        "tablet"
+    } else if ev & EV_ABS != 0 && prop & INPUT_PROP_DIRECT != 0 {
+        "touchscreen"
+    } else if lower.contains("touchpad")
+        || lower.contains("trackpad")
+        || (ev & EV_ABS != 0 && prop & INPUT_PROP_POINTER != 0)
+    {
+        "touchpad"
+    } else if handlers.iter().any(|h| h.starts_with("mouse")) {
+        "mouse"
+    } else if handlers.contains(&"kbd") && ev & EV_REP != 0 {
+        "keyboard"
+    } else {
+        return None;
+    };
+    Some((kind, bus))
+}
+
+/// Parse `/proc/bus/input/devices`
+#[cfg(target_os = "linux")]
+fn parse_input_devices(text: &str) -> Vec<InputDeviceInfo> {
+    let hex = |s: &str| u64::from_str_radix(s, 16).unwrap_or(0);
+    let mut devices: Vec<InputDeviceInfo> = Vec::new();
+    for block in text.split("\n\n") {
+        let (mut bus, mut vendor, mut product) = (0, "", "");
+        let (mut name, mut handlers, mut ev, mut prop) = ("", Vec::new(), 0, 0);
+        for line in block.lines() {
+            if let Some(ids) = line.strip_prefix("I: ") {
+                for (key, value) in ids.split_whitespace().filter_map(|f| f.split_once('=')) {
+                    match key {
+                        "Bus" => bus = hex(value) as u16,
+                        "Vendor" => vendor = value,
+                        "Product" => product = value,
+                        _ => {}
+                    }
+                }
+            } else if let Some(value) = line.strip_prefix("N: Name=") {
+                name = value.trim_matches('"');
+            } else if let Some(value) = line.strip_prefix("H: Handlers=") {
+                handlers = value.split_whitespace().collect();
+            } else if let Some(value) = line.strip_prefix("B: EV=") {
+                ev = hex(value);
+            } else if let Some(value) = line.strip_prefix("B: PROP=") {
+                prop = hex(value);
+            }
+        }
+        let Some((kind, bus)) = classify_input(bus, name, &handlers, ev, prop) else {
+            continue;
+        };
+        let device = InputDeviceInfo {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            bus: bus.to_string(),
+            vendor_id: vendor.to_string(),
+            product_id: product.to_string(),
+        };
+        // Receivers register one node per interface
+        if !devices.contains(&device) {
+            devices.push(device);
+        }
+    }
+    devices
+}
+
+/// Whether a kernel uevent is about a monitor, input device or camera
+#[cfg(target_os = "linux")]
+fn is_peripheral_event(message: &[u8]) -> bool {
+    message
+        .split(|&b| b == 0)
+        .filter_map(|field| field.strip_prefix(b"SUBSYSTEM="))
+        .any(|subsystem| matches!(subsystem, b"drm" | b"input" | b"video4linux"))
+}
+
+#[cfg(target_os = "linux")]
+fn scan() -> Peripherals {
+    use std::fs;
+    use std::path::Path;
+
+    let read = |path: &Path| {
+        fs::read_to_string(path)
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default()
+    };
+
+    let mut displays = Vec::new();
+    if let Ok(entries) = fs::read_dir("/sys/class/drm") {
+        let mut paths: Vec<_> = entries.flatten().map(|e| e.path()).collect();
+        paths.sort();
+        for path in paths {
+            // Connectors are named card<N>-<connector>
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            let Some((_, connector)) = file_name.split_once('-') else {
+                continue;
+            };
+            if read(&path.join("status")) != "connected" {
+                continue;
+            }
+            let edid = fs::read(path.join("edid"))
+                .ok()
+                .and_then(|data| parse_edid(&data))
+                .unwrap_or_default();
+            let mut display = DisplayInfo {
+                connector: connector.to_string(),
+                manufacturer: edid.manufacturer,
+                model: edid.model,
+                serial: edid.serial,
+                width_px: edid.width_px,
+                height_px: edid.height_px,
+                diagonal_inches: edid.diagonal_inches,
+                manufacture_year: edid.year,
+                internal: ["eDP", "LVDS", "DSI"]
+                    .iter()
+                    .any(|prefix| connector.starts_with(prefix)),
+            };
+            // Without EDID the first listed mode is the preferred one
+            if display.width_px == 0
+                && let Some((width, height)) = read(&path.join("modes"))
+                    .lines()
+                    .next()
+                    .and_then(|mode| mode.split_once('x'))
+            {
+                display.width_px = width.parse().unwrap_or(0);
+                display.height_px = height
+                    .trim_end_matches(|c: char| !c.is_ascii_digit())
+                    .parse()
+                    .unwrap_or(0);
+            }
+            displays.push(display);
+        }
+    }
+
+    let input_devices = fs::read_to_string("/proc/bus/input/devices")
+        .map(|text| parse_input_devices(&text))
+        .unwrap_or_default();
+
+    let mut cameras = Vec::new();
+    if let Ok(entries) = fs::read_dir("/sys/class/video4linux") {
+        let mut paths: Vec<_> = entries.flatten().map(|e| e.path()).collect();
+        paths.sort();
+        for path in paths {
+            // Each camera also has metadata nodes with a higher index
+            if read(&path.join("index")) != "0" {
+                continue;
+            }
+            // The device link points at the USB interface
+            let usb = path.join("device").join("..");
+            let vendor_id = read(&usb.join("idVendor"));
+            cameras.push(CameraInfo {
+                name: read(&path.join("name")),
+                bus: if vendor_id.is_empty() {
+                    "internal"
+                } else {
+                    "usb"
+                }
+                .to_string(),
+                product_id: read(&usb.join("idProduct")),
+                vendor_id,
+            });
+        }
+    }
+
+    Peripherals {
+        displays,
+        input_devices,
+        cameras,
+    }
+}
+
+/// Array items, or the value itself where PowerShell unwrapped a single item
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+fn items(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(items) => items.iter().collect(),
+        Value::Null => Vec::new(),
+        item => vec![item],
+    }
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+fn text(value: &Value, key: &str) -> String {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+#[cfg(target_os = "windows")]
+fn scan() -> Peripherals {
+    const SCRIPT: &str = r#"
+$ErrorActionPreference = 'SilentlyContinue'
+function Text($codes) { -join ($codes | Where-Object { $_ -ne 0 } | ForEach-Object { [char]$_ }) }
+$modes = @{}; $sizes = @{}; $outputs = @{}
+Get-CimInstance -Namespace root\wmi -ClassName WmiMonitorListedSupportedSourceModes | ForEach-Object {
+  $m = $_.MonitorSourceModes[$_.PreferredMonitorSourceModeIndex]
+  $modes[$_.InstanceName] = @($m.HorizontalActivePixels, $m.VerticalActivePixels)
+}
+Get-CimInstance -Namespace root\wmi -ClassName WmiMonitorBasicDisplayParams | ForEach-Object {
+  $sizes[$_.InstanceName] = @($_.MaxHorizontalImageSize, $_.MaxVerticalImageSize)
+}
+Get-CimInstance -Namespace root\wmi -ClassName WmiMonitorConnectionParams | ForEach-Object {
+  $outputs[$_.InstanceName] = $_.VideoOutputTechnology
+}
+$displays = @(Get-CimInstance -Namespace root\wmi -ClassName WmiMonitorID | ForEach-Object {
+  [pscustomobject]@{
+    manufacturer = Text $_.ManufacturerName; model = Text $_.UserFriendlyName
+    serial = Text $_.SerialNumberID; year = $_.YearOfManufacture
+    mode = $modes[$_.InstanceName]; size = $sizes[$_.InstanceName]; output = $outputs[$_.InstanceName]
+  }
+})
+$inputs = @(
+  Get-CimInstance Win32_Keyboard | ForEach-Object { [pscustomobject]@{ kind = 'keyboard'; name = $_.Description; id = $_.PNPDeviceID } }
+  Get-CimInstance Win32_PointingDevice | ForEach-Object { [pscustomobject]@{ kind = 'mouse'; name = $_.Name; id = $_.PNPDeviceID } }
+)
+$cameras = @(Get-CimInstance Win32_PnPEntity -Filter "PNPClass='Camera' OR PNPClass='Image'" | ForEach-Object {
+  [pscustomobject]@{ name = $_.Name; id = $_.PNPDeviceID }
+})
+[pscustomobject]@{ displays = $displays; inputs = $inputs; cameras = $cameras } | ConvertTo-Json -Depth 4 -Compress
+"#;
+    let mut cmd = Command::new("powershell");
+    cmd.args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT]);
+    let Some(json) = exec_with_timeout(cmd, COMMAND_TIMEOUT)
+        .and_then(|o| serde_json::from_slice::<Value>(&o.stdout).ok())
+    else {
+        return Peripherals::default();
+    };
+
+    // PNP device IDs look like USB\VID_046D&PID_C52B\...
+    let ids = |id: &str| {
+        let field = |key: &str| {
+            id.find(key)
+                .map(|at| id[at + key.len()..].chars().take(4).collect::<String>())
+                .unwrap_or_default()
+                .to_lowercase()
+        };
+        let upper = id.to_uppercase();
+        let bus = if upper.starts_with("BTH")
+            || upper.contains("{00001124")
+            || upper.contains("{00001812")
+        {
+            "bluetooth"
+        } else if upper.contains("VID_") {
+            "usb"
+        } else {
+            "internal"
+        };
+        (bus.to_string(), field("VID_"), field("PID_"))
+    };
+    let number = |value: Option<&Value>| value.and_then(Value::as_u64).unwrap_or(0);
+
+    let displays = items(&json["displays"])
+        .into_iter()
+        .map(|d| {
+            let mode = items(&d["mode"]);
+            let size = items(&d["size"]);
+            let output = number(d.get("output"));
+            let (width_cm, height_cm) =
+                (number(size.first().copied()), number(size.get(1).copied()));
+            DisplayInfo {
+                connector: match output {
+                    0 => "VGA",
+                    4 => "DVI",
+                    5 => "HDMI",
+                    6 => "LVDS",
+                    10 => "DP",
+                    11 => "eDP",
+                    15 => "Miracast",
+                    0x8000_0000 => "internal",
+                    _ => "",
+                }
+                .to_string(),
+                manufacturer: text(d, "manufacturer"),
+                model: text(d, "model"),
+                serial: text(d, "serial"),
+                width_px: number(mode.first().copied()) as u32,
+                height_px: number(mode.get(1).copied()) as u32,
+                diagonal_inches: if width_cm > 0 && height_cm > 0 {
+                    (((width_cm.pow(2) + height_cm.pow(2)) as f32).sqrt() / 2.54 * 10.0).round()
+                        / 10.0
+                } else {
+                    0.0
+                },
+                manufacture_year: number(d.get("year")) as u32,
+                internal: matches!(output, 6 | 11 | 13 | 0x8000_0000),
+            }
+        })
+        .collect();
+
+    let input_devices = items(&json["inputs"])
+        .into_iter()
+        .map(|i| {
+            let (bus, vendor_id, product_id) = ids(&text(i, "id"));
+            InputDeviceInfo {
+                name: text(i, "name"),
+                kind: text(i, "kind"),
+                bus,
+                vendor_id,
+                product_id,
+            }
+        })
+        .collect();
+
+    let cameras = items(&json["cameras"])
+        .into_iter()
+        .map(|c| {
+            let (bus, vendor_id, product_id) = ids(&text(c, "id"));
+            CameraInfo {
+                name: text(c, "name"),
+                bus,
+                vendor_id,
+                product_id,
+            }
+        })
+        .collect();
+
+    Peripherals {
+        displays,
+        input_devices,
+        cameras,
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn scan() -> Peripherals {
+    let mut cmd = Command::new("system_profiler");
+    cmd.args([
+        "SPDisplaysDataType",
+        "SPCameraDataType",
+        "SPUSBDataType",
+        "SPBluetoothDataType",
+        "-json",
+    ]);
+    let Some(json) = exec_with_timeout(cmd, COMMAND_TIMEOUT)
+        .and_then(|o| serde_json::from_slice::<Value>(&o.stdout).ok())
+    else {
+        return Peripherals::default();
+    };
+
+    // "1920 x 1080" and "0x046d  (Logitech Inc.)"
+    let pixels = |s: &str| {
+        let mut parts = s.split(" x ").map(|p| {
+            p.trim()
+                .chars()
+                .take_while(char::is_ascii_digit)
+                .collect::<String>()
+                .parse()
+                .unwrap_or(0)
+        });
+        (parts.next().unwrap_or(0), parts.next().unwrap_or(0))
+    };
+    let hex_id = |s: String| {
+        s.split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches("0x")
+            .to_lowercase()
+    };
+    let input_kind = |name: &str| {
+        let lower = name.to_lowercase();
+        if lower.contains("keyboard") {
+            Some("keyboard")
+        } else if lower.contains("trackpad") {
+            Some("touchpad")
+        } else if lower.contains("mouse") {
+            Some("mouse")
+        } else {
+            None
+        }
+    };
+
+    let mut displays = Vec::new();
+    for gpu in items(&json["SPDisplaysDataType"]) {
+        for d in items(&gpu["spdisplays_ndrvs"]) {
+            let (width_px, height_px) = pixels(&text(d, "_spdisplays_pixels"));
+            let internal = text(d, "spdisplays_connection_type") == "spdisplays_internal";
+            displays.push(DisplayInfo {
+                connector: if internal { "internal" } else { "" }.to_string(),
+                manufacturer: text(d, "_spdisplays_display-vendor-id"),
+                model: text(d, "_name"),
+                serial: text(d, "_spdisplays_display-serial-number"),
+                width_px,
+                height_px,
+                diagonal_inches: 0.0,
+                manufacture_year: text(d, "_spdisplays_display-year").parse().unwrap_or(0),
+                internal,
+            });
+        }
+    }
+
+    let mut input_devices = Vec::new();
+    let mut usb: Vec<&Value> = items(&json["SPUSBDataType"]);
+    while let Some(device) = usb.pop() {
+        usb.extend(items(&device["_items"]));
+        let name = text(device, "_name");
+        if let Some(kind) = input_kind(&name) {
+            input_devices.push(InputDeviceInfo {
+                kind: kind.to_string(),
+                bus: "usb".to_string(),
+                vendor_id: hex_id(text(device, "vendor_id")),
+                product_id: hex_id(text(device, "product_id")),
+                name,
+            });
+        }
+    }
+    for controller in items(&json["SPBluetoothDataType"]) {
+        for entry in items(&controller["device_connected"]) {
+            let Some(entry) = entry.as_object() else {
+                continue;
+            };
+            for (name, device) in entry {
+                let minor_type = text(device, "device_minorType");
+                if let Some(kind) = input_kind(&minor_type) {
+                    input_devices.push(InputDeviceInfo {
+                        name: name.clone(),
+                        kind: kind.to_string(),
+                        bus: "bluetooth".to_string(),
+                        vendor_id: hex_id(text(device, "device_vendorID")),
+                        product_id: hex_id(text(device, "device_productID")),
+                    });
+                }
+            }
+        }
+    }
+
+    let cameras = items(&json["SPCameraDataType"])
+        .into_iter()
+        .map(|c| CameraInfo {
+            name: text(c, "_name"),
+            bus: String::new(),
+            vendor_id: String::new(),
+            product_id: String::new(),
+        })
+        .collect();
+
+    Peripherals {
+        displays,
+        input_devices,
+        cameras,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+fn scan() -> Peripherals {
+    Peripherals::default()
+}
+
+#[cfg(target_os = "linux")]
+fn run(watcher: &ChangeWatcher) -> anyhow::Result<()> {
+    use nix::errno::Errno;
+    use nix::sys::socket::{
+        AddressFamily, MsgFlags, NetlinkAddr, SockFlag, SockProtocol, SockType, bind, recv, socket,
+    };
+    use std::os::fd::AsRawFd;
+
+    // Kernel uevents, not the ones udev re-broadcasts
+    const KERNEL_GROUP: u32 = 1;
+
+    let sock = socket(
+        AddressFamily::Netlink,
+        SockType::Datagram,
+        SockFlag::SOCK_CLOEXEC,
+        SockProtocol::NetlinkKObjectUEvent,
+    )?;
+    bind(sock.as_raw_fd(), &NetlinkAddr::new(0, KERNEL_GROUP))?;
+
+    let mut buf = vec![0u8; 16 * 1024];
+    loop {
+        let len = match recv(sock.as_raw_fd(), &mut buf, MsgFlags::empty()) {
+            Ok(len) => len,
+            Err(Errno::EINTR) => continue,
+            // Kernel buffer overrun: events were lost, so rescan
+            Err(Errno::ENOBUFS) => buf.len(),
+            Err(e) => return Err(e.into()),
+        };
+        if len < buf.len() && !is_peripheral_event(&buf[..len]) {
+            continue;
+        }
+
+        // One monitor or receiver produces a burst of events
+        watcher.settle();
+        while recv(sock.as_raw_fd(), &mut buf, MsgFlags::MSG_DONTWAIT).is_ok() {}
+        watcher.notify();
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn run(watcher: &ChangeWatcher) -> anyhow::Result<()> {
+    let mut known = scan();
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        let current = scan();
+        if current != known {
+            known = current;
+            watcher.notify();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// EDID of a 24" Dell U2415, 1920x1200
+    fn dell_edid() -> Vec<u8> {
+        let mut edid = vec![0u8; 128];
+        edid[..8].copy_from_slice(&[0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00]);
+        // "DEL", product 0xa0b4, serial 0
+        edid[8..12].copy_from_slice(&[0x10, 0xac, 0xb4, 0xa0]);
+        edid[17] = 25; // 2015
+        edid[21] = 52;
+        edid[22] = 32;
+        // Detailed timing: 1920x1200
+        edid[54..72].copy_from_slice(&[
+            0x28, 0x3c, 0x80, 0xa0, 0x70, 0xb0, 0x23, 0x40, 0x30, 0x20, 0x36, 0x00, 0x06, 0x44,
+            0x21, 0x00, 0x00, 0x1a,
+        ]);
+        let descriptor = |tag: u8, text: &str| {
+            let mut block = [0u8; 18];
+            block[3] = tag;
+            let mut padded = format!("{text}\n").into_bytes();
+            padded.resize(13, b' ');
+            block[5..].copy_from_slice(&padded);
+            block
+        };
+        edid[72..90].copy_from_slice(&descriptor(0xff, "7MT0157G0AXL"));
+        edid[90..108].copy_from_slice(&descriptor(0xfc, "DELL U2415"));
+        edid
+    }
+
+    #[test]
+    fn test_parse_edid() {
+        let edid = parse_edid(&dell_edid()).unwrap();
+        assert_eq!(
+            edid,
+            Edid {
+                manufacturer: "DEL".to_string(),
+                model: "DELL U2415".to_string(),
+                serial: "7MT0157G0AXL".to_string(),
+                width_px: 1920,
+                height_px: 1200,
+                diagonal_inches: 24.0,
+                year: 2015,
+            }
+        );
+
+        assert!(parse_edid(&[0u8; 128]).is_none());
+        assert!(parse_edid(&dell_edid()[..100]).is_none());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_input_devices() {
+        let text = r#"I: Bus=0019 Vendor=0000 Product=0001 Version=0000
+N: Name="Power Button"
+H: Handlers=kbd event0
+B: PROP=0
+B: EV=3
+
+I: Bus=0011 Vendor=0001 Product=0001 Version=ab41
+N: Name="AT Translated Set 2 keyboard"
+H: Handlers=sysrq kbd leds event2
+B: PROP=0
+B: EV=120013
+
+I: Bus=0018 Vendor=06cb Product=cd8b Version=0100
+N: Name="SYNA3602:00 06CB:CD8B Touchpad"
+H: Handlers=mouse0 event5
+B: PROP=5
+B: EV=1b
+
+I: Bus=0003 Vendor=046d Product=c52b Version=0111
+N: Name="Logitech USB Receiver"
+H: Handlers=sysrq kbd leds event6
+B: PROP=0
+B: EV=120013
+
+I: Bus=0003 Vendor=046d Product=c52b Version=0111
+N: Name="Logitech USB Receiver Mouse"
+H: Handlers=mouse1 event7
+B: PROP=0
+B: EV=17
+
+I: Bus=0003 Vendor=046d Product=c52b Version=0111
+N: Name="Logitech USB Receiver Consumer Control"
+H: Handlers=kbd event8
+B: PROP=0
+B: EV=1f
+"#;
+        let devices: Vec<_> = parse_input_devices(text)
+            .into_iter()
+            .map(|d| (d.kind, d.bus, d.vendor_id))
+            .collect();
+        let expected = [
+            ("keyboard", "internal", "0001"),
+            ("touchpad", "internal", "06cb"),
+            ("keyboard", "usb", "046d"),
+            ("mouse", "usb", "046d"),
+        ];
+        assert_eq!(devices.len(), expected.len());
+        for ((kind, bus, vendor), (k, b, v)) in devices.iter().zip(expected) {
+            assert_eq!((kind.as_str(), bus.as_str(), vendor.as_str()), (k, b, v));
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_peripheral_events() {
+        assert!(is_peripheral_event(
+            b"change@/devices/pci0000:00/0000:00:02.0/drm/card0\0ACTION=change\0SUBSYSTEM=drm\0HOTPLUG=1\0"
+        ));
+        assert!(!is_peripheral_event(
+            b"change@/devices/LNXSYSTM:00/power_supply/BAT0\0ACTION=change\0SUBSYSTEM=power_supply\0"
+        ));
+    }
+}
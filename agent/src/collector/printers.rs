@@ -0,0 +1,287 @@
+//! Print queue monitoring
+//!
+//! Off by default (`collector.printers.enabled`). For every printer: its
+//! state, the spooler's reason for it (paper jam, offline and the like),
+//! whether it accepts jobs, how many jobs are queued and how many of them
+//! are stuck. A job is stuck once it has been queued for longer than
+//! `stuck_after_minutes`, or when the spooler marks it as failed.
+//!
+//! - Linux/macOS: CUPS through `lpstat`
+//! - Windows: `Get-Printer` and `Get-PrintJob`
+//!
+//! Stuck queues are cleared with the `PRINT_QUEUE_CLEAR` command.
+
+use std::process::Command;
+use std::time::Duration;
+
+#[cfg(not(target_os = "windows"))]
+use chrono::{Local, NaiveDateTime};
+
+use crate::config::{CollectorConfig, PrinterConfig};
+use crate::proto::PrinterStatus;
+use crate::utils::safe_command::exec_with_timeout;
+
+use super::registry::{CollectContext, Collector, Fragment, IntervalClass};
+
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub struct PrinterCollector {
+    config: PrinterConfig,
+}
+
+impl PrinterCollector {
+    pub fn new(config: PrinterConfig) -> Self {
+        Self { config }
+    }
+
+    fn stuck_after(&self) -> Duration {
+        Duration::from_secs(self.config.stuck_after_minutes * 60)
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn collect(&self) -> Vec<PrinterStatus> {
+        let mut cmd = Command::new("lpstat");
+        // Dates in job lines are only parseable in the C locale
+        cmd.args(["-d", "-p", "-a", "-o"]).env("LC_ALL", "C");
+        // lpstat exits non-zero without any printers
+        exec_with_timeout(cmd, COMMAND_TIMEOUT)
+            .map(|o| {
+                parse_lpstat(
+                    &String::from_utf8_lossy(&o.stdout),
+                    Local::now().naive_local(),
+                    self.stuck_after(),
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    #[cfg(target_os = "windows")]
+    fn collect(&self) -> Vec<PrinterStatus> {
+        use serde_json::Value;
+
+        const SCRIPT: &str = r#"
+$ErrorActionPreference = 'SilentlyContinue'
+$default = (Get-CimInstance Win32_Printer -Filter 'Default=TRUE').Name
+$now = Get-Date
+@(Get-Printer | ForEach-Object {
+  $jobs = @(Get-PrintJob -PrinterName $_.Name | ForEach-Object {
+    [pscustomobject]@{ age = [int64]($now - $_.SubmittedTime).TotalSeconds; status = [string]$_.JobStatus }
+  })
+  [pscustomobject]@{ name = $_.Name; status = [string]$_.PrinterStatus; default = ($_.Name -eq $default); jobs = $jobs }
+}) | ConvertTo-Json -Depth 3 -Compress
+"#;
+        let mut cmd = Command::new("powershell");
+        cmd.args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT]);
+        let Some(json) = exec_with_timeout(cmd, COMMAND_TIMEOUT)
+            .and_then(|o| serde_json::from_slice::<Value>(&o.stdout).ok())
+        else {
+            return Vec::new();
+        };
+        // ConvertTo-Json unwraps single-item arrays
+        let items = |value: &Value| match value {
+            Value::Array(items) => items.clone(),
+            Value::Null => Vec::new(),
+            item => vec![item.clone()],
+        };
+
+        let stuck_after = self.stuck_after().as_secs();
+        items(&json)
+            .iter()
+            .map(|printer| {
+                let jobs = items(&printer["jobs"]);
+                let ages = jobs.iter().map(|j| j["age"].as_u64().unwrap_or(0));
+                let status = printer["status"].as_str().unwrap_or_default();
+                let (state, message) = windows_state(status, !jobs.is_empty());
+                PrinterStatus {
+                    name: printer["name"].as_str().unwrap_or_default().to_string(),
+                    state: state.to_string(),
+                    state_message: message,
+                    is_default: printer["default"].as_bool().unwrap_or(false),
+                    // The Windows spooler has no per-printer reject switch
+                    accepting_jobs: true,
+                    queued_jobs: jobs.len() as u32,
+                    stuck_jobs: jobs
+                        .iter()
+                        .filter(|j| {
+                            let status = j["status"].as_str().unwrap_or_default();
+                            j["age"].as_u64().unwrap_or(0) >= stuck_after
+                                || status.contains("Error")
+                                || status.contains("Blocked")
+                        })
+                        .count() as u32,
+                    oldest_job_age_seconds: ages.max().unwrap_or(0),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Collector for PrinterCollector {
+    fn name(&self) -> &'static str {
+        "printers"
+    }
+
+    fn interval(&self, _config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Periodic(Duration::from_millis(self.config.interval_ms))
+    }
+
+    fn collect(&mut self, _ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        Ok(Fragment::Printers(PrinterCollector::collect(self)))
+    }
+}
+
+/// State and message for a `Get-Printer` `PrinterStatus` name
+#[cfg(target_os = "windows")]
+fn windows_state(status: &str, has_jobs: bool) -> (&'static str, String) {
+    match status {
+        "Normal" | "PowerSave" | "WarmingUp" | "Initializing" | "Waiting" if has_jobs => {
+            ("printing", String::new())
+        }
+        "Normal" | "PowerSave" | "WarmingUp" | "Initializing" | "Waiting" => {
+            ("idle", String::new())
+        }
+        "Printing" | "Processing" | "Busy" | "IOActive" => ("printing", String::new()),
+        // Low toner still prints
+        "TonerLow" => (
+            if has_jobs { "printing" } else { "idle" },
+            status.to_string(),
+        ),
+        "Paused" | "PendingDeletion" => ("stopped", status.to_string()),
+        "Offline" | "NotAvailable" | "ServerUnknown" => ("offline", status.to_string()),
+        _ => ("error", status.to_string()),
+    }
+}
+
+/// Parse `lpstat -d -p -a -o` output in the C locale
+#[cfg(not(target_os = "windows"))]
+fn parse_lpstat(text: &str, now: NaiveDateTime, stuck_after: Duration) -> Vec<PrinterStatus> {
+    let mut printers: Vec<PrinterStatus> = Vec::new();
+    let mut default = None;
+    // Printer whose state message follows on indented lines
+    let mut current: Option<usize> = None;
+
+    for line in text.lines() {
+        if line.starts_with(char::is_whitespace) {
+            if let (Some(i), message) = (current, line.trim()) {
+                let printer = &mut printers[i];
+                if !message.is_empty() && printer.state_message.is_empty() {
+                    printer.state_message = message.to_string();
+                }
+            }
+            continue;
+        }
+        current = None;
+
+        if let Some(name) = line.strip_prefix("system default destination: ") {
+            default = Some(name.trim().to_string());
+        } else if let Some(rest) = line.strip_prefix("printer ") {
+            let Some((name, status)) = rest.split_once(' ') else {
+                continue;
+            };
+            let state = if status.starts_with("now printing") {
+                "printing"
+            } else if status.starts_with("disabled") {
+                "stopped"
+            } else {
+                "idle"
+            };
+            printers.push(PrinterStatus {
+                name: name.to_string(),
+                state: state.to_string(),
+                accepting_jobs: true,
+                ..Default::default()
+            });
+            current = Some(printers.len() - 1);
+        } else if let Some((name, status)) = line.split_once(' ')
+            && (status.starts_with("accepting requests")
+                || status.starts_with("not accepting requests"))
+        {
+            if let Some(printer) = printers.iter_mut().find(|p| p.name == name) {
+                printer.accepting_jobs = status.starts_with("accepting");
+            }
+        } else {
+            // <printer>-<job id>  <user>  <size>  <submitted>
+            let mut fields = line.split_whitespace();
+            let (Some(job), Some(_user), Some(_size)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let Some((name, _id)) = job.rsplit_once('-') else {
+                continue;
+            };
+            let Some(printer) = printers.iter_mut().find(|p| p.name == name) else {
+                continue;
+            };
+            let submitted = fields.collect::<Vec<_>>().join(" ");
+            let age = NaiveDateTime::parse_from_str(&submitted, "%a %b %e %H:%M:%S %Y")
+                .map(|at| (now - at).num_seconds().max(0) as u64)
+                .unwrap_or(0);
+            printer.queued_jobs += 1;
+            if age >= stuck_after.as_secs() {
+                printer.stuck_jobs += 1;
+            }
+            printer.oldest_job_age_seconds = printer.oldest_job_age_seconds.max(age);
+        }
+    }
+
+    for printer in &mut printers {
+        printer.is_default = default.as_deref() == Some(printer.name.as_str());
+    }
+    printers
+}
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lpstat() {
+        let text = "\
+system default destination: Office
+printer Office now printing Office-42.  enabled since Fri Oct 16 08:00:00 2026
+\tSending data to printer.
+printer Label is idle.  enabled since Fri Oct 16 08:00:00 2026
+printer Basement disabled since Fri Oct 16 07:00:00 2026 -
+\tMedia jam!
+Office accepting requests since Fri Oct 16 08:00:00 2026
+Label not accepting requests since Fri Oct 16 08:00:00 2026 -
+\tRejecting Jobs
+Basement accepting requests since Fri Oct 16 07:00:00 2026
+Office-42               alice            10240   Fri Oct 16 09:10:00 2026
+Office-43               bob               2048   Fri Oct 16 09:55:00 2026
+Basement-7              carol           512000   Thu Oct  1 17:00:00 2026
+";
+        let now =
+            NaiveDateTime::parse_from_str("2026-10-16 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let printers = parse_lpstat(text, now, Duration::from_secs(30 * 60));
+        assert_eq!(printers.len(), 3);
+
+        let office = &printers[0];
+        assert_eq!(
+            (office.name.as_str(), office.state.as_str()),
+            ("Office", "printing")
+        );
+        assert!(office.is_default && office.accepting_jobs);
+        assert_eq!(office.state_message, "Sending data to printer.");
+        assert_eq!((office.queued_jobs, office.stuck_jobs), (2, 1));
+        assert_eq!(office.oldest_job_age_seconds, 50 * 60);
+
+        let label = &printers[1];
+        assert_eq!(label.state, "idle");
+        assert!(!label.is_default && !label.accepting_jobs);
+        assert!(label.state_message.is_empty());
+        assert_eq!(label.queued_jobs, 0);
+
+        let basement = &printers[2];
+        assert_eq!(basement.state, "stopped");
+        assert_eq!(basement.state_message, "Media jam!");
+        assert_eq!((basement.queued_jobs, basement.stuck_jobs), (1, 1));
+    }
+
+    #[test]
+    fn test_parse_lpstat_without_printers() {
+        let now = Local::now().naive_local();
+        assert!(parse_lpstat("no system default destination\n", now, Duration::ZERO).is_empty());
+    }
+}
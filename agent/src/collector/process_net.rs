@@ -0,0 +1,263 @@
+//! Per-process network bandwidth accounting
+//!
+//! Off by default (`collector.enable_process_network`): mapping sockets to
+//! processes walks every `/proc/<pid>/fd` directory, which is expensive on
+//! busy hosts.
+//!
+//! - Linux: TCP byte counters per socket from `ss -tinHe` (sock_diag
+//!   `tcp_info`), mapped to processes through socket inodes in
+//!   `/proc/<pid>/fd`. Rates are deltas between two samples.
+//! - Windows: connection counts per process from `Get-NetTCPConnection`;
+//!   byte counters need TCP extended statistics (`GetPerTcpConnectionEStats`)
+//!   and are reported as 0.
+//! - Other platforms: not supported.
+
+use std::collections::HashMap;
+#[cfg(target_os = "linux")]
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use nanolink_parse::ss::SocketBytes;
+
+use crate::proto::ProcessNetworkUsage;
+#[cfg(any(target_os = "linux", target_os = "windows"))]
+use crate::utils::safe_command::exec_with_timeout;
+
+/// Number of processes reported, ordered by total bandwidth
+const TOP_PROCESSES: usize = 20;
+
+#[allow(dead_code)]
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Per-process network collector
+pub struct ProcessNetworkCollector {
+    prev: HashMap<u64, SocketBytes>,
+    prev_time: Option<Instant>,
+}
+
+impl ProcessNetworkCollector {
+    pub fn new() -> Self {
+        Self {
+            prev: HashMap::new(),
+            prev_time: None,
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn collect(&mut self) -> Vec<ProcessNetworkUsage> {
+        let mut cmd = Command::new("ss");
+        cmd.args(["-tinHe"]);
+        let sockets = match exec_with_timeout(cmd, COMMAND_TIMEOUT) {
+            Some(output) if output.status.success() => {
+                nanolink_parse::ss::socket_bytes(&String::from_utf8_lossy(&output.stdout))
+            }
+            _ => return Vec::new(),
+        };
+
+        let owners = socket_owners();
+        let now = Instant::now();
+        let elapsed = self
+            .prev_time
+            .map(|t| now.duration_since(t).as_secs_f64())
+            .unwrap_or(0.0);
+
+        let usage = aggregate(&sockets, &self.prev, &owners, elapsed);
+
+        self.prev = sockets.into_iter().map(|s| (s.inode, s)).collect();
+        self.prev_time = Some(now);
+        usage
+    }
+
+    #[cfg(target_os = "windows")]
+    pub fn collect(&mut self) -> Vec<ProcessNetworkUsage> {
+        use std::process::Command;
+
+        let mut cmd = Command::new("powershell");
+        cmd.args([
+            "-NoProfile",
+            "-Command",
+            "Get-NetTCPConnection -State Established | Group-Object OwningProcess \
+             | ForEach-Object { \"$($_.Name),$((Get-Process -Id $_.Name -ErrorAction SilentlyContinue).ProcessName),$($_.Count)\" }",
+        ]);
+        let Some(output) = exec_with_timeout(cmd, COMMAND_TIMEOUT) else {
+            return Vec::new();
+        };
+
+        let mut usage: Vec<ProcessNetworkUsage> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.trim().splitn(3, ',');
+                let pid = fields.next()?.parse().ok()?;
+                let name = fields.next()?.to_string();
+                let connections = fields.next()?.parse().ok()?;
+                Some(ProcessNetworkUsage {
+                    pid,
+                    name,
+                    connections,
+                    ..Default::default()
+                })
+            })
+            .collect();
+        usage.sort_by(|a, b| b.connections.cmp(&a.connections));
+        usage.truncate(TOP_PROCESSES);
+        usage
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    pub fn collect(&mut self) -> Vec<ProcessNetworkUsage> {
+        Vec::new()
+    }
+}
+
+impl Default for ProcessNetworkCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Map socket inodes to `(pid, process name)` by scanning `/proc/<pid>/fd`
+#[cfg(target_os = "linux")]
+fn socket_owners() -> HashMap<u64, (u32, String)> {
+    let mut owners = HashMap::new();
+    let Ok(procs) = std::fs::read_dir("/proc") else {
+        return owners;
+    };
+
+    for entry in procs.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        let name = std::fs::read_to_string(entry.path().join("comm"))
+            .map(|c| c.trim().to_string())
+            .unwrap_or_default();
+
+        for fd in fds.flatten() {
+            let Ok(target) = std::fs::read_link(fd.path()) else {
+                continue;
+            };
+            if let Some(inode) = target
+                .to_str()
+                .and_then(|t| t.strip_prefix("socket:["))
+                .and_then(|t| t.strip_suffix(']'))
+                .and_then(|t| t.parse().ok())
+            {
+                owners.insert(inode, (pid, name.clone()));
+            }
+        }
+    }
+
+    owners
+}
+
+/// Sum per-socket deltas into per-process rates, keeping the top talkers
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn aggregate(
+    sockets: &[SocketBytes],
+    prev: &HashMap<u64, SocketBytes>,
+    owners: &HashMap<u64, (u32, String)>,
+    elapsed_secs: f64,
+) -> Vec<ProcessNetworkUsage> {
+    let mut by_pid: HashMap<u32, ProcessNetworkUsage> = HashMap::new();
+
+    for socket in sockets {
+        let Some((pid, name)) = owners.get(&socket.inode) else {
+            continue;
+        };
+        let usage = by_pid.entry(*pid).or_insert_with(|| ProcessNetworkUsage {
+            pid: *pid,
+            name: name.clone(),
+            ..Default::default()
+        });
+        usage.connections += 1;
+
+        // New sockets have no baseline; their first interval is skipped
+        if let Some(previous) = prev.get(&socket.inode) {
+            if elapsed_secs > 0.0 {
+                usage.tx_bytes_sec +=
+                    (socket.sent.saturating_sub(previous.sent) as f64 / elapsed_secs) as u64;
+                usage.rx_bytes_sec += (socket.received.saturating_sub(previous.received) as f64
+                    / elapsed_secs) as u64;
+            }
+        }
+    }
+
+    let mut usage: Vec<_> = by_pid.into_values().collect();
+    usage.sort_by(|a, b| {
+        (b.tx_bytes_sec + b.rx_bytes_sec)
+            .cmp(&(a.tx_bytes_sec + a.rx_bytes_sec))
+            .then(b.connections.cmp(&a.connections))
+    });
+    usage.truncate(TOP_PROCESSES);
+    usage
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aggregate() {
+        let owners = HashMap::from([
+            (1, (100, "nginx".to_string())),
+            (2, (100, "nginx".to_string())),
+            (3, (200, "sshd".to_string())),
+        ]);
+        let prev = HashMap::from([
+            (
+                1,
+                SocketBytes {
+                    inode: 1,
+                    sent: 0,
+                    received: 0,
+                },
+            ),
+            (
+                3,
+                SocketBytes {
+                    inode: 3,
+                    sent: 0,
+                    received: 0,
+                },
+            ),
+        ]);
+        let now = [
+            SocketBytes {
+                inode: 1,
+                sent: 2000,
+                received: 1000,
+            },
+            SocketBytes {
+                inode: 2,
+                sent: 5000,
+                received: 0,
+            },
+            SocketBytes {
+                inode: 3,
+                sent: 20,
+                received: 20,
+            },
+            SocketBytes {
+                inode: 4,
+                sent: 99,
+                received: 99,
+            },
+        ];
+
+        let usage = aggregate(&now, &prev, &owners, 2.0);
+        assert_eq!(usage.len(), 2);
+        assert_eq!(usage[0].name, "nginx");
+        assert_eq!(usage[0].connections, 2);
+        // Socket 2 has no baseline yet
+        assert_eq!(usage[0].tx_bytes_sec, 1000);
+        assert_eq!(usage[0].rx_bytes_sec, 500);
+        assert_eq!(usage[1].pid, 200);
+        assert_eq!(usage[1].tx_bytes_sec, 10);
+    }
+}
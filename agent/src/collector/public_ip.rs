@@ -0,0 +1,265 @@
+//! Public IP detection
+//!
+//! Off by default (`collector.enable_public_ip`): it contacts third-party
+//! endpoints. Interface addresses of NAT'd edge devices are meaningless to
+//! the dashboard, so the agent asks the outside world which address it
+//! appears from. Endpoints are tried in order until one answers:
+//!
+//! - `stun:<host>:<port>`: STUN Binding request (RFC 5389) over UDP
+//! - `http(s)://...`: a plain-text "what is my IP" service, fetched with
+//!   curl (Unix) or `Invoke-RestMethod` (Windows)
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use tracing::debug;
+
+use crate::utils::safe_command::exec_with_timeout;
+
+/// Per-endpoint timeout
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+const STUN_MAGIC_COOKIE: u32 = 0x2112_A442;
+const STUN_BINDING_REQUEST: u16 = 0x0001;
+const STUN_BINDING_SUCCESS: u16 = 0x0101;
+const STUN_ATTR_MAPPED_ADDRESS: u16 = 0x0001;
+const STUN_ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+
+/// Public IP collector
+pub struct PublicIpCollector {
+    endpoints: Vec<String>,
+    /// Minimum age before the endpoints are asked again; static info is
+    /// rebuilt on every reconnect and mount change
+    max_age: Duration,
+    cached: Option<(Instant, String)>,
+}
+
+impl PublicIpCollector {
+    pub fn new(endpoints: Vec<String>, interval: Duration) -> Self {
+        Self {
+            endpoints,
+            max_age: interval / 2,
+            cached: None,
+        }
+    }
+
+    /// Public IP address, or an empty string if no endpoint answered
+    pub fn collect(&mut self) -> String {
+        if let Some((at, ip)) = &self.cached
+            && at.elapsed() < self.max_age
+        {
+            return ip.clone();
+        }
+
+        let ip = self
+            .endpoints
+            .iter()
+            .find_map(|endpoint| {
+                let result = match endpoint.strip_prefix("stun:") {
+                    Some(server) => stun_query(server),
+                    None => http_query(endpoint),
+                };
+                if result.is_none() {
+                    debug!("Public IP endpoint {} did not answer", endpoint);
+                }
+                result
+            })
+            .map(|ip| ip.to_string())
+            .unwrap_or_default();
+
+        self.cached = Some((Instant::now(), ip.clone()));
+        ip
+    }
+}
+
+/// Ask a STUN server for our server-reflexive address
+fn stun_query(server: &str) -> Option<IpAddr> {
+    let addr = server.to_socket_addrs().ok()?.next()?;
+    let bind: SocketAddr = if addr.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+
+    let socket = UdpSocket::bind(bind).ok()?;
+    socket.set_read_timeout(Some(TIMEOUT)).ok()?;
+    socket.connect(addr).ok()?;
+
+    let mut transaction_id = [0u8; 12];
+    transaction_id.copy_from_slice(&uuid::Uuid::new_v4().as_bytes()[..12]);
+    socket.send(&stun_request(&transaction_id)).ok()?;
+
+    let mut buf = [0u8; 512];
+    let len = socket.recv(&mut buf).ok()?;
+    parse_stun_response(&buf[..len], &transaction_id)
+}
+
+fn stun_request(transaction_id: &[u8; 12]) -> [u8; 20] {
+    let mut request = [0u8; 20];
+    request[0..2].copy_from_slice(&STUN_BINDING_REQUEST.to_be_bytes());
+    // Message length 0: no attributes
+    request[4..8].copy_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+    request[8..20].copy_from_slice(transaction_id);
+    request
+}
+
+/// Extract the mapped address from a STUN Binding success response
+fn parse_stun_response(msg: &[u8], transaction_id: &[u8; 12]) -> Option<IpAddr> {
+    if msg.len() < 20
+        || u16::from_be_bytes([msg[0], msg[1]]) != STUN_BINDING_SUCCESS
+        || msg[4..8] != STUN_MAGIC_COOKIE.to_be_bytes()
+        || &msg[8..20] != transaction_id
+    {
+        return None;
+    }
+
+    let body_len = u16::from_be_bytes([msg[2], msg[3]]) as usize;
+    let body = msg.get(20..20 + body_len)?;
+
+    let mut mapped = None;
+    let mut offset = 0;
+    while offset + 4 <= body.len() {
+        let attr_type = u16::from_be_bytes([body[offset], body[offset + 1]]);
+        let attr_len = u16::from_be_bytes([body[offset + 2], body[offset + 3]]) as usize;
+        let value = body.get(offset + 4..offset + 4 + attr_len)?;
+
+        match attr_type {
+            // XOR-MAPPED-ADDRESS is preferred: some NATs rewrite plain
+            // addresses found in packet payloads
+            STUN_ATTR_XOR_MAPPED_ADDRESS => return decode_address(value, Some(&msg[4..20])),
+            STUN_ATTR_MAPPED_ADDRESS => mapped = decode_address(value, None),
+            _ => {}
+        }
+
+        // Attributes are padded to a multiple of 4 bytes
+        offset += 4 + attr_len.div_ceil(4) * 4;
+    }
+
+    mapped
+}
+
+/// Decode a (XOR-)MAPPED-ADDRESS value; `xor_key` is cookie + transaction ID
+fn decode_address(value: &[u8], xor_key: Option<&[u8]>) -> Option<IpAddr> {
+    let family = *value.get(1)?;
+    let len = match family {
+        0x01 => 4,
+        0x02 => 16,
+        _ => return None,
+    };
+    let mut addr = value.get(4..4 + len)?.to_vec();
+    if let Some(key) = xor_key {
+        addr.iter_mut().zip(key).for_each(|(b, k)| *b ^= k);
+    }
+
+    Some(match len {
+        4 => IpAddr::from(<[u8; 4]>::try_from(addr).ok()?),
+        _ => IpAddr::from(<[u8; 16]>::try_from(addr).ok()?),
+    })
+}
+
+/// Fetch a plain-text IP address over HTTP(S)
+fn http_query(url: &str) -> Option<IpAddr> {
+    #[cfg(windows)]
+    let cmd = {
+        let mut cmd = Command::new("powershell");
+        cmd.args([
+            "-NoProfile",
+            "-Command",
+            &format!(
+                "(Invoke-RestMethod -Uri '{}' -TimeoutSec {}).ToString()",
+                url.replace('\'', "''"),
+                TIMEOUT.as_secs()
+            ),
+        ]);
+        cmd
+    };
+    #[cfg(not(windows))]
+    let cmd = {
+        let mut cmd = Command::new("curl");
+        cmd.args([
+            "-sfL",
+            "--max-time",
+            &TIMEOUT.as_secs().to_string(),
+            "-H",
+            "User-Agent: NanoLink-Agent",
+            url,
+        ]);
+        cmd
+    };
+
+    let output = exec_with_timeout(cmd, TIMEOUT + Duration::from_secs(2))?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRANSACTION_ID: [u8; 12] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+
+    fn response(attrs: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (attr_type, value) in attrs {
+            body.extend_from_slice(&attr_type.to_be_bytes());
+            body.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            body.extend_from_slice(value);
+            body.resize(body.len().div_ceil(4) * 4, 0);
+        }
+
+        let mut msg = Vec::new();
+        msg.extend_from_slice(&STUN_BINDING_SUCCESS.to_be_bytes());
+        msg.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        msg.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+        msg.extend_from_slice(&TRANSACTION_ID);
+        msg.extend(body);
+        msg
+    }
+
+    #[test]
+    fn test_stun_request() {
+        let request = stun_request(&TRANSACTION_ID);
+        assert_eq!(&request[0..4], &[0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(&request[4..8], &[0x21, 0x12, 0xA4, 0x42]);
+        assert_eq!(&request[8..], &TRANSACTION_ID);
+    }
+
+    #[test]
+    fn test_parse_xor_mapped_address() {
+        // 203.0.113.7:54321 XORed with the magic cookie
+        let ip = [203 ^ 0x21, 0x12, 113 ^ 0xA4, 7 ^ 0x42];
+        let mut value = vec![0x00, 0x01, 0xF4, 0x23];
+        value.extend_from_slice(&ip);
+
+        // Unknown attributes (SOFTWARE) before the address are skipped
+        let msg = response(&[
+            (0x8022, b"stun".to_vec()),
+            (STUN_ATTR_XOR_MAPPED_ADDRESS, value),
+        ]);
+        assert_eq!(
+            parse_stun_response(&msg, &TRANSACTION_ID),
+            Some("203.0.113.7".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_parse_mapped_address_fallback() {
+        let value = vec![0x00, 0x01, 0x1F, 0x90, 198, 51, 100, 20];
+        let msg = response(&[(STUN_ATTR_MAPPED_ADDRESS, value)]);
+        assert_eq!(
+            parse_stun_response(&msg, &TRANSACTION_ID),
+            Some("198.51.100.20".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_foreign_transaction() {
+        let value = vec![0x00, 0x01, 0x1F, 0x90, 198, 51, 100, 20];
+        let msg = response(&[(STUN_ATTR_MAPPED_ADDRESS, value)]);
+        assert_eq!(parse_stun_response(&msg, &[0; 12]), None);
+        assert_eq!(parse_stun_response(&msg[..10], &TRANSACTION_ID), None);
+    }
+}
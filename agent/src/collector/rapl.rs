@@ -0,0 +1,274 @@
+//! Intel/AMD RAPL energy counters (Linux powercap)
+//!
+//! `/sys/class/powercap/intel-rapl:<n>` is one CPU package and
+//! `intel-rapl:<n>:<m>` its sub-domains (core, uncore, dram). AMD Zen CPUs
+//! expose the same interface. Each domain has a cumulative `energy_uj`
+//! counter that wraps at `max_energy_range_uj`.
+//!
+//! Since CVE-2020-8694 most distributions make `energy_uj` readable by root
+//! only. When no powercap counter is readable, the `amd_energy` hwmon driver
+//! is tried; otherwise [`PowerMeter`] reports nothing and logs how to grant
+//! access once.
+
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use tracing::{info, warn};
+
+use crate::proto::CpuPower;
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+const POWERCAP_DIR: &str = "/sys/class/powercap";
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+const HWMON_DIR: &str = "/sys/class/hwmon";
+
+/// One RAPL power domain
+#[derive(Debug, Clone)]
+pub struct Domain {
+    /// e.g. "package-0", "dram"
+    pub name: String,
+    /// Package index
+    pub package: u32,
+    energy_path: PathBuf,
+    max_range_uj: u64,
+}
+
+impl Domain {
+    fn load(dir: &Path, package: u32) -> Option<Self> {
+        let read = |file: &str| std::fs::read_to_string(dir.join(file)).ok();
+        Some(Self {
+            name: read("name")?.trim().to_string(),
+            package,
+            energy_path: dir.join("energy_uj"),
+            max_range_uj: read("max_energy_range_uj")
+                .and_then(|s| s.trim().parse().ok())
+                .unwrap_or(0),
+        })
+    }
+
+    /// Cumulative energy in microjoules; `None` if unreadable (root-only on
+    /// kernels patched for CVE-2020-8694)
+    pub fn energy_uj(&self) -> Option<u64> {
+        std::fs::read_to_string(&self.energy_path)
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    }
+
+    /// Energy used between two readings, allowing for one counter wrap
+    pub fn delta_uj(&self, previous: u64, current: u64) -> u64 {
+        if current >= previous {
+            current - previous
+        } else {
+            self.max_range_uj.saturating_sub(previous) + current
+        }
+    }
+
+    /// "package" or "dram"
+    fn kind(&self) -> &str {
+        if self.name.starts_with("package") {
+            "package"
+        } else {
+            &self.name
+        }
+    }
+}
+
+/// Package domains and their DRAM sub-domains. Core and uncore are part of
+/// the package figure and are left out so domains can be summed.
+///
+/// Falls back to `amd_energy` hwmon sockets when no powercap counter can be
+/// read; unreadable powercap domains are still returned so callers can tell
+/// "no RAPL" from "no permission".
+pub fn domains() -> Vec<Domain> {
+    #[cfg(target_os = "linux")]
+    {
+        let powercap = scan(Path::new(POWERCAP_DIR));
+        if powercap.iter().any(|d| d.energy_uj().is_some()) {
+            return powercap;
+        }
+        let hwmon = scan_hwmon(Path::new(HWMON_DIR));
+        if hwmon.iter().any(|d| d.energy_uj().is_some()) {
+            return hwmon;
+        }
+        powercap
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    Vec::new()
+}
+
+/// `amd_energy` exposes per-socket counters as `energyN_input` labelled
+/// `Esocket<n>`, in microjoules
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn scan_hwmon(root: &Path) -> Vec<Domain> {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut domains = Vec::new();
+    for dir in entries.flatten().map(|e| e.path()) {
+        let name = std::fs::read_to_string(dir.join("name")).unwrap_or_default();
+        if name.trim() != "amd_energy" {
+            continue;
+        }
+        for index in 1.. {
+            let Ok(label) = std::fs::read_to_string(dir.join(format!("energy{index}_label")))
+            else {
+                break;
+            };
+            let Some(package) = label
+                .trim()
+                .strip_prefix("Esocket")
+                .and_then(|n| n.parse().ok())
+            else {
+                continue;
+            };
+            domains.push(Domain {
+                name: format!("package-{package}"),
+                package,
+                energy_path: dir.join(format!("energy{index}_input")),
+                // 64-bit counters in practice never wrap
+                max_range_uj: u64::MAX,
+            });
+        }
+    }
+    domains
+}
+
+/// Turns energy counters into average power between calls
+pub struct PowerMeter {
+    domains: Vec<(Domain, Option<u64>)>,
+    last: Instant,
+}
+
+impl PowerMeter {
+    pub fn new() -> Self {
+        let domains = domains();
+        let readable = domains.iter().any(|d| d.energy_uj().is_some());
+        if !domains.is_empty() && !readable {
+            warn!(
+                "RAPL energy counters are not readable (root only); CPU power is not reported. \
+                 Run the agent as root or grant read access, e.g. a udev rule: \
+                 SUBSYSTEM==\"powercap\", ACTION==\"add\", RUN+=\"/bin/chmod 0444 %S%p/energy_uj\""
+            );
+        } else if readable {
+            info!("Measuring CPU power from {} RAPL domains", domains.len());
+        }
+        Self::with_domains(if readable { domains } else { Vec::new() })
+    }
+
+    fn with_domains(domains: Vec<Domain>) -> Self {
+        Self {
+            domains: domains
+                .into_iter()
+                .map(|d| {
+                    let reading = d.energy_uj();
+                    (d, reading)
+                })
+                .collect(),
+            last: Instant::now(),
+        }
+    }
+
+    /// Average power per domain since the previous call
+    pub fn sample(&mut self) -> Vec<CpuPower> {
+        let now = Instant::now();
+        let secs = now.duration_since(self.last).as_secs_f64();
+        self.last = now;
+
+        let mut power = Vec::new();
+        for (domain, previous) in &mut self.domains {
+            let current = domain.energy_uj();
+            if let (Some(prev), Some(cur)) = (*previous, current)
+                && secs > 0.0
+            {
+                power.push(CpuPower {
+                    package: domain.package,
+                    domain: domain.kind().to_string(),
+                    watts: domain.delta_uj(prev, cur) as f64 / 1_000_000.0 / secs,
+                });
+            }
+            *previous = current;
+        }
+        power
+    }
+}
+
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn scan(root: &Path) -> Vec<Domain> {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut domains: Vec<Domain> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let mut parts = name.strip_prefix("intel-rapl:")?.split(':');
+            let package = parts.next()?.parse().ok()?;
+            let sub = parts.next().is_some();
+            let domain = Domain::load(&entry.path(), package)?;
+            (!sub || domain.name == "dram").then_some(domain)
+        })
+        .collect();
+    domains.sort_by(|a, b| (a.package, &a.name).cmp(&(b.package, &b.name)));
+    domains
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan() {
+        let root = std::env::temp_dir().join(format!("nanolink-rapl-{}", std::process::id()));
+        let domain = |dir: &str, name: &str, energy: &str| {
+            let path = root.join(dir);
+            std::fs::create_dir_all(&path).unwrap();
+            std::fs::write(path.join("name"), format!("{name}\n")).unwrap();
+            std::fs::write(path.join("energy_uj"), energy).unwrap();
+            std::fs::write(path.join("max_energy_range_uj"), "1000").unwrap();
+        };
+        domain("intel-rapl:0", "package-0", "900");
+        domain("intel-rapl:0:0", "core", "400");
+        domain("intel-rapl:0:1", "dram", "100");
+        domain("intel-rapl-mmio:0", "package-0", "5");
+
+        let domains = scan(&root);
+        let _ = std::fs::remove_dir_all(&root);
+
+        let names: Vec<_> = domains.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, ["dram", "package-0"]);
+        assert_eq!(domains[1].delta_uj(100, 300), 200);
+        // Counter wrapped at 1000
+        assert_eq!(domains[1].delta_uj(900, 50), 150);
+    }
+
+    #[test]
+    fn test_hwmon_fallback() {
+        let root = std::env::temp_dir().join(format!("nanolink-rapl-hwmon-{}", std::process::id()));
+        let dir = root.join("hwmon3");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("name"), "amd_energy\n").unwrap();
+        std::fs::write(dir.join("energy1_label"), "Ecore000\n").unwrap();
+        std::fs::write(dir.join("energy1_input"), "10").unwrap();
+        std::fs::write(dir.join("energy2_label"), "Esocket0\n").unwrap();
+        std::fs::write(dir.join("energy2_input"), "2000000").unwrap();
+
+        let domains = scan_hwmon(&root);
+        assert_eq!(domains.len(), 1);
+        assert_eq!(domains[0].name, "package-0");
+
+        let mut meter = PowerMeter::with_domains(domains);
+        meter.last -= std::time::Duration::from_secs(2);
+        std::fs::write(dir.join("energy2_input"), "6000000").unwrap();
+        let power = meter.sample();
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert_eq!(power.len(), 1);
+        assert_eq!(power[0].domain, "package");
+        // 4 J over ~2 s
+        assert!((power[0].watts - 2.0).abs() < 0.1);
+    }
+}
@@ -0,0 +1,660 @@
+//! Pluggable collector trait and registry
+//!
+//! Each collector produces a [`Fragment`] of metrics data. The fragment
+//! knows how to merge itself into every outgoing message shape (full
+//! metrics, realtime, periodic, static), so a collector is written once and
+//! works in both the legacy and layered pipelines. Collectors are enabled or
+//! disabled by name through `collector.disabled_collectors`.
+
+use std::time::{Duration, Instant};
+
+use sysinfo::System;
+use tracing::warn;
+
+use crate::config::CollectorConfig;
+use crate::proto::{
+    CgroupUsage, CustomMetric, FailedLoginSummary, GpuStaticInfo, GpuUsage, HardwareSensor,
+    KernelModules, Metrics, NpuStaticInfo, NpuUsage, PeriodicData, Peripherals, PrinterStatus,
+    ProcessNetworkUsage, RealtimeMetrics, RoutingInfo, SshKey, StaticInfo,
+};
+
+use super::anomaly::AnomalyCollector;
+use super::capture::CaptureCollector;
+use super::cgroups::CgroupCollector;
+use super::dir_growth::DirGrowthCollector;
+use super::energy::{self, EnergyCollector};
+use super::failed_logins::FailedLoginCollector;
+use super::gpu::{self, GpuCollector};
+use super::kernel_modules::KernelModuleCollector;
+use super::npu::{self, NpuCollector};
+use super::peripherals::PeripheralCollector;
+use super::printers::PrinterCollector;
+use super::process_net::ProcessNetworkCollector;
+use super::public_ip::PublicIpCollector;
+use super::routes::RouteCollector;
+#[cfg(feature = "rules")]
+use super::rules::RulesCollector;
+use super::sensors::SensorCollector;
+use super::sessions::{self, SessionCollector};
+use super::ssh_keys::SshKeyCollector;
+use super::textfile::TextfileCollector;
+
+/// How often a collector's data is sent in the layered pipeline.
+///
+/// Every collector is also included in full metrics and static info.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntervalClass {
+    /// Every realtime tick
+    Realtime,
+    /// With periodic data, at the given interval
+    Periodic(Duration),
+    /// Only in full metrics and static info
+    Static,
+}
+
+/// Shared state available to collectors
+#[allow(dead_code)]
+pub struct CollectContext<'a> {
+    pub system: &'a System,
+    pub config: &'a CollectorConfig,
+}
+
+/// A piece of collected data
+#[derive(Debug, Clone)]
+pub enum Fragment {
+    Gpus(Vec<gpu::GpuMetrics>),
+    Npus(Vec<npu::NpuMetrics>),
+    UserSessions(Vec<sessions::UserSession>),
+    Sensors(Vec<HardwareSensor>),
+    Routing(RoutingInfo),
+    ProcessNetwork(Vec<ProcessNetworkUsage>),
+    PublicIp(String),
+    FailedLogins(FailedLoginSummary),
+    Cgroups(Vec<CgroupUsage>),
+    Peripherals(Peripherals),
+    Printers(Vec<PrinterStatus>),
+    SshKeys(Vec<SshKey>),
+    KernelModules(KernelModules),
+    /// Free-form metrics from collectors without a dedicated proto field
+    #[allow(dead_code)]
+    Custom(Vec<CustomMetric>),
+}
+
+impl Fragment {
+    /// Merge into a full metrics message
+    pub fn apply_full(self, metrics: &mut Metrics) {
+        match self {
+            Fragment::Gpus(gpus) => metrics.gpus.extend(gpus.into_iter().map(Into::into)),
+            Fragment::Npus(npus) => metrics.npus.extend(npus.into_iter().map(Into::into)),
+            Fragment::UserSessions(sessions) => metrics
+                .user_sessions
+                .extend(sessions.into_iter().map(Into::into)),
+            Fragment::Sensors(sensors) => metrics.sensors.extend(sensors),
+            Fragment::Routing(routing) => metrics.routing = Some(routing),
+            Fragment::ProcessNetwork(usage) => metrics.process_network.extend(usage),
+            Fragment::PublicIp(ip) => metrics.public_ip = ip,
+            Fragment::FailedLogins(summary) => metrics.failed_logins = Some(summary),
+            Fragment::Cgroups(cgroups) => metrics.cgroups.extend(cgroups),
+            Fragment::Printers(printers) => metrics.printers.extend(printers),
+            Fragment::SshKeys(keys) => metrics.ssh_keys.extend(keys),
+            Fragment::KernelModules(modules) => metrics.kernel_modules = Some(modules),
+            Fragment::Custom(custom) => metrics.custom_metrics.extend(custom),
+            // Static info only
+            Fragment::Peripherals(_) => {}
+        }
+    }
+
+    /// Merge into a realtime message
+    pub fn apply_realtime(self, realtime: &mut RealtimeMetrics) {
+        match self {
+            Fragment::Gpus(gpus) => realtime
+                .gpu_usage
+                .extend(gpus.into_iter().map(|g| GpuUsage {
+                    index: g.index,
+                    usage_percent: g.usage_percent,
+                    memory_used: g.memory_used,
+                    temperature: g.temperature,
+                    power_watts: g.power_watts,
+                    clock_core_mhz: g.clock_core_mhz,
+                    encoder_usage: g.encoder_usage,
+                    decoder_usage: g.decoder_usage,
+                })),
+            Fragment::Npus(npus) => realtime
+                .npu_usage
+                .extend(npus.into_iter().map(|n| NpuUsage {
+                    index: n.index,
+                    usage_percent: n.usage_percent,
+                    memory_used: n.memory_used,
+                    temperature: n.temperature,
+                    power_watts: n.power_watts,
+                })),
+            Fragment::UserSessions(_)
+            | Fragment::Sensors(_)
+            | Fragment::Routing(_)
+            | Fragment::ProcessNetwork(_)
+            | Fragment::PublicIp(_)
+            | Fragment::FailedLogins(_)
+            | Fragment::Cgroups(_)
+            | Fragment::Peripherals(_)
+            | Fragment::Printers(_)
+            | Fragment::SshKeys(_)
+            | Fragment::KernelModules(_) => {}
+            Fragment::Custom(custom) => realtime.custom_metrics.extend(custom),
+        }
+    }
+
+    /// Merge into a periodic message
+    pub fn apply_periodic(self, periodic: &mut PeriodicData) {
+        match self {
+            Fragment::UserSessions(sessions) => periodic
+                .user_sessions
+                .extend(sessions.into_iter().map(Into::into)),
+            Fragment::Sensors(sensors) => periodic.sensors.extend(sensors),
+            Fragment::Routing(routing) => periodic.routing = Some(routing),
+            Fragment::ProcessNetwork(usage) => periodic.process_network.extend(usage),
+            Fragment::PublicIp(ip) => periodic.public_ip = ip,
+            Fragment::FailedLogins(summary) => periodic.failed_logins = Some(summary),
+            Fragment::Cgroups(cgroups) => periodic.cgroups.extend(cgroups),
+            Fragment::Printers(printers) => periodic.printers.extend(printers),
+            Fragment::SshKeys(keys) => periodic.ssh_keys.extend(keys),
+            Fragment::KernelModules(modules) => periodic.kernel_modules = Some(modules),
+            Fragment::Custom(custom) => periodic.custom_metrics.extend(custom),
+            Fragment::Gpus(_) | Fragment::Npus(_) | Fragment::Peripherals(_) => {}
+        }
+    }
+
+    /// Merge into a static info message
+    pub fn apply_static(self, info: &mut StaticInfo) {
+        match self {
+            Fragment::Gpus(gpus) => info.gpus.extend(gpus.into_iter().map(|g| GpuStaticInfo {
+                index: g.index,
+                name: g.name,
+                vendor: g.vendor,
+                memory_total: g.memory_total,
+                driver_version: g.driver_version,
+                pcie_generation: g.pcie_generation,
+                power_limit_watts: g.power_limit_watts,
+            })),
+            Fragment::Npus(npus) => info.npus.extend(npus.into_iter().map(|n| NpuStaticInfo {
+                index: n.index,
+                name: n.name,
+                vendor: n.vendor,
+                memory_total: n.memory_total,
+                driver_version: n.driver_version,
+            })),
+            Fragment::Routing(routing) => info.routing = Some(routing),
+            Fragment::PublicIp(ip) => info.public_ip = ip,
+            Fragment::Peripherals(peripherals) => info.peripherals = Some(peripherals),
+            Fragment::UserSessions(_)
+            | Fragment::Sensors(_)
+            | Fragment::ProcessNetwork(_)
+            | Fragment::FailedLogins(_)
+            | Fragment::Cgroups(_)
+            | Fragment::Printers(_)
+            | Fragment::SshKeys(_)
+            | Fragment::KernelModules(_)
+            | Fragment::Custom(_) => {}
+        }
+    }
+}
+
+impl From<gpu::GpuMetrics> for crate::proto::GpuMetrics {
+    fn from(g: gpu::GpuMetrics) -> Self {
+        Self {
+            index: g.index,
+            name: g.name,
+            vendor: g.vendor,
+            usage_percent: g.usage_percent,
+            memory_total: g.memory_total,
+            memory_used: g.memory_used,
+            temperature: g.temperature,
+            fan_speed_percent: g.fan_speed_percent,
+            power_watts: g.power_watts,
+            power_limit_watts: g.power_limit_watts,
+            clock_core_mhz: g.clock_core_mhz,
+            clock_memory_mhz: g.clock_memory_mhz,
+            driver_version: g.driver_version,
+            pcie_generation: g.pcie_generation,
+            encoder_usage: g.encoder_usage,
+            decoder_usage: g.decoder_usage,
+        }
+    }
+}
+
+impl From<npu::NpuMetrics> for crate::proto::NpuMetrics {
+    fn from(n: npu::NpuMetrics) -> Self {
+        Self {
+            index: n.index,
+            name: n.name,
+            vendor: n.vendor,
+            usage_percent: n.usage_percent,
+            memory_total: n.memory_total,
+            memory_used: n.memory_used,
+            temperature: n.temperature,
+            power_watts: n.power_watts,
+            driver_version: n.driver_version,
+        }
+    }
+}
+
+impl From<sessions::UserSession> for crate::proto::UserSession {
+    fn from(s: sessions::UserSession) -> Self {
+        Self {
+            username: s.username,
+            tty: s.tty,
+            login_time: s.login_time,
+            remote_host: s.remote_host,
+            idle_seconds: s.idle_seconds,
+            session_type: s.session_type,
+        }
+    }
+}
+
+/// A source of metrics data
+pub trait Collector: Send {
+    /// Unique name, used in `collector.disabled_collectors`
+    fn name(&self) -> &'static str;
+
+    /// How often this collector runs in the layered pipeline
+    fn interval(&self, config: &CollectorConfig) -> IntervalClass;
+
+    /// Collect one fragment
+    fn collect(&mut self, ctx: &CollectContext<'_>) -> anyhow::Result<Fragment>;
+}
+
+impl Collector for GpuCollector {
+    fn name(&self) -> &'static str {
+        "gpu"
+    }
+
+    fn interval(&self, _config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Realtime
+    }
+
+    fn collect(&mut self, _ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        let gpus = GpuCollector::collect(self);
+        energy::record_gpu_power(gpus.iter().map(|g| g.power_watts).sum());
+        Ok(Fragment::Gpus(gpus))
+    }
+}
+
+impl Collector for NpuCollector {
+    fn name(&self) -> &'static str {
+        "npu"
+    }
+
+    fn interval(&self, _config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Realtime
+    }
+
+    fn collect(&mut self, _ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        Ok(Fragment::Npus(NpuCollector::collect(self)))
+    }
+}
+
+impl Collector for SessionCollector {
+    fn name(&self) -> &'static str {
+        "sessions"
+    }
+
+    fn interval(&self, config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Periodic(Duration::from_millis(config.session_interval_ms))
+    }
+
+    fn collect(&mut self, _ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        Ok(Fragment::UserSessions(SessionCollector::collect(self)))
+    }
+}
+
+impl Collector for SensorCollector {
+    fn name(&self) -> &'static str {
+        "sensors"
+    }
+
+    fn interval(&self, config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Periodic(Duration::from_millis(config.sensor_interval_ms))
+    }
+
+    fn collect(&mut self, _ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        Ok(Fragment::Sensors(SensorCollector::collect(self)))
+    }
+}
+
+impl Collector for RouteCollector {
+    fn name(&self) -> &'static str {
+        "routes"
+    }
+
+    fn interval(&self, config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Periodic(Duration::from_millis(config.route_interval_ms))
+    }
+
+    fn collect(&mut self, _ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        Ok(Fragment::Routing(RouteCollector::collect(self)))
+    }
+}
+
+impl Collector for FailedLoginCollector {
+    fn name(&self) -> &'static str {
+        "failed_logins"
+    }
+
+    fn interval(&self, config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Periodic(Duration::from_millis(config.failed_login_interval_ms))
+    }
+
+    fn collect(&mut self, _ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        Ok(Fragment::FailedLogins(FailedLoginCollector::collect(self)))
+    }
+}
+
+impl Collector for ProcessNetworkCollector {
+    fn name(&self) -> &'static str {
+        "process_network"
+    }
+
+    fn interval(&self, config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Periodic(Duration::from_millis(config.process_network_interval_ms))
+    }
+
+    fn collect(&mut self, _ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        Ok(Fragment::ProcessNetwork(ProcessNetworkCollector::collect(
+            self,
+        )))
+    }
+}
+
+impl Collector for PublicIpCollector {
+    fn name(&self) -> &'static str {
+        "public_ip"
+    }
+
+    fn interval(&self, config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Periodic(Duration::from_millis(config.public_ip_interval_ms))
+    }
+
+    fn collect(&mut self, _ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        Ok(Fragment::PublicIp(PublicIpCollector::collect(self)))
+    }
+}
+
+struct Entry {
+    collector: Box<dyn Collector>,
+    interval: IntervalClass,
+    last_run: Instant,
+}
+
+/// Set of enabled collectors
+pub struct CollectorRegistry {
+    entries: Vec<Entry>,
+}
+
+impl CollectorRegistry {
+    /// Empty registry
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Registry with the built-in collectors enabled in `config`
+    pub fn with_builtin(config: &CollectorConfig) -> Self {
+        let mut registry = Self::new();
+        if cfg!(feature = "gpu") {
+            registry.register(Box::new(GpuCollector::new()), config);
+        }
+        if cfg!(feature = "npu") {
+            registry.register(Box::new(NpuCollector::new()), config);
+        }
+        registry.register(Box::new(SessionCollector::new()), config);
+        registry.register(Box::new(SensorCollector::new()), config);
+        registry.register(Box::new(RouteCollector::new()), config);
+        registry.register(Box::new(FailedLoginCollector::new()), config);
+        if config.enable_process_network {
+            registry.register(Box::new(ProcessNetworkCollector::new()), config);
+        }
+        if config.enable_public_ip {
+            let collector = PublicIpCollector::new(
+                config.public_ip_endpoints.clone(),
+                Duration::from_millis(config.public_ip_interval_ms),
+            );
+            registry.register(Box::new(collector), config);
+        }
+        if config.enable_peripherals {
+            registry.register(Box::new(PeripheralCollector::new()), config);
+        }
+        if let Some(dir) = &config.textfile_dir {
+            registry.register(Box::new(TextfileCollector::new(dir)), config);
+        }
+        if !config.rules.metrics.is_empty() || !config.rules.alerts.is_empty() {
+            #[cfg(feature = "rules")]
+            registry.register(Box::new(RulesCollector::new(&config.rules)), config);
+            #[cfg(not(feature = "rules"))]
+            warn!("collector.rules is set but this build has no rule scripting");
+        }
+        if config.cgroups.enabled {
+            registry.register(
+                Box::new(CgroupCollector::new(config.cgroups.clone())),
+                config,
+            );
+        }
+        if config.printers.enabled {
+            registry.register(
+                Box::new(PrinterCollector::new(config.printers.clone())),
+                config,
+            );
+        }
+        if config.capture_devices.enabled {
+            registry.register(
+                Box::new(CaptureCollector::new(config.capture_devices.clone())),
+                config,
+            );
+        }
+        if config.ssh_keys.enabled {
+            registry.register(
+                Box::new(SshKeyCollector::new(config.ssh_keys.clone())),
+                config,
+            );
+        }
+        if config.kernel_modules.enabled {
+            registry.register(
+                Box::new(KernelModuleCollector::new(config.kernel_modules.clone())),
+                config,
+            );
+        }
+        if config.dir_growth.enabled {
+            registry.register(
+                Box::new(DirGrowthCollector::new(config.dir_growth.clone())),
+                config,
+            );
+        }
+        if config.anomaly.enabled {
+            registry.register(
+                Box::new(AnomalyCollector::new(config.anomaly.clone())),
+                config,
+            );
+        }
+        if config.energy.enabled {
+            registry.register(
+                Box::new(EnergyCollector::new(config.energy.clone())),
+                config,
+            );
+        }
+        if config.wasm.enabled {
+            #[cfg(feature = "wasm")]
+            match super::wasm::WasmCollector::new(config.wasm.clone()) {
+                Ok(collector) => registry.register(Box::new(collector), config),
+                Err(e) => warn!("WASM plugin runtime unavailable: {:#}", e),
+            }
+            #[cfg(not(feature = "wasm"))]
+            warn!("collector.wasm is enabled but this build has no WASM support");
+        }
+        registry
+    }
+
+    /// Add a collector unless it is disabled in `config`
+    pub fn register(&mut self, collector: Box<dyn Collector>, config: &CollectorConfig) {
+        let name = collector.name();
+        if config.disabled_collectors.iter().any(|d| d == name) {
+            return;
+        }
+        if self.entries.iter().any(|e| e.collector.name() == name) {
+            warn!("Collector '{}' is already registered", name);
+            return;
+        }
+
+        self.entries.push(Entry {
+            interval: collector.interval(config),
+            collector,
+            last_run: Instant::now(),
+        });
+    }
+
+    /// Names of the registered collectors
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.entries.iter().map(|e| e.collector.name())
+    }
+
+    fn run(entry: &mut Entry, ctx: &CollectContext<'_>) -> Option<Fragment> {
+        entry.last_run = Instant::now();
+        match entry.collector.collect(ctx) {
+            Ok(fragment) => Some(fragment),
+            Err(e) => {
+                warn!("Collector '{}' failed: {}", entry.collector.name(), e);
+                None
+            }
+        }
+    }
+
+    /// Run every collector into a full metrics message
+    pub fn collect_full(&mut self, ctx: &CollectContext<'_>, metrics: &mut Metrics) {
+        for entry in &mut self.entries {
+            if let Some(fragment) = Self::run(entry, ctx) {
+                fragment.apply_full(metrics);
+            }
+        }
+    }
+
+    /// Run every collector into a static info message
+    pub fn collect_static(&mut self, ctx: &CollectContext<'_>, info: &mut StaticInfo) {
+        for entry in &mut self.entries {
+            if let Some(fragment) = Self::run(entry, ctx) {
+                fragment.apply_static(info);
+            }
+        }
+    }
+
+    /// Run realtime collectors
+    pub fn collect_realtime(&mut self, ctx: &CollectContext<'_>, realtime: &mut RealtimeMetrics) {
+        for entry in &mut self.entries {
+            if entry.interval == IntervalClass::Realtime
+                && let Some(fragment) = Self::run(entry, ctx)
+            {
+                fragment.apply_realtime(realtime);
+            }
+        }
+    }
+
+    /// Run periodic collectors whose interval has elapsed.
+    ///
+    /// Returns true if any collector ran.
+    pub fn collect_periodic(
+        &mut self,
+        ctx: &CollectContext<'_>,
+        periodic: &mut PeriodicData,
+    ) -> bool {
+        let mut ran = false;
+        for entry in &mut self.entries {
+            let IntervalClass::Periodic(interval) = entry.interval else {
+                continue;
+            };
+            if entry.last_run.elapsed() < interval {
+                continue;
+            }
+            ran = true;
+            if let Some(fragment) = Self::run(entry, ctx) {
+                fragment.apply_periodic(periodic);
+            }
+        }
+        ran
+    }
+
+    /// Run a single collector by name regardless of its interval
+    pub fn collect_one(&mut self, name: &str, ctx: &CollectContext<'_>) -> Option<Fragment> {
+        let entry = self
+            .entries
+            .iter_mut()
+            .find(|e| e.collector.name() == name)?;
+        Self::run(entry, ctx)
+    }
+}
+
+impl Default for CollectorRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Probe;
+
+    impl Collector for Probe {
+        fn name(&self) -> &'static str {
+            "probe"
+        }
+
+        fn interval(&self, _config: &CollectorConfig) -> IntervalClass {
+            IntervalClass::Periodic(Duration::ZERO)
+        }
+
+        fn collect(&mut self, _ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+            Ok(Fragment::Custom(vec![CustomMetric {
+                collector: "probe".to_string(),
+                name: "latency_ms".to_string(),
+                value: 12.5,
+                ..Default::default()
+            }]))
+        }
+    }
+
+    #[test]
+    fn test_registry_runs_custom_collector() {
+        let config = CollectorConfig::default();
+        let system = System::new();
+        let ctx = CollectContext {
+            system: &system,
+            config: &config,
+        };
+
+        let mut registry = CollectorRegistry::new();
+        registry.register(Box::new(Probe), &config);
+
+        let mut periodic = PeriodicData::default();
+        assert!(registry.collect_periodic(&ctx, &mut periodic));
+        assert_eq!(periodic.custom_metrics.len(), 1);
+
+        // Periodic collectors are not part of realtime data
+        let mut realtime = RealtimeMetrics::default();
+        registry.collect_realtime(&ctx, &mut realtime);
+        assert!(realtime.custom_metrics.is_empty());
+
+        let mut full = Metrics::default();
+        registry.collect_full(&ctx, &mut full);
+        assert_eq!(full.custom_metrics[0].name, "latency_ms");
+    }
+
+    #[test]
+    fn test_disabled_collectors_are_skipped() {
+        let config = CollectorConfig {
+            disabled_collectors: vec!["probe".to_string()],
+            ..Default::default()
+        };
+
+        let mut registry = CollectorRegistry::new();
+        registry.register(Box::new(Probe), &config);
+        assert_eq!(registry.names().count(), 0);
+    }
+}
@@ -0,0 +1,353 @@
+//! Routing table and default gateway collector
+//!
+//! Reports routes, default gateways and gateway reachability so connectivity
+//! problems can be triaged from the dashboard without shell access.
+//!
+//! - Linux: `/proc/net/route` and `/proc/net/ipv6_route`
+//! - macOS: `netstat -rn`
+//! - Windows: `Get-NetRoute`
+//!
+//! Gateway latency is measured with a single `ping` per default gateway.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::proto::{GatewayProbe, Route, RoutingInfo};
+use crate::utils::safe_command::exec_with_timeout;
+
+/// Routers can carry full BGP tables; only the first routes are reported
+const MAX_ROUTES: usize = 512;
+
+/// Per-gateway ping timeout
+const PING_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Routing table collector
+pub struct RouteCollector;
+
+impl RouteCollector {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn collect(&mut self) -> RoutingInfo {
+        let mut routes = Self::collect_routes();
+        routes.truncate(MAX_ROUTES);
+
+        let gateways = routes
+            .iter()
+            .filter(|r| is_default(&r.destination) && !r.gateway.is_empty())
+            .map(|r| probe_gateway(&r.gateway, &r.interface))
+            .collect();
+
+        RoutingInfo { routes, gateways }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn collect_routes() -> Vec<Route> {
+        let mut routes = std::fs::read_to_string("/proc/net/route")
+            .map(|s| parse_proc_route(&s))
+            .unwrap_or_default();
+        routes.extend(
+            std::fs::read_to_string("/proc/net/ipv6_route")
+                .map(|s| parse_proc_ipv6_route(&s))
+                .unwrap_or_default(),
+        );
+        routes
+    }
+
+    #[cfg(target_os = "macos")]
+    fn collect_routes() -> Vec<Route> {
+        let mut cmd = Command::new("netstat");
+        cmd.arg("-rn");
+        match exec_with_timeout(cmd, Duration::from_secs(5)) {
+            Some(output) if output.status.success() => {
+                parse_netstat(&String::from_utf8_lossy(&output.stdout))
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    fn collect_routes() -> Vec<Route> {
+        let mut cmd = Command::new("powershell");
+        cmd.args([
+            "-NoProfile",
+            "-Command",
+            "Get-NetRoute | Select-Object DestinationPrefix,NextHop,InterfaceAlias,RouteMetric | ConvertTo-Csv -NoTypeInformation",
+        ]);
+        match exec_with_timeout(cmd, Duration::from_secs(10)) {
+            Some(output) if output.status.success() => {
+                parse_net_route_csv(&String::from_utf8_lossy(&output.stdout))
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    fn collect_routes() -> Vec<Route> {
+        Vec::new()
+    }
+}
+
+impl Default for RouteCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_default(destination: &str) -> bool {
+    matches!(destination, "0.0.0.0/0" | "::/0")
+}
+
+fn route(destination: String, gateway: String, interface: &str, metric: u32) -> Route {
+    Route {
+        destination,
+        gateway,
+        interface: interface.to_string(),
+        metric,
+    }
+}
+
+/// Ping a gateway once and report its round-trip time
+fn probe_gateway(gateway: &str, interface: &str) -> GatewayProbe {
+    // IPv6 link-local gateways need a scope
+    let target = if gateway.starts_with("fe80:") {
+        format!("{gateway}%{interface}")
+    } else {
+        gateway.to_string()
+    };
+
+    let mut cmd = Command::new("ping");
+    #[cfg(target_os = "windows")]
+    cmd.args(["-n", "1", "-w", "1000", &target]);
+    #[cfg(target_os = "macos")]
+    cmd.args(["-c", "1", "-t", "1", &target]);
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    cmd.args(["-c", "1", "-W", "1", &target]);
+
+    let latency_ms = exec_with_timeout(cmd, PING_TIMEOUT)
+        .filter(|o| o.status.success())
+        .and_then(|o| parse_ping_latency(&String::from_utf8_lossy(&o.stdout)));
+
+    GatewayProbe {
+        gateway: gateway.to_string(),
+        interface: interface.to_string(),
+        reachable: latency_ms.is_some(),
+        latency_ms: latency_ms.unwrap_or(0.0),
+    }
+}
+
+/// Parse the round-trip time from ping output (`time=0.42 ms`, `time<1ms`)
+fn parse_ping_latency(output: &str) -> Option<f64> {
+    output.lines().find_map(|line| {
+        let (_, rest) = line.split_once("time")?;
+        let value = rest.strip_prefix('=').or_else(|| rest.strip_prefix('<'))?;
+        let number: String = value
+            .chars()
+            .take_while(|c| c.is_ascii_digit() || *c == '.')
+            .collect();
+        number.parse().ok()
+    })
+}
+
+/// Parse `/proc/net/route` (IPv4, little-endian hex addresses)
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn parse_proc_route(content: &str) -> Vec<Route> {
+    let hex_ip = |s: &str| {
+        u32::from_str_radix(s, 16)
+            .ok()
+            .map(|v| Ipv4Addr::from(v.swap_bytes()))
+    };
+
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 8 || fields[0] == "lo" {
+                return None;
+            }
+            let destination = hex_ip(fields[1])?;
+            let gateway = hex_ip(fields[2])?;
+            let metric = fields[6].parse().unwrap_or(0);
+            let prefix = hex_ip(fields[7])?.to_bits().count_ones();
+            Some(route(
+                format!("{destination}/{prefix}"),
+                if gateway.is_unspecified() {
+                    String::new()
+                } else {
+                    gateway.to_string()
+                },
+                fields[0],
+                metric,
+            ))
+        })
+        .collect()
+}
+
+/// Parse `/proc/net/ipv6_route`
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn parse_proc_ipv6_route(content: &str) -> Vec<Route> {
+    let hex_ip = |s: &str| u128::from_str_radix(s, 16).ok().map(Ipv6Addr::from);
+
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 10 || fields[9] == "lo" {
+                return None;
+            }
+            let destination = hex_ip(fields[0])?;
+            let prefix = u8::from_str_radix(fields[1], 16).ok()?;
+            let gateway = hex_ip(fields[4])?;
+            let metric = u32::from_str_radix(fields[5], 16).unwrap_or(0);
+            Some(route(
+                format!("{destination}/{prefix}"),
+                if gateway.is_unspecified() {
+                    String::new()
+                } else {
+                    gateway.to_string()
+                },
+                fields[9],
+                metric,
+            ))
+        })
+        .collect()
+}
+
+/// Parse `netstat -rn` (macOS/BSD)
+#[cfg_attr(not(any(test, target_os = "macos")), allow(dead_code))]
+fn parse_netstat(output: &str) -> Vec<Route> {
+    let mut ipv6 = false;
+    output
+        .lines()
+        .filter_map(|line| {
+            if line.starts_with("Internet6") {
+                ipv6 = true;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 4 || fields[0] == "Destination" {
+                return None;
+            }
+            let (destination, gateway, interface) = (fields[0], fields[1], fields[3]);
+            if interface.starts_with("lo") || !fields[2].contains('U') {
+                return None;
+            }
+            let destination = match destination {
+                "default" if ipv6 => "::/0".to_string(),
+                "default" => "0.0.0.0/0".to_string(),
+                other => other.to_string(),
+            };
+            // On-link routes name a link or MAC address instead of a gateway
+            let gateway = if fields[2].contains('G') {
+                gateway.split('%').next().unwrap_or(gateway).to_string()
+            } else {
+                String::new()
+            };
+            Some(route(destination, gateway, interface, 0))
+        })
+        .collect()
+}
+
+/// Parse `Get-NetRoute` CSV output (Windows)
+#[cfg_attr(not(any(test, target_os = "windows")), allow(dead_code))]
+fn parse_net_route_csv(csv: &str) -> Vec<Route> {
+    csv.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line
+                .split(',')
+                .map(|f| f.trim().trim_matches('"'))
+                .collect();
+            let [destination, next_hop, interface, metric] = fields[..] else {
+                return None;
+            };
+            if interface.contains("Loopback") {
+                return None;
+            }
+            let gateway = match next_hop {
+                "0.0.0.0" | "::" => String::new(),
+                other => other.to_string(),
+            };
+            Some(route(
+                destination.to_string(),
+                gateway,
+                interface,
+                metric.parse().unwrap_or(0),
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_route() {
+        let content = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+eth0\t00000000\t010200C0\t0003\t0\t0\t100\t00000000\t0\t0\t0\n\
+eth0\t000200C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0\n";
+        let routes = parse_proc_route(content);
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].destination, "0.0.0.0/0");
+        assert_eq!(routes[0].gateway, "192.0.2.1");
+        assert_eq!(routes[0].metric, 100);
+        assert_eq!(routes[1].destination, "192.0.2.0/24");
+        assert!(routes[1].gateway.is_empty());
+    }
+
+    #[test]
+    fn test_parse_proc_ipv6_route() {
+        let content = "00000000000000000000000000000000 00 00000000000000000000000000000000 00 fe800000000000000000000000000001 00000400 00000001 00000000 00000003     eth0\n\
+00000000000000000000000000000000 00 00000000000000000000000000000000 00 00000000000000000000000000000000 ffffffff 00000001 00000000 00200200       lo\n";
+        let routes = parse_proc_ipv6_route(content);
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].destination, "::/0");
+        assert_eq!(routes[0].gateway, "fe80::1");
+        assert_eq!(routes[0].metric, 1024);
+    }
+
+    #[test]
+    fn test_parse_netstat() {
+        let output = "Routing tables\n\nInternet:\nDestination        Gateway            Flags           Netif Expire\n\
+default            192.168.1.1        UGScg             en0\n\
+127                127.0.0.1          UCS               lo0\n\
+192.168.1          link#6             UCS               en0      !\n\n\
+Internet6:\nDestination                             Gateway                                 Flags           Netif Expire\n\
+default                                 fe80::1%en0                             UGcg              en0\n";
+        let routes = parse_netstat(output);
+        assert_eq!(routes.len(), 3);
+        assert_eq!(routes[0].destination, "0.0.0.0/0");
+        assert_eq!(routes[0].gateway, "192.168.1.1");
+        assert!(routes[1].gateway.is_empty());
+        assert_eq!(routes[2].destination, "::/0");
+        assert_eq!(routes[2].gateway, "fe80::1");
+    }
+
+    #[test]
+    fn test_parse_net_route_csv() {
+        let csv = "\"DestinationPrefix\",\"NextHop\",\"InterfaceAlias\",\"RouteMetric\"\n\
+\"0.0.0.0/0\",\"10.0.0.1\",\"Ethernet\",\"0\"\n\
+\"10.0.0.0/24\",\"0.0.0.0\",\"Ethernet\",\"256\"\n\
+\"127.0.0.0/8\",\"0.0.0.0\",\"Loopback Pseudo-Interface 1\",\"256\"\n";
+        let routes = parse_net_route_csv(csv);
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].gateway, "10.0.0.1");
+        assert_eq!(routes[1].metric, 256);
+    }
+
+    #[test]
+    fn test_parse_ping_latency() {
+        assert_eq!(
+            parse_ping_latency("64 bytes from 10.0.0.1: icmp_seq=1 ttl=64 time=0.421 ms"),
+            Some(0.421)
+        );
+        assert_eq!(
+            parse_ping_latency("Reply from 10.0.0.1: bytes=32 time<1ms TTL=64"),
+            Some(1.0)
+        );
+        assert_eq!(parse_ping_latency("Request timed out."), None);
+    }
+}
@@ -0,0 +1,351 @@
+//! Scripted metrics and alert rules
+//!
+//! `collector.rules` holds small Rhai scripts evaluated against the latest
+//! system data. Metric scripts return a number that is sent as a custom
+//! metric (collector "rules"); alert conditions return a boolean and are
+//! reported as `alerts` log events when they start or stop firing. Scripts
+//! see these variables:
+//!
+//! - `cpu`: CPU usage percent
+//! - `mem_used`, `mem_total`, `mem_percent`, `swap_used`, `swap_total`
+//! - `load1`, `load5`, `load15`, `uptime`
+//! - `disks`: array of `#{mount, total, used, percent}`
+//! - `metrics`: derived metrics computed earlier in the same run
+//!
+//! Scripts can't touch files, the network or processes, and runs are capped
+//! by `max_operations`.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use rhai::{AST, Array, Dynamic, Engine, Map, Scope};
+use sysinfo::{Disks, System};
+use tracing::{debug, info, warn};
+
+use crate::config::{CollectorConfig, RulesConfig};
+use crate::proto::{CustomMetric, LogBatch, LogEntry};
+
+use super::registry::{CollectContext, Collector, Fragment, IntervalClass};
+use super::syslog;
+
+struct Rule {
+    name: String,
+    ast: AST,
+}
+
+struct Alert {
+    rule: Rule,
+    severity: String,
+    message: String,
+    firing: bool,
+}
+
+/// Evaluates the configured metric and alert scripts
+pub struct RulesCollector {
+    engine: Engine,
+    interval: Duration,
+    metrics: Vec<(Rule, String)>,
+    alerts: Vec<Alert>,
+}
+
+impl RulesCollector {
+    pub fn new(config: &RulesConfig) -> Self {
+        let engine = sandboxed_engine(config.max_operations);
+        let compile = |name: &str, script: &str| match engine.compile(script) {
+            Ok(ast) => Some(Rule {
+                name: name.to_string(),
+                ast,
+            }),
+            Err(e) => {
+                warn!("Rule '{}' does not compile: {}", name, e);
+                None
+            }
+        };
+
+        let metrics = config
+            .metrics
+            .iter()
+            .filter_map(|m| Some((compile(&m.name, &m.script)?, m.unit.clone())))
+            .collect();
+        let alerts = config
+            .alerts
+            .iter()
+            .filter_map(|a| {
+                Some(Alert {
+                    rule: compile(&a.name, &a.condition)?,
+                    severity: a.severity.clone(),
+                    message: a.message.clone(),
+                    firing: false,
+                })
+            })
+            .collect();
+
+        Self {
+            engine,
+            interval: Duration::from_millis(config.interval_ms),
+            metrics,
+            alerts,
+        }
+    }
+
+    /// Run every rule against `scope`, returning metrics and alert transitions
+    fn evaluate(&mut self, scope: &mut Scope<'_>) -> (Vec<CustomMetric>, Vec<LogEntry>) {
+        let mut output = Vec::new();
+        let mut derived = Map::new();
+        for (rule, unit) in &self.metrics {
+            scope.set_value("metrics", derived.clone());
+            let result = self
+                .engine
+                .eval_ast_with_scope::<Dynamic>(scope, &rule.ast)
+                .map_err(|e| e.to_string())
+                .and_then(|value| as_number(&value));
+            match result {
+                Ok(value) => {
+                    derived.insert(rule.name.as_str().into(), Dynamic::from_float(value));
+                    output.push(CustomMetric {
+                        collector: "rules".to_string(),
+                        name: rule.name.clone(),
+                        value,
+                        unit: unit.clone(),
+                        ..Default::default()
+                    });
+                }
+                Err(e) => warn!("Rule '{}' failed: {}", rule.name, e),
+            }
+        }
+
+        scope.set_value("metrics", derived);
+        let mut events = Vec::new();
+        for alert in &mut self.alerts {
+            let firing = match self
+                .engine
+                .eval_ast_with_scope::<Dynamic>(scope, &alert.rule.ast)
+                .map_err(|e| e.to_string())
+                .and_then(|v| v.as_bool().map_err(|t| format!("expected bool, got {t}")))
+            {
+                Ok(firing) => firing,
+                Err(e) => {
+                    warn!("Alert '{}' failed: {}", alert.rule.name, e);
+                    continue;
+                }
+            };
+            if firing != alert.firing {
+                alert.firing = firing;
+                info!(
+                    "Alert '{}' {}",
+                    alert.rule.name,
+                    if firing { "firing" } else { "resolved" }
+                );
+                events.push(alert_event(alert));
+            }
+            output.push(CustomMetric {
+                collector: "rules".to_string(),
+                name: format!("alert.{}", alert.rule.name),
+                value: if firing { 1.0 } else { 0.0 },
+                ..Default::default()
+            });
+        }
+        (output, events)
+    }
+}
+
+impl Collector for RulesCollector {
+    fn name(&self) -> &'static str {
+        "rules"
+    }
+
+    fn interval(&self, _config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Periodic(self.interval)
+    }
+
+    fn collect(&mut self, ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        let mut scope = system_scope(ctx.system);
+        let (metrics, events) = self.evaluate(&mut scope);
+        if !events.is_empty() {
+            syslog::publish(LogBatch {
+                source: "alerts".to_string(),
+                entries: events,
+                dropped: 0,
+            });
+        }
+        Ok(Fragment::Custom(metrics))
+    }
+}
+
+/// Engine with resource limits and without `eval`
+fn sandboxed_engine(max_operations: u64) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(max_operations)
+        .set_max_call_levels(16)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(4096)
+        .set_max_array_size(1024)
+        .set_max_map_size(1024)
+        .disable_symbol("eval");
+    engine.on_print(|s| debug!("[rules] {}", s));
+    engine.on_debug(|s, _, _| debug!("[rules] {}", s));
+    engine
+}
+
+fn system_scope(system: &System) -> Scope<'static> {
+    let load = System::load_average();
+    let percent = |used: u64, total: u64| {
+        if total == 0 {
+            0.0
+        } else {
+            used as f64 / total as f64 * 100.0
+        }
+    };
+
+    let disks: Array = Disks::new_with_refreshed_list()
+        .list()
+        .iter()
+        .map(|disk| {
+            let total = disk.total_space();
+            let used = total.saturating_sub(disk.available_space());
+            let mut map = Map::new();
+            map.insert(
+                "mount".into(),
+                disk.mount_point().to_string_lossy().into_owned().into(),
+            );
+            map.insert("total".into(), Dynamic::from_int(total as i64));
+            map.insert("used".into(), Dynamic::from_int(used as i64));
+            map.insert("percent".into(), Dynamic::from_float(percent(used, total)));
+            Dynamic::from_map(map)
+        })
+        .collect();
+
+    let mut scope = Scope::new();
+    scope
+        .push_constant("cpu", f64::from(system.global_cpu_usage()))
+        .push_constant("mem_used", system.used_memory() as i64)
+        .push_constant("mem_total", system.total_memory() as i64)
+        .push_constant(
+            "mem_percent",
+            percent(system.used_memory(), system.total_memory()),
+        )
+        .push_constant("swap_used", system.used_swap() as i64)
+        .push_constant("swap_total", system.total_swap() as i64)
+        .push_constant("load1", load.one)
+        .push_constant("load5", load.five)
+        .push_constant("load15", load.fifteen)
+        .push_constant("uptime", System::uptime() as i64)
+        .push_constant("disks", disks)
+        .push("metrics", Map::new());
+    scope
+}
+
+fn as_number(value: &Dynamic) -> Result<f64, String> {
+    let number = value
+        .as_float()
+        .or_else(|_| value.as_int().map(|i| i as f64))
+        .map_err(|t| format!("expected a number, got {t}"))?;
+    if number.is_finite() {
+        Ok(number)
+    } else {
+        Err("result is not finite".to_string())
+    }
+}
+
+fn alert_event(alert: &Alert) -> LogEntry {
+    let state = if alert.firing { "firing" } else { "resolved" };
+    let message = if alert.message.is_empty() {
+        format!("{} {}", alert.rule.name, state)
+    } else {
+        format!("{} ({})", alert.message, state)
+    };
+    LogEntry {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        level: if alert.firing {
+            alert.severity.clone()
+        } else {
+            "info".to_string()
+        },
+        source: "rules".to_string(),
+        message,
+        metadata: HashMap::from([
+            ("rule".to_string(), alert.rule.name.clone()),
+            ("state".to_string(), state.to_string()),
+        ]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ScriptAlert, ScriptMetric};
+
+    fn scope(cpu: f64) -> Scope<'static> {
+        let mut scope = Scope::new();
+        scope
+            .push_constant("cpu", cpu)
+            .push_constant("mem_used", 3_i64)
+            .push_constant("mem_total", 4_i64)
+            .push("metrics", Map::new());
+        scope
+    }
+
+    #[test]
+    fn test_metrics_and_alerts() {
+        let config = RulesConfig {
+            metrics: vec![
+                ScriptMetric {
+                    name: "mem_ratio".to_string(),
+                    script: "mem_used.to_float() / mem_total.to_float()".to_string(),
+                    unit: String::new(),
+                },
+                ScriptMetric {
+                    name: "headroom".to_string(),
+                    script: "let r = metrics.mem_ratio; (1.0 - r) * 100.0".to_string(),
+                    unit: "%".to_string(),
+                },
+            ],
+            alerts: vec![ScriptAlert {
+                name: "busy".to_string(),
+                condition: "cpu > 90.0 && metrics.headroom < 50.0".to_string(),
+                severity: "critical".to_string(),
+                message: "Host is saturated".to_string(),
+            }],
+            ..Default::default()
+        };
+        let mut rules = RulesCollector::new(&config);
+
+        let (metrics, events) = rules.evaluate(&mut scope(95.0));
+        assert_eq!(metrics[0].value, 0.75);
+        assert_eq!(metrics[1].value, 25.0);
+        assert_eq!(metrics[2].name, "alert.busy");
+        assert_eq!(metrics[2].value, 1.0);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].level, "critical");
+
+        // Only transitions are reported
+        assert!(rules.evaluate(&mut scope(95.0)).1.is_empty());
+        let (_, events) = rules.evaluate(&mut scope(10.0));
+        assert_eq!(events[0].metadata["state"], "resolved");
+    }
+
+    #[test]
+    fn test_sandbox_limits() {
+        let config = RulesConfig {
+            max_operations: 1000,
+            metrics: vec![
+                ScriptMetric {
+                    name: "spin".to_string(),
+                    script: "let x = 0; loop { x += 1; }".to_string(),
+                    unit: String::new(),
+                },
+                ScriptMetric {
+                    name: "eval".to_string(),
+                    script: r#"eval("1")"#.to_string(),
+                    unit: String::new(),
+                },
+            ],
+            ..Default::default()
+        };
+        let mut rules = RulesCollector::new(&config);
+        // `eval` is rejected at compile time, the loop runs out of operations
+        assert_eq!(rules.metrics.len(), 1);
+        assert!(rules.evaluate(&mut scope(0.0)).0.is_empty());
+    }
+}
@@ -0,0 +1,326 @@
+//! Fan speed and chassis sensor collector
+//!
+//! Fan failures usually show up well before thermal throttling, so fans are
+//! reported with their alarm state alongside chassis temperatures.
+//!
+//! - Linux: hwmon (`fanN_input`, `fanN_min`, `fanN_alarm`, `tempN_input`)
+//! - Windows: LibreHardwareMonitor/OpenHardwareMonitor WMI bridge
+//! - macOS: `powermetrics --samplers smc` (root only)
+//! - Servers: `ipmitool sdr` when no local fan sensors are found
+
+use std::process::Command;
+use std::time::Duration;
+
+use crate::proto::{HardwareSensor, SensorKind};
+use crate::utils::safe_command::exec_with_timeout;
+
+/// Sensor command timeout (IPMI over a slow BMC can take a while)
+const SENSOR_COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Fan and chassis sensor collector
+pub struct SensorCollector {
+    ipmi_available: bool,
+}
+
+impl SensorCollector {
+    pub fn new() -> Self {
+        Self {
+            ipmi_available: true,
+        }
+    }
+
+    pub fn collect(&mut self) -> Vec<HardwareSensor> {
+        let mut sensors = Self::collect_platform();
+
+        // Servers often expose fans only through the BMC
+        if self.ipmi_available
+            && !sensors
+                .iter()
+                .any(|s| s.kind == SensorKind::SensorFan as i32)
+        {
+            match Self::collect_ipmi() {
+                Some(ipmi) => sensors.extend(ipmi),
+                // Don't retry a missing ipmitool or BMC every interval
+                None => self.ipmi_available = false,
+            }
+        }
+
+        sensors
+    }
+
+    #[cfg(target_os = "linux")]
+    fn collect_platform() -> Vec<HardwareSensor> {
+        read_hwmon(std::path::Path::new("/sys/class/hwmon"))
+    }
+
+    #[cfg(target_os = "windows")]
+    fn collect_platform() -> Vec<HardwareSensor> {
+        for namespace in ["root/LibreHardwareMonitor", "root/OpenHardwareMonitor"] {
+            let script = format!(
+                "Get-CimInstance -Namespace {namespace} -ClassName Sensor \
+                 -ErrorAction SilentlyContinue \
+                 | Where-Object {{ $_.SensorType -eq 'Fan' -or $_.SensorType -eq 'Temperature' }} \
+                 | Select-Object Name,SensorType,Value | ConvertTo-Csv -NoTypeInformation"
+            );
+            let mut cmd = Command::new("powershell");
+            cmd.args(["-NoProfile", "-Command", &script]);
+            if let Some(output) = exec_with_timeout(cmd, SENSOR_COMMAND_TIMEOUT) {
+                if output.status.success() {
+                    let sensors =
+                        parse_hardware_monitor_csv(&String::from_utf8_lossy(&output.stdout));
+                    if !sensors.is_empty() {
+                        return sensors;
+                    }
+                }
+            }
+        }
+        Vec::new()
+    }
+
+    #[cfg(target_os = "macos")]
+    fn collect_platform() -> Vec<HardwareSensor> {
+        let mut cmd = Command::new("powermetrics");
+        cmd.args(["--samplers", "smc", "-i", "1", "-n", "1"]);
+        match exec_with_timeout(cmd, SENSOR_COMMAND_TIMEOUT) {
+            Some(output) if output.status.success() => {
+                parse_powermetrics(&String::from_utf8_lossy(&output.stdout))
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+    fn collect_platform() -> Vec<HardwareSensor> {
+        Vec::new()
+    }
+
+    fn collect_ipmi() -> Option<Vec<HardwareSensor>> {
+        let mut cmd = Command::new("ipmitool");
+        cmd.args(["sdr", "elist", "full"]);
+        let output = exec_with_timeout(cmd, SENSOR_COMMAND_TIMEOUT)?;
+        if !output.status.success() {
+            return None;
+        }
+        Some(parse_ipmitool_sdr(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+impl Default for SensorCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn sensor(name: String, source: &str, kind: SensorKind, value: f64) -> HardwareSensor {
+    HardwareSensor {
+        name,
+        source: source.to_string(),
+        kind: kind as i32,
+        value,
+        ..Default::default()
+    }
+}
+
+/// Read a trimmed integer sysfs attribute
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn read_sysfs(path: &std::path::Path) -> Option<i64> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Collect fans and temperatures from a hwmon class directory
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn read_hwmon(root: &std::path::Path) -> Vec<HardwareSensor> {
+    let mut sensors = Vec::new();
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return sensors;
+    };
+
+    let mut chips: Vec<_> = entries.flatten().map(|e| e.path()).collect();
+    chips.sort();
+
+    for chip in chips {
+        let chip_name = std::fs::read_to_string(chip.join("name"))
+            .map(|n| n.trim().to_string())
+            .unwrap_or_default();
+        let source = format!("hwmon:{chip_name}");
+
+        for index in 1..=16 {
+            let label = |prefix: &str| {
+                std::fs::read_to_string(chip.join(format!("{prefix}{index}_label")))
+                    .map(|l| l.trim().to_string())
+                    .unwrap_or_else(|_| format!("{prefix}{index}"))
+            };
+
+            if let Some(rpm) = read_sysfs(&chip.join(format!("fan{index}_input"))) {
+                let min = read_sysfs(&chip.join(format!("fan{index}_min"))).unwrap_or(0);
+                let alarm = read_sysfs(&chip.join(format!("fan{index}_alarm"))).unwrap_or(0);
+                let mut fan = sensor(label("fan"), &source, SensorKind::SensorFan, rpm as f64);
+                fan.min_value = min as f64;
+                fan.alarm = alarm != 0 || (min > 0 && rpm < min);
+                sensors.push(fan);
+            }
+
+            if let Some(millis) = read_sysfs(&chip.join(format!("temp{index}_input"))) {
+                let alarm = read_sysfs(&chip.join(format!("temp{index}_alarm"))).unwrap_or(0);
+                let mut temp = sensor(
+                    label("temp"),
+                    &source,
+                    SensorKind::SensorTemperature,
+                    millis as f64 / 1000.0,
+                );
+                temp.alarm = alarm != 0;
+                sensors.push(temp);
+            }
+        }
+    }
+
+    sensors
+}
+
+/// Parse `ipmitool sdr elist full` output.
+///
+/// Lines look like `FAN1 | 30h | ok | 29.1 | 5400 RPM`; non-`ok` states
+/// (`cr`, `nr`, `lnc`, ...) are reported as alarms.
+fn parse_ipmitool_sdr(output: &str) -> Vec<HardwareSensor> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('|').map(str::trim).collect();
+            if fields.len() < 5 {
+                return None;
+            }
+            let (name, status, reading) = (fields[0], fields[2], fields[4]);
+            let (value, unit) = reading.split_once(' ')?;
+            let value: f64 = value.parse().ok()?;
+            let kind = match unit.trim() {
+                "RPM" => SensorKind::SensorFan,
+                "degrees C" => SensorKind::SensorTemperature,
+                _ => return None,
+            };
+            let mut s = sensor(name.to_string(), "ipmi", kind, value);
+            s.alarm = !matches!(status, "ok" | "ns");
+            Some(s)
+        })
+        .collect()
+}
+
+/// Parse `Name,SensorType,Value` CSV rows from the hardware monitor WMI bridge
+#[cfg_attr(not(any(test, target_os = "windows")), allow(dead_code))]
+fn parse_hardware_monitor_csv(csv: &str) -> Vec<HardwareSensor> {
+    csv.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line
+                .split(',')
+                .map(|f| f.trim().trim_matches('"'))
+                .collect();
+            let [name, kind, value] = fields[..] else {
+                return None;
+            };
+            let kind = match kind {
+                "Fan" => SensorKind::SensorFan,
+                "Temperature" => SensorKind::SensorTemperature,
+                _ => return None,
+            };
+            Some(sensor(name.to_string(), "wmi", kind, value.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Parse fan lines (`Fan: 1797.76 rpm`) from `powermetrics --samplers smc`
+#[cfg_attr(not(any(test, target_os = "macos")), allow(dead_code))]
+fn parse_powermetrics(output: &str) -> Vec<HardwareSensor> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, reading) = line.split_once(':')?;
+            let name = name.trim();
+            let reading = reading.trim();
+            if let Some(rpm) = reading.strip_suffix("rpm") {
+                let rpm = rpm.trim().parse().ok()?;
+                return Some(sensor(name.to_string(), "smc", SensorKind::SensorFan, rpm));
+            }
+            if name.ends_with("temperature") {
+                let celsius = reading.strip_suffix('C')?.trim().parse().ok()?;
+                return Some(sensor(
+                    name.to_string(),
+                    "smc",
+                    SensorKind::SensorTemperature,
+                    celsius,
+                ));
+            }
+            None
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_hwmon() {
+        let root = std::env::temp_dir().join(format!("nanolink-hwmon-{}", std::process::id()));
+        let chip = root.join("hwmon0");
+        std::fs::create_dir_all(&chip).unwrap();
+        for (file, value) in [
+            ("name", "nct6775"),
+            ("fan1_input", "1200"),
+            ("fan1_label", "CPU Fan"),
+            ("fan2_input", "0"),
+            ("fan2_min", "300"),
+            ("temp1_input", "41500"),
+        ] {
+            std::fs::write(chip.join(file), value).unwrap();
+        }
+
+        let sensors = read_hwmon(&root);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(sensors.len(), 3);
+        assert_eq!(sensors[0].name, "CPU Fan");
+        assert_eq!(sensors[0].source, "hwmon:nct6775");
+        assert_eq!(sensors[0].value, 1200.0);
+        assert!(!sensors[0].alarm);
+        assert_eq!(sensors[1].name, "temp1");
+        assert_eq!(sensors[1].kind, SensorKind::SensorTemperature as i32);
+        assert_eq!(sensors[1].value, 41.5);
+        // Stalled fan below its minimum
+        assert_eq!(sensors[2].name, "fan2");
+        assert!(sensors[2].alarm);
+    }
+
+    #[test]
+    fn test_parse_ipmitool_sdr() {
+        let output = "\
+FAN1             | 30h | ok  | 29.1 | 5400 RPM
+FAN2             | 31h | cr  | 29.2 | 0 RPM
+Inlet Temp       | 04h | ok  |  7.1 | 23 degrees C
+PS1 Status       | 63h | ok  | 10.1 | Presence detected
+";
+        let sensors = parse_ipmitool_sdr(output);
+        assert_eq!(sensors.len(), 3);
+        assert_eq!(sensors[0].value, 5400.0);
+        assert!(!sensors[0].alarm);
+        assert!(sensors[1].alarm);
+        assert_eq!(sensors[2].kind, SensorKind::SensorTemperature as i32);
+    }
+
+    #[test]
+    fn test_parse_hardware_monitor_csv() {
+        let csv = "\"Name\",\"SensorType\",\"Value\"\n\"Fan #1\",\"Fan\",\"850\"\n\"CPU Package\",\"Temperature\",\"52\"\n\"CPU Total\",\"Load\",\"10\"\n";
+        let sensors = parse_hardware_monitor_csv(csv);
+        assert_eq!(sensors.len(), 2);
+        assert_eq!(sensors[0].kind, SensorKind::SensorFan as i32);
+        assert_eq!(sensors[1].value, 52.0);
+    }
+
+    #[test]
+    fn test_parse_powermetrics() {
+        let output = "**** SMC sensors ****\n\nFan: 1797.76 rpm\nCPU die temperature: 52.31 C\nCPU Thermal level: 0\n";
+        let sensors = parse_powermetrics(output);
+        assert_eq!(sensors.len(), 2);
+        assert_eq!(sensors[0].value, 1797.76);
+        assert_eq!(sensors[1].name, "CPU die temperature");
+    }
+}
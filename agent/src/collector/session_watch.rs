@@ -0,0 +1,220 @@
+//! Login/logout detection
+//!
+//! User sessions are otherwise only polled every `session_interval_ms`. The
+//! watcher signals every change to the login records so an interactive
+//! login on a production box is reported as a [`SessionEvent`] within a
+//! second, and the optional `collector.session_event_hook` is run.
+//!
+//! - Linux: inotify on utmp and the systemd-logind session directory
+//! - macOS: utmpx modification time, checked every `POLL_INTERVAL`
+//! - Windows: `query user` output, compared every `POLL_INTERVAL` (logon
+//!   events would need an EventLog subscription)
+
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tracing::warn;
+
+use crate::proto::{SessionEvent, SessionEventKind, UserSession};
+use crate::utils::async_command::{CommandResult, CommandTimeout, run_command_async};
+
+use super::watcher::ChangeWatcher;
+
+/// Login record polling interval where no change notification is available
+#[cfg(not(target_os = "linux"))]
+const POLL_INTERVAL: Duration = Duration::from_secs(if cfg!(windows) { 5 } else { 2 });
+
+/// Session event hooks are killed after this long
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+static WATCHER: ChangeWatcher = ChangeWatcher::new("session-watcher", run);
+
+/// Subscribe to login/logout changes
+pub fn subscribe() -> watch::Receiver<u64> {
+    WATCHER.subscribe()
+}
+
+#[cfg(target_os = "linux")]
+fn run(watcher: &ChangeWatcher) -> anyhow::Result<()> {
+    use nix::errno::Errno;
+    use nix::poll::{PollFd, PollFlags, PollTimeout, poll};
+    use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+    use std::os::fd::AsFd;
+
+    let inotify = Inotify::init(InitFlags::IN_CLOEXEC | InitFlags::IN_NONBLOCK)?;
+
+    // utmp is rewritten in place; logind replaces its per-session state
+    // files by rename
+    let utmp = ["/run/utmp", "/var/run/utmp"]
+        .iter()
+        .any(|path| inotify.add_watch(*path, AddWatchFlags::IN_MODIFY).is_ok());
+    let logind = inotify
+        .add_watch(
+            "/run/systemd/sessions",
+            AddWatchFlags::IN_CREATE | AddWatchFlags::IN_DELETE | AddWatchFlags::IN_MOVED_TO,
+        )
+        .is_ok();
+    if !utmp && !logind {
+        anyhow::bail!("neither utmp nor the logind session directory can be watched");
+    }
+
+    loop {
+        let mut fds = [PollFd::new(inotify.as_fd(), PollFlags::POLLIN)];
+        match poll(&mut fds, PollTimeout::NONE) {
+            Ok(_) => {}
+            Err(Errno::EINTR) => continue,
+            Err(e) => return Err(e.into()),
+        }
+
+        watcher.settle();
+        while inotify.read_events().is_ok_and(|events| !events.is_empty()) {}
+        watcher.notify();
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn run(watcher: &ChangeWatcher) -> anyhow::Result<()> {
+    let modified = || {
+        std::fs::metadata("/var/run/utmpx")
+            .and_then(|m| m.modified())
+            .ok()
+    };
+
+    let mut known = modified();
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        let current = modified();
+        if current != known {
+            known = current;
+            watcher.settle();
+            watcher.notify();
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn run(watcher: &ChangeWatcher) -> anyhow::Result<()> {
+    use crate::utils::safe_command::exec_with_timeout;
+
+    let query = || {
+        let mut cmd = std::process::Command::new("query");
+        cmd.arg("user");
+        exec_with_timeout(cmd, Duration::from_secs(3))
+            .map(|output| output.stdout)
+            .unwrap_or_default()
+    };
+
+    let mut known = query();
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        let current = query();
+        if current != known {
+            known = current;
+            watcher.settle();
+            watcher.notify();
+        }
+    }
+}
+
+/// Compare two session lists by user, terminal and origin
+pub fn diff(old: &[UserSession], new: &[UserSession], timestamp: u64) -> Vec<SessionEvent> {
+    let same = |a: &UserSession, b: &UserSession| {
+        a.username == b.username && a.tty == b.tty && a.remote_host == b.remote_host
+    };
+    let event = |kind: SessionEventKind, s: &UserSession| SessionEvent {
+        kind: kind as i32,
+        username: s.username.clone(),
+        tty: s.tty.clone(),
+        remote_host: s.remote_host.clone(),
+        session_type: s.session_type.clone(),
+        timestamp,
+    };
+
+    let logins = new
+        .iter()
+        .filter(|s| !old.iter().any(|o| same(o, s)))
+        .map(|s| event(SessionEventKind::SessionLogin, s));
+    let logouts = old
+        .iter()
+        .filter(|o| !new.iter().any(|s| same(o, s)))
+        .map(|o| event(SessionEventKind::SessionLogout, o));
+
+    logins.chain(logouts).collect()
+}
+
+/// Run the configured alert hook for one event.
+///
+/// The hook is invoked as `<hook> <login|logout> <user> <remote_host>
+/// <session_type> <tty>`; empty fields are passed as `-`.
+pub fn run_hook(hook: &str, event: &SessionEvent) {
+    let kind = match SessionEventKind::try_from(event.kind) {
+        Ok(SessionEventKind::SessionLogout) => "logout",
+        _ => "login",
+    };
+    let field = |s: &str| {
+        if s.is_empty() {
+            "-".to_string()
+        } else {
+            s.to_string()
+        }
+    };
+
+    let hook = hook.to_string();
+    let args = [
+        kind.to_string(),
+        field(&event.username),
+        field(&event.remote_host),
+        field(&event.session_type),
+        field(&event.tty),
+    ];
+    tokio::spawn(async move {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        match run_command_async(&hook, &args, CommandTimeout::Custom(HOOK_TIMEOUT)).await {
+            CommandResult::Success(_) => {}
+            CommandResult::Failed(code, _) => {
+                warn!("Session event hook {} exited with {}", hook, code)
+            }
+            CommandResult::Timeout => warn!("Session event hook {} timed out", hook),
+            CommandResult::NotFound => warn!("Session event hook {} not found", hook),
+            CommandResult::Error(e) => warn!("Session event hook {} failed: {}", hook, e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(username: &str, tty: &str, remote_host: &str) -> UserSession {
+        UserSession {
+            username: username.to_string(),
+            tty: tty.to_string(),
+            remote_host: remote_host.to_string(),
+            session_type: "ssh".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = vec![
+            session("root", "tty1", ""),
+            session("alice", "pts/0", "10.0.0.5"),
+        ];
+        let new = vec![
+            session("root", "tty1", ""),
+            session("bob", "pts/1", "203.0.113.9"),
+        ];
+
+        let events = diff(&old, &new, 42);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, SessionEventKind::SessionLogin as i32);
+        assert_eq!(events[0].username, "bob");
+        assert_eq!(events[0].remote_host, "203.0.113.9");
+        assert_eq!(events[0].timestamp, 42);
+        assert_eq!(events[1].kind, SessionEventKind::SessionLogout as i32);
+        assert_eq!(events[1].username, "alice");
+
+        assert!(diff(&new, &new, 42).is_empty());
+    }
+}
@@ -60,7 +60,7 @@ impl SessionCollector {
 
         // Fallback to basic 'who' if -u flag not supported (macOS)
         if sessions.is_empty() {
-            let mut cmd = Command::new("who");
+            let cmd = Command::new("who");
             if let Some(output) = exec_with_timeout(cmd, SESSION_COMMAND_TIMEOUT) {
                 if output.status.success() {
                     let stdout = String::from_utf8_lossy(&output.stdout);
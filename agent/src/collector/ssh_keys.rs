@@ -0,0 +1,596 @@
+//! SSH key audit
+//!
+//! Off by default (`collector.ssh_keys.enabled`) and read-only. Lists the
+//! keys in every user's authorized_keys files, i.e. the `AuthorizedKeysFile`
+//! paths from sshd_config (`.ssh/authorized_keys` and
+//! `.ssh/authorized_keys2` by default), and the `known_hosts` entries, with
+//! key type, comment, options and the SHA256 fingerprint `ssh-keygen -l`
+//! shows.
+//!
+//! An authorized key that wasn't there on the previous scan is published as
+//! an `ssh_keys` log event at warning level, a removed one at info level. The
+//! keys seen so far are kept in `/var/lib/nanolink/ssh_keys.json`
+//! (`%ProgramData%\nanolink` on Windows), so keys added while the agent was
+//! down are reported too. The first scan only records a baseline.
+//!
+//! - Linux: home directories from `/etc/passwd`
+//! - macOS: home directories from `dscl`
+//! - Windows: `C:\Users\*` and `administrators_authorized_keys`
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD as BASE64, STANDARD_NO_PAD};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+
+use crate::config::{CollectorConfig, SshKeyConfig};
+use crate::proto::{LogBatch, LogEntry, SshKey};
+
+use super::registry::{CollectContext, Collector, Fragment, IntervalClass};
+use super::syslog;
+
+const AUTHORIZED_KEY: &str = "authorized_key";
+const KNOWN_HOST: &str = "known_host";
+
+/// sshd's default `AuthorizedKeysFile`
+const DEFAULT_AUTHORIZED_KEYS: &[&str] = &[".ssh/authorized_keys", ".ssh/authorized_keys2"];
+
+/// An authorized key as remembered between scans
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SeenKey {
+    user: String,
+    file: String,
+    key_type: String,
+    fingerprint: String,
+    comment: String,
+    first_seen: String,
+}
+
+pub struct SshKeyCollector {
+    config: SshKeyConfig,
+    path: Option<PathBuf>,
+    /// Authorized keys by file and fingerprint; None until the baseline
+    seen: Option<BTreeMap<String, SeenKey>>,
+}
+
+impl SshKeyCollector {
+    pub fn new(config: SshKeyConfig) -> Self {
+        let path = (!cfg!(test)).then(state_file);
+        let seen = path.as_deref().and_then(|path| {
+            let json = std::fs::read(path).ok()?;
+            serde_json::from_slice(&json)
+                .inspect_err(|e| warn!("Ignoring unreadable {:?}: {}", path, e))
+                .ok()
+        });
+        Self { config, path, seen }
+    }
+
+    /// Stamp the authorized keys with when they were first seen and return
+    /// the keys added and removed since the previous scan. Keys in files that
+    /// couldn't be read are kept.
+    fn update(
+        &mut self,
+        keys: &mut [SshKey],
+        unreadable: &HashSet<String>,
+    ) -> (Vec<SeenKey>, Vec<SeenKey>) {
+        let now = chrono::Utc::now().to_rfc3339();
+        let baseline = self.seen.is_none();
+        let previous = self.seen.take().unwrap_or_default();
+
+        let mut current = BTreeMap::new();
+        let mut added = Vec::new();
+        for key in keys.iter_mut().filter(|k| k.kind == AUTHORIZED_KEY) {
+            let id = format!("{}\t{}", key.file, key.fingerprint);
+            let seen = previous.get(&id).cloned().unwrap_or_else(|| {
+                let seen = SeenKey {
+                    user: key.user.clone(),
+                    file: key.file.clone(),
+                    key_type: key.key_type.clone(),
+                    fingerprint: key.fingerprint.clone(),
+                    comment: key.comment.clone(),
+                    first_seen: now.clone(),
+                };
+                if !baseline && !current.contains_key(&id) {
+                    added.push(seen.clone());
+                }
+                seen
+            });
+            key.first_seen = seen.first_seen.clone();
+            current.insert(id, seen);
+        }
+
+        let mut removed = Vec::new();
+        for (id, seen) in previous {
+            if current.contains_key(&id) {
+                continue;
+            }
+            if unreadable.contains(&seen.file) {
+                current.insert(id, seen);
+            } else {
+                removed.push(seen);
+            }
+        }
+
+        if baseline {
+            info!(
+                "Recorded {} authorized SSH key(s) as the baseline",
+                current.len()
+            );
+        }
+        self.seen = Some(current);
+        (added, removed)
+    }
+
+    fn save(&self) {
+        let (Some(path), Some(seen)) = (&self.path, &self.seen) else {
+            return;
+        };
+        let written = serde_json::to_vec(seen)
+            .map_err(std::io::Error::other)
+            .and_then(|json| {
+                path.parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .and_then(|_| std::fs::write(path, json))
+            });
+        if let Err(e) = written {
+            debug!("Failed to save SSH keys to {:?}: {}", path, e);
+        }
+    }
+}
+
+impl Collector for SshKeyCollector {
+    fn name(&self) -> &'static str {
+        "ssh_keys"
+    }
+
+    fn interval(&self, _config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Periodic(Duration::from_millis(self.config.interval_ms))
+    }
+
+    fn collect(&mut self, _ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        let (mut keys, unreadable) = scan(self.config.known_hosts);
+        let baseline = self.seen.is_none();
+        let (added, removed) = self.update(&mut keys, &unreadable);
+        if baseline || !added.is_empty() || !removed.is_empty() {
+            self.save();
+        }
+
+        let entries: Vec<LogEntry> = added
+            .iter()
+            .map(|key| event(key, true))
+            .chain(removed.iter().map(|key| event(key, false)))
+            .collect();
+        for entry in &entries {
+            info!("SSH keys: {}", entry.message);
+        }
+        if !entries.is_empty() {
+            syslog::publish(LogBatch {
+                source: "ssh_keys".to_string(),
+                entries,
+                dropped: 0,
+            });
+        }
+        Ok(Fragment::SshKeys(keys))
+    }
+}
+
+fn event(key: &SeenKey, added: bool) -> LogEntry {
+    let (level, change) = if added {
+        ("warning", "added")
+    } else {
+        ("info", "removed")
+    };
+    let comment = match key.comment.as_str() {
+        "" => String::new(),
+        comment => format!(" ({comment})"),
+    };
+    LogEntry {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        level: level.to_string(),
+        source: "ssh_keys".to_string(),
+        message: format!(
+            "Authorized SSH key {change} for {}: {} {}{comment} in {}",
+            key.user, key.key_type, key.fingerprint, key.file
+        ),
+        metadata: HashMap::from([
+            ("user".to_string(), key.user.clone()),
+            ("file".to_string(), key.file.clone()),
+            ("key_type".to_string(), key.key_type.clone()),
+            ("fingerprint".to_string(), key.fingerprint.clone()),
+            ("comment".to_string(), key.comment.clone()),
+            ("change".to_string(), change.to_string()),
+        ]),
+    }
+}
+
+fn state_file() -> PathBuf {
+    #[cfg(windows)]
+    {
+        let base = std::env::var("ProgramData").unwrap_or_else(|_| "C:\\ProgramData".to_string());
+        PathBuf::from(base).join("nanolink").join("ssh_keys.json")
+    }
+    #[cfg(unix)]
+    {
+        PathBuf::from("/var/lib/nanolink/ssh_keys.json")
+    }
+}
+
+/// All authorized keys and, with `known_hosts`, known host keys, plus the
+/// files that exist but couldn't be read
+fn scan(known_hosts: bool) -> (Vec<SshKey>, HashSet<String>) {
+    let sshd_config = std::fs::read_to_string(ssh_dir().join("sshd_config")).unwrap_or_default();
+    let patterns = authorized_keys_files(&sshd_config);
+
+    let mut keys = Vec::new();
+    let mut unreadable = HashSet::new();
+    let mut read = |user: &str, path: &Path, kind: &str| {
+        let file = path.display().to_string();
+        match std::fs::read_to_string(path) {
+            Ok(text) if kind == AUTHORIZED_KEY => {
+                keys.extend(parse_authorized_keys(&text, user, &file))
+            }
+            Ok(text) => keys.extend(parse_known_hosts(&text, user, &file)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                debug!("Cannot read {}: {}", file, e);
+                unreadable.insert(file);
+            }
+        }
+    };
+
+    // Accounts sharing a home directory would list its files twice
+    let mut files = HashSet::new();
+    for (user, home) in homes() {
+        for pattern in &patterns {
+            let path = expand(pattern, &user, &home);
+            if files.insert(path.clone()) {
+                read(&user, &path, AUTHORIZED_KEY);
+            }
+        }
+        let path = home.join(".ssh").join("known_hosts");
+        if known_hosts && files.insert(path.clone()) {
+            read(&user, &path, KNOWN_HOST);
+        }
+    }
+
+    // Keys for every member of the Administrators group
+    #[cfg(target_os = "windows")]
+    read(
+        "Administrators",
+        &ssh_dir().join("administrators_authorized_keys"),
+        AUTHORIZED_KEY,
+    );
+    if known_hosts {
+        read("", &ssh_dir().join("ssh_known_hosts"), KNOWN_HOST);
+    }
+    (keys, unreadable)
+}
+
+/// Directory with sshd_config and the system-wide known hosts
+fn ssh_dir() -> PathBuf {
+    #[cfg(windows)]
+    {
+        let base = std::env::var("ProgramData").unwrap_or_else(|_| "C:\\ProgramData".to_string());
+        PathBuf::from(base).join("ssh")
+    }
+    #[cfg(unix)]
+    {
+        PathBuf::from("/etc/ssh")
+    }
+}
+
+/// Accounts and their home directories
+#[cfg(target_os = "linux")]
+fn homes() -> Vec<(String, PathBuf)> {
+    std::fs::read_to_string("/etc/passwd")
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            (fields.len() >= 7 && !fields[5].is_empty())
+                .then(|| (fields[0].to_string(), PathBuf::from(fields[5])))
+        })
+        .collect()
+}
+
+#[cfg(target_os = "macos")]
+fn homes() -> Vec<(String, PathBuf)> {
+    let mut cmd = std::process::Command::new("dscl");
+    cmd.args([".", "-list", "/Users", "NFSHomeDirectory"]);
+    crate::utils::safe_command::exec_with_timeout(cmd, Duration::from_secs(10))
+        .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let (user, home) = line.split_once(char::is_whitespace)?;
+            Some((user.to_string(), PathBuf::from(home.trim())))
+        })
+        .collect()
+}
+
+#[cfg(target_os = "windows")]
+fn homes() -> Vec<(String, PathBuf)> {
+    let drive = std::env::var("SystemDrive").unwrap_or_else(|_| "C:".to_string());
+    let Ok(entries) = std::fs::read_dir(PathBuf::from(format!("{drive}\\Users"))) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .filter_map(|e| Some((e.file_name().into_string().ok()?, e.path())))
+        .filter(|(user, _)| {
+            !matches!(
+                user.as_str(),
+                "Public" | "Default" | "Default User" | "All Users"
+            )
+        })
+        .collect()
+}
+
+/// `AuthorizedKeysFile` patterns from the global part of sshd_config
+fn authorized_keys_files(sshd_config: &str) -> Vec<String> {
+    for line in sshd_config.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (keyword, value) = line
+            .split_once(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or((line, ""));
+        // Settings after the first Match apply to some connections only
+        if keyword.eq_ignore_ascii_case("Match") {
+            break;
+        }
+        if keyword.eq_ignore_ascii_case("AuthorizedKeysFile") {
+            return value
+                .trim_start_matches(|c: char| c.is_whitespace() || c == '=')
+                .split_whitespace()
+                .filter(|p| *p != "none")
+                .map(String::from)
+                .collect();
+        }
+    }
+    DEFAULT_AUTHORIZED_KEYS
+        .iter()
+        .map(|p| p.to_string())
+        .collect()
+}
+
+/// Expand `%h`, `%u` and `%%` in an `AuthorizedKeysFile` pattern; relative
+/// paths are relative to the home directory
+fn expand(pattern: &str, user: &str, home: &Path) -> PathBuf {
+    let mut path = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            path.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('h') => path.push_str(&home.to_string_lossy()),
+            Some('u') => path.push_str(user),
+            Some(other) => path.push(other),
+            None => {}
+        }
+    }
+    home.join(path)
+}
+
+/// sshd's key type names, including certificates and security keys
+fn is_key_type(word: &str) -> bool {
+    word.starts_with("ssh-") || word.starts_with("ecdsa-sha2-") || word.starts_with("sk-")
+}
+
+/// SHA256 fingerprint of a base64 key blob, as `ssh-keygen -l` prints it
+fn fingerprint(blob: &str) -> Option<String> {
+    let key = BASE64.decode(blob).ok()?;
+    Some(format!(
+        "SHA256:{}",
+        STANDARD_NO_PAD.encode(Sha256::digest(&key))
+    ))
+}
+
+/// Split off the first field; double quotes may contain spaces
+fn split_field(line: &str) -> (&str, &str) {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => return (&line[..i], line[i..].trim_start()),
+            _ => {}
+        }
+    }
+    (line, "")
+}
+
+/// Keys in an authorized_keys file: `[options] type key [comment]`
+fn parse_authorized_keys(text: &str, user: &str, file: &str) -> Vec<SshKey> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (first, rest) = split_field(line);
+            let (options, rest) = if is_key_type(first) {
+                ("", line)
+            } else {
+                (first, rest)
+            };
+            let (key_type, rest) = split_field(rest);
+            let (blob, comment) = split_field(rest);
+            Some(SshKey {
+                user: user.to_string(),
+                file: file.to_string(),
+                kind: AUTHORIZED_KEY.to_string(),
+                key_type: key_type.to_string(),
+                fingerprint: fingerprint(blob)?,
+                comment: comment.to_string(),
+                options: options.to_string(),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Keys in a known_hosts file: `[@marker] hosts type key [comment]`
+fn parse_known_hosts(text: &str, user: &str, file: &str) -> Vec<SshKey> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (mut hosts, mut rest) = split_field(line);
+            // @cert-authority and @revoked apply to the hosts that follow
+            let marker = hosts.starts_with('@').then_some(hosts);
+            if marker.is_some() {
+                (hosts, rest) = split_field(rest);
+            }
+            let (key_type, rest) = split_field(rest);
+            let (blob, comment) = split_field(rest);
+            Some(SshKey {
+                user: user.to_string(),
+                file: file.to_string(),
+                kind: KNOWN_HOST.to_string(),
+                key_type: key_type.to_string(),
+                fingerprint: fingerprint(blob)?,
+                comment: comment.to_string(),
+                options: marker.unwrap_or_default().to_string(),
+                hosts: hosts.to_string(),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ED25519: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIK3W08e7LL9gJSrsKWykglB+I7DgpD+2/YUL0MMssV33";
+    const ED25519_FINGERPRINT: &str = "SHA256:qS6amzIzj/8eTVzVIkJtLOljX9Z2sEYoHeoE+JowW8Y";
+    const ECDSA: &str = "ecdsa-sha2-nistp256 AAAAE2VjZHNhLXNoYTItbmlzdHAyNTYAAAAIbmlzdHAyNTYAAABBBN8/iQCbfrVZ7GNO67j8F8kUmRs1oIaLI2TI10bU4V+f8bo+59TeeNUIUZmR+X1csbtNKGuYL2MfjGe/42eiYPc=";
+    const ECDSA_FINGERPRINT: &str = "SHA256:Tl4PkN38CGNyQJ8h0XeIIx7tplaS8uFCdnHKxHmxYoA";
+
+    #[test]
+    fn test_parse_authorized_keys() {
+        let text = format!(
+            "# deploy keys\n\
+             {ED25519} alice@laptop\n\
+             from=\"10.0.0.0/8\",command=\"/usr/bin/backup --run now\",no-pty {ECDSA}\n\
+             ssh-rsa not-base64!! broken\n\n"
+        );
+        let keys = parse_authorized_keys(&text, "alice", "/home/alice/.ssh/authorized_keys");
+        assert_eq!(keys.len(), 2);
+
+        assert_eq!(keys[0].key_type, "ssh-ed25519");
+        assert_eq!(keys[0].fingerprint, ED25519_FINGERPRINT);
+        assert_eq!(keys[0].comment, "alice@laptop");
+        assert!(keys[0].options.is_empty());
+
+        assert_eq!(keys[1].key_type, "ecdsa-sha2-nistp256");
+        assert_eq!(keys[1].fingerprint, ECDSA_FINGERPRINT);
+        assert_eq!(
+            keys[1].options,
+            "from=\"10.0.0.0/8\",command=\"/usr/bin/backup --run now\",no-pty"
+        );
+        assert!(keys[1].comment.is_empty());
+        assert_eq!(keys[1].kind, AUTHORIZED_KEY);
+    }
+
+    #[test]
+    fn test_parse_known_hosts() {
+        let text = format!(
+            "github.com,140.82.121.4 {ED25519}\n\
+             |1|F1E1KeoE/eEWhi10WpGv4OdiO6Y=|3988QV0VE8wmZL7suNrYQLITLCg= {ECDSA}\n\
+             @cert-authority *.example.com {ED25519} ca\n"
+        );
+        let keys = parse_known_hosts(&text, "", "/etc/ssh/ssh_known_hosts");
+        assert_eq!(keys.len(), 3);
+        assert_eq!(keys[0].hosts, "github.com,140.82.121.4");
+        assert_eq!(keys[0].fingerprint, ED25519_FINGERPRINT);
+        assert!(keys[1].hosts.starts_with("|1|"));
+        assert_eq!(keys[1].fingerprint, ECDSA_FINGERPRINT);
+        assert_eq!(
+            (keys[2].options.as_str(), keys[2].hosts.as_str()),
+            ("@cert-authority", "*.example.com")
+        );
+        assert_eq!(keys[2].comment, "ca");
+    }
+
+    #[test]
+    fn test_authorized_keys_files() {
+        assert_eq!(authorized_keys_files(""), DEFAULT_AUTHORIZED_KEYS);
+        let config = "\
+# AuthorizedKeysFile ignored
+AuthorizedKeysFile .ssh/authorized_keys /etc/ssh/keys/%u none
+Match Group admins
+    AuthorizedKeysFile /etc/ssh/admin_keys
+";
+        let patterns = authorized_keys_files(config);
+        assert_eq!(patterns, [".ssh/authorized_keys", "/etc/ssh/keys/%u"]);
+        assert_eq!(
+            authorized_keys_files("Match User bob\nAuthorizedKeysFile /x\n"),
+            DEFAULT_AUTHORIZED_KEYS
+        );
+
+        let home = Path::new("/home/bob");
+        assert_eq!(
+            expand(&patterns[0], "bob", home),
+            Path::new("/home/bob/.ssh/authorized_keys")
+        );
+        assert_eq!(
+            expand(&patterns[1], "bob", home),
+            Path::new("/etc/ssh/keys/bob")
+        );
+        assert_eq!(
+            expand("%h/keys%%", "bob", home),
+            Path::new("/home/bob/keys%")
+        );
+    }
+
+    #[test]
+    fn test_update_reports_added_and_removed_keys() {
+        let mut collector = SshKeyCollector::new(SshKeyConfig::default());
+        let file = "/home/alice/.ssh/authorized_keys";
+        let scan = |lines: &[&str]| parse_authorized_keys(&lines.join("\n"), "alice", file);
+
+        // The first scan is the baseline
+        let mut keys = scan(&[ED25519]);
+        let (added, removed) = collector.update(&mut keys, &HashSet::new());
+        assert!(added.is_empty() && removed.is_empty());
+        let first_seen = keys[0].first_seen.clone();
+        assert!(!first_seen.is_empty());
+
+        let mut keys = scan(&[ED25519, ECDSA]);
+        let (added, removed) = collector.update(&mut keys, &HashSet::new());
+        assert_eq!(added.len(), 1);
+        assert_eq!(added[0].fingerprint, ECDSA_FINGERPRINT);
+        assert_eq!(event(&added[0], true).level, "warning");
+        assert!(removed.is_empty());
+        assert_eq!(keys[0].first_seen, first_seen);
+
+        // Keys in an unreadable file are not reported as removed
+        let unreadable = HashSet::from([file.to_string()]);
+        let (added, removed) = collector.update(&mut Vec::new(), &unreadable);
+        assert!(added.is_empty() && removed.is_empty());
+
+        let mut keys = scan(&[ECDSA]);
+        let (added, removed) = collector.update(&mut keys, &HashSet::new());
+        assert!(added.is_empty());
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].fingerprint, ED25519_FINGERPRINT);
+        let entry = event(&removed[0], false);
+        assert_eq!(entry.level, "info");
+        assert!(
+            entry
+                .message
+                .starts_with("Authorized SSH key removed for alice")
+        );
+    }
+}
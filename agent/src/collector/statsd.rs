@@ -0,0 +1,326 @@
+//! StatsD / DogStatsD listener
+//!
+//! Applications on the host send metrics over UDP in the StatsD line format
+//! (`name:value|type[|@rate][|#tag:value,...]`), one or more per datagram.
+//! Values are aggregated in memory and flushed by [`StatsdCollector`] as
+//! custom metrics (collector "statsd"):
+//!
+//! - counters (`c`): sum over the flush interval, sample rate applied
+//! - gauges (`g`): last value, kept across flushes; `+n`/`-n` adjust it
+//! - timers, histograms and distributions (`ms`, `h`, `d`): `.count`,
+//!   `.min`, `.max`, `.mean`, `.p50`, `.p95` and `.p99`
+//! - sets (`s`): number of distinct values
+//!
+//! DogStatsD tags become metric labels. Events and service checks are
+//! ignored.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use tokio::net::UdpSocket;
+use tracing::{info, warn};
+
+use crate::config::{CollectorConfig, Config};
+use crate::proto::CustomMetric;
+
+use super::registry::{CollectContext, Collector, Fragment, IntervalClass};
+
+/// Samples kept per timer series between flushes
+const MAX_SAMPLES: usize = 10_000;
+
+static AGGREGATOR: LazyLock<Mutex<Aggregator>> = LazyLock::new(|| Mutex::new(Aggregator::new(0)));
+
+/// Metric name plus sorted tags
+type SeriesKey = (String, BTreeMap<String, String>);
+
+#[derive(Debug, PartialEq)]
+enum Kind {
+    Counter,
+    Gauge { delta: bool },
+    Timer,
+    Set,
+}
+
+#[derive(Debug, PartialEq)]
+struct Sample {
+    name: String,
+    kind: Kind,
+    value: f64,
+    /// Raw value for sets
+    raw: String,
+    rate: f64,
+    tags: BTreeMap<String, String>,
+}
+
+#[derive(Default)]
+struct Aggregator {
+    max_series: usize,
+    counters: HashMap<SeriesKey, f64>,
+    gauges: HashMap<SeriesKey, f64>,
+    timers: HashMap<SeriesKey, Vec<f64>>,
+    sets: HashMap<SeriesKey, HashSet<String>>,
+    dropped: u64,
+}
+
+impl Aggregator {
+    fn new(max_series: usize) -> Self {
+        Self {
+            max_series,
+            ..Default::default()
+        }
+    }
+
+    fn series_count(&self) -> usize {
+        self.counters.len() + self.gauges.len() + self.timers.len() + self.sets.len()
+    }
+
+    fn record(&mut self, sample: Sample) {
+        let key = (sample.name, sample.tags);
+        let full = self.max_series > 0 && self.series_count() >= self.max_series;
+        let known = match sample.kind {
+            Kind::Counter => self.counters.contains_key(&key),
+            Kind::Gauge { .. } => self.gauges.contains_key(&key),
+            Kind::Timer => self.timers.contains_key(&key),
+            Kind::Set => self.sets.contains_key(&key),
+        };
+        if full && !known {
+            self.dropped += 1;
+            return;
+        }
+
+        match sample.kind {
+            Kind::Counter => *self.counters.entry(key).or_default() += sample.value / sample.rate,
+            Kind::Gauge { delta: true } => *self.gauges.entry(key).or_default() += sample.value,
+            Kind::Gauge { delta: false } => {
+                self.gauges.insert(key, sample.value);
+            }
+            Kind::Timer => {
+                let samples = self.timers.entry(key).or_default();
+                if samples.len() < MAX_SAMPLES {
+                    samples.push(sample.value);
+                }
+            }
+            Kind::Set => {
+                self.sets.entry(key).or_default().insert(sample.raw);
+            }
+        }
+    }
+
+    /// Aggregates since the previous flush; gauges keep their value
+    fn flush(&mut self) -> Vec<CustomMetric> {
+        let metric = |(name, tags): &SeriesKey, suffix: &str, value: f64| CustomMetric {
+            collector: "statsd".to_string(),
+            name: format!("{name}{suffix}"),
+            value,
+            labels: tags.clone().into_iter().collect(),
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        for (key, value) in self.counters.drain() {
+            out.push(metric(&key, "", value));
+        }
+        for (key, value) in &self.gauges {
+            out.push(metric(key, "", *value));
+        }
+        for (key, mut samples) in self.timers.drain() {
+            if samples.is_empty() {
+                continue;
+            }
+            samples.sort_by(f64::total_cmp);
+            let count = samples.len();
+            let mean = samples.iter().sum::<f64>() / count as f64;
+            let rank = |p: f64| samples[((count as f64 * p).ceil() as usize).clamp(1, count) - 1];
+            out.push(metric(&key, ".count", count as f64));
+            out.push(metric(&key, ".min", samples[0]));
+            out.push(metric(&key, ".max", samples[count - 1]));
+            out.push(metric(&key, ".mean", mean));
+            out.push(metric(&key, ".p50", rank(0.50)));
+            out.push(metric(&key, ".p95", rank(0.95)));
+            out.push(metric(&key, ".p99", rank(0.99)));
+        }
+        for (key, values) in self.sets.drain() {
+            out.push(metric(&key, "", values.len() as f64));
+        }
+        if self.dropped > 0 {
+            warn!(
+                "StatsD series limit reached, {} samples dropped",
+                self.dropped
+            );
+            self.dropped = 0;
+        }
+        out
+    }
+}
+
+/// Parse one line; `None` for malformed lines, events and service checks
+fn parse_line(line: &str) -> Option<Sample> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with("_e{") || line.starts_with("_sc|") {
+        return None;
+    }
+    let (name, rest) = line.split_once(':')?;
+    let mut parts = rest.split('|');
+    let raw = parts.next()?;
+    let kind = match parts.next()? {
+        "c" => Kind::Counter,
+        "g" => Kind::Gauge {
+            delta: raw.starts_with(['+', '-']),
+        },
+        "ms" | "h" | "d" => Kind::Timer,
+        "s" => Kind::Set,
+        _ => return None,
+    };
+    let value = match kind {
+        Kind::Set => 0.0,
+        _ => raw.parse::<f64>().ok().filter(|v| v.is_finite())?,
+    };
+
+    let mut rate = 1.0;
+    let mut tags = BTreeMap::new();
+    for part in parts {
+        if let Some(r) = part.strip_prefix('@') {
+            rate = r.parse().ok().filter(|r: &f64| *r > 0.0 && *r <= 1.0)?;
+        } else if let Some(list) = part.strip_prefix('#') {
+            for tag in list.split(',').filter(|t| !t.is_empty()) {
+                let (key, value) = tag.split_once(':').unwrap_or((tag, ""));
+                tags.insert(key.to_string(), value.to_string());
+            }
+        }
+    }
+
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    Some(Sample {
+        name: name.to_string(),
+        kind,
+        value,
+        raw: raw.to_string(),
+        rate,
+        tags,
+    })
+}
+
+/// Receive StatsD datagrams until the task is aborted
+pub async fn run(config: Arc<Config>) {
+    let cfg = &config.statsd;
+    *AGGREGATOR.lock() = Aggregator::new(cfg.max_series);
+
+    let socket = match UdpSocket::bind(&cfg.listen).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            warn!("Failed to bind StatsD UDP {}: {}", cfg.listen, e);
+            return;
+        }
+    };
+    info!("StatsD listener on udp://{}", cfg.listen);
+
+    let mut buf = vec![0u8; 65535];
+    loop {
+        let len = match socket.recv_from(&mut buf).await {
+            Ok((len, _)) => len,
+            Err(e) => {
+                warn!("StatsD receive failed: {}", e);
+                continue;
+            }
+        };
+        let text = String::from_utf8_lossy(&buf[..len]);
+        let mut aggregator = AGGREGATOR.lock();
+        for sample in text.lines().filter_map(parse_line) {
+            aggregator.record(sample);
+        }
+    }
+}
+
+/// Flushes aggregated StatsD metrics into periodic data
+pub struct StatsdCollector {
+    interval: Duration,
+}
+
+impl StatsdCollector {
+    pub fn new(flush_interval_ms: u64) -> Self {
+        Self {
+            interval: Duration::from_millis(flush_interval_ms),
+        }
+    }
+}
+
+impl Collector for StatsdCollector {
+    fn name(&self) -> &'static str {
+        "statsd"
+    }
+
+    fn interval(&self, _config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Periodic(self.interval)
+    }
+
+    fn collect(&mut self, _ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        Ok(Fragment::Custom(AGGREGATOR.lock().flush()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find<'a>(metrics: &'a [CustomMetric], name: &str) -> &'a CustomMetric {
+        metrics.iter().find(|m| m.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_parse_line() {
+        let sample = parse_line("page.views:3|c|@0.5|#env:prod,canary").unwrap();
+        assert_eq!(sample.kind, Kind::Counter);
+        assert_eq!(sample.rate, 0.5);
+        assert_eq!(sample.tags["env"], "prod");
+        assert_eq!(sample.tags["canary"], "");
+
+        assert_eq!(
+            parse_line("queue:-2|g").unwrap().kind,
+            Kind::Gauge { delta: true }
+        );
+        assert!(parse_line("bad:abc|c").is_none());
+        assert!(parse_line("noType:1").is_none());
+        assert!(parse_line("_e{5,4}:title|text").is_none());
+    }
+
+    #[test]
+    fn test_aggregate_and_flush() {
+        let mut agg = Aggregator::new(0);
+        let lines = "hits:1|c\nhits:2|c|@0.5\nqueue:10|g\nqueue:-3|g\n\
+                     users:alice|s\nusers:bob|s\nusers:alice|s";
+        for sample in lines.lines().filter_map(parse_line) {
+            agg.record(sample);
+        }
+        for ms in 1..=100 {
+            agg.record(parse_line(&format!("latency:{ms}|ms")).unwrap());
+        }
+
+        let metrics = agg.flush();
+        assert_eq!(find(&metrics, "hits").value, 5.0);
+        assert_eq!(find(&metrics, "queue").value, 7.0);
+        assert_eq!(find(&metrics, "users").value, 2.0);
+        assert_eq!(find(&metrics, "latency.count").value, 100.0);
+        assert_eq!(find(&metrics, "latency.p95").value, 95.0);
+        assert_eq!(find(&metrics, "latency.max").value, 100.0);
+
+        // Gauges persist, everything else resets
+        let metrics = agg.flush();
+        assert_eq!(metrics.len(), 1);
+        assert_eq!(metrics[0].name, "queue");
+    }
+
+    #[test]
+    fn test_series_limit() {
+        let mut agg = Aggregator::new(2);
+        for line in ["a:1|c", "b:1|c", "c:1|c", "a:1|c"] {
+            agg.record(parse_line(line).unwrap());
+        }
+        assert_eq!(agg.dropped, 1);
+        assert_eq!(agg.counters[&("a".to_string(), BTreeMap::new())], 2.0);
+    }
+}
@@ -0,0 +1,539 @@
+//! Syslog listener
+//!
+//! Covers network devices that can't run an agent: switches, firewalls and
+//! appliances send syslog (RFC 3164 or RFC 5424) over UDP or TCP to the
+//! agent, which redacts secrets with the log sanitizer patterns and forwards
+//! the messages to every connected server as [`LogBatch`] messages.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::{Semaphore, broadcast, mpsc};
+use tokio::task::JoinSet;
+use tokio::time;
+use tracing::{debug, info, warn};
+
+use crate::config::Config;
+use crate::executor::redact_sensitive;
+use crate::proto::{LogBatch, LogEntry};
+
+/// Batches kept for streams that fall behind
+const BROADCAST_CAPACITY: usize = 64;
+
+/// Messages queued between the sockets and the batcher
+const QUEUE_CAPACITY: usize = 10_000;
+
+/// Concurrent TCP senders
+const MAX_TCP_CONNECTIONS: usize = 64;
+
+const FACILITIES: [&str; 24] = [
+    "kern",
+    "user",
+    "mail",
+    "daemon",
+    "auth",
+    "syslog",
+    "lpr",
+    "news",
+    "uucp",
+    "cron",
+    "authpriv",
+    "ftp",
+    "ntp",
+    "security",
+    "console",
+    "solaris-cron",
+    "local0",
+    "local1",
+    "local2",
+    "local3",
+    "local4",
+    "local5",
+    "local6",
+    "local7",
+];
+
+static BATCHES: LazyLock<broadcast::Sender<LogBatch>> =
+    LazyLock::new(|| broadcast::channel(BROADCAST_CAPACITY).0);
+
+/// Messages dropped because the queue was full, reported with the next batch
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+/// Subscribe to log batches received by the listener
+pub fn subscribe() -> broadcast::Receiver<LogBatch> {
+    BATCHES.subscribe()
+}
+
+/// Send a batch from another source (e.g. alert rules) to connected servers
+pub fn publish(mut batch: LogBatch) {
+    crate::silence::filter_batch(&mut batch);
+    if batch.entries.is_empty() && batch.dropped == 0 {
+        return;
+    }
+    let _ = BATCHES.send(batch);
+}
+
+/// Receive syslog messages until the task is aborted
+pub async fn run(config: Arc<Config>) {
+    let cfg = &config.syslog;
+    let max_message = cfg.max_message_bytes.max(480);
+    let allowed: Arc<Vec<IpAddr>> = Arc::new(
+        cfg.allowed_sources
+            .iter()
+            .filter_map(|source| match source.parse() {
+                Ok(ip) => Some(ip),
+                Err(_) => {
+                    warn!("Ignoring invalid syslog allowed source: {}", source);
+                    None
+                }
+            })
+            .collect(),
+    );
+
+    let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+    // Dropping the set (task abort) stops the socket tasks as well
+    let mut receivers = JoinSet::new();
+
+    if !cfg.udp_listen.is_empty() {
+        match UdpSocket::bind(&cfg.udp_listen).await {
+            Ok(socket) => {
+                info!("Syslog listener on udp://{}", cfg.udp_listen);
+                receivers.spawn(receive_udp(
+                    socket,
+                    tx.clone(),
+                    allowed.clone(),
+                    max_message,
+                ));
+            }
+            Err(e) => warn!("Failed to bind syslog UDP {}: {}", cfg.udp_listen, e),
+        }
+    }
+    if !cfg.tcp_listen.is_empty() {
+        match TcpListener::bind(&cfg.tcp_listen).await {
+            Ok(listener) => {
+                info!("Syslog listener on tcp://{}", cfg.tcp_listen);
+                receivers.spawn(accept_tcp(
+                    listener,
+                    tx.clone(),
+                    allowed.clone(),
+                    max_message,
+                ));
+            }
+            Err(e) => warn!("Failed to bind syslog TCP {}: {}", cfg.tcp_listen, e),
+        }
+    }
+    drop(tx);
+
+    batch(
+        rx,
+        cfg.batch_size.max(1),
+        Duration::from_millis(cfg.flush_interval_ms.max(100)),
+    )
+    .await;
+}
+
+fn is_allowed(allowed: &[IpAddr], peer: IpAddr) -> bool {
+    allowed.is_empty() || allowed.contains(&peer.to_canonical())
+}
+
+/// Parse a raw message and queue it for the batcher, counting it as dropped
+/// if the queue is full
+fn queue(tx: &mpsc::Sender<LogEntry>, raw: &[u8], peer: IpAddr, max_message: usize) {
+    let raw = &raw[..raw.len().min(max_message)];
+    let text = String::from_utf8_lossy(raw);
+    let text = text.trim_end_matches(['\r', '\n', '\0']);
+    if text.is_empty() {
+        return;
+    }
+    if tx.try_send(parse_message(text, peer)).is_err() {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+async fn receive_udp(
+    socket: UdpSocket,
+    tx: mpsc::Sender<LogEntry>,
+    allowed: Arc<Vec<IpAddr>>,
+    max_message: usize,
+) {
+    let mut buf = vec![0u8; 65_536];
+    loop {
+        match socket.recv_from(&mut buf).await {
+            Ok((len, peer)) => {
+                if is_allowed(&allowed, peer.ip()) {
+                    queue(&tx, &buf[..len], peer.ip().to_canonical(), max_message);
+                }
+            }
+            Err(e) => debug!("Syslog UDP receive failed: {}", e),
+        }
+    }
+}
+
+async fn accept_tcp(
+    listener: TcpListener,
+    tx: mpsc::Sender<LogEntry>,
+    allowed: Arc<Vec<IpAddr>>,
+    max_message: usize,
+) {
+    let permits = Arc::new(Semaphore::new(MAX_TCP_CONNECTIONS));
+    let mut connections = JoinSet::new();
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                debug!("Syslog TCP accept failed: {}", e);
+                continue;
+            }
+        };
+        if !is_allowed(&allowed, peer.ip()) {
+            debug!("Rejected syslog connection from {}", peer);
+            continue;
+        }
+        let Ok(permit) = permits.clone().try_acquire_owned() else {
+            warn!("Too many syslog TCP connections, rejecting {}", peer);
+            continue;
+        };
+        while connections.try_join_next().is_some() {}
+
+        let tx = tx.clone();
+        connections.spawn(async move {
+            let _permit = permit;
+            receive_tcp(stream, peer.ip().to_canonical(), tx, max_message).await;
+        });
+    }
+}
+
+async fn receive_tcp(
+    stream: TcpStream,
+    peer: IpAddr,
+    tx: mpsc::Sender<LogEntry>,
+    max_message: usize,
+) {
+    let mut reader = BufReader::new(stream);
+    let mut frame = Vec::new();
+    loop {
+        match read_frame(&mut reader, &mut frame, max_message).await {
+            Ok(true) => queue(&tx, &frame, peer, max_message),
+            Ok(false) => break,
+            Err(e) => {
+                debug!("Syslog connection from {} closed: {}", peer, e);
+                break;
+            }
+        }
+    }
+}
+
+/// Read one RFC 6587 frame, octet-counted (`<length> <message>`) or
+/// newline-terminated, keeping at most `max_message` bytes of it.
+/// Returns `false` at end of stream.
+async fn read_frame<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    frame: &mut Vec<u8>,
+    max_message: usize,
+) -> io::Result<bool> {
+    frame.clear();
+    let Some(&first) = reader.fill_buf().await?.first() else {
+        return Ok(false);
+    };
+
+    if first.is_ascii_digit() {
+        let mut length = Vec::new();
+        (&mut *reader)
+            .take(10)
+            .read_until(b' ', &mut length)
+            .await?;
+        let length: u64 = std::str::from_utf8(&length)
+            .ok()
+            .and_then(|l| l.trim_end().parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid frame length"))?;
+        let mut body = (&mut *reader).take(length);
+        (&mut body)
+            .take(max_message as u64)
+            .read_to_end(frame)
+            .await?;
+        tokio::io::copy(&mut body, &mut tokio::io::sink()).await?;
+    } else {
+        (&mut *reader)
+            .take(max_message as u64)
+            .read_until(b'\n', frame)
+            .await?;
+        if !frame.ends_with(b"\n") {
+            skip_line(reader).await?;
+        }
+    }
+    Ok(true)
+}
+
+/// Discard the rest of an overlong line
+async fn skip_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<()> {
+    loop {
+        let buf = reader.fill_buf().await?;
+        if buf.is_empty() {
+            return Ok(());
+        }
+        match buf.iter().position(|b| *b == b'\n') {
+            Some(end) => {
+                reader.consume(end + 1);
+                return Ok(());
+            }
+            None => {
+                let len = buf.len();
+                reader.consume(len);
+            }
+        }
+    }
+}
+
+/// Collect queued messages into batches of `batch_size`, sending partial
+/// batches every `flush_interval`
+async fn batch(mut rx: mpsc::Receiver<LogEntry>, batch_size: usize, flush_interval: Duration) {
+    let mut entries = Vec::with_capacity(batch_size);
+    let mut ticker = time::interval(flush_interval);
+    loop {
+        tokio::select! {
+            entry = rx.recv() => match entry {
+                Some(entry) => {
+                    entries.push(entry);
+                    if entries.len() >= batch_size {
+                        flush(&mut entries);
+                    }
+                }
+                None => {
+                    flush(&mut entries);
+                    return;
+                }
+            },
+            _ = ticker.tick() => flush(&mut entries),
+        }
+    }
+}
+
+fn flush(entries: &mut Vec<LogEntry>) {
+    let dropped = DROPPED.swap(0, Ordering::Relaxed);
+    if entries.is_empty() && dropped == 0 {
+        return;
+    }
+    if dropped > 0 {
+        warn!("Syslog queue full, {} messages dropped", dropped);
+    }
+    // Without a connected server there are no receivers and the batch is discarded
+    let _ = BATCHES.send(LogBatch {
+        source: "syslog".to_string(),
+        entries: std::mem::take(entries),
+        dropped,
+    });
+}
+
+/// Parse an RFC 5424 or RFC 3164 message. Anything unrecognized is kept
+/// as the message text.
+fn parse_message(raw: &str, peer: IpAddr) -> LogEntry {
+    let mut metadata = HashMap::new();
+    metadata.insert("remote".to_string(), peer.to_string());
+
+    let (priority, rest) = split_priority(raw);
+    let level = match priority.map(|p| p & 7) {
+        Some(0..=2) => "critical",
+        Some(3) => "error",
+        Some(4) => "warning",
+        Some(7) => "debug",
+        _ => "info",
+    };
+    if let Some(priority) = priority {
+        let facility = (priority >> 3) as usize;
+        metadata.insert(
+            "facility".to_string(),
+            FACILITIES
+                .get(facility)
+                .map(|f| f.to_string())
+                .unwrap_or_else(|| facility.to_string()),
+        );
+    }
+
+    let (timestamp, host, app, pid, message) = match rest.strip_prefix("1 ") {
+        Some(body) => parse_rfc5424(body),
+        None => parse_rfc3164(rest),
+    };
+    for (key, value) in [("host", host), ("app", app), ("pid", pid)] {
+        if !value.is_empty() {
+            metadata.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    let (message, redacted) = redact_sensitive(message);
+    if redacted {
+        metadata.insert("redacted".to_string(), "true".to_string());
+    }
+    LogEntry {
+        timestamp: timestamp.to_string(),
+        level: level.to_string(),
+        source: if host.is_empty() {
+            peer.to_string()
+        } else {
+            host.to_string()
+        },
+        message,
+        metadata,
+    }
+}
+
+/// Split `<PRI>` off the front of a message
+fn split_priority(raw: &str) -> (Option<u8>, &str) {
+    raw.strip_prefix('<')
+        .and_then(|rest| rest.split_once('>'))
+        .and_then(|(pri, rest)| {
+            let pri: u8 = pri.parse().ok().filter(|p| *p <= 191)?;
+            Some((Some(pri), rest))
+        })
+        .unwrap_or((None, raw))
+}
+
+/// `TIMESTAMP HOSTNAME APP-NAME PROCID MSGID [SD] MSG` (after the version)
+fn parse_rfc5424(body: &str) -> (&str, &str, &str, &str, &str) {
+    let mut fields = body.splitn(6, ' ');
+    let mut next = || match fields.next() {
+        Some("-") | None => "",
+        Some(field) => field,
+    };
+    let timestamp = next();
+    let host = next();
+    let app = next();
+    let pid = next();
+    let _msgid = next();
+    let message = skip_structured_data(fields.next().unwrap_or_default());
+    (timestamp, host, app, pid, message)
+}
+
+/// Skip the structured data (`-` or `[...]` elements) in front of an
+/// RFC 5424 message
+fn skip_structured_data(rest: &str) -> &str {
+    let message = if rest == "-" || rest.starts_with("- ") {
+        &rest[1..]
+    } else if rest.starts_with('[') {
+        let mut depth = 0;
+        let mut escaped = false;
+        let mut quoted = false;
+        let mut end = rest.len();
+        for (i, c) in rest.char_indices() {
+            if escaped {
+                escaped = false;
+                continue;
+            }
+            match c {
+                '\\' => escaped = true,
+                '"' => quoted = !quoted,
+                '[' if !quoted => depth += 1,
+                ']' if !quoted => depth -= 1,
+                ' ' if depth == 0 => {
+                    end = i;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        &rest[end..]
+    } else {
+        rest
+    };
+    message.trim_start().trim_start_matches('\u{feff}')
+}
+
+/// `Mmm dd hh:mm:ss HOSTNAME TAG[PID]: MSG`
+fn parse_rfc3164(rest: &str) -> (&str, &str, &str, &str, &str) {
+    let bytes = rest.as_bytes();
+    let has_timestamp = bytes.len() > 16
+        && bytes[..3].iter().all(u8::is_ascii_alphabetic)
+        && bytes[3] == b' '
+        && bytes[6] == b' '
+        && bytes[9] == b':'
+        && bytes[15] == b' ';
+    if !has_timestamp {
+        return ("", "", "", "", rest);
+    }
+    let timestamp = &rest[..15];
+    let Some((host, message)) = rest[16..].split_once(' ') else {
+        return (timestamp, "", "", "", &rest[16..]);
+    };
+
+    // The tag ends at the first ':' and never contains spaces
+    match message.split_once(": ") {
+        Some((tag, text)) if !tag.contains(' ') && tag.len() <= 48 => {
+            let (app, pid) = match tag.split_once('[') {
+                Some((app, pid)) => (app, pid.trim_end_matches(']')),
+                None => (tag, ""),
+            };
+            (timestamp, host, app, pid, text)
+        }
+        _ => (timestamp, host, "", "", message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer() -> IpAddr {
+        "192.0.2.10".parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_rfc3164() {
+        let entry = parse_message(
+            "<34>Oct 11 22:14:15 fw01 sshd[4242]: Failed password=hunter2 for root",
+            peer(),
+        );
+        assert_eq!(entry.timestamp, "Oct 11 22:14:15");
+        assert_eq!(entry.level, "critical");
+        assert_eq!(entry.source, "fw01");
+        assert_eq!(entry.metadata["facility"], "auth");
+        assert_eq!(entry.metadata["app"], "sshd");
+        assert_eq!(entry.metadata["pid"], "4242");
+        assert_eq!(entry.metadata["redacted"], "true");
+        assert!(!entry.message.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_parse_rfc5424() {
+        let entry = parse_message(
+            "<165>1 2026-10-11T22:14:15.003Z switch1 evntslog - ID47 \
+             [exampleSDID@32473 iut=\"3\" eventID=\"1011\"][x@1 a=\"]\"] Link down on port 7",
+            peer(),
+        );
+        assert_eq!(entry.timestamp, "2026-10-11T22:14:15.003Z");
+        assert_eq!(entry.level, "info");
+        assert_eq!(entry.source, "switch1");
+        assert_eq!(entry.metadata["facility"], "local4");
+        assert_eq!(entry.metadata["app"], "evntslog");
+        assert!(!entry.metadata.contains_key("pid"));
+        assert_eq!(entry.message, "Link down on port 7");
+    }
+
+    #[test]
+    fn test_parse_unstructured() {
+        let entry = parse_message("link flap detected", peer());
+        assert_eq!(entry.source, "192.0.2.10");
+        assert_eq!(entry.level, "info");
+        assert_eq!(entry.message, "link flap detected");
+    }
+
+    #[tokio::test]
+    async fn test_read_frames() {
+        let mut input: &[u8] = b"11 <13>hello\nx<13>line one\n<13>too long line\n<13>last";
+        let mut frame = Vec::new();
+
+        assert!(read_frame(&mut input, &mut frame, 100).await.unwrap());
+        assert_eq!(frame, b"<13>hello\nx");
+        assert!(read_frame(&mut input, &mut frame, 100).await.unwrap());
+        assert_eq!(frame, b"<13>line one\n");
+        assert!(read_frame(&mut input, &mut frame, 8).await.unwrap());
+        assert_eq!(frame, b"<13>too ");
+        assert!(read_frame(&mut input, &mut frame, 100).await.unwrap());
+        assert_eq!(frame, b"<13>last");
+        assert!(!read_frame(&mut input, &mut frame, 100).await.unwrap());
+    }
+}
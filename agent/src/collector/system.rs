@@ -1,12 +1,16 @@
+#[cfg(any(target_os = "macos", target_os = "windows"))]
 use std::process::Command;
 use std::sync::OnceLock;
+#[cfg(any(target_os = "macos", target_os = "windows"))]
 use std::time::Duration;
 use sysinfo::System;
 
-use crate::proto::SystemInfo;
+use crate::proto::{PlatformSecurity, SystemInfo};
+#[cfg(any(target_os = "macos", target_os = "windows"))]
 use crate::utils::safe_command::exec_with_timeout;
 
 /// System info command timeout - 10 seconds
+#[cfg(any(target_os = "macos", target_os = "windows"))]
 const SYSTEM_COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Static system info that doesn't change
@@ -24,6 +28,7 @@ struct SystemInfoStatic {
     bios_version: String,
     system_model: String,
     system_vendor: String,
+    security: PlatformSecurity,
 }
 
 /// System info collector
@@ -59,21 +64,124 @@ impl SystemInfoCollector {
         #[cfg(target_os = "linux")]
         {
             info = Self::add_linux_hardware_info(info);
+            info.security = Self::linux_security();
         }
 
         #[cfg(target_os = "macos")]
         {
             info = Self::add_macos_hardware_info(info);
+            info.security = Self::macos_security();
         }
 
         #[cfg(target_os = "windows")]
         {
             info = Self::add_windows_hardware_info(info);
+            info.security = Self::windows_security();
         }
 
         info
     }
 
+    /// Secure Boot from the EFI variable, TPM from sysfs
+    #[cfg(target_os = "linux")]
+    fn linux_security() -> PlatformSecurity {
+        use std::fs;
+        use std::path::Path;
+
+        let secure_boot = if !Path::new("/sys/firmware/efi").exists() {
+            "unsupported".to_string()
+        } else {
+            fs::read("/sys/firmware/efi/efivars/SecureBoot-8be4df61-93ca-11d2-aa0d-00e098032b8c")
+                .ok()
+                .and_then(|data| parse_efi_secure_boot(&data))
+                .unwrap_or("unknown")
+                .to_string()
+        };
+
+        let tpm = Path::new("/sys/class/tpm/tpm0");
+        let tpm_version = match fs::read_to_string(tpm.join("tpm_version_major")) {
+            Ok(major) if major.trim() == "2" => "2.0".to_string(),
+            Ok(major) if major.trim() == "1" => "1.2".to_string(),
+            // Kernels before 5.6 only expose caps for TPM 1.2
+            _ => fs::read_to_string(tpm.join("device/caps"))
+                .ok()
+                .and_then(|caps| {
+                    caps.lines()
+                        .find_map(|l| l.strip_prefix("TCG version:"))
+                        .map(|v| v.trim().to_string())
+                })
+                .unwrap_or_default(),
+        };
+
+        PlatformSecurity {
+            secure_boot,
+            tpm_present: tpm.exists(),
+            tpm_version,
+            ..Default::default()
+        }
+    }
+
+    /// SIP from csrutil; Secure Boot is always on for Apple silicon and set
+    /// by the T2 boot policy on Intel Macs. Macs have no TPM.
+    #[cfg(target_os = "macos")]
+    fn macos_security() -> PlatformSecurity {
+        let mut cmd = Command::new("csrutil");
+        cmd.arg("status");
+        let sip_status = exec_with_timeout(cmd, SYSTEM_COMMAND_TIMEOUT)
+            .and_then(|output| parse_csrutil(&String::from_utf8_lossy(&output.stdout)))
+            .unwrap_or("unknown")
+            .to_string();
+
+        let secure_boot = if cfg!(target_arch = "aarch64") {
+            "enabled".to_string()
+        } else {
+            let mut cmd = Command::new("nvram");
+            cmd.arg("94b73556-2197-4702-82a8-3e1337dafbfb:AppleSecureBootPolicy");
+            match exec_with_timeout(cmd, SYSTEM_COMMAND_TIMEOUT) {
+                // "%00" is "No Security", "%01" medium and "%02" full
+                Some(output) if output.status.success() => {
+                    let policy = String::from_utf8_lossy(&output.stdout);
+                    if policy.trim_end().ends_with("%00") {
+                        "disabled".to_string()
+                    } else {
+                        "enabled".to_string()
+                    }
+                }
+                // Macs without a T2 chip
+                Some(_) => "unsupported".to_string(),
+                None => "unknown".to_string(),
+            }
+        };
+
+        PlatformSecurity {
+            secure_boot,
+            sip_status,
+            ..Default::default()
+        }
+    }
+
+    /// One PowerShell call for Secure Boot, the TPM and Device Guard
+    #[cfg(target_os = "windows")]
+    fn windows_security() -> PlatformSecurity {
+        const SCRIPT: &str = r#"
+try { "secureboot=" + (Confirm-SecureBootUEFI) }
+catch [System.PlatformNotSupportedException] { "secureboot=unsupported" }
+catch { "secureboot=unknown" }
+$tpm = Get-CimInstance -Namespace root\cimv2\security\microsofttpm -ClassName Win32_Tpm -ErrorAction SilentlyContinue
+if ($tpm) { "tpm=" + $tpm.SpecVersion } else { "tpm=" }
+$dg = Get-CimInstance -Namespace root\Microsoft\Windows\DeviceGuard -ClassName Win32_DeviceGuard -ErrorAction SilentlyContinue
+if ($dg) { "vbs=" + $dg.VirtualizationBasedSecurityStatus }
+"#;
+        let mut cmd = Command::new("powershell");
+        cmd.args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT]);
+        exec_with_timeout(cmd, SYSTEM_COMMAND_TIMEOUT)
+            .map(|output| parse_windows_security(&String::from_utf8_lossy(&output.stdout)))
+            .unwrap_or_else(|| PlatformSecurity {
+                secure_boot: "unknown".to_string(),
+                ..Default::default()
+            })
+    }
+
     #[cfg(target_os = "linux")]
     fn add_linux_hardware_info(mut info: SystemInfoStatic) -> SystemInfoStatic {
         use std::fs;
@@ -226,6 +334,7 @@ impl SystemInfoCollector {
             bios_version: static_info.bios_version.clone(),
             system_model: static_info.system_model.clone(),
             system_vendor: static_info.system_vendor.clone(),
+            security: Some(static_info.security.clone()),
         }
     }
 }
@@ -251,3 +360,96 @@ fn extract_json_string(line: &str) -> Option<String> {
         None
     }
 }
+
+/// `SecureBoot` EFI variable: 4 attribute bytes, then the value
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn parse_efi_secure_boot(data: &[u8]) -> Option<&'static str> {
+    match data.get(4)? {
+        0 => Some("disabled"),
+        1 => Some("enabled"),
+        _ => None,
+    }
+}
+
+/// `csrutil status`, e.g. "System Integrity Protection status: enabled."
+#[cfg_attr(not(any(test, target_os = "macos")), allow(dead_code))]
+fn parse_csrutil(output: &str) -> Option<&'static str> {
+    let status = output.split_once("status:")?.1.trim_start();
+    // A custom configuration is reported as "enabled (Custom Configuration)"
+    if status.starts_with("enabled") {
+        Some("enabled")
+    } else if status.starts_with("disabled") {
+        Some("disabled")
+    } else {
+        None
+    }
+}
+
+/// `key=value` lines printed by the Windows security script
+#[cfg_attr(not(any(test, target_os = "windows")), allow(dead_code))]
+fn parse_windows_security(output: &str) -> PlatformSecurity {
+    let mut security = PlatformSecurity {
+        secure_boot: "unknown".to_string(),
+        ..Default::default()
+    };
+    for (key, value) in output.lines().filter_map(|l| l.trim().split_once('=')) {
+        match key {
+            "secureboot" => {
+                security.secure_boot = match value {
+                    "True" => "enabled",
+                    "False" => "disabled",
+                    "unsupported" => "unsupported",
+                    _ => "unknown",
+                }
+                .to_string();
+            }
+            // SpecVersion lists the family first, e.g. "2.0, 0, 1.38"
+            "tpm" => {
+                let version = value.split(',').next().unwrap_or_default().trim();
+                security.tpm_present = !version.is_empty();
+                security.tpm_version = version.to_string();
+            }
+            "vbs" => {
+                security.vbs_status = match value {
+                    "2" => "running",
+                    "1" => "enabled",
+                    _ => "off",
+                }
+                .to_string();
+            }
+            _ => {}
+        }
+    }
+    security
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_security_parsers() {
+        assert_eq!(parse_efi_secure_boot(&[6, 0, 0, 0, 1]), Some("enabled"));
+        assert_eq!(parse_efi_secure_boot(&[6, 0, 0, 0, 0]), Some("disabled"));
+        assert_eq!(parse_efi_secure_boot(&[6, 0]), None);
+
+        assert_eq!(
+            parse_csrutil("System Integrity Protection status: enabled."),
+            Some("enabled")
+        );
+        assert_eq!(
+            parse_csrutil("System Integrity Protection status: disabled."),
+            Some("disabled")
+        );
+
+        let security = parse_windows_security("secureboot=True\r\ntpm=2.0, 0, 1.38\r\nvbs=2\r\n");
+        assert_eq!(security.secure_boot, "enabled");
+        assert!(security.tpm_present);
+        assert_eq!(security.tpm_version, "2.0");
+        assert_eq!(security.vbs_status, "running");
+
+        let security = parse_windows_security("secureboot=unsupported\ntpm=\n");
+        assert_eq!(security.secure_boot, "unsupported");
+        assert!(!security.tpm_present);
+    }
+}
@@ -0,0 +1,281 @@
+//! CPU temperature backends
+//!
+//! - Linux: hwmon (`coretemp`/`k10temp`) with a thermal zone fallback
+//! - Windows: LibreHardwareMonitor/OpenHardwareMonitor WMI bridge, falling
+//!   back to ACPI thermal zones (`MSAcpi_ThermalZoneTemperature`)
+//! - macOS: SMC/HID sensors, falling back to `powermetrics` (root only)
+//!
+//! The Windows and macOS backends are comparatively expensive, so their
+//! readings are cached for [`REFRESH_INTERVAL`].
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+use std::time::{Duration, Instant};
+
+/// Minimum time between two reads of the slow backends
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Timeout for the PowerShell and `powermetrics` fallbacks, which run in the
+/// collector tick
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// CPU temperature reader
+pub struct CpuTemperature {
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    components: sysinfo::Components,
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    last_read: Option<(Instant, f64)>,
+}
+
+impl CpuTemperature {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(any(target_os = "windows", target_os = "macos"))]
+            components: sysinfo::Components::new_with_refreshed_list(),
+            #[cfg(any(target_os = "windows", target_os = "macos"))]
+            last_read: None,
+        }
+    }
+
+    /// Current CPU temperature in °C, or 0.0 when no sensor is available
+    #[cfg(target_os = "linux")]
+    pub fn read(&mut self) -> f64 {
+        use std::fs;
+
+        // Try hwmon thermal zones
+        if let Ok(entries) = fs::read_dir("/sys/class/hwmon") {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                // Check if this is a CPU thermal sensor
+                if let Ok(name) = fs::read_to_string(path.join("name")) {
+                    let name = name.trim();
+                    if name.contains("coretemp") || name.contains("k10temp") || name.contains("cpu")
+                    {
+                        // Read temp1_input (in millidegrees)
+                        if let Ok(temp) = fs::read_to_string(path.join("temp1_input")) {
+                            if let Ok(temp_mc) = temp.trim().parse::<i64>() {
+                                return temp_mc as f64 / 1000.0;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Fallback to thermal zones
+        if let Ok(temp) = fs::read_to_string("/sys/class/thermal/thermal_zone0/temp") {
+            if let Ok(temp_mc) = temp.trim().parse::<i64>() {
+                return temp_mc as f64 / 1000.0;
+            }
+        }
+
+        0.0
+    }
+
+    /// Current CPU temperature in °C, or 0.0 when no sensor is available
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    pub fn read(&mut self) -> f64 {
+        if let Some((at, value)) = self.last_read {
+            if at.elapsed() < REFRESH_INTERVAL {
+                return value;
+            }
+        }
+
+        let value = self.read_uncached();
+        self.last_read = Some((Instant::now(), value));
+        value
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+    pub fn read(&mut self) -> f64 {
+        0.0
+    }
+
+    #[cfg(target_os = "windows")]
+    fn read_uncached(&mut self) -> f64 {
+        use std::process::Command;
+
+        use crate::utils::safe_command::exec_with_timeout;
+
+        // Hardware monitor bridge: reads the real package sensor when
+        // LibreHardwareMonitor (or its predecessor) is running
+        for namespace in ["root/LibreHardwareMonitor", "root/OpenHardwareMonitor"] {
+            let script = format!(
+                "Get-CimInstance -Namespace {namespace} -ClassName Sensor \
+                 -Filter \"SensorType='Temperature'\" -ErrorAction SilentlyContinue \
+                 | Select-Object Name,Value | ConvertTo-Csv -NoTypeInformation"
+            );
+            let mut cmd = Command::new("powershell");
+            cmd.args(["-NoProfile", "-Command", &script]);
+            if let Some(output) = exec_with_timeout(cmd, COMMAND_TIMEOUT) {
+                if output.status.success() {
+                    let stdout = String::from_utf8_lossy(&output.stdout);
+                    if let Some(temp) = parse_hardware_monitor_csv(&stdout) {
+                        return temp;
+                    }
+                }
+            }
+        }
+
+        // ACPI thermal zones via WMI (MSAcpi_ThermalZoneTemperature)
+        self.components.refresh(true);
+        pick_cpu_component(
+            self.components
+                .iter()
+                .filter_map(|c| c.temperature().map(|t| (c.label(), t))),
+        )
+        .unwrap_or(0.0)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn read_uncached(&mut self) -> f64 {
+        use std::process::Command;
+
+        use crate::utils::safe_command::exec_with_timeout;
+
+        // SMC keys (Intel) and HID sensors (Apple Silicon)
+        self.components.refresh(true);
+        if let Some(temp) = pick_cpu_component(
+            self.components
+                .iter()
+                .filter_map(|c| c.temperature().map(|t| (c.label(), t))),
+        ) {
+            return temp;
+        }
+
+        // powermetrics reads the same SMC data but requires root
+        let mut cmd = Command::new("powermetrics");
+        cmd.args(["--samplers", "smc", "-i", "1", "-n", "1"]);
+        if let Some(output) = exec_with_timeout(cmd, COMMAND_TIMEOUT) {
+            if output.status.success() {
+                if let Some(temp) = parse_powermetrics(&String::from_utf8_lossy(&output.stdout)) {
+                    return temp;
+                }
+            }
+        }
+
+        0.0
+    }
+}
+
+impl Default for CpuTemperature {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Plausible CPU temperature range; filters out disconnected sensors
+fn plausible(temp: f64) -> bool {
+    temp.is_finite() && temp > 0.0 && temp < 150.0
+}
+
+/// Pick the hottest CPU sensor among `(label, °C)` pairs.
+///
+/// Falls back to the hottest sensor of any kind when none is labelled as a
+/// CPU sensor (ACPI thermal zones usually carry generic names).
+#[cfg_attr(
+    not(any(test, target_os = "windows", target_os = "macos")),
+    allow(dead_code)
+)]
+fn pick_cpu_component<'a>(sensors: impl Iterator<Item = (&'a str, f32)>) -> Option<f64> {
+    let mut cpu: Option<f64> = None;
+    let mut any: Option<f64> = None;
+
+    for (label, temp) in sensors {
+        let temp = temp as f64;
+        if !plausible(temp) {
+            continue;
+        }
+        let label = label.to_ascii_lowercase();
+        let is_cpu = !label.contains("gpu")
+            && [
+                "cpu", "core", "package", "peci", "die", "pacc", "eacc", "soc",
+            ]
+            .iter()
+            .any(|k| label.contains(k));
+        if is_cpu {
+            cpu = Some(cpu.map_or(temp, |c| c.max(temp)));
+        }
+        any = Some(any.map_or(temp, |a| a.max(temp)));
+    }
+
+    cpu.or(any)
+}
+
+/// Parse `Name,Value` CSV rows from the hardware monitor WMI bridge
+#[cfg_attr(not(any(test, target_os = "windows")), allow(dead_code))]
+fn parse_hardware_monitor_csv(csv: &str) -> Option<f64> {
+    let mut package = None;
+    let mut cores: Option<f64> = None;
+
+    for line in csv.lines().skip(1) {
+        let Some((name, value)) = line.split_once(',') else {
+            continue;
+        };
+        let name = name.trim().trim_matches('"');
+        let Ok(value) = value.trim().trim_matches('"').parse::<f64>() else {
+            continue;
+        };
+        if !plausible(value) {
+            continue;
+        }
+
+        if name.eq_ignore_ascii_case("CPU Package") {
+            package = Some(value);
+        } else if name.starts_with("CPU Core") || name.starts_with("Core") || name == "Tctl/Tdie" {
+            cores = Some(cores.map_or(value, |c| c.max(value)));
+        }
+    }
+
+    package.or(cores)
+}
+
+/// Parse `CPU die temperature: 52.31 C` from `powermetrics --samplers smc`
+#[cfg_attr(not(any(test, target_os = "macos")), allow(dead_code))]
+fn parse_powermetrics(output: &str) -> Option<f64> {
+    output.lines().find_map(|line| {
+        let value = line.trim().strip_prefix("CPU die temperature:")?;
+        let temp = value.trim().trim_end_matches('C').trim().parse().ok()?;
+        plausible(temp).then_some(temp)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_cpu_component() {
+        let sensors = [("GPU", 80.0), ("CPU Proximity", 45.0), ("PECI CPU", 51.5)];
+        assert_eq!(pick_cpu_component(sensors.into_iter()), Some(51.5));
+
+        // Generic ACPI zone names fall back to the hottest sensor
+        let zones = [
+            ("ACPI\\ThermalZone\\TZ00_0", 27.8),
+            ("ACPI\\ThermalZone\\TZ01_0", 40.0),
+        ];
+        assert_eq!(pick_cpu_component(zones.into_iter()), Some(40.0));
+
+        let bogus = [("CPU", -273.0), ("CPU", f32::NAN)];
+        assert_eq!(pick_cpu_component(bogus.into_iter()), None);
+    }
+
+    #[test]
+    fn test_parse_hardware_monitor_csv() {
+        let csv = "\"Name\",\"Value\"\n\"CPU Core #1\",\"48\"\n\"CPU Package\",\"55.5\"\n\"GPU Core\",\"70\"\n";
+        assert_eq!(parse_hardware_monitor_csv(csv), Some(55.5));
+
+        let cores = "\"Name\",\"Value\"\n\"CPU Core #1\",\"48\"\n\"CPU Core #2\",\"50.25\"\n";
+        assert_eq!(parse_hardware_monitor_csv(cores), Some(50.25));
+
+        assert_eq!(parse_hardware_monitor_csv("\"Name\",\"Value\"\n"), None);
+    }
+
+    #[test]
+    fn test_parse_powermetrics() {
+        let output = "**** SMC sensors ****\n\nCPU Thermal level: 0\nCPU die temperature: 52.31 C\nGPU die temperature: 47.00 C\n";
+        assert_eq!(parse_powermetrics(output), Some(52.31));
+        assert_eq!(parse_powermetrics("CPU Thermal level: 0\n"), None);
+    }
+}
@@ -0,0 +1,270 @@
+//! Textfile collector (node_exporter compatible)
+//!
+//! Reads `*.prom` files from `collector.textfile_dir` in the Prometheus text
+//! exposition format and forwards every sample as a custom metric
+//! (collector "textfile"). As with node_exporter, writers should create the
+//! file under another name and rename it into place. Each run also reports
+//! `textfile_mtime_seconds{file}` per file and `textfile_scrape_error`
+//! (1 if any file failed to parse).
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
+
+use tracing::warn;
+
+use crate::config::CollectorConfig;
+use crate::proto::CustomMetric;
+
+use super::registry::{CollectContext, Collector, Fragment, IntervalClass};
+
+/// Files larger than this are skipped
+const MAX_FILE_BYTES: u64 = 4 * 1024 * 1024;
+
+pub struct TextfileCollector {
+    dir: PathBuf,
+}
+
+impl TextfileCollector {
+    pub fn new(dir: &str) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    fn collect_dir(&self) -> Vec<CustomMetric> {
+        let mut metrics = Vec::new();
+        let mut error = false;
+
+        let mut files: Vec<PathBuf> = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|ext| ext == "prom"))
+                .collect(),
+            Err(e) => {
+                warn!(
+                    "Cannot read textfile directory {}: {}",
+                    self.dir.display(),
+                    e
+                );
+                error = true;
+                Vec::new()
+            }
+        };
+        files.sort();
+
+        for path in files {
+            let file = path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let result = std::fs::metadata(&path)
+                .and_then(|meta| {
+                    if meta.len() > MAX_FILE_BYTES {
+                        return Err(std::io::Error::other("file too large"));
+                    }
+                    Ok((meta.modified()?, std::fs::read_to_string(&path)?))
+                })
+                .map_err(|e| e.to_string())
+                .and_then(|(modified, text)| Ok((modified, parse(&text)?)));
+
+            match result {
+                Ok((modified, samples)) => {
+                    metrics.extend(samples);
+                    let mtime = modified
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs_f64();
+                    metrics.push(CustomMetric {
+                        collector: "textfile".to_string(),
+                        name: "textfile_mtime_seconds".to_string(),
+                        value: mtime,
+                        unit: "s".to_string(),
+                        labels: HashMap::from([("file".to_string(), file)]),
+                    });
+                }
+                Err(e) => {
+                    warn!("Failed to parse textfile {}: {}", path.display(), e);
+                    error = true;
+                }
+            }
+        }
+
+        metrics.push(CustomMetric {
+            collector: "textfile".to_string(),
+            name: "textfile_scrape_error".to_string(),
+            value: if error { 1.0 } else { 0.0 },
+            ..Default::default()
+        });
+        metrics
+    }
+}
+
+impl Collector for TextfileCollector {
+    fn name(&self) -> &'static str {
+        "textfile"
+    }
+
+    fn interval(&self, config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Periodic(Duration::from_millis(config.textfile_interval_ms))
+    }
+
+    fn collect(&mut self, _ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        Ok(Fragment::Custom(self.collect_dir()))
+    }
+}
+
+/// Parse a whole exposition file; any bad line fails the file, as in
+/// node_exporter
+fn parse(text: &str) -> Result<Vec<CustomMetric>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .map(|(i, line)| parse_sample(line.trim()).map_err(|e| format!("line {}: {e}", i + 1)))
+        .collect()
+}
+
+/// `name{label="value",...} value [timestamp]`
+fn parse_sample(line: &str) -> Result<CustomMetric, String> {
+    let name_end = line
+        .find(|c: char| c == '{' || c.is_whitespace())
+        .ok_or("missing value")?;
+    let name = &line[..name_end];
+    if !is_metric_name(name) {
+        return Err(format!("invalid metric name '{name}'"));
+    }
+
+    let mut rest = &line[name_end..];
+    let mut labels = HashMap::new();
+    if let Some(body) = rest.strip_prefix('{') {
+        rest = parse_labels(body, &mut labels)?;
+    }
+
+    let mut fields = rest.split_whitespace();
+    let value = fields.next().ok_or("missing value")?;
+    let value = match value {
+        "+Inf" => f64::INFINITY,
+        "-Inf" => f64::NEG_INFINITY,
+        other => other
+            .parse::<f64>()
+            .map_err(|_| format!("invalid value '{other}'"))?,
+    };
+    if fields.nth(1).is_some() {
+        return Err("trailing data".to_string());
+    }
+
+    Ok(CustomMetric {
+        collector: "textfile".to_string(),
+        name: name.to_string(),
+        value,
+        unit: String::new(),
+        labels,
+    })
+}
+
+/// Parse labels after `{`, returning the text after the closing `}`
+fn parse_labels<'a>(
+    mut body: &'a str,
+    labels: &mut HashMap<String, String>,
+) -> Result<&'a str, String> {
+    loop {
+        body = body.trim_start();
+        if let Some(rest) = body.strip_prefix('}') {
+            return Ok(rest);
+        }
+        let (key, rest) = body.split_once('=').ok_or("unterminated labels")?;
+        let key = key.trim();
+        if !is_metric_name(key) || key.contains(':') {
+            return Err(format!("invalid label name '{key}'"));
+        }
+        let rest = rest
+            .trim_start()
+            .strip_prefix('"')
+            .ok_or("label value must be quoted")?;
+
+        let mut value = String::new();
+        let mut chars = rest.char_indices();
+        let end = loop {
+            match chars.next() {
+                Some((i, '"')) => break i,
+                Some((_, '\\')) => match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, c)) => value.push(c),
+                    None => return Err("unterminated label value".to_string()),
+                },
+                Some((_, c)) => value.push(c),
+                None => return Err("unterminated label value".to_string()),
+            }
+        };
+        labels.insert(key.to_string(), value);
+
+        body = rest[end + 1..].trim_start();
+        body = body.strip_prefix(',').unwrap_or(body);
+    }
+}
+
+fn is_metric_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text = r#"
+# HELP backup_last_success_timestamp Last successful backup
+# TYPE backup_last_success_timestamp gauge
+backup_last_success_timestamp{job="db",path="C:\\backup \"nightly\""} 1.7e9
+raid_degraded 0 1700000000000
+queue_depth{ queue = "mail" , } -3
+"#;
+        let samples = parse(text).unwrap();
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[0].value, 1.7e9);
+        assert_eq!(samples[0].labels["path"], r#"C:\backup "nightly""#);
+        assert_eq!(samples[1].name, "raid_degraded");
+        assert_eq!(samples[2].labels["queue"], "mail");
+        assert_eq!(samples[2].value, -3.0);
+
+        assert!(parse("bad-name 1").is_err());
+        assert!(parse("x{a=\"1} 2").is_err());
+        assert!(parse("x 1 2 3").is_err());
+        assert!(parse("x abc").is_err());
+    }
+
+    #[test]
+    fn test_collect_dir() {
+        let dir = std::env::temp_dir().join(format!("nanolink-textfile-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("good.prom"), "up 1\n").unwrap();
+        std::fs::write(dir.join("bad.prom"), "up{\n").unwrap();
+        std::fs::write(dir.join("skip.txt"), "ignored 1\n").unwrap();
+
+        let metrics = TextfileCollector::new(&dir.to_string_lossy()).collect_dir();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(metrics.iter().any(|m| m.name == "up" && m.value == 1.0));
+        assert!(!metrics.iter().any(|m| m.name == "ignored"));
+        let mtimes: Vec<_> = metrics
+            .iter()
+            .filter(|m| m.name == "textfile_mtime_seconds")
+            .collect();
+        assert_eq!(mtimes.len(), 1);
+        assert_eq!(mtimes[0].labels["file"], "good.prom");
+        let error = metrics
+            .iter()
+            .find(|m| m.name == "textfile_scrape_error")
+            .unwrap();
+        assert_eq!(error.value, 1.0);
+    }
+}
@@ -0,0 +1,376 @@
+//! Sandboxed WASM collector plugins
+//!
+//! Plugins we don't fully trust run as WebAssembly modules in wasmtime
+//! without WASI: no filesystem, network, clock or environment access. The
+//! only host function is `nanolink.log(ptr, len)`. A plugin exports
+//!
+//! - `memory`
+//! - `alloc(len: i32) -> i32`, returning a buffer for the input
+//! - `collect(ptr: i32, len: i32) -> i64`, taking the input JSON and
+//!   returning `(ptr << 32) | len` of the output JSON
+//!
+//! The input is a read-only snapshot of host metrics:
+//!
+//! ```json
+//! {"timestamp": 1760000000000, "cpu_usage_percent": 12.5,
+//!  "memory_used": 2147483648, "memory_total": 8589934592,
+//!  "load_average": [0.5, 0.4, 0.3], "uptime_seconds": 86400}
+//! ```
+//!
+//! The output is a list of `{"name", "value", "unit", "labels"}` metrics,
+//! sent as custom metrics with the plugin's file name as collector. Every run
+//! gets a fresh instance with a fuel (instruction) budget and a memory cap.
+//! The directory is rescanned on every run, so plugins are hot-loaded.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use sysinfo::System;
+use tracing::{info, warn};
+use wasmtime::{Caller, Engine, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use crate::config::{CollectorConfig, WasmPluginConfig};
+use crate::proto::CustomMetric;
+
+use super::registry::{CollectContext, Collector, Fragment, IntervalClass};
+
+/// Largest output a plugin may return
+const MAX_OUTPUT_BYTES: usize = 1024 * 1024;
+
+/// Most metrics taken from one plugin run
+const MAX_METRICS: usize = 1000;
+
+#[derive(Serialize)]
+struct Input {
+    timestamp: u64,
+    cpu_usage_percent: f32,
+    memory_used: u64,
+    memory_total: u64,
+    load_average: [f64; 3],
+    uptime_seconds: u64,
+}
+
+impl Input {
+    fn snapshot(system: &System) -> Self {
+        let load = System::load_average();
+        Self {
+            timestamp: crate::utils::clock::now_millis(),
+            cpu_usage_percent: system.global_cpu_usage(),
+            memory_used: system.used_memory(),
+            memory_total: system.total_memory(),
+            load_average: [load.one, load.five, load.fifteen],
+            uptime_seconds: System::uptime(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct OutputMetric {
+    name: String,
+    value: f64,
+    #[serde(default)]
+    unit: String,
+    #[serde(default)]
+    labels: HashMap<String, String>,
+}
+
+struct State {
+    limits: StoreLimits,
+    plugin: String,
+}
+
+struct Plugin {
+    name: String,
+    modified: SystemTime,
+    /// `None` when the current file failed to compile
+    module: Option<Module>,
+}
+
+/// Runs every plugin in the configured directory
+pub struct WasmCollector {
+    engine: Engine,
+    linker: Linker<State>,
+    config: WasmPluginConfig,
+    plugins: BTreeMap<PathBuf, Plugin>,
+}
+
+impl WasmCollector {
+    pub fn new(config: WasmPluginConfig) -> anyhow::Result<Self> {
+        let mut engine_config = wasmtime::Config::new();
+        engine_config.consume_fuel(true);
+        let engine = Engine::new(&engine_config)?;
+
+        let mut linker = Linker::new(&engine);
+        linker.func_wrap(
+            "nanolink",
+            "log",
+            |mut caller: Caller<'_, State>, ptr: i32, len: i32| {
+                let Some(memory) = exported_memory(&mut caller) else {
+                    return;
+                };
+                if let Ok(bytes) = read(&memory, &caller, ptr, len.min(4096)) {
+                    info!(
+                        "[wasm:{}] {}",
+                        caller.data().plugin,
+                        String::from_utf8_lossy(&bytes)
+                    );
+                }
+            },
+        )?;
+
+        Ok(Self {
+            engine,
+            linker,
+            config,
+            plugins: BTreeMap::new(),
+        })
+    }
+
+    /// Pick up added, changed and removed plugin files
+    fn rescan(&mut self) {
+        let entries = match std::fs::read_dir(&self.config.dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                if !self.plugins.is_empty() {
+                    warn!("Cannot read plugin directory {}: {}", self.config.dir, e);
+                    self.plugins.clear();
+                }
+                return;
+            }
+        };
+
+        let mut seen = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "wasm") {
+                continue;
+            }
+            let Ok(modified) = entry.metadata().and_then(|m| m.modified()) else {
+                continue;
+            };
+            seen.push(path.clone());
+            if self
+                .plugins
+                .get(&path)
+                .is_some_and(|p| p.modified == modified)
+            {
+                continue;
+            }
+
+            let name = plugin_name(&path);
+            let module = match Module::from_file(&self.engine, &path) {
+                Ok(module) => {
+                    info!("Loaded WASM plugin '{}' from {}", name, path.display());
+                    Some(module)
+                }
+                Err(e) => {
+                    warn!("Failed to load WASM plugin {}: {:#}", path.display(), e);
+                    None
+                }
+            };
+            self.plugins.insert(
+                path,
+                Plugin {
+                    name,
+                    modified,
+                    module,
+                },
+            );
+        }
+
+        self.plugins.retain(|path, plugin| {
+            let keep = seen.contains(path);
+            if !keep {
+                info!("Unloaded WASM plugin '{}'", plugin.name);
+            }
+            keep
+        });
+    }
+
+    /// Run one plugin in a fresh, limited store
+    fn run(&self, name: &str, module: &Module, input: &[u8]) -> anyhow::Result<Vec<CustomMetric>> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size((self.config.max_memory_mb * 1024 * 1024) as usize)
+            .instances(1)
+            .build();
+        let mut store = Store::new(
+            &self.engine,
+            State {
+                limits,
+                plugin: name.to_string(),
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(self.config.fuel)?;
+
+        let instance = self.linker.instantiate(&mut store, module)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| anyhow::anyhow!("plugin does not export memory"))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+        let collect = instance.get_typed_func::<(i32, i32), i64>(&mut store, "collect")?;
+
+        let len = i32::try_from(input.len())?;
+        let ptr = alloc.call(&mut store, len)?;
+        memory.write(&mut store, usize::try_from(ptr)?, input)?;
+        let packed = collect.call(&mut store, (ptr, len))?;
+
+        let (out_ptr, out_len) = ((packed >> 32) as i32, packed as i32);
+        if usize::try_from(out_len).is_ok_and(|l| l > MAX_OUTPUT_BYTES) {
+            anyhow::bail!("plugin output exceeds {} bytes", MAX_OUTPUT_BYTES);
+        }
+        let output = read(&memory, &store, out_ptr, out_len)?;
+        let metrics: Vec<OutputMetric> = serde_json::from_slice(&output)?;
+        Ok(metrics
+            .into_iter()
+            .take(MAX_METRICS)
+            .filter(|m| !m.name.is_empty() && m.value.is_finite())
+            .map(|m| CustomMetric {
+                collector: name.to_string(),
+                name: m.name,
+                value: m.value,
+                unit: m.unit,
+                labels: m.labels,
+            })
+            .collect())
+    }
+}
+
+impl Collector for WasmCollector {
+    fn name(&self) -> &'static str {
+        "wasm"
+    }
+
+    fn interval(&self, _config: &CollectorConfig) -> IntervalClass {
+        IntervalClass::Periodic(Duration::from_millis(self.config.interval_ms))
+    }
+
+    fn collect(&mut self, ctx: &CollectContext<'_>) -> anyhow::Result<Fragment> {
+        self.rescan();
+        let input = serde_json::to_vec(&Input::snapshot(ctx.system))?;
+
+        let mut metrics = Vec::new();
+        for plugin in self.plugins.values() {
+            let Some(module) = &plugin.module else {
+                continue;
+            };
+            match self.run(&plugin.name, module, &input) {
+                Ok(output) => metrics.extend(output),
+                Err(e) => warn!("WASM plugin '{}' failed: {:#}", plugin.name, e),
+            }
+        }
+        Ok(Fragment::Custom(metrics))
+    }
+}
+
+fn plugin_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn exported_memory(caller: &mut Caller<'_, State>) -> Option<Memory> {
+    caller.get_export("memory")?.into_memory()
+}
+
+/// Copy a guest buffer out, bounds-checked against its memory
+fn read(
+    memory: &Memory,
+    store: impl wasmtime::AsContext,
+    ptr: i32,
+    len: i32,
+) -> anyhow::Result<Vec<u8>> {
+    let (ptr, len) = (usize::try_from(ptr)?, usize::try_from(len)?);
+    let mut buffer = vec![0u8; len];
+    memory.read(&store, ptr, &mut buffer)?;
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Echoes the input length and logs through the host function
+    const PLUGIN: &str = r#"
+        (module
+          (import "nanolink" "log" (func $log (param i32 i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "hello")
+          (data (i32.const 64) "[{\"name\":\"answer\",\"value\":42,\"unit\":\"count\"}]")
+          (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+          (func (export "collect") (param i32 i32) (result i64)
+            (call $log (i32.const 0) (i32.const 5))
+            (i64.or (i64.shl (i64.const 64) (i64.const 32)) (i64.const 45))))
+    "#;
+
+    const SPIN: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) (i32.const 0))
+          (func (export "collect") (param i32 i32) (result i64)
+            (loop $l (br $l))
+            (i64.const 0)))
+    "#;
+
+    const WASI: &str = r#"
+        (module
+          (import "wasi_snapshot_preview1" "fd_write"
+            (func (param i32 i32 i32 i32) (result i32))))
+    "#;
+
+    fn collector() -> WasmCollector {
+        WasmCollector::new(WasmPluginConfig {
+            enabled: true,
+            fuel: 1_000_000,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_plugin_output() {
+        let collector = collector();
+        let module = Module::new(&collector.engine, PLUGIN).unwrap();
+        let metrics = collector.run("answer", &module, b"{}").unwrap();
+        assert_eq!(metrics.len(), 1);
+        assert_eq!(metrics[0].collector, "answer");
+        assert_eq!(metrics[0].value, 42.0);
+        assert_eq!(metrics[0].unit, "count");
+    }
+
+    #[test]
+    fn test_plugin_is_contained() {
+        let collector = collector();
+        // Runaway loops run out of fuel
+        let spin = Module::new(&collector.engine, SPIN).unwrap();
+        assert!(collector.run("spin", &spin, b"{}").is_err());
+        // Only the nanolink host functions exist
+        let wasi = Module::new(&collector.engine, WASI).unwrap();
+        assert!(collector.run("wasi", &wasi, b"{}").is_err());
+    }
+
+    #[test]
+    fn test_hot_reload() {
+        let dir = std::env::temp_dir().join(format!("nanolink-wasm-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Text format is accepted as well
+        std::fs::write(dir.join("answer.wasm"), PLUGIN).unwrap();
+        std::fs::write(dir.join("notes.txt"), b"ignored").unwrap();
+
+        let mut collector = WasmCollector::new(WasmPluginConfig {
+            enabled: true,
+            dir: dir.to_string_lossy().into_owned(),
+            ..Default::default()
+        })
+        .unwrap();
+        collector.rescan();
+        assert_eq!(collector.plugins.len(), 1);
+
+        std::fs::remove_file(dir.join("answer.wasm")).unwrap();
+        collector.rescan();
+        assert!(collector.plugins.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,67 @@
+//! Background change watchers
+//!
+//! A watcher runs a blocking OS notification loop on its own thread and
+//! bumps a counter on every change. Collectors subscribe to the counter and
+//! re-collect the affected data immediately instead of waiting for the next
+//! polling interval. One thread is shared by all subscribers (a layered
+//! collector is created per server connection).
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tracing::{debug, warn};
+
+/// Settle time so a burst of events (e.g. a multi-partition USB stick, a
+/// link flap) produces a single notification
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Process-wide change watcher
+pub struct ChangeWatcher {
+    name: &'static str,
+    run: fn(&ChangeWatcher) -> anyhow::Result<()>,
+    changes: OnceLock<watch::Sender<u64>>,
+}
+
+impl ChangeWatcher {
+    /// `run` blocks and calls [`ChangeWatcher::notify`] on every change
+    pub const fn new(name: &'static str, run: fn(&ChangeWatcher) -> anyhow::Result<()>) -> Self {
+        Self {
+            name,
+            run,
+            changes: OnceLock::new(),
+        }
+    }
+
+    /// Subscribe to changes, starting the watcher thread on first use
+    pub fn subscribe(&'static self) -> watch::Receiver<u64> {
+        self.changes
+            .get_or_init(|| {
+                let spawned = std::thread::Builder::new()
+                    .name(self.name.to_string())
+                    .spawn(move || {
+                        if let Err(e) = (self.run)(self) {
+                            warn!("{} stopped: {}", self.name, e);
+                        }
+                    });
+                if let Err(e) = spawned {
+                    warn!("Failed to start {}: {}", self.name, e);
+                }
+                watch::channel(0).0
+            })
+            .subscribe()
+    }
+
+    /// Wait for a burst of events to settle
+    pub fn settle(&self) {
+        std::thread::sleep(DEBOUNCE);
+    }
+
+    /// Signal a change to all subscribers
+    pub fn notify(&self) {
+        debug!("{}: change detected", self.name);
+        if let Some(changes) = self.changes.get() {
+            changes.send_modify(|n| *n += 1);
+        }
+    }
+}
@@ -0,0 +1,2930 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::utils::clock::ClockMode;
+use serde_yaml::Value;
+
+mod overlay;
+
+/// Current config version for migration support
+pub const CONFIG_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    /// Config file version for migration support
+    #[serde(default = "default_config_version")]
+    pub config_version: u32,
+
+    /// Files with defaults this file overrides (paths or globs, relative to
+    /// this file); `conf.d/` next to it overrides this file in turn
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+
+    /// How the config was assembled from several files
+    #[serde(skip)]
+    layers: Option<Arc<overlay::Layers>>,
+
+    /// Agent settings
+    #[serde(default)]
+    pub agent: AgentConfig,
+
+    /// Server connections
+    pub servers: Vec<ServerConfig>,
+
+    /// Metrics collection settings
+    #[serde(default)]
+    pub collector: CollectorConfig,
+
+    /// Ring buffer settings
+    #[serde(default)]
+    pub buffer: BufferConfig,
+
+    /// Shell command settings
+    #[serde(default)]
+    pub shell: ShellConfig,
+
+    /// Logging settings
+    #[serde(default)]
+    pub logging: LoggingConfig,
+
+    /// Management API settings
+    #[serde(default)]
+    pub management: ManagementConfig,
+
+    /// Security settings
+    #[serde(default)]
+    pub security: SecurityConfig,
+
+    /// Update settings
+    #[serde(default)]
+    pub update: UpdateConfig,
+
+    /// Scripts configuration
+    #[serde(default)]
+    pub scripts: ScriptsConfig,
+
+    /// Config management settings
+    #[serde(default)]
+    pub config_management: ConfigManagementConfig,
+
+    /// Package management settings
+    #[serde(default)]
+    pub package_management: PackageManagementConfig,
+
+    /// Reboot/shutdown command settings
+    #[serde(default)]
+    pub power: PowerConfig,
+
+    /// Privileged operations through a separate helper process
+    #[serde(default)]
+    pub privsep: PrivsepConfig,
+
+    /// Throughput probe settings
+    #[serde(default)]
+    pub speedtest: SpeedTestConfig,
+
+    /// Old file cleanup rules
+    #[serde(default)]
+    pub cleanup: CleanupConfig,
+
+    /// Syslog listener for devices that can't run an agent
+    #[serde(default)]
+    pub syslog: SyslogConfig,
+
+    /// StatsD listener for application metrics
+    #[serde(default)]
+    pub statsd: StatsdConfig,
+
+    /// Store-and-forward relay for agents behind this one
+    #[serde(default)]
+    pub relay: RelayConfig,
+
+    /// Maintenance windows for queued updates
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+
+    /// Recording of shell and script sessions
+    #[serde(default)]
+    pub recording: RecordingConfig,
+
+    /// Pseudonymization of personal data before it leaves the machine
+    #[serde(default)]
+    pub privacy: PrivacyConfig,
+
+    /// Alerts and connection events pushed to chat and incident tools
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
+
+    /// Signed config profile fetched from a central location
+    #[serde(default)]
+    pub profile: ProfileConfig,
+}
+
+fn default_config_version() -> u32 {
+    1 // Default to version 1 for old configs without version field
+}
+
+/// Update source for downloading updates
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateSource {
+    /// GitHub releases (default)
+    #[default]
+    Github,
+    /// Cloudflare R2 mirror
+    Cloudflare,
+    /// Custom URL
+    Custom,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateConfig {
+    /// Enable automatic update check
+    #[serde(default)]
+    pub auto_check: bool,
+
+    /// Update check interval in hours (default: 24)
+    #[serde(default = "default_update_check_interval")]
+    pub check_interval_hours: u64,
+
+    /// GitHub repository for updates (default: chenqi92/NanoLink)
+    #[serde(default = "default_update_repo")]
+    pub repo: String,
+
+    /// Allow automatic download of updates
+    #[serde(default)]
+    pub auto_download: bool,
+
+    /// Allow automatic application of updates (requires auto_download)
+    #[serde(default)]
+    pub auto_apply: bool,
+
+    /// Pre-release updates allowed
+    #[serde(default)]
+    pub allow_prerelease: bool,
+
+    /// Update source: github, cloudflare, or custom
+    #[serde(default)]
+    pub source: UpdateSource,
+
+    /// Custom update URL (used when source = "custom")
+    #[serde(default)]
+    pub custom_url: Option<String>,
+
+    /// Health-check the new binary next to the running agent before
+    /// switching, and roll back if it doesn't stay up
+    #[serde(default = "default_true")]
+    pub blue_green: bool,
+
+    /// Localhost port the candidate serves its health check on
+    #[serde(default = "default_candidate_port")]
+    pub candidate_port: u16,
+
+    /// How long the candidate may take to report healthy
+    #[serde(default = "default_health_timeout")]
+    pub health_timeout_seconds: u64,
+
+    /// Roll back if the promoted agent hasn't confirmed within this time
+    #[serde(default = "default_rollback_timeout")]
+    pub rollback_timeout_seconds: u64,
+
+    /// Ed25519 public keys (base64) trusted to sign offline update bundles
+    #[serde(default)]
+    pub bundle_public_keys: Vec<String>,
+
+    /// Where data files from update bundles (e.g. vulnerability databases)
+    /// are installed
+    #[serde(default = "default_update_data_dir")]
+    pub data_dir: String,
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        Self {
+            auto_check: false,
+            check_interval_hours: default_update_check_interval(),
+            repo: default_update_repo(),
+            auto_download: false,
+            auto_apply: false,
+            allow_prerelease: false,
+            source: UpdateSource::default(),
+            custom_url: None,
+            blue_green: true,
+            candidate_port: default_candidate_port(),
+            health_timeout_seconds: default_health_timeout(),
+            rollback_timeout_seconds: default_rollback_timeout(),
+            bundle_public_keys: Vec::new(),
+            data_dir: default_update_data_dir(),
+        }
+    }
+}
+
+fn default_update_check_interval() -> u64 {
+    24
+}
+
+fn default_update_repo() -> String {
+    "chenqi92/NanoLink".to_string()
+}
+
+fn default_candidate_port() -> u16 {
+    39199
+}
+
+fn default_health_timeout() -> u64 {
+    30
+}
+
+fn default_rollback_timeout() -> u64 {
+    120
+}
+
+fn default_update_data_dir() -> String {
+    #[cfg(unix)]
+    return "/var/lib/nanolink/data".to_string();
+    #[cfg(windows)]
+    return "C:\\ProgramData\\nanolink\\data".to_string();
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptsConfig {
+    /// Enable script execution
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Scripts directory path
+    #[serde(default = "default_scripts_dir")]
+    pub scripts_dir: String,
+
+    /// Require signature verification for scripts
+    #[serde(default)]
+    pub require_signature: bool,
+
+    /// Allowed script categories (empty = all allowed)
+    #[serde(default)]
+    pub allowed_categories: Vec<String>,
+
+    /// Script execution timeout in seconds
+    #[serde(default = "default_script_timeout")]
+    pub timeout_seconds: u64,
+
+    /// Maximum script output size in bytes
+    #[serde(default = "default_max_output_size")]
+    pub max_output_size: usize,
+
+    /// Run scripts in a sandbox
+    #[serde(default, skip_serializing_if = "SandboxConfig::is_disabled")]
+    pub sandbox: SandboxConfig,
+}
+
+impl Default for ScriptsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            scripts_dir: default_scripts_dir(),
+            require_signature: false,
+            allowed_categories: Vec::new(),
+            timeout_seconds: default_script_timeout(),
+            max_output_size: default_max_output_size(),
+            sandbox: SandboxConfig::default(),
+        }
+    }
+}
+
+fn default_scripts_dir() -> String {
+    #[cfg(unix)]
+    return "/opt/nanolink/scripts".to_string();
+    #[cfg(windows)]
+    return "C:\\ProgramData\\nanolink\\scripts".to_string();
+}
+
+fn default_script_timeout() -> u64 {
+    60
+}
+
+fn default_max_output_size() -> usize {
+    1024 * 1024 // 1MB
+}
+
+/// How config backups are kept
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupMode {
+    /// Timestamped `.bak` copies in `backup_dir`
+    #[default]
+    Files,
+    /// Commits in a git repository under `backup_dir/history`
+    Git,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigManagementConfig {
+    /// Enable config management
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Allowed config file paths (whitelist)
+    #[serde(default)]
+    pub allowed_configs: Vec<String>,
+
+    /// Automatically backup before changes
+    #[serde(default = "default_true")]
+    pub backup_on_change: bool,
+
+    /// Maximum number of backups to keep
+    #[serde(default = "default_max_backups")]
+    pub max_backups: u32,
+
+    /// Backup directory
+    #[serde(default = "default_backup_dir")]
+    pub backup_dir: String,
+
+    /// Keep backups as timestamped .bak files or in a local git repository
+    #[serde(default)]
+    pub backup_mode: BackupMode,
+
+    /// Commands allowed as `verify_command` after a config write
+    /// (e.g. "nginx -t"); matched exactly
+    #[serde(default)]
+    pub verify_commands: Vec<String>,
+
+    /// How long a post-write verification may keep failing before the
+    /// previous config is restored
+    #[serde(default = "default_verify_timeout")]
+    pub verify_timeout_seconds: u64,
+}
+
+impl Default for ConfigManagementConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allowed_configs: Vec::new(),
+            backup_on_change: true,
+            max_backups: default_max_backups(),
+            backup_dir: default_backup_dir(),
+            backup_mode: BackupMode::default(),
+            verify_commands: Vec::new(),
+            verify_timeout_seconds: default_verify_timeout(),
+        }
+    }
+}
+
+fn default_verify_timeout() -> u64 {
+    30
+}
+
+fn default_max_backups() -> u32 {
+    10
+}
+
+fn default_backup_dir() -> String {
+    #[cfg(unix)]
+    return "/var/lib/nanolink/backups".to_string();
+    #[cfg(windows)]
+    return "C:\\ProgramData\\nanolink\\backups".to_string();
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct PackageManagementConfig {
+    /// Enable package management
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Allow package updates (dangerous)
+    #[serde(default)]
+    pub allow_update: bool,
+
+    /// Allow system updates (very dangerous)
+    #[serde(default)]
+    pub allow_system_update: bool,
+
+    /// Also list installed desktop applications in PACKAGE_LIST: Windows
+    /// Add/Remove Programs entries and macOS application bundles, which
+    /// package managers don't know about (e.g. MSI installs)
+    #[serde(default)]
+    pub include_applications: bool,
+
+    /// Run package manager commands in a sandbox; resource limits fit
+    /// here, a low-privilege user usually can't install packages
+    #[serde(default, skip_serializing_if = "SandboxConfig::is_disabled")]
+    pub sandbox: SandboxConfig,
+}
+
+/// Privilege separation (Unix)
+///
+/// The agent runs as `agent_user` and asks `nanolink-agent helper`, running
+/// as root, to start, stop and restart services and to schedule or cancel
+/// reboots. The helper reads the same config file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivsepConfig {
+    /// Send privileged operations to the helper
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Helper socket; with systemd socket activation this must match
+    /// `ListenStream=` of nanolink-helper.socket
+    #[serde(default = "default_helper_socket")]
+    pub socket_path: String,
+
+    /// Only this user (and root) may talk to the helper
+    #[serde(default = "default_privsep_user")]
+    pub agent_user: String,
+
+    /// Operations the helper performs: service_start, service_stop,
+    /// service_restart, reboot, shutdown, power_cancel
+    #[serde(default = "default_helper_operations")]
+    pub allowed_operations: Vec<String>,
+
+    /// Services the helper may manage (glob patterns); empty allows any
+    #[serde(default)]
+    pub allowed_services: Vec<String>,
+}
+
+impl Default for PrivsepConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            socket_path: default_helper_socket(),
+            agent_user: default_privsep_user(),
+            allowed_operations: default_helper_operations(),
+            allowed_services: Vec::new(),
+        }
+    }
+}
+
+fn default_helper_socket() -> String {
+    "/run/nanolink/helper.sock".to_string()
+}
+
+fn default_privsep_user() -> String {
+    "nanolink".to_string()
+}
+
+fn default_helper_operations() -> Vec<String> {
+    [
+        "service_start",
+        "service_stop",
+        "service_restart",
+        "reboot",
+        "shutdown",
+        "power_cancel",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceConfig {
+    /// Queue PACKAGE_UPDATE, SYSTEM_UPDATE and WINDOWS_UPDATE_INSTALL and
+    /// run them only inside a maintenance window
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Maintenance windows; commands pick one with the `window` param,
+    /// otherwise the first is used
+    #[serde(default)]
+    pub windows: Vec<MaintenanceWindow>,
+
+    /// Where queued jobs are kept across restarts
+    #[serde(default = "default_maintenance_state_file")]
+    pub state_file: String,
+
+    /// Hooks are killed after this long
+    #[serde(default = "default_hook_timeout")]
+    pub hook_timeout_seconds: u64,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            windows: Vec::new(),
+            state_file: default_maintenance_state_file(),
+            hook_timeout_seconds: default_hook_timeout(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceWindow {
+    /// Window name used by commands
+    pub name: String,
+
+    /// Cron expression (local time) for when the window opens, e.g.
+    /// "0 2 * * sat,sun"
+    pub schedule: String,
+
+    /// How long the window stays open; jobs are only started while open
+    #[serde(default = "default_window_duration")]
+    pub duration_minutes: u32,
+
+    /// Jobs run at the same time in this window
+    #[serde(default = "default_window_concurrency")]
+    pub max_concurrent: usize,
+
+    /// Commands run before each job (e.g. snapshot, drain); a failure
+    /// fails the job
+    #[serde(default)]
+    pub pre_hooks: Vec<String>,
+
+    /// Commands run after each job, whether it succeeded or not
+    #[serde(default)]
+    pub post_hooks: Vec<String>,
+}
+
+fn default_maintenance_state_file() -> String {
+    #[cfg(unix)]
+    return "/var/lib/nanolink/maintenance.json".to_string();
+    #[cfg(windows)]
+    return "C:\\ProgramData\\nanolink\\maintenance.json".to_string();
+}
+
+fn default_hook_timeout() -> u64 {
+    600
+}
+
+fn default_window_duration() -> u32 {
+    120
+}
+
+fn default_window_concurrency() -> usize {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingConfig {
+    /// Record input and output of SHELL_EXECUTE and SCRIPT_EXECUTE
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Directory for recordings (asciicast v2) and the hash chain
+    #[serde(default = "default_recording_dir")]
+    pub dir: String,
+
+    /// Recordings older than this are deleted
+    #[serde(default = "default_recording_retention_days")]
+    pub retention_days: u32,
+
+    /// Oldest recordings are deleted once the store grows past this
+    #[serde(default = "default_recording_max_total_mb")]
+    pub max_total_mb: u64,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: default_recording_dir(),
+            retention_days: default_recording_retention_days(),
+            max_total_mb: default_recording_max_total_mb(),
+        }
+    }
+}
+
+fn default_recording_dir() -> String {
+    #[cfg(unix)]
+    return "/var/lib/nanolink/recordings".to_string();
+    #[cfg(windows)]
+    return "C:\\ProgramData\\nanolink\\recordings".to_string();
+}
+
+fn default_recording_retention_days() -> u32 {
+    90
+}
+
+fn default_recording_max_total_mb() -> u64 {
+    1024
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivacyConfig {
+    /// Scrub usernames, remote hosts and addresses in metrics before sending
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Key for hashing; the same salt gives the same pseudonyms on every
+    /// agent. Defaults to the agent ID, so pseudonyms can't be correlated
+    /// across machines.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt: Option<String>,
+
+    /// Usernames in sessions, session events and failed logins
+    #[serde(default = "default_privacy_pseudonymize")]
+    pub usernames: PrivacyMode,
+
+    /// Remote hosts of sessions and failed login sources
+    #[serde(default = "default_privacy_pseudonymize")]
+    pub remote_hosts: PrivacyMode,
+
+    /// Interface and public IP addresses
+    #[serde(default = "default_privacy_pseudonymize")]
+    pub ip_addresses: PrivacyMode,
+
+    /// Interface MAC addresses
+    #[serde(default)]
+    pub mac_addresses: PrivacyMode,
+
+    /// The machine's own hostname
+    #[serde(default)]
+    pub hostname: PrivacyMode,
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            salt: None,
+            usernames: PrivacyMode::Pseudonymize,
+            remote_hosts: PrivacyMode::Pseudonymize,
+            ip_addresses: PrivacyMode::Pseudonymize,
+            mac_addresses: PrivacyMode::Keep,
+            hostname: PrivacyMode::Keep,
+        }
+    }
+}
+
+/// How a personal field is exported
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PrivacyMode {
+    /// Sent unchanged
+    #[default]
+    Keep,
+    /// Replaced by a keyed hash
+    Hash,
+    /// Replaced by a stable value of the same shape, e.g. `user-3f9a1c2b`
+    /// or an address in 10.0.0.0/8 / fd00::/8
+    Pseudonymize,
+    /// Removed
+    Drop,
+}
+
+fn default_privacy_pseudonymize() -> PrivacyMode {
+    PrivacyMode::Pseudonymize
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationsConfig {
+    /// Send alerts and connection events to the configured webhooks
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Event sources forwarded (alert rules, anomaly detection, disk forecast)
+    #[serde(default = "default_notification_sources")]
+    pub sources: Vec<String>,
+
+    /// Notify when a server connection is lost and restored
+    #[serde(default = "default_true")]
+    pub connection_events: bool,
+
+    /// A connection counts as lost after being down this long
+    #[serde(default = "default_notification_connection_grace")]
+    pub connection_grace_secs: u64,
+
+    /// Notifications per webhook per minute; the rest are dropped and counted
+    #[serde(default = "default_notification_rate")]
+    pub max_per_minute: u32,
+
+    /// Delivery attempts after the first one fails
+    #[serde(default = "default_notification_retries")]
+    pub retries: u32,
+
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+
+    #[serde(default)]
+    pub pagerduty: Vec<PagerDutyConfig>,
+
+    #[serde(default)]
+    pub opsgenie: Vec<OpsgenieConfig>,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sources: default_notification_sources(),
+            connection_events: true,
+            connection_grace_secs: default_notification_connection_grace(),
+            max_per_minute: default_notification_rate(),
+            retries: default_notification_retries(),
+            webhooks: Vec::new(),
+            pagerduty: Vec::new(),
+            opsgenie: Vec::new(),
+        }
+    }
+}
+
+fn default_notification_sources() -> Vec<String> {
+    vec![
+        "alerts".to_string(),
+        "anomaly".to_string(),
+        "disk_forecast".to_string(),
+    ]
+}
+
+fn default_notification_connection_grace() -> u64 {
+    60
+}
+
+fn default_notification_rate() -> u32 {
+    20
+}
+
+fn default_notification_retries() -> u32 {
+    3
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    /// Shown in logs
+    #[serde(default)]
+    pub name: String,
+
+    pub url: String,
+
+    /// Payload shape
+    #[serde(default)]
+    pub format: WebhookFormat,
+
+    /// Custom JSON body (minijinja) for the generic format; needs the
+    /// `templates` feature
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+
+    /// Extra request headers, e.g. an authorization header
+    #[serde(default, skip_serializing_if = "std::collections::HashMap::is_empty")]
+    pub headers: std::collections::HashMap<String, String>,
+
+    /// Lowest level sent (info, warning, critical); resolutions always are
+    #[serde(default = "default_webhook_min_level")]
+    pub min_level: String,
+}
+
+fn default_webhook_min_level() -> String {
+    "info".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PagerDutyConfig {
+    /// Shown in logs
+    #[serde(default)]
+    pub name: String,
+
+    /// Events API v2 integration key of the service
+    pub routing_key: String,
+
+    /// Lowest level that opens an incident
+    #[serde(default = "default_incident_min_level")]
+    pub min_level: String,
+
+    /// Events API endpoint, e.g. https://events.eu.pagerduty.com/v2/enqueue
+    #[serde(default = "default_pagerduty_url")]
+    pub url: String,
+}
+
+fn default_incident_min_level() -> String {
+    "critical".to_string()
+}
+
+fn default_pagerduty_url() -> String {
+    "https://events.pagerduty.com/v2/enqueue".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpsgenieConfig {
+    /// Shown in logs
+    #[serde(default)]
+    pub name: String,
+
+    /// API key of an API integration
+    pub api_key: String,
+
+    /// Lowest level that opens an alert
+    #[serde(default = "default_incident_min_level")]
+    pub min_level: String,
+
+    #[serde(default)]
+    pub region: OpsgenieRegion,
+
+    /// Tags added to every alert
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// Opsgenie instance the account lives on
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OpsgenieRegion {
+    #[default]
+    Us,
+    Eu,
+}
+
+/// Payload shape of a webhook
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    /// JSON object with every field of the notification
+    #[default]
+    Generic,
+    /// Slack incoming webhook
+    Slack,
+    /// Discord webhook
+    Discord,
+    /// Microsoft Teams incoming webhook (MessageCard)
+    Teams,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileConfig {
+    /// Fetch a central config profile and merge it with the local files
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Profile URL; `{agent_id}` and `{hostname}` are substituted. Without
+    /// one, the profile is requested from the first configured server.
+    #[serde(default)]
+    pub url: Option<String>,
+
+    /// Detached signature URL, defaults to the profile URL plus `.sig`
+    #[serde(default)]
+    pub signature_url: Option<String>,
+
+    /// Extra request headers for the URL, e.g. an authorization header
+    #[serde(default, skip_serializing_if = "std::collections::HashMap::is_empty")]
+    pub headers: std::collections::HashMap<String, String>,
+
+    /// Ed25519 public keys (base64) trusted to sign profiles
+    #[serde(default)]
+    pub public_keys: Vec<String>,
+
+    /// How often to check for a new profile
+    #[serde(default = "default_profile_refresh")]
+    pub refresh_interval_secs: u64,
+
+    /// Let the profile override the local files instead of only providing
+    /// defaults for them
+    #[serde(default)]
+    pub override_local: bool,
+
+    /// Restart the agent service when a new profile is received, so it
+    /// takes effect; otherwise it applies on the next start
+    #[serde(default = "default_true")]
+    pub restart_on_change: bool,
+}
+
+impl Default for ProfileConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: None,
+            signature_url: None,
+            headers: Default::default(),
+            public_keys: Vec::new(),
+            refresh_interval_secs: default_profile_refresh(),
+            override_local: false,
+            restart_on_change: true,
+        }
+    }
+}
+
+fn default_profile_refresh() -> u64 {
+    900
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CleanupConfig {
+    /// Enable the cleanup command
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Cleanup rules; commands can only run these
+    #[serde(default)]
+    pub rules: Vec<CleanupRule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanupRule {
+    /// Rule name used by the command
+    pub name: String,
+
+    /// Directory to clean up
+    pub directory: String,
+
+    /// Glob matched against file names (e.g. "*.gz")
+    pub pattern: String,
+
+    /// Also clean up subdirectories
+    #[serde(default)]
+    pub recursive: bool,
+
+    /// Only files last modified at least this many days ago
+    #[serde(default = "default_cleanup_age")]
+    pub older_than_days: u64,
+
+    /// What to do with matching files
+    #[serde(default)]
+    pub action: CleanupAction,
+}
+
+fn default_cleanup_age() -> u64 {
+    7
+}
+
+/// Action applied to files matched by a cleanup rule
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CleanupAction {
+    /// Delete the file
+    #[default]
+    Delete,
+    /// Compress to `<file>.gz` and delete the original
+    Compress,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyslogConfig {
+    /// Receive syslog messages and forward them to servers as log batches
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// UDP listen address (RFC 3164/5424), empty to disable
+    #[serde(default = "default_syslog_udp_listen")]
+    pub udp_listen: String,
+
+    /// TCP listen address (RFC 6587 framing), empty to disable
+    #[serde(default)]
+    pub tcp_listen: String,
+
+    /// Sender IP addresses accepted; empty accepts any sender
+    #[serde(default)]
+    pub allowed_sources: Vec<String>,
+
+    /// Maximum messages per batch
+    #[serde(default = "default_syslog_batch_size")]
+    pub batch_size: usize,
+
+    /// Send a partial batch after this long
+    #[serde(default = "default_syslog_flush_interval")]
+    pub flush_interval_ms: u64,
+
+    /// Longer messages are truncated
+    #[serde(default = "default_syslog_max_message")]
+    pub max_message_bytes: usize,
+}
+
+impl Default for SyslogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            udp_listen: default_syslog_udp_listen(),
+            tcp_listen: String::new(),
+            allowed_sources: Vec::new(),
+            batch_size: default_syslog_batch_size(),
+            flush_interval_ms: default_syslog_flush_interval(),
+            max_message_bytes: default_syslog_max_message(),
+        }
+    }
+}
+
+/// Store-and-forward relay
+///
+/// Agents at a remote site list this agent in their `servers`. What they
+/// stream is kept per agent and forwarded to this agent's primary server
+/// (or its first one) whenever the uplink is up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayConfig {
+    /// Accept downstream agents
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// gRPC listen address for downstream agents
+    #[serde(default = "default_relay_listen")]
+    pub listen: String,
+
+    /// Tokens downstream agents authenticate with
+    #[serde(default)]
+    pub tokens: Vec<String>,
+
+    /// TLS certificate and key paths; plain gRPC without them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_cert: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_key: Option<String>,
+
+    /// Messages kept per downstream agent while the uplink is down; the
+    /// oldest are dropped first
+    #[serde(default = "default_relay_buffer_capacity")]
+    pub buffer_capacity: usize,
+
+    /// Most downstream agents accepted
+    #[serde(default = "default_relay_max_agents")]
+    pub max_agents: usize,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: default_relay_listen(),
+            tokens: Vec::new(),
+            tls_cert: None,
+            tls_key: None,
+            buffer_capacity: default_relay_buffer_capacity(),
+            max_agents: default_relay_max_agents(),
+        }
+    }
+}
+
+fn default_relay_listen() -> String {
+    format!("0.0.0.0:{DEFAULT_GRPC_PORT}")
+}
+
+fn default_relay_buffer_capacity() -> usize {
+    3600
+}
+
+fn default_relay_max_agents() -> usize {
+    64
+}
+
+fn default_syslog_udp_listen() -> String {
+    "0.0.0.0:514".to_string()
+}
+
+fn default_syslog_batch_size() -> usize {
+    100
+}
+
+fn default_syslog_flush_interval() -> u64 {
+    1000
+}
+
+fn default_syslog_max_message() -> usize {
+    8192
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsdConfig {
+    /// Receive StatsD / DogStatsD metrics from local applications
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// UDP listen address
+    #[serde(default = "default_statsd_listen")]
+    pub listen: String,
+
+    /// Aggregation window; counters and timers reset after each flush
+    #[serde(default = "default_statsd_flush_interval")]
+    pub flush_interval_ms: u64,
+
+    /// Distinct series (name plus tags) kept per window; samples for new
+    /// series are dropped beyond this
+    #[serde(default = "default_statsd_max_series")]
+    pub max_series: usize,
+}
+
+impl Default for StatsdConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: default_statsd_listen(),
+            flush_interval_ms: default_statsd_flush_interval(),
+            max_series: default_statsd_max_series(),
+        }
+    }
+}
+
+fn default_statsd_listen() -> String {
+    "127.0.0.1:8125".to_string()
+}
+
+fn default_statsd_flush_interval() -> u64 {
+    10000
+}
+
+fn default_statsd_max_series() -> usize {
+    10000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerConfig {
+    /// Delay before a reboot/shutdown when the command doesn't set one,
+    /// giving users a warning and operators a window to cancel
+    #[serde(default = "default_power_delay")]
+    pub default_delay_seconds: u64,
+
+    /// Lower bound for the delay, even if the command asks for less
+    #[serde(default)]
+    pub min_delay_seconds: u64,
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            default_delay_seconds: default_power_delay(),
+            min_delay_seconds: 0,
+        }
+    }
+}
+
+fn default_power_delay() -> u64 {
+    60
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeedTestConfig {
+    /// iperf3 server as `host[:port]` (default port 5201)
+    #[serde(default)]
+    pub iperf3_server: Option<String>,
+
+    /// File downloaded by the HTTP fallback
+    #[serde(default)]
+    pub http_download_url: Option<String>,
+
+    /// Endpoint accepting POST uploads for the HTTP fallback
+    #[serde(default)]
+    pub http_upload_url: Option<String>,
+
+    /// Payload size for the HTTP upload in bytes
+    #[serde(default = "default_speedtest_upload_bytes")]
+    pub upload_bytes: u64,
+
+    /// Probe duration per direction, also the upper bound for the command
+    #[serde(default = "default_speedtest_duration")]
+    pub duration_seconds: u64,
+
+    /// Bandwidth cap for the probe in Mbit/s (0 = unlimited)
+    #[serde(default = "default_speedtest_max_mbps")]
+    pub max_mbps: u64,
+
+    /// Minimum time between two probes
+    #[serde(default = "default_speedtest_min_interval")]
+    pub min_interval_seconds: u64,
+}
+
+impl Default for SpeedTestConfig {
+    fn default() -> Self {
+        Self {
+            iperf3_server: None,
+            http_download_url: None,
+            http_upload_url: None,
+            upload_bytes: default_speedtest_upload_bytes(),
+            duration_seconds: default_speedtest_duration(),
+            max_mbps: default_speedtest_max_mbps(),
+            min_interval_seconds: default_speedtest_min_interval(),
+        }
+    }
+}
+
+fn default_speedtest_upload_bytes() -> u64 {
+    10 * 1024 * 1024 // 10MB
+}
+
+fn default_speedtest_duration() -> u64 {
+    10
+}
+
+fn default_speedtest_max_mbps() -> u64 {
+    100
+}
+
+fn default_speedtest_min_interval() -> u64 {
+    300 // 5 minutes
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManagementConfig {
+    /// Enable management API (默认禁用以提高安全性)
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Port to listen on
+    #[serde(default = "default_management_port")]
+    pub port: u16,
+
+    /// Bind address (默认仅localhost以限制访问)
+    #[serde(default = "default_bind_address")]
+    pub bind_address: String,
+
+    /// API token for authentication (已废弃，改用 ServerConfig.management_token)
+    #[serde(default)]
+    pub api_token: Option<String>,
+
+    /// Enable TLS encryption
+    #[serde(default)]
+    pub tls_enabled: bool,
+
+    /// TLS certificate path
+    #[serde(default)]
+    pub tls_cert: Option<String>,
+
+    /// TLS private key path
+    #[serde(default)]
+    pub tls_key: Option<String>,
+
+    /// Rate limiting configuration
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+
+    /// Audit logging configuration
+    #[serde(default)]
+    pub audit: AuditConfig,
+
+    /// Desktop apps paired with `nanolink-agent pair`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paired_clients: Vec<PairedClient>,
+}
+
+/// A desktop app holding a management token from pairing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairedClient {
+    /// Name the app gave when pairing
+    pub name: String,
+
+    /// SHA-256 (hex) of the app's management token
+    pub token_sha256: String,
+
+    /// Permission level (0-3) granted to the app
+    pub permission: u8,
+
+    /// When the app was paired (RFC 3339)
+    #[serde(default)]
+    pub paired_at: String,
+}
+
+/// Rate limiting configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    /// Enable rate limiting
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Default requests per minute
+    #[serde(default = "default_requests_per_minute")]
+    pub requests_per_minute: u32,
+
+    /// Default burst size
+    #[serde(default = "default_burst")]
+    pub burst: u32,
+
+    /// Per-endpoint rate limits (endpoint path -> config)
+    #[serde(default)]
+    pub endpoints: std::collections::HashMap<String, EndpointRateLimit>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            requests_per_minute: default_requests_per_minute(),
+            burst: default_burst(),
+            endpoints: std::collections::HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EndpointRateLimit {
+    pub requests_per_minute: u32,
+    pub burst: u32,
+}
+
+fn default_requests_per_minute() -> u32 {
+    60
+}
+
+fn default_burst() -> u32 {
+    10
+}
+
+/// Audit logging configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditConfig {
+    /// Enable audit logging
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Maximum log file size in MB
+    #[serde(default = "default_max_size_mb")]
+    pub max_size_mb: u32,
+
+    /// Maximum age of log files in days
+    #[serde(default = "default_max_age_days")]
+    pub max_age_days: u32,
+
+    /// Maximum number of log files to keep
+    #[serde(default = "default_max_files")]
+    pub max_files: u32,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_size_mb: default_max_size_mb(),
+            max_age_days: default_max_age_days(),
+            max_files: default_max_files(),
+        }
+    }
+}
+
+fn default_max_size_mb() -> u32 {
+    100
+}
+
+fn default_max_age_days() -> u32 {
+    30
+}
+
+fn default_max_files() -> u32 {
+    10
+}
+
+impl Default for ManagementConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: default_management_port(),
+            bind_address: default_bind_address(),
+            api_token: None,
+            tls_enabled: false,
+            tls_cert: None,
+            tls_key: None,
+            rate_limit: RateLimitConfig::default(),
+            audit: AuditConfig::default(),
+            paired_clients: Vec::new(),
+        }
+    }
+}
+
+fn default_management_port() -> u16 {
+    9101
+}
+
+fn default_bind_address() -> String {
+    "127.0.0.1".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentConfig {
+    /// Persistent agent ID (auto-generated on first run, used for data continuity)
+    /// This ID is sent to the server to associate metrics with the same agent across restarts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+
+    /// Hostname override (defaults to system hostname)
+    pub hostname: Option<String>,
+
+    /// Heartbeat interval in seconds
+    #[serde(default = "default_heartbeat_interval")]
+    pub heartbeat_interval: u64,
+
+    /// Self-telemetry report interval in seconds (0 = disabled)
+    #[serde(default = "default_telemetry_interval")]
+    pub telemetry_interval: u64,
+
+    /// Maximum gRPC message size in bytes. Larger static/full metrics messages
+    /// are split into chunks; the server may advertise a lower limit.
+    #[serde(default = "default_max_message_size")]
+    pub max_message_size: usize,
+
+    /// Messages queued per server while it is slow. When full, the oldest
+    /// realtime samples are dropped; static and periodic data never are.
+    #[serde(default = "default_send_queue_size")]
+    pub send_queue_size: usize,
+
+    /// Open a second stream per server for commands and their results, so
+    /// large results don't delay metrics. Servers without it keep commands
+    /// on the metrics stream.
+    #[serde(default)]
+    pub command_stream: bool,
+
+    /// Timestamp clock: "system" (raw wall clock) or "monotonic" (never goes backwards)
+    #[serde(default)]
+    pub clock: ClockMode,
+
+    /// Reconnect delay in seconds
+    #[serde(default = "default_reconnect_delay")]
+    pub reconnect_delay: u64,
+
+    /// Maximum reconnect delay in seconds
+    #[serde(default = "default_max_reconnect_delay")]
+    pub max_reconnect_delay: u64,
+
+    /// Seconds the primary server must be unreachable before the standby
+    /// server takes over the metrics stream
+    #[serde(default = "default_failover_delay")]
+    pub failover_delay: u64,
+
+    /// Preferred language (en/zh). If not set, auto-detect from system locale.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+
+    /// Record the metrics stream to the first server in this file, for
+    /// `nanolink-agent replay`. The file is replaced on every start.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record_stream: Option<PathBuf>,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            agent_id: None,
+            hostname: None,
+            heartbeat_interval: default_heartbeat_interval(),
+            telemetry_interval: default_telemetry_interval(),
+            max_message_size: default_max_message_size(),
+            send_queue_size: default_send_queue_size(),
+            command_stream: false,
+            clock: ClockMode::default(),
+            reconnect_delay: default_reconnect_delay(),
+            max_reconnect_delay: default_max_reconnect_delay(),
+            failover_delay: default_failover_delay(),
+            language: None,
+            record_stream: None,
+        }
+    }
+}
+
+pub use nanolink_core::{
+    BandwidthCap, DEFAULT_GRPC_PORT, ReportingWindow, ServerConfig, ServerRole,
+};
+
+// Protocol enum removed - gRPC only
+// WebSocket support has been removed from Agent
+// Server-side WebSocket is still available for Dashboard communication
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectorConfig {
+    // ========== Realtime data (sent every interval) ==========
+    /// Realtime metrics collection interval in milliseconds (CPU/memory/IO)
+    #[serde(default = "default_realtime_interval")]
+    pub realtime_interval_ms: u64,
+
+    // ========== Periodic data (sent less frequently) ==========
+    /// Disk usage collection interval in milliseconds
+    #[serde(default = "default_disk_usage_interval")]
+    pub disk_usage_interval_ms: u64,
+
+    /// User sessions collection interval in milliseconds
+    #[serde(default = "default_session_interval")]
+    pub session_interval_ms: u64,
+
+    /// IP address check interval in milliseconds
+    #[serde(default = "default_ip_check_interval")]
+    pub ip_check_interval_ms: u64,
+
+    /// Disk health (S.M.A.R.T) check interval in milliseconds
+    #[serde(default = "default_health_check_interval")]
+    pub health_check_interval_ms: u64,
+
+    /// Fan and chassis sensor collection interval in milliseconds
+    #[serde(default = "default_sensor_interval")]
+    pub sensor_interval_ms: u64,
+
+    /// Routing table and gateway reachability interval in milliseconds
+    #[serde(default = "default_route_interval")]
+    pub route_interval_ms: u64,
+
+    /// Failed login summary interval in milliseconds
+    #[serde(default = "default_failed_login_interval")]
+    pub failed_login_interval_ms: u64,
+
+    /// Per-process network bandwidth interval in milliseconds
+    #[serde(default = "default_process_network_interval")]
+    pub process_network_interval_ms: u64,
+
+    /// Public IP detection interval in milliseconds
+    #[serde(default = "default_public_ip_interval")]
+    pub public_ip_interval_ms: u64,
+
+    /// Textfile directory scan interval in milliseconds
+    #[serde(default = "default_textfile_interval")]
+    pub textfile_interval_ms: u64,
+
+    // ========== Legacy intervals (for backwards compatibility) ==========
+    /// CPU/Memory collection interval in milliseconds
+    #[serde(default = "default_cpu_interval")]
+    pub cpu_interval_ms: u64,
+
+    /// Disk I/O collection interval in milliseconds
+    #[serde(default = "default_disk_interval")]
+    pub disk_interval_ms: u64,
+
+    /// Network collection interval in milliseconds
+    #[serde(default = "default_network_interval")]
+    pub network_interval_ms: u64,
+
+    /// Process list collection interval in milliseconds
+    #[serde(default = "default_process_interval")]
+    pub process_interval_ms: u64,
+
+    /// Disk space collection interval in milliseconds
+    #[serde(default = "default_disk_space_interval")]
+    pub disk_space_interval_ms: u64,
+
+    // ========== Feature flags ==========
+    /// Enable disk I/O metrics
+    #[serde(default = "default_true")]
+    pub enable_disk_io: bool,
+
+    /// Enable network metrics
+    #[serde(default = "default_true")]
+    pub enable_network: bool,
+
+    /// Enable per-core CPU metrics
+    #[serde(default = "default_true")]
+    pub enable_per_core_cpu: bool,
+
+    /// Enable layered metrics (realtime/periodic/static separation)
+    #[serde(default = "default_true")]
+    pub enable_layered_metrics: bool,
+
+    /// Enable per-process network bandwidth accounting (maps every socket
+    /// to its owning process, so it is off by default)
+    #[serde(default)]
+    pub enable_process_network: bool,
+
+    /// Detect the public IP address through `public_ip_endpoints` (contacts
+    /// third-party services, so it is off by default)
+    #[serde(default)]
+    pub enable_public_ip: bool,
+
+    /// Public IP endpoints, tried in order: `stun:<host>:<port>` or an
+    /// HTTP(S) URL returning the address as plain text
+    #[serde(default = "default_public_ip_endpoints")]
+    pub public_ip_endpoints: Vec<String>,
+
+    /// Fetch instance ID, type, region and account from the cloud provider's
+    /// metadata service when running on AWS, GCP or Azure
+    #[serde(default)]
+    pub enable_cloud_metadata: bool,
+
+    /// Report attached monitors, keyboards, mice and cameras in static info,
+    /// refreshed on hotplug
+    #[serde(default)]
+    pub enable_peripherals: bool,
+
+    /// Send full metrics on initial connection
+    #[serde(default = "default_true")]
+    pub send_initial_full: bool,
+
+    /// Program run on every login/logout as
+    /// `<hook> <login|logout> <user> <remote_host> <session_type> <tty>`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_event_hook: Option<String>,
+
+    /// Directory of `*.prom` files (Prometheus text format) whose samples
+    /// are forwarded, like node_exporter's textfile collector
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub textfile_dir: Option<String>,
+
+    /// Collectors to disable by name (e.g. ["gpu", "npu", "sessions"])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disabled_collectors: Vec<String>,
+
+    // ========== Idle mode (when not connected to any server) ==========
+    /// Metrics collection interval when not connected to any server (milliseconds)
+    /// This reduces CPU usage when idle. Default: 30 seconds
+    #[serde(default = "default_idle_interval")]
+    pub idle_interval_ms: u64,
+
+    /// Sandboxed WASM collector plugins (requires the `wasm` feature)
+    #[serde(default)]
+    pub wasm: WasmPluginConfig,
+
+    /// Scripted metrics and alert rules (requires the `rules` feature)
+    #[serde(default)]
+    pub rules: RulesConfig,
+
+    /// Energy consumption and cost estimation
+    #[serde(default)]
+    pub energy: EnergyConfig,
+
+    /// Per-cgroup (slice/service) resource usage
+    #[serde(default)]
+    pub cgroups: CgroupConfig,
+
+    /// Print queue length, stuck jobs and printer errors
+    #[serde(default)]
+    pub printers: PrinterConfig,
+
+    /// Processes using the camera or microphone
+    #[serde(default)]
+    pub capture_devices: CaptureDeviceConfig,
+
+    /// SSH authorized_keys and known_hosts audit
+    #[serde(default)]
+    pub ssh_keys: SshKeyConfig,
+
+    /// Loaded kernel modules/drivers, their signatures and kernel taint
+    #[serde(default)]
+    pub kernel_modules: KernelModuleConfig,
+
+    /// Local anomaly detection on CPU, memory, disk and network
+    #[serde(default)]
+    pub anomaly: AnomalyConfig,
+
+    /// Days-until-full estimates from the disk usage trend
+    #[serde(default)]
+    pub disk_forecast: DiskForecastConfig,
+
+    /// Fastest-growing directories
+    #[serde(default)]
+    pub dir_growth: DirGrowthConfig,
+}
+
+impl Default for CollectorConfig {
+    fn default() -> Self {
+        Self {
+            realtime_interval_ms: default_realtime_interval(),
+            disk_usage_interval_ms: default_disk_usage_interval(),
+            session_interval_ms: default_session_interval(),
+            ip_check_interval_ms: default_ip_check_interval(),
+            health_check_interval_ms: default_health_check_interval(),
+            sensor_interval_ms: default_sensor_interval(),
+            route_interval_ms: default_route_interval(),
+            failed_login_interval_ms: default_failed_login_interval(),
+            process_network_interval_ms: default_process_network_interval(),
+            public_ip_interval_ms: default_public_ip_interval(),
+            textfile_interval_ms: default_textfile_interval(),
+            cpu_interval_ms: default_cpu_interval(),
+            disk_interval_ms: default_disk_interval(),
+            network_interval_ms: default_network_interval(),
+            process_interval_ms: default_process_interval(),
+            disk_space_interval_ms: default_disk_space_interval(),
+            enable_disk_io: true,
+            enable_network: true,
+            enable_per_core_cpu: true,
+            enable_layered_metrics: true,
+            enable_process_network: false,
+            enable_public_ip: false,
+            public_ip_endpoints: default_public_ip_endpoints(),
+            enable_cloud_metadata: false,
+            enable_peripherals: false,
+            send_initial_full: true,
+            session_event_hook: None,
+            textfile_dir: None,
+            disabled_collectors: Vec::new(),
+            idle_interval_ms: default_idle_interval(),
+            wasm: WasmPluginConfig::default(),
+            rules: RulesConfig::default(),
+            energy: EnergyConfig::default(),
+            cgroups: CgroupConfig::default(),
+            printers: PrinterConfig::default(),
+            capture_devices: CaptureDeviceConfig::default(),
+            ssh_keys: SshKeyConfig::default(),
+            kernel_modules: KernelModuleConfig::default(),
+            anomaly: AnomalyConfig::default(),
+            disk_forecast: DiskForecastConfig::default(),
+            dir_growth: DirGrowthConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WasmPluginConfig {
+    /// Load `*.wasm` plugins from `dir`
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Plugin directory, rescanned on every run so plugins can be added,
+    /// replaced or removed without a restart
+    #[serde(default = "default_wasm_dir")]
+    pub dir: String,
+
+    /// How often plugins run, in milliseconds
+    #[serde(default = "default_wasm_interval")]
+    pub interval_ms: u64,
+
+    /// Instruction budget per run; a plugin that exceeds it is aborted
+    #[serde(default = "default_wasm_fuel")]
+    pub fuel: u64,
+
+    /// Linear memory limit per plugin in MB
+    #[serde(default = "default_wasm_max_memory")]
+    pub max_memory_mb: u64,
+}
+
+impl Default for WasmPluginConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: default_wasm_dir(),
+            interval_ms: default_wasm_interval(),
+            fuel: default_wasm_fuel(),
+            max_memory_mb: default_wasm_max_memory(),
+        }
+    }
+}
+
+fn default_wasm_dir() -> String {
+    #[cfg(unix)]
+    return "/etc/nanolink/plugins".to_string();
+    #[cfg(windows)]
+    return "C:\\ProgramData\\nanolink\\plugins".to_string();
+}
+
+fn default_wasm_interval() -> u64 {
+    60000
+}
+
+fn default_wasm_fuel() -> u64 {
+    100_000_000
+}
+
+fn default_wasm_max_memory() -> u64 {
+    64
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CgroupConfig {
+    /// Report cgroup v2 usage (Linux)
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Collection interval in milliseconds
+    #[serde(default = "default_cgroup_interval")]
+    pub interval_ms: u64,
+
+    /// Levels below the cgroup root to report; 2 covers
+    /// `system.slice/<name>.service`
+    #[serde(default = "default_cgroup_depth")]
+    pub depth: u32,
+
+    /// Only report cgroups whose path matches one of these globs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+
+    /// Skip cgroups whose path matches one of these globs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+}
+
+impl Default for CgroupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: default_cgroup_interval(),
+            depth: default_cgroup_depth(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+fn default_cgroup_interval() -> u64 {
+    30000
+}
+
+fn default_cgroup_depth() -> u32 {
+    2
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrinterConfig {
+    /// Report CUPS/Windows print queues
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Collection interval in milliseconds
+    #[serde(default = "default_printer_interval")]
+    pub interval_ms: u64,
+
+    /// Jobs queued for longer than this count as stuck
+    #[serde(default = "default_printer_stuck_after")]
+    pub stuck_after_minutes: u64,
+}
+
+impl Default for PrinterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: default_printer_interval(),
+            stuck_after_minutes: default_printer_stuck_after(),
+        }
+    }
+}
+
+fn default_printer_interval() -> u64 {
+    60000
+}
+
+fn default_printer_stuck_after() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureDeviceConfig {
+    /// Report processes that start or stop using a camera or microphone
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Check interval in milliseconds
+    #[serde(default = "default_capture_interval")]
+    pub interval_ms: u64,
+
+    /// Process names whose use is expected (e.g. the conferencing client);
+    /// they are reported at info instead of warning level
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expected_processes: Vec<String>,
+}
+
+impl Default for CaptureDeviceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: default_capture_interval(),
+            expected_processes: Vec::new(),
+        }
+    }
+}
+
+fn default_capture_interval() -> u64 {
+    5000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SshKeyConfig {
+    /// List authorized SSH keys and report keys added since the last scan
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Scan interval in milliseconds
+    #[serde(default = "default_ssh_key_interval")]
+    pub interval_ms: u64,
+
+    /// Also list known_hosts entries (no events)
+    #[serde(default = "default_true")]
+    pub known_hosts: bool,
+}
+
+impl Default for SshKeyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: default_ssh_key_interval(),
+            known_hosts: true,
+        }
+    }
+}
+
+fn default_ssh_key_interval() -> u64 {
+    300_000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KernelModuleConfig {
+    /// List loaded kernel modules (Linux) or drivers (Windows)
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Scan interval in milliseconds; checking every driver's signature on
+    /// Windows takes a while
+    #[serde(default = "default_kernel_module_interval")]
+    pub interval_ms: u64,
+}
+
+impl Default for KernelModuleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: default_kernel_module_interval(),
+        }
+    }
+}
+
+fn default_kernel_module_interval() -> u64 {
+    600_000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnomalyConfig {
+    /// Compare key metrics against their learned baseline
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Sampling interval in milliseconds
+    #[serde(default = "default_anomaly_interval")]
+    pub interval_ms: u64,
+
+    /// EWMA smoothing factor; smaller values learn more slowly
+    #[serde(default = "default_anomaly_alpha")]
+    pub alpha: f64,
+
+    /// Standard deviations from the baseline that count as unusual
+    #[serde(default = "default_anomaly_threshold")]
+    pub threshold: f64,
+
+    /// Samples learned before a baseline is used
+    #[serde(default = "default_anomaly_warmup")]
+    pub warmup_samples: u64,
+
+    /// Consecutive unusual samples before an anomaly is reported
+    #[serde(default = "default_anomaly_consecutive")]
+    pub consecutive: u32,
+
+    /// Keep a separate baseline per hour of the day
+    #[serde(default)]
+    pub seasonal: bool,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: default_anomaly_interval(),
+            alpha: default_anomaly_alpha(),
+            threshold: default_anomaly_threshold(),
+            warmup_samples: default_anomaly_warmup(),
+            consecutive: default_anomaly_consecutive(),
+            seasonal: false,
+        }
+    }
+}
+
+fn default_anomaly_interval() -> u64 {
+    10000
+}
+
+fn default_anomaly_alpha() -> f64 {
+    0.05
+}
+
+fn default_anomaly_threshold() -> f64 {
+    4.0
+}
+
+fn default_anomaly_warmup() -> u64 {
+    30
+}
+
+fn default_anomaly_consecutive() -> u32 {
+    3
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskForecastConfig {
+    /// Keep a disk usage history and estimate when filesystems fill up
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Hours of history the trend is fitted to
+    #[serde(default = "default_forecast_window")]
+    pub window_hours: u64,
+
+    /// Samples needed before an estimate is reported
+    #[serde(default = "default_forecast_min_samples")]
+    pub min_samples: usize,
+
+    /// Publish a warning event when a filesystem is expected to be full
+    /// within this many days
+    #[serde(default = "default_forecast_alert_days")]
+    pub alert_days: f64,
+}
+
+impl Default for DiskForecastConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            window_hours: default_forecast_window(),
+            min_samples: default_forecast_min_samples(),
+            alert_days: default_forecast_alert_days(),
+        }
+    }
+}
+
+fn default_forecast_window() -> u64 {
+    72
+}
+
+fn default_forecast_min_samples() -> usize {
+    12
+}
+
+fn default_forecast_alert_days() -> f64 {
+    7.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirGrowthConfig {
+    /// Track directory sizes
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Directories to size
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+
+    /// Subdirectory levels below each path reported on their own
+    #[serde(default = "default_dir_growth_depth")]
+    pub depth: u32,
+
+    /// Hours between snapshots
+    #[serde(default = "default_dir_growth_interval")]
+    pub snapshot_interval_hours: u64,
+
+    /// Fastest-growing directories reported
+    #[serde(default = "default_dir_growth_top_n")]
+    pub top_n: usize,
+
+    /// Entries visited per snapshot before the walk gives up
+    #[serde(default = "default_dir_growth_max_files")]
+    pub max_files: u64,
+}
+
+impl Default for DirGrowthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            paths: Vec::new(),
+            depth: default_dir_growth_depth(),
+            snapshot_interval_hours: default_dir_growth_interval(),
+            top_n: default_dir_growth_top_n(),
+            max_files: default_dir_growth_max_files(),
+        }
+    }
+}
+
+fn default_dir_growth_depth() -> u32 {
+    2
+}
+
+fn default_dir_growth_interval() -> u64 {
+    24
+}
+
+fn default_dir_growth_top_n() -> usize {
+    10
+}
+
+fn default_dir_growth_max_files() -> u64 {
+    1_000_000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnergyConfig {
+    /// Report energy and cost metrics
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Reporting interval in milliseconds
+    #[serde(default = "default_energy_interval")]
+    pub interval_ms: u64,
+
+    /// Electricity price per kWh
+    #[serde(default)]
+    pub price_per_kwh: f64,
+
+    /// Currency label for cost metrics
+    #[serde(default = "default_currency")]
+    pub currency: String,
+
+    /// Host power at idle in watts, used when RAPL counters are unavailable
+    #[serde(default)]
+    pub idle_watts: f64,
+
+    /// Host power at full CPU load in watts; 0 disables the estimate
+    #[serde(default)]
+    pub max_watts: f64,
+}
+
+impl Default for EnergyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: default_energy_interval(),
+            price_per_kwh: 0.0,
+            currency: default_currency(),
+            idle_watts: 0.0,
+            max_watts: 0.0,
+        }
+    }
+}
+
+fn default_energy_interval() -> u64 {
+    60000
+}
+
+fn default_currency() -> String {
+    "USD".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RulesConfig {
+    /// How often rules are evaluated, in milliseconds
+    #[serde(default = "default_rules_interval")]
+    pub interval_ms: u64,
+
+    /// Operation limit per script run; longer scripts are aborted
+    #[serde(default = "default_rules_max_operations")]
+    pub max_operations: u64,
+
+    /// Derived metrics, sent as custom metrics
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub metrics: Vec<ScriptMetric>,
+
+    /// Alert conditions, reported as log events when they fire or resolve
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<ScriptAlert>,
+}
+
+impl Default for RulesConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: default_rules_interval(),
+            max_operations: default_rules_max_operations(),
+            metrics: Vec::new(),
+            alerts: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptMetric {
+    pub name: String,
+    /// Rhai expression evaluating to a number
+    pub script: String,
+    #[serde(default)]
+    pub unit: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptAlert {
+    pub name: String,
+    /// Rhai expression evaluating to true while the alert should fire
+    pub condition: String,
+    #[serde(default = "default_alert_severity")]
+    pub severity: String,
+    #[serde(default)]
+    pub message: String,
+}
+
+fn default_rules_interval() -> u64 {
+    10000
+}
+
+fn default_rules_max_operations() -> u64 {
+    100_000
+}
+
+fn default_alert_severity() -> String {
+    "warning".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BufferConfig {
+    /// Ring buffer capacity (number of metrics to cache)
+    /// Default: 720 (1 hour at 5-second interval)
+    #[serde(default = "default_buffer_capacity")]
+    pub capacity: usize,
+
+    /// Optional memory budget in bytes (approximate serialized size).
+    /// When set, the oldest entries are evicted once the budget is exceeded,
+    /// in addition to the entry capacity. Default: unlimited
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<usize>,
+
+    /// Enable data compensation (resend buffered data after reconnection)
+    /// Default: false
+    #[serde(default)]
+    pub data_compensation: bool,
+
+    /// Maximum number of metrics to send in one compensation batch
+    /// Default: 100
+    #[serde(default = "default_compensation_batch_size")]
+    pub compensation_batch_size: usize,
+}
+
+fn default_compensation_batch_size() -> usize {
+    100
+}
+
+impl Default for BufferConfig {
+    fn default() -> Self {
+        Self {
+            capacity: default_buffer_capacity(),
+            max_bytes: None,
+            data_compensation: false,
+            compensation_batch_size: default_compensation_batch_size(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShellConfig {
+    /// Enable shell command execution
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Super token for shell command authentication
+    pub super_token: Option<String>,
+
+    /// Command execution timeout in seconds
+    #[serde(default = "default_shell_timeout")]
+    pub timeout_seconds: u64,
+
+    /// Whitelisted command patterns
+    #[serde(default)]
+    pub whitelist: Vec<CommandPattern>,
+
+    /// Blacklisted command patterns (always blocked)
+    #[serde(default)]
+    pub blacklist: Vec<String>,
+
+    /// Commands requiring confirmation
+    #[serde(default)]
+    pub require_confirmation: Vec<CommandPattern>,
+
+    /// Run shell commands in a sandbox
+    #[serde(default, skip_serializing_if = "SandboxConfig::is_disabled")]
+    pub sandbox: SandboxConfig,
+}
+
+impl Default for ShellConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            super_token: None,
+            timeout_seconds: default_shell_timeout(),
+            whitelist: Vec::new(),
+            blacklist: default_blacklist(),
+            require_confirmation: Vec::new(),
+            sandbox: SandboxConfig::default(),
+        }
+    }
+}
+
+/// Confinement of shell, script and package manager subprocesses
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct SandboxConfig {
+    /// Run as this low-privilege user (Unix; the agent must run as root)
+    #[serde(default)]
+    pub user: Option<String>,
+
+    /// Run inside a transient systemd scope carrying the limits below
+    /// (Linux). On Windows the limits are applied through a Job Object.
+    #[serde(default)]
+    pub systemd_scope: bool,
+
+    /// CPU limit in percent of one CPU (200 = two CPUs)
+    #[serde(default)]
+    pub cpu_quota_percent: Option<u32>,
+
+    /// Memory limit in MB for the command and everything it starts
+    #[serde(default)]
+    pub memory_max_mb: Option<u64>,
+
+    /// Maximum number of processes/threads
+    #[serde(default)]
+    pub max_tasks: Option<u32>,
+}
+
+impl SandboxConfig {
+    pub fn is_disabled(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether any resource limit is set
+    #[cfg_attr(not(feature = "executors"), allow(dead_code))]
+    pub fn has_limits(&self) -> bool {
+        self.cpu_quota_percent.is_some() || self.memory_max_mb.is_some() || self.max_tasks.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandPattern {
+    /// Command pattern (supports * wildcard)
+    pub pattern: String,
+
+    /// Description of what this command does
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// Log level
+    #[serde(default = "default_log_level")]
+    pub level: String,
+
+    /// Log file path (if not set, logs to stdout)
+    pub file: Option<String>,
+
+    /// Enable audit logging for commands
+    #[serde(default = "default_true")]
+    pub audit_enabled: bool,
+
+    /// Audit log file path
+    #[serde(default = "default_audit_file")]
+    pub audit_file: String,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: default_log_level(),
+            file: None,
+            audit_enabled: true,
+            audit_file: default_audit_file(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecurityConfig {
+    /// Allowed paths for file operations (empty = all paths allowed)
+    /// Paths are checked after canonicalization
+    #[serde(default)]
+    pub allowed_paths: Vec<String>,
+
+    /// Denied paths (always blocked, checked before allowed_paths)
+    /// Default includes sensitive system directories
+    #[serde(default = "default_denied_paths")]
+    pub denied_paths: Vec<String>,
+
+    /// Enable path traversal protection (detect and block '..' in paths)
+    #[serde(default = "default_true")]
+    pub path_traversal_protection: bool,
+
+    /// Maximum file size for download/upload operations (in bytes)
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: u64,
+
+    /// Restricted crypto mode for compliance deployments: rustls with
+    /// FIPS-approved cipher suites and key exchange groups only, and plaintext
+    /// server / management API connections are rejected.
+    /// Always on when built with the `fips` feature.
+    #[serde(default)]
+    pub fips_mode: bool,
+
+    /// Encryption of data the agent persists to disk
+    #[serde(default)]
+    pub at_rest: AtRestConfig,
+
+    /// Kernel-level hardening of the agent process (Linux)
+    #[serde(default)]
+    pub hardening: HardeningConfig,
+}
+
+/// AES-256-GCM encryption of persisted state. The key (32 bytes, base64) is
+/// taken from `key_file`, then the OS keychain, then `key`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct AtRestConfig {
+    /// Encrypt session recordings and the maintenance queue
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Inline key; prefer `key_file` or the keychain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+
+    /// File holding the key, e.g. a systemd credential
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_file: Option<String>,
+
+    /// Read the key from the OS keychain (service "nanolink", account
+    /// "at-rest"): macOS Keychain or the Secret Service via `secret-tool`;
+    /// not available on Windows
+    #[serde(default)]
+    pub keychain: bool,
+}
+
+/// Seccomp filter for the agent process. The filter denies syscalls the
+/// agent never needs (kernel modules, kexec, BPF, ...) and is inherited by
+/// every command the agent runs.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct HardeningConfig {
+    /// Apply the seccomp filter at startup (Linux only)
+    #[serde(default)]
+    pub seccomp: bool,
+
+    /// What happens on a denied syscall
+    #[serde(default)]
+    pub seccomp_action: SeccompAction,
+}
+
+/// Outcome of a syscall denied by the seccomp filter
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SeccompAction {
+    /// Fail the syscall with EPERM
+    #[default]
+    Errno,
+    /// Allow it but log it to the kernel audit log (for trying the filter out)
+    Log,
+    /// Kill the process
+    Kill,
+}
+
+impl SecurityConfig {
+    /// Whether restricted crypto mode is active (config flag or `fips` build)
+    pub fn fips_enabled(&self) -> bool {
+        self.fips_mode || cfg!(feature = "fips")
+    }
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            allowed_paths: Vec::new(),
+            denied_paths: default_denied_paths(),
+            path_traversal_protection: true,
+            max_file_size: default_max_file_size(),
+            fips_mode: false,
+            at_rest: AtRestConfig::default(),
+            hardening: HardeningConfig::default(),
+        }
+    }
+}
+
+fn default_denied_paths() -> Vec<String> {
+    vec![
+        "/etc/shadow".to_string(),
+        "/etc/passwd".to_string(),
+        "/etc/sudoers".to_string(),
+        "/root/.ssh".to_string(),
+        "/home/*/.ssh".to_string(),
+        "/etc/ssh".to_string(),
+        "C:\\Windows\\System32\\config".to_string(),
+    ]
+}
+
+fn default_max_file_size() -> u64 {
+    50 * 1024 * 1024 // 50MB
+}
+
+// Default value functions
+fn default_heartbeat_interval() -> u64 {
+    30
+}
+fn default_telemetry_interval() -> u64 {
+    60
+}
+fn default_max_message_size() -> usize {
+    nanolink_client::chunking::DEFAULT_MAX_MESSAGE_SIZE
+}
+fn default_send_queue_size() -> usize {
+    100
+}
+fn default_reconnect_delay() -> u64 {
+    5
+}
+fn default_max_reconnect_delay() -> u64 {
+    300
+}
+fn default_failover_delay() -> u64 {
+    30
+}
+fn default_cpu_interval() -> u64 {
+    1000
+}
+fn default_disk_interval() -> u64 {
+    3000
+}
+fn default_network_interval() -> u64 {
+    1000
+}
+fn default_process_interval() -> u64 {
+    5000
+}
+fn default_disk_space_interval() -> u64 {
+    30000
+}
+fn default_realtime_interval() -> u64 {
+    5000 // 5 seconds for realtime metrics (balance between responsiveness and resource usage)
+}
+fn default_disk_usage_interval() -> u64 {
+    30000 // 30 seconds for disk usage
+}
+fn default_session_interval() -> u64 {
+    60000 // 1 minute for user sessions
+}
+fn default_ip_check_interval() -> u64 {
+    60000 // 1 minute for IP address changes
+}
+fn default_health_check_interval() -> u64 {
+    300000 // 5 minutes for S.M.A.R.T health
+}
+fn default_sensor_interval() -> u64 {
+    60000 // 1 minute for fans and chassis sensors
+}
+fn default_route_interval() -> u64 {
+    300000 // 5 minutes for routes and gateway reachability
+}
+fn default_failed_login_interval() -> u64 {
+    60000 // 1 minute for failed login summaries
+}
+fn default_process_network_interval() -> u64 {
+    30000 // 30 seconds for per-process bandwidth
+}
+fn default_public_ip_interval() -> u64 {
+    600000 // 10 minutes for public IP detection
+}
+fn default_public_ip_endpoints() -> Vec<String> {
+    vec![
+        "stun:stun.l.google.com:19302".to_string(),
+        "stun:stun.cloudflare.com:3478".to_string(),
+        "https://api.ipify.org".to_string(),
+    ]
+}
+fn default_textfile_interval() -> u64 {
+    60000
+}
+fn default_idle_interval() -> u64 {
+    30000 // 30 seconds when not connected to any server (reduces CPU usage)
+}
+fn default_buffer_capacity() -> usize {
+    720 // 1 hour at 5-second interval
+}
+fn default_shell_timeout() -> u64 {
+    30
+}
+fn default_true() -> bool {
+    true
+}
+fn default_log_level() -> String {
+    "info".to_string()
+}
+fn default_audit_file() -> String {
+    "nanolink-audit.log".to_string()
+}
+
+fn default_blacklist() -> Vec<String> {
+    vec![
+        // 破坏性命令
+        "rm -rf".to_string(),
+        "mkfs".to_string(),
+        "> /dev".to_string(),
+        "dd if=".to_string(),
+        ":(){:|:&};:".to_string(), // fork bomb
+        "shred".to_string(),
+        "wipefs".to_string(),
+        // 敏感文件操作
+        "> /etc/passwd".to_string(),
+        "> /etc/shadow".to_string(),
+        "chmod 777 /".to_string(),
+        // 远程执行/后门
+        "curl |".to_string(),
+        "wget |".to_string(),
+        "| sh".to_string(),
+        "| bash".to_string(),
+        "/dev/tcp/".to_string(),
+        "nc -e".to_string(),
+        "nc -l".to_string(),
+        // 脚本执行
+        "python -c".to_string(),
+        "perl -e".to_string(),
+        "ruby -e".to_string(),
+        "base64 -d |".to_string(),
+        // 权限提升
+        "sudo ".to_string(),
+        " su ".to_string(),
+        " su\n".to_string(),
+    ]
+}
+
+impl Config {
+    /// Load configuration from file
+    pub fn load(path: &Path) -> Result<Self> {
+        let to_defaults = |mut includes: Value| {
+            // Servers are required, but may all come from the main file
+            if let Value::Mapping(map) = &mut includes {
+                map.entry("servers".into())
+                    .or_insert_with(|| Value::Sequence(Vec::new()));
+            }
+            Ok(serde_yaml::to_value(serde_yaml::from_value::<Config>(
+                includes,
+            )?)?)
+        };
+        let (mut merged, mut layers) = overlay::assemble(path, None, to_defaults)?;
+
+        // The cached central profile, if the local files enable one
+        let profile: ProfileConfig = match merged.get("profile") {
+            Some(value) => serde_yaml::from_value(value.clone())
+                .with_context(|| format!("Invalid profile section in {path:?}"))?,
+            None => ProfileConfig::default(),
+        };
+        if profile.enabled
+            && let Some(value) = crate::profile::cached(&profile)
+        {
+            let remote = overlay::Remote {
+                value,
+                path: crate::profile::cache_file(),
+                override_local: profile.override_local,
+            };
+            (merged, layers) = overlay::assemble(path, Some(remote), to_defaults)?;
+        }
+
+        let mut config: Config = serde_yaml::from_value(merged)
+            .with_context(|| format!("Invalid configuration in {path:?}"))?;
+        config.layers = layers.map(Arc::new);
+
+        // Migrate config if needed
+        if config.config_version < CONFIG_VERSION {
+            config = config.migrate()?;
+            // Optionally save migrated config
+            if let Err(e) = config.save(path) {
+                eprintln!("Warning: Failed to save migrated config: {e}");
+            }
+        }
+
+        // Generate persistent agent_id if not present
+        // This ensures the same agent ID is used across restarts for data continuity.
+        // Derived from the machine ID so it also survives reinstalls.
+        if config.agent.agent_id.is_none() {
+            let (agent_id, machine_derived) = crate::utils::machine_id::stable_agent_id();
+            eprintln!(
+                "Generated new agent_id: {} ({})",
+                agent_id,
+                if machine_derived {
+                    "derived from machine ID"
+                } else {
+                    "random, machine ID unavailable"
+                }
+            );
+            config.agent.agent_id = Some(agent_id);
+            // Save config with the new agent_id
+            if let Err(e) = config.save(path) {
+                eprintln!("Warning: Failed to save config with new agent_id: {e}");
+            }
+        }
+
+        // Auto-disable Management API if enabled but api_token is not set
+        // This ensures backward compatibility with old configs
+        if config.management.enabled && config.management.api_token.is_none() {
+            eprintln!(
+                "Warning: Management API was enabled but api_token is not set. \
+                 Disabling Management API for security. \
+                 To enable, add 'api_token: <your-token>' to the management section."
+            );
+            config.management.enabled = false;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Migrate config from older versions
+    fn migrate(mut self) -> Result<Self> {
+        let original_version = self.config_version;
+
+        // Migration from v1 to v2: Add update config section
+        if self.config_version == 1 {
+            eprintln!("Migrating config from v1 to v2...");
+            self.update = UpdateConfig::default();
+            self.config_version = 2;
+        }
+
+        // Add future migrations here:
+        // if self.config_version == 2 {
+        //     // migrate v2 -> v3
+        //     self.config_version = 3;
+        // }
+
+        if original_version != self.config_version {
+            eprintln!(
+                "Config migrated from v{} to v{}",
+                original_version, self.config_version
+            );
+        }
+
+        Ok(self)
+    }
+
+    /// Save configuration to file. A config assembled from several files
+    /// writes only what belongs in the main file.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let content = self.to_file_content(path)?;
+        std::fs::write(path, content)
+            .with_context(|| format!("Failed to write config file: {path:?}"))?;
+
+        Ok(())
+    }
+
+    /// Save like [`Config::save`], for a config holding secrets: the file is
+    /// only ever readable by its owner (on Unix), written to a temporary
+    /// file created with mode 0600 and renamed into place
+    pub fn save_private(&self, path: &Path) -> Result<()> {
+        use std::io::Write;
+
+        let content = self.to_file_content(path)?;
+        let tmp = path.with_extension("tmp");
+        // A leftover temp file would keep its permissions
+        match std::fs::remove_file(&tmp) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(e).with_context(|| format!("Failed to remove {tmp:?}"));
+            }
+            _ => {}
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(&tmp)
+            .with_context(|| format!("Failed to create {tmp:?}"))?;
+        file.write_all(content.as_bytes())
+            .and_then(|_| file.sync_all())
+            .with_context(|| format!("Failed to write {tmp:?}"))?;
+        std::fs::rename(&tmp, path)
+            .with_context(|| format!("Failed to write config file: {path:?}"))?;
+
+        Ok(())
+    }
+
+    /// What [`Config::save`] writes to `path` (YAML, or TOML by extension)
+    pub fn to_file_content(&self, path: &Path) -> Result<String> {
+        Ok(match &self.layers {
+            Some(layers) => {
+                let mut value = layers.main_file(serde_yaml::to_value(self)?)?;
+                if overlay::is_toml(path) {
+                    overlay::strip_nulls(&mut value);
+                    toml::to_string_pretty(&value)?
+                } else {
+                    serde_yaml::to_string(&value)?
+                }
+            }
+            None if overlay::is_toml(path) => toml::to_string_pretty(self)?,
+            None => serde_yaml::to_string(self)?,
+        })
+    }
+
+    /// Files the config was merged from, in order; empty for a single file
+    pub fn sources(&self) -> &[PathBuf] {
+        self.layers.as_ref().map_or(&[], |l| &l.files)
+    }
+
+    /// Generate a sample configuration
+    pub fn sample() -> Self {
+        Self {
+            config_version: CONFIG_VERSION,
+            include: Vec::new(),
+            layers: None,
+            agent: AgentConfig::default(),
+            update: UpdateConfig::default(),
+            servers: vec![ServerConfig {
+                host: "localhost".to_string(),
+                port: DEFAULT_GRPC_PORT,
+                token: "your_token_here".to_string(),
+                management_token: None,
+                permission: 0,
+                tls_enabled: false,
+                tls_verify: true,
+                tls_pin: None,
+                srv: None,
+                role: None,
+                bandwidth_cap: None,
+                reporting_schedule: None,
+            }],
+            collector: CollectorConfig::default(),
+            buffer: BufferConfig::default(),
+            shell: ShellConfig {
+                enabled: true,
+                super_token: Some("super_secret_token".to_string()),
+                timeout_seconds: 30,
+                whitelist: vec![
+                    CommandPattern {
+                        pattern: "df -h".to_string(),
+                        description: "Show disk space".to_string(),
+                    },
+                    CommandPattern {
+                        pattern: "free -m".to_string(),
+                        description: "Show memory usage".to_string(),
+                    },
+                    CommandPattern {
+                        pattern: "tail -n * /var/log/*.log".to_string(),
+                        description: "View log tail".to_string(),
+                    },
+                ],
+                blacklist: default_blacklist(),
+                require_confirmation: vec![
+                    CommandPattern {
+                        pattern: "reboot".to_string(),
+                        description: "Reboot system".to_string(),
+                    },
+                    CommandPattern {
+                        pattern: "shutdown".to_string(),
+                        description: "Shutdown system".to_string(),
+                    },
+                ],
+                sandbox: SandboxConfig::default(),
+            },
+            logging: LoggingConfig::default(),
+            management: ManagementConfig::default(),
+            security: SecurityConfig::default(),
+            scripts: ScriptsConfig::default(),
+            config_management: ConfigManagementConfig::default(),
+            package_management: PackageManagementConfig::default(),
+            power: PowerConfig::default(),
+            speedtest: SpeedTestConfig::default(),
+            cleanup: CleanupConfig::default(),
+            syslog: SyslogConfig::default(),
+            statsd: StatsdConfig::default(),
+            relay: RelayConfig::default(),
+            maintenance: MaintenanceConfig::default(),
+            recording: RecordingConfig::default(),
+            privacy: PrivacyConfig::default(),
+            notifications: NotificationsConfig::default(),
+            profile: ProfileConfig::default(),
+            privsep: PrivsepConfig::default(),
+        }
+    }
+
+    /// Validate configuration
+    fn validate(&self) -> Result<()> {
+        if self.servers.is_empty() {
+            anyhow::bail!("At least one server must be configured");
+        }
+
+        for (i, server) in self.servers.iter().enumerate() {
+            server
+                .validate()
+                .map_err(|e| anyhow::anyhow!("Server {i} {e}"))?;
+            for window in server.reporting_schedule.iter().flat_map(|s| &s.windows) {
+                window
+                    .schedule
+                    .parse::<crate::utils::cron::CronSchedule>()
+                    .map_err(|e| anyhow::anyhow!("Server {i} reporting_schedule: {e}"))?;
+            }
+        }
+        let count = |role| self.servers.iter().filter(|s| s.role == Some(role)).count();
+        if count(ServerRole::Primary) > 1 || count(ServerRole::Standby) > 1 {
+            anyhow::bail!("Only one primary and one standby server can be configured");
+        }
+        if count(ServerRole::Standby) == 1 && count(ServerRole::Primary) == 0 {
+            anyhow::bail!("A standby server needs a primary server");
+        }
+
+        if self.security.fips_enabled() {
+            // Unix sockets never leave the host
+            for (i, server) in self.servers.iter().enumerate() {
+                if !server.tls_enabled && server.unix_socket().is_none() {
+                    anyhow::bail!(
+                        "Server {i} ({}:{}) must use TLS when FIPS mode is enabled",
+                        server.host,
+                        server.port
+                    );
+                }
+            }
+            if self.management.enabled && !self.management.tls_enabled {
+                anyhow::bail!("Management API must use TLS when FIPS mode is enabled");
+            }
+            if self.relay.enabled && self.relay.tls_cert.is_none() {
+                anyhow::bail!("Relay must use TLS when FIPS mode is enabled");
+            }
+        }
+
+        if self.relay.enabled {
+            if self.relay.tokens.iter().all(|token| token.is_empty()) {
+                anyhow::bail!("Relay is enabled but no tokens are set");
+            }
+            if self.relay.tls_cert.is_some() != self.relay.tls_key.is_some() {
+                anyhow::bail!("Relay TLS needs both tls_cert and tls_key");
+            }
+        }
+
+        if self.shell.enabled && self.shell.super_token.is_none() {
+            anyhow::bail!("Shell is enabled but super_token is not set");
+        }
+
+        // P1-2: 检查危险的通配符配置
+        for pattern in &self.shell.whitelist {
+            if pattern.pattern == "*" {
+                tracing::warn!(
+                    "[SECURITY WARNING] Shell whitelist contains '*' pattern - this allows ALL commands!"
+                );
+                // 除非显式允许，否则拒绝通配符
+                if std::env::var("NANOLINK_ALLOW_WILDCARD").is_err() {
+                    anyhow::bail!(
+                        "Wildcard '*' in whitelist is not allowed for security reasons. \
+                        Set NANOLINK_ALLOW_WILDCARD=1 environment variable to override."
+                    );
+                }
+            }
+        }
+
+        // P1-3: 管理API启用时如果未设置token，则自动禁用并警告（向后兼容）
+        // 不再报错退出，以支持旧版本配置文件升级
+        // Note: We can't mutate self here, so we just warn. The actual disable happens in load()
+
+        Ok(())
+    }
+
+    /// Get effective hostname
+    pub fn get_hostname(&self) -> String {
+        self.agent.hostname.clone().unwrap_or_else(|| {
+            hostname::get()
+                .ok()
+                .and_then(|h| h.into_string().ok())
+                .unwrap_or_else(|| "unknown".to_string())
+        })
+    }
+}
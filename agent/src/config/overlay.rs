@@ -0,0 +1,476 @@
+//! Config includes and drop-in overlays
+//!
+//! A config is assembled from up to three layers, later ones winning:
+//!
+//! 1. files named by `include:` in the main file (paths or glob patterns,
+//!    relative to the main file), in the listed order, matches of one
+//!    pattern sorted by path: fleet-wide defaults,
+//! 2. the main file,
+//! 3. `*.yaml`, `*.yml` and `*.toml` files in the `conf.d` directory next to
+//!    the main file, sorted by file name: host-specific overrides.
+//!
+//! A central profile (see `crate::profile`) joins as one more layer, below
+//! the includes or, with `profile.override_local`, above the drop-ins.
+//!
+//! Mappings are merged key by key; scalars and lists (e.g. `servers`) are
+//! replaced as a whole. Included and drop-in files can't include further
+//! files.
+//!
+//! When the agent saves a layered config (new agent ID, `server add`,
+//! settings window), only the main file is written, and only with the keys
+//! it already had plus values that differ from the included defaults. Files
+//! in `conf.d` are never written. Since their values would override anything
+//! saved to the main file, a save that changes one of them (e.g. `server add`
+//! with `servers` in a drop-in) fails and names the file to edit instead.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use serde_yaml::{Mapping, Value};
+
+/// Drop-in directory next to the main file
+pub const CONF_D: &str = "conf.d";
+
+/// Key listing the files to include
+const INCLUDE_KEY: &str = "include";
+
+/// How a layered config was assembled, kept for saving
+#[derive(Debug, Clone)]
+pub struct Layers {
+    /// Serialized config built from the included files (and a base profile)
+    /// alone, defaults filled in: the values the main file need not repeat
+    defaults: Value,
+    /// The main file as read
+    main: Value,
+    /// Merged `conf.d` files and an overriding profile: values that come
+    /// from there, not the main file
+    drop_ins: Value,
+    /// Each of those, for naming the file that sets a value
+    owners: Vec<(PathBuf, Value)>,
+    /// The merged config as loaded, defaults filled in
+    loaded: Value,
+    /// Files merged, in order (main file included)
+    pub files: Vec<PathBuf>,
+}
+
+/// A layer that doesn't come from the local files
+pub struct Remote {
+    pub value: Value,
+    /// Where it is cached, listed among the merged files
+    pub path: PathBuf,
+    /// Merge it last instead of first
+    pub override_local: bool,
+}
+
+/// Read a YAML or TOML file (by extension) into a generic value
+pub fn read(path: &Path) -> Result<Value> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file: {path:?}"))?;
+    let value = if is_toml(path) {
+        toml::from_str(&content).with_context(|| format!("Invalid TOML in {path:?}"))?
+    } else {
+        serde_yaml::from_str(&content).with_context(|| format!("Invalid YAML in {path:?}"))?
+    };
+    // An empty file is an empty layer
+    Ok(match value {
+        Value::Null => Value::Mapping(Mapping::new()),
+        value => value,
+    })
+}
+
+pub fn is_toml(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "toml")
+}
+
+/// Merge the layers of the config at `path`. Returns the merged value, and
+/// the layers when there is more than the main file.
+///
+/// `to_defaults` turns merged layers into a full serialized config.
+pub fn assemble(
+    path: &Path,
+    remote: Option<Remote>,
+    to_defaults: impl Fn(Value) -> Result<Value>,
+) -> Result<(Value, Option<Layers>)> {
+    let mut main = read(path)?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let patterns = include_patterns(&main)?;
+    if let Value::Mapping(map) = &mut main
+        && !patterns.is_empty()
+    {
+        map.insert(
+            INCLUDE_KEY.into(),
+            patterns.iter().map(|p| Value::from(p.as_str())).collect(),
+        );
+    }
+
+    let mut includes = Value::Mapping(Mapping::new());
+    let mut files = Vec::new();
+    let (base, top) = match remote {
+        Some(remote) if remote.override_local => (None, Some(remote)),
+        remote => (remote, None),
+    };
+    if let Some(base) = base {
+        merge(&mut includes, base.value);
+        files.push(base.path);
+    }
+    for include in patterns {
+        for file in expand(dir, &include)? {
+            merge(&mut includes, read_layer(&file)?);
+            files.push(file);
+        }
+    }
+    files.push(path.to_path_buf());
+
+    let drop_ins = drop_ins(&dir.join(CONF_D))?;
+    if files.len() == 1 && drop_ins.is_empty() && top.is_none() {
+        return Ok((main, None));
+    }
+
+    let mut owners = Vec::new();
+    for file in drop_ins {
+        owners.push((file.clone(), read_layer(&file)?));
+        files.push(file);
+    }
+    if let Some(top) = top {
+        owners.push((top.path.clone(), top.value));
+        files.push(top.path);
+    }
+    let mut overrides = Value::Mapping(Mapping::new());
+    for (_, value) in &owners {
+        merge(&mut overrides, value.clone());
+    }
+    let mut merged = includes.clone();
+    merge(&mut merged, main.clone());
+    merge(&mut merged, overrides.clone());
+    let layers = Layers {
+        defaults: to_defaults(includes)?,
+        main,
+        drop_ins: overrides,
+        owners,
+        loaded: to_defaults(merged.clone())?,
+        files,
+    };
+    Ok((merged, Some(layers)))
+}
+
+/// `include:` of the main file: a list, or a single path
+fn include_patterns(main: &Value) -> Result<Vec<String>> {
+    match main.get(INCLUDE_KEY) {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::String(s)) => Ok(vec![s.clone()]),
+        Some(Value::Sequence(items)) => items
+            .iter()
+            .map(|item| match item {
+                Value::String(s) => Ok(s.clone()),
+                _ => bail!("include entries must be paths"),
+            })
+            .collect(),
+        Some(_) => bail!("include must be a path or a list of paths"),
+    }
+}
+
+/// Files matching an include. A plain path must exist; a pattern may match
+/// nothing.
+fn expand(dir: &Path, include: &str) -> Result<Vec<PathBuf>> {
+    let path = dir.join(include);
+    if !include.contains(['*', '?', '[']) {
+        if !path.is_file() {
+            bail!("Included config file not found: {path:?}");
+        }
+        return Ok(vec![path]);
+    }
+    let pattern = path.to_string_lossy();
+    let mut files: Vec<PathBuf> = glob::glob(&pattern)
+        .with_context(|| format!("Invalid include pattern: {include}"))?
+        .filter_map(|entry| entry.ok())
+        .filter(|p| p.is_file())
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Config files in `conf.d`, sorted by name
+fn drop_ins(dir: &Path) -> Result<Vec<PathBuf>> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.is_file()
+                && p.extension()
+                    .is_some_and(|e| e == "yaml" || e == "yml" || e == "toml")
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Read an included or drop-in file
+fn read_layer(path: &Path) -> Result<Value> {
+    let value = read(path)?;
+    if value.get(INCLUDE_KEY).is_some() {
+        bail!("{path:?}: include is only allowed in the main config file");
+    }
+    if !value.is_mapping() {
+        bail!("{path:?}: expected a mapping of config sections");
+    }
+    Ok(value)
+}
+
+/// Merge `overlay` into `base`: mappings key by key, anything else replaced
+pub fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+impl Layers {
+    /// What to write to the main file for the config serialized as `current`.
+    /// Fails if `current` changes a value a drop-in sets, as the drop-in
+    /// would override it again on the next load.
+    pub fn main_file(&self, current: Value) -> Result<Value> {
+        let layers = Layers3 {
+            defaults: Some(&self.defaults),
+            original: Some(&self.main),
+            drop_ins: Some(&self.drop_ins),
+            loaded: Some(&self.loaded),
+        };
+        let mut key = Vec::new();
+        let mut shadowed = Vec::new();
+        let value = diff(current, layers, &mut key, &mut shadowed)
+            .unwrap_or_else(|| Value::Mapping(Mapping::new()));
+        if !shadowed.is_empty() {
+            let keys: Vec<String> = shadowed
+                .iter()
+                .map(|key| match self.owner(key) {
+                    Some(file) => format!("{} (set in {})", dotted(key), file.display()),
+                    None => dotted(key),
+                })
+                .collect();
+            bail!(
+                "Not saving: {} would be overridden by a drop-in on the next load, change it there",
+                keys.join(", ")
+            );
+        }
+        Ok(value)
+    }
+
+    /// The last drop-in (or overriding profile) that sets `key`
+    fn owner(&self, key: &[Value]) -> Option<&Path> {
+        self.owners
+            .iter()
+            .rev()
+            .find(|(_, value)| {
+                key.iter()
+                    .try_fold(value, |value, k| value.get(k))
+                    .is_some()
+            })
+            .map(|(file, _)| file.as_path())
+    }
+}
+
+fn dotted(key: &[Value]) -> String {
+    key.iter()
+        .map(|k| k.as_str().map_or_else(|| format!("{k:?}"), str::to_string))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// The same position in each layer
+#[derive(Clone, Copy)]
+struct Layers3<'a> {
+    defaults: Option<&'a Value>,
+    original: Option<&'a Value>,
+    drop_ins: Option<&'a Value>,
+    loaded: Option<&'a Value>,
+}
+
+impl<'a> Layers3<'a> {
+    fn get(self, key: &Value) -> Self {
+        Self {
+            defaults: self.defaults.and_then(|v| v.get(key)),
+            original: self.original.and_then(|v| v.get(key)),
+            drop_ins: self.drop_ins.and_then(|v| v.get(key)),
+            loaded: self.loaded.and_then(|v| v.get(key)),
+        }
+    }
+}
+
+/// The parts of `current` to keep: keys present in the original main file,
+/// and values that neither the defaults nor the drop-ins account for. Where
+/// a drop-in sets a value, the main file keeps its own, and `key` is added
+/// to `shadowed` if `current` changed it since loading.
+fn diff(
+    current: Value,
+    layers: Layers3<'_>,
+    key: &mut Vec<Value>,
+    shadowed: &mut Vec<Vec<Value>>,
+) -> Option<Value> {
+    match current {
+        Value::Mapping(current) => {
+            let mut out = Mapping::new();
+            for (k, value) in current {
+                key.push(k.clone());
+                if let Some(value) = diff(value, layers.get(&k), key, shadowed) {
+                    out.insert(k, value);
+                }
+                key.pop();
+            }
+            (!out.is_empty() || layers.original.is_some()).then_some(Value::Mapping(out))
+        }
+        current if layers.drop_ins.is_some() => {
+            if layers.loaded != Some(&current) {
+                shadowed.push(key.clone());
+            }
+            layers.original.cloned()
+        }
+        current => {
+            (layers.original.is_some() || layers.defaults != Some(&current)).then_some(current)
+        }
+    }
+}
+
+/// Remove nulls, which TOML can't represent
+pub fn strip_nulls(value: &mut Value) {
+    match value {
+        Value::Mapping(map) => {
+            map.retain(|_, v| !v.is_null());
+            map.values_mut().for_each(strip_nulls);
+        }
+        Value::Sequence(items) => items.iter_mut().for_each(strip_nulls),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(s: &str) -> Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut base = yaml("agent: {hostname: a, heartbeat_interval: 30}\nservers: [{host: x}]");
+        merge(
+            &mut base,
+            yaml("agent: {hostname: b}\nservers: [{host: y}, {host: z}]"),
+        );
+        assert_eq!(
+            base,
+            yaml("agent: {hostname: b, heartbeat_interval: 30}\nservers: [{host: y}, {host: z}]")
+        );
+    }
+
+    #[test]
+    fn test_main_file_diff() {
+        let layers = Layers {
+            defaults: yaml(
+                "agent: {heartbeat_interval: 30, reconnect_delay: 5}\nlogging: {level: info}",
+            ),
+            main: yaml("include: fleet.yaml\nagent: {agent_id: abc}"),
+            drop_ins: yaml("logging: {level: debug}"),
+            owners: vec![(
+                PathBuf::from("conf.d/50-host.yaml"),
+                yaml("logging: {level: debug}"),
+            )],
+            loaded: yaml(
+                "include: fleet.yaml\nagent: {agent_id: abc, heartbeat_interval: 30, reconnect_delay: 5}\nlogging: {level: debug}",
+            ),
+            files: Vec::new(),
+        };
+        let current = yaml(
+            "include: fleet.yaml\nagent: {agent_id: abc, heartbeat_interval: 30, reconnect_delay: 10}\nlogging: {level: debug}",
+        );
+        assert_eq!(
+            layers.main_file(current).unwrap(),
+            yaml("include: fleet.yaml\nagent: {agent_id: abc, reconnect_delay: 10}")
+        );
+
+        // A change the drop-in would undo
+        let current = yaml(
+            "include: fleet.yaml\nagent: {agent_id: abc, heartbeat_interval: 30, reconnect_delay: 5}\nlogging: {level: warn}",
+        );
+        let err = layers.main_file(current).unwrap_err().to_string();
+        assert!(
+            err.contains("logging.level (set in conf.d/50-host.yaml)"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_assemble() {
+        let dir = std::env::temp_dir().join(format!("nanolink-overlay-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("fleet")).unwrap();
+        std::fs::create_dir_all(dir.join(CONF_D)).unwrap();
+        let write = |name: &str, content: &str| std::fs::write(dir.join(name), content).unwrap();
+        write(
+            "fleet/10-base.yaml",
+            "agent: {heartbeat_interval: 60}\nservers: [{host: fleet}]",
+        );
+        write("fleet/20-logging.yaml", "logging: {level: warn}");
+        write(
+            "nanolink.yaml",
+            "include: [\"fleet/*.yaml\"]\nagent: {heartbeat_interval: 30}\nlogging: {level: debug}",
+        );
+        write("conf.d/50-host.toml", "[[servers]]\nhost = \"local\"\n");
+        write("conf.d/notes.txt", "ignored");
+
+        let (merged, layers) = assemble(&dir.join("nanolink.yaml"), None, Ok).unwrap();
+        assert_eq!(merged["agent"]["heartbeat_interval"], yaml("30"));
+        assert_eq!(merged["logging"]["level"], yaml("debug"));
+        assert_eq!(merged["servers"], yaml("[{host: local}]"));
+        let files: Vec<_> = layers
+            .unwrap()
+            .files
+            .iter()
+            .map(|f| f.strip_prefix(&dir).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            files,
+            [
+                "fleet/10-base.yaml",
+                "fleet/20-logging.yaml",
+                "nanolink.yaml",
+                "conf.d/50-host.toml"
+            ]
+            .map(PathBuf::from)
+        );
+
+        // A profile fills in defaults, or wins over everything
+        let remote = |override_local| Remote {
+            value: yaml("logging: {level: error}\nstatsd: {enabled: true}"),
+            path: dir.join("profile.yaml"),
+            override_local,
+        };
+        let (merged, layers) =
+            assemble(&dir.join("nanolink.yaml"), Some(remote(false)), Ok).unwrap();
+        assert_eq!(merged["logging"]["level"], yaml("debug"));
+        assert_eq!(merged["statsd"]["enabled"], yaml("true"));
+        assert_eq!(layers.unwrap().files[0], dir.join("profile.yaml"));
+        let (merged, layers) =
+            assemble(&dir.join("nanolink.yaml"), Some(remote(true)), Ok).unwrap();
+        assert_eq!(merged["logging"]["level"], yaml("error"));
+        assert_eq!(layers.unwrap().files[4], dir.join("profile.yaml"));
+
+        write("nanolink.yaml", "include: missing.yaml");
+        assert!(assemble(&dir.join("nanolink.yaml"), None, Ok).is_err());
+        write("nanolink.yaml", "agent: {}");
+        write("conf.d/60-bad.yaml", "include: other.yaml");
+        assert!(assemble(&dir.join("nanolink.yaml"), None, Ok).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,336 @@
+//! Bandwidth accounting and caps per server
+//!
+//! Every message handed to a server stream is counted against that server's
+//! usage for the day and for the month. The counts are kept in a state file,
+//! so a restart doesn't start them over. With `servers[].bandwidth_cap` set,
+//! the stream stops sending realtime samples and backfill once
+//! `reduce_at_percent` of a cap is used, and the server still gets periodic
+//! data. Once a cap is reached only stream control, alerts and events, and
+//! command results are sent until the day or month rolls over. Usage and
+//! mode are reported in self-telemetry.
+//!
+//! Counted sizes are encoded message sizes; TLS and HTTP/2 framing come on
+//! top, so leave some headroom below the carrier's limit.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use chrono::{Datelike, Local, Months, NaiveDate};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use super::queue::Priority;
+use crate::config::{BandwidthCap, ServerConfig};
+use crate::proto::MetricsStreamRequest;
+use crate::telemetry::telemetry;
+
+/// Caps are in decimal megabytes, as carriers bill them
+const MB: u64 = 1_000_000;
+
+/// Usage is written to the state file at most this often
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Legacy streams send one full sample this often while reduced
+pub const REDUCED_SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What a server is sent, depending on how much of its caps is used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BandwidthMode {
+    Full,
+    /// Periodic data, but no realtime samples or backfill
+    Reduced,
+    /// Stream control, alerts and events, and command results only
+    Capped,
+}
+
+impl BandwidthMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::Reduced => "reduced",
+            Self::Capped => "capped",
+        }
+    }
+
+    /// Whether messages of `priority` are sent in this mode
+    pub fn allows(&self, priority: Priority) -> bool {
+        match self {
+            Self::Full => true,
+            Self::Reduced => priority < Priority::Realtime,
+            Self::Capped => priority < Priority::Periodic,
+        }
+    }
+
+    fn of(cap: &BandwidthCap, usage: &Usage) -> Self {
+        let share = |bytes: u64, cap_mb: Option<u64>| {
+            cap_mb.map_or(0.0, |mb| bytes as f64 / (mb.max(1) * MB) as f64)
+        };
+        let used =
+            share(usage.day_bytes, cap.daily_mb).max(share(usage.month_bytes, cap.monthly_mb));
+        if used >= 1.0 {
+            Self::Capped
+        } else if used * 100.0 >= f64::from(cap.reduce_at_percent) {
+            Self::Reduced
+        } else {
+            Self::Full
+        }
+    }
+}
+
+/// Bytes sent to one server in the current day and month
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub day: Option<NaiveDate>,
+    pub day_bytes: u64,
+    /// First day of the current monthly period
+    pub month: Option<NaiveDate>,
+    pub month_bytes: u64,
+}
+
+impl Usage {
+    /// Start the counts over when `today` is in a new day or month
+    fn roll(&mut self, today: NaiveDate, reset_day: u8) {
+        if self.day != Some(today) {
+            self.day = Some(today);
+            self.day_bytes = 0;
+        }
+        let month = period_start(today, reset_day);
+        if self.month != Some(month) {
+            self.month = Some(month);
+            self.month_bytes = 0;
+        }
+    }
+}
+
+/// First day of the monthly period that contains `today`
+fn period_start(today: NaiveDate, reset_day: u8) -> NaiveDate {
+    let reset_day = u32::from(reset_day.clamp(1, 28));
+    let this_month = today
+        .with_day(reset_day)
+        .expect("days 1-28 exist in every month");
+    if today.day() >= reset_day {
+        this_month
+    } else {
+        this_month
+            .checked_sub_months(Months::new(1))
+            .unwrap_or(this_month)
+    }
+}
+
+/// Usage of all servers, shared by every connection to the same address
+struct Ledger {
+    path: Option<PathBuf>,
+    state: Mutex<LedgerState>,
+}
+
+struct LedgerState {
+    servers: HashMap<String, Usage>,
+    saved: Instant,
+    dirty: bool,
+}
+
+impl Ledger {
+    fn new(path: Option<PathBuf>) -> Self {
+        let servers = path
+            .as_deref()
+            .and_then(|path| {
+                let json = std::fs::read(path).ok()?;
+                serde_json::from_slice(&json)
+                    .inspect_err(|e| warn!("Ignoring unreadable {:?}: {}", path, e))
+                    .ok()
+            })
+            .unwrap_or_default();
+        Self {
+            path,
+            state: Mutex::new(LedgerState {
+                servers,
+                saved: Instant::now(),
+                dirty: false,
+            }),
+        }
+    }
+
+    /// Add `bytes` to the usage of `server` and return the new usage
+    fn record(&self, server: &str, bytes: u64, today: NaiveDate, reset_day: u8) -> Usage {
+        let mut state = self.state.lock();
+        let usage = state.servers.entry(server.to_string()).or_default();
+        usage.roll(today, reset_day);
+        usage.day_bytes += bytes;
+        usage.month_bytes += bytes;
+        let usage = usage.clone();
+        state.dirty |= bytes > 0;
+
+        if state.dirty && state.saved.elapsed() >= SAVE_INTERVAL {
+            state.saved = Instant::now();
+            state.dirty = false;
+            let json = serde_json::to_vec(&state.servers);
+            drop(state);
+            if let (Some(path), Ok(json)) = (&self.path, json) {
+                let written = path
+                    .parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .and_then(|_| std::fs::write(path, json));
+                if let Err(e) = written {
+                    debug!("Failed to save bandwidth usage to {:?}: {}", path, e);
+                }
+            }
+        }
+        usage
+    }
+}
+
+fn ledger() -> &'static Ledger {
+    static LEDGER: OnceLock<Ledger> = OnceLock::new();
+    LEDGER.get_or_init(|| Ledger::new((!cfg!(test)).then(state_file)))
+}
+
+fn state_file() -> PathBuf {
+    #[cfg(windows)]
+    {
+        let base = std::env::var("ProgramData").unwrap_or_else(|_| "C:\\ProgramData".to_string());
+        PathBuf::from(base).join("nanolink").join("bandwidth.json")
+    }
+    #[cfg(unix)]
+    {
+        PathBuf::from("/var/lib/nanolink/bandwidth.json")
+    }
+}
+
+/// Traffic to one server, counted against its caps
+pub struct Meter {
+    server: String,
+    cap: Option<BandwidthCap>,
+    mode: Mutex<BandwidthMode>,
+}
+
+impl Meter {
+    pub fn new(server: &ServerConfig) -> Arc<Self> {
+        let meter = Arc::new(Self {
+            server: server.address(),
+            cap: server.bandwidth_cap.clone(),
+            mode: Mutex::new(BandwidthMode::Full),
+        });
+        // Pick up usage from before a restart
+        meter.record(0);
+        meter
+    }
+
+    pub fn mode(&self) -> BandwidthMode {
+        *self.mode.lock()
+    }
+
+    /// Whether `request` is sent in the current mode
+    #[cfg_attr(not(feature = "relay"), allow(dead_code))]
+    pub fn allows(&self, request: &MetricsStreamRequest) -> bool {
+        self.mode().allows(Priority::of(request))
+    }
+
+    /// Count a message of `bytes` sent to the server
+    pub fn record(&self, bytes: usize) {
+        let reset_day = self.cap.as_ref().map_or(1, |cap| cap.reset_day);
+        let today = Local::now().date_naive();
+        let usage = ledger().record(&self.server, bytes as u64, today, reset_day);
+
+        let mode = self
+            .cap
+            .as_ref()
+            .map_or(BandwidthMode::Full, |cap| BandwidthMode::of(cap, &usage));
+        let previous = std::mem::replace(&mut *self.mode.lock(), mode);
+        if mode != previous {
+            match mode {
+                BandwidthMode::Full => info!(
+                    "Bandwidth for {} is below its cap again, sending the full stream",
+                    self.server
+                ),
+                BandwidthMode::Reduced => warn!(
+                    "Bandwidth for {} is near its cap, sending periodic data only",
+                    self.server
+                ),
+                BandwidthMode::Capped => warn!(
+                    "Bandwidth cap for {} reached, sending alerts and command results only",
+                    self.server
+                ),
+            }
+        }
+        let cap_bytes = |mb: Option<u64>| mb.unwrap_or(0).saturating_mul(MB);
+        let caps = self.cap.as_ref().map_or([0, 0], |cap| {
+            [cap_bytes(cap.daily_mb), cap_bytes(cap.monthly_mb)]
+        });
+        telemetry().record_bandwidth(
+            &self.server,
+            usage.day_bytes,
+            usage.month_bytes,
+            caps,
+            mode.as_str(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_period_start() {
+        assert_eq!(period_start(date(2026, 3, 15), 1), date(2026, 3, 1));
+        assert_eq!(period_start(date(2026, 3, 15), 15), date(2026, 3, 15));
+        assert_eq!(period_start(date(2026, 3, 14), 15), date(2026, 2, 15));
+        assert_eq!(period_start(date(2026, 1, 3), 10), date(2025, 12, 10));
+    }
+
+    #[test]
+    fn test_usage_rolls_over() {
+        let ledger = Ledger::new(None);
+        ledger.record("a:1", 100, date(2026, 3, 14), 15);
+        let usage = ledger.record("a:1", 50, date(2026, 3, 14), 15);
+        assert_eq!((usage.day_bytes, usage.month_bytes), (150, 150));
+
+        // New day, same month
+        let usage = ledger.record("a:1", 10, date(2026, 3, 14).succ_opt().unwrap(), 15);
+        assert_eq!((usage.day_bytes, usage.month_bytes), (10, 10));
+        let usage = ledger.record("a:1", 10, date(2026, 3, 20), 15);
+        assert_eq!((usage.day_bytes, usage.month_bytes), (10, 20));
+    }
+
+    #[test]
+    fn test_mode_follows_caps() {
+        let cap = BandwidthCap {
+            daily_mb: Some(10),
+            monthly_mb: Some(500),
+            reset_day: 1,
+            reduce_at_percent: 80,
+        };
+        let usage = |day_mb: u64, month_mb: u64| Usage {
+            day_bytes: day_mb * MB,
+            month_bytes: month_mb * MB,
+            ..Default::default()
+        };
+        assert_eq!(BandwidthMode::of(&cap, &usage(1, 100)), BandwidthMode::Full);
+        assert_eq!(
+            BandwidthMode::of(&cap, &usage(8, 100)),
+            BandwidthMode::Reduced
+        );
+        assert_eq!(
+            BandwidthMode::of(&cap, &usage(1, 400)),
+            BandwidthMode::Reduced
+        );
+        assert_eq!(
+            BandwidthMode::of(&cap, &usage(1, 500)),
+            BandwidthMode::Capped
+        );
+
+        let reduced = BandwidthMode::Reduced;
+        assert!(reduced.allows(Priority::Periodic) && !reduced.allows(Priority::Realtime));
+        assert!(!reduced.allows(Priority::Backfill));
+        let capped = BandwidthMode::Capped;
+        assert!(capped.allows(Priority::Event) && capped.allows(Priority::CommandResult));
+        assert!(!capped.allows(Priority::Periodic));
+    }
+}
@@ -0,0 +1,82 @@
+//! Failover between a primary and a standby server
+//!
+//! The primary's connection task reports whether its stream is up. The
+//! standby's task stays disconnected until the primary has been down for
+//! `agent.failover_delay` seconds, then streams in its place and hands back
+//! as soon as the primary is up again.
+
+use std::time::Duration;
+
+use tokio::sync::watch;
+
+/// Whether the primary server's stream is up
+pub struct PrimaryState {
+    tx: watch::Sender<bool>,
+}
+
+impl PrimaryState {
+    pub fn new() -> Self {
+        Self {
+            tx: watch::channel(false).0,
+        }
+    }
+
+    pub fn set_up(&self, up: bool) {
+        self.tx
+            .send_if_modified(|current| std::mem::replace(current, up) != up);
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.tx.subscribe()
+    }
+}
+
+/// Wait until the primary has been down for `delay` without interruption
+pub async fn primary_down_for(primary: &mut watch::Receiver<bool>, delay: Duration) {
+    loop {
+        if primary.wait_for(|up| !*up).await.is_err() {
+            return std::future::pending().await;
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => return,
+            _ = primary.wait_for(|up| *up) => {}
+        }
+    }
+}
+
+/// Wait until the primary is up
+pub async fn primary_up(primary: &mut watch::Receiver<bool>) {
+    if primary.wait_for(|up| *up).await.is_err() {
+        std::future::pending().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_standby_waits_for_the_delay() {
+        let primary = PrimaryState::new();
+        let mut rx = primary.subscribe();
+        let delay = Duration::from_millis(200);
+
+        // A short outage doesn't fail over
+        let waiting = tokio::spawn(async move { primary_down_for(&mut rx, delay).await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        primary.set_up(true);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(!waiting.is_finished());
+
+        // A long one does, counted from when the primary went down
+        let down = tokio::time::Instant::now();
+        primary.set_up(false);
+        waiting.await.unwrap();
+        assert!(down.elapsed() >= delay);
+
+        let mut rx = primary.subscribe();
+        let failback = tokio::spawn(async move { primary_up(&mut rx).await });
+        primary.set_up(true);
+        failback.await.unwrap();
+    }
+}
@@ -2,31 +2,44 @@
 //!
 //! Provides high-performance bidirectional streaming for metrics and commands.
 
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
-use tokio::sync::mpsc;
-use tokio::task::JoinHandle;
+use nanolink_client::protocol::downgrade;
+use nanolink_client::{AgentIdentity, Client, ConnectOptions, Server, chunking};
+use prost::Message;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::task::{AbortHandle, JoinHandle};
 use tokio::time;
 use tokio_stream::wrappers::ReceiverStream;
-use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+use tokio_stream::{Stream, StreamExt};
 use tonic::{Request, Streaming};
 use tracing::{debug, error, info, warn};
 
+use super::bandwidth::{self, Meter};
+use super::privacy;
+use super::queue::{Priority, SendQueue};
+use super::replay::{self, RecordedRequest, Recorder};
+use super::schedule::Pacer;
 use crate::buffer::RingBuffer;
 use crate::collector::layered::{DataRequest, LayeredCollector, LayeredMetricsMessage};
+use crate::collector::syslog;
 use crate::config::{Config, ServerConfig};
 use crate::proto::{
-    AgentInit, AuthRequest, AuthResponse, Command, CommandResult, DataRequestType, Heartbeat,
-    Metrics, MetricsStreamRequest, MetricsStreamResponse, metrics_stream_request,
-    metrics_stream_response, nano_link_service_client::NanoLinkServiceClient,
+    AuthResponse, Command, CommandResult, ConfigBaseline, ConfigProfile, ConfigProfileRequest,
+    DataRequestType, EnrollResponse, Heartbeat, LogBatch, MetricsStreamRequest,
+    MetricsStreamResponse, metrics_stream_request, metrics_stream_response,
 };
+use crate::security::capability::{self, CapabilitySet};
+use crate::telemetry::telemetry;
 
 /// Guard that ensures spawned tasks are aborted when dropped.
 /// This is critical for cleanup when stream errors cause early returns via `?`.
 struct TaskCleanupGuard {
-    handles: Vec<JoinHandle<()>>,
+    handles: Vec<AbortHandle>,
 }
 
 impl TaskCleanupGuard {
@@ -37,7 +50,12 @@ impl TaskCleanupGuard {
     }
 
     fn add(&mut self, handle: JoinHandle<()>) {
-        self.handles.push(handle);
+        self.watch(&handle);
+    }
+
+    /// Abort a task the caller still awaits
+    fn watch<T>(&mut self, handle: &JoinHandle<T>) {
+        self.handles.push(handle.abort_handle());
     }
 }
 
@@ -55,59 +73,277 @@ impl Drop for TaskCleanupGuard {
     }
 }
 
+/// Self-telemetry ticker; `None` when disabled.
+///
+/// The first tick is skipped so the report covers a full interval.
+fn telemetry_interval(secs: u64) -> Option<time::Interval> {
+    (secs > 0).then(|| {
+        let period = Duration::from_secs(secs);
+        time::interval_at(time::Instant::now() + period, period)
+    })
+}
+
+/// Tick an optional interval, pending forever when disabled
+async fn tick(interval: &mut Option<time::Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Next batch from the syslog listener. Batches missed while this stream
+/// was behind are skipped.
+async fn next_log_batch(rx: &mut broadcast::Receiver<LogBatch>) -> LogBatch {
+    loop {
+        match rx.recv().await {
+            Ok(batch) => return batch,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Stream fell behind, {} syslog batches skipped", skipped);
+            }
+            Err(RecvError::Closed) => std::future::pending().await,
+        }
+    }
+}
+
+/// Queue a sequence of stream requests, returning false once the stream is closed.
+/// Messages the server's protocol version doesn't know are skipped.
+fn send_all(
+    queue: &SendQueue,
+    protocol: u32,
+    requests: impl IntoIterator<Item = metrics_stream_request::Request>,
+) -> bool {
+    send_all_as(queue, protocol, requests, None)
+}
+
+/// [`send_all`] with an explicit priority instead of each message's own
+fn send_all_as(
+    queue: &SendQueue,
+    protocol: u32,
+    requests: impl IntoIterator<Item = metrics_stream_request::Request>,
+    priority: Option<Priority>,
+) -> bool {
+    for request in requests {
+        let Some(mut request) = downgrade(request, protocol) else {
+            continue;
+        };
+        privacy::scrub(&mut request);
+        let request = MetricsStreamRequest {
+            request: Some(request),
+        };
+        let priority = priority.unwrap_or_else(|| Priority::of(&request));
+        if !queue.push_as(request, priority) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Resend the metrics buffered while disconnected, at backfill priority so
+/// live messages go first. The sync position advances once a batch has left
+/// the queue; if the stream breaks before that, the batch is sent again on
+/// the next connection.
+async fn backfill(
+    queue: Arc<SendQueue>,
+    buffer: Arc<RingBuffer>,
+    batch_size: usize,
+    max_message_size: usize,
+    protocol: u32,
+) {
+    let unsynced = buffer.get_unsynced();
+    if unsynced.is_empty() {
+        info!("No unsynced data to compensate");
+        return;
+    }
+    let count = unsynced.len();
+    info!(
+        "Starting data compensation: {} unsynced metrics to send",
+        count
+    );
+
+    let mut sent = 0;
+    for batch in unsynced.chunks(batch_size.max(1)) {
+        // Near a bandwidth cap the buffer waits for the next day or month
+        while !queue.accepts(Priority::Backfill) {
+            if queue.is_closed() {
+                return;
+            }
+            time::sleep(Duration::from_secs(10)).await;
+        }
+        for metrics in batch {
+            let chunks = chunking::split_metrics((**metrics).clone(), max_message_size);
+            for chunk in chunks {
+                let request = metrics_stream_request::Request::Metrics(chunk);
+                let Some(mut request) = downgrade(request, protocol) else {
+                    continue;
+                };
+                privacy::scrub(&mut request);
+                let request = MetricsStreamRequest {
+                    request: Some(request),
+                };
+                if !queue.push_as(request, Priority::Backfill) {
+                    return;
+                }
+            }
+        }
+        // Wait for the batch to be taken by the stream
+        while queue.pending(Priority::Backfill) > 0 {
+            if queue.is_closed() {
+                info!(
+                    "Data compensation interrupted: sent {}/{} metrics",
+                    sent, count
+                );
+                return;
+            }
+            time::sleep(Duration::from_millis(50)).await;
+        }
+        sent += batch.len();
+        if let Some(sequence) = batch.iter().map(|m| m.sequence).max() {
+            buffer.set_last_sync_sequence(sequence.max(buffer.get_last_sync_sequence()));
+        }
+    }
+    info!(
+        "Data compensation completed: sent {}/{} metrics",
+        sent, count
+    );
+}
+
+/// Wait for the command stream task to end, pending forever without one
+async fn command_stream_closed(task: &mut Option<JoinHandle<()>>) {
+    match task {
+        Some(task) => {
+            let _ = task.await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Stream message carrying the current telemetry snapshot
+fn telemetry_request(agent_id: &str) -> metrics_stream_request::Request {
+    metrics_stream_request::Request::Telemetry(telemetry().snapshot().to_proto(agent_id))
+}
+
+/// Identity this agent reports, with the hostname as privacy mode allows
+fn identity(hostname: String, agent_id: String) -> AgentIdentity {
+    AgentIdentity::new(
+        agent_id,
+        privacy::hostname(hostname),
+        env!("CARGO_PKG_VERSION"),
+    )
+}
+
+/// Hostname of this machine, for commands that run without a loaded config
+fn system_hostname() -> String {
+    hostname::get()
+        .map(|h| h.to_string_lossy().to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Result of a one-shot connection test against a server
+#[derive(Debug, Clone)]
+pub struct ConnectionProbe {
+    /// Resolved gRPC URL
+    pub url: String,
+    /// Whether the channel was established over TLS
+    pub tls_enabled: bool,
+    /// Whether certificate verification was requested
+    pub tls_verify: bool,
+    /// SHA-256 fingerprint of the server's leaf certificate (TLS only)
+    pub tls_fingerprint: Option<String>,
+    /// Negotiated TLS protocol version and cipher suite (TLS only)
+    pub tls_session: Option<(String, String)>,
+    /// Time to establish the channel (TCP + TLS + HTTP/2 handshake)
+    pub connect_rtt: Duration,
+    /// Round-trip time of the Authenticate RPC
+    pub auth_rtt: Duration,
+    /// Permission level granted by the server
+    pub granted_permission: i32,
+}
+
 /// gRPC client for communicating with NanoLink server
+#[derive(Clone)]
 pub struct GrpcClient {
-    client: NanoLinkServiceClient<Channel>,
+    client: Client,
     config: Arc<Config>,
     server_config: ServerConfig,
-    permission_level: i32,
+    capabilities: CapabilitySet,
+    recorder: Option<Arc<Recorder>>,
+    config_path: Option<PathBuf>,
+    meter: Arc<Meter>,
 }
 
 impl GrpcClient {
     /// Connect to a gRPC server
     pub async fn connect(server_config: &ServerConfig, config: &Arc<Config>) -> Result<Self> {
-        let url = server_config.get_grpc_url();
-
-        let mut endpoint = Endpoint::from_shared(url.clone())
-            .context("Invalid server URL")?
-            // Note: Don't set .timeout() here - it kills streaming RPCs
-            // Use connect_timeout for connection establishment instead
-            // Keep this SHORT to detect failures quickly and allow fast reconnection
-            .connect_timeout(Duration::from_secs(15))
-            // TCP keepalive - OS level (aggressive for NAT/firewall environments)
-            .tcp_keepalive(Some(Duration::from_secs(20)))
-            // HTTP/2 keepalive - gRPC level (must match server settings)
-            // Server: keepAliveTime=30s, keepAliveTimeout=10s
-            .http2_keep_alive_interval(Duration::from_secs(20))
-            .keep_alive_timeout(Duration::from_secs(10))
-            .keep_alive_while_idle(true);
-
-        // Configure TLS if enabled
-        if server_config.tls_enabled {
-            let tls_config = ClientTlsConfig::new();
-            endpoint = endpoint.tls_config(tls_config)?;
-        }
-
         info!(
             "Connecting to gRPC server: {} with HTTP/2 keepalive enabled",
-            url
+            server_config.get_grpc_url()
         );
 
-        let channel = endpoint
-            .connect()
-            .await
-            .context("Failed to connect to gRPC server")?;
-
-        let client = NanoLinkServiceClient::new(channel);
+        // Keep the connect timeout SHORT to detect failures quickly and
+        // allow fast reconnection
+        let options = ConnectOptions {
+            connect_timeout: Duration::from_secs(15),
+            keepalive: true,
+            max_message_size: config.agent.max_message_size,
+        };
+        let client = Client::connect(&Server::from(server_config), &options).await?;
 
         Ok(Self {
             client,
             config: config.clone(),
             server_config: server_config.clone(),
-            permission_level: 0,
+            capabilities: CapabilitySet::default(),
+            recorder: None,
+            config_path: None,
+            meter: Meter::new(server_config),
         })
     }
 
+    /// Write every message sent on the stream to a recording
+    pub fn with_recorder(mut self, recorder: Option<Arc<Recorder>>) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    /// Config file re-read for drift checks
+    pub fn with_config_path(mut self, config_path: Option<PathBuf>) -> Self {
+        self.config_path = config_path;
+        self
+    }
+
+    /// Check config drift against the baselines sent to the returned channel
+    fn spawn_drift_watch(
+        &self,
+        queue: &Arc<SendQueue>,
+        protocol: u32,
+    ) -> (JoinHandle<()>, watch::Sender<Option<ConfigBaseline>>) {
+        let (baseline_tx, baseline_rx) = watch::channel(None);
+        let queue = queue.clone();
+        let task = tokio::spawn(crate::drift::watch(
+            self.config.clone(),
+            self.config_path.clone(),
+            baseline_rx,
+            move |drift| {
+                send_all(
+                    &queue,
+                    protocol,
+                    [metrics_stream_request::Request::ConfigDrift(drift)],
+                )
+            },
+        ));
+        (task, baseline_tx)
+    }
+
+    /// Identity of this agent as configured
+    fn identity(&self) -> AgentIdentity {
+        identity(
+            self.config.get_hostname(),
+            self.config.agent.agent_id.clone().unwrap_or_default(),
+        )
+    }
+
     /// Authenticate with the server
     pub async fn authenticate(&mut self) -> Result<AuthResponse> {
         // Resolve token (supports environment variables and file references)
@@ -116,27 +352,24 @@ impl GrpcClient {
             .resolve_token()
             .map_err(|e| anyhow::anyhow!("Token resolution failed: {e}"))?;
 
-        let request = Request::new(AuthRequest {
-            token: resolved_token,
-            hostname: self.config.get_hostname(),
-            agent_version: env!("CARGO_PKG_VERSION").to_string(),
-            os: std::env::consts::OS.to_string(),
-            arch: std::env::consts::ARCH.to_string(),
-        });
+        let local_capabilities = capability::local_capabilities(&self.config);
+        let mut identity = self.identity();
+        identity.capabilities = local_capabilities.clone();
 
-        let response = self
+        let auth_response = self
             .client
-            .authenticate(request)
-            .await
-            .context("Authentication failed")?;
-
-        let auth_response = response.into_inner();
+            .authenticate(identity.auth_request(resolved_token))
+            .await?;
 
         if auth_response.success {
-            self.permission_level = auth_response.permission_level;
+            self.capabilities =
+                CapabilitySet::negotiate(&local_capabilities, &auth_response.allowed_capabilities);
             info!(
-                "Authenticated with permission level: {}",
-                self.permission_level
+                "Authenticated with permission level: {}, capabilities: [{}], max message size: {} bytes, protocol: {}",
+                self.client.permission_level(),
+                self.capabilities.iter().collect::<Vec<_>>().join(", "),
+                self.client.max_message_size(),
+                self.client.protocol_version()
             );
         } else {
             error!("Authentication failed: {}", auth_response.error_message);
@@ -145,6 +378,36 @@ impl GrpcClient {
         Ok(auth_response)
     }
 
+    /// Capabilities negotiated during the last successful authentication
+    pub fn capabilities(&self) -> &CapabilitySet {
+        &self.capabilities
+    }
+
+    /// SHA-256 fingerprint of the server certificate seen during the TLS handshake
+    pub fn tls_fingerprint(&self) -> Option<&str> {
+        self.client.tls_fingerprint()
+    }
+
+    /// Wrap the outgoing messages so every message is counted in self-telemetry
+    /// and recorded when a recording is running
+    fn counted_stream(
+        &self,
+        requests: impl Stream<Item = MetricsStreamRequest> + Send + 'static,
+    ) -> impl Stream<Item = MetricsStreamRequest> + Send + 'static {
+        let address = self.server_config.address();
+        let recorder = self.recorder.clone();
+        let meter = self.meter.clone();
+        requests.map(move |request| {
+            let size = request.encoded_len();
+            telemetry().record_sent(&address, size);
+            meter.record(size);
+            if let Some(recorder) = &recorder {
+                recorder.record(&request);
+            }
+            request
+        })
+    }
+
     /// Start bidirectional streaming for metrics and commands
     pub async fn stream_metrics<F, Fut>(
         &mut self,
@@ -153,44 +416,81 @@ impl GrpcClient {
     ) -> Result<()>
     where
         F: Fn(Command) -> Fut + Send + Sync + 'static,
-        Fut: std::future::Future<Output = CommandResult> + Send,
+        Fut: std::future::Future<Output = CommandResult> + Send + 'static,
     {
-        // Create channel for sending requests
-        let (tx, rx) = mpsc::channel::<MetricsStreamRequest>(100);
-        let request_stream = ReceiverStream::new(rx);
+        // Collection queues messages without waiting for the server
+        let queue = SendQueue::new(
+            self.server_config.address(),
+            self.config.agent.send_queue_size,
+            Some(self.meter.clone()),
+        );
+        let request_stream = self.counted_stream(queue.stream());
 
         // Start the bidirectional stream
-        let response = self
-            .client
-            .stream_metrics(Request::new(request_stream))
-            .await
-            .context("Failed to start metrics stream")?;
-
-        let mut response_stream: Streaming<MetricsStreamResponse> = response.into_inner();
+        let mut response_stream: Streaming<MetricsStreamResponse> =
+            self.client.stream_metrics(request_stream).await?;
 
         // Spawn task to send metrics with cleanup guard
-        let tx_clone = tx.clone();
+        let sender_queue = queue.clone();
         let config = self.config.clone();
         let buffer_clone = buffer.clone();
+        let max_message_size = self.client.max_message_size();
+        let protocol = self.client.protocol_version();
 
         // Use cleanup guard to ensure task is aborted on any exit (including ? early returns)
         let mut cleanup_guard = TaskCleanupGuard::new();
 
+        if config.buffer.data_compensation {
+            cleanup_guard.add(tokio::spawn(backfill(
+                queue.clone(),
+                buffer.clone(),
+                config.buffer.compensation_batch_size,
+                max_message_size,
+                protocol,
+            )));
+        }
+
+        let (drift_task, baseline_tx) = self.spawn_drift_watch(&queue, protocol);
+        cleanup_guard.add(drift_task);
+
+        let mut pacer = Pacer::new(&self.server_config);
         let sender_handle = tokio::spawn(async move {
             let mut interval =
                 time::interval(Duration::from_millis(config.collector.cpu_interval_ms));
             let mut heartbeat_interval =
                 time::interval(Duration::from_secs(config.agent.heartbeat_interval));
+            let mut telemetry_ticker = telemetry_interval(config.agent.telemetry_interval);
+            let agent_id = config.agent.agent_id.clone().unwrap_or_default();
+            let mut log_batches = syslog::subscribe();
+            let mut last_reduced: Option<time::Instant> = None;
 
             loop {
                 tokio::select! {
                     _ = interval.tick() => {
+                        if !pacer.due() {
+                            continue;
+                        }
+                        // Near a bandwidth cap a sample goes out now and then,
+                        // as periodic data
+                        let priority = if sender_queue.accepts(Priority::Realtime) {
+                            None
+                        } else if last_reduced.is_none_or(|at| {
+                            at.elapsed() >= bandwidth::REDUCED_SAMPLE_INTERVAL
+                        }) {
+                            last_reduced = Some(time::Instant::now());
+                            Some(Priority::Periodic)
+                        } else {
+                            continue;
+                        };
                         // Get latest metrics from buffer
                         if let Some(metrics) = buffer_clone.latest() {
-                            let request = MetricsStreamRequest {
-                                request: Some(metrics_stream_request::Request::Metrics(metrics)),
-                            };
-                            if tx_clone.send(request).await.is_err() {
+                            let chunks = chunking::split_metrics(
+                                Arc::unwrap_or_clone(metrics),
+                                max_message_size,
+                            );
+                            if !send_all_as(&sender_queue, protocol, chunks.into_iter().map(
+                                metrics_stream_request::Request::Metrics,
+                            ), priority) {
                                 break;
                             }
                         }
@@ -198,12 +498,24 @@ impl GrpcClient {
                     _ = heartbeat_interval.tick() => {
                         let heartbeat = Heartbeat {
                             timestamp: chrono::Utc::now().timestamp_millis() as u64,
-                            uptime_seconds: 0, // TODO: Calculate uptime
+                            uptime_seconds: telemetry().uptime_seconds(),
+                            silence: crate::silence::current().map(|s| s.to_proto()),
                         };
                         let request = MetricsStreamRequest {
                             request: Some(metrics_stream_request::Request::Heartbeat(heartbeat)),
                         };
-                        if tx_clone.send(request).await.is_err() {
+                        if !sender_queue.push(request) {
+                            break;
+                        }
+                    }
+                    _ = tick(&mut telemetry_ticker) => {
+                        if !send_all(&sender_queue, protocol, [telemetry_request(&agent_id)]) {
+                            break;
+                        }
+                    }
+                    batch = next_log_batch(&mut log_batches) => {
+                        let request = metrics_stream_request::Request::LogBatch(batch);
+                        if !send_all(&sender_queue, protocol, [request]) {
                             break;
                         }
                     }
@@ -212,9 +524,24 @@ impl GrpcClient {
         });
         cleanup_guard.add(sender_handle);
 
+        let command_handler = Arc::new(command_handler);
+        let mut command_stream = self.open_command_stream(command_handler.clone()).await?;
+        if let Some(task) = &command_stream {
+            cleanup_guard.watch(task);
+        }
+
         // Handle responses from server
         // Note: cleanup_guard will abort tasks when dropped (including on ? early return)
-        while let Some(response) = response_stream.message().await? {
+        loop {
+            let response = tokio::select! {
+                response = response_stream.message() => match response? {
+                    Some(response) => response,
+                    None => break,
+                },
+                _ = command_stream_closed(&mut command_stream) => {
+                    anyhow::bail!("Command stream closed");
+                }
+            };
             match response.response {
                 Some(metrics_stream_response::Response::Command(cmd)) => {
                     info!("Received command: {:?}", cmd.r#type);
@@ -224,7 +551,7 @@ impl GrpcClient {
                     let request = MetricsStreamRequest {
                         request: Some(metrics_stream_request::Request::CommandResult(result)),
                     };
-                    if tx.send(request).await.is_err() {
+                    if !queue.push(request) {
                         break;
                     }
                 }
@@ -235,6 +562,10 @@ impl GrpcClient {
                     info!("Received config update from server");
                     // TODO: Apply config update
                 }
+                Some(metrics_stream_response::Response::ConfigBaseline(baseline)) => {
+                    info!("Received config baseline '{}'", baseline.baseline_id);
+                    let _ = baseline_tx.send(Some(baseline));
+                }
                 Some(metrics_stream_response::Response::DataRequest(req)) => {
                     info!("Received data request: {:?}", req.request_type);
                     // In legacy stream_metrics, we don't have layered support
@@ -248,20 +579,89 @@ impl GrpcClient {
         Ok(())
     }
 
-    /// Report metrics using unary RPC (simpler, but less efficient)
-    #[allow(dead_code)]
-    pub async fn report_metrics(&mut self, metrics: Metrics) -> Result<()> {
-        let response = self
-            .client
-            .report_metrics(Request::new(metrics))
-            .await
-            .context("Failed to report metrics")?;
+    /// Start a stream of alerts and events only, for a mirror server
+    ///
+    /// Metrics and relayed syslog stay with the other servers, and commands
+    /// from a mirror are refused.
+    pub async fn stream_events(&mut self) -> Result<()> {
+        let queue = SendQueue::new(
+            self.server_config.address(),
+            self.config.agent.send_queue_size,
+            Some(self.meter.clone()),
+        );
+        let request_stream = self.counted_stream(queue.stream());
+        let mut response_stream: Streaming<MetricsStreamResponse> =
+            self.client.stream_metrics(request_stream).await?;
 
-        let ack = response.into_inner();
-        if !ack.success {
-            warn!("Metrics report was not acknowledged");
-        }
+        queue.push(MetricsStreamRequest {
+            request: Some(metrics_stream_request::Request::AgentInit(
+                self.identity().agent_init(),
+            )),
+        });
+
+        let sender_queue = queue.clone();
+        let heartbeat_interval = self.config.agent.heartbeat_interval;
+        let protocol = self.client.protocol_version();
+        let mut log_batches = syslog::subscribe();
+        let mut cleanup_guard = TaskCleanupGuard::new();
+
+        cleanup_guard.add(tokio::spawn(async move {
+            let mut heartbeat_ticker = time::interval(Duration::from_secs(heartbeat_interval));
+            loop {
+                tokio::select! {
+                    _ = heartbeat_ticker.tick() => {
+                        let heartbeat = Heartbeat {
+                            timestamp: chrono::Utc::now().timestamp_millis() as u64,
+                            uptime_seconds: telemetry().uptime_seconds(),
+                            silence: crate::silence::current().map(|s| s.to_proto()),
+                        };
+                        let request = MetricsStreamRequest {
+                            request: Some(metrics_stream_request::Request::Heartbeat(heartbeat)),
+                        };
+                        if !sender_queue.push(request) {
+                            break;
+                        }
+                    }
+                    batch = next_log_batch(&mut log_batches) => {
+                        if batch.source == "syslog" {
+                            continue;
+                        }
+                        let request = metrics_stream_request::Request::LogBatch(batch);
+                        if !send_all(&sender_queue, protocol, [request]) {
+                            break;
+                        }
+                    }
+                }
+            }
+        }));
 
+        while let Some(response) = response_stream.message().await? {
+            match response.response {
+                Some(metrics_stream_response::Response::Command(cmd)) => {
+                    warn!(
+                        "[AUDIT] Refused command {:?} from mirror server {}",
+                        cmd.r#type,
+                        self.server_config.address()
+                    );
+                    let result = CommandResult {
+                        command_id: cmd.command_id,
+                        success: false,
+                        error: "Commands are not accepted from a mirror server".to_string(),
+                        ..Default::default()
+                    };
+                    let request = MetricsStreamRequest {
+                        request: Some(metrics_stream_request::Request::CommandResult(result)),
+                    };
+                    if !queue.push(request) {
+                        break;
+                    }
+                }
+                Some(metrics_stream_response::Response::HeartbeatAck(ack)) => {
+                    debug!("Heartbeat acknowledged: {}", ack.timestamp);
+                }
+                _ => {}
+            }
+        }
         Ok(())
     }
 
@@ -270,6 +670,7 @@ impl GrpcClient {
     pub async fn execute_command(&mut self, command: Command) -> Result<CommandResult> {
         let response = self
             .client
+            .service()
             .execute_command(Request::new(command))
             .await
             .context("Failed to execute command")?;
@@ -277,6 +678,19 @@ impl GrpcClient {
         Ok(response.into_inner())
     }
 
+    /// Request the central config profile; `current_sha256` is the digest of
+    /// the cached one
+    pub async fn get_config_profile(&mut self, current_sha256: String) -> Result<ConfigProfile> {
+        let identity = self.identity();
+        self.client
+            .get_config_profile(ConfigProfileRequest {
+                agent_id: identity.agent_id,
+                hostname: identity.hostname,
+                current_sha256,
+            })
+            .await
+    }
+
     /// Test connection to a server without full authentication
     ///
     /// This method attempts to connect and authenticate with the server,
@@ -286,54 +700,70 @@ impl GrpcClient {
         server_config: &ServerConfig,
         configured_permission: u8,
     ) -> Result<String> {
-        let url = server_config.get_grpc_url();
+        let probe = Self::probe_server(server_config).await?;
 
-        let mut endpoint = Endpoint::from_shared(url.clone())
-            .context("Invalid server URL")?
-            .connect_timeout(Duration::from_secs(10))
-            .tcp_keepalive(Some(Duration::from_secs(20)));
+        // Show configured permission vs server-granted permission
+        Ok(format!(
+            "Configured: {}, Server: {}",
+            configured_permission, probe.granted_permission
+        ))
+    }
 
-        // Configure TLS if enabled
-        if server_config.tls_enabled {
-            let tls_config = ClientTlsConfig::new();
-            endpoint = endpoint.tls_config(tls_config)?;
-        }
+    /// Exchange an enrollment code for a token. TLS is required, as the code
+    /// alone grants access. Returns the response and the server certificate
+    /// fingerprint.
+    pub async fn enroll(
+        server_config: &ServerConfig,
+        code: &str,
+        agent_id: &str,
+    ) -> Result<(EnrollResponse, String)> {
+        let options = ConnectOptions {
+            connect_timeout: Duration::from_secs(10),
+            keepalive: false,
+            ..Default::default()
+        };
+        let request = identity(system_hostname(), agent_id.to_string()).enroll_request(code);
+        Client::enroll(&Server::from(server_config), &options, request).await
+    }
 
-        let channel = endpoint
-            .connect()
-            .await
-            .context("Failed to connect to server")?;
+    /// One-shot connect + authenticate against a server, measuring round-trip times
+    ///
+    /// Used by `server test` and the wizards to validate tokens and firewall rules
+    /// without starting the full agent.
+    pub async fn probe_server(server_config: &ServerConfig) -> Result<ConnectionProbe> {
+        let options = ConnectOptions {
+            connect_timeout: Duration::from_secs(10),
+            keepalive: false,
+            ..Default::default()
+        };
 
-        let mut client = NanoLinkServiceClient::new(channel);
+        let connect_start = Instant::now();
+        let mut client = Client::connect(&Server::from(server_config), &options).await?;
+        let connect_rtt = connect_start.elapsed();
 
         // Resolve token and authenticate
         let resolved_token = server_config
             .resolve_token()
             .map_err(|e| anyhow::anyhow!("Token resolution failed: {e}"))?;
+        let request = identity(system_hostname(), String::new()).auth_request(resolved_token);
 
-        let request = Request::new(AuthRequest {
-            token: resolved_token,
-            hostname: hostname::get()
-                .map(|h| h.to_string_lossy().to_string())
-                .unwrap_or_else(|_| "unknown".to_string()),
-            agent_version: env!("CARGO_PKG_VERSION").to_string(),
-            os: std::env::consts::OS.to_string(),
-            arch: std::env::consts::ARCH.to_string(),
-        });
-
-        let response = client
-            .authenticate(request)
-            .await
-            .context("Authentication failed")?;
-
-        let auth_response = response.into_inner();
+        let auth_start = Instant::now();
+        let auth_response = client.authenticate(request).await?;
+        let auth_rtt = auth_start.elapsed();
 
         if auth_response.success {
-            // Show configured permission vs server-granted permission
-            Ok(format!(
-                "Configured: {}, Server: {}",
-                configured_permission, auth_response.permission_level
-            ))
+            Ok(ConnectionProbe {
+                url: server_config.get_grpc_url(),
+                tls_enabled: server_config.tls_enabled,
+                tls_verify: server_config.tls_verify,
+                tls_fingerprint: client.tls_fingerprint().map(str::to_string),
+                tls_session: client
+                    .tls_handshake()
+                    .map(|tls| (tls.protocol_version.clone(), tls.cipher_suite.clone())),
+                connect_rtt,
+                auth_rtt,
+                granted_permission: auth_response.permission_level,
+            })
         } else {
             Err(anyhow::anyhow!(
                 "Authentication failed: {}",
@@ -342,43 +772,94 @@ impl GrpcClient {
         }
     }
 
+    /// Open the dedicated command stream when enabled. Returns the task
+    /// answering commands on it; `None` when disabled or when the server
+    /// doesn't support it, in which case commands arrive on the metrics stream.
+    async fn open_command_stream<F, Fut>(
+        &mut self,
+        command_handler: Arc<F>,
+    ) -> Result<Option<JoinHandle<()>>>
+    where
+        F: Fn(Command) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = CommandResult> + Send + 'static,
+    {
+        if !self.config.agent.command_stream {
+            return Ok(None);
+        }
+
+        let (tx, rx) = mpsc::channel::<MetricsStreamRequest>(16);
+        let init_request = MetricsStreamRequest {
+            request: Some(metrics_stream_request::Request::AgentInit(
+                self.identity().agent_init(),
+            )),
+        };
+        let _ = tx.send(init_request).await;
+        let request_stream = self.counted_stream(ReceiverStream::new(rx));
+        let Some(mut responses) = self.client.stream_commands(request_stream).await? else {
+            info!("Server has no command stream, commands stay on the metrics stream");
+            return Ok(None);
+        };
+        info!("Command stream opened");
+
+        Ok(Some(tokio::spawn(async move {
+            loop {
+                let response = match responses.message().await {
+                    Ok(Some(response)) => response,
+                    Ok(None) => {
+                        warn!("Server closed the command stream");
+                        break;
+                    }
+                    Err(e) => {
+                        warn!("Command stream failed: {}", e);
+                        break;
+                    }
+                };
+                if let Some(metrics_stream_response::Response::Command(cmd)) = response.response {
+                    info!("Received command: {:?}", cmd.r#type);
+                    let result = command_handler(cmd).await;
+                    let request = MetricsStreamRequest {
+                        request: Some(metrics_stream_request::Request::CommandResult(result)),
+                    };
+                    if tx.send(request).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        })))
+    }
+
     /// Start bidirectional streaming with layered metrics support
     ///
     /// This method uses the LayeredCollector to send different types of metrics
     /// at different intervals (realtime, periodic, static).
-    pub async fn stream_layered_metrics<F, Fut>(&mut self, command_handler: F) -> Result<()>
+    pub async fn stream_layered_metrics<F, Fut>(
+        &mut self,
+        buffer: Arc<RingBuffer>,
+        command_handler: F,
+    ) -> Result<()>
     where
         F: Fn(Command) -> Fut + Send + Sync + 'static,
-        Fut: std::future::Future<Output = CommandResult> + Send,
+        Fut: std::future::Future<Output = CommandResult> + Send + 'static,
     {
-        // Create channel for sending requests
-        let (tx, rx) = mpsc::channel::<MetricsStreamRequest>(100);
-        let request_stream = ReceiverStream::new(rx);
+        // Collection queues messages without waiting for the server
+        let queue = SendQueue::new(
+            self.server_config.address(),
+            self.config.agent.send_queue_size,
+            Some(self.meter.clone()),
+        );
+        let request_stream = self.counted_stream(queue.stream());
 
         // Start the bidirectional stream
-        let response = self
-            .client
-            .stream_metrics(Request::new(request_stream))
-            .await
-            .context("Failed to start metrics stream")?;
-
-        let mut response_stream: Streaming<MetricsStreamResponse> = response.into_inner();
+        let mut response_stream: Streaming<MetricsStreamResponse> =
+            self.client.stream_metrics(request_stream).await?;
 
         // Send AgentInit as the FIRST message to identify this agent with its persistent ID
-        let agent_init = AgentInit {
-            agent_id: self.config.agent.agent_id.clone().unwrap_or_default(),
-            hostname: self.config.get_hostname(),
-            os: std::env::consts::OS.to_string(),
-            arch: std::env::consts::ARCH.to_string(),
-            agent_version: env!("CARGO_PKG_VERSION").to_string(),
-        };
+        let agent_init = self.identity().agent_init();
         info!("Sending AgentInit with agent_id: {}", agent_init.agent_id);
         let init_request = MetricsStreamRequest {
             request: Some(metrics_stream_request::Request::AgentInit(agent_init)),
         };
-        tx.send(init_request)
-            .await
-            .context("Failed to send AgentInit")?;
+        queue.push(init_request);
 
         // Create layered collector with cleanup guard
         let (metrics_tx, mut metrics_rx) = mpsc::channel::<LayeredMetricsMessage>(100);
@@ -397,42 +878,60 @@ impl GrpcClient {
         cleanup_guard.add(collector_handle);
 
         // Spawn task to forward layered messages to gRPC stream
-        let tx_clone = tx.clone();
+        let sender_queue = queue.clone();
         let heartbeat_interval = self.config.agent.heartbeat_interval;
+        let max_message_size = self.client.max_message_size();
+        let protocol = self.client.protocol_version();
+        let mut telemetry_ticker = telemetry_interval(self.config.agent.telemetry_interval);
+        let agent_id = self.config.agent.agent_id.clone().unwrap_or_default();
+        let mut log_batches = syslog::subscribe();
+
+        if self.config.buffer.data_compensation {
+            cleanup_guard.add(tokio::spawn(backfill(
+                queue.clone(),
+                buffer,
+                self.config.buffer.compensation_batch_size,
+                max_message_size,
+                protocol,
+            )));
+        }
+
+        let (drift_task, baseline_tx) = self.spawn_drift_watch(&queue, protocol);
+        cleanup_guard.add(drift_task);
 
+        let mut pacer = Pacer::new(&self.server_config);
         let sender_handle = tokio::spawn(async move {
             let mut heartbeat_ticker = time::interval(Duration::from_secs(heartbeat_interval));
 
             loop {
                 tokio::select! {
                     Some(msg) = metrics_rx.recv() => {
-                        let request = match msg {
+                        let requests: Vec<_> = match msg {
                             LayeredMetricsMessage::Static(static_info) => {
                                 debug!("Sending static info");
-                                MetricsStreamRequest {
-                                    request: Some(metrics_stream_request::Request::StaticInfo(static_info)),
-                                }
+                                chunking::split_static_info(static_info, max_message_size)
+                                    .into_iter()
+                                    .map(metrics_stream_request::Request::StaticInfo)
+                                    .collect()
                             }
+                            LayeredMetricsMessage::Realtime(_) if !pacer.due() => continue,
                             LayeredMetricsMessage::Realtime(realtime) => {
-                                MetricsStreamRequest {
-                                    request: Some(metrics_stream_request::Request::Realtime(realtime)),
-                                }
+                                vec![metrics_stream_request::Request::Realtime(realtime)]
                             }
                             LayeredMetricsMessage::Periodic(periodic) => {
                                 debug!("Sending periodic data");
-                                MetricsStreamRequest {
-                                    request: Some(metrics_stream_request::Request::Periodic(periodic)),
-                                }
+                                vec![metrics_stream_request::Request::Periodic(periodic)]
                             }
                             LayeredMetricsMessage::Full(metrics) => {
                                 debug!("Sending full metrics (initial={})", metrics.is_initial);
-                                MetricsStreamRequest {
-                                    request: Some(metrics_stream_request::Request::Metrics(metrics)),
-                                }
+                                chunking::split_metrics(metrics, max_message_size)
+                                    .into_iter()
+                                    .map(metrics_stream_request::Request::Metrics)
+                                    .collect()
                             }
                         };
 
-                        if tx_clone.send(request).await.is_err() {
+                        if !send_all(&sender_queue, protocol, requests) {
                             error!("Failed to send to gRPC stream");
                             break;
                         }
@@ -440,24 +939,54 @@ impl GrpcClient {
                     _ = heartbeat_ticker.tick() => {
                         let heartbeat = Heartbeat {
                             timestamp: chrono::Utc::now().timestamp_millis() as u64,
-                            uptime_seconds: 0, // TODO: Calculate uptime
+                            uptime_seconds: telemetry().uptime_seconds(),
+                            silence: crate::silence::current().map(|s| s.to_proto()),
                         };
                         let request = MetricsStreamRequest {
                             request: Some(metrics_stream_request::Request::Heartbeat(heartbeat)),
                         };
-                        if tx_clone.send(request).await.is_err() {
+                        if !sender_queue.push(request) {
                             error!("Failed to send heartbeat");
                             break;
                         }
                     }
+                    _ = tick(&mut telemetry_ticker) => {
+                        if !send_all(&sender_queue, protocol, [telemetry_request(&agent_id)]) {
+                            error!("Failed to send telemetry");
+                            break;
+                        }
+                    }
+                    batch = next_log_batch(&mut log_batches) => {
+                        debug!("Sending {} syslog messages", batch.entries.len());
+                        let request = metrics_stream_request::Request::LogBatch(batch);
+                        if !send_all(&sender_queue, protocol, [request]) {
+                            error!("Failed to send log batch");
+                            break;
+                        }
+                    }
                 }
             }
         });
         cleanup_guard.add(sender_handle);
 
+        let command_handler = Arc::new(command_handler);
+        let mut command_stream = self.open_command_stream(command_handler.clone()).await?;
+        if let Some(task) = &command_stream {
+            cleanup_guard.watch(task);
+        }
+
         // Handle responses from server
         // Note: cleanup_guard will abort tasks when dropped (including on ? early return)
-        while let Some(response) = response_stream.message().await? {
+        loop {
+            let response = tokio::select! {
+                response = response_stream.message() => match response? {
+                    Some(response) => response,
+                    None => break,
+                },
+                _ = command_stream_closed(&mut command_stream) => {
+                    anyhow::bail!("Command stream closed");
+                }
+            };
             match response.response {
                 Some(metrics_stream_response::Response::Command(cmd)) => {
                     info!("Received command: {:?}", cmd.r#type);
@@ -467,7 +996,7 @@ impl GrpcClient {
                     let request = MetricsStreamRequest {
                         request: Some(metrics_stream_request::Request::CommandResult(result)),
                     };
-                    if tx.send(request).await.is_err() {
+                    if !queue.push(request) {
                         break;
                     }
                 }
@@ -478,6 +1007,10 @@ impl GrpcClient {
                     info!("Received config update from server");
                     // TODO: Apply config update
                 }
+                Some(metrics_stream_response::Response::ConfigBaseline(baseline)) => {
+                    info!("Received config baseline '{}'", baseline.baseline_id);
+                    let _ = baseline_tx.send(Some(baseline));
+                }
                 Some(metrics_stream_response::Response::DataRequest(data_req)) => {
                     info!("Received data request: {:?}", data_req.request_type);
                     // Forward the request to the layered collector
@@ -493,4 +1026,104 @@ impl GrpcClient {
         debug!("Layered metrics stream ended, cleanup guard will abort tasks");
         Ok(())
     }
+
+    /// Send a recorded stream, at `speed` times the recorded pace and
+    /// `repeat` times over, then close the stream. With `retime`, message
+    /// timestamps are moved to the time they are replayed. Returns the number
+    /// of messages sent.
+    pub async fn replay(
+        &mut self,
+        recording: Arc<Vec<RecordedRequest>>,
+        speed: f64,
+        repeat: u32,
+        retime: bool,
+    ) -> Result<u64> {
+        self.send_stream(move |tx| async move {
+            let mut sent = 0u64;
+            for _ in 0..repeat {
+                let start = time::Instant::now();
+                for recorded in recording.iter() {
+                    let Some(mut request) = recorded.request.clone() else {
+                        continue;
+                    };
+                    // Only the first AgentInit opens the stream; later ones
+                    // were recorded after reconnects
+                    if sent > 0
+                        && matches!(
+                            request.request,
+                            Some(metrics_stream_request::Request::AgentInit(_))
+                        )
+                    {
+                        continue;
+                    }
+                    time::sleep_until(start + replay::due(recorded.offset_ms, speed)).await;
+                    if retime && let Some(request) = &mut request.request {
+                        let now = chrono::Utc::now().timestamp_millis();
+                        replay::retime(request, now - recorded.unix_ms as i64);
+                    }
+                    if tx.send(request).await.is_err() {
+                        return sent;
+                    }
+                    sent += 1;
+                }
+            }
+            sent
+        })
+        .await
+    }
+
+    /// Open a stream, send what `producer` writes to its channel until it
+    /// returns, then close the stream. For streams that don't come from this
+    /// host (replays and simulated agents), so commands from the server are
+    /// refused. Returns the result of `producer`.
+    pub async fn send_stream<F, Fut, T>(&mut self, producer: F) -> Result<T>
+    where
+        F: FnOnce(mpsc::Sender<MetricsStreamRequest>) -> Fut,
+        Fut: std::future::Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel::<MetricsStreamRequest>(100);
+        let request_stream = self.counted_stream(ReceiverStream::new(rx));
+        let mut response_stream: Streaming<MetricsStreamResponse> =
+            self.client.stream_metrics(request_stream).await?;
+        let mut sender = tokio::spawn(producer(tx.clone()));
+
+        let result = loop {
+            tokio::select! {
+                result = &mut sender => break result?,
+                response = response_stream.message() => {
+                    let response = match response {
+                        Ok(Some(response)) => response,
+                        Ok(None) => {
+                            sender.abort();
+                            anyhow::bail!("Server closed the stream");
+                        }
+                        Err(e) => {
+                            sender.abort();
+                            return Err(e.into());
+                        }
+                    };
+                    if let Some(metrics_stream_response::Response::Command(cmd)) = response.response {
+                        info!("Refusing command {:?}", cmd.r#type);
+                        let result = CommandResult {
+                            command_id: cmd.command_id,
+                            success: false,
+                            error: "Commands are not run by replayed or simulated agents".to_string(),
+                            ..Default::default()
+                        };
+                        let request = MetricsStreamRequest {
+                            request: Some(metrics_stream_request::Request::CommandResult(result)),
+                        };
+                        let _ = tx.send(request).await;
+                    }
+                }
+            }
+        };
+
+        // Close our side and give the server time to read what is still queued
+        drop(tx);
+        let drain = async { while let Ok(Some(_)) = response_stream.message().await {} };
+        let _ = time::timeout(Duration::from_secs(5), drain).await;
+        Ok(result)
+    }
 }
@@ -1,14 +1,21 @@
+use nanolink_client::protocol::PROTOCOL_VERSION;
 use std::sync::Arc;
 use tracing::{info, warn};
 
 use crate::buffer::RingBuffer;
 use crate::config::Config;
+#[cfg(feature = "executors")]
 use crate::executor::{
-    ConfigManager, DockerExecutor, FileExecutor, LogExecutor, PackageManager, ProcessExecutor,
-    ScriptExecutor, ServiceExecutor, ShellExecutor, UpdateExecutor,
+    AccountAuditExecutor, ChangeOrigin, CleanupExecutor, ConfigManager, DockerExecutor,
+    FileExecutor, MaintenanceExecutor, PackageManager, PowerAction, PowerManager,
+    PrintQueueExecutor, ProcessExecutor, ScriptExecutor, ServiceExecutor, SessionRecorder,
+    ShellExecutor, SpeedTestExecutor,
 };
+use crate::executor::{LogExecutor, UpdateExecutor};
 use crate::proto::{Command, CommandResult, CommandType};
 use crate::security::PermissionChecker;
+use crate::security::capability::CapabilitySet;
+use crate::telemetry::telemetry;
 
 /// Handles incoming commands from the server
 pub struct MessageHandler {
@@ -17,44 +24,93 @@ pub struct MessageHandler {
     #[allow(dead_code)]
     buffer: Arc<RingBuffer>,
     permission_level: u8,
+    /// Server this handler serves, recorded with config changes
+    server: String,
     permission_checker: PermissionChecker,
-    process_executor: ProcessExecutor,
-    service_executor: ServiceExecutor,
-    file_executor: FileExecutor,
-    docker_executor: DockerExecutor,
-    shell_executor: ShellExecutor,
+    capabilities: CapabilitySet,
     update_executor: UpdateExecutor,
     log_executor: LogExecutor,
-    script_executor: ScriptExecutor,
-    config_manager: ConfigManager,
-    package_manager: PackageManager,
+    #[cfg(feature = "executors")]
+    host: HostExecutors,
+}
+
+/// Executors for commands that act on the host
+#[cfg(feature = "executors")]
+struct HostExecutors {
+    process: ProcessExecutor,
+    service: ServiceExecutor,
+    file: FileExecutor,
+    docker: DockerExecutor,
+    shell: ShellExecutor,
+    script: ScriptExecutor,
+    config: ConfigManager,
+    packages: PackageManager,
+    maintenance: MaintenanceExecutor,
+    recorder: SessionRecorder,
+    power: PowerManager,
+    speedtest: SpeedTestExecutor,
+    cleanup: CleanupExecutor,
+    print_queue: PrintQueueExecutor,
+    account_audit: AccountAuditExecutor,
 }
 
 impl MessageHandler {
     /// Create a new message handler
-    pub fn new(config: Arc<Config>, buffer: Arc<RingBuffer>, permission_level: u8) -> Self {
+    pub fn new(
+        config: Arc<Config>,
+        buffer: Arc<RingBuffer>,
+        permission_level: u8,
+        capabilities: CapabilitySet,
+        server: String,
+    ) -> Self {
         Self {
             config: config.clone(),
             buffer,
             permission_level,
+            server,
             permission_checker: PermissionChecker::new(config.clone()),
-            process_executor: ProcessExecutor::new(),
-            service_executor: ServiceExecutor::new(),
-            file_executor: FileExecutor::new(config.clone()),
-            docker_executor: DockerExecutor::new(),
-            shell_executor: ShellExecutor::new(config.clone()),
+            capabilities,
             update_executor: UpdateExecutor::new(config.update.clone()),
             log_executor: LogExecutor::new(),
-            script_executor: ScriptExecutor::new(config.clone()),
-            config_manager: ConfigManager::new(config.clone()),
-            package_manager: PackageManager::new(config.clone()),
+            #[cfg(feature = "executors")]
+            host: HostExecutors {
+                process: ProcessExecutor::new(),
+                service: ServiceExecutor::new(config.clone()),
+                file: FileExecutor::new(config.clone()),
+                docker: DockerExecutor::new(),
+                shell: ShellExecutor::new(config.clone()),
+                script: ScriptExecutor::new(config.clone()),
+                config: ConfigManager::new(config.clone()),
+                packages: PackageManager::new(config.clone()),
+                maintenance: MaintenanceExecutor::new(config.clone()),
+                recorder: SessionRecorder::new(config.clone()),
+                power: PowerManager::new(config.clone()),
+                speedtest: SpeedTestExecutor::new(config.clone()),
+                cleanup: CleanupExecutor::new(config),
+                print_queue: PrintQueueExecutor::new(),
+                account_audit: AccountAuditExecutor::new(),
+            },
         }
     }
 
     /// Handle a command
     pub async fn handle_command(&self, command: Command) -> CommandResult {
-        let command_type =
-            CommandType::try_from(command.r#type).unwrap_or(CommandType::Unspecified);
+        // Commands from a newer server that this agent doesn't know
+        let Ok(command_type) = CommandType::try_from(command.r#type) else {
+            warn!("Unsupported command type {} from server", command.r#type);
+            return CommandResult {
+                command_id: command.command_id,
+                success: false,
+                error: format!(
+                    "Command type {} is not supported by this agent (version {}, protocol {})",
+                    command.r#type,
+                    env!("CARGO_PKG_VERSION"),
+                    PROTOCOL_VERSION
+                ),
+                ..Default::default()
+            };
+        };
+        telemetry().record_command(command_type.as_str_name());
 
         info!(
             "Received command: {:?} (target: {}, id: {})",
@@ -85,25 +141,98 @@ impl MessageHandler {
             };
         }
 
+        // Check negotiated capabilities
+        if let Err(e) = self.capabilities.check_command(command_type) {
+            warn!(
+                "Capability check failed for command {:?}: {}",
+                command_type, e
+            );
+            return CommandResult {
+                command_id: command.command_id,
+                success: false,
+                output: String::new(),
+                error: e,
+                ..Default::default()
+            };
+        }
+
+        // Silence with reject_commands refuses anything that changes the host
+        if let Some(error) = crate::silence::rejection(command_type) {
+            warn!("Rejected command {:?}: {}", command_type, error);
+            return CommandResult {
+                command_id: command.command_id,
+                success: false,
+                error,
+                ..Default::default()
+            };
+        }
+
         // Execute command
         let result = match command_type {
+            // Agent update commands
+            CommandType::AgentCheckUpdate => self.update_executor.check_update().await,
+            CommandType::AgentDownloadUpdate => {
+                self.update_executor.download_update(&command.params).await
+            }
+            CommandType::AgentApplyUpdate => {
+                self.update_executor.apply_update(&command.params).await
+            }
+            CommandType::AgentGetVersion => self.update_executor.get_version().await,
+
+            // Log query commands
+            CommandType::ServiceLogs => self.log_executor.get_service_logs(&command.params).await,
+            CommandType::SystemLogs => self.log_executor.get_system_logs(&command.params).await,
+            CommandType::AuditLogs => self.log_executor.get_audit_logs(&command.params).await,
+
+            CommandType::SilenceStart => {
+                crate::silence::start_command(&command.params, &self.server)
+            }
+            CommandType::SilenceStop => crate::silence::stop_command(&self.server),
+
+            _ => self.execute_on_host(command_type, &command).await,
+        };
+
+        CommandResult {
+            command_id: command.command_id,
+            ..result
+        }
+    }
+
+    /// Run a command that acts on the host
+    #[cfg(feature = "executors")]
+    async fn execute_on_host(&self, command_type: CommandType, command: &Command) -> CommandResult {
+        let host = &self.host;
+        let origin = ChangeOrigin {
+            server: &self.server,
+            command_id: &command.command_id,
+        };
+
+        match command_type {
             // Process management
-            CommandType::ProcessList => self.process_executor.list_processes().await,
+            CommandType::ProcessList => host.process.list_processes().await,
             CommandType::ProcessKill => {
-                self.process_executor
+                host.process
                     .kill_process(&command.target, &command.params)
                     .await
             }
+            CommandType::ProcessTree => host.process.process_tree(&command.target).await,
+            CommandType::ProcessKillTree => {
+                host.process
+                    .kill_tree(&command.target, &command.params)
+                    .await
+            }
 
             // Service management
-            CommandType::ServiceStart => self.service_executor.start_service(&command.target).await,
-            CommandType::ServiceStop => self.service_executor.stop_service(&command.target).await,
-            CommandType::ServiceRestart => {
-                self.service_executor.restart_service(&command.target).await
-            }
-            CommandType::ServiceStatus => {
-                self.service_executor.service_status(&command.target).await
+            CommandType::ServiceStart => host.service.start_service(&command.target).await,
+            CommandType::ServiceStop => host.service.stop_service(&command.target).await,
+            CommandType::ServiceRestart => host.service.restart_service(&command.target).await,
+            CommandType::ServiceStatus => host.service.service_status(&command.target).await,
+            CommandType::ServiceRestartWithDependents => {
+                host.service
+                    .restart_with_dependents(&command.target, &command.params)
+                    .await
             }
+            CommandType::ServiceInventory => host.service.service_inventory(&command.params).await,
 
             // File operations
             CommandType::FileTail => {
@@ -112,90 +241,118 @@ impl MessageHandler {
                     .get("lines")
                     .and_then(|s| s.parse().ok())
                     .unwrap_or(100);
-                self.file_executor.tail_file(&command.target, lines).await
+                host.file.tail_file(&command.target, lines).await
             }
-            CommandType::FileDownload => self.file_executor.download_file(&command.target).await,
+            CommandType::FileDownload => host.file.download_file(&command.target).await,
             CommandType::FileUpload => {
                 let content = command.params.get("content").map(|s| s.as_bytes().to_vec());
-                self.file_executor
-                    .upload_file(&command.target, content)
-                    .await
+                host.file.upload_file(&command.target, content).await
             }
-            CommandType::FileTruncate => self.file_executor.truncate_file(&command.target).await,
+            CommandType::FileTruncate => host.file.truncate_file(&command.target).await,
+            CommandType::FileCleanup => host.cleanup.cleanup(&command.params).await,
 
             // Docker operations
-            CommandType::DockerList => self.docker_executor.list_containers().await,
-            CommandType::DockerStart => self.docker_executor.start_container(&command.target).await,
-            CommandType::DockerStop => self.docker_executor.stop_container(&command.target).await,
-            CommandType::DockerRestart => {
-                self.docker_executor
-                    .restart_container(&command.target)
-                    .await
-            }
+            CommandType::DockerList => host.docker.list_containers().await,
+            CommandType::DockerStart => host.docker.start_container(&command.target).await,
+            CommandType::DockerStop => host.docker.stop_container(&command.target).await,
+            CommandType::DockerRestart => host.docker.restart_container(&command.target).await,
             CommandType::DockerLogs => {
                 let lines = command
                     .params
                     .get("lines")
                     .and_then(|s| s.parse().ok())
                     .unwrap_or(100);
-                self.docker_executor
-                    .container_logs(&command.target, lines)
-                    .await
+                host.docker.container_logs(&command.target, lines).await
             }
 
             // System operations
-            CommandType::SystemReboot => self.execute_system_reboot().await,
+            CommandType::SystemReboot => {
+                host.power
+                    .schedule(PowerAction::Reboot, &command.params)
+                    .await
+            }
+            CommandType::SystemShutdown => {
+                host.power
+                    .schedule(PowerAction::Shutdown, &command.params)
+                    .await
+            }
+            CommandType::SystemPowerCancel => host.power.cancel().await,
 
             // Shell command
             CommandType::ShellExecute => {
-                self.shell_executor
+                let started = chrono::Utc::now();
+                let result = host
+                    .shell
                     .execute(&command.target, &command.super_token)
-                    .await
+                    .await;
+                host.recorder
+                    .record("shell", &command.target, origin.server, started, &result);
+                result
             }
 
-            // Agent update commands
-            CommandType::AgentCheckUpdate => self.update_executor.check_update().await,
-            CommandType::AgentDownloadUpdate => {
-                self.update_executor.download_update(&command.params).await
-            }
-            CommandType::AgentApplyUpdate => {
-                self.update_executor.apply_update(&command.params).await
-            }
-            CommandType::AgentGetVersion => self.update_executor.get_version().await,
-
-            // Log query commands
-            CommandType::ServiceLogs => self.log_executor.get_service_logs(&command.params).await,
-            CommandType::SystemLogs => self.log_executor.get_system_logs(&command.params).await,
-            CommandType::AuditLogs => self.log_executor.get_audit_logs(&command.params).await,
-
             // Script execution commands
-            CommandType::ScriptList => self.script_executor.list_scripts(&command.params).await,
+            CommandType::ScriptList => host.script.list_scripts(&command.params).await,
             CommandType::ScriptExecute => {
-                self.script_executor.execute_script(&command.params).await
+                let started = chrono::Utc::now();
+                let result = host.script.execute_script(&command.params).await;
+                let script = [command.params.get("name"), command.params.get("args")]
+                    .into_iter()
+                    .flatten()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                host.recorder
+                    .record("script", &script, origin.server, started, &result);
+                result
             }
+            CommandType::SessionRecordings => host.recorder.list(&command.params).await,
+            CommandType::SessionExport => host.recorder.export(&command.params).await,
 
             // Config management commands
-            CommandType::ConfigRead => self.config_manager.read_config(&command.params).await,
-            CommandType::ConfigWrite => self.config_manager.write_config(&command.params).await,
-            CommandType::ConfigValidate => {
-                self.config_manager.validate_config(&command.params).await
-            }
+            CommandType::ConfigRead => host.config.read_config(&command.params).await,
+            CommandType::ConfigWrite => host.config.write_config(&command.params, &origin).await,
+            CommandType::ConfigValidate => host.config.validate_config(&command.params).await,
             CommandType::ConfigRollback => {
-                self.config_manager.rollback_config(&command.params).await
-            }
-            CommandType::ConfigListBackups => {
-                self.config_manager.list_backups(&command.params).await
+                host.config.rollback_config(&command.params, &origin).await
             }
+            CommandType::ConfigListBackups => host.config.list_backups(&command.params).await,
+            CommandType::ConfigRender => host.config.render_config(&command.params, &origin).await,
+            CommandType::ConfigDiff => host.config.diff_config(&command.params).await,
 
             // Package management commands
-            CommandType::PackageList => self.package_manager.list_packages(&command.params).await,
-            CommandType::PackageCheckUpdates => {
-                self.package_manager.check_updates(&command.params).await
+            // Updates wait for a maintenance window when enabled
+            CommandType::PackageUpdate
+            | CommandType::SystemUpdate
+            | CommandType::WindowsUpdateInstall
+                if self.config.maintenance.enabled =>
+            {
+                host.maintenance
+                    .enqueue(command_type, &command.params, &origin)
+            }
+            CommandType::PackageList => host.packages.list_packages(&command.params).await,
+            CommandType::PackageCheckUpdates => host.packages.check_updates(&command.params).await,
+            CommandType::PackageUpdate => host.packages.update_package(&command.params).await,
+            CommandType::SystemUpdate => host.packages.system_update(&command.params).await,
+            CommandType::WindowsUpdateStatus => {
+                host.packages.windows_update_status(&command.params).await
+            }
+            CommandType::WindowsUpdateInstall => {
+                host.packages.windows_update_install(&command.params).await
             }
-            CommandType::PackageUpdate => {
-                self.package_manager.update_package(&command.params).await
+            CommandType::MaintenanceStatus => host.maintenance.status().await,
+            CommandType::MaintenanceCancel => host.maintenance.cancel(&command.target).await,
+            // Network diagnostics
+            CommandType::NetworkSpeedtest => host.speedtest.run(&command.params).await,
+
+            // Printing
+            CommandType::PrintQueueClear => {
+                host.print_queue
+                    .clear_queue(&command.target, &command.params)
+                    .await
             }
-            CommandType::SystemUpdate => self.package_manager.system_update(&command.params).await,
+
+            // Security audit
+            CommandType::AccountAudit => host.account_audit.audit(&command.params).await,
 
             _ => CommandResult {
                 command_id: command.command_id.clone(),
@@ -204,57 +361,19 @@ impl MessageHandler {
                 error: format!("Unknown command type: {command_type:?}"),
                 ..Default::default()
             },
-        };
-
-        CommandResult {
-            command_id: command.command_id,
-            ..result
         }
     }
 
-    /// Execute system reboot
-    async fn execute_system_reboot(&self) -> CommandResult {
-        #[cfg(unix)]
-        {
-            match std::process::Command::new("reboot").output() {
-                Ok(output) => CommandResult {
-                    command_id: String::new(),
-                    success: output.status.success(),
-                    output: String::from_utf8_lossy(&output.stdout).to_string(),
-                    error: String::from_utf8_lossy(&output.stderr).to_string(),
-                    ..Default::default()
-                },
-                Err(e) => CommandResult {
-                    command_id: String::new(),
-                    success: false,
-                    output: String::new(),
-                    error: format!("Failed to execute reboot: {}", e),
-                    ..Default::default()
-                },
-            }
-        }
-
-        #[cfg(windows)]
-        {
-            match std::process::Command::new("shutdown")
-                .args(["/r", "/t", "0"])
-                .output()
-            {
-                Ok(output) => CommandResult {
-                    command_id: String::new(),
-                    success: output.status.success(),
-                    output: String::from_utf8_lossy(&output.stdout).to_string(),
-                    error: String::from_utf8_lossy(&output.stderr).to_string(),
-                    ..Default::default()
-                },
-                Err(e) => CommandResult {
-                    command_id: String::new(),
-                    success: false,
-                    output: String::new(),
-                    error: format!("Failed to execute shutdown: {e}"),
-                    ..Default::default()
-                },
-            }
+    #[cfg(not(feature = "executors"))]
+    async fn execute_on_host(&self, command_type: CommandType, command: &Command) -> CommandResult {
+        CommandResult {
+            command_id: command.command_id.clone(),
+            success: false,
+            error: format!(
+                "{} is not available in this build of the agent (built without executors)",
+                command_type.as_str_name()
+            ),
+            ..Default::default()
         }
     }
 }
@@ -1,10 +1,21 @@
 //! Connection management for NanoLink Agent
 //!
 //! Manages gRPC connections to NanoLink servers with automatic reconnection.
+//! Servers without a role each get the full stream. With roles, metrics go
+//! to the primary, or to the standby while the primary is down, and mirrors
+//! only get alerts and events.
 
+pub mod bandwidth;
+mod failover;
 pub mod grpc;
 mod handler;
+pub mod privacy;
+mod queue;
+pub mod replay;
+mod schedule;
+pub mod tls;
 
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::{RwLock, broadcast};
@@ -12,7 +23,8 @@ use tokio::time;
 use tracing::{error, info, warn};
 
 use crate::buffer::RingBuffer;
-use crate::config::{Config, ServerConfig};
+use crate::config::{Config, ServerConfig, ServerRole};
+use crate::telemetry::telemetry;
 
 pub use handler::MessageHandler;
 
@@ -34,6 +46,10 @@ pub struct ConnectionStatus {
     pub last_error: Option<String>,
     pub reconnect_delay_secs: u64,
     pub connection_attempts: u32,
+    #[cfg_attr(not(feature = "management"), allow(dead_code))]
+    pub role: Option<ServerRole>,
+    /// A standby staying disconnected while the primary is up
+    pub idle: bool,
 }
 
 /// Manages gRPC connections to multiple servers
@@ -44,6 +60,8 @@ pub struct ConnectionManager {
     signal_tx: broadcast::Sender<ConnectionSignal>,
     /// Connection status for each server
     status: Arc<RwLock<Vec<ConnectionStatus>>>,
+    /// Config file used to persist trust-on-first-use certificate pins
+    config_path: Option<PathBuf>,
 }
 
 impl ConnectionManager {
@@ -56,10 +74,18 @@ impl ConnectionManager {
             buffer,
             signal_tx,
             status,
+            config_path: None,
         }
     }
 
+    /// Persist first-use TLS certificate pins to this config file
+    pub fn with_config_path(mut self, config_path: PathBuf) -> Self {
+        self.config_path = Some(config_path);
+        self
+    }
+
     /// Get a signal sender for external control
+    #[cfg(feature = "management")]
     pub fn get_signal_sender(&self) -> broadcast::Sender<ConnectionSignal> {
         self.signal_tx.clone()
     }
@@ -86,12 +112,29 @@ impl ConnectionManager {
                     last_error: None,
                     reconnect_delay_secs: self.config.agent.reconnect_delay,
                     connection_attempts: 0,
+                    role: server.role,
+                    idle: server.role == Some(ServerRole::Standby),
                 });
             }
         }
 
+        // Only the stream to the first server is recorded
+        let recorder = self.config.agent.record_stream.as_deref().and_then(|path| {
+            match replay::Recorder::create(path) {
+                Ok(recorder) => {
+                    info!("Recording the metrics stream to {}", path.display());
+                    Some(Arc::new(recorder))
+                }
+                Err(e) => {
+                    warn!("{:#}", e);
+                    None
+                }
+            }
+        });
+
         // Spawn gRPC connection tasks for each server
         let mut handles = Vec::new();
+        let primary = Arc::new(failover::PrimaryState::new());
 
         for (idx, server_config) in self.config.servers.iter().enumerate() {
             let config = self.config.clone();
@@ -99,11 +142,32 @@ impl ConnectionManager {
             let server = server_config.clone();
             let signal_rx = self.signal_tx.subscribe();
             let status = self.status.clone();
-
-            info!("Connecting to gRPC server: {}:{}", server.host, server.port);
+            let config_path = self.config_path.clone();
+            let recorder = if idx == 0 { recorder.clone() } else { None };
+            let primary = primary.clone();
+
+            match server.role {
+                Some(role) => info!(
+                    "Connecting to {} server: {}",
+                    role.as_str(),
+                    server.address()
+                ),
+                None => info!("Connecting to gRPC server: {}", server.address()),
+            }
 
             let handle = tokio::spawn(async move {
-                Self::manage_grpc_connection(config, buffer, server, signal_rx, status, idx).await;
+                Self::manage_grpc_connection(
+                    config,
+                    buffer,
+                    server,
+                    signal_rx,
+                    status,
+                    idx,
+                    config_path,
+                    recorder,
+                    primary,
+                )
+                .await;
             });
 
             handles.push(handle);
@@ -116,13 +180,17 @@ impl ConnectionManager {
     }
 
     /// Manage a gRPC connection with reconnection logic
+    #[allow(clippy::too_many_arguments)]
     async fn manage_grpc_connection(
         config: Arc<Config>,
         buffer: Arc<RingBuffer>,
-        server: ServerConfig,
+        mut server: ServerConfig,
         mut signal_rx: broadcast::Receiver<ConnectionSignal>,
         status: Arc<RwLock<Vec<ConnectionStatus>>>,
         status_idx: usize,
+        config_path: Option<PathBuf>,
+        recorder: Option<Arc<replay::Recorder>>,
+        primary: Arc<failover::PrimaryState>,
     ) {
         let initial_delay = config.agent.reconnect_delay;
         let max_delay = config.agent.max_reconnect_delay;
@@ -131,9 +199,45 @@ impl ConnectionManager {
         let mut total_connected_time: u64 = 0;
         let mut was_previously_connected = false;
         let mut reconnect_delay = initial_delay;
+        let standby = server.role == Some(ServerRole::Standby);
+        let mut failed_over = false;
 
         loop {
+            if standby && *primary.subscribe().borrow() {
+                failed_over = false;
+            }
+            if standby && !failed_over {
+                {
+                    let mut s = status.write().await;
+                    if let Some(st) = s.get_mut(status_idx) {
+                        st.idle = true;
+                        st.last_error = None;
+                    }
+                }
+                let mut primary_rx = primary.subscribe();
+                let failover_delay = Duration::from_secs(config.agent.failover_delay);
+                tokio::select! {
+                    _ = failover::primary_down_for(&mut primary_rx, failover_delay) => {}
+                    _ = shutdown_signal(&mut signal_rx) => {
+                        info!("Received shutdown signal, stopping connection manager");
+                        return;
+                    }
+                }
+                warn!(
+                    "Primary server down for {}s, failing over to standby {}",
+                    failover_delay.as_secs(),
+                    grpc_url
+                );
+                failed_over = true;
+                reconnect_delay = initial_delay;
+                let mut s = status.write().await;
+                if let Some(st) = s.get_mut(status_idx) {
+                    st.idle = false;
+                }
+            }
+
             connection_attempts += 1;
+            telemetry().record_connection_attempt(&server.address(), was_previously_connected);
 
             // Update status
             {
@@ -156,7 +260,10 @@ impl ConnectionManager {
 
             let connect_start = std::time::Instant::now();
             match grpc::GrpcClient::connect(&server, &config).await {
-                Ok(mut client) => {
+                Ok(client) => {
+                    let mut client = client
+                        .with_recorder(recorder.clone())
+                        .with_config_path(config_path.clone());
                     let connect_elapsed = connect_start.elapsed();
                     let connection_start = std::time::Instant::now();
                     info!(
@@ -187,48 +294,125 @@ impl ConnectionManager {
                                 auth.permission_level
                             );
 
-                            // Data compensation: send buffered data if enabled
-                            if config.buffer.data_compensation {
-                                Self::send_compensated_data(&mut client, &buffer, &config).await;
+                            // Trust on first use: pin the certificate of the first
+                            // server that completes a TLS handshake and accepts our token
+                            if server.tls_pin.is_none()
+                                && let Some(fingerprint) = client.tls_fingerprint()
+                            {
+                                info!(
+                                    "Pinning certificate for {}:{} ({})",
+                                    server.host, server.port, fingerprint
+                                );
+                                server.tls_pin = Some(fingerprint.to_string());
+                                if let Some(path) = &config_path
+                                    && let Err(e) = tls::persist_pin(path, &server)
+                                {
+                                    warn!("Failed to persist certificate pin: {}", e);
+                                }
                             }
 
-                            // Start streaming metrics based on config
-                            let stream_result = if config.collector.enable_layered_metrics {
-                                info!("Using layered metrics stream");
-                                // Create MessageHandler with all executors and permission checker
-                                let message_handler = std::sync::Arc::new(MessageHandler::new(
+                            // Without a profile URL, the first server serves the profile
+                            let profile_task = (status_idx == 0
+                                && config.profile.enabled
+                                && config.profile.url.is_none())
+                            .then(|| config_path.clone())
+                            .flatten()
+                            .map(|path| {
+                                let client = client.clone();
+                                tokio::spawn(crate::profile::refresh(
                                     config.clone(),
-                                    buffer.clone(),
-                                    auth.permission_level as u8,
-                                ));
-
-                                client
-                                    .stream_layered_metrics(move |cmd| {
-                                        let handler = message_handler.clone();
-                                        async move { handler.handle_command(cmd).await }
-                                    })
-                                    .await
+                                    path,
+                                    move |current| {
+                                        let mut client = client.clone();
+                                        async move {
+                                            client
+                                                .get_config_profile(current)
+                                                .await
+                                                .map_err(|e| format!("{e:#}"))
+                                        }
+                                    },
+                                ))
+                            });
+
+                            // Start streaming metrics based on config
+                            let stream = async {
+                                if server.role == Some(ServerRole::Mirror) {
+                                    info!("Streaming alerts and events only to mirror server");
+                                    return client.stream_events().await;
+                                }
+                                if config.collector.enable_layered_metrics {
+                                    info!("Using layered metrics stream");
+                                    // Create MessageHandler with all executors and permission checker
+                                    let message_handler = std::sync::Arc::new(MessageHandler::new(
+                                        config.clone(),
+                                        buffer.clone(),
+                                        auth.permission_level as u8,
+                                        client.capabilities().clone(),
+                                        format!("{}:{}", server.host, server.port),
+                                    ));
+
+                                    client
+                                        .stream_layered_metrics(buffer.clone(), move |cmd| {
+                                            let handler = message_handler.clone();
+                                            async move { handler.handle_command(cmd).await }
+                                        })
+                                        .await
+                                } else {
+                                    info!("Using legacy metrics stream");
+                                    // Create MessageHandler with all executors and permission checker
+                                    let message_handler = std::sync::Arc::new(MessageHandler::new(
+                                        config.clone(),
+                                        buffer.clone(),
+                                        auth.permission_level as u8,
+                                        client.capabilities().clone(),
+                                        format!("{}:{}", server.host, server.port),
+                                    ));
+
+                                    client
+                                        .stream_metrics(buffer.clone(), move |cmd| {
+                                            let handler = message_handler.clone();
+                                            async move { handler.handle_command(cmd).await }
+                                        })
+                                        .await
+                                }
+                            };
+
+                            let is_primary = server.role == Some(ServerRole::Primary);
+                            if is_primary {
+                                primary.set_up(true);
+                            }
+                            // A standby hands back as soon as the primary is up
+                            let mut handed_back = false;
+                            let stream_result = if standby {
+                                let mut primary_rx = primary.subscribe();
+                                tokio::select! {
+                                    result = stream => result,
+                                    _ = failover::primary_up(&mut primary_rx) => {
+                                        handed_back = true;
+                                        Ok(())
+                                    }
+                                }
                             } else {
-                                info!("Using legacy metrics stream");
-                                // Create MessageHandler with all executors and permission checker
-                                let message_handler = std::sync::Arc::new(MessageHandler::new(
-                                    config.clone(),
-                                    buffer.clone(),
-                                    auth.permission_level as u8,
-                                ));
-
-                                client
-                                    .stream_metrics(buffer.clone(), move |cmd| {
-                                        let handler = message_handler.clone();
-                                        async move { handler.handle_command(cmd).await }
-                                    })
-                                    .await
+                                stream.await
                             };
+                            if is_primary {
+                                primary.set_up(false);
+                            }
 
+                            if let Some(task) = profile_task {
+                                task.abort();
+                            }
                             let connection_duration = connection_start.elapsed();
                             total_connected_time += connection_duration.as_secs();
 
                             match &stream_result {
+                                Ok(_) if handed_back => {
+                                    info!(
+                                        "Primary server is back, standby {} handed back after {:?}",
+                                        grpc_url, connection_duration
+                                    );
+                                    failed_over = false;
+                                }
                                 Ok(_) => {
                                     warn!(
                                         "gRPC stream ended normally for {} after {:?} (server may have closed the connection)",
@@ -274,6 +458,10 @@ impl ConnectionManager {
                             st.connected = false;
                         }
                     }
+                    if standby && !failed_over {
+                        connection_attempts = 0;
+                        continue;
+                    }
 
                     warn!(
                         "gRPC connection to {} lost, will reconnect (total connected time: {}s)",
@@ -339,67 +527,16 @@ impl ConnectionManager {
             reconnect_delay = (reconnect_delay * 2).min(max_delay);
         }
     }
+}
 
-    /// Send compensated (buffered) data after reconnection
-    async fn send_compensated_data(
-        client: &mut grpc::GrpcClient,
-        buffer: &Arc<RingBuffer>,
-        config: &Arc<Config>,
-    ) {
-        let unsynced = buffer.get_unsynced();
-        let count = unsynced.len();
-
-        if count == 0 {
-            info!("No unsynced data to compensate");
-            return;
-        }
-
-        info!(
-            "Starting data compensation: {} unsynced metrics to send",
-            count
-        );
-
-        let batch_size = config.buffer.compensation_batch_size;
-        let mut sent = 0;
-        let mut last_timestamp = buffer.get_last_sync_timestamp();
-
-        for batch in unsynced.chunks(batch_size) {
-            for metrics in batch {
-                match client.report_metrics(metrics.clone()).await {
-                    Ok(_) => {
-                        sent += 1;
-                        if metrics.timestamp > last_timestamp {
-                            last_timestamp = metrics.timestamp;
-                        }
-                    }
-                    Err(e) => {
-                        warn!(
-                            "Failed to send compensated metrics (timestamp: {}): {}",
-                            metrics.timestamp, e
-                        );
-                        // Update sync timestamp to last successful
-                        buffer.set_last_sync_timestamp(last_timestamp);
-                        info!(
-                            "Data compensation interrupted: sent {}/{} metrics",
-                            sent, count
-                        );
-                        return;
-                    }
-                }
-            }
-
-            // Small delay between batches to avoid overwhelming the server
-            if batch.len() == batch_size {
-                time::sleep(Duration::from_millis(50)).await;
-            }
+/// Wait for a shutdown signal, ignoring other signals
+async fn shutdown_signal(signal_rx: &mut broadcast::Receiver<ConnectionSignal>) {
+    loop {
+        match signal_rx.recv().await {
+            Ok(ConnectionSignal::Shutdown) => return,
+            Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+            Err(broadcast::error::RecvError::Closed) => std::future::pending().await,
         }
-
-        // Update sync timestamp after successful compensation
-        buffer.set_last_sync_timestamp(last_timestamp);
-        info!(
-            "Data compensation completed: sent {}/{} metrics",
-            sent, count
-        );
     }
 }
 
@@ -0,0 +1,316 @@
+//! Privacy mode: scrub personal data from outgoing messages
+//!
+//! With `privacy.enabled`, usernames, remote hosts, IP/MAC addresses and the
+//! hostname are rewritten in every metrics message right before it is put on
+//! the stream, so nothing identifying leaves the machine. Hashes and
+//! pseudonyms use HMAC-SHA256 keyed with `privacy.salt` (or the agent ID), so
+//! the same value always maps to the same replacement and servers can still
+//! count distinct users or correlate a source address across messages.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::OnceLock;
+
+use ring::hmac;
+
+use crate::config::{PrivacyConfig, PrivacyMode};
+use crate::proto::{
+    FailedLoginSummary, Metrics, NetworkStaticInfo, PeriodicData, StaticInfo, SystemInfo,
+    UserSession, metrics_stream_request::Request,
+};
+
+static SCRUBBER: OnceLock<Option<Scrubber>> = OnceLock::new();
+
+/// Applies the configured mode to each kind of field
+pub struct Scrubber {
+    key: hmac::Key,
+    usernames: PrivacyMode,
+    remote_hosts: PrivacyMode,
+    ip_addresses: PrivacyMode,
+    mac_addresses: PrivacyMode,
+    hostname: PrivacyMode,
+}
+
+impl Scrubber {
+    pub fn new(config: &PrivacyConfig, agent_id: &str) -> Self {
+        let salt = config.salt.as_deref().unwrap_or(agent_id);
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, salt.as_bytes()),
+            usernames: config.usernames,
+            remote_hosts: config.remote_hosts,
+            ip_addresses: config.ip_addresses,
+            mac_addresses: config.mac_addresses,
+            hostname: config.hostname,
+        }
+    }
+
+    fn digest(&self, kind: &str, value: &str) -> hmac::Tag {
+        let mut ctx = hmac::Context::with_key(&self.key);
+        ctx.update(kind.as_bytes());
+        ctx.update(b"\0");
+        ctx.update(value.as_bytes());
+        ctx.sign()
+    }
+
+    /// Rewrite one value; `None` means the value is dropped
+    fn apply(&self, mode: PrivacyMode, kind: Kind, value: &str) -> Option<String> {
+        if value.is_empty() {
+            return Some(String::new());
+        }
+        match mode {
+            PrivacyMode::Keep => Some(value.to_string()),
+            PrivacyMode::Drop => None,
+            PrivacyMode::Hash => Some(hex(self.digest(kind.label(), value).as_ref())),
+            PrivacyMode::Pseudonymize => Some(self.pseudonym(kind, value)),
+        }
+    }
+
+    fn pseudonym(&self, kind: Kind, value: &str) -> String {
+        let short = |kind: &str, value: &str| hex(&self.digest(kind, value).as_ref()[..4]);
+        match kind {
+            Kind::User => format!("user-{}", short("user", value)),
+            Kind::Host => match value.parse::<IpAddr>() {
+                Ok(_) => self.pseudonym(Kind::Ip, value),
+                Err(_) => format!("host-{}", short("host", value)),
+            },
+            Kind::Ip => {
+                // Keep a prefix length or zone suffix, e.g. "10.1.2.3/24"
+                let split = value.find(['/', '%']).unwrap_or(value.len());
+                let (addr, suffix) = value.split_at(split);
+                match addr.parse::<IpAddr>() {
+                    Ok(ip) if ip.is_loopback() || ip.is_unspecified() => value.to_string(),
+                    Ok(ip) => format!("{}{suffix}", self.pseudo_ip(ip)),
+                    Err(_) => format!("host-{}", short("host", value)),
+                }
+            }
+            Kind::Mac => {
+                let tag = self.digest("mac", &value.to_ascii_lowercase());
+                let b = tag.as_ref();
+                // Locally administered, unicast
+                format!(
+                    "02:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+                    b[0], b[1], b[2], b[3], b[4]
+                )
+            }
+        }
+    }
+
+    /// Same family, inside 10.0.0.0/8 or fd00::/8
+    fn pseudo_ip(&self, ip: IpAddr) -> IpAddr {
+        let tag = self.digest("ip", &ip.to_string());
+        let b = tag.as_ref();
+        match ip {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::new(10, b[0], b[1], b[2])),
+            IpAddr::V6(_) => {
+                let mut octets = [0u8; 16];
+                octets[0] = 0xfd;
+                octets[1..].copy_from_slice(&b[..15]);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+        }
+    }
+
+    fn field(&self, mode: PrivacyMode, kind: Kind, value: &mut String) {
+        *value = self.apply(mode, kind, value).unwrap_or_default();
+    }
+
+    fn list(&self, mode: PrivacyMode, kind: Kind, values: &mut Vec<String>) {
+        *values = values
+            .iter()
+            .filter_map(|v| self.apply(mode, kind, v))
+            .collect();
+    }
+
+    pub fn hostname(&self, hostname: &mut String) {
+        self.field(self.hostname, Kind::Host, hostname);
+    }
+
+    fn user_sessions(&self, sessions: &mut [UserSession]) {
+        for session in sessions {
+            self.field(self.usernames, Kind::User, &mut session.username);
+            self.field(self.remote_hosts, Kind::Host, &mut session.remote_host);
+        }
+    }
+
+    fn failed_logins(&self, summary: &mut FailedLoginSummary) {
+        for source in &mut summary.top_sources {
+            self.field(self.remote_hosts, Kind::Host, &mut source.ip);
+            self.field(self.usernames, Kind::User, &mut source.last_user);
+        }
+    }
+
+    fn system_info(&self, info: &mut SystemInfo) {
+        self.hostname(&mut info.hostname);
+    }
+
+    fn static_networks(&self, networks: &mut [NetworkStaticInfo]) {
+        for net in networks {
+            self.field(self.mac_addresses, Kind::Mac, &mut net.mac_address);
+            self.list(self.ip_addresses, Kind::Ip, &mut net.ip_addresses);
+        }
+    }
+
+    pub fn metrics(&self, metrics: &mut Metrics) {
+        self.hostname(&mut metrics.hostname);
+        if let Some(info) = &mut metrics.system_info {
+            self.system_info(info);
+        }
+        for net in &mut metrics.networks {
+            self.field(self.mac_addresses, Kind::Mac, &mut net.mac_address);
+            self.list(self.ip_addresses, Kind::Ip, &mut net.ip_addresses);
+        }
+        self.user_sessions(&mut metrics.user_sessions);
+        self.field(self.ip_addresses, Kind::Ip, &mut metrics.public_ip);
+        if let Some(summary) = &mut metrics.failed_logins {
+            self.failed_logins(summary);
+        }
+    }
+
+    pub fn static_info(&self, info: &mut StaticInfo) {
+        if let Some(system) = &mut info.system_info {
+            self.system_info(system);
+        }
+        self.static_networks(&mut info.networks);
+        self.field(self.ip_addresses, Kind::Ip, &mut info.public_ip);
+    }
+
+    pub fn periodic(&self, data: &mut PeriodicData) {
+        self.user_sessions(&mut data.user_sessions);
+        for update in &mut data.network_updates {
+            self.list(self.ip_addresses, Kind::Ip, &mut update.ip_addresses);
+        }
+        self.field(self.ip_addresses, Kind::Ip, &mut data.public_ip);
+        for event in &mut data.session_events {
+            self.field(self.usernames, Kind::User, &mut event.username);
+            self.field(self.remote_hosts, Kind::Host, &mut event.remote_host);
+        }
+        if let Some(summary) = &mut data.failed_logins {
+            self.failed_logins(summary);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    User,
+    Host,
+    Ip,
+    Mac,
+}
+
+impl Kind {
+    fn label(self) -> &'static str {
+        match self {
+            Kind::User => "user",
+            Kind::Host => "host",
+            Kind::Ip => "ip",
+            Kind::Mac => "mac",
+        }
+    }
+}
+
+/// Set up privacy mode once at startup
+pub fn init(config: &PrivacyConfig, agent_id: &str) {
+    let scrubber = config.enabled.then(|| Scrubber::new(config, agent_id));
+    let _ = SCRUBBER.set(scrubber);
+}
+
+fn active() -> Option<&'static Scrubber> {
+    SCRUBBER.get().and_then(Option::as_ref)
+}
+
+/// Scrub an outgoing stream request in place
+pub fn scrub(request: &mut Request) {
+    let Some(scrubber) = active() else {
+        return;
+    };
+    match request {
+        Request::Metrics(metrics) => scrubber.metrics(metrics),
+        Request::StaticInfo(info) => scrubber.static_info(info),
+        Request::Periodic(data) => scrubber.periodic(data),
+        _ => {}
+    }
+}
+
+/// Hostname as reported to servers
+pub fn hostname(mut hostname: String) -> String {
+    if let Some(scrubber) = active() {
+        scrubber.hostname(&mut hostname);
+    }
+    hostname
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::{FailedLoginSource, NetworkMetrics};
+
+    fn scrubber(salt: &str) -> Scrubber {
+        let config = PrivacyConfig {
+            enabled: true,
+            salt: Some(salt.to_string()),
+            mac_addresses: PrivacyMode::Drop,
+            hostname: PrivacyMode::Hash,
+            ..Default::default()
+        };
+        Scrubber::new(&config, "agent")
+    }
+
+    #[test]
+    fn test_scrub_metrics() {
+        let mut metrics = Metrics {
+            hostname: "alice-laptop".to_string(),
+            networks: vec![NetworkMetrics {
+                mac_address: "aa:bb:cc:dd:ee:ff".to_string(),
+                ip_addresses: vec![
+                    "192.168.1.20".to_string(),
+                    "fe80::1%eth0".to_string(),
+                    "127.0.0.1".to_string(),
+                ],
+                ..Default::default()
+            }],
+            user_sessions: vec![UserSession {
+                username: "alice".to_string(),
+                remote_host: "203.0.113.9".to_string(),
+                ..Default::default()
+            }],
+            failed_logins: Some(FailedLoginSummary {
+                top_sources: vec![FailedLoginSource {
+                    ip: "203.0.113.9".to_string(),
+                    last_user: "alice".to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let s = scrubber("msp-1");
+        s.metrics(&mut metrics);
+
+        assert_eq!(metrics.hostname.len(), 64);
+        let net = &metrics.networks[0];
+        assert!(net.mac_address.is_empty());
+        assert!(net.ip_addresses[0].starts_with("10."));
+        assert!(net.ip_addresses[1].starts_with("fd") && net.ip_addresses[1].ends_with("%eth0"));
+        assert_eq!(net.ip_addresses[2], "127.0.0.1");
+
+        // Same input, same pseudonym across fields
+        let session = &metrics.user_sessions[0];
+        let source = &metrics.failed_logins.as_ref().unwrap().top_sources[0];
+        assert!(session.username.starts_with("user-"));
+        assert_eq!(session.username, source.last_user);
+        assert_eq!(session.remote_host, source.ip);
+        assert!(session.remote_host.parse::<Ipv4Addr>().is_ok());
+
+        // A different salt gives different pseudonyms
+        let mut other = UserSession {
+            username: "alice".to_string(),
+            ..Default::default()
+        };
+        scrubber("msp-2").user_sessions(std::slice::from_mut(&mut other));
+        assert_ne!(other.username, session.username);
+    }
+}
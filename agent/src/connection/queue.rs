@@ -0,0 +1,373 @@
+//! Bounded priority send queue between the collectors and one server stream
+//!
+//! Collection pushes without waiting for the network, and the stream takes
+//! messages as fast as the server accepts them, highest priority first:
+//! stream control (AgentInit, heartbeats), alerts and events, command
+//! results, periodic data, realtime samples, and last the backfill of
+//! buffered samples after a reconnect. So when bandwidth is short an alert
+//! never waits behind a large backfill upload.
+//!
+//! When a slow server lets the queue fill up, the oldest message that can be
+//! replaced by a newer one (realtime samples, heartbeats, telemetry) is
+//! dropped, lowest priority first. Static info, periodic data, command
+//! results and events are never dropped, so the queue may briefly exceed its
+//! capacity with them. Backfill is paced by its sender and doesn't count
+//! against the capacity. Queue depth and drops are reported in
+//! self-telemetry per server.
+//!
+//! A queue with a [`Meter`] skips the classes its server's bandwidth mode
+//! doesn't allow, instead of queueing them.
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+use parking_lot::Mutex;
+use tokio_stream::Stream;
+
+use super::bandwidth::Meter;
+use crate::proto::{MetricsStreamRequest, metrics_stream_request::Request};
+use crate::telemetry::telemetry;
+
+/// Delivery order of outgoing messages, highest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// AgentInit and heartbeats, which keep the stream alive
+    Control,
+    /// Alerts and events (log batches not from the syslog listener, config
+    /// drift)
+    Event,
+    CommandResult,
+    /// Static info, periodic data, telemetry and relayed syslog
+    Periodic,
+    Realtime,
+    /// Buffered samples resent after a reconnect
+    Backfill,
+}
+
+impl Priority {
+    const COUNT: usize = 6;
+
+    /// Class of a live message
+    pub fn of(request: &MetricsStreamRequest) -> Self {
+        match &request.request {
+            Some(Request::AgentInit(_) | Request::Heartbeat(_)) => Self::Control,
+            Some(Request::LogBatch(batch)) if batch.source != "syslog" => Self::Event,
+            Some(Request::ConfigDrift(_)) => Self::Event,
+            Some(Request::CommandResult(_)) => Self::CommandResult,
+            Some(Request::Realtime(_)) => Self::Realtime,
+            // Periodic samples of the legacy stream; initial ones are full data
+            Some(Request::Metrics(m)) if !m.is_initial => Self::Realtime,
+            _ => Self::Periodic,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// One queue per priority
+    classes: [VecDeque<MetricsStreamRequest>; Priority::COUNT],
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl State {
+    /// Messages counted against the capacity
+    fn live_len(&self) -> usize {
+        self.classes[..Priority::Backfill as usize]
+            .iter()
+            .map(VecDeque::len)
+            .sum()
+    }
+
+    fn len(&self) -> usize {
+        self.classes.iter().map(VecDeque::len).sum()
+    }
+}
+
+/// Messages waiting to be sent to one server
+pub struct SendQueue {
+    state: Mutex<State>,
+    capacity: usize,
+    /// Server address, for telemetry
+    server: String,
+    meter: Option<Arc<Meter>>,
+}
+
+impl SendQueue {
+    /// Queue for `server`; with a `meter`, its bandwidth mode decides which
+    /// classes are sent
+    pub fn new(server: String, capacity: usize, meter: Option<Arc<Meter>>) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(State::default()),
+            capacity: capacity.max(1),
+            server,
+            meter,
+        })
+    }
+
+    /// Whether messages of `priority` are currently sent, or skipped to
+    /// stay within the server's bandwidth cap
+    pub fn accepts(&self, priority: Priority) -> bool {
+        self.meter
+            .as_ref()
+            .is_none_or(|meter| meter.mode().allows(priority))
+    }
+
+    /// Queue a live message; false once the stream has gone away
+    pub fn push(&self, request: MetricsStreamRequest) -> bool {
+        let priority = Priority::of(&request);
+        self.push_as(request, priority)
+    }
+
+    /// Queue a message with an explicit priority
+    pub fn push_as(&self, request: MetricsStreamRequest, priority: Priority) -> bool {
+        if !self.accepts(priority) {
+            return !self.is_closed();
+        }
+        let mut state = self.state.lock();
+        if state.closed {
+            return false;
+        }
+        if priority != Priority::Backfill && state.live_len() >= self.capacity {
+            // Lowest priority first, oldest first within a class
+            let victim = state.classes[..Priority::Backfill as usize]
+                .iter()
+                .enumerate()
+                .rev()
+                .find_map(|(class, queue)| Some((class, queue.iter().position(droppable)?)));
+            match victim {
+                Some((class, oldest)) => {
+                    state.classes[class].remove(oldest);
+                    telemetry().record_queue_drop(&self.server);
+                }
+                None if droppable(&request) => {
+                    telemetry().record_queue_drop(&self.server);
+                    return true;
+                }
+                None => {}
+            }
+        }
+        state.classes[priority as usize].push_back(request);
+        telemetry().record_queue_depth(&self.server, state.len());
+        let waker = state.waker.take();
+        drop(state);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        true
+    }
+
+    /// Messages of `priority` waiting
+    pub fn pending(&self, priority: Priority) -> usize {
+        self.state.lock().classes[priority as usize].len()
+    }
+
+    /// Whether the stream has gone away
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().closed
+    }
+
+    /// Stream of queued messages for the gRPC call. Dropping it closes the queue.
+    pub fn stream(self: &Arc<Self>) -> QueueStream {
+        QueueStream {
+            queue: self.clone(),
+        }
+    }
+
+    fn close(&self) {
+        let mut state = self.state.lock();
+        state.closed = true;
+        state.classes.iter_mut().for_each(VecDeque::clear);
+        telemetry().record_queue_depth(&self.server, 0);
+    }
+}
+
+/// Whether a newer message of the same kind makes this one redundant
+fn droppable(request: &MetricsStreamRequest) -> bool {
+    match &request.request {
+        Some(Request::Realtime(_) | Request::Heartbeat(_) | Request::Telemetry(_)) => true,
+        // Periodic samples of the legacy stream; initial and chunked ones are kept
+        Some(Request::Metrics(m)) => !m.is_initial && m.chunk.is_none(),
+        _ => false,
+    }
+}
+
+/// Receiving end of a [`SendQueue`]
+pub struct QueueStream {
+    queue: Arc<SendQueue>,
+}
+
+impl Stream for QueueStream {
+    type Item = MetricsStreamRequest;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let queue = &self.queue;
+        let mut state = queue.state.lock();
+        if let Some(request) = state.classes.iter_mut().find_map(VecDeque::pop_front) {
+            telemetry().record_queue_depth(&queue.server, state.len());
+            return Poll::Ready(Some(request));
+        }
+        if state.closed {
+            return Poll::Ready(None);
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for QueueStream {
+    fn drop(&mut self) {
+        self.queue.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::{
+        AgentInit, CommandResult, Heartbeat, LogBatch, Metrics, PeriodicData, RealtimeMetrics,
+        StaticInfo,
+    };
+    use tokio_stream::StreamExt;
+
+    fn request(request: Request) -> MetricsStreamRequest {
+        MetricsStreamRequest {
+            request: Some(request),
+        }
+    }
+
+    fn realtime(sequence: u64) -> MetricsStreamRequest {
+        request(Request::Realtime(RealtimeMetrics {
+            sequence,
+            ..Default::default()
+        }))
+    }
+
+    fn events(source: &str) -> MetricsStreamRequest {
+        request(Request::LogBatch(LogBatch {
+            source: source.to_string(),
+            ..Default::default()
+        }))
+    }
+
+    async fn drain(stream: &mut QueueStream, count: usize) -> Vec<Request> {
+        let mut requests = Vec::new();
+        for _ in 0..count {
+            requests.push(stream.next().await.unwrap().request.unwrap());
+        }
+        requests
+    }
+
+    #[test]
+    fn test_priority_classes() {
+        let init = request(Request::AgentInit(AgentInit::default()));
+        assert_eq!(Priority::of(&init), Priority::Control);
+        assert_eq!(Priority::of(&events("alerts")), Priority::Event);
+        assert_eq!(Priority::of(&events("syslog")), Priority::Periodic);
+        let result = request(Request::CommandResult(CommandResult::default()));
+        assert_eq!(Priority::of(&result), Priority::CommandResult);
+        assert_eq!(Priority::of(&realtime(1)), Priority::Realtime);
+        let initial = request(Request::Metrics(Metrics {
+            is_initial: true,
+            ..Default::default()
+        }));
+        assert_eq!(Priority::of(&initial), Priority::Periodic);
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_drops_oldest_realtime() {
+        let queue = SendQueue::new("queue-test:1".to_string(), 3, None);
+        assert!(queue.push(request(Request::StaticInfo(StaticInfo::default()))));
+        assert!(queue.push(realtime(1)));
+        assert!(queue.push(realtime(2)));
+        // Full: the oldest realtime sample makes room
+        assert!(queue.push(realtime(3)));
+        assert!(queue.push(request(Request::Periodic(PeriodicData::default()))));
+        assert!(queue.push(request(Request::CommandResult(CommandResult::default()))));
+        // Nothing left to drop but the new sample itself
+        assert!(queue.push(realtime(4)));
+        assert_eq!(queue.pending(Priority::Realtime), 0);
+        // Never dropped, even over capacity
+        assert!(queue.push(request(Request::Periodic(PeriodicData::default()))));
+        assert_eq!(queue.pending(Priority::Periodic), 3);
+
+        let mut stream = queue.stream();
+        let requests = drain(&mut stream, 4).await;
+        assert!(matches!(requests[0], Request::CommandResult(_)));
+        assert!(matches!(requests[1], Request::StaticInfo(_)));
+        assert!(matches!(requests[2], Request::Periodic(_)));
+        assert!(matches!(requests[3], Request::Periodic(_)));
+
+        let snapshot = telemetry().snapshot();
+        let server = &snapshot.servers["queue-test:1"];
+        assert_eq!(server.queue_dropped, 4);
+        assert_eq!(server.queue_depth, 0);
+    }
+
+    #[tokio::test]
+    async fn test_events_overtake_backfill() {
+        let queue = SendQueue::new("queue-test:3".to_string(), 3, None);
+        for sequence in 1..=5 {
+            let metrics = request(Request::Metrics(Metrics {
+                sequence,
+                ..Default::default()
+            }));
+            assert!(queue.push_as(metrics, Priority::Backfill));
+        }
+        // Backfill doesn't fill the queue
+        assert!(queue.push(realtime(1)));
+        assert!(queue.push(events("alerts")));
+        assert!(queue.push(request(Request::Heartbeat(Heartbeat::default()))));
+
+        let mut stream = queue.stream();
+        let requests = drain(&mut stream, 8).await;
+        assert!(matches!(requests[0], Request::Heartbeat(_)));
+        assert!(matches!(requests[1], Request::LogBatch(_)));
+        assert!(matches!(requests[2], Request::Realtime(_)));
+        for (i, request) in requests[3..].iter().enumerate() {
+            assert!(matches!(request, Request::Metrics(m) if m.sequence == i as u64 + 1));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_capped_server_gets_events_only() {
+        let server: crate::config::ServerConfig = serde_json::from_str(
+            r#"{"host": "queue-test", "port": 4, "token": "t", "bandwidth_cap": {"daily_mb": 1}}"#,
+        )
+        .unwrap();
+        let meter = Meter::new(&server);
+        meter.record(1_000_000);
+        let queue = SendQueue::new(server.address(), 10, Some(meter));
+        assert!(!queue.accepts(Priority::Periodic));
+
+        assert!(queue.push(realtime(1)));
+        assert!(queue.push(request(Request::Periodic(PeriodicData::default()))));
+        assert!(queue.push(events("alerts")));
+        let mut stream = queue.stream();
+        let requests = drain(&mut stream, 1).await;
+        assert!(matches!(requests[0], Request::LogBatch(_)));
+        assert_eq!(queue.pending(Priority::Periodic), 0);
+        assert_eq!(queue.pending(Priority::Realtime), 0);
+    }
+
+    #[tokio::test]
+    async fn test_stream_waits_and_closes() {
+        let queue = SendQueue::new("queue-test:2".to_string(), 10, None);
+        let mut stream = queue.stream();
+        let pusher = {
+            let queue = queue.clone();
+            tokio::spawn(async move {
+                tokio::task::yield_now().await;
+                queue.push(request(Request::Heartbeat(Heartbeat::default())))
+            })
+        };
+        assert!(stream.next().await.is_some());
+        assert!(pusher.await.unwrap());
+
+        drop(stream);
+        assert!(queue.is_closed());
+        assert!(!queue.push(realtime(1)));
+    }
+}
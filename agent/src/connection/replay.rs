@@ -0,0 +1,262 @@
+//! Record and replay of the outgoing metric stream
+//!
+//! With `agent.record_stream` set, every message sent on the stream to the
+//! first server is written to that file along with when it was sent.
+//! `nanolink-agent replay FILE` sends a recording to a server again, at the
+//! recorded pace or faster, so dashboards can be developed and servers load
+//! tested without live hosts.
+//!
+//! A recording is a sequence of length-delimited `RecordedRequest` protobuf
+//! messages, so a recording cut short by a crash stays readable up to the
+//! last complete message. With `security.at_rest.enabled` each message is
+//! sealed on its own (see `utils::at_rest`) before its length is written.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow};
+use parking_lot::Mutex;
+use prost::Message;
+use tracing::warn;
+
+use crate::proto::{MetricsStreamRequest, metrics_stream_request::Request};
+use crate::utils::at_rest::{self, Cipher};
+
+/// Associated data of sealed messages
+const PURPOSE: &str = "stream-recording";
+
+/// One message of a recording
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RecordedRequest {
+    /// Milliseconds since the recording started
+    #[prost(uint64, tag = "1")]
+    pub offset_ms: u64,
+    /// Wall clock time the message was sent (Unix ms)
+    #[prost(uint64, tag = "2")]
+    pub unix_ms: u64,
+    #[prost(message, optional, tag = "3")]
+    pub request: Option<MetricsStreamRequest>,
+}
+
+/// Writes the outgoing stream to a file
+pub struct Recorder {
+    started: Instant,
+    file: Mutex<BufWriter<File>>,
+    cipher: Option<&'static Cipher>,
+}
+
+impl Recorder {
+    /// Start a recording, replacing the file if it exists
+    pub fn create(path: &Path) -> Result<Self> {
+        Self::with_cipher(path, at_rest::cipher())
+    }
+
+    fn with_cipher(path: &Path, cipher: Option<&'static Cipher>) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create recording {}", path.display()))?;
+        Ok(Self {
+            started: Instant::now(),
+            file: Mutex::new(BufWriter::new(file)),
+            cipher,
+        })
+    }
+
+    /// Append one message. Each message is flushed so the recording can be
+    /// replayed while the agent is still running.
+    pub fn record(&self, request: &MetricsStreamRequest) {
+        let recorded = RecordedRequest {
+            offset_ms: self.started.elapsed().as_millis() as u64,
+            unix_ms: chrono::Utc::now().timestamp_millis().max(0) as u64,
+            request: Some(request.clone()),
+        };
+        let sealed = match at_rest::seal_with(self.cipher, PURPOSE, &recorded.encode_to_vec()) {
+            Ok(sealed) => sealed,
+            Err(e) => {
+                warn!("Failed to encrypt stream recording: {}", e);
+                return;
+            }
+        };
+        let mut frame = Vec::with_capacity(sealed.len() + 10);
+        prost::encoding::encode_varint(sealed.len() as u64, &mut frame);
+        frame.extend_from_slice(&sealed);
+        let mut file = self.file.lock();
+        let result = file.write_all(&frame).and_then(|_| file.flush());
+        if let Err(e) = result {
+            warn!("Failed to write to stream recording: {}", e);
+        }
+    }
+}
+
+/// Read a recording. A truncated last message is dropped.
+pub fn read(path: &Path) -> Result<Vec<RecordedRequest>> {
+    read_with(path, at_rest::cipher())
+}
+
+fn read_with(path: &Path, cipher: Option<&Cipher>) -> Result<Vec<RecordedRequest>> {
+    let data = std::fs::read(path)
+        .with_context(|| format!("Failed to read recording {}", path.display()))?;
+    let mut buf = data.as_slice();
+    let mut recording = Vec::new();
+    while !buf.is_empty() {
+        let decoded = next_frame(&mut buf).map(|frame| {
+            at_rest::open_with(cipher, PURPOSE, frame)
+                .map(|plain| RecordedRequest::decode(plain.as_slice()))
+        });
+        match decoded {
+            Some(Ok(Ok(recorded))) => recording.push(recorded),
+            Some(Err(e)) => {
+                return Err(anyhow!(e))
+                    .with_context(|| format!("Failed to read recording {}", path.display()));
+            }
+            _ if recording.is_empty() => {
+                anyhow::bail!("{} is not a stream recording", path.display());
+            }
+            _ => {
+                warn!(
+                    "Recording {} ends with an incomplete message, {} messages read",
+                    path.display(),
+                    recording.len()
+                );
+                break;
+            }
+        }
+    }
+    Ok(recording)
+}
+
+/// The next length-delimited message, or `None` if it is cut off
+fn next_frame<'a>(buf: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = usize::try_from(prost::encoding::decode_varint(buf).ok()?).ok()?;
+    if buf.len() < len {
+        return None;
+    }
+    let (frame, rest) = buf.split_at(len);
+    *buf = rest;
+    Some(frame)
+}
+
+/// When a message recorded `offset_ms` into the recording is due, relative
+/// to the start of the replay
+pub fn due(offset_ms: u64, speed: f64) -> Duration {
+    Duration::from_secs_f64(offset_ms as f64 / 1000.0 / speed)
+}
+
+/// Move the timestamps of a recorded message by `shift_ms`, so a replay
+/// appears to be happening now
+pub fn retime(request: &mut Request, shift_ms: i64) {
+    let timestamp = match request {
+        Request::Metrics(m) => &mut m.timestamp,
+        Request::Heartbeat(h) => &mut h.timestamp,
+        Request::Realtime(r) => &mut r.timestamp,
+        Request::StaticInfo(s) => &mut s.timestamp,
+        Request::Periodic(p) => &mut p.timestamp,
+        Request::Telemetry(t) => &mut t.timestamp,
+        Request::ConfigDrift(d) => &mut d.timestamp,
+        Request::CommandResult(_) | Request::AgentInit(_) | Request::LogBatch(_) => return,
+    };
+    *timestamp = timestamp.saturating_add_signed(shift_ms);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::{AgentInit, Heartbeat};
+
+    #[test]
+    fn test_recording_round_trip() {
+        let dir = std::env::temp_dir().join(format!("nanolink-replay-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("stream.rec");
+
+        let recorder = Recorder::create(&path).unwrap();
+        let init = MetricsStreamRequest {
+            request: Some(Request::AgentInit(AgentInit {
+                agent_id: "a1".to_string(),
+                ..Default::default()
+            })),
+        };
+        let heartbeat = MetricsStreamRequest {
+            request: Some(Request::Heartbeat(Heartbeat {
+                timestamp: 1_000,
+                ..Default::default()
+            })),
+        };
+        recorder.record(&init);
+        recorder.record(&heartbeat);
+        drop(recorder);
+
+        // A message cut off by a crash is dropped
+        let mut data = std::fs::read(&path).unwrap();
+        data.extend_from_slice(&[0x20, 0x08]);
+        std::fs::write(&path, &data).unwrap();
+
+        let recording = read(&path).unwrap();
+        assert_eq!(recording.len(), 2);
+        assert_eq!(recording[0].request, Some(init));
+        assert_eq!(recording[1].request, Some(heartbeat));
+        assert!(recording[0].offset_ms <= recording[1].offset_ms);
+
+        std::fs::write(&path, b"not a recording").unwrap();
+        assert!(read(&path).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sealed_recording() {
+        let dir = std::env::temp_dir().join(format!("nanolink-replay-enc-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("stream.rec");
+        let cipher: &'static Cipher = Box::leak(Box::new(Cipher::new(&[7u8; 32]).unwrap()));
+
+        let recorder = Recorder::with_cipher(&path, Some(cipher)).unwrap();
+        let init = MetricsStreamRequest {
+            request: Some(Request::AgentInit(AgentInit {
+                agent_id: "agent-secret-id".to_string(),
+                ..Default::default()
+            })),
+        };
+        recorder.record(&init);
+        recorder.record(&init);
+        drop(recorder);
+
+        let data = std::fs::read(&path).unwrap();
+        assert!(!data.windows(15).any(|w| w == b"agent-secret-id"));
+        let recording = read_with(&path, Some(cipher)).unwrap();
+        assert_eq!(recording.len(), 2);
+        assert_eq!(recording[1].request, Some(init));
+
+        // Without the key, or with another one
+        assert!(read_with(&path, None).is_err());
+        let other = Cipher::new(&[8u8; 32]).unwrap();
+        assert!(read_with(&path, Some(&other)).is_err());
+
+        // A sealed message cut off by a crash is dropped
+        std::fs::write(&path, &data[..data.len() - 3]).unwrap();
+        assert_eq!(read_with(&path, Some(cipher)).unwrap().len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_due_and_retime() {
+        assert_eq!(due(10_000, 1.0), Duration::from_secs(10));
+        assert_eq!(due(10_000, 10.0), Duration::from_secs(1));
+
+        let mut heartbeat = Request::Heartbeat(Heartbeat {
+            timestamp: 1_000,
+            ..Default::default()
+        });
+        retime(&mut heartbeat, 500);
+        let Request::Heartbeat(h) = &heartbeat else {
+            unreachable!()
+        };
+        assert_eq!(h.timestamp, 1_500);
+        retime(&mut heartbeat, -5_000);
+        let Request::Heartbeat(h) = &heartbeat else {
+            unreachable!()
+        };
+        assert_eq!(h.timestamp, 0);
+    }
+}
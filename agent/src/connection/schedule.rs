@@ -0,0 +1,159 @@
+//! Reporting schedules per server
+//!
+//! `servers[].reporting_schedule` sets how often a server gets a realtime
+//! sample by time of day: for example every sample from 08:00 to 20:00 and
+//! one a minute overnight. Samples in between are skipped on the way to that
+//! server only; periodic data, events and commands are not affected.
+
+use std::time::{Duration, Instant};
+
+use chrono::{Local, NaiveDateTime};
+use tracing::{info, warn};
+
+use crate::config::{ReportingWindow, ServerConfig};
+use crate::utils::cron::CronSchedule;
+
+/// Windows of a reporting schedule with their parsed cron expressions
+struct Plan {
+    windows: Vec<(ReportingWindow, CronSchedule)>,
+    interval: Duration,
+}
+
+impl Plan {
+    /// Time between realtime samples at `now`, zero for every sample
+    fn interval_at(&self, now: &NaiveDateTime) -> Duration {
+        self.windows
+            .iter()
+            .find(|(window, schedule)| {
+                schedule
+                    .last_at_or_before(now, window.duration_minutes.saturating_sub(1))
+                    .is_some()
+            })
+            .map_or(self.interval, |(window, _)| {
+                Duration::from_secs(window.interval_secs)
+            })
+    }
+}
+
+/// Decides which realtime samples go to one server
+pub struct Pacer {
+    server: String,
+    plan: Option<Plan>,
+    /// Interval in force, for logging changes
+    current: Option<Duration>,
+    last_sent: Option<Instant>,
+}
+
+impl Pacer {
+    pub fn new(server: &ServerConfig) -> Self {
+        let plan = server.reporting_schedule.as_ref().map(|schedule| Plan {
+            windows: schedule
+                .windows
+                .iter()
+                .filter_map(|window| match window.schedule.parse() {
+                    Ok(cron) => Some((window.clone(), cron)),
+                    Err(e) => {
+                        warn!("Ignoring reporting window {}: {}", window.schedule, e);
+                        None
+                    }
+                })
+                .collect(),
+            interval: Duration::from_secs(schedule.interval_secs),
+        });
+        Self {
+            server: server.address(),
+            plan,
+            current: None,
+            last_sent: None,
+        }
+    }
+
+    /// Whether a realtime sample is due now
+    pub fn due(&mut self) -> bool {
+        let Some(plan) = &self.plan else {
+            return true;
+        };
+        let interval = plan.interval_at(&Local::now().naive_local());
+        self.due_at(interval, Instant::now())
+    }
+
+    fn due_at(&mut self, interval: Duration, now: Instant) -> bool {
+        if self.current != Some(interval) {
+            if interval.is_zero() {
+                info!("Reporting every realtime sample to {}", self.server);
+            } else {
+                info!(
+                    "Reporting a realtime sample every {}s to {}",
+                    interval.as_secs(),
+                    self.server
+                );
+            }
+            self.current = Some(interval);
+        }
+        let due = self
+            .last_sent
+            .is_none_or(|last| now.saturating_duration_since(last) >= interval);
+        if due {
+            self.last_sent = Some(now);
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interval_by_time_of_day() {
+        let server: ServerConfig = serde_yaml::from_str(
+            r#"
+host: h
+token: t
+reporting_schedule:
+  interval_secs: 60
+  windows:
+    - schedule: "0 8 * * mon-fri"
+      duration_minutes: 720
+    - schedule: "0 20 * * *"
+      duration_minutes: 60
+      interval_secs: 10
+"#,
+        )
+        .unwrap();
+        let pacer = Pacer::new(&server);
+        let plan = pacer.plan.as_ref().unwrap();
+        let at = |s| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+
+        // Friday office hours, then the evening window, then overnight
+        assert_eq!(plan.interval_at(&at("2026-10-16 08:00")), Duration::ZERO);
+        assert_eq!(plan.interval_at(&at("2026-10-16 19:59")), Duration::ZERO);
+        assert_eq!(
+            plan.interval_at(&at("2026-10-16 20:30")),
+            Duration::from_secs(10)
+        );
+        assert_eq!(
+            plan.interval_at(&at("2026-10-16 23:00")),
+            Duration::from_secs(60)
+        );
+        // Saturday
+        assert_eq!(
+            plan.interval_at(&at("2026-10-17 10:00")),
+            Duration::from_secs(60)
+        );
+    }
+
+    #[test]
+    fn test_pacer_skips_samples_in_between() {
+        let server: ServerConfig = serde_yaml::from_str("host: h\ntoken: t").unwrap();
+        let mut pacer = Pacer::new(&server);
+        assert!(pacer.due());
+
+        let start = Instant::now();
+        let minute = Duration::from_secs(60);
+        assert!(pacer.due_at(minute, start));
+        assert!(!pacer.due_at(minute, start + Duration::from_secs(30)));
+        assert!(pacer.due_at(minute, start + minute));
+        assert!(pacer.due_at(Duration::ZERO, start + minute));
+    }
+}
@@ -0,0 +1,35 @@
+//! TLS settings of server connections
+//!
+//! Certificate pinning and the crypto provider live in the client library;
+//! this module stores trust-on-first-use pins in the agent config.
+
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::config::{Config, ServerConfig};
+
+#[cfg(feature = "management")]
+pub use nanolink_client::tls::{fingerprint, fips_mode};
+pub use nanolink_client::tls::{init, normalize_fingerprint, self_check, web_client_config};
+
+/// Store (or clear) the certificate pin of a server in the config file
+pub fn persist_pin(config_path: &Path, server: &ServerConfig) -> Result<()> {
+    let mut config = Config::load(config_path)?;
+
+    let entry = config
+        .servers
+        .iter_mut()
+        .find(|s| s.host == server.host && s.port == server.port)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Server {}:{} not found in {}",
+                server.host,
+                server.port,
+                config_path.display()
+            )
+        })?;
+
+    entry.tls_pin = server.tls_pin.clone();
+    config.save(config_path)
+}
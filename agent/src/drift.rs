@@ -0,0 +1,296 @@
+//! Config drift against a fleet baseline
+//!
+//! A server can send a `ConfigBaseline`: the digest each top-level config
+//! section should have. The agent compares its own section digests when a
+//! baseline arrives and every `check_interval_seconds` after that, and sends
+//! a `ConfigDrift` naming the sections that differ. It sends one when a
+//! baseline arrives and again whenever the result changes.
+//!
+//! Digests leave secrets out: tokens, keys, passwords, salts and webhook
+//! URLs are replaced by a marker before hashing. A digest therefore shows
+//! whether a secret is set but says nothing about its value. The per-host
+//! `agent.agent_id` and `agent.hostname` are removed. The config is read
+//! from its files on every check, so hand edits show up before a restart.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
+
+use crate::config::Config;
+use crate::proto::{ConfigBaseline, ConfigDrift};
+
+/// Stands in for a secret that is set
+const SECRET: &str = "<secret>";
+
+/// Check interval when the baseline doesn't set one
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Shortest check interval honored
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Whether the value under `key` in `section` is a secret
+fn is_secret(section: &str, key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    ["token", "key", "password", "secret", "salt"]
+        .iter()
+        .any(|word| key == *word || key.ends_with(&format!("_{word}")))
+        || key == "token_sha256"
+        || key == "tokens"
+        || key == "headers"
+        // Chat webhook URLs carry their credentials
+        || section == "notifications" && key == "url"
+}
+
+/// Replace secrets in `value`, a part of `section`
+fn redact(section: &str, value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if is_secret(section, key) {
+                    if !is_empty(value) {
+                        *value = Value::String(SECRET.to_string());
+                    }
+                } else {
+                    redact(section, value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| redact(section, item)),
+        _ => {}
+    }
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(s) => s.is_empty(),
+        Value::Array(items) => items.is_empty(),
+        Value::Object(map) => map.is_empty(),
+        _ => false,
+    }
+}
+
+/// Digest of every top-level section of `config`
+pub fn section_digests(config: &Config) -> BTreeMap<String, String> {
+    let Ok(Value::Object(sections)) = serde_json::to_value(config) else {
+        return BTreeMap::new();
+    };
+    sections
+        .into_iter()
+        .map(|(name, mut value)| {
+            if name == "agent"
+                && let Value::Object(agent) = &mut value
+            {
+                agent.remove("agent_id");
+                agent.remove("hostname");
+            }
+            redact(&name, &mut value);
+            // serde_json keeps object keys sorted, so this is canonical
+            let json = value.to_string();
+            let digest: String = Sha256::digest(json.as_bytes())
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect();
+            (name, format!("sha256:{digest}"))
+        })
+        .collect()
+}
+
+/// Compare `digests` with `baseline`
+pub fn compare(baseline: &ConfigBaseline, digests: &BTreeMap<String, String>) -> ConfigDrift {
+    let mut drift = ConfigDrift {
+        baseline_id: baseline.baseline_id.clone(),
+        timestamp: chrono::Utc::now().timestamp_millis() as u64,
+        ..Default::default()
+    };
+    let expected: BTreeMap<_, _> = baseline.sections.iter().collect();
+    for (section, expected) in expected {
+        match digests.get(section) {
+            Some(digest) if digest.eq_ignore_ascii_case(expected) => {}
+            Some(digest) => {
+                drift.drifted_sections.push(section.clone());
+                drift.digests.insert(section.clone(), digest.clone());
+            }
+            None => drift.unknown_sections.push(section.clone()),
+        }
+    }
+    drift
+}
+
+/// The config as its files load now, or `running` if they don't
+fn current_config(running: &Config, path: Option<&PathBuf>) -> Config {
+    match path.map(|path| Config::load(path)) {
+        Some(Ok(config)) => config,
+        Some(Err(e)) => {
+            debug!("Drift check uses the running config: {:#}", e);
+            running.clone()
+        }
+        None => running.clone(),
+    }
+}
+
+/// Check against the latest baseline from `baselines` and hand drift
+/// reports to `report` until it returns false
+pub async fn watch<F>(
+    config: Arc<Config>,
+    config_path: Option<PathBuf>,
+    mut baselines: watch::Receiver<Option<ConfigBaseline>>,
+    mut report: F,
+) where
+    F: FnMut(ConfigDrift) -> bool,
+{
+    let mut last: Option<(Vec<String>, Vec<String>)> = None;
+    let mut new_baseline = false;
+    let mut next_check = tokio::time::Instant::now();
+
+    loop {
+        tokio::select! {
+            changed = baselines.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+                new_baseline = true;
+            }
+            _ = tokio::time::sleep_until(next_check) => {}
+        }
+
+        let Some(baseline) = baselines.borrow_and_update().clone() else {
+            next_check = tokio::time::Instant::now() + DEFAULT_INTERVAL;
+            continue;
+        };
+        let interval = match baseline.check_interval_seconds {
+            0 => DEFAULT_INTERVAL,
+            secs => Duration::from_secs(secs).max(MIN_INTERVAL),
+        };
+        next_check = tokio::time::Instant::now() + interval;
+
+        let (running, path) = (config.clone(), config_path.clone());
+        let digests = match tokio::task::spawn_blocking(move || {
+            section_digests(&current_config(&running, path.as_ref()))
+        })
+        .await
+        {
+            Ok(digests) => digests,
+            Err(e) => {
+                warn!("Config drift check failed: {}", e);
+                continue;
+            }
+        };
+        let drift = compare(&baseline, &digests);
+
+        let result = (
+            drift.drifted_sections.clone(),
+            drift.unknown_sections.clone(),
+        );
+        if !new_baseline && last.as_ref() == Some(&result) {
+            continue;
+        }
+        if drift.drifted_sections.is_empty() {
+            info!("Config matches baseline '{}'", drift.baseline_id);
+        } else {
+            warn!(
+                "Config drifted from baseline '{}': {}",
+                drift.baseline_id,
+                drift.drifted_sections.join(", ")
+            );
+        }
+        if !report(drift) {
+            return;
+        }
+        last = Some(result);
+        new_baseline = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digests_hide_secrets_and_identity() {
+        let mut config = Config::sample();
+        let digests = section_digests(&config);
+        assert!(digests["collector"].starts_with("sha256:"));
+
+        // Per-host identity doesn't count
+        config.agent.agent_id = Some("another-agent".to_string());
+        config.agent.hostname = Some("another-host".to_string());
+        assert_eq!(section_digests(&config)["agent"], digests["agent"]);
+
+        // Nor does a secret's value, only whether it is set
+        config.servers[0].token = "another-token".to_string();
+        assert_eq!(section_digests(&config)["servers"], digests["servers"]);
+        config.shell.super_token = None;
+        config.shell.timeout_seconds += 1;
+        assert_ne!(section_digests(&config)["shell"], digests["shell"]);
+    }
+
+    #[test]
+    fn test_redact() {
+        let mut value = serde_json::json!({
+            "webhooks": [{"url": "https://hooks.slack.com/T0/B0/x", "min_level": "info"}],
+            "opsgenie": [{"api_key": "k", "region": "eu"}],
+            "salt": "",
+        });
+        redact("notifications", &mut value);
+        assert_eq!(value["webhooks"][0]["url"], SECRET);
+        assert_eq!(value["webhooks"][0]["min_level"], "info");
+        assert_eq!(value["opsgenie"][0]["api_key"], SECRET);
+        assert_eq!(value["salt"], "");
+    }
+
+    #[test]
+    fn test_compare() {
+        let config = Config::sample();
+        let digests = section_digests(&config);
+        let mut baseline = ConfigBaseline {
+            baseline_id: "fleet-v3".to_string(),
+            sections: [
+                ("collector".to_string(), digests["collector"].clone()),
+                ("shell".to_string(), "sha256:00".to_string()),
+                ("future_section".to_string(), "sha256:00".to_string()),
+            ]
+            .into(),
+            check_interval_seconds: 0,
+        };
+
+        let drift = compare(&baseline, &digests);
+        assert_eq!(drift.baseline_id, "fleet-v3");
+        assert_eq!(drift.drifted_sections, ["shell"]);
+        assert_eq!(drift.unknown_sections, ["future_section"]);
+        assert_eq!(drift.digests["shell"], digests["shell"]);
+
+        baseline
+            .sections
+            .retain(|section, _| section == "collector");
+        assert!(compare(&baseline, &digests).drifted_sections.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_watch_reports_on_baseline() {
+        let config = Arc::new(Config::sample());
+        let (tx, rx) = watch::channel(None);
+        let (report_tx, mut report_rx) = tokio::sync::mpsc::unbounded_channel();
+        let task = tokio::spawn(watch(config, None, rx, move |drift| {
+            report_tx.send(drift).is_ok()
+        }));
+
+        tx.send(Some(ConfigBaseline {
+            baseline_id: "b1".to_string(),
+            sections: [("shell".to_string(), "sha256:00".to_string())].into(),
+            check_interval_seconds: 0,
+        }))
+        .unwrap();
+        let drift = report_rx.recv().await.unwrap();
+        assert_eq!(drift.drifted_sections, ["shell"]);
+
+        drop(tx);
+        task.await.unwrap();
+    }
+}
@@ -0,0 +1,1024 @@
+//! Local account audit
+//!
+//! `ACCOUNT_AUDIT` reports local accounts with their password state, the
+//! password policy, members of administrative groups and sudoers rules, and
+//! turns them into findings:
+//!
+//! - critical: accounts that log in without a password, non-root accounts
+//!   with UID 0
+//! - warning: expired passwords, passwords older than the policy allows or
+//!   that never expire although the policy requires changes, NOPASSWD sudo
+//!   rules, and sources the agent could not read
+//! - info: no maximum password age configured
+//!
+//! The policy comes from `/etc/login.defs` (Linux), `pwpolicy` (macOS) or
+//! `net accounts` (Windows). Password hashes are only checked for being set;
+//! reading `/etc/shadow` needs root.
+
+use std::collections::HashMap;
+#[cfg(unix)]
+use std::path::Path;
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+use std::process::Command;
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+use std::time::Duration;
+
+use tracing::info;
+
+use crate::proto::{
+    AccountAudit, AdminGroup, AuditFinding, CommandResult, LocalAccount, PasswordPolicy, SudoRule,
+};
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+use crate::utils::safe_command::exec_with_timeout;
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Groups whose members administer the host, besides `%group` sudoers rules
+#[cfg(target_os = "linux")]
+const ADMIN_GROUPS: &[&str] = &["root", "sudo", "wheel", "admin"];
+
+/// Nesting limit for sudoers includes
+#[cfg(unix)]
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// sudoers alias definitions, which are not rules
+#[cfg_attr(not(any(test, unix)), allow(dead_code))]
+const SUDOERS_ALIASES: &[&str] = &[
+    "User_Alias",
+    "Runas_Alias",
+    "Host_Alias",
+    "Cmnd_Alias",
+    "Cmd_Alias",
+];
+
+/// Account audit executor
+pub struct AccountAuditExecutor;
+
+impl AccountAuditExecutor {
+    /// Create a new account audit executor
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Helper to create an error CommandResult
+    fn error_result(error: String) -> CommandResult {
+        CommandResult {
+            command_id: String::new(),
+            success: false,
+            output: String::new(),
+            error,
+            ..Default::default()
+        }
+    }
+
+    /// Audit local accounts and administrative privileges.
+    ///
+    /// Params: `max_password_age_days` (compliance limit used instead of the
+    /// system policy's maximum age).
+    pub async fn audit(&self, params: &HashMap<String, String>) -> CommandResult {
+        let max_age = match params
+            .get("max_password_age_days")
+            .map(|v| v.parse::<u32>())
+        {
+            None => None,
+            Some(Ok(days)) => Some(days),
+            Some(Err(_)) => {
+                return Self::error_result("Invalid max_password_age_days".to_string());
+            }
+        };
+
+        let mut report = collect();
+        if let Some(days) = max_age {
+            let policy = report.policy.get_or_insert_default();
+            policy.max_age_days = days;
+            policy.source = "command".to_string();
+        }
+        evaluate(&mut report);
+        report.generated_at = chrono::Utc::now().to_rfc3339();
+
+        let count = |severity: &str| {
+            report
+                .findings
+                .iter()
+                .filter(|f| f.severity == severity)
+                .count()
+        };
+        let output = format!(
+            "{} account(s), {} critical and {} warning finding(s)",
+            report.accounts.len(),
+            count("critical"),
+            count("warning")
+        );
+        info!("[AUDIT] Account audit: {}", output);
+        CommandResult {
+            success: true,
+            output,
+            account_audit: Some(report),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for AccountAuditExecutor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn finding(severity: &str, check: &str, subject: &str, message: String) -> AuditFinding {
+    AuditFinding {
+        severity: severity.to_string(),
+        check: check.to_string(),
+        subject: subject.to_string(),
+        message,
+    }
+}
+
+/// Add findings for the collected accounts, policy and sudoers rules
+fn evaluate(report: &mut AccountAudit) {
+    let policy_max = report.policy.as_ref().map_or(0, |p| p.max_age_days);
+    let mut findings = Vec::new();
+
+    if policy_max == 0 {
+        findings.push(finding(
+            "info",
+            "password_policy",
+            "policy",
+            "No maximum password age is configured".to_string(),
+        ));
+    }
+
+    for account in &report.accounts {
+        let name = &account.name;
+        if account.id == "0" && name != "root" {
+            findings.push(finding(
+                "critical",
+                "uid_zero",
+                name,
+                format!("{name} has UID 0 and therefore root privileges"),
+            ));
+        }
+        if !account.enabled {
+            continue;
+        }
+        if account.empty_password {
+            findings.push(finding(
+                "critical",
+                "empty_password",
+                name,
+                format!("{name} can log in without a password"),
+            ));
+        } else if account.password_expired {
+            findings.push(finding(
+                "warning",
+                "password_expired",
+                name,
+                format!("The password of {name} has expired"),
+            ));
+        } else if policy_max > 0 && account.password_age_days > i64::from(policy_max) {
+            findings.push(finding(
+                "warning",
+                "password_age",
+                name,
+                format!(
+                    "The password of {name} is {} days old, the policy allows {policy_max}",
+                    account.password_age_days
+                ),
+            ));
+        } else if policy_max > 0 && account.password_max_age_days == 0 {
+            findings.push(finding(
+                "warning",
+                "password_never_expires",
+                name,
+                format!(
+                    "The password of {name} never expires, the policy requires a change every {policy_max} days"
+                ),
+            ));
+        }
+    }
+
+    for rule in report.sudo_rules.iter().filter(|r| r.nopasswd) {
+        let commands = if rule.all_commands {
+            "any command"
+        } else {
+            "commands"
+        };
+        findings.push(finding(
+            "warning",
+            "sudo_nopasswd",
+            &rule.principal,
+            format!(
+                "{} runs {commands} through sudo without a password ({})",
+                rule.principal, rule.source
+            ),
+        ));
+    }
+
+    report.findings.extend(findings);
+    report.compliant = report.findings.iter().all(|f| f.severity == "info");
+}
+
+/// Finding for a source the audit could not read
+#[cfg_attr(not(any(test, unix)), allow(dead_code))]
+fn incomplete(source: &str, error: impl std::fmt::Display) -> AuditFinding {
+    finding(
+        "warning",
+        "incomplete",
+        source,
+        format!("Could not read {source}: {error}"),
+    )
+}
+
+#[cfg(target_os = "linux")]
+fn collect() -> AccountAudit {
+    let mut findings = Vec::new();
+    let mut read = |path: &str| match std::fs::read_to_string(path) {
+        Ok(text) => Some(text),
+        Err(e) => {
+            findings.push(incomplete(path, e));
+            None
+        }
+    };
+    let (policy, uid_min) = parse_login_defs(&read("/etc/login.defs").unwrap_or_default());
+    let passwd = read("/etc/passwd").unwrap_or_default();
+    let shadow = read("/etc/shadow");
+    let group = read("/etc/group").unwrap_or_default();
+
+    let today = chrono::Utc::now().timestamp() / 86400;
+    let mut accounts = parse_accounts(&passwd, shadow.as_deref(), uid_min, today);
+
+    let mut sudo_rules = Vec::new();
+    read_sudoers(Path::new("/etc/sudoers"), 0, &mut sudo_rules, &mut findings);
+
+    let mut groups: Vec<String> = ADMIN_GROUPS.iter().map(|g| g.to_string()).collect();
+    groups.extend(sudo_groups(&sudo_rules));
+    let admin_groups = parse_admin_groups(&group, &passwd, &groups);
+    mark_admins(&mut accounts, &admin_groups, &sudo_rules);
+
+    AccountAudit {
+        policy: Some(policy),
+        accounts,
+        admin_groups,
+        sudo_rules,
+        findings,
+        ..Default::default()
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn collect() -> AccountAudit {
+    let run = |program: &str, args: &[&str]| {
+        let mut cmd = Command::new(program);
+        cmd.args(args);
+        exec_with_timeout(cmd, COMMAND_TIMEOUT)
+            .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+            .unwrap_or_default()
+    };
+    let mut findings = Vec::new();
+    let now = chrono::Utc::now().timestamp();
+
+    // pwpolicy stores the maximum age in days as policyAttributeExpiresEveryNDays
+    let policies = run("pwpolicy", &["-getaccountpolicies"]);
+    let policy = PasswordPolicy {
+        max_age_days: plist_value(&policies, "policyAttributeExpiresEveryNDays")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0),
+        source: "pwpolicy".to_string(),
+        ..Default::default()
+    };
+
+    let mut accounts = Vec::new();
+    for line in run("dscl", &[".", "-list", "/Users", "UniqueID"]).lines() {
+        let Some((name, uid)) = line.split_once(char::is_whitespace) else {
+            continue;
+        };
+        let Ok(uid) = uid.trim().parse::<i64>() else {
+            continue;
+        };
+        // Underscore accounts and low UIDs belong to system services
+        if name.starts_with('_') || (uid != 0 && uid < 500) {
+            continue;
+        }
+        let record = run(
+            "dscl",
+            &[
+                ".",
+                "-read",
+                &format!("/Users/{name}"),
+                "UserShell",
+                "AuthenticationAuthority",
+                "accountPolicyData",
+            ],
+        );
+        let shell = record
+            .lines()
+            .find_map(|l| l.strip_prefix("UserShell:"))
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        let last_set = plist_value(&record, "passwordLastSetTime")
+            .and_then(|v| v.parse::<f64>().ok())
+            .map(|t| t as i64);
+        accounts.push(LocalAccount {
+            name: name.to_string(),
+            id: uid.to_string(),
+            enabled: !record.contains("DisabledUser"),
+            interactive: !is_nologin_shell(&shell),
+            password_age_days: last_set.map_or(-1, |t| (now - t) / 86400),
+            password_last_set: last_set
+                .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+                .map(|t| t.date_naive().to_string())
+                .unwrap_or_default(),
+            shell,
+            ..Default::default()
+        });
+    }
+
+    let membership = run("dscl", &[".", "-read", "/Groups/admin", "GroupMembership"]);
+    let admin_groups = vec![AdminGroup {
+        name: "admin".to_string(),
+        members: membership
+            .strip_prefix("GroupMembership:")
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(String::from)
+            .collect(),
+    }];
+
+    let mut sudo_rules = Vec::new();
+    read_sudoers(Path::new("/etc/sudoers"), 0, &mut sudo_rules, &mut findings);
+    mark_admins(&mut accounts, &admin_groups, &sudo_rules);
+
+    AccountAudit {
+        policy: Some(policy),
+        accounts,
+        admin_groups,
+        sudo_rules,
+        findings,
+        ..Default::default()
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn collect() -> AccountAudit {
+    use serde_json::Value;
+
+    const SCRIPT: &str = r#"
+$ErrorActionPreference = 'SilentlyContinue'
+$date = { param($d) if ($d) { $d.ToUniversalTime().ToString('o') } else { '' } }
+[pscustomobject]@{
+  users = @(Get-LocalUser | ForEach-Object {
+    [pscustomobject]@{ name = $_.Name; sid = [string]$_.SID; enabled = $_.Enabled; required = $_.PasswordRequired
+      last_set = (& $date $_.PasswordLastSet); expires = (& $date $_.PasswordExpires) }
+  })
+  admin_group = (Get-LocalGroup -SID 'S-1-5-32-544').Name
+  admins = @(Get-LocalGroupMember -SID 'S-1-5-32-544' | ForEach-Object { [string]$_.Name })
+  policy = ((net accounts) -join "`n")
+} | ConvertTo-Json -Depth 3 -Compress
+"#;
+    let mut cmd = Command::new("powershell");
+    cmd.args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT]);
+    let Some(json) = exec_with_timeout(cmd, COMMAND_TIMEOUT)
+        .and_then(|o| serde_json::from_slice::<Value>(&o.stdout).ok())
+    else {
+        return AccountAudit {
+            findings: vec![finding(
+                "warning",
+                "incomplete",
+                "Get-LocalUser",
+                "Could not read the local accounts".to_string(),
+            )],
+            ..Default::default()
+        };
+    };
+
+    let (accounts, admin_group) = parse_windows_accounts(&json, chrono::Utc::now());
+    AccountAudit {
+        policy: Some(parse_net_accounts(
+            json["policy"].as_str().unwrap_or_default(),
+        )),
+        accounts,
+        admin_groups: vec![admin_group],
+        ..Default::default()
+    }
+}
+
+#[cfg_attr(not(any(test, unix)), allow(dead_code))]
+fn is_nologin_shell(shell: &str) -> bool {
+    let program = shell.rsplit('/').next().unwrap_or_default();
+    matches!(program, "nologin" | "false" | "sync" | "shutdown" | "halt")
+}
+
+/// Password policy and the first regular UID from `/etc/login.defs`
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn parse_login_defs(text: &str) -> (PasswordPolicy, u32) {
+    let value = |key: &str| {
+        text.lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                (fields.next() == Some(key))
+                    .then(|| fields.next()?.parse::<u32>().ok())
+                    .flatten()
+            })
+            .next_back()
+    };
+    // 99999 days is the conventional "never"
+    let max_age = value("PASS_MAX_DAYS").filter(|&d| d < 99999).unwrap_or(0);
+    let policy = PasswordPolicy {
+        max_age_days: max_age,
+        min_age_days: value("PASS_MIN_DAYS").unwrap_or(0),
+        warn_days: value("PASS_WARN_AGE").unwrap_or(0),
+        min_length: value("PASS_MIN_LEN").unwrap_or(0),
+        source: "/etc/login.defs".to_string(),
+    };
+    (policy, value("UID_MIN").unwrap_or(1000))
+}
+
+/// Accounts from `/etc/passwd` and `/etc/shadow`; `today` in days since the
+/// epoch. Service accounts that cannot log in are left out.
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn parse_accounts(
+    passwd: &str,
+    shadow: Option<&str>,
+    uid_min: u32,
+    today: i64,
+) -> Vec<LocalAccount> {
+    let shadow: HashMap<&str, Vec<&str>> = shadow
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            (fields.len() >= 8).then(|| (fields[0], fields))
+        })
+        .collect();
+
+    passwd
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            if fields.len() < 7 {
+                return None;
+            }
+            let (name, uid, shell) = (fields[0], fields[2].parse::<u32>().ok()?, fields[6]);
+            let interactive = !is_nologin_shell(shell);
+            let mut account = LocalAccount {
+                name: name.to_string(),
+                id: uid.to_string(),
+                interactive,
+                password_age_days: -1,
+                shell: shell.to_string(),
+                ..Default::default()
+            };
+
+            match shadow.get(name) {
+                Some(entry) => {
+                    let hash = entry[1];
+                    account.empty_password = hash.is_empty();
+                    account.enabled = !hash.starts_with('!') && !hash.starts_with('*');
+                    match entry[2].parse::<i64>() {
+                        // Forced change at the next login
+                        Ok(0) => account.password_expired = true,
+                        Ok(changed) => {
+                            account.password_age_days = today - changed;
+                            account.password_last_set =
+                                chrono::DateTime::from_timestamp(changed * 86400, 0)
+                                    .map(|t| t.date_naive().to_string())
+                                    .unwrap_or_default();
+                        }
+                        Err(_) => {}
+                    }
+                    account.password_max_age_days = entry[4]
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|&d| d < 99999)
+                        .unwrap_or(0);
+                    if account.password_max_age_days > 0
+                        && account.password_age_days > i64::from(account.password_max_age_days)
+                    {
+                        account.password_expired = true;
+                    }
+                    if entry[7].parse::<i64>().is_ok_and(|expire| expire <= today) {
+                        account.enabled = false;
+                    }
+                }
+                // Without shadow data only an empty passwd field is conclusive
+                None => {
+                    account.empty_password = fields[1].is_empty();
+                    account.enabled = interactive;
+                }
+            }
+
+            let regular = uid == 0 || (uid >= uid_min && uid != 65534);
+            (regular || interactive || account.enabled).then_some(account)
+        })
+        .collect()
+}
+
+/// Members of `names` from `/etc/group`, with users whose primary group it is
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn parse_admin_groups(group: &str, passwd: &str, names: &[String]) -> Vec<AdminGroup> {
+    let mut groups: Vec<AdminGroup> = Vec::new();
+    for line in group.lines() {
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields.len() < 4
+            || !names.iter().any(|n| n == fields[0])
+            || groups.iter().any(|g| g.name == fields[0])
+        {
+            continue;
+        }
+        let mut members: Vec<String> = fields[3]
+            .split(',')
+            .filter(|m| !m.is_empty())
+            .map(String::from)
+            .collect();
+        members.extend(passwd.lines().filter_map(|user| {
+            let user: Vec<&str> = user.split(':').collect();
+            (user.len() >= 4 && user[3] == fields[2]).then(|| user[0].to_string())
+        }));
+        members.sort();
+        members.dedup();
+        groups.push(AdminGroup {
+            name: fields[0].to_string(),
+            members,
+        });
+    }
+    groups
+}
+
+/// Groups granted sudo through `%group` principals
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn sudo_groups(rules: &[SudoRule]) -> Vec<String> {
+    rules
+        .iter()
+        .flat_map(|r| r.principal.split(','))
+        .filter_map(|p| p.trim().strip_prefix('%'))
+        .map(String::from)
+        .collect()
+}
+
+#[cfg_attr(not(any(test, unix)), allow(dead_code))]
+fn mark_admins(accounts: &mut [LocalAccount], groups: &[AdminGroup], rules: &[SudoRule]) {
+    for account in accounts {
+        let name = account.name.as_str();
+        account.admin = account.id == "0"
+            || groups.iter().any(|g| g.members.iter().any(|m| m == name))
+            || rules.iter().any(|r| {
+                r.principal
+                    .split(',')
+                    .any(|p| p.trim() == "ALL" || p.trim() == name)
+            });
+    }
+}
+
+/// Read a sudoers file and the files it includes
+#[cfg(unix)]
+fn read_sudoers(
+    path: &Path,
+    depth: usize,
+    rules: &mut Vec<SudoRule>,
+    findings: &mut Vec<AuditFinding>,
+) {
+    let source = path.display().to_string();
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        // sudo isn't installed
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+        Err(e) => {
+            findings.push(incomplete(&source, e));
+            return;
+        }
+    };
+    let (file_rules, includes) = parse_sudoers(&text, &source);
+    rules.extend(file_rules);
+    if depth >= MAX_INCLUDE_DEPTH {
+        return;
+    }
+
+    // Relative includes are resolved against the including file
+    let base = path.parent().unwrap_or(Path::new("/"));
+    for (target, is_dir) in includes {
+        let target = base.join(target);
+        if !is_dir {
+            read_sudoers(&target, depth + 1, rules, findings);
+            continue;
+        }
+        let Ok(entries) = std::fs::read_dir(&target) else {
+            continue;
+        };
+        // sudo skips names ending in '~' or containing '.'
+        let mut files: Vec<_> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| !n.ends_with('~') && !n.contains('.'))
+            })
+            .collect();
+        files.sort();
+        for file in files {
+            read_sudoers(&file, depth + 1, rules, findings);
+        }
+    }
+}
+
+/// Rules and includes (`(path, is_dir)`) of one sudoers file
+#[cfg_attr(not(any(test, unix)), allow(dead_code))]
+fn parse_sudoers(text: &str, source: &str) -> (Vec<SudoRule>, Vec<(String, bool)>) {
+    let mut rules = Vec::new();
+    let mut includes = Vec::new();
+    let mut pending = String::new();
+
+    for line in text.lines() {
+        let line = line.trim_end();
+        if let Some(part) = line.strip_suffix('\\') {
+            pending.push_str(part);
+            pending.push(' ');
+            continue;
+        }
+        pending.push_str(line);
+        let entry = std::mem::take(&mut pending);
+        let entry = entry.trim();
+
+        if let Some(dir) = entry
+            .strip_prefix("#includedir")
+            .or_else(|| entry.strip_prefix("@includedir"))
+        {
+            includes.push((dir.trim().to_string(), true));
+            continue;
+        }
+        if let Some(file) = entry
+            .strip_prefix("#include")
+            .or_else(|| entry.strip_prefix("@include"))
+        {
+            includes.push((file.trim().to_string(), false));
+            continue;
+        }
+        // "#1000" is a UID, not a comment
+        let comment =
+            entry.starts_with('#') && !entry[1..].starts_with(|c: char| c.is_ascii_digit());
+        if entry.is_empty()
+            || comment
+            || entry.starts_with("Defaults")
+            || SUDOERS_ALIASES.iter().any(|a| entry.starts_with(a))
+        {
+            continue;
+        }
+
+        // <users> <hosts>=(<runas>) [TAG:] <commands>
+        let Some((users_hosts, spec)) = entry.split_once('=') else {
+            continue;
+        };
+        let Some((principal, _hosts)) = users_hosts.trim().rsplit_once(char::is_whitespace) else {
+            continue;
+        };
+        rules.push(SudoRule {
+            principal: principal.trim().to_string(),
+            rule: entry.to_string(),
+            source: source.to_string(),
+            nopasswd: spec.contains("NOPASSWD:"),
+            all_commands: spec.split(',').any(|c| sudo_command(c) == "ALL"),
+        });
+    }
+    (rules, includes)
+}
+
+/// Command of a sudoers command spec without its runas list and tags
+#[cfg_attr(not(any(test, unix)), allow(dead_code))]
+fn sudo_command(spec: &str) -> &str {
+    let mut spec = spec.trim();
+    if spec.starts_with('(')
+        && let Some(end) = spec.find(')')
+    {
+        spec = spec[end + 1..].trim();
+    }
+    while let Some((tag, rest)) = spec.split_once(':') {
+        if tag.is_empty() || !tag.chars().all(|c| c.is_ascii_uppercase() || c == '_') {
+            break;
+        }
+        spec = rest.trim();
+    }
+    spec
+}
+
+/// Value following `<key>name</key>` in a property list
+#[cfg(target_os = "macos")]
+fn plist_value<'a>(plist: &'a str, key: &str) -> Option<&'a str> {
+    let rest = &plist[plist.find(&format!("<key>{key}</key>"))?..];
+    let rest = &rest[rest.find("</key>")? + 6..];
+    let start = rest.find('>')? + 1;
+    let end = start + rest[start..].find('<')?;
+    Some(rest[start..end].trim())
+}
+
+/// Password policy from `net accounts`
+#[cfg_attr(not(any(test, target_os = "windows")), allow(dead_code))]
+fn parse_net_accounts(text: &str) -> PasswordPolicy {
+    let value = |label: &str| {
+        text.lines()
+            .find(|l| l.trim_start().starts_with(label))
+            .and_then(|l| l.split_whitespace().next_back())
+            // "Unlimited" and "None" parse as 0
+            .map(|v| v.parse::<u32>().unwrap_or(0))
+            .unwrap_or(0)
+    };
+    PasswordPolicy {
+        max_age_days: value("Maximum password age"),
+        min_age_days: value("Minimum password age"),
+        warn_days: 0,
+        min_length: value("Minimum password length"),
+        source: "net accounts".to_string(),
+    }
+}
+
+/// Local users and the Administrators group from the audit script
+#[cfg_attr(not(any(test, target_os = "windows")), allow(dead_code))]
+fn parse_windows_accounts(
+    json: &serde_json::Value,
+    now: chrono::DateTime<chrono::Utc>,
+) -> (Vec<LocalAccount>, AdminGroup) {
+    use serde_json::Value;
+
+    // ConvertTo-Json unwraps single-item arrays
+    let items = |value: &Value| match value {
+        Value::Array(items) => items.clone(),
+        Value::Null => Vec::new(),
+        item => vec![item.clone()],
+    };
+    let text = |value: &Value| value.as_str().unwrap_or_default().to_string();
+    let date = |value: &Value| {
+        chrono::DateTime::parse_from_rfc3339(value.as_str().unwrap_or_default())
+            .ok()
+            .map(|d| d.with_timezone(&chrono::Utc))
+    };
+
+    // Members are listed as HOST\name
+    let admins: Vec<String> = items(&json["admins"]).iter().map(text).collect();
+    let is_admin = |name: &str| {
+        admins.iter().any(|m| {
+            m.rsplit('\\')
+                .next()
+                .is_some_and(|m| m.eq_ignore_ascii_case(name))
+        })
+    };
+
+    let accounts = items(&json["users"])
+        .iter()
+        .map(|user| {
+            let name = text(&user["name"]);
+            let last_set = date(&user["last_set"]);
+            let expires = date(&user["expires"]);
+            LocalAccount {
+                admin: is_admin(&name),
+                id: text(&user["sid"]),
+                enabled: user["enabled"].as_bool().unwrap_or(false),
+                interactive: true,
+                empty_password: user["required"] == false,
+                password_age_days: last_set.map_or(-1, |t| (now - t).num_days()),
+                password_max_age_days: match (last_set, expires) {
+                    (Some(set), Some(expires)) => (expires - set).num_days().max(0) as u32,
+                    _ => 0,
+                },
+                password_expired: expires.is_some_and(|e| e <= now),
+                password_last_set: last_set
+                    .map(|t| t.date_naive().to_string())
+                    .unwrap_or_default(),
+                name,
+                shell: String::new(),
+            }
+        })
+        .collect();
+
+    let group = AdminGroup {
+        name: text(&json["admin_group"]),
+        members: admins,
+    };
+    (accounts, group)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_accounts() {
+        let passwd = "\
+root:x:0:0:root:/root:/bin/bash
+daemon:x:1:1:daemon:/usr/sbin:/usr/sbin/nologin
+toor:x:0:0::/root:/bin/sh
+alice:x:1000:1000:Alice:/home/alice:/bin/bash
+bob:x:1001:1001::/home/bob:/bin/zsh
+svc:x:998:998::/var/lib/svc:/usr/sbin/nologin
+nobody:x:65534:65534::/nonexistent:/usr/sbin/nologin
+";
+        // Day 20000 is 2024-10-04
+        let shadow = "\
+root:!:19000:0:99999:7:::
+daemon:*:19000:0:99999:7:::
+toor::19000:0:99999:7:::
+alice:$6$salt$hash:19900:0:90:7:::
+bob:$y$salt$hash:0:0:99999:7:::
+svc:!:19000::::::19500
+nobody:*:19000:0:99999:7:::
+";
+        let (policy, uid_min) = parse_login_defs(
+            "# comment\nPASS_MAX_DAYS\t90\nPASS_MIN_DAYS 1\nPASS_WARN_AGE 7\nUID_MIN 1000\n",
+        );
+        assert_eq!(
+            (policy.max_age_days, policy.min_age_days, uid_min),
+            (90, 1, 1000)
+        );
+
+        let accounts = parse_accounts(passwd, Some(shadow), uid_min, 20000);
+        let names: Vec<&str> = accounts.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["root", "toor", "alice", "bob"]);
+
+        let root = &accounts[0];
+        assert!(!root.enabled && !root.empty_password);
+        assert_eq!(root.password_max_age_days, 0);
+
+        let toor = &accounts[1];
+        assert!(toor.enabled && toor.empty_password);
+
+        let alice = &accounts[2];
+        assert_eq!(alice.password_age_days, 100);
+        assert_eq!(alice.password_max_age_days, 90);
+        assert!(alice.password_expired);
+        assert_eq!(alice.password_last_set, "2024-06-26");
+
+        let bob = &accounts[3];
+        assert!(bob.password_expired && bob.enabled);
+        assert_eq!(bob.password_age_days, -1);
+
+        // Without /etc/shadow only accounts with a login shell count as enabled
+        let accounts = parse_accounts(passwd, None, uid_min, 20000);
+        assert!(accounts.iter().all(|a| a.enabled == a.interactive));
+    }
+
+    #[test]
+    fn test_parse_sudoers() {
+        let text = "\
+Defaults\tenv_reset
+Defaults:alice !requiretty
+Cmnd_Alias UPDATES = /usr/bin/apt update, /usr/bin/apt upgrade
+# User privilege specification
+root\tALL=(ALL:ALL) ALL
+%sudo ALL=(ALL:ALL) ALL
+deploy ALL=(root) NOPASSWD: UPDATES, \\
+    /usr/bin/systemctl restart app
+#1001 ALL=(ALL) NOPASSWD:ALL
+alice, bob ALL = (ALL) ALL
+#includedir /etc/sudoers.d
+@include extra
+";
+        let (rules, includes) = parse_sudoers(text, "/etc/sudoers");
+        assert_eq!(
+            includes,
+            [
+                ("/etc/sudoers.d".to_string(), true),
+                ("extra".to_string(), false)
+            ]
+        );
+        let principals: Vec<&str> = rules.iter().map(|r| r.principal.as_str()).collect();
+        assert_eq!(
+            principals,
+            ["root", "%sudo", "deploy", "#1001", "alice, bob"]
+        );
+
+        let deploy = &rules[2];
+        assert!(deploy.nopasswd && !deploy.all_commands);
+        assert!(deploy.rule.ends_with("/usr/bin/systemctl restart app"));
+        assert!(rules[3].nopasswd && rules[3].all_commands);
+        assert!(rules[4].all_commands && !rules[4].nopasswd);
+        assert_eq!(sudo_groups(&rules), ["sudo"]);
+
+        let group = "root:x:0:\nsudo:x:27:alice\nwheel:x:10:\nusers:x:100:bob\n";
+        let passwd = "root:x:0:0::/root:/bin/bash\ncarol:x:1002:27::/home/carol:/bin/bash\n";
+        let groups = parse_admin_groups(group, passwd, &["root".into(), "sudo".into()]);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].members, ["root"]);
+        assert_eq!(groups[1].members, ["alice", "carol"]);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let account = |name: &str, id: &str| LocalAccount {
+            name: name.to_string(),
+            id: id.to_string(),
+            enabled: true,
+            password_age_days: 10,
+            password_max_age_days: 90,
+            ..Default::default()
+        };
+        let mut report = AccountAudit {
+            policy: Some(PasswordPolicy {
+                max_age_days: 60,
+                ..Default::default()
+            }),
+            accounts: vec![
+                account("root", "0"),
+                LocalAccount {
+                    empty_password: true,
+                    ..account("toor", "0")
+                },
+                LocalAccount {
+                    password_age_days: 75,
+                    ..account("alice", "1000")
+                },
+                LocalAccount {
+                    password_max_age_days: 0,
+                    ..account("bob", "1001")
+                },
+                LocalAccount {
+                    enabled: false,
+                    empty_password: true,
+                    ..account("locked", "1002")
+                },
+            ],
+            sudo_rules: vec![SudoRule {
+                principal: "deploy".to_string(),
+                source: "/etc/sudoers.d/deploy".to_string(),
+                nopasswd: true,
+                all_commands: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        evaluate(&mut report);
+
+        let checks: Vec<(&str, &str)> = report
+            .findings
+            .iter()
+            .map(|f| (f.check.as_str(), f.subject.as_str()))
+            .collect();
+        assert_eq!(
+            checks,
+            [
+                ("uid_zero", "toor"),
+                ("empty_password", "toor"),
+                ("password_age", "alice"),
+                ("password_never_expires", "bob"),
+                ("sudo_nopasswd", "deploy"),
+            ]
+        );
+        assert!(!report.compliant);
+
+        let mut clean = AccountAudit {
+            accounts: vec![account("root", "0")],
+            ..Default::default()
+        };
+        evaluate(&mut clean);
+        assert_eq!(clean.findings[0].check, "password_policy");
+        assert!(clean.compliant);
+    }
+
+    #[test]
+    fn test_parse_windows_accounts() {
+        let json: serde_json::Value = serde_json::from_str(
+            r#"{
+              "users": [
+                {"name": "Administrator", "sid": "S-1-5-21-1-500", "enabled": true, "required": true,
+                 "last_set": "2026-06-19T08:00:00.0000000Z", "expires": "2026-07-31T08:00:00.0000000Z"},
+                {"name": "kiosk", "sid": "S-1-5-21-1-1001", "enabled": true, "required": false,
+                 "last_set": "", "expires": ""}
+              ],
+              "admin_group": "Administrators",
+              "admins": "PC01\\Administrator"
+            }"#,
+        )
+        .unwrap();
+        let now = chrono::DateTime::parse_from_rfc3339("2026-10-17T08:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let (accounts, group) = parse_windows_accounts(&json, now);
+        assert_eq!(group.name, "Administrators");
+        assert_eq!(group.members, ["PC01\\Administrator"]);
+
+        let admin = &accounts[0];
+        assert!(admin.admin && admin.password_expired && !admin.empty_password);
+        assert_eq!(
+            (admin.password_age_days, admin.password_max_age_days),
+            (120, 42)
+        );
+        assert_eq!(admin.password_last_set, "2026-06-19");
+
+        let kiosk = &accounts[1];
+        assert!(!kiosk.admin && kiosk.empty_password);
+        assert_eq!(
+            (kiosk.password_age_days, kiosk.password_max_age_days),
+            (-1, 0)
+        );
+
+        let policy = parse_net_accounts(
+            "Force user logoff how long after time expires?:       Never\n\
+             Minimum password age (days):                          1\n\
+             Maximum password age (days):                          Unlimited\n\
+             Minimum password length:                              14\n",
+        );
+        assert_eq!(
+            (policy.max_age_days, policy.min_age_days, policy.min_length),
+            (0, 1, 14)
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! Desktop application inventory
+//!
+//! Installed applications as the desktop shows them, independent of package
+//! managers: winget and Chocolatey only know what they installed, so MSI and
+//! vendor setup installs are missing there. Returned by `PACKAGE_LIST` with
+//! `package_manager` "desktop" when `package_management.include_applications`
+//! is set.
+//!
+//! - Windows: Add/Remove Programs entries under the machine-wide (64- and
+//!   32-bit) and every loaded user's `Uninstall` registry keys, without
+//!   system components and updates
+//! - macOS: application bundles in `/Applications` and `~/Applications`,
+//!   from `system_profiler SPApplicationsDataType`
+//! - Linux: desktop software comes from packages, so there is nothing extra
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+use std::process::Command;
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::proto::PackageInfo;
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+use crate::utils::safe_command::exec_with_timeout;
+
+/// `system_profiler` checks every bundle's signature, which takes a while
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Installed applications whose name contains `filter`, at most `limit`
+pub fn list(filter: Option<&str>, limit: usize) -> Result<Vec<PackageInfo>, String> {
+    let applications = scan()?;
+    Ok(applications
+        .into_iter()
+        .filter(|app| filter.is_none_or(|f| app.name.contains(f)))
+        .take(limit)
+        .collect())
+}
+
+#[cfg(target_os = "windows")]
+fn scan() -> Result<Vec<PackageInfo>, String> {
+    const SCRIPT: &str = r#"
+$ErrorActionPreference = 'SilentlyContinue'
+$uninstall = 'Software\Microsoft\Windows\CurrentVersion\Uninstall\*'
+$keys = @(
+  @{ path = "HKLM:\$uninstall"; arch = 'x64' }
+  @{ path = "HKLM:\Software\WOW6432Node\Microsoft\Windows\CurrentVersion\Uninstall\*"; arch = 'x86' }
+)
+Get-ChildItem 'Registry::HKEY_USERS' | Where-Object { $_.PSChildName -match '^S-1-5-21-[\d-]+$' } | ForEach-Object {
+  $keys += @{ path = "Registry::HKEY_USERS\$($_.PSChildName)\$uninstall"; arch = '' }
+}
+@(foreach ($key in $keys) {
+  Get-ItemProperty -Path $key.path | ForEach-Object {
+    [pscustomobject]@{
+      name = [string]$_.DisplayName; version = [string]$_.DisplayVersion; publisher = [string]$_.Publisher
+      date = [string]$_.InstallDate; size = [int64]$_.EstimatedSize; arch = $key.arch
+      msi = ($_.WindowsInstaller -eq 1); system = ($_.SystemComponent -eq 1)
+      parent = [string]$_.ParentKeyName; release = [string]$_.ReleaseType
+    }
+  }
+}) | ConvertTo-Json -Compress
+"#;
+    let mut cmd = Command::new("powershell");
+    cmd.args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT]);
+    let output = exec_with_timeout(cmd, COMMAND_TIMEOUT)
+        .ok_or_else(|| "Failed to read the installed programs".to_string())?;
+    let json: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse the installed programs: {e}"))?;
+    Ok(parse_uninstall_entries(&json))
+}
+
+#[cfg(target_os = "macos")]
+fn scan() -> Result<Vec<PackageInfo>, String> {
+    let mut cmd = Command::new("system_profiler");
+    cmd.args(["SPApplicationsDataType", "-json"]);
+    let output = exec_with_timeout(cmd, COMMAND_TIMEOUT)
+        .ok_or_else(|| "Failed to run system_profiler".to_string())?;
+    let json: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse system_profiler output: {e}"))?;
+    Ok(parse_sp_applications(&json))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn scan() -> Result<Vec<PackageInfo>, String> {
+    Err("Application inventory is only available on Windows and macOS".to_string())
+}
+
+fn text(value: &Value, key: &str) -> String {
+    value[key].as_str().unwrap_or_default().trim().to_string()
+}
+
+/// Add/Remove Programs entries from the registry script, sorted by name
+#[cfg_attr(not(any(test, target_os = "windows")), allow(dead_code))]
+fn parse_uninstall_entries(json: &Value) -> Vec<PackageInfo> {
+    // ConvertTo-Json unwraps single-item arrays
+    let entries = match json {
+        Value::Array(entries) => entries.iter().collect(),
+        Value::Null => Vec::new(),
+        entry => vec![entry],
+    };
+
+    let mut applications: Vec<PackageInfo> = entries
+        .into_iter()
+        .filter(|e| {
+            // Updates and hotfixes are listed under the product they patch
+            let release = text(e, "release");
+            e["system"] != true
+                && text(e, "parent").is_empty()
+                && !matches!(release.as_str(), "Update" | "Hotfix" | "Security Update")
+        })
+        .filter_map(|e| {
+            let name = text(e, "name");
+            if name.is_empty() {
+                return None;
+            }
+            // InstallDate is yyyyMMdd
+            let date = text(e, "date");
+            let install_date = chrono::NaiveDate::parse_from_str(&date, "%Y%m%d")
+                .map(|d| d.to_string())
+                .unwrap_or_default();
+            Some(PackageInfo {
+                name,
+                version: text(e, "version"),
+                architecture: text(e, "arch"),
+                // EstimatedSize is in KiB
+                installed_size: e["size"].as_i64().unwrap_or(0).max(0) * 1024,
+                install_date,
+                repository: if e["msi"] == true { "msi" } else { "setup" }.to_string(),
+                package_manager: "desktop".to_string(),
+                publisher: text(e, "publisher"),
+                ..Default::default()
+            })
+        })
+        .collect();
+
+    applications.sort_by(|a, b| {
+        (a.name.to_lowercase(), &a.version, &a.architecture).cmp(&(
+            b.name.to_lowercase(),
+            &b.version,
+            &b.architecture,
+        ))
+    });
+    // Per-user installs of a machine-wide product show up once per user
+    applications.dedup_by(|a, b| {
+        a.name == b.name && a.version == b.version && a.architecture == b.architecture
+    });
+    applications
+}
+
+/// Application bundles from `system_profiler SPApplicationsDataType -json`
+#[cfg_attr(not(any(test, target_os = "macos")), allow(dead_code))]
+fn parse_sp_applications(json: &Value) -> Vec<PackageInfo> {
+    let Some(apps) = json["SPApplicationsDataType"].as_array() else {
+        return Vec::new();
+    };
+
+    let mut applications: Vec<PackageInfo> = apps
+        .iter()
+        .filter(|app| {
+            let path = text(app, "path");
+            path.starts_with("/Applications/")
+                || (path.starts_with("/Users/") && path.contains("/Applications/"))
+        })
+        .map(|app| {
+            // "Developer ID Application: Google LLC (EQHXZ8M8AV)"
+            let signer = app["signed_by"][0].as_str().unwrap_or_default();
+            let publisher = match signer.split_once(": ") {
+                Some((_, name)) => name.rsplit_once(" (").map_or(name, |(name, _)| name),
+                None => signer,
+            };
+            PackageInfo {
+                name: text(app, "_name"),
+                version: text(app, "version"),
+                architecture: match text(app, "arch_kind").as_str() {
+                    "arch_arm_i64" => "arm64".to_string(),
+                    "arch_i64" => "x86_64".to_string(),
+                    "arch_arm_i64_i64" | "arch_i64_arm_i64" => "universal".to_string(),
+                    other => other.to_string(),
+                },
+                install_date: text(app, "lastModified"),
+                repository: text(app, "obtained_from"),
+                package_manager: "desktop".to_string(),
+                publisher: publisher.to_string(),
+                description: text(app, "path"),
+                ..Default::default()
+            }
+        })
+        .collect();
+    applications.sort_by_key(|app| app.name.to_lowercase());
+    applications
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_uninstall_entries() {
+        let json: Value = serde_json::from_str(
+            r#"[
+              {"name": "7-Zip 23.01 (x64)", "version": "23.01", "publisher": "Igor Pavlov",
+               "date": "", "size": 5800, "arch": "x64", "msi": false, "system": false, "parent": "", "release": ""},
+              {"name": "Acme CAD", "version": "4.2.0", "publisher": "Acme Corp",
+               "date": "20240131", "size": 102400, "arch": "x86", "msi": true, "system": false, "parent": "", "release": ""},
+              {"name": "Acme CAD", "version": "4.2.0", "publisher": "Acme Corp",
+               "date": "20240131", "size": 102400, "arch": "x86", "msi": true, "system": false, "parent": "", "release": ""},
+              {"name": "Security Update for Acme CAD (KB123)", "version": "", "publisher": "Acme Corp",
+               "date": "", "size": 0, "arch": "x86", "msi": true, "system": false, "parent": "AcmeCAD", "release": "Security Update"},
+              {"name": "Microsoft Visual C++ Runtime Component", "version": "14.0", "publisher": "Microsoft",
+               "date": "", "size": 0, "arch": "x64", "msi": true, "system": true, "parent": "", "release": ""},
+              {"name": "", "version": "", "publisher": "", "date": "", "size": 0, "arch": "x64",
+               "msi": false, "system": false, "parent": "", "release": ""}
+            ]"#,
+        )
+        .unwrap();
+        let apps = parse_uninstall_entries(&json);
+        assert_eq!(apps.len(), 2);
+
+        assert_eq!(apps[0].name, "7-Zip 23.01 (x64)");
+        assert_eq!(apps[0].repository, "setup");
+        assert!(apps[0].install_date.is_empty());
+
+        let cad = &apps[1];
+        assert_eq!(
+            (cad.name.as_str(), cad.version.as_str()),
+            ("Acme CAD", "4.2.0")
+        );
+        assert_eq!(cad.publisher, "Acme Corp");
+        assert_eq!(cad.install_date, "2024-01-31");
+        assert_eq!(cad.installed_size, 100 * 1024 * 1024);
+        assert_eq!(
+            (cad.architecture.as_str(), cad.repository.as_str()),
+            ("x86", "msi")
+        );
+        assert_eq!(cad.package_manager, "desktop");
+
+        // A single entry is not wrapped in an array
+        let single: Value = serde_json::from_str(
+            r#"{"name": "Only", "version": "1", "publisher": "", "date": "", "size": 0,
+                "arch": "x64", "msi": false, "system": false, "parent": "", "release": ""}"#,
+        )
+        .unwrap();
+        assert_eq!(parse_uninstall_entries(&single).len(), 1);
+    }
+
+    #[test]
+    fn test_parse_sp_applications() {
+        let json: Value = serde_json::from_str(
+            r#"{"SPApplicationsDataType": [
+              {"_name": "Google Chrome", "version": "129.0.6668.100", "path": "/Applications/Google Chrome.app",
+               "arch_kind": "arch_arm_i64_i64", "obtained_from": "identified_developer",
+               "lastModified": "2024-10-10T08:00:00Z",
+               "signed_by": ["Developer ID Application: Google LLC (EQHXZ8M8AV)", "Developer ID Certification Authority", "Apple Root CA"]},
+              {"_name": "Slack", "version": "4.40.0", "path": "/Users/alice/Applications/Slack.app",
+               "arch_kind": "arch_arm_i64", "obtained_from": "mac_app_store",
+               "signed_by": ["Apple Mac OS Application Signing"]},
+              {"_name": "Finder", "version": "14.6", "path": "/System/Library/CoreServices/Finder.app",
+               "obtained_from": "apple"}
+            ]}"#,
+        )
+        .unwrap();
+        let apps = parse_sp_applications(&json);
+        assert_eq!(apps.len(), 2);
+
+        let chrome = &apps[0];
+        assert_eq!(chrome.name, "Google Chrome");
+        assert_eq!(chrome.publisher, "Google LLC");
+        assert_eq!(chrome.architecture, "universal");
+        assert_eq!(chrome.repository, "identified_developer");
+        assert_eq!(chrome.install_date, "2024-10-10T08:00:00Z");
+
+        let slack = &apps[1];
+        assert_eq!(slack.publisher, "Apple Mac OS Application Signing");
+        assert_eq!(slack.architecture, "arm64");
+        assert_eq!(slack.description, "/Users/alice/Applications/Slack.app");
+    }
+}
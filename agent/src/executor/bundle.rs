@@ -0,0 +1,208 @@
+//! Offline update bundles
+//!
+//! For networks without internet egress, updates are carried in as a tarball
+//! (optionally gzipped) holding `manifest.json`, its detached Ed25519
+//! signature `manifest.sig` (base64) and the files the manifest lists:
+//!
+//! ```json
+//! {"version": "0.5.0", "created": "2026-10-01T00:00:00Z", "files": [
+//!   {"name": "nanolink-agent-linux-x86_64", "kind": "agent",
+//!    "platform": "linux-x86_64", "sha256": "…"},
+//!   {"name": "vulndb.json.gz", "kind": "data", "sha256": "…"}]}
+//! ```
+//!
+//! The signature must verify against one of `update.bundle_public_keys` and
+//! every file must match its digest before anything is installed. `agent`
+//! files for this platform go through the regular update path, `data` files
+//! (e.g. vulnerability databases) are placed in `update.data_dir`.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::utils::tar;
+
+/// Largest bundle accepted (unpacked)
+const MAX_BUNDLE_BYTES: u64 = 1024 * 1024 * 1024;
+
+const MANIFEST: &str = "manifest.json";
+const SIGNATURE: &str = "manifest.sig";
+
+#[derive(Debug, Deserialize)]
+pub(super) struct Manifest {
+    pub version: String,
+    #[serde(default)]
+    pub created: String,
+    pub files: Vec<ManifestFile>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct ManifestFile {
+    pub name: String,
+    pub kind: FileKind,
+    /// Platform identifier for agent binaries, e.g. "linux-x86_64"
+    #[serde(default)]
+    pub platform: String,
+    pub sha256: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum FileKind {
+    Agent,
+    Data,
+}
+
+/// A verified bundle
+pub(super) struct Bundle {
+    pub manifest: Manifest,
+    files: HashMap<String, Vec<u8>>,
+}
+
+impl Bundle {
+    /// Read and verify a bundle file
+    pub fn open(path: &Path, public_keys: &[String]) -> Result<Self, String> {
+        let file = std::fs::File::open(path)
+            .map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+        let mut raw = Vec::new();
+        file.take(MAX_BUNDLE_BYTES)
+            .read_to_end(&mut raw)
+            .map_err(|e| format!("Failed to read bundle: {e}"))?;
+        if raw.starts_with(&[0x1f, 0x8b]) {
+            let mut unpacked = Vec::new();
+            GzDecoder::new(raw.as_slice())
+                .take(MAX_BUNDLE_BYTES)
+                .read_to_end(&mut unpacked)
+                .map_err(|e| format!("Failed to decompress bundle: {e}"))?;
+            raw = unpacked;
+        }
+        Self::from_tar(&raw, public_keys)
+    }
+
+    fn from_tar(archive: &[u8], public_keys: &[String]) -> Result<Self, String> {
+        let mut files = tar::read(archive)?;
+        let manifest_bytes = files
+            .remove(MANIFEST)
+            .ok_or_else(|| format!("Bundle has no {MANIFEST}"))?;
+        let signature = files
+            .remove(SIGNATURE)
+            .ok_or_else(|| format!("Bundle has no {SIGNATURE}"))?;
+        verify_signature(&manifest_bytes, &signature, public_keys)?;
+
+        let manifest: Manifest = serde_json::from_slice(&manifest_bytes)
+            .map_err(|e| format!("Invalid manifest: {e}"))?;
+        for entry in &manifest.files {
+            if !is_plain_name(&entry.name) {
+                return Err(format!("Invalid file name in manifest: {}", entry.name));
+            }
+            let data = files
+                .get(&entry.name)
+                .ok_or_else(|| format!("Bundle is missing {}", entry.name))?;
+            let digest = hex(&Sha256::digest(data));
+            if !digest.eq_ignore_ascii_case(&entry.sha256) {
+                return Err(format!("Checksum mismatch for {}", entry.name));
+            }
+        }
+        Ok(Self { manifest, files })
+    }
+
+    /// Contents of a verified file
+    pub fn file(&self, name: &str) -> &[u8] {
+        self.files.get(name).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+fn verify_signature(
+    message: &[u8],
+    signature: &[u8],
+    public_keys: &[String],
+) -> Result<(), String> {
+    if public_keys.is_empty() {
+        return Err(
+            "No update.bundle_public_keys configured, refusing unsigned bundle".to_string(),
+        );
+    }
+    let signature = BASE64
+        .decode(String::from_utf8_lossy(signature).trim())
+        .map_err(|e| format!("Invalid signature encoding: {e}"))?;
+    let trusted = public_keys.iter().any(|key| {
+        BASE64.decode(key.trim()).is_ok_and(|key| {
+            ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, key)
+                .verify(message, &signature)
+                .is_ok()
+        })
+    });
+    if trusted {
+        Ok(())
+    } else {
+        Err("Bundle signature does not match any trusted key".to_string())
+    }
+}
+
+/// File name without directories, so data files can't escape `data_dir`
+fn is_plain_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains(['/', '\\', '\0'])
+        && name != MANIFEST
+        && name != SIGNATURE
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    #[test]
+    fn test_signed_bundle() {
+        let rng = ring::rand::SystemRandom::new();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let key = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let public = BASE64.encode(key.public_key().as_ref());
+
+        let db = b"{\"cves\":[]}";
+        let manifest = format!(
+            r#"{{"version":"0.5.0","files":[{{"name":"vulndb.json","kind":"data","sha256":"{}"}}]}}"#,
+            hex(&Sha256::digest(db))
+        );
+        let signature = BASE64.encode(key.sign(manifest.as_bytes()).as_ref());
+        let build = |manifest: &str, db: &[u8]| {
+            let mut archive = Vec::new();
+            tar::append(&mut archive, "./manifest.json", manifest.as_bytes(), 0).unwrap();
+            tar::append(&mut archive, "manifest.sig", signature.as_bytes(), 0).unwrap();
+            tar::append(&mut archive, "vulndb.json", db, 0).unwrap();
+            tar::finish(&mut archive);
+            archive
+        };
+
+        let bundle =
+            Bundle::from_tar(&build(&manifest, db), std::slice::from_ref(&public)).unwrap();
+        assert_eq!(bundle.manifest.version, "0.5.0");
+        assert_eq!(bundle.manifest.files[0].kind, FileKind::Data);
+        assert_eq!(bundle.file("vulndb.json"), db);
+
+        // Tampered payload, tampered manifest, untrusted key
+        assert!(Bundle::from_tar(&build(&manifest, b"{}"), std::slice::from_ref(&public)).is_err());
+        let tampered = manifest.replace("0.5.0", "9.9.9");
+        assert!(Bundle::from_tar(&build(&tampered, db), &[public]).is_err());
+        assert!(Bundle::from_tar(&build(&manifest, db), &[]).is_err());
+    }
+
+    #[test]
+    fn test_plain_name() {
+        assert!(is_plain_name("vulndb.json.gz"));
+        assert!(!is_plain_name("../etc/passwd"));
+        assert!(!is_plain_name("manifest.sig"));
+    }
+}
@@ -0,0 +1,367 @@
+//! Old file cleanup executor
+//!
+//! Deletes or gzip-compresses files matching the configured cleanup rules
+//! (directory + file name pattern + minimum age). Commands can only select
+//! rules, never arbitrary paths, and run as a dry run unless `dry_run=false`
+//! is passed explicitly.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use glob::Pattern;
+use tracing::{info, warn};
+
+use crate::config::{CleanupAction, CleanupRule, Config};
+use crate::proto::{CleanupFile, CleanupResult, CommandResult};
+
+/// Maximum number of files listed in a result
+const MAX_LISTED_FILES: usize = 500;
+
+/// Maximum directory depth for recursive rules
+const MAX_DEPTH: usize = 16;
+
+/// Cleanup executor
+pub struct CleanupExecutor {
+    config: Arc<Config>,
+}
+
+impl CleanupExecutor {
+    /// Create a new cleanup executor
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
+    }
+
+    /// Helper to create an error CommandResult
+    fn error_result(error: String) -> CommandResult {
+        CommandResult {
+            command_id: String::new(),
+            success: false,
+            output: String::new(),
+            error,
+            ..Default::default()
+        }
+    }
+
+    /// Run cleanup rules.
+    ///
+    /// Params: `rule` (rule name, default all rules), `dry_run` (default
+    /// `true`) and `older_than_days` (overrides the rule's age, at least 1).
+    pub async fn cleanup(&self, params: &HashMap<String, String>) -> CommandResult {
+        let cfg = &self.config.cleanup;
+        if !cfg.enabled {
+            return Self::error_result("File cleanup is disabled".to_string());
+        }
+
+        let rules: Vec<CleanupRule> = match params.get("rule").filter(|r| !r.is_empty()) {
+            Some(name) => match cfg.rules.iter().find(|r| &r.name == name) {
+                Some(rule) => vec![rule.clone()],
+                None => return Self::error_result(format!("Unknown cleanup rule: {name}")),
+            },
+            None => cfg.rules.clone(),
+        };
+        if rules.is_empty() {
+            return Self::error_result("No cleanup rules configured".to_string());
+        }
+
+        let dry_run = params.get("dry_run").is_none_or(|v| v != "false");
+        let older_than_days = match params.get("older_than_days") {
+            Some(value) => match value.parse::<u64>() {
+                Ok(days) if days >= 1 => Some(days),
+                _ => return Self::error_result(format!("Invalid older_than_days: {value}")),
+            },
+            None => None,
+        };
+
+        info!(
+            "[AUDIT] FileCleanup: rules={:?}, dry_run={}, older_than_days={:?}",
+            rules.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(),
+            dry_run,
+            older_than_days
+        );
+
+        let config = self.config.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let mut result = CleanupResult {
+                dry_run,
+                ..Default::default()
+            };
+            for rule in &rules {
+                if let Err(e) = run_rule(&config, rule, older_than_days, dry_run, &mut result) {
+                    warn!("[AUDIT] FileCleanup rule {} failed: {}", rule.name, e);
+                    return Err(format!("Rule {}: {e}", rule.name));
+                }
+            }
+            Ok(result)
+        })
+        .await;
+
+        match result {
+            Ok(Ok(result)) => {
+                info!(
+                    "[AUDIT] FileCleanup finished: {} files, {} bytes matched, {} bytes reclaimed",
+                    result.files_matched, result.bytes_matched, result.bytes_reclaimed
+                );
+                CommandResult {
+                    success: true,
+                    output: summary(&result),
+                    cleanup_result: Some(result),
+                    ..Default::default()
+                }
+            }
+            Ok(Err(e)) => Self::error_result(e),
+            Err(e) => Self::error_result(format!("Cleanup task failed: {e}")),
+        }
+    }
+}
+
+fn summary(result: &CleanupResult) -> String {
+    if result.dry_run {
+        format!(
+            "Dry run: {} files ({} bytes) would be cleaned up",
+            result.files_matched, result.bytes_matched
+        )
+    } else {
+        format!(
+            "Cleaned up {} files, {} bytes reclaimed",
+            result.files_matched, result.bytes_reclaimed
+        )
+    }
+}
+
+fn run_rule(
+    config: &Config,
+    rule: &CleanupRule,
+    older_than_days: Option<u64>,
+    dry_run: bool,
+    result: &mut CleanupResult,
+) -> Result<(), String> {
+    let directory = Path::new(&rule.directory)
+        .canonicalize()
+        .map_err(|e| format!("Cannot resolve {}: {e}", rule.directory))?;
+    let pattern = Pattern::new(&rule.pattern)
+        .map_err(|e| format!("Invalid pattern {}: {e}", rule.pattern))?;
+    let days = older_than_days.unwrap_or(rule.older_than_days);
+    let cutoff = SystemTime::now()
+        .checked_sub(Duration::from_secs(days * 86_400))
+        .unwrap_or(UNIX_EPOCH);
+
+    let mut files = Vec::new();
+    let depth = if rule.recursive { MAX_DEPTH } else { 1 };
+    collect_files(&directory, &pattern, cutoff, depth, &mut files);
+
+    for (path, size, modified) in files {
+        if is_denied(config, &path) {
+            warn!(
+                "[AUDIT] FileCleanup skipped denied path: {}",
+                path.display()
+            );
+            continue;
+        }
+        // Already compressed files are left alone by compress rules
+        if rule.action == CleanupAction::Compress && path.extension().is_some_and(|ext| ext == "gz")
+        {
+            continue;
+        }
+
+        let mut entry = CleanupFile {
+            path: path.to_string_lossy().to_string(),
+            size,
+            modified_time: modified
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            action: match rule.action {
+                CleanupAction::Delete => "delete",
+                CleanupAction::Compress => "compress",
+            }
+            .to_string(),
+            error: String::new(),
+        };
+        result.files_matched += 1;
+        result.bytes_matched += size;
+
+        if !dry_run {
+            let outcome = match rule.action {
+                CleanupAction::Delete => fs::remove_file(&path).map(|_| size),
+                CleanupAction::Compress => compress(&path).map(|gz| size.saturating_sub(gz)),
+            };
+            match outcome {
+                Ok(reclaimed) => {
+                    info!("[AUDIT] FileCleanup {}: {}", entry.action, entry.path);
+                    result.bytes_reclaimed += reclaimed;
+                }
+                Err(e) => {
+                    warn!(
+                        "[AUDIT] FileCleanup {} failed: {}: {}",
+                        entry.action, entry.path, e
+                    );
+                    entry.error = e.to_string();
+                }
+            }
+        }
+
+        if result.files.len() < MAX_LISTED_FILES {
+            result.files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// Regular files below `dir` matching `pattern` and last modified before
+/// `cutoff`. Symlinks are never followed or touched.
+fn collect_files(
+    dir: &Path,
+    pattern: &Pattern,
+    cutoff: SystemTime,
+    depth: usize,
+    files: &mut Vec<(PathBuf, u64, SystemTime)>,
+) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(meta) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if meta.is_dir() {
+            if depth > 1 {
+                collect_files(&path, pattern, cutoff, depth - 1, files);
+            }
+        } else if meta.is_file()
+            && pattern.matches(&entry.file_name().to_string_lossy())
+            && meta.modified().is_ok_and(|m| m <= cutoff)
+        {
+            files.push((path, meta.len(), meta.modified().unwrap_or(UNIX_EPOCH)));
+        }
+    }
+}
+
+fn is_denied(config: &Config, path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    config.security.denied_paths.iter().any(|denied| {
+        path.starts_with(denied.as_str())
+            || Pattern::new(denied).is_ok_and(|pattern| pattern.matches(&path))
+    })
+}
+
+/// Compress `path` to `path.gz` and remove the original, returning the
+/// compressed size
+fn compress(path: &Path) -> io::Result<u64> {
+    let mut gz_name = path.as_os_str().to_owned();
+    gz_name.push(".gz");
+    let gz_path = PathBuf::from(gz_name);
+    if gz_path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", gz_path.display()),
+        ));
+    }
+
+    let write = || -> io::Result<u64> {
+        let mut input = BufReader::new(File::open(path)?);
+        let mut encoder = GzEncoder::new(
+            BufWriter::new(File::create(&gz_path)?),
+            Compression::default(),
+        );
+        io::copy(&mut input, &mut encoder)?;
+        encoder
+            .finish()?
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        Ok(fs::metadata(&gz_path)?.len())
+    };
+    match write() {
+        Ok(size) => {
+            fs::remove_file(path)?;
+            Ok(size)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&gz_path);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn rule(directory: &Path, action: CleanupAction) -> CleanupRule {
+        CleanupRule {
+            name: "test".to_string(),
+            directory: directory.to_string_lossy().to_string(),
+            pattern: "*.log".to_string(),
+            recursive: true,
+            older_than_days: 0,
+            action,
+        }
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("nanolink-cleanup-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("a.log"), "a".repeat(4096)).unwrap();
+        fs::write(dir.join("nested/b.log"), "b".repeat(100)).unwrap();
+        fs::write(dir.join("keep.txt"), "c").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_dry_run_and_delete() {
+        let dir = test_dir("delete");
+        let config = Config::sample();
+        let rule = rule(&dir, CleanupAction::Delete);
+
+        let mut result = CleanupResult::default();
+        run_rule(&config, &rule, None, true, &mut result).unwrap();
+        assert_eq!(result.files_matched, 2);
+        assert_eq!(result.bytes_matched, 4196);
+        assert_eq!(result.bytes_reclaimed, 0);
+        assert!(dir.join("a.log").exists());
+
+        let mut result = CleanupResult::default();
+        run_rule(&config, &rule, None, false, &mut result).unwrap();
+        assert_eq!(result.bytes_reclaimed, 4196);
+        assert!(!dir.join("a.log").exists());
+        assert!(!dir.join("nested/b.log").exists());
+        assert!(dir.join("keep.txt").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_compress() {
+        let dir = test_dir("compress");
+        let config = Config::sample();
+        let rule = CleanupRule {
+            recursive: false,
+            ..rule(&dir, CleanupAction::Compress)
+        };
+
+        let mut result = CleanupResult::default();
+        run_rule(&config, &rule, None, false, &mut result).unwrap();
+        assert_eq!(result.files_matched, 1);
+        assert!(result.bytes_reclaimed > 0);
+        assert!(!dir.join("a.log").exists());
+        assert!(dir.join("nested/b.log").exists());
+
+        let mut content = String::new();
+        flate2::read::GzDecoder::new(File::open(dir.join("a.log.gz")).unwrap())
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "a".repeat(4096));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
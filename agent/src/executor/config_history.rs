@@ -0,0 +1,283 @@
+//! Git-backed config history
+//!
+//! With `config_management.backup_mode: git`, managed config files are
+//! mirrored into a local repository (`<backup_dir>/history`, `/etc/nginx/nginx.conf`
+//! is kept as `etc/nginx/nginx.conf`) and committed on every change, with the
+//! requesting server and command ID in the commit message. Edits made outside
+//! the agent are committed separately the next time the file is changed.
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+const AUTHOR_NAME: &str = "NanoLink Agent";
+const AUTHOR_EMAIL: &str = "agent@nanolink.local";
+
+/// One recorded version of a config file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Revision {
+    pub hash: String,
+    /// Commit time (ISO 8601)
+    pub time: String,
+    pub message: String,
+}
+
+/// Who requested a config change, recorded in the history
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChangeOrigin<'a> {
+    pub server: &'a str,
+    pub command_id: &'a str,
+}
+
+/// Local git repository holding config history
+pub struct ConfigHistory {
+    repo: PathBuf,
+}
+
+impl ConfigHistory {
+    pub fn new(backup_dir: &str) -> Self {
+        Self {
+            repo: Path::new(backup_dir).join("history"),
+        }
+    }
+
+    /// Commit the current content of `path` if it differs from the last
+    /// recorded version. Returns the new revision, if any.
+    pub fn record(&self, path: &str, message: &str) -> Result<Option<String>, String> {
+        self.ensure_repo()?;
+        let relative = mirror_path(Path::new(path));
+        let relative_str = relative.to_string_lossy().to_string();
+        let mirror = self.repo.join(&relative);
+
+        match fs::read(path) {
+            Ok(content) => {
+                if let Some(parent) = mirror.parent() {
+                    fs::create_dir_all(parent)
+                        .map_err(|e| format!("Failed to create {}: {e}", parent.display()))?;
+                }
+                fs::write(&mirror, content)
+                    .map_err(|e| format!("Failed to copy {path} into history: {e}"))?;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let _ = fs::remove_file(&mirror);
+            }
+            Err(e) => return Err(format!("Failed to read {path}: {e}")),
+        }
+
+        self.git(&["add", "-A", "--", &relative_str])?;
+        if self
+            .git(&["status", "--porcelain", "--", &relative_str])?
+            .trim()
+            .is_empty()
+        {
+            return Ok(None);
+        }
+        self.git(&[
+            "-c",
+            &format!("user.name={AUTHOR_NAME}"),
+            "-c",
+            &format!("user.email={AUTHOR_EMAIL}"),
+            "-c",
+            "commit.gpgsign=false",
+            "commit",
+            "-q",
+            "-m",
+            message,
+            "--",
+            &relative_str,
+        ])?;
+        self.git(&["rev-parse", "HEAD"])
+            .map(|hash| Some(hash.trim().to_string()))
+    }
+
+    /// Recorded versions of `path`, newest first
+    pub fn log(&self, path: &str) -> Result<Vec<Revision>, String> {
+        if !self.repo.join(".git").exists() {
+            return Ok(Vec::new());
+        }
+        let relative = mirror_path(Path::new(path));
+        let output = self.git(&[
+            "log",
+            "--format=%H%x1f%cI%x1f%s",
+            "--",
+            &relative.to_string_lossy(),
+        ])?;
+        Ok(parse_log(&output))
+    }
+
+    /// Content of `path` at `revision`
+    pub fn show(&self, path: &str, revision: &str) -> Result<String, String> {
+        validate_revision(revision)?;
+        let relative = mirror_path(Path::new(path));
+        // git always uses forward slashes in tree paths
+        let tree_path = relative.to_string_lossy().replace('\\', "/");
+        self.git(&["show", &format!("{revision}:{tree_path}")])
+    }
+
+    /// Latest revision of `path` committed at or before `time` (ISO 8601)
+    pub fn revision_at(&self, path: &str, time: &str) -> Result<String, String> {
+        chrono::DateTime::parse_from_rfc3339(time)
+            .map_err(|e| format!("Invalid time {time}: {e}"))?;
+        let relative = mirror_path(Path::new(path));
+        let hash = self.git(&[
+            "rev-list",
+            "-1",
+            &format!("--before={time}"),
+            "HEAD",
+            "--",
+            &relative.to_string_lossy(),
+        ])?;
+        let hash = hash.trim();
+        if hash.is_empty() {
+            return Err(format!("No recorded version of {path} at {time}"));
+        }
+        Ok(hash.to_string())
+    }
+
+    fn ensure_repo(&self) -> Result<(), String> {
+        if self.repo.join(".git").exists() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.repo)
+            .map_err(|e| format!("Failed to create {}: {e}", self.repo.display()))?;
+        self.git(&["init", "-q"]).map(|_| ())
+    }
+
+    fn git(&self, args: &[&str]) -> Result<String, String> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.repo)
+            .args(args)
+            .output()
+            .map_err(|e| format!("Failed to run git: {e}"))?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        } else {
+            Err(format!(
+                "git {} failed: {}",
+                args.first().unwrap_or(&""),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+}
+
+/// Commit message for a change made through a command
+pub fn change_message(action: &str, path: &str, origin: &ChangeOrigin) -> String {
+    let mut message = format!("{action} {path}");
+    if !origin.server.is_empty() {
+        message.push_str(&format!(" by {}", origin.server));
+    }
+    if !origin.command_id.is_empty() {
+        message.push_str(&format!(" (command {})", origin.command_id));
+    }
+    message
+}
+
+/// Location of a config file inside the repository: the absolute path
+/// without its root, with the drive letter as first directory on Windows
+fn mirror_path(path: &Path) -> PathBuf {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+            Component::Prefix(prefix) => Some(
+                prefix
+                    .as_os_str()
+                    .to_string_lossy()
+                    .trim_matches(|c| c == ':' || c == '\\' || c == '?' || c == '.')
+                    .to_string(),
+            ),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Only plain commit hashes are accepted, never refs or options
+fn validate_revision(revision: &str) -> Result<(), String> {
+    if (4..=40).contains(&revision.len()) && revision.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(())
+    } else {
+        Err(format!("Invalid revision: {revision}"))
+    }
+}
+
+fn parse_log(output: &str) -> Vec<Revision> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\u{1f}');
+            Some(Revision {
+                hash: fields.next()?.to_string(),
+                time: fields.next()?.to_string(),
+                message: fields.next()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mirror_path() {
+        assert_eq!(
+            mirror_path(Path::new("/etc/nginx/nginx.conf")),
+            PathBuf::from("etc/nginx/nginx.conf")
+        );
+    }
+
+    #[test]
+    fn test_validate_revision() {
+        assert!(validate_revision("3f2a9c1").is_ok());
+        assert!(validate_revision("HEAD").is_err());
+        assert!(validate_revision("--output=/tmp/x").is_err());
+    }
+
+    #[test]
+    fn test_record_and_restore() {
+        if Command::new("git").arg("--version").output().is_err() {
+            return;
+        }
+        let dir = std::env::temp_dir().join(format!("nanolink-history-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("app.conf");
+        let file_str = file.to_string_lossy().to_string();
+        let history = ConfigHistory::new(&dir.join("backups").to_string_lossy());
+
+        fs::write(&file, "port = 80\n").unwrap();
+        let first = history.record(&file_str, "initial").unwrap().unwrap();
+        assert_eq!(history.record(&file_str, "unchanged").unwrap(), None);
+
+        fs::write(&file, "port = 8080\n").unwrap();
+        let origin = ChangeOrigin {
+            server: "10.0.0.1:39100",
+            command_id: "cmd-7",
+        };
+        let message = change_message("CONFIG_WRITE", &file_str, &origin);
+        history.record(&file_str, &message).unwrap().unwrap();
+
+        let log = history.log(&file_str).unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].message, message);
+        assert!(
+            log[0]
+                .message
+                .ends_with("by 10.0.0.1:39100 (command cmd-7)")
+        );
+        assert_eq!(log[1].hash, first);
+        assert_eq!(history.show(&file_str, &first).unwrap(), "port = 80\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_log() {
+        let log =
+            parse_log("abc123\u{1f}2026-01-02T03:04:05+00:00\u{1f}CONFIG_WRITE /etc/a.conf\n");
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].hash, "abc123");
+        assert_eq!(log[0].message, "CONFIG_WRITE /etc/a.conf");
+    }
+}
@@ -2,14 +2,22 @@ use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tracing::{info, warn};
 
-use crate::config::Config;
-use crate::proto::CommandResult;
+use super::config_history::{ChangeOrigin, ConfigHistory, change_message};
+use crate::config::{BackupMode, Config};
+use crate::executor::ServiceExecutor;
+use crate::proto::{CommandResult, ConfigBackup, ConfigResult};
+use crate::utils::async_command::{
+    CommandResult as AsyncResult, CommandTimeout, run_command_async,
+};
 
 /// Config file manager with backup and rollback support
 pub struct ConfigManager {
     config: Arc<Config>,
+    /// Set in git backup mode
+    history: Option<ConfigHistory>,
 }
 
 /// Sensitive patterns to sanitize in config output
@@ -64,7 +72,9 @@ const FORBIDDEN_PATHS: &[&str] = &[
 impl ConfigManager {
     /// Create a new config manager
     pub fn new(config: Arc<Config>) -> Self {
-        Self { config }
+        let history = (config.config_management.backup_mode == BackupMode::Git)
+            .then(|| ConfigHistory::new(&config.config_management.backup_dir));
+        Self { config, history }
     }
 
     /// Read a config file (with optional sanitization)
@@ -135,7 +145,11 @@ impl ConfigManager {
     }
 
     /// Write a config file (with automatic backup)
-    pub async fn write_config(&self, params: &HashMap<String, String>) -> CommandResult {
+    pub async fn write_config(
+        &self,
+        params: &HashMap<String, String>,
+        origin: &ChangeOrigin<'_>,
+    ) -> CommandResult {
         if !self.config.config_management.enabled {
             return CommandResult {
                 command_id: String::new(),
@@ -184,6 +198,18 @@ impl ConfigManager {
             };
         }
 
+        let verification = match Verification::from_params(&self.config, params) {
+            Ok(v) => v,
+            Err(e) => return Self::error_result(e),
+        };
+        let restart = params.get("restart_service").is_some_and(|s| !s.is_empty());
+        // Two-phase apply keeps the previous content to restore on failure
+        let previous = if verification.is_some() || restart {
+            fs::read_to_string(path).ok()
+        } else {
+            None
+        };
+
         // Create backup if enabled and file exists
         if self.config.config_management.backup_on_change && Path::new(path).exists() {
             if let Err(e) = self.create_backup(path) {
@@ -194,8 +220,26 @@ impl ConfigManager {
 
         // Write file
         match fs::write(path, content) {
+            Ok(()) if verification.is_some() || restart => {
+                info!("Wrote config file: {} (verifying)", path);
+                match self
+                    .activate(path, previous.as_deref(), params, verification)
+                    .await
+                {
+                    Ok(summary) => {
+                        self.record_change("CONFIG_WRITE", path, origin);
+                        CommandResult {
+                            success: true,
+                            output: format!("Config written successfully: {path}{summary}"),
+                            ..Default::default()
+                        }
+                    }
+                    Err(e) => Self::error_result(e),
+                }
+            }
             Ok(()) => {
                 info!("Wrote config file: {}", path);
+                self.record_change("CONFIG_WRITE", path, origin);
                 CommandResult {
                     command_id: String::new(),
                     success: true,
@@ -248,31 +292,7 @@ impl ConfigManager {
 
         let format = params.get("format").map(|s| s.as_str()).unwrap_or("auto");
 
-        // Try to parse based on format
-        // Map all success values to () since we only care about parse success
-        let result: Result<(), String> = match format {
-            "yaml" | "yml" => serde_yaml::from_str::<serde_yaml::Value>(&content)
-                .map(|_| ())
-                .map_err(|e| format!("Invalid YAML: {e}")),
-            "json" => serde_json::from_str::<serde_json::Value>(&content)
-                .map(|_| ())
-                .map_err(|e| format!("Invalid JSON: {e}")),
-            "toml" => toml::from_str::<toml::Value>(&content)
-                .map(|_| ())
-                .map_err(|e| format!("Invalid TOML: {e}")),
-            _ => {
-                // Try each format (auto-detect)
-                let is_yaml = serde_yaml::from_str::<serde_yaml::Value>(&content).is_ok();
-                let is_json = serde_json::from_str::<serde_json::Value>(&content).is_ok();
-                let is_toml = toml::from_str::<toml::Value>(&content).is_ok();
-
-                if is_yaml || is_json || is_toml {
-                    Ok(())
-                } else {
-                    Err("Content is not valid YAML, JSON, or TOML".to_string())
-                }
-            }
-        };
+        let result = check_syntax(&content, format);
 
         match result {
             Ok(_) => CommandResult {
@@ -292,8 +312,202 @@ impl ConfigManager {
         }
     }
 
+    /// Render a template with fleet variables and apply it as a transaction.
+    ///
+    /// Params: `path`, `template` (minijinja syntax), `variables` (JSON
+    /// object; agent facts are available as `agent.hostname`, `agent.os`,
+    /// `agent.arch` and `agent.id`), `format` (optional syntax check of the
+    /// result), `dry_run` (only return the diff), `restart_service` and the
+    /// verification params of [`Verification::from_params`]. The old file
+    /// is restored if the restart or the verification fails.
+    pub async fn render_config(
+        &self,
+        params: &HashMap<String, String>,
+        origin: &ChangeOrigin<'_>,
+    ) -> CommandResult {
+        if !self.config.config_management.enabled {
+            return Self::error_result("Config management is disabled".to_string());
+        }
+
+        let Some(path) = params.get("path") else {
+            return Self::error_result("Config path is required".to_string());
+        };
+        let Some(template) = params.get("template") else {
+            return Self::error_result("Template is required".to_string());
+        };
+        if let Err(e) = self.validate_config_path(path) {
+            warn!("Config path validation failed: {} - {}", path, e);
+            return Self::error_result(e);
+        }
+
+        let variables = match params.get("variables").filter(|v| !v.is_empty()) {
+            Some(json) => match serde_json::from_str::<serde_json::Value>(json) {
+                Ok(serde_json::Value::Object(map)) => map,
+                _ => return Self::error_result("variables must be a JSON object".to_string()),
+            },
+            None => serde_json::Map::new(),
+        };
+        let rendered = match render_template(template, variables, self.agent_facts()) {
+            Ok(r) => r,
+            Err(e) => return Self::error_result(e),
+        };
+
+        if let Some(format) = params.get("format") {
+            if let Err(e) = check_syntax(&rendered, format) {
+                return Self::error_result(format!("Rendered config is invalid: {e}"));
+            }
+        }
+        let verification = match Verification::from_params(&self.config, params) {
+            Ok(v) => v,
+            Err(e) => return Self::error_result(e),
+        };
+
+        let existing = if Path::new(path).exists() {
+            match fs::read_to_string(path) {
+                Ok(c) => Some(c),
+                Err(e) => return Self::error_result(format!("Failed to read config: {e}")),
+            }
+        } else {
+            None
+        };
+        let diff = similar::TextDiff::from_lines(existing.as_deref().unwrap_or(""), &rendered)
+            .unified_diff()
+            .header(path, path)
+            .to_string();
+        let mut result = ConfigResult {
+            path: path.clone(),
+            diff: self.sanitize_content(&diff),
+            changed: existing.as_deref() != Some(rendered.as_str()),
+            ..Default::default()
+        };
+
+        if !result.changed {
+            return Self::render_result(result, "Config is up to date".to_string());
+        }
+        if params.get("dry_run").is_some_and(|v| v == "true") {
+            return Self::render_result(result, "Dry run: config would change".to_string());
+        }
+
+        info!(
+            "[AUDIT] ConfigRender: writing {} (restart: {:?})",
+            path,
+            params.get("restart_service")
+        );
+        if existing.is_some() && self.config.config_management.backup_on_change {
+            match self.create_backup(path) {
+                Ok(backup) => result.backup_path = backup,
+                Err(e) => warn!("Failed to create backup for {}: {}", path, e),
+            }
+        }
+        if let Err(e) = write_replace(path, &rendered) {
+            return Self::error_result(format!("Failed to write config: {e}"));
+        }
+
+        match self
+            .activate(path, existing.as_deref(), params, verification)
+            .await
+        {
+            Ok(summary) => {
+                self.record_change("CONFIG_RENDER", path, origin);
+                Self::render_result(result, format!("Config written: {path}{summary}"))
+            }
+            Err(e) => CommandResult {
+                success: false,
+                error: e,
+                config_result: Some(result),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Restart the associated service and run the verification after a
+    /// write, restoring `previous` (and restarting again) if either fails
+    async fn activate(
+        &self,
+        path: &str,
+        previous: Option<&str>,
+        params: &HashMap<String, String>,
+        verification: Option<Verification>,
+    ) -> Result<String, String> {
+        let service = params.get("restart_service").filter(|s| !s.is_empty());
+        let services = ServiceExecutor::new(self.config.clone());
+        let mut summary = String::new();
+
+        let failure = 'apply: {
+            if let Some(service) = service {
+                let restart = services.restart_service(service).await;
+                if !restart.success {
+                    break 'apply format!("Restarting {service} failed ({})", restart.error.trim());
+                }
+                summary.push_str(&format!(", {service} restarted"));
+            }
+            if let Some(verification) = &verification {
+                if let Err(e) = verification.wait().await {
+                    break 'apply format!("Verification failed ({e})");
+                }
+                summary.push_str(", verified");
+            }
+            return Ok(summary);
+        };
+
+        // Undo the write so the service comes back with its last good config
+        warn!("[AUDIT] Config apply: {}, restoring {}", failure, path);
+        let restored = match previous {
+            Some(content) => write_replace(path, content),
+            None => fs::remove_file(path),
+        };
+        let recovery = match restored {
+            Ok(()) => match service {
+                Some(service) if !services.restart_service(service).await.success => {
+                    "previous config restored, but the service still fails".to_string()
+                }
+                _ => "previous config restored".to_string(),
+            },
+            Err(e) => format!("restoring the previous config failed: {e}"),
+        };
+        Err(format!("{failure}; {recovery}"))
+    }
+
+    /// Agent facts available to templates as `agent.*`
+    fn agent_facts(&self) -> serde_json::Value {
+        serde_json::json!({
+            "hostname": self.config.get_hostname(),
+            "id": self.config.agent.agent_id.clone().unwrap_or_default(),
+            "os": std::env::consts::OS,
+            "arch": std::env::consts::ARCH,
+        })
+    }
+
+    fn render_result(result: ConfigResult, output: String) -> CommandResult {
+        CommandResult {
+            success: true,
+            output,
+            config_result: Some(result),
+            ..Default::default()
+        }
+    }
+
+    /// Helper to create an error CommandResult
+    fn error_result(error: String) -> CommandResult {
+        CommandResult {
+            command_id: String::new(),
+            success: false,
+            output: String::new(),
+            error,
+            ..Default::default()
+        }
+    }
+
     /// Rollback config to a previous backup
-    pub async fn rollback_config(&self, params: &HashMap<String, String>) -> CommandResult {
+    ///
+    /// In git backup mode, `revision` (commit hash) or `at` (ISO 8601 time)
+    /// select the version to restore; the default is the version before
+    /// the latest change.
+    pub async fn rollback_config(
+        &self,
+        params: &HashMap<String, String>,
+        origin: &ChangeOrigin<'_>,
+    ) -> CommandResult {
         if !self.config.config_management.enabled {
             return CommandResult {
                 command_id: String::new(),
@@ -328,6 +542,10 @@ impl ConfigManager {
             };
         }
 
+        if let Some(history) = &self.history {
+            return self.rollback_git(history, path, params, origin);
+        }
+
         // Find the latest backup
         let backup_path = match self.find_latest_backup(path) {
             Some(p) => p,
@@ -397,6 +615,37 @@ impl ConfigManager {
             }
         };
 
+        if let Some(history) = &self.history {
+            return match history.log(path) {
+                Ok(log) => CommandResult {
+                    success: true,
+                    output: if log.is_empty() {
+                        "No backups found".to_string()
+                    } else {
+                        log.iter()
+                            .map(|r| format!("{} {} {}", r.hash, r.time, r.message))
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    },
+                    config_result: Some(ConfigResult {
+                        path: path.clone(),
+                        backups: log
+                            .into_iter()
+                            .map(|r| ConfigBackup {
+                                created_at: r.time,
+                                revision: r.hash,
+                                message: r.message,
+                                ..Default::default()
+                            })
+                            .collect(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                Err(e) => Self::error_result(e),
+            };
+        }
+
         let backups = self.find_all_backups(path);
 
         let output = if backups.is_empty() {
@@ -418,6 +667,139 @@ impl ConfigManager {
         }
     }
 
+    /// Diff two versions of a config file.
+    ///
+    /// Params: `path`, `from` (default: the latest backup) and `to` (default:
+    /// the current file). Revisions can only be selected in git backup mode.
+    pub async fn diff_config(&self, params: &HashMap<String, String>) -> CommandResult {
+        if !self.config.config_management.enabled {
+            return Self::error_result("Config management is disabled".to_string());
+        }
+        let Some(path) = params.get("path") else {
+            return Self::error_result("Config path is required".to_string());
+        };
+        if let Err(e) = self.validate_config_path(path) {
+            return Self::error_result(e);
+        }
+
+        let from = params.get("from").filter(|r| !r.is_empty());
+        let to = params.get("to").filter(|r| !r.is_empty());
+        let versions = match &self.history {
+            Some(history) => {
+                let from = match from {
+                    Some(revision) => Ok(revision.clone()),
+                    None => history.log(path).and_then(|log| {
+                        log.into_iter()
+                            .next()
+                            .map(|r| r.hash)
+                            .ok_or_else(|| "No backup found for this config".to_string())
+                    }),
+                };
+                from.and_then(|from| {
+                    let old = history.show(path, &from)?;
+                    let new = match to {
+                        Some(to) => (history.show(path, to)?, to.clone()),
+                        None => (
+                            fs::read_to_string(path).unwrap_or_default(),
+                            "current".into(),
+                        ),
+                    };
+                    Ok(((old, from), new))
+                })
+            }
+            None if from.is_some() || to.is_some() => {
+                Err("Revisions require config_management.backup_mode: git".to_string())
+            }
+            None => match self.find_latest_backup(path) {
+                Some(backup) => fs::read_to_string(&backup)
+                    .map(|old| {
+                        (
+                            (old, backup.display().to_string()),
+                            (
+                                fs::read_to_string(path).unwrap_or_default(),
+                                "current".into(),
+                            ),
+                        )
+                    })
+                    .map_err(|e| format!("Failed to read backup: {e}")),
+                None => Err("No backup found for this config".to_string()),
+            },
+        };
+        let ((old, old_label), (new, new_label)) = match versions {
+            Ok(v) => v,
+            Err(e) => return Self::error_result(e),
+        };
+
+        let diff = similar::TextDiff::from_lines(&old, &new)
+            .unified_diff()
+            .header(&old_label, &new_label)
+            .to_string();
+        let changed = old != new;
+        CommandResult {
+            success: true,
+            output: if changed {
+                self.sanitize_content(&diff)
+            } else {
+                "No differences".to_string()
+            },
+            config_result: Some(ConfigResult {
+                path: path.clone(),
+                diff: self.sanitize_content(&diff),
+                changed,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn rollback_git(
+        &self,
+        history: &ConfigHistory,
+        path: &str,
+        params: &HashMap<String, String>,
+        origin: &ChangeOrigin<'_>,
+    ) -> CommandResult {
+        // Keep edits made outside the agent restorable as well
+        if let Err(e) = history.record(path, &format!("Recorded {path} before rollback")) {
+            warn!("Failed to record {} before rollback: {}", path, e);
+        }
+
+        let revision = match (params.get("revision"), params.get("at")) {
+            (Some(revision), _) => Ok(revision.clone()),
+            (None, Some(at)) => history.revision_at(path, at),
+            (None, None) => history.log(path).and_then(|log| {
+                log.get(1)
+                    .map(|r| r.hash.clone())
+                    .ok_or_else(|| "No previous version recorded for this config".to_string())
+            }),
+        };
+        let (content, revision) = match revision.and_then(|r| Ok((history.show(path, &r)?, r))) {
+            Ok(v) => v,
+            Err(e) => return Self::error_result(e),
+        };
+
+        if let Err(e) = write_replace(path, &content) {
+            return Self::error_result(format!("Failed to restore config: {e}"));
+        }
+        let short = &revision[..revision.len().min(12)];
+        info!("[AUDIT] Rolled back config {} to revision {}", path, short);
+        self.record_change(&format!("CONFIG_ROLLBACK to {short}"), path, origin);
+        CommandResult {
+            success: true,
+            output: format!("Config rolled back to revision {short}"),
+            ..Default::default()
+        }
+    }
+
+    /// Commit a change made by a command to the git history, if enabled
+    fn record_change(&self, action: &str, path: &str, origin: &ChangeOrigin<'_>) {
+        if let Some(history) = &self.history {
+            if let Err(e) = history.record(path, &change_message(action, path, origin)) {
+                warn!("Failed to record {} in config history: {}", path, e);
+            }
+        }
+    }
+
     /// Validate config path against whitelist and forbidden paths
     fn validate_config_path(&self, path: &str) -> Result<(), String> {
         // Check for obvious path traversal patterns
@@ -498,8 +880,20 @@ impl ConfigManager {
         result
     }
 
-    /// Create a backup of the config file
-    fn create_backup(&self, path: &str) -> Result<PathBuf, String> {
+    /// Create a backup of the config file, returning the backup path (or
+    /// the revision in git mode)
+    fn create_backup(&self, path: &str) -> Result<String, String> {
+        if let Some(history) = &self.history {
+            // Commits the current state only if it was edited outside the agent
+            history.record(path, &format!("Recorded {path} before change"))?;
+            return history
+                .log(path)?
+                .into_iter()
+                .next()
+                .map(|r| r.hash)
+                .ok_or_else(|| "Nothing recorded".to_string());
+        }
+
         let backup_dir = PathBuf::from(&self.config.config_management.backup_dir);
 
         // Ensure backup directory exists
@@ -523,7 +917,7 @@ impl ConfigManager {
         self.cleanup_old_backups(path);
 
         info!("Created backup: {}", backup_path.display());
-        Ok(backup_path)
+        Ok(backup_path.display().to_string())
     }
 
     /// Find the latest backup for a config file
@@ -581,3 +975,263 @@ impl ConfigManager {
         }
     }
 }
+
+/// Health check run after a config write
+#[derive(Debug, PartialEq)]
+enum Verification {
+    /// Command from `config_management.verify_commands`, must exit 0
+    Command(Vec<String>, Duration),
+    /// `tcp://host:port`, must accept a connection
+    Tcp(String, Duration),
+    /// http(s) URL, must answer with a 2xx status
+    Http(String, Duration),
+}
+
+/// Pause between verification attempts
+const VERIFY_RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+impl Verification {
+    /// Params: `verify_command` (must be listed in
+    /// `config_management.verify_commands`), or `verify_probe`
+    /// (`tcp://host:port` or an http(s) URL), and `verify_timeout_seconds`
+    /// (default `config_management.verify_timeout_seconds`).
+    fn from_params(
+        config: &Config,
+        params: &HashMap<String, String>,
+    ) -> Result<Option<Self>, String> {
+        let cfg = &config.config_management;
+        let timeout = match params.get("verify_timeout_seconds") {
+            Some(value) => value
+                .parse::<u64>()
+                .ok()
+                .filter(|t| *t > 0)
+                .ok_or_else(|| format!("Invalid verify_timeout_seconds: {value}"))?,
+            None => cfg.verify_timeout_seconds,
+        };
+        let timeout = Duration::from_secs(timeout);
+
+        if let Some(command) = params.get("verify_command").filter(|c| !c.is_empty()) {
+            if !cfg.verify_commands.iter().any(|allowed| allowed == command) {
+                warn!("[AUDIT] Verify command not in allowed list: {}", command);
+                return Err("verify_command is not in config_management.verify_commands".into());
+            }
+            let argv = command.split_whitespace().map(String::from).collect();
+            return Ok(Some(Self::Command(argv, timeout)));
+        }
+        match params.get("verify_probe").filter(|p| !p.is_empty()) {
+            Some(probe) if probe.starts_with("tcp://") => Ok(Some(Self::Tcp(
+                probe["tcp://".len()..].to_string(),
+                timeout,
+            ))),
+            Some(probe) if probe.starts_with("http://") || probe.starts_with("https://") => {
+                Ok(Some(Self::Http(probe.clone(), timeout)))
+            }
+            Some(probe) => Err(format!("Unsupported verify_probe: {probe}")),
+            None => Ok(None),
+        }
+    }
+
+    /// Repeat the check until it passes or the timeout expires
+    async fn wait(&self) -> Result<(), String> {
+        let timeout = match self {
+            Self::Command(_, t) | Self::Tcp(_, t) | Self::Http(_, t) => *t,
+        };
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.check(remaining.max(Duration::from_secs(1))).await {
+                Ok(()) => return Ok(()),
+                Err(e) if Instant::now() + VERIFY_RETRY_INTERVAL >= deadline => return Err(e),
+                Err(_) => tokio::time::sleep(VERIFY_RETRY_INTERVAL).await,
+            }
+        }
+    }
+
+    async fn check(&self, remaining: Duration) -> Result<(), String> {
+        // A single attempt never takes longer than this
+        let attempt = remaining.min(Duration::from_secs(10));
+        match self {
+            Self::Command(argv, _) => {
+                let args: Vec<&str> = argv[1..].iter().map(String::as_str).collect();
+                match run_command_async(&argv[0], &args, CommandTimeout::Custom(attempt)).await {
+                    AsyncResult::Success(_) => Ok(()),
+                    AsyncResult::Failed(code, _) => Err(format!("{} exited with {code}", argv[0])),
+                    AsyncResult::Timeout => Err(format!("{} timed out", argv[0])),
+                    AsyncResult::NotFound => Err(format!("{} not found", argv[0])),
+                    AsyncResult::Error(e) => Err(e),
+                }
+            }
+            Self::Tcp(address, _) => {
+                match tokio::time::timeout(
+                    attempt,
+                    tokio::net::TcpStream::connect(address.as_str()),
+                )
+                .await
+                {
+                    Ok(Ok(_)) => Ok(()),
+                    Ok(Err(e)) => Err(format!("{address}: {e}")),
+                    Err(_) => Err(format!("{address}: connection timed out")),
+                }
+            }
+            Self::Http(url, _) => {
+                let null = if cfg!(windows) { "NUL" } else { "/dev/null" };
+                let max_time = attempt.as_secs().max(1).to_string();
+                let args = ["-fsS", "-o", null, "--max-time", &max_time, url.as_str()];
+                match run_command_async(
+                    "curl",
+                    &args,
+                    CommandTimeout::Custom(attempt + Duration::from_secs(1)),
+                )
+                .await
+                {
+                    AsyncResult::Success(_) => Ok(()),
+                    AsyncResult::Failed(code, _) => Err(format!("{url}: curl exited with {code}")),
+                    AsyncResult::NotFound => Err("curl not found".to_string()),
+                    AsyncResult::Timeout => Err(format!("{url}: timed out")),
+                    AsyncResult::Error(e) => Err(e),
+                }
+            }
+        }
+    }
+}
+
+/// Parse `content` as `format` (yaml, json, toml or auto)
+fn check_syntax(content: &str, format: &str) -> Result<(), String> {
+    // Map all success values to () since we only care about parse success
+    match format {
+        "yaml" | "yml" => serde_yaml::from_str::<serde_yaml::Value>(content)
+            .map(|_| ())
+            .map_err(|e| format!("Invalid YAML: {e}")),
+        "json" => serde_json::from_str::<serde_json::Value>(content)
+            .map(|_| ())
+            .map_err(|e| format!("Invalid JSON: {e}")),
+        "toml" => toml::from_str::<toml::Value>(content)
+            .map(|_| ())
+            .map_err(|e| format!("Invalid TOML: {e}")),
+        _ => {
+            // Try each format (auto-detect)
+            let is_yaml = serde_yaml::from_str::<serde_yaml::Value>(content).is_ok();
+            let is_json = serde_json::from_str::<serde_json::Value>(content).is_ok();
+            let is_toml = toml::from_str::<toml::Value>(content).is_ok();
+
+            if is_yaml || is_json || is_toml {
+                Ok(())
+            } else {
+                Err("Content is not valid YAML, JSON, or TOML".to_string())
+            }
+        }
+    }
+}
+
+/// Render a template; undefined variables are errors rather than empty
+/// strings so a missing fleet variable can't produce a broken config
+fn render_template(
+    template: &str,
+    mut variables: serde_json::Map<String, serde_json::Value>,
+    agent: serde_json::Value,
+) -> Result<String, String> {
+    let mut env = minijinja::Environment::new();
+    env.set_undefined_behavior(minijinja::UndefinedBehavior::Strict);
+    env.set_keep_trailing_newline(true);
+    variables.insert("agent".to_string(), agent);
+    env.render_str(template, variables)
+        .map_err(|e| format!("Template error: {e}"))
+}
+
+/// Replace a file through a temporary sibling and rename, keeping the
+/// permissions of the file it replaces
+fn write_replace(path: &str, content: &str) -> std::io::Result<()> {
+    let tmp = format!("{path}.nanolink-tmp");
+    fs::write(&tmp, content)?;
+    if let Ok(meta) = fs::metadata(path) {
+        fs::set_permissions(&tmp, meta.permissions())?;
+    }
+    fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_template() {
+        let variables = serde_json::json!({"port": 8080, "upstreams": ["a", "b"]});
+        let serde_json::Value::Object(variables) = variables else {
+            unreachable!()
+        };
+        let agent = serde_json::json!({"hostname": "web1"});
+
+        let rendered = render_template(
+            "listen {{ port }}\nserver_name {{ agent.hostname }}\n{% for u in upstreams %}upstream {{ u }}\n{% endfor %}",
+            variables.clone(),
+            agent.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            rendered,
+            "listen 8080\nserver_name web1\nupstream a\nupstream b\n"
+        );
+
+        assert!(render_template("{{ missing }}", variables, agent).is_err());
+    }
+
+    #[test]
+    fn test_verification_from_params() {
+        let mut config = Config::sample();
+        config.config_management.verify_commands = vec!["nginx -t".to_string()];
+        let params = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        assert_eq!(Verification::from_params(&config, &params(&[])), Ok(None));
+        assert_eq!(
+            Verification::from_params(
+                &config,
+                &params(&[
+                    ("verify_command", "nginx -t"),
+                    ("verify_timeout_seconds", "5")
+                ])
+            ),
+            Ok(Some(Verification::Command(
+                vec!["nginx".to_string(), "-t".to_string()],
+                Duration::from_secs(5)
+            )))
+        );
+        assert!(
+            Verification::from_params(&config, &params(&[("verify_command", "rm -rf /")])).is_err()
+        );
+        assert_eq!(
+            Verification::from_params(&config, &params(&[("verify_probe", "tcp://127.0.0.1:80")])),
+            Ok(Some(Verification::Tcp(
+                "127.0.0.1:80".to_string(),
+                Duration::from_secs(30)
+            )))
+        );
+        assert!(
+            Verification::from_params(&config, &params(&[("verify_probe", "udp://x:1")])).is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verification_wait() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let check = Verification::Tcp(address, Duration::from_secs(1));
+        assert!(check.wait().await.is_ok());
+
+        drop(listener);
+        assert!(check.wait().await.is_err());
+    }
+
+    #[test]
+    fn test_check_syntax() {
+        assert!(check_syntax("a: 1", "yaml").is_ok());
+        assert!(check_syntax("{", "json").is_err());
+        assert!(check_syntax("a = 1", "toml").is_ok());
+    }
+}
@@ -10,6 +10,9 @@
 
 use std::collections::HashMap;
 use std::process::Command;
+use std::sync::LazyLock;
+
+use regex::Regex;
 use tracing::{info, warn};
 
 use crate::proto::{CommandResult, LogEntry, LogQueryResult};
@@ -104,6 +107,34 @@ const SENSITIVE_PATTERNS: &[(&str, &str)] = &[
     (r"X-Api-Key\s*:\s*\S+", "X-Api-Key: ***REDACTED***"),
 ];
 
+/// Compiled [`SENSITIVE_PATTERNS`]
+static SANITIZERS: LazyLock<Vec<(Regex, &'static str)>> = LazyLock::new(|| {
+    SENSITIVE_PATTERNS
+        .iter()
+        .filter_map(|(pattern, replacement)| {
+            Regex::new(&format!("(?i){pattern}"))
+                .ok()
+                .map(|re| (re, *replacement))
+        })
+        .collect()
+});
+
+/// Redact sensitive information from a log line, returning the line and
+/// whether anything was redacted
+pub fn redact_sensitive(line: &str) -> (String, bool) {
+    let mut result = line.to_string();
+    let mut was_sanitized = false;
+
+    for (re, replacement) in SANITIZERS.iter() {
+        if re.is_match(&result) {
+            result = re.replace_all(&result, *replacement).to_string();
+            was_sanitized = true;
+        }
+    }
+
+    (result, was_sanitized)
+}
+
 /// Allowed log file paths (whitelist)
 const ALLOWED_LOG_PATHS: &[&str] = &[
     "/var/log/syslog",
@@ -159,19 +190,7 @@ impl LogExecutor {
             return (line.to_string(), false);
         }
 
-        let mut result = line.to_string();
-        let mut was_sanitized = false;
-
-        for (pattern, replacement) in SENSITIVE_PATTERNS {
-            if let Ok(re) = regex::Regex::new(&format!("(?i){pattern}")) {
-                if re.is_match(&result) {
-                    result = re.replace_all(&result, *replacement).to_string();
-                    was_sanitized = true;
-                }
-            }
-        }
-
-        (result, was_sanitized)
+        redact_sensitive(line)
     }
 
     /// Parse a log line into LogEntry
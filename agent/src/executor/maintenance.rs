@@ -0,0 +1,599 @@
+//! Maintenance windows
+//!
+//! With `maintenance.enabled`, update commands are not run when they arrive
+//! but queued for a maintenance window. The queue is kept in
+//! `maintenance.state_file` so it survives restarts. The scheduler starts
+//! queued jobs while their window is open, at most `max_concurrent` at a
+//! time, each wrapped in the window's pre and post hooks.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use chrono::{Local, NaiveDateTime, TimeZone};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::time;
+use tracing::{info, warn};
+
+use super::config_history::ChangeOrigin;
+use super::package_mgr::PackageManager;
+use crate::config::{Config, MaintenanceWindow};
+use crate::proto::{
+    CommandResult, CommandType, MaintenanceJob, MaintenanceStatus, MaintenanceWindowState,
+};
+use crate::utils::async_command::{CommandResult as RunResult, CommandTimeout, run_command_async};
+use crate::utils::at_rest;
+use crate::utils::cron::CronSchedule;
+
+/// Finished jobs kept for MAINTENANCE_STATUS
+const MAX_FINISHED_JOBS: usize = 100;
+
+/// Output kept per job
+const MAX_OUTPUT_BYTES: usize = 4096;
+
+/// Associated data for at-rest encryption
+const STATE_PURPOSE: &str = "maintenance";
+
+/// How often the scheduler looks for open windows
+const TICK: Duration = Duration::from_secs(30);
+
+static QUEUE: OnceLock<Mutex<JobQueue>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+    /// The agent stopped while the job was running
+    Interrupted,
+}
+
+impl JobStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+            Self::Interrupted => "interrupted",
+        }
+    }
+
+    fn is_finished(self) -> bool {
+        !matches!(self, Self::Queued | Self::Running)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct QueuedJob {
+    id: String,
+    /// CommandType name, e.g. "PACKAGE_UPDATE"
+    command: String,
+    params: HashMap<String, String>,
+    window: String,
+    status: JobStatus,
+    requested_by: String,
+    queued_at: String,
+    #[serde(default)]
+    started_at: String,
+    #[serde(default)]
+    finished_at: String,
+    #[serde(default)]
+    output: String,
+}
+
+impl QueuedJob {
+    fn to_proto(&self) -> MaintenanceJob {
+        MaintenanceJob {
+            id: self.id.clone(),
+            command: self.command.clone(),
+            window: self.window.clone(),
+            status: self.status.as_str().to_string(),
+            requested_by: self.requested_by.clone(),
+            queued_at: self.queued_at.clone(),
+            started_at: self.started_at.clone(),
+            finished_at: self.finished_at.clone(),
+            output: self.output.clone(),
+            params: self.params.clone(),
+        }
+    }
+}
+
+/// Persistent job queue
+struct JobQueue {
+    path: PathBuf,
+    jobs: Vec<QueuedJob>,
+}
+
+impl JobQueue {
+    /// Load the queue. Jobs still marked running were cut off by a restart.
+    fn load(path: &Path) -> Self {
+        let mut jobs: Vec<QueuedJob> = fs::read(path)
+            .ok()
+            .and_then(|data| match at_rest::open(STATE_PURPOSE, &data) {
+                Ok(content) => serde_json::from_slice(&content).ok(),
+                Err(e) => {
+                    warn!("Failed to read maintenance queue: {}", e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        for job in jobs.iter_mut().filter(|j| j.status == JobStatus::Running) {
+            warn!("Maintenance job {} was interrupted by a restart", job.id);
+            job.status = JobStatus::Interrupted;
+            job.finished_at = now_rfc3339();
+        }
+        Self {
+            path: path.to_path_buf(),
+            jobs,
+        }
+    }
+
+    fn save(&mut self) {
+        // Keep every pending job and the most recent finished ones
+        let finished = self.jobs.iter().filter(|j| j.status.is_finished()).count();
+        let mut excess = finished.saturating_sub(MAX_FINISHED_JOBS);
+        self.jobs.retain(|job| {
+            if excess > 0 && job.status.is_finished() {
+                excess -= 1;
+                false
+            } else {
+                true
+            }
+        });
+
+        let write = || -> std::io::Result<()> {
+            if let Some(parent) = self.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let tmp = self.path.with_extension("json.tmp");
+            let sealed = at_rest::seal(STATE_PURPOSE, &serde_json::to_vec_pretty(&self.jobs)?)
+                .map_err(std::io::Error::other)?;
+            fs::write(&tmp, sealed)?;
+            fs::rename(&tmp, &self.path)
+        };
+        if let Err(e) = write() {
+            warn!(
+                "Failed to save maintenance queue to {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+
+    fn running(&self, window: &str) -> usize {
+        self.jobs
+            .iter()
+            .filter(|j| j.window == window && j.status == JobStatus::Running)
+            .count()
+    }
+
+    /// Mark up to `slots` queued jobs of `window` as running, oldest first
+    fn start(&mut self, window: &str, slots: usize) -> Vec<QueuedJob> {
+        let started: Vec<QueuedJob> = self
+            .jobs
+            .iter_mut()
+            .filter(|j| j.window == window && j.status == JobStatus::Queued)
+            .take(slots)
+            .map(|job| {
+                job.status = JobStatus::Running;
+                job.started_at = now_rfc3339();
+                job.clone()
+            })
+            .collect();
+        if !started.is_empty() {
+            self.save();
+        }
+        started
+    }
+
+    fn finish(&mut self, id: &str, success: bool, output: String) {
+        if let Some(job) = self.jobs.iter_mut().find(|j| j.id == id) {
+            job.status = if success {
+                JobStatus::Succeeded
+            } else {
+                JobStatus::Failed
+            };
+            job.finished_at = now_rfc3339();
+            job.output = truncate(output);
+        }
+        self.save();
+    }
+}
+
+fn queue(config: &Config) -> &'static Mutex<JobQueue> {
+    QUEUE.get_or_init(|| Mutex::new(JobQueue::load(Path::new(&config.maintenance.state_file))))
+}
+
+fn now_rfc3339() -> String {
+    Local::now().to_rfc3339()
+}
+
+fn truncate(mut output: String) -> String {
+    if output.len() > MAX_OUTPUT_BYTES {
+        let mut end = MAX_OUTPUT_BYTES;
+        while !output.is_char_boundary(end) {
+            end -= 1;
+        }
+        output.truncate(end);
+        output.push_str("\n[truncated]");
+    }
+    output
+}
+
+/// Configured windows with valid schedules
+fn windows(config: &Config) -> Vec<(MaintenanceWindow, CronSchedule)> {
+    config
+        .maintenance
+        .windows
+        .iter()
+        .filter_map(|window| match window.schedule.parse() {
+            Ok(schedule) => Some((window.clone(), schedule)),
+            Err(e) => {
+                warn!("Ignoring maintenance window {}: {}", window.name, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// A window is open for `duration_minutes` after each scheduled start
+fn is_open(window: &MaintenanceWindow, schedule: &CronSchedule, now: &NaiveDateTime) -> bool {
+    window.duration_minutes > 0
+        && schedule
+            .last_at_or_before(now, window.duration_minutes - 1)
+            .is_some()
+}
+
+/// Maintenance window executor
+pub struct MaintenanceExecutor {
+    config: Arc<Config>,
+}
+
+impl MaintenanceExecutor {
+    /// Create a new maintenance executor
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
+    }
+
+    /// Helper to create an error CommandResult
+    fn error_result(error: String) -> CommandResult {
+        CommandResult {
+            command_id: String::new(),
+            success: false,
+            output: String::new(),
+            error,
+            ..Default::default()
+        }
+    }
+
+    /// Queue an update command for a maintenance window.
+    ///
+    /// Params: `window` (window name, default the first configured window);
+    /// all params are passed to the command when it runs.
+    pub fn enqueue(
+        &self,
+        command_type: CommandType,
+        params: &HashMap<String, String>,
+        origin: &ChangeOrigin<'_>,
+    ) -> CommandResult {
+        if !self.config.package_management.enabled {
+            return Self::error_result("Package management is disabled".to_string());
+        }
+        let windows = windows(&self.config);
+        let window = match params.get("window").filter(|w| !w.is_empty()) {
+            Some(name) => windows.iter().find(|(w, _)| &w.name == name),
+            None => windows.first(),
+        };
+        let Some((window, schedule)) = window else {
+            return Self::error_result(match params.get("window") {
+                Some(name) => format!("Unknown maintenance window: {name}"),
+                None => "No maintenance windows configured".to_string(),
+            });
+        };
+
+        let job = QueuedJob {
+            id: uuid::Uuid::new_v4().simple().to_string()[..12].to_string(),
+            command: command_type.as_str_name().to_string(),
+            params: params.clone(),
+            window: window.name.clone(),
+            status: JobStatus::Queued,
+            requested_by: origin.server.to_string(),
+            queued_at: now_rfc3339(),
+            started_at: String::new(),
+            finished_at: String::new(),
+            output: String::new(),
+        };
+        info!(
+            "[AUDIT] Maintenance: queued {} as job {} for window {} (requested by {})",
+            job.command, job.id, job.window, job.requested_by
+        );
+
+        let now = Local::now().naive_local();
+        let when = if is_open(window, schedule, &now) {
+            "window is open, starting shortly".to_string()
+        } else {
+            match schedule.next_after(&now).and_then(local_rfc3339) {
+                Some(next) => format!("window opens {next}"),
+                None => "window has no upcoming start".to_string(),
+            }
+        };
+        let output = format!(
+            "Queued {} as job {} for maintenance window {} ({when})",
+            job.command, job.id, job.window
+        );
+
+        {
+            let mut queue = queue(&self.config).lock();
+            queue.jobs.push(job);
+            queue.save();
+        }
+        CommandResult {
+            success: true,
+            output,
+            maintenance: Some(self.snapshot()),
+            ..Default::default()
+        }
+    }
+
+    /// Maintenance windows and the job queue
+    pub async fn status(&self) -> CommandResult {
+        let status = self.snapshot();
+        let open: Vec<&str> = status
+            .windows
+            .iter()
+            .filter(|w| w.open)
+            .map(|w| w.name.as_str())
+            .collect();
+        CommandResult {
+            success: true,
+            output: format!(
+                "{} windows ({} open), {} jobs queued",
+                status.windows.len(),
+                open.len(),
+                status.windows.iter().map(|w| w.queued).sum::<u32>()
+            ),
+            maintenance: Some(status),
+            ..Default::default()
+        }
+    }
+
+    /// Cancel a queued job
+    pub async fn cancel(&self, job_id: &str) -> CommandResult {
+        let mut queue = queue(&self.config).lock();
+        let Some(job) = queue.jobs.iter_mut().find(|j| j.id == job_id) else {
+            return Self::error_result(format!("Unknown maintenance job: {job_id}"));
+        };
+        if job.status != JobStatus::Queued {
+            return Self::error_result(format!(
+                "Job {job_id} is {}, only queued jobs can be cancelled",
+                job.status.as_str()
+            ));
+        }
+        job.status = JobStatus::Cancelled;
+        job.finished_at = now_rfc3339();
+        info!("[AUDIT] Maintenance: cancelled job {}", job_id);
+        queue.save();
+        drop(queue);
+
+        CommandResult {
+            success: true,
+            output: format!("Cancelled maintenance job {job_id}"),
+            maintenance: Some(self.snapshot()),
+            ..Default::default()
+        }
+    }
+
+    fn snapshot(&self) -> MaintenanceStatus {
+        let now = Local::now().naive_local();
+        let queue = queue(&self.config).lock();
+        MaintenanceStatus {
+            windows: windows(&self.config)
+                .iter()
+                .map(|(window, schedule)| MaintenanceWindowState {
+                    name: window.name.clone(),
+                    schedule: window.schedule.clone(),
+                    duration_minutes: window.duration_minutes,
+                    max_concurrent: window.max_concurrent as u32,
+                    open: is_open(window, schedule, &now),
+                    next_start: schedule
+                        .next_after(&now)
+                        .and_then(local_rfc3339)
+                        .unwrap_or_default(),
+                    running: queue.running(&window.name) as u32,
+                    queued: queue
+                        .jobs
+                        .iter()
+                        .filter(|j| j.window == window.name && j.status == JobStatus::Queued)
+                        .count() as u32,
+                })
+                .collect(),
+            jobs: queue.jobs.iter().map(QueuedJob::to_proto).collect(),
+        }
+    }
+
+    /// Start queued jobs while their windows are open. Runs until the task
+    /// is aborted.
+    pub async fn run_scheduler(self) {
+        let windows = windows(&self.config);
+        if windows.is_empty() {
+            warn!("Maintenance is enabled but no valid windows are configured");
+        }
+        // Load now so interrupted jobs are reported right away
+        queue(&self.config).lock().save();
+
+        let mut ticker = time::interval(TICK);
+        loop {
+            ticker.tick().await;
+            let now = Local::now().naive_local();
+            for (window, schedule) in &windows {
+                if !is_open(window, schedule, &now) {
+                    continue;
+                }
+                let started = {
+                    let mut queue = queue(&self.config).lock();
+                    let slots = window
+                        .max_concurrent
+                        .max(1)
+                        .saturating_sub(queue.running(&window.name));
+                    queue.start(&window.name, slots)
+                };
+                for job in started {
+                    info!(
+                        "[AUDIT] Maintenance window {}: starting job {} ({})",
+                        window.name, job.id, job.command
+                    );
+                    tokio::spawn(execute(self.config.clone(), window.clone(), job));
+                }
+            }
+        }
+    }
+}
+
+/// Run a job between the window's hooks and record the outcome
+async fn execute(config: Arc<Config>, window: MaintenanceWindow, job: QueuedJob) {
+    let hook_timeout = Duration::from_secs(config.maintenance.hook_timeout_seconds);
+    let mut output = String::new();
+    let mut success = true;
+
+    for hook in &window.pre_hooks {
+        if let Err(e) = run_hook(hook, hook_timeout).await {
+            output.push_str(&format!("Pre hook `{hook}` failed: {e}\n"));
+            success = false;
+            break;
+        }
+    }
+
+    if success {
+        let manager = PackageManager::new(config.clone());
+        let result = match CommandType::from_str_name(&job.command) {
+            Some(CommandType::PackageUpdate) => manager.update_package(&job.params).await,
+            Some(CommandType::SystemUpdate) => manager.system_update(&job.params).await,
+            Some(CommandType::WindowsUpdateInstall) => {
+                manager.windows_update_install(&job.params).await
+            }
+            _ => MaintenanceExecutor::error_result(format!(
+                "{} can't run in a maintenance window",
+                job.command
+            )),
+        };
+        success = result.success;
+        output.push_str(&result.output);
+        if !result.error.is_empty() {
+            output.push_str(&result.error);
+        }
+    }
+
+    // Post hooks run even if the job failed, e.g. to undrain the node
+    for hook in &window.post_hooks {
+        if let Err(e) = run_hook(hook, hook_timeout).await {
+            output.push_str(&format!("\nPost hook `{hook}` failed: {e}"));
+        }
+    }
+
+    if success {
+        info!("[AUDIT] Maintenance job {} succeeded", job.id);
+    } else {
+        warn!("[AUDIT] Maintenance job {} failed", job.id);
+    }
+    queue(&config).lock().finish(&job.id, success, output);
+}
+
+async fn run_hook(hook: &str, timeout: Duration) -> Result<(), String> {
+    let argv: Vec<&str> = hook.split_whitespace().collect();
+    let Some((program, args)) = argv.split_first() else {
+        return Ok(());
+    };
+    match run_command_async(program, args, CommandTimeout::Custom(timeout)).await {
+        RunResult::Success(_) => Ok(()),
+        RunResult::Failed(code, _) => Err(format!("exited with {code}")),
+        RunResult::Timeout => Err("timed out".to_string()),
+        RunResult::NotFound => Err("not found".to_string()),
+        RunResult::Error(e) => Err(e),
+    }
+}
+
+fn local_rfc3339(time: NaiveDateTime) -> Option<String> {
+    Local
+        .from_local_datetime(&time)
+        .earliest()
+        .map(|t| t.to_rfc3339())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(id: &str, status: JobStatus) -> QueuedJob {
+        QueuedJob {
+            id: id.to_string(),
+            command: "SYSTEM_UPDATE".to_string(),
+            params: HashMap::new(),
+            window: "weekend".to_string(),
+            status,
+            requested_by: "10.0.0.1:39100".to_string(),
+            queued_at: now_rfc3339(),
+            started_at: String::new(),
+            finished_at: String::new(),
+            output: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_is_open() {
+        let window = MaintenanceWindow {
+            name: "weekend".to_string(),
+            schedule: "0 2 * * sat".to_string(),
+            duration_minutes: 120,
+            max_concurrent: 1,
+            pre_hooks: Vec::new(),
+            post_hooks: Vec::new(),
+        };
+        let schedule: CronSchedule = window.schedule.parse().unwrap();
+        let at = |s| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+        assert!(!is_open(&window, &schedule, &at("2026-10-17 01:59")));
+        assert!(is_open(&window, &schedule, &at("2026-10-17 02:00")));
+        assert!(is_open(&window, &schedule, &at("2026-10-17 03:59")));
+        assert!(!is_open(&window, &schedule, &at("2026-10-17 04:00")));
+    }
+
+    #[test]
+    fn test_queue_persistence() {
+        let path =
+            std::env::temp_dir().join(format!("nanolink-maintenance-{}.json", std::process::id()));
+        let mut queue = JobQueue::load(&path);
+        queue.jobs.push(job("a", JobStatus::Queued));
+        queue.jobs.push(job("b", JobStatus::Queued));
+        queue.jobs.push(job("c", JobStatus::Queued));
+
+        let started = queue.start("weekend", 2);
+        assert_eq!(
+            started.iter().map(|j| j.id.as_str()).collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+        assert_eq!(queue.running("weekend"), 2);
+        queue.finish("a", true, "x".repeat(MAX_OUTPUT_BYTES + 10));
+
+        // "b" was running when the agent stopped
+        let reloaded = JobQueue::load(&path);
+        let status: Vec<JobStatus> = reloaded.jobs.iter().map(|j| j.status).collect();
+        assert_eq!(
+            status,
+            [
+                JobStatus::Succeeded,
+                JobStatus::Interrupted,
+                JobStatus::Queued
+            ]
+        );
+        assert!(reloaded.jobs[0].output.ends_with("[truncated]"));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
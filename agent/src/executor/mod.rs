@@ -1,21 +1,85 @@
+//! Command executors
+//!
+//! Without the `executors` feature (the lite build) only agent updates and
+//! log queries are compiled in; commands that act on the host are refused.
+
+#[cfg(feature = "executors")]
+mod account_audit;
+#[cfg(feature = "executors")]
+mod applications;
+mod bundle;
+#[cfg(feature = "executors")]
+mod cleanup;
+#[cfg(feature = "executors")]
+mod config_history;
+#[cfg(feature = "executors")]
 mod config_mgr;
+#[cfg(feature = "executors")]
 mod docker_ops;
+#[cfg(feature = "executors")]
 mod file_ops;
 mod log_ops;
+#[cfg(feature = "executors")]
+mod maintenance;
+#[cfg(feature = "executors")]
 mod package_mgr;
+#[cfg(feature = "executors")]
+mod power_mgr;
+#[cfg(feature = "executors")]
+mod print_queue;
+#[cfg(feature = "executors")]
 mod process_mgr;
+#[cfg(feature = "executors")]
+mod recording;
+mod rollout;
+#[cfg(feature = "executors")]
+mod sandbox;
+#[cfg(feature = "executors")]
 mod script_executor;
+#[cfg(feature = "executors")]
 mod service_mgr;
+#[cfg(feature = "executors")]
 mod shell;
+#[cfg(feature = "executors")]
+mod speedtest;
 mod update;
+#[cfg(feature = "executors")]
+mod windows_update;
 
+#[cfg(feature = "executors")]
+pub use account_audit::AccountAuditExecutor;
+#[cfg(feature = "executors")]
+pub use cleanup::CleanupExecutor;
+#[cfg(feature = "executors")]
+pub use config_history::ChangeOrigin;
+#[cfg(feature = "executors")]
 pub use config_mgr::ConfigManager;
+#[cfg(feature = "executors")]
 pub use docker_ops::DockerExecutor;
+#[cfg(feature = "executors")]
 pub use file_ops::FileExecutor;
-pub use log_ops::LogExecutor;
+pub use log_ops::{LogExecutor, redact_sensitive};
+#[cfg(feature = "executors")]
+pub use maintenance::MaintenanceExecutor;
+#[cfg(feature = "executors")]
 pub use package_mgr::PackageManager;
+#[cfg(feature = "executors")]
+pub use power_mgr::{PowerAction, PowerManager};
+#[cfg(feature = "executors")]
+pub use print_queue::PrintQueueExecutor;
+#[cfg(feature = "executors")]
 pub use process_mgr::ProcessExecutor;
+#[cfg(feature = "executors")]
+pub use recording::SessionRecorder;
+pub use rollout::{CANDIDATE_LIFETIME, confirm_rollout, serve_health, set_config_path};
+#[cfg(feature = "executors")]
+pub use sandbox::Sandbox;
+#[cfg(feature = "executors")]
 pub use script_executor::ScriptExecutor;
+#[cfg(feature = "executors")]
 pub use service_mgr::ServiceExecutor;
+#[cfg(feature = "executors")]
 pub use shell::ShellExecutor;
+#[cfg(feature = "executors")]
+pub use speedtest::SpeedTestExecutor;
 pub use update::UpdateExecutor;
@@ -1,15 +1,29 @@
 use std::collections::HashMap;
-use std::process::Command;
+use std::process::{Command, Output};
 use std::sync::Arc;
+
+use nanolink_parse::{dpkg, winget};
 use tracing::{info, warn};
 
 use crate::config::Config;
 use crate::proto::{CommandResult, PackageInfo};
 
+use super::Sandbox;
+use super::applications;
+use super::windows_update;
+
+/// Windows Update searches and installs can be slow
+#[cfg(target_os = "windows")]
+const WINDOWS_UPDATE_SEARCH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(600);
+#[cfg(target_os = "windows")]
+const WINDOWS_UPDATE_INSTALL_TIMEOUT: std::time::Duration =
+    std::time::Duration::from_secs(2 * 60 * 60);
+
 /// Package manager executor with multi-platform support
 pub struct PackageManager {
     config: Arc<Config>,
     package_manager_type: PackageManagerType,
+    sandbox: Sandbox,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -31,6 +45,7 @@ impl PackageManager {
         let package_manager_type = Self::detect_package_manager();
         info!("Detected package manager: {:?}", package_manager_type);
         Self {
+            sandbox: Sandbox::new(config.package_management.sandbox.clone()),
             config,
             package_manager_type,
         }
@@ -95,6 +110,11 @@ impl PackageManager {
     }
 
     /// List installed packages
+    ///
+    /// Params: `filter`, `limit` (per source, default 100) and `source`:
+    /// `packages` for the package manager, `applications` for installed
+    /// desktop applications, or `all`. Defaults to `all` when
+    /// `package_management.include_applications` is set, `packages` otherwise.
     pub async fn list_packages(&self, params: &HashMap<String, String>) -> CommandResult {
         if !self.config.package_management.enabled {
             return CommandResult {
@@ -111,25 +131,45 @@ impl PackageManager {
             .get("limit")
             .and_then(|s| s.parse().ok())
             .unwrap_or(100);
+        let include_applications = self.config.package_management.include_applications;
+        let source = params
+            .get("source")
+            .map(|s| s.as_str())
+            .unwrap_or(if include_applications {
+                "all"
+            } else {
+                "packages"
+            });
+
+        if source != "packages" && !include_applications {
+            return CommandResult {
+                command_id: String::new(),
+                success: false,
+                output: String::new(),
+                error:
+                    "Application inventory is disabled (package_management.include_applications)"
+                        .to_string(),
+                ..Default::default()
+            };
+        }
 
-        let packages = match self.package_manager_type {
-            PackageManagerType::Apt => self.list_apt_packages(filter, limit),
-            PackageManagerType::Yum | PackageManagerType::Dnf => {
-                self.list_yum_packages(filter, limit)
-            }
-            PackageManagerType::Pacman => self.list_pacman_packages(filter, limit),
-            PackageManagerType::Brew => self.list_brew_packages(filter, limit),
-            PackageManagerType::Winget => self.list_winget_packages(filter, limit),
-            PackageManagerType::Choco => self.list_choco_packages(filter, limit),
-            PackageManagerType::Unknown => {
-                return CommandResult {
-                    command_id: String::new(),
-                    success: false,
-                    output: String::new(),
-                    error: "No supported package manager found".to_string(),
-                    ..Default::default()
+        let packages = match source {
+            "packages" => self.list_manager_packages(filter, limit),
+            "applications" => applications::list(filter, limit),
+            "all" => {
+                // Hosts without a package manager still have applications
+                let packages = match self.package_manager_type {
+                    PackageManagerType::Unknown => Ok(Vec::new()),
+                    _ => self.list_manager_packages(filter, limit),
                 };
+                packages.and_then(|mut packages| {
+                    packages.extend(applications::list(filter, limit)?);
+                    Ok(packages)
+                })
             }
+            other => Err(format!(
+                "Invalid source: {other} (expected packages, applications or all)"
+            )),
         };
 
         match packages {
@@ -154,6 +194,25 @@ impl PackageManager {
         }
     }
 
+    /// Packages known to the detected package manager
+    fn list_manager_packages(
+        &self,
+        filter: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<PackageInfo>, String> {
+        match self.package_manager_type {
+            PackageManagerType::Apt => self.list_apt_packages(filter, limit),
+            PackageManagerType::Yum | PackageManagerType::Dnf => {
+                self.list_yum_packages(filter, limit)
+            }
+            PackageManagerType::Pacman => self.list_pacman_packages(filter, limit),
+            PackageManagerType::Brew => self.list_brew_packages(filter, limit),
+            PackageManagerType::Winget => self.list_winget_packages(filter, limit),
+            PackageManagerType::Choco => self.list_choco_packages(filter, limit),
+            PackageManagerType::Unknown => Err("No supported package manager found".to_string()),
+        }
+    }
+
     /// Check for available updates
     pub async fn check_updates(&self, _params: &HashMap<String, String>) -> CommandResult {
         if !self.config.package_management.enabled {
@@ -184,6 +243,16 @@ impl PackageManager {
             }
         };
 
+        // Application managers don't cover OS patches
+        #[cfg(target_os = "windows")]
+        let packages = packages.map(|mut pkgs| {
+            match self.search_windows_updates() {
+                Ok((os_updates, _)) => pkgs.extend(os_updates),
+                Err(e) => warn!("Windows Update search failed: {}", e),
+            }
+            pkgs
+        });
+
         match packages {
             Ok(pkgs) => {
                 info!("Found {} packages with updates", pkgs.len());
@@ -367,6 +436,156 @@ impl PackageManager {
         }
     }
 
+    /// Windows Update status: pending OS updates, last install time,
+    /// pending reboot and WSUS server
+    pub async fn windows_update_status(&self, _params: &HashMap<String, String>) -> CommandResult {
+        if !self.config.package_management.enabled {
+            return Self::error_result("Package management is disabled".to_string());
+        }
+
+        #[cfg(target_os = "windows")]
+        {
+            match self.search_windows_updates() {
+                Ok((packages, status)) => CommandResult {
+                    success: true,
+                    output: format!(
+                        "{} OS updates pending ({} important){}",
+                        status.pending_count,
+                        status.important_count,
+                        if status.reboot_pending {
+                            ", reboot pending"
+                        } else {
+                            ""
+                        }
+                    ),
+                    packages,
+                    windows_update: Some(status),
+                    ..Default::default()
+                },
+                Err(e) => Self::error_result(e),
+            }
+        }
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            Self::error_result("Windows Update is only available on Windows".to_string())
+        }
+    }
+
+    /// Download and install OS updates (very dangerous, requires SYSTEM_ADMIN
+    /// and `allow_system_update`).
+    ///
+    /// Params: `kb` (comma-separated KB articles, default all important
+    /// updates). The machine is never rebooted; use SYSTEM_REBOOT.
+    pub async fn windows_update_install(&self, params: &HashMap<String, String>) -> CommandResult {
+        if !self.config.package_management.enabled {
+            return Self::error_result("Package management is disabled".to_string());
+        }
+        if !self.config.package_management.allow_system_update {
+            warn!("Windows Update install attempted but allow_system_update is disabled");
+            return Self::error_result("System updates are disabled in configuration".to_string());
+        }
+        let kbs =
+            match windows_update::parse_kb_list(params.get("kb").map(|s| s.as_str()).unwrap_or(""))
+            {
+                Ok(kbs) => kbs,
+                Err(e) => return Self::error_result(e),
+            };
+
+        #[cfg(target_os = "windows")]
+        {
+            use crate::utils::async_command::{
+                CommandResult as RunResult, CommandTimeout, run_command_async,
+            };
+
+            info!("[AUDIT] Windows Update install: {:?}", kbs);
+            let script = windows_update::install_script(&kbs);
+            let output = match run_command_async(
+                "powershell",
+                &["-NoProfile", "-NonInteractive", "-Command", &script],
+                CommandTimeout::Custom(WINDOWS_UPDATE_INSTALL_TIMEOUT),
+            )
+            .await
+            {
+                RunResult::Success(output) | RunResult::Failed(_, output) => output,
+                RunResult::Timeout => {
+                    return Self::error_result("Windows Update install timed out".to_string());
+                }
+                RunResult::NotFound => {
+                    return Self::error_result("PowerShell not found".to_string());
+                }
+                RunResult::Error(e) => return Self::error_result(e),
+            };
+
+            match windows_update::parse_install(&output) {
+                Ok(report) => {
+                    info!("[AUDIT] Windows Update install: {}", report.summary);
+                    let status = self.search_windows_updates().ok().map(|(_, mut status)| {
+                        status.reboot_pending |= report.reboot_required;
+                        status
+                    });
+                    CommandResult {
+                        success: report.success,
+                        output: report.summary.clone(),
+                        error: if report.success {
+                            String::new()
+                        } else {
+                            report.summary
+                        },
+                        windows_update: status,
+                        ..Default::default()
+                    }
+                }
+                Err(e) => {
+                    warn!("[AUDIT] Windows Update install failed: {}", e);
+                    Self::error_result(e)
+                }
+            }
+        }
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            let _ = kbs;
+            Self::error_result("Windows Update is only available on Windows".to_string())
+        }
+    }
+
+    /// Query pending OS updates through the Windows Update Agent
+    #[cfg(target_os = "windows")]
+    fn search_windows_updates(
+        &self,
+    ) -> Result<(Vec<PackageInfo>, crate::proto::WindowsUpdateStatus), String> {
+        use crate::utils::safe_command::exec_with_timeout;
+
+        let mut cmd = Command::new("powershell");
+        cmd.args([
+            "-NoProfile",
+            "-NonInteractive",
+            "-Command",
+            windows_update::STATUS_SCRIPT,
+        ]);
+        let output = exec_with_timeout(cmd, WINDOWS_UPDATE_SEARCH_TIMEOUT)
+            .ok_or_else(|| "Windows Update search timed out".to_string())?;
+        windows_update::parse_status(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    /// Run a package manager command in the configured sandbox
+    fn run(&self, program: &str, args: &[&str]) -> std::io::Result<Output> {
+        self.sandbox
+            .output(self.sandbox.command(program).args(args))
+    }
+
+    /// Helper to create an error CommandResult
+    fn error_result(error: String) -> CommandResult {
+        CommandResult {
+            command_id: String::new(),
+            success: false,
+            output: String::new(),
+            error,
+            ..Default::default()
+        }
+    }
+
     /// Validate package name to prevent command injection
     fn is_valid_package_name(name: &str) -> bool {
         // Package name should only contain alphanumeric, dash, underscore, dot
@@ -411,37 +630,32 @@ impl PackageManager {
         filter: Option<&str>,
         limit: usize,
     ) -> Result<Vec<PackageInfo>, String> {
-        let output = Command::new("dpkg-query")
-            .args(["-W", "-f", "${Package}\t${Version}\t${Status}\n"])
-            .output()
+        let output = self
+            .run("dpkg-query", &["-W", "-f", dpkg::QUERY_FORMAT])
             .map_err(|e| format!("Failed to run dpkg-query: {e}"))?;
 
         if !output.status.success() {
             return Err(String::from_utf8_lossy(&output.stderr).to_string());
         }
 
-        let packages: Vec<PackageInfo> = String::from_utf8_lossy(&output.stdout)
-            .lines()
-            .filter(|line| filter.map(|f| line.contains(f)).unwrap_or(true))
+        let packages: Vec<PackageInfo> = dpkg::packages(&String::from_utf8_lossy(&output.stdout))
+            .into_iter()
+            // Removed packages with config files left behind are listed too
+            .filter(|package| package.is_installed())
+            .filter(|package| filter.map(|f| package.name.contains(f)).unwrap_or(true))
             .take(limit)
-            .filter_map(|line| {
-                let parts: Vec<&str> = line.split('\t').collect();
-                if parts.len() >= 2 {
-                    Some(PackageInfo {
-                        name: parts[0].to_string(),
-                        version: parts[1].to_string(),
-                        description: String::new(),
-                        architecture: String::new(),
-                        installed_size: 0,
-                        install_date: String::new(),
-                        update_available: false,
-                        new_version: String::new(),
-                        repository: String::new(),
-                        package_manager: "apt".to_string(),
-                    })
-                } else {
-                    None
-                }
+            .map(|package| PackageInfo {
+                name: package.name,
+                version: package.version,
+                description: String::new(),
+                architecture: String::new(),
+                installed_size: 0,
+                install_date: String::new(),
+                update_available: false,
+                new_version: String::new(),
+                repository: String::new(),
+                package_manager: "apt".to_string(),
+                publisher: String::new(),
             })
             .collect();
 
@@ -450,14 +664,11 @@ impl PackageManager {
 
     fn check_apt_updates(&self) -> Result<Vec<PackageInfo>, String> {
         // Update package lists first
-        Command::new("apt-get")
-            .args(["update", "-qq"])
-            .output()
+        self.run("apt-get", &["update", "-qq"])
             .map_err(|e| format!("Failed to update package lists: {e}"))?;
 
-        let output = Command::new("apt-get")
-            .args(["--simulate", "upgrade"])
-            .output()
+        let output = self
+            .run("apt-get", &["--simulate", "upgrade"])
             .map_err(|e| format!("Failed to check updates: {e}"))?;
 
         let packages: Vec<PackageInfo> = String::from_utf8_lossy(&output.stdout)
@@ -481,6 +692,7 @@ impl PackageManager {
                             .to_string(),
                         repository: String::new(),
                         package_manager: "apt".to_string(),
+                        publisher: String::new(),
                     })
                 } else {
                     None
@@ -492,9 +704,8 @@ impl PackageManager {
     }
 
     fn update_apt_package(&self, name: &str) -> Result<String, String> {
-        let output = Command::new("apt-get")
-            .args(["install", "--only-upgrade", "-y", name])
-            .output()
+        let output = self
+            .run("apt-get", &["install", "--only-upgrade", "-y", name])
             .map_err(|e| format!("Failed to update package: {e}"))?;
 
         if output.status.success() {
@@ -505,9 +716,8 @@ impl PackageManager {
     }
 
     fn system_update_apt(&self) -> Result<String, String> {
-        let output = Command::new("apt-get")
-            .args(["upgrade", "-y"])
-            .output()
+        let output = self
+            .run("apt-get", &["upgrade", "-y"])
             .map_err(|e| format!("Failed to perform system update: {e}"))?;
 
         if output.status.success() {
@@ -529,9 +739,8 @@ impl PackageManager {
             "yum"
         };
 
-        let output = Command::new(cmd)
-            .args(["list", "installed", "-q"])
-            .output()
+        let output = self
+            .run(cmd, &["list", "installed", "-q"])
             .map_err(|e| format!("Failed to run {cmd}: {e}"))?;
 
         let packages: Vec<PackageInfo> = String::from_utf8_lossy(&output.stdout)
@@ -552,6 +761,7 @@ impl PackageManager {
                         new_version: String::new(),
                         repository: String::new(),
                         package_manager: cmd.to_string(),
+                        publisher: String::new(),
                     })
                 } else {
                     None
@@ -569,9 +779,8 @@ impl PackageManager {
             "yum"
         };
 
-        let output = Command::new(cmd)
-            .args(["check-update", "-q"])
-            .output()
+        let output = self
+            .run(cmd, &["check-update", "-q"])
             .map_err(|e| format!("Failed to check updates: {e}"))?;
 
         let packages: Vec<PackageInfo> = String::from_utf8_lossy(&output.stdout)
@@ -590,6 +799,7 @@ impl PackageManager {
                         new_version: parts[1].to_string(),
                         repository: String::new(),
                         package_manager: cmd.to_string(),
+                        publisher: String::new(),
                     })
                 } else {
                     None
@@ -607,9 +817,8 @@ impl PackageManager {
             "yum"
         };
 
-        let output = Command::new(cmd)
-            .args(["update", "-y", name])
-            .output()
+        let output = self
+            .run(cmd, &["update", "-y", name])
             .map_err(|e| format!("Failed to update package: {e}"))?;
 
         if output.status.success() {
@@ -626,9 +835,8 @@ impl PackageManager {
             "yum"
         };
 
-        let output = Command::new(cmd)
-            .args(["update", "-y"])
-            .output()
+        let output = self
+            .run(cmd, &["update", "-y"])
             .map_err(|e| format!("Failed to perform system update: {e}"))?;
 
         if output.status.success() {
@@ -644,9 +852,8 @@ impl PackageManager {
         filter: Option<&str>,
         limit: usize,
     ) -> Result<Vec<PackageInfo>, String> {
-        let output = Command::new("pacman")
-            .args(["-Q"])
-            .output()
+        let output = self
+            .run("pacman", &["-Q"])
             .map_err(|e| format!("Failed to run pacman: {e}"))?;
 
         let packages: Vec<PackageInfo> = String::from_utf8_lossy(&output.stdout)
@@ -667,6 +874,7 @@ impl PackageManager {
                         new_version: String::new(),
                         repository: String::new(),
                         package_manager: "pacman".to_string(),
+                        publisher: String::new(),
                     })
                 } else {
                     None
@@ -679,11 +887,10 @@ impl PackageManager {
 
     fn check_pacman_updates(&self) -> Result<Vec<PackageInfo>, String> {
         // Sync first
-        Command::new("pacman").args(["-Sy"]).output().ok();
+        self.run("pacman", &["-Sy"]).ok();
 
-        let output = Command::new("pacman")
-            .args(["-Qu"])
-            .output()
+        let output = self
+            .run("pacman", &["-Qu"])
             .map_err(|e| format!("Failed to check updates: {e}"))?;
 
         let packages: Vec<PackageInfo> = String::from_utf8_lossy(&output.stdout)
@@ -702,6 +909,7 @@ impl PackageManager {
                         new_version: parts[3].to_string(),
                         repository: String::new(),
                         package_manager: "pacman".to_string(),
+                        publisher: String::new(),
                     })
                 } else {
                     None
@@ -713,9 +921,8 @@ impl PackageManager {
     }
 
     fn update_pacman_package(&self, name: &str) -> Result<String, String> {
-        let output = Command::new("pacman")
-            .args(["-S", "--noconfirm", name])
-            .output()
+        let output = self
+            .run("pacman", &["-S", "--noconfirm", name])
             .map_err(|e| format!("Failed to update package: {e}"))?;
 
         if output.status.success() {
@@ -726,9 +933,8 @@ impl PackageManager {
     }
 
     fn system_update_pacman(&self) -> Result<String, String> {
-        let output = Command::new("pacman")
-            .args(["-Syu", "--noconfirm"])
-            .output()
+        let output = self
+            .run("pacman", &["-Syu", "--noconfirm"])
             .map_err(|e| format!("Failed to perform system update: {e}"))?;
 
         if output.status.success() {
@@ -744,9 +950,8 @@ impl PackageManager {
         filter: Option<&str>,
         limit: usize,
     ) -> Result<Vec<PackageInfo>, String> {
-        let output = Command::new("brew")
-            .args(["list", "--versions"])
-            .output()
+        let output = self
+            .run("brew", &["list", "--versions"])
             .map_err(|e| format!("Failed to run brew: {e}"))?;
 
         let packages: Vec<PackageInfo> = String::from_utf8_lossy(&output.stdout)
@@ -767,6 +972,7 @@ impl PackageManager {
                         new_version: String::new(),
                         repository: String::new(),
                         package_manager: "brew".to_string(),
+                        publisher: String::new(),
                     })
                 } else {
                     None
@@ -778,11 +984,10 @@ impl PackageManager {
     }
 
     fn check_brew_updates(&self) -> Result<Vec<PackageInfo>, String> {
-        Command::new("brew").args(["update"]).output().ok();
+        self.run("brew", &["update"]).ok();
 
-        let output = Command::new("brew")
-            .args(["outdated", "--verbose"])
-            .output()
+        let output = self
+            .run("brew", &["outdated", "--verbose"])
             .map_err(|e| format!("Failed to check updates: {e}"))?;
 
         let packages: Vec<PackageInfo> = String::from_utf8_lossy(&output.stdout)
@@ -801,6 +1006,7 @@ impl PackageManager {
                         new_version: parts.get(3).unwrap_or(&"").to_string(),
                         repository: String::new(),
                         package_manager: "brew".to_string(),
+                        publisher: String::new(),
                     })
                 } else {
                     None
@@ -812,9 +1018,8 @@ impl PackageManager {
     }
 
     fn update_brew_package(&self, name: &str) -> Result<String, String> {
-        let output = Command::new("brew")
-            .args(["upgrade", name])
-            .output()
+        let output = self
+            .run("brew", &["upgrade", name])
             .map_err(|e| format!("Failed to update package: {e}"))?;
 
         if output.status.success() {
@@ -825,9 +1030,8 @@ impl PackageManager {
     }
 
     fn system_update_brew(&self) -> Result<String, String> {
-        let output = Command::new("brew")
-            .args(["upgrade"])
-            .output()
+        let output = self
+            .run("brew", &["upgrade"])
             .map_err(|e| format!("Failed to perform system update: {e}"))?;
 
         if output.status.success() {
@@ -843,83 +1047,50 @@ impl PackageManager {
         filter: Option<&str>,
         limit: usize,
     ) -> Result<Vec<PackageInfo>, String> {
-        let output = Command::new("winget")
-            .args(["list", "--accept-source-agreements"])
-            .output()
+        let output = self
+            .run("winget", &["list", "--accept-source-agreements"])
             .map_err(|e| format!("Failed to run winget: {e}"))?;
 
-        let packages: Vec<PackageInfo> = String::from_utf8_lossy(&output.stdout)
-            .lines()
-            .skip(2) // Skip header lines
-            .filter(|line| filter.map(|f| line.contains(f)).unwrap_or(true))
-            .take(limit)
-            .filter_map(|line| {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 2 {
-                    Some(PackageInfo {
-                        name: parts[0].to_string(),
-                        version: parts.get(1).unwrap_or(&"").to_string(),
-                        description: String::new(),
-                        architecture: String::new(),
-                        installed_size: 0,
-                        install_date: String::new(),
-                        update_available: false,
-                        new_version: String::new(),
-                        repository: String::new(),
-                        package_manager: "winget".to_string(),
-                    })
-                } else {
-                    None
-                }
+        let packages: Vec<PackageInfo> = winget::packages(&String::from_utf8_lossy(&output.stdout))
+            .into_iter()
+            .filter(|package| {
+                filter
+                    .map(|f| package.name.contains(f) || package.id.contains(f))
+                    .unwrap_or(true)
             })
+            .take(limit)
+            .map(|package| winget_package_info(package, false))
             .collect();
 
         Ok(packages)
     }
 
     fn check_winget_updates(&self) -> Result<Vec<PackageInfo>, String> {
-        let output = Command::new("winget")
-            .args(["upgrade", "--accept-source-agreements"])
-            .output()
+        let output = self
+            .run("winget", &["upgrade", "--accept-source-agreements"])
             .map_err(|e| format!("Failed to check updates: {e}"))?;
 
-        let packages: Vec<PackageInfo> = String::from_utf8_lossy(&output.stdout)
-            .lines()
-            .skip(2)
-            .filter_map(|line| {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 3 {
-                    Some(PackageInfo {
-                        name: parts[0].to_string(),
-                        version: parts[1].to_string(),
-                        description: String::new(),
-                        architecture: String::new(),
-                        installed_size: 0,
-                        install_date: String::new(),
-                        update_available: true,
-                        new_version: parts[2].to_string(),
-                        repository: String::new(),
-                        package_manager: "winget".to_string(),
-                    })
-                } else {
-                    None
-                }
-            })
+        let packages: Vec<PackageInfo> = winget::packages(&String::from_utf8_lossy(&output.stdout))
+            .into_iter()
+            .filter(|package| package.available.is_some())
+            .map(|package| winget_package_info(package, true))
             .collect();
 
         Ok(packages)
     }
 
     fn update_winget_package(&self, name: &str) -> Result<String, String> {
-        let output = Command::new("winget")
-            .args([
-                "upgrade",
-                "--id",
-                name,
-                "--accept-source-agreements",
-                "--silent",
-            ])
-            .output()
+        let output = self
+            .run(
+                "winget",
+                &[
+                    "upgrade",
+                    "--id",
+                    name,
+                    "--accept-source-agreements",
+                    "--silent",
+                ],
+            )
             .map_err(|e| format!("Failed to update package: {e}"))?;
 
         if output.status.success() {
@@ -930,9 +1101,11 @@ impl PackageManager {
     }
 
     fn system_update_winget(&self) -> Result<String, String> {
-        let output = Command::new("winget")
-            .args(["upgrade", "--all", "--accept-source-agreements", "--silent"])
-            .output()
+        let output = self
+            .run(
+                "winget",
+                &["upgrade", "--all", "--accept-source-agreements", "--silent"],
+            )
             .map_err(|e| format!("Failed to perform system update: {e}"))?;
 
         if output.status.success() {
@@ -948,9 +1121,8 @@ impl PackageManager {
         filter: Option<&str>,
         limit: usize,
     ) -> Result<Vec<PackageInfo>, String> {
-        let output = Command::new("choco")
-            .args(["list", "--local-only"])
-            .output()
+        let output = self
+            .run("choco", &["list", "--local-only"])
             .map_err(|e| format!("Failed to run choco: {e}"))?;
 
         let packages: Vec<PackageInfo> = String::from_utf8_lossy(&output.stdout)
@@ -971,6 +1143,7 @@ impl PackageManager {
                         new_version: String::new(),
                         repository: String::new(),
                         package_manager: "choco".to_string(),
+                        publisher: String::new(),
                     })
                 } else {
                     None
@@ -982,9 +1155,8 @@ impl PackageManager {
     }
 
     fn check_choco_updates(&self) -> Result<Vec<PackageInfo>, String> {
-        let output = Command::new("choco")
-            .args(["outdated"])
-            .output()
+        let output = self
+            .run("choco", &["outdated"])
             .map_err(|e| format!("Failed to check updates: {e}"))?;
 
         let packages: Vec<PackageInfo> = String::from_utf8_lossy(&output.stdout)
@@ -1004,6 +1176,7 @@ impl PackageManager {
                             new_version: parts[2].trim().to_string(),
                             repository: String::new(),
                             package_manager: "choco".to_string(),
+                            publisher: String::new(),
                         });
                     }
                 }
@@ -1015,9 +1188,8 @@ impl PackageManager {
     }
 
     fn update_choco_package(&self, name: &str) -> Result<String, String> {
-        let output = Command::new("choco")
-            .args(["upgrade", "-y", name])
-            .output()
+        let output = self
+            .run("choco", &["upgrade", "-y", name])
             .map_err(|e| format!("Failed to update package: {e}"))?;
 
         if output.status.success() {
@@ -1028,9 +1200,8 @@ impl PackageManager {
     }
 
     fn system_update_choco(&self) -> Result<String, String> {
-        let output = Command::new("choco")
-            .args(["upgrade", "-y", "all"])
-            .output()
+        let output = self
+            .run("choco", &["upgrade", "-y", "all"])
             .map_err(|e| format!("Failed to perform system update: {e}"))?;
 
         if output.status.success() {
@@ -1040,3 +1211,21 @@ impl PackageManager {
         }
     }
 }
+
+/// Packages are named by their winget ID, as updates are addressed with
+/// `winget upgrade --id`; the display name goes in the description.
+fn winget_package_info(package: winget::Package, update_available: bool) -> PackageInfo {
+    PackageInfo {
+        name: package.id,
+        version: package.version,
+        description: package.name,
+        architecture: String::new(),
+        installed_size: 0,
+        install_date: String::new(),
+        update_available,
+        new_version: package.available.unwrap_or_default(),
+        repository: package.source.unwrap_or_default(),
+        package_manager: "winget".to_string(),
+        publisher: String::new(),
+    }
+}
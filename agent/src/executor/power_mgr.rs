@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::config::Config;
+#[cfg(unix)]
+use crate::privsep::{HelperClient, Operation};
+use crate::proto::CommandResult;
+
+/// Longest broadcast message passed to the OS
+const MAX_MESSAGE_LEN: usize = 200;
+
+/// Power action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerAction {
+    Reboot,
+    Shutdown,
+}
+
+/// Power management executor
+///
+/// Reboots and shutdowns are scheduled with the OS `shutdown` command, which
+/// warns logged-in users and survives an agent restart, so a pending action
+/// can be cancelled from any connection until it fires.
+pub struct PowerManager {
+    config: Arc<Config>,
+    /// Privileged helper that runs `shutdown`
+    #[cfg(unix)]
+    helper: Option<HelperClient>,
+}
+
+impl PowerManager {
+    /// Create a new power manager
+    pub fn new(config: Arc<Config>) -> Self {
+        Self {
+            #[cfg(unix)]
+            helper: HelperClient::from_config(&config.privsep),
+            config,
+        }
+    }
+
+    /// Power manager that always acts itself (the privileged helper)
+    #[cfg(unix)]
+    pub fn local(config: Arc<Config>) -> Self {
+        Self {
+            config,
+            helper: None,
+        }
+    }
+
+    /// Run `shutdown`, through the privileged helper if there is one
+    async fn run_schedule(
+        &self,
+        action: PowerAction,
+        delay: u64,
+        message: String,
+    ) -> CommandResult {
+        #[cfg(unix)]
+        if let Some(helper) = &self.helper {
+            let operation = match action {
+                PowerAction::Reboot => Operation::Reboot {
+                    delay_seconds: delay,
+                    message,
+                },
+                PowerAction::Shutdown => Operation::Shutdown {
+                    delay_seconds: delay,
+                    message,
+                },
+            };
+            return helper.call(&operation).await;
+        }
+
+        let (program, args) = schedule_command(action, delay, &message);
+        run(program, &args)
+    }
+
+    /// Cancel through the privileged helper if there is one
+    async fn run_cancel(&self) -> CommandResult {
+        #[cfg(unix)]
+        if let Some(helper) = &self.helper {
+            return helper.call(&Operation::PowerCancel).await;
+        }
+
+        let (program, args) = cancel_command();
+        run(program, &args)
+    }
+
+    /// Helper to create an error CommandResult
+    fn error_result(error: String) -> CommandResult {
+        CommandResult {
+            command_id: String::new(),
+            success: false,
+            output: String::new(),
+            error,
+            ..Default::default()
+        }
+    }
+
+    /// Schedule a reboot or shutdown.
+    ///
+    /// Params: `delay_seconds` (default `power.default_delay_seconds`, raised
+    /// to `power.min_delay_seconds`) and `message` (broadcast to users).
+    pub async fn schedule(
+        &self,
+        action: PowerAction,
+        params: &HashMap<String, String>,
+    ) -> CommandResult {
+        let power = &self.config.power;
+        let delay = match params.get("delay_seconds") {
+            Some(value) => match value.parse::<u64>() {
+                Ok(delay) => delay,
+                Err(_) => return Self::error_result(format!("Invalid delay_seconds: {value}")),
+            },
+            None => power.default_delay_seconds,
+        }
+        .max(power.min_delay_seconds);
+
+        let message = params
+            .get("message")
+            .map(|m| sanitize_message(m))
+            .filter(|m| !m.is_empty())
+            .unwrap_or_else(|| {
+                let verb = match action {
+                    PowerAction::Reboot => "reboot",
+                    PowerAction::Shutdown => "shut down",
+                };
+                format!("NanoLink: system will {verb} in {delay} seconds")
+            });
+
+        info!(
+            "[AUDIT] Power {:?} scheduled in {}s: {}",
+            action, delay, message
+        );
+
+        let result = self.run_schedule(action, delay, message).await;
+        if result.success {
+            CommandResult {
+                output: format!("{action:?} scheduled in {delay} seconds"),
+                ..result
+            }
+        } else {
+            warn!("[AUDIT] Power {:?} failed: {}", action, result.error);
+            result
+        }
+    }
+
+    /// Cancel a pending reboot or shutdown
+    pub async fn cancel(&self) -> CommandResult {
+        info!("[AUDIT] Power action cancel requested");
+        let result = self.run_cancel().await;
+        if result.success {
+            CommandResult {
+                output: "Pending power action cancelled".to_string(),
+                ..result
+            }
+        } else {
+            warn!("[AUDIT] Power cancel failed: {}", result.error);
+            result
+        }
+    }
+}
+
+fn run(program: &str, args: &[String]) -> CommandResult {
+    match Command::new(program).args(args).output() {
+        Ok(output) => CommandResult {
+            command_id: String::new(),
+            success: output.status.success(),
+            output: String::from_utf8_lossy(&output.stdout).to_string(),
+            error: String::from_utf8_lossy(&output.stderr).to_string(),
+            ..Default::default()
+        },
+        Err(e) => PowerManager::error_result(format!("Failed to execute {program}: {e}")),
+    }
+}
+
+/// Strip control characters and limit the length of a broadcast message
+fn sanitize_message(message: &str) -> String {
+    message
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_MESSAGE_LEN)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// `shutdown` invocation for a delayed power action.
+///
+/// Unix `shutdown` only accepts whole minutes, so the delay is rounded up.
+#[cfg(unix)]
+fn schedule_command(action: PowerAction, delay: u64, message: &str) -> (&'static str, Vec<String>) {
+    let flag = match action {
+        PowerAction::Reboot => "-r",
+        #[cfg(target_os = "macos")]
+        PowerAction::Shutdown => "-h",
+        #[cfg(not(target_os = "macos"))]
+        PowerAction::Shutdown => "-P",
+    };
+    let when = match delay.div_ceil(60) {
+        0 => "now".to_string(),
+        minutes => format!("+{minutes}"),
+    };
+    (
+        "shutdown",
+        vec![flag.to_string(), when, message.to_string()],
+    )
+}
+
+#[cfg(windows)]
+fn schedule_command(action: PowerAction, delay: u64, message: &str) -> (&'static str, Vec<String>) {
+    let flag = match action {
+        PowerAction::Reboot => "/r",
+        PowerAction::Shutdown => "/s",
+    };
+    // /t above 0 implies /f; 315360000 (10 years) is the documented maximum
+    (
+        "shutdown",
+        vec![
+            flag.to_string(),
+            "/t".to_string(),
+            delay.min(315_360_000).to_string(),
+            "/c".to_string(),
+            message.to_string(),
+            "/d".to_string(),
+            "p:0:0".to_string(),
+        ],
+    )
+}
+
+#[cfg(target_os = "macos")]
+fn cancel_command() -> (&'static str, Vec<String>) {
+    // The delayed shutdown waits as a background `shutdown` process
+    ("killall", vec!["shutdown".to_string()])
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn cancel_command() -> (&'static str, Vec<String>) {
+    ("shutdown", vec!["-c".to_string()])
+}
+
+#[cfg(windows)]
+fn cancel_command() -> (&'static str, Vec<String>) {
+    ("shutdown", vec!["/a".to_string()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_message() {
+        assert_eq!(
+            sanitize_message("  maintenance\n\x07 window "),
+            "maintenance window"
+        );
+        assert_eq!(sanitize_message(&"x".repeat(500)).len(), MAX_MESSAGE_LEN);
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn test_schedule_command() {
+        let (program, args) = schedule_command(PowerAction::Reboot, 90, "bye");
+        assert_eq!(program, "shutdown");
+        assert_eq!(args, ["-r", "+2", "bye"]);
+
+        let (_, args) = schedule_command(PowerAction::Shutdown, 0, "now");
+        assert_eq!(args, ["-P", "now", "now"]);
+    }
+}
@@ -0,0 +1,142 @@
+//! Print queue clearing
+//!
+//! Cancels every job of one printer so a job stuck at the head of the queue
+//! stops blocking the rest. On CUPS the queue is also re-enabled and set to
+//! accept jobs again, since a failing backend stops it. On Windows
+//! `restart_spooler=true` additionally restarts the Spooler service and
+//! removes leftover spool files, which clears jobs that refuse to cancel;
+//! that drops the spooled jobs of every printer.
+
+use std::collections::HashMap;
+use std::process::Command;
+
+use tracing::{info, warn};
+
+use crate::proto::CommandResult;
+use crate::security::validation::validate_printer_name;
+
+/// Print queue executor
+pub struct PrintQueueExecutor;
+
+impl PrintQueueExecutor {
+    /// Create a new print queue executor
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Helper to create an error CommandResult
+    fn error_result(error: String) -> CommandResult {
+        CommandResult {
+            command_id: String::new(),
+            success: false,
+            output: String::new(),
+            error,
+            ..Default::default()
+        }
+    }
+
+    /// Cancel all jobs of `printer` and resume it.
+    ///
+    /// Params: `restart_spooler` (Windows, `true` to restart the Spooler
+    /// service and remove spool files as well).
+    pub async fn clear_queue(
+        &self,
+        printer: &str,
+        params: &HashMap<String, String>,
+    ) -> CommandResult {
+        if let Err(e) = validate_printer_name(printer) {
+            return Self::error_result(e);
+        }
+        let restart_spooler = params.get("restart_spooler").is_some_and(|v| v == "true");
+        info!(
+            "[AUDIT] Clearing print queue {} (restart spooler: {})",
+            printer, restart_spooler
+        );
+
+        let result = Self::clear(printer, restart_spooler);
+        if !result.success {
+            warn!(
+                "[AUDIT] Clearing print queue {} failed: {}",
+                printer,
+                result.error.trim()
+            );
+        }
+        result
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn clear(printer: &str, restart_spooler: bool) -> CommandResult {
+        if restart_spooler {
+            return Self::error_result("restart_spooler is only supported on Windows".to_string());
+        }
+        let queued = run("lpstat", &["-o", printer])
+            .output
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .count();
+
+        let cancel = run("cancel", &["-a", printer]);
+        if !cancel.success {
+            return cancel;
+        }
+
+        // A failing backend leaves the queue stopped
+        let mut output = format!("Cancelled {queued} job(s) on {printer}\n");
+        for tool in ["cupsenable", "cupsaccept"] {
+            let result = run(tool, &[printer]);
+            if !result.success {
+                output.push_str(&format!("{tool} failed: {}\n", result.error.trim()));
+            }
+        }
+        CommandResult {
+            success: true,
+            output,
+            ..Default::default()
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    fn clear(printer: &str, restart_spooler: bool) -> CommandResult {
+        let script = format!(
+            r#"
+$ErrorActionPreference = 'Stop'
+$name = '{name}'
+$null = Get-Printer -Name $name
+$jobs = @(Get-PrintJob -PrinterName $name)
+$jobs | Remove-PrintJob
+if (${restart}) {{
+  Stop-Service -Name Spooler -Force
+  Get-ChildItem -Path "$env:SystemRoot\System32\spool\PRINTERS" -File | Remove-Item -Force
+  Start-Service -Name Spooler
+  "Restarted the Spooler service"
+}}
+"Cancelled $($jobs.Count) job(s) on $name"
+"#,
+            name = printer.replace('\'', "''"),
+            restart = restart_spooler,
+        );
+        run(
+            "powershell",
+            &["-NoProfile", "-NonInteractive", "-Command", &script],
+        )
+    }
+}
+
+impl Default for PrintQueueExecutor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> CommandResult {
+    match Command::new(program).args(args).output() {
+        Ok(output) => CommandResult {
+            command_id: String::new(),
+            success: output.status.success(),
+            output: String::from_utf8_lossy(&output.stdout).to_string(),
+            error: String::from_utf8_lossy(&output.stderr).to_string(),
+            ..Default::default()
+        },
+        Err(e) => PrintQueueExecutor::error_result(format!("Failed to execute {program}: {e}")),
+    }
+}
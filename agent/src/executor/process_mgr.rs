@@ -1,9 +1,18 @@
-use std::collections::HashMap;
-use tracing::info;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use sysinfo::{Pid, Process, ProcessStatus, ProcessesToUpdate, Signal, System, ThreadKind};
+use tracing::{info, warn};
 
 use crate::proto::{CommandResult, ProcessInfo};
 use crate::security::validation::{validate_pid_killable, validate_process_name};
 
+/// Default time between the requested signal and SIGKILL for PROCESS_KILL_TREE
+const DEFAULT_GRACE_SECONDS: u64 = 10;
+
+/// Upper bound for `grace_seconds`
+const MAX_GRACE_SECONDS: u64 = 300;
+
 /// Process management executor
 pub struct ProcessExecutor {
     _marker: (),
@@ -17,24 +26,10 @@ impl ProcessExecutor {
 
     /// List all processes
     pub async fn list_processes(&self) -> CommandResult {
-        use sysinfo::{ProcessesToUpdate, System};
-
         let mut system = System::new();
         system.refresh_processes(ProcessesToUpdate::All, true);
 
-        let processes: Vec<ProcessInfo> = system
-            .processes()
-            .iter()
-            .map(|(pid, process)| ProcessInfo {
-                pid: pid.as_u32(),
-                name: process.name().to_string_lossy().to_string(),
-                user: process.user_id().map(|u| u.to_string()).unwrap_or_default(),
-                cpu_percent: process.cpu_usage() as f64,
-                memory_bytes: process.memory(),
-                status: format!("{:?}", process.status()),
-                start_time: process.start_time(),
-            })
-            .collect();
+        let processes: Vec<ProcessInfo> = system.processes().values().map(basic_info).collect();
 
         CommandResult {
             command_id: String::new(),
@@ -77,6 +72,143 @@ impl ProcessExecutor {
         self.kill_by_name(target, signal).await
     }
 
+    /// Get a process and all of its descendants, parents before children,
+    /// with thread count, open file descriptors and cgroup
+    pub async fn process_tree(&self, target: &str) -> CommandResult {
+        let Ok(pid) = target.parse::<u32>() else {
+            return Self::error_result(format!("Invalid PID: {target}"));
+        };
+
+        let mut system = System::new();
+        system.refresh_processes(ProcessesToUpdate::All, true);
+        let processes = tree_info(&system, pid);
+        if processes.is_empty() {
+            return Self::error_result(format!("Process {pid} not found"));
+        }
+
+        CommandResult {
+            success: true,
+            output: format!("Process {} has {} descendants", pid, processes.len() - 1),
+            processes,
+            ..Default::default()
+        }
+    }
+
+    /// Kill a process and all of its descendants.
+    ///
+    /// Params: `signal` (default `TERM`) and `grace_seconds` (default 10):
+    /// processes still running after the grace period are sent SIGKILL.
+    /// Trees containing PID 1 or the agent itself are refused.
+    pub async fn kill_tree(&self, target: &str, params: &HashMap<String, String>) -> CommandResult {
+        let Ok(pid) = target.parse::<u32>() else {
+            return Self::error_result(format!("Invalid PID: {target}"));
+        };
+        if let Err(e) = validate_pid_killable(pid) {
+            return Self::error_result(e);
+        }
+        let signal_name = params.get("signal").map(|s| s.as_str()).unwrap_or("TERM");
+        let Some(signal) = parse_signal(signal_name) else {
+            return Self::error_result(format!("Unsupported signal: {signal_name}"));
+        };
+        let grace = match params.get("grace_seconds") {
+            Some(value) => match value.parse::<u64>() {
+                Ok(seconds) if seconds <= MAX_GRACE_SECONDS => seconds,
+                _ => return Self::error_result(format!("Invalid grace_seconds: {value}")),
+            },
+            None => DEFAULT_GRACE_SECONDS,
+        };
+
+        let mut system = System::new();
+        system.refresh_processes(ProcessesToUpdate::All, true);
+        let mut processes = tree_info(&system, pid);
+        if processes.is_empty() {
+            return Self::error_result(format!("Process {pid} not found"));
+        }
+        let agent_pid = std::process::id();
+        if let Some(protected) = processes.iter().find(|p| p.pid <= 1 || p.pid == agent_pid) {
+            warn!(
+                "[SECURITY] Blocked kill of process tree {} containing PID {}",
+                pid, protected.pid
+            );
+            return Self::error_result(format!(
+                "Refusing to kill process tree {pid}: it contains protected PID {}",
+                protected.pid
+            ));
+        }
+
+        info!(
+            "[AUDIT] ProcessKillTree: PID {} ({} processes, signal: {:?}, grace: {}s)",
+            pid,
+            processes.len(),
+            signal,
+            grace
+        );
+        // Parents first, so supervisors cannot respawn the children
+        for info in &processes {
+            if let Some(process) = system.process(Pid::from_u32(info.pid)) {
+                send_signal(process, signal);
+            }
+        }
+
+        let mut remaining: Vec<(u32, u64)> =
+            processes.iter().map(|p| (p.pid, p.start_time)).collect();
+        let deadline = Instant::now() + Duration::from_secs(grace);
+        loop {
+            remaining = survivors(&mut system, &remaining);
+            if remaining.is_empty() || signal == Signal::Kill || Instant::now() >= deadline {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
+
+        let forced = remaining.len();
+        if forced > 0 {
+            warn!(
+                "[AUDIT] ProcessKillTree: {} processes of tree {} still running, sending KILL",
+                forced, pid
+            );
+            for (survivor, _) in &remaining {
+                if let Some(process) = system.process(Pid::from_u32(*survivor)) {
+                    process.kill();
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            remaining = survivors(&mut system, &remaining);
+        }
+
+        let alive: HashSet<u32> = remaining.iter().map(|(pid, _)| *pid).collect();
+        for info in &mut processes {
+            info.status = if alive.contains(&info.pid) {
+                "Running".to_string()
+            } else {
+                "Killed".to_string()
+            };
+        }
+
+        let mut output = format!(
+            "Sent {:?} to {} processes in tree {}",
+            signal,
+            processes.len(),
+            pid
+        );
+        if forced > 0 {
+            output.push_str(&format!(
+                "; {forced} still running after {grace}s were sent KILL"
+            ));
+        }
+        CommandResult {
+            success: alive.is_empty(),
+            output,
+            error: if alive.is_empty() {
+                String::new()
+            } else {
+                format!("{} processes could not be killed", alive.len())
+            },
+            processes,
+            ..Default::default()
+        }
+    }
+
     /// Helper to create an error CommandResult
     fn error_result(error: String) -> CommandResult {
         CommandResult {
@@ -210,3 +342,176 @@ impl Default for ProcessExecutor {
         Self::new()
     }
 }
+
+fn basic_info(process: &Process) -> ProcessInfo {
+    ProcessInfo {
+        pid: process.pid().as_u32(),
+        name: process.name().to_string_lossy().to_string(),
+        user: process.user_id().map(|u| u.to_string()).unwrap_or_default(),
+        cpu_percent: process.cpu_usage() as f64,
+        memory_bytes: process.memory(),
+        status: format!("{:?}", process.status()),
+        start_time: process.start_time(),
+        ..Default::default()
+    }
+}
+
+/// Details of `root` and its descendants, parents before children
+fn tree_info(system: &System, root: u32) -> Vec<ProcessInfo> {
+    // Threads are listed as processes on Linux; only real processes count
+    let parents: Vec<(u32, u32)> = system
+        .processes()
+        .values()
+        .filter(|p| p.thread_kind() != Some(ThreadKind::Userland))
+        .map(|p| {
+            (
+                p.pid().as_u32(),
+                p.parent().map(|pp| pp.as_u32()).unwrap_or(0),
+            )
+        })
+        .collect();
+
+    tree_order(root, &parents)
+        .into_iter()
+        .filter_map(|(pid, depth)| {
+            let process = system.process(Pid::from_u32(pid))?;
+            let mut info = basic_info(process);
+            info.parent_pid = process.parent().map(|p| p.as_u32()).unwrap_or(0);
+            info.depth = depth;
+            info.thread_count = process.tasks().map(|t| t.len() as u32).unwrap_or(0);
+            info.open_fds = -1;
+            #[cfg(target_os = "linux")]
+            {
+                info.open_fds = std::fs::read_dir(format!("/proc/{pid}/fd"))
+                    .map(|fds| fds.count() as i32)
+                    .unwrap_or(-1);
+                info.cgroup = std::fs::read_to_string(format!("/proc/{pid}/cgroup"))
+                    .map(|content| parse_cgroup(&content))
+                    .unwrap_or_default();
+            }
+            Some(info)
+        })
+        .collect()
+}
+
+/// Depth-first walk from `root` over `(pid, parent_pid)` pairs, returning
+/// `(pid, depth)` with parents before children. Empty if `root` is unknown.
+fn tree_order(root: u32, parents: &[(u32, u32)]) -> Vec<(u32, u32)> {
+    if !parents.iter().any(|(pid, _)| *pid == root) {
+        return Vec::new();
+    }
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (pid, parent) in parents {
+        if pid != parent {
+            children.entry(*parent).or_default().push(*pid);
+        }
+    }
+    for list in children.values_mut() {
+        list.sort_unstable();
+    }
+
+    let mut order = Vec::new();
+    let mut seen = HashSet::new();
+    let mut stack = vec![(root, 0)];
+    while let Some((pid, depth)) = stack.pop() {
+        if !seen.insert(pid) {
+            continue;
+        }
+        order.push((pid, depth));
+        if let Some(list) = children.get(&pid) {
+            stack.extend(list.iter().rev().map(|child| (*child, depth + 1)));
+        }
+    }
+    order
+}
+
+/// cgroup v2 path, or the systemd hierarchy path on cgroup v1 hosts
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn parse_cgroup(content: &str) -> String {
+    let entries: Vec<(&str, &str)> = content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, ':');
+            let _id = fields.next()?;
+            Some((fields.next()?, fields.next()?))
+        })
+        .collect();
+    entries
+        .iter()
+        .find(|(controllers, _)| controllers.is_empty())
+        .or_else(|| entries.iter().find(|(c, _)| *c == "name=systemd"))
+        .or_else(|| entries.first())
+        .map(|(_, path)| path.to_string())
+        .unwrap_or_default()
+}
+
+fn parse_signal(signal: &str) -> Option<Signal> {
+    match signal.to_uppercase().as_str() {
+        "TERM" | "SIGTERM" | "15" => Some(Signal::Term),
+        "KILL" | "SIGKILL" | "9" => Some(Signal::Kill),
+        "HUP" | "SIGHUP" | "1" => Some(Signal::Hangup),
+        "INT" | "SIGINT" | "2" => Some(Signal::Interrupt),
+        "QUIT" | "SIGQUIT" | "3" => Some(Signal::Quit),
+        _ => None,
+    }
+}
+
+/// Send `signal`, falling back to a forced kill where the platform does not
+/// support it (Windows only supports termination)
+fn send_signal(process: &Process, signal: Signal) {
+    if process.kill_with(signal).is_none() {
+        process.kill();
+    }
+}
+
+/// Processes from `tracked` that are still alive. The start time guards
+/// against PIDs reused by new processes; zombies count as exited.
+fn survivors(system: &mut System, tracked: &[(u32, u64)]) -> Vec<(u32, u64)> {
+    let pids: Vec<Pid> = tracked.iter().map(|(pid, _)| Pid::from_u32(*pid)).collect();
+    system.refresh_processes(ProcessesToUpdate::Some(&pids), true);
+    tracked
+        .iter()
+        .filter(|(pid, start_time)| {
+            system.process(Pid::from_u32(*pid)).is_some_and(|p| {
+                p.start_time() == *start_time && p.status() != ProcessStatus::Zombie
+            })
+        })
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tree_order() {
+        let parents = [(1, 0), (10, 1), (11, 10), (12, 10), (13, 11), (20, 1)];
+        assert_eq!(
+            tree_order(10, &parents),
+            vec![(10, 0), (11, 1), (13, 2), (12, 1)]
+        );
+        assert!(tree_order(99, &parents).is_empty());
+    }
+
+    #[test]
+    fn test_parse_cgroup() {
+        assert_eq!(
+            parse_cgroup("0::/system.slice/nginx.service\n"),
+            "/system.slice/nginx.service"
+        );
+        assert_eq!(
+            parse_cgroup(
+                "12:cpu,cpuacct:/docker/abc\n1:name=systemd:/system.slice/docker.service\n"
+            ),
+            "/system.slice/docker.service"
+        );
+    }
+
+    #[test]
+    fn test_parse_signal() {
+        assert_eq!(parse_signal("sigterm"), Some(Signal::Term));
+        assert_eq!(parse_signal("9"), Some(Signal::Kill));
+        assert_eq!(parse_signal("STOP"), None);
+    }
+}
@@ -0,0 +1,591 @@
+//! Session recording
+//!
+//! With `recording.enabled`, the input and output of every SHELL_EXECUTE and
+//! SCRIPT_EXECUTE is stored in `recording.dir` as an asciicast v2 file that
+//! `asciinema play` can replay. Each recording, and each deletion by the
+//! retention limits, is appended to `chain.jsonl`, where every entry carries
+//! the hash of the previous one and the digest of the recording. Editing or
+//! removing a recording or a chain entry breaks verification.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Utc};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::proto::{CommandResult, SessionRecording};
+use crate::utils::{at_rest, tar};
+
+/// Optional `since`/`until` bounds
+type TimeRange = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+const CHAIN_FILE: &str = "chain.jsonl";
+const EXPORT_DIR: &str = "exports";
+
+/// Associated data for at-rest encryption
+const CAST_PURPOSE: &str = "recording";
+const CHAIN_PURPOSE: &str = "recording-chain";
+
+/// Serializes writers so the chain stays linear across connections
+static CHAIN_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum Action {
+    Record,
+    Prune,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordingMeta {
+    kind: String,
+    command: String,
+    requested_by: String,
+    started_at: String,
+    duration_ms: u64,
+    success: bool,
+    size_bytes: u64,
+    sha256: String,
+}
+
+impl RecordingMeta {
+    fn to_proto(&self, id: &str) -> SessionRecording {
+        SessionRecording {
+            id: id.to_string(),
+            kind: self.kind.clone(),
+            command: self.command.clone(),
+            requested_by: self.requested_by.clone(),
+            started_at: self.started_at.clone(),
+            duration_ms: self.duration_ms,
+            success: self.success,
+            size_bytes: self.size_bytes,
+            sha256: self.sha256.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChainEntry {
+    seq: u64,
+    time: String,
+    action: Action,
+    id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    meta: Option<RecordingMeta>,
+    prev: String,
+    /// SHA-256 of this entry serialized with an empty `hash`
+    hash: String,
+}
+
+impl ChainEntry {
+    fn digest(&self) -> String {
+        let mut unsigned = self.clone();
+        unsigned.hash = String::new();
+        hex(&Sha256::digest(
+            serde_json::to_vec(&unsigned).unwrap_or_default(),
+        ))
+    }
+}
+
+/// Result of walking the chain
+struct Verification {
+    entries: u64,
+    /// Recordings not yet pruned as (ID, metadata), oldest first
+    live: Vec<(String, RecordingMeta)>,
+    last: Option<(u64, String)>,
+    error: Option<String>,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Walk the chain and check every link and every live recording
+fn verify(dir: &Path) -> Verification {
+    let mut result = Verification {
+        entries: 0,
+        live: Vec::new(),
+        last: None,
+        error: None,
+    };
+    let content = fs::read_to_string(dir.join(CHAIN_FILE)).unwrap_or_default();
+    let mut prev = String::new();
+    for (index, line) in content.lines().enumerate() {
+        let parsed = at_rest::open_line(CHAIN_PURPOSE, line)
+            .and_then(|line| serde_json::from_str(&line).map_err(|e| e.to_string()));
+        let entry: ChainEntry = match parsed {
+            Ok(entry) => entry,
+            Err(e) => {
+                result.error = Some(format!("Chain entry {} is unreadable: {e}", index + 1));
+                return result;
+            }
+        };
+        if entry.seq != index as u64 + 1 || entry.prev != prev || entry.digest() != entry.hash {
+            result.error = Some(format!("Chain broken at entry {}", index + 1));
+            return result;
+        }
+        match (entry.action, entry.meta) {
+            (Action::Record, Some(meta)) => {
+                result.live.push((entry.id.clone(), meta));
+            }
+            (Action::Prune, _) => {
+                result.live.retain(|(id, _)| *id != entry.id);
+            }
+            (Action::Record, None) => {
+                result.error = Some(format!("Chain entry {} has no metadata", entry.seq));
+                return result;
+            }
+        }
+        prev = entry.hash.clone();
+        result.entries = entry.seq;
+        result.last = Some((entry.seq, entry.hash));
+    }
+
+    for (id, meta) in &result.live {
+        let digest = read_cast(dir, id).map(|data| hex(&Sha256::digest(data)));
+        match digest {
+            Ok(digest) if digest == meta.sha256 => {}
+            Ok(_) => {
+                result.error = Some(format!("Recording {id} was modified"));
+                break;
+            }
+            Err(_) => {
+                result.error = Some(format!("Recording {id} is missing"));
+                break;
+            }
+        }
+    }
+    result
+}
+
+fn append(
+    dir: &Path,
+    last: &mut Option<(u64, String)>,
+    action: Action,
+    id: &str,
+    meta: Option<RecordingMeta>,
+) -> std::io::Result<()> {
+    let (seq, prev) = last.clone().unwrap_or_default();
+    let mut entry = ChainEntry {
+        seq: seq + 1,
+        time: Utc::now().to_rfc3339(),
+        action,
+        id: id.to_string(),
+        meta,
+        prev,
+        hash: String::new(),
+    };
+    entry.hash = entry.digest();
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(CHAIN_FILE))?;
+    let line = at_rest::seal_line(CHAIN_PURPOSE, &serde_json::to_string(&entry)?)
+        .map_err(std::io::Error::other)?;
+    writeln!(file, "{line}")?;
+    file.sync_data()?;
+    *last = Some((entry.seq, entry.hash));
+    Ok(())
+}
+
+/// Render an execution as asciicast v2
+fn asciicast(meta: &RecordingMeta, started: &DateTime<Utc>, result: &CommandResult) -> String {
+    let header = serde_json::json!({
+        "version": 2,
+        "width": 120,
+        "height": 40,
+        "timestamp": started.timestamp(),
+        "command": meta.command,
+        "title": format!("{} by {}", meta.kind, meta.requested_by),
+    });
+    let end = meta.duration_ms as f64 / 1000.0;
+    let terminal = |text: &str| text.replace("\r\n", "\n").replace('\n', "\r\n");
+    let mut lines = vec![
+        header.to_string(),
+        serde_json::json!([0.0, "i", format!("{}\r\n", meta.command)]).to_string(),
+    ];
+    for text in [&result.output, &result.error] {
+        if !text.is_empty() {
+            lines.push(serde_json::json!([end, "o", terminal(text)]).to_string());
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Parse `since`/`until` params (RFC 3339 or YYYY-MM-DD)
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+        .map_err(|_| format!("Invalid time: {value}"))
+}
+
+/// Shell/script session recorder
+pub struct SessionRecorder {
+    config: Arc<Config>,
+}
+
+impl SessionRecorder {
+    /// Create a new session recorder
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
+    }
+
+    /// Helper to create an error CommandResult
+    fn error_result(error: String) -> CommandResult {
+        CommandResult {
+            command_id: String::new(),
+            success: false,
+            output: String::new(),
+            error,
+            ..Default::default()
+        }
+    }
+
+    fn dir(&self) -> PathBuf {
+        PathBuf::from(&self.config.recording.dir)
+    }
+
+    /// Record one execution. Failures are logged, never returned: recording
+    /// must not change the outcome of the command.
+    pub fn record(
+        &self,
+        kind: &str,
+        command: &str,
+        requested_by: &str,
+        started: DateTime<Utc>,
+        result: &CommandResult,
+    ) {
+        if !self.config.recording.enabled {
+            return;
+        }
+        if let Err(e) = self.try_record(kind, command, requested_by, started, result) {
+            warn!("Failed to record {} session: {}", kind, e);
+        }
+    }
+
+    fn try_record(
+        &self,
+        kind: &str,
+        command: &str,
+        requested_by: &str,
+        started: DateTime<Utc>,
+        result: &CommandResult,
+    ) -> std::io::Result<()> {
+        let dir = self.dir();
+        let _guard = CHAIN_LOCK.lock();
+        create_private_dir(&dir)?;
+
+        let id = format!(
+            "{}-{}",
+            started.format("%Y%m%dT%H%M%S"),
+            &uuid::Uuid::new_v4().simple().to_string()[..8]
+        );
+        let mut meta = RecordingMeta {
+            kind: kind.to_string(),
+            command: command.to_string(),
+            requested_by: requested_by.to_string(),
+            started_at: started.to_rfc3339(),
+            duration_ms: (Utc::now() - started).num_milliseconds().max(0) as u64,
+            success: result.success,
+            size_bytes: 0,
+            sha256: String::new(),
+        };
+        let cast = asciicast(&meta, &started, result);
+        meta.size_bytes = cast.len() as u64;
+        meta.sha256 = hex(&Sha256::digest(cast.as_bytes()));
+        let sealed = at_rest::seal(CAST_PURPOSE, cast.as_bytes()).map_err(std::io::Error::other)?;
+        write_private(&dir.join(format!("{id}.cast")), &sealed)?;
+
+        let verification = verify(&dir);
+        if let Some(error) = &verification.error {
+            warn!(
+                "[AUDIT] Session recording chain failed verification: {}",
+                error
+            );
+        }
+        let mut last = verification.last;
+        append(&dir, &mut last, Action::Record, &id, Some(meta))?;
+        self.prune(&dir, &mut last, verification.live)
+    }
+
+    /// Apply retention limits to the recordings that were live before this
+    /// one; the newest recording is never pruned
+    fn prune(
+        &self,
+        dir: &Path,
+        last: &mut Option<(u64, String)>,
+        live: Vec<(String, RecordingMeta)>,
+    ) -> std::io::Result<()> {
+        let retention = &self.config.recording;
+        let cutoff = Utc::now() - chrono::Duration::days(i64::from(retention.retention_days));
+        let max_bytes = retention.max_total_mb * 1024 * 1024;
+        let mut total: u64 = live.iter().map(|(_, m)| m.size_bytes).sum();
+
+        for (id, meta) in live {
+            let expired = DateTime::parse_from_rfc3339(&meta.started_at)
+                .is_ok_and(|started| started < cutoff);
+            if !expired && total <= max_bytes {
+                break;
+            }
+            match fs::remove_file(dir.join(format!("{id}.cast"))) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+            append(dir, last, Action::Prune, &id, None)?;
+            total = total.saturating_sub(meta.size_bytes);
+        }
+        Ok(())
+    }
+
+    /// List recordings and verify the chain
+    pub async fn list(&self, params: &HashMap<String, String>) -> CommandResult {
+        let (since, until) = match Self::range(params) {
+            Ok(range) => range,
+            Err(e) => return Self::error_result(e),
+        };
+        let verification = {
+            let _guard = CHAIN_LOCK.lock();
+            verify(&self.dir())
+        };
+        let recordings = Self::select(&verification, since, until);
+        CommandResult {
+            success: verification.error.is_none(),
+            output: format!(
+                "{} recordings, chain of {} entries {}",
+                recordings.len(),
+                verification.entries,
+                if verification.error.is_none() {
+                    "verified"
+                } else {
+                    "BROKEN"
+                }
+            ),
+            error: verification.error.unwrap_or_default(),
+            recordings,
+            ..Default::default()
+        }
+    }
+
+    /// Export recordings and the chain as a tar.gz in `<dir>/exports`
+    pub async fn export(&self, params: &HashMap<String, String>) -> CommandResult {
+        let (since, until) = match Self::range(params) {
+            Ok(range) => range,
+            Err(e) => return Self::error_result(e),
+        };
+        let dir = self.dir();
+        let _guard = CHAIN_LOCK.lock();
+        let verification = verify(&dir);
+        let recordings = Self::select(&verification, since, until);
+
+        let build = || -> Result<PathBuf, String> {
+            let mtime = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or(Duration::ZERO)
+                .as_secs();
+            let mut archive = Vec::new();
+            // Exports are plaintext so auditors can verify and replay them
+            let chain = fs::read_to_string(dir.join(CHAIN_FILE))
+                .unwrap_or_default()
+                .lines()
+                .map(|line| at_rest::open_line(CHAIN_PURPOSE, line).map(|line| line + "\n"))
+                .collect::<Result<String, _>>()?;
+            tar::append(&mut archive, CHAIN_FILE, chain.as_bytes(), mtime)?;
+            for recording in &recordings {
+                let name = format!("{}.cast", recording.id);
+                let data = read_cast(&dir, &recording.id).map_err(|e| format!("{name}: {e}"))?;
+                tar::append(&mut archive, &name, &data, mtime)?;
+            }
+            tar::finish(&mut archive);
+
+            let exports = dir.join(EXPORT_DIR);
+            create_private_dir(&exports).map_err(|e| e.to_string())?;
+            let path = exports.join(format!(
+                "recordings-{}.tar.gz",
+                Utc::now().format("%Y%m%dT%H%M%S")
+            ));
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&archive).map_err(|e| e.to_string())?;
+            let gz = encoder.finish().map_err(|e| e.to_string())?;
+            write_private(&path, &gz).map_err(|e| e.to_string())?;
+            Ok(path)
+        };
+        let path = match build() {
+            Ok(path) => path,
+            Err(e) => return Self::error_result(format!("Export failed: {e}")),
+        };
+        info!(
+            "[AUDIT] Exported {} session recordings to {}",
+            recordings.len(),
+            path.display()
+        );
+
+        let mut output = format!(
+            "Exported {} recordings to {}\nChain of {} entries ",
+            recordings.len(),
+            path.display(),
+            verification.entries
+        );
+        match &verification.error {
+            None => output.push_str("verified"),
+            Some(error) => output.push_str(&format!("BROKEN: {error}")),
+        }
+        CommandResult {
+            success: true,
+            output,
+            recordings,
+            ..Default::default()
+        }
+    }
+
+    fn range(params: &HashMap<String, String>) -> Result<TimeRange, String> {
+        let parse = |key: &str| {
+            params
+                .get(key)
+                .filter(|v| !v.is_empty())
+                .map(|v| parse_time(v))
+                .transpose()
+        };
+        Ok((parse("since")?, parse("until")?))
+    }
+
+    fn select(
+        verification: &Verification,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> Vec<SessionRecording> {
+        verification
+            .live
+            .iter()
+            .filter(|(_, meta)| {
+                let Ok(started) = DateTime::parse_from_rfc3339(&meta.started_at) else {
+                    return false;
+                };
+                since.is_none_or(|since| started >= since)
+                    && until.is_none_or(|until| started < until)
+            })
+            .map(|(id, meta)| meta.to_proto(id))
+            .collect()
+    }
+}
+
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+/// Read a recording, decrypting it if needed
+fn read_cast(dir: &Path, id: &str) -> Result<Vec<u8>, String> {
+    let data = fs::read(dir.join(format!("{id}.cast"))).map_err(|e| e.to_string())?;
+    at_rest::open(CAST_PURPOSE, &data)
+}
+
+fn write_private(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorder(dir: &Path, max_total_mb: u64) -> SessionRecorder {
+        let mut config = Config::sample();
+        config.recording.enabled = true;
+        config.recording.dir = dir.display().to_string();
+        config.recording.max_total_mb = max_total_mb;
+        SessionRecorder::new(Arc::new(config))
+    }
+
+    fn output(text: &str) -> CommandResult {
+        CommandResult {
+            success: true,
+            output: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_chain_detects_tampering() {
+        let dir = std::env::temp_dir().join(format!("nanolink-rec-{}", std::process::id()));
+        let recorder = recorder(&dir, 1024);
+        recorder.record(
+            "shell",
+            "uptime",
+            "10.0.0.1:39100",
+            Utc::now(),
+            &output("up 3 days\n"),
+        );
+        recorder.record(
+            "script",
+            "backup.sh",
+            "10.0.0.1:39100",
+            Utc::now(),
+            &output("done"),
+        );
+
+        let verification = verify(&dir);
+        assert!(verification.error.is_none());
+        assert_eq!(verification.entries, 2);
+        let (id, _) = &verification.live[0];
+        let cast = fs::read_to_string(dir.join(format!("{id}.cast"))).unwrap();
+        assert!(cast.contains("\"version\":2"));
+        assert!(cast.contains("up 3 days\\r\\n"));
+
+        // Edit a recording
+        fs::write(dir.join(format!("{id}.cast")), "forged").unwrap();
+        assert!(verify(&dir).error.unwrap().contains("modified"));
+
+        // Drop a chain entry
+        fs::write(dir.join(format!("{id}.cast")), cast).unwrap();
+        let chain = fs::read_to_string(dir.join(CHAIN_FILE)).unwrap();
+        let second = chain.lines().nth(1).unwrap();
+        fs::write(dir.join(CHAIN_FILE), format!("{second}\n")).unwrap();
+        assert!(verify(&dir).error.unwrap().contains("broken"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_size_retention() {
+        let dir = std::env::temp_dir().join(format!("nanolink-rec-prune-{}", std::process::id()));
+        // Zero budget: every older recording is pruned, the newest kept
+        let recorder = recorder(&dir, 0);
+        for command in ["one", "two", "three"] {
+            recorder.record("shell", command, "local", Utc::now(), &output(command));
+        }
+        let verification = verify(&dir);
+        assert!(verification.error.is_none());
+        assert_eq!(verification.live.len(), 1);
+        assert_eq!(verification.live[0].1.command, "three");
+        // 3 records + 2 prunes
+        assert_eq!(verification.entries, 5);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
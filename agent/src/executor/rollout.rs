@@ -0,0 +1,441 @@
+//! Blue/green agent updates
+//!
+//! The downloaded binary is first started next to the running agent as a
+//! candidate (`--candidate-port`), which loads the current config and serves
+//! only `/api/health` on localhost. Only a healthy candidate is promoted: the
+//! binary is swapped, a pending marker is written and a detached watchdog
+//! restarts the service. The new agent removes the marker once it has stayed
+//! up for [`CONFIRM_AFTER`]; if the marker is still there when the watchdog
+//! wakes up, the backup is restored and the service restarted again.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::process::Command;
+use tokio::time::{self, Instant};
+use tracing::{info, warn};
+
+use crate::config::UpdateConfig;
+
+/// How long a promoted agent must stay up before the rollout is confirmed
+pub const CONFIRM_AFTER: Duration = Duration::from_secs(30);
+
+/// Candidates exit on their own after this long in case nobody stops them
+pub const CANDIDATE_LIFETIME: Duration = Duration::from_secs(300);
+
+/// Config file of the running agent, handed to candidates
+static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Remember the config file so candidates are checked against it
+pub fn set_config_path(path: PathBuf) {
+    let _ = CONFIG_PATH.set(path);
+}
+
+/// Written next to the binary between promotion and confirmation
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct PendingRollout {
+    pub from_version: String,
+    pub to_version: String,
+    pub promoted_at: String,
+}
+
+pub(super) fn marker_path(exe: &Path) -> PathBuf {
+    exe.with_extension("pending")
+}
+
+pub(super) fn backup_path(exe: &Path) -> PathBuf {
+    exe.with_extension("bak")
+}
+
+/// Rollout waiting for confirmation, if any
+pub(super) fn pending(exe: &Path) -> Option<PendingRollout> {
+    let content = std::fs::read_to_string(marker_path(exe)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Copy the update next to the current binary so it can be started
+pub(super) fn stage(update_path: &Path, exe: &Path) -> Result<PathBuf, String> {
+    let staged = if cfg!(windows) {
+        exe.with_extension("new.exe")
+    } else {
+        exe.with_extension("new")
+    };
+    std::fs::copy(update_path, &staged).map_err(|e| format!("Failed to stage update: {e}"))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("Failed to set permissions: {e}"))?;
+    }
+    Ok(staged)
+}
+
+/// Start the staged binary as a candidate and wait until it reports healthy.
+/// Returns the candidate's version.
+pub(super) async fn verify_candidate(
+    staged: &Path,
+    config: &UpdateConfig,
+) -> Result<String, String> {
+    let mut command = Command::new(staged);
+    command
+        .arg("--candidate-port")
+        .arg(config.candidate_port.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(path) = CONFIG_PATH.get() {
+        command.arg("--config").arg(path);
+    }
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("Failed to start candidate: {e}"))?;
+    info!(
+        "Started update candidate (pid {:?}) on port {}",
+        child.id(),
+        config.candidate_port
+    );
+
+    let deadline = Instant::now() + Duration::from_secs(config.health_timeout_seconds);
+    let result = loop {
+        if let Ok(Some(status)) = child.try_wait() {
+            let mut stderr = String::new();
+            if let Some(mut pipe) = child.stderr.take() {
+                let _ = pipe.read_to_string(&mut stderr).await;
+            }
+            let last_line = stderr.lines().last().unwrap_or_default().to_string();
+            break Err(format!("Candidate exited with {status}: {last_line}"));
+        }
+        match probe_health(config.candidate_port).await {
+            Ok(version) => break Ok(version),
+            Err(e) if Instant::now() >= deadline => {
+                break Err(format!(
+                    "Candidate not healthy after {}s: {e}",
+                    config.health_timeout_seconds
+                ));
+            }
+            Err(_) => time::sleep(Duration::from_millis(500)).await,
+        }
+    };
+
+    let _ = child.kill().await;
+    result
+}
+
+/// Serve only `/api/health` on localhost. A new binary runs this as the
+/// candidate so the running agent can check it before switching; it speaks
+/// just enough HTTP for [`probe_health`] and curl.
+pub async fn serve_health(port: u16) -> std::io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        tokio::spawn(async move {
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 8192 {
+                match time::timeout(Duration::from_secs(2), stream.read(&mut buf)).await {
+                    Ok(Ok(n)) if n > 0 => request.extend_from_slice(&buf[..n]),
+                    _ => return,
+                }
+            }
+            let _ = stream.write_all(health_response(&request).as_bytes()).await;
+            let _ = stream.shutdown().await;
+        });
+    }
+}
+
+/// The response to one request to a candidate
+fn health_response(request: &[u8]) -> String {
+    let request = String::from_utf8_lossy(request);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let (status, body) = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/api/health")) => (
+            "200 OK",
+            serde_json::json!({
+                "status": "healthy",
+                "version": env!("CARGO_PKG_VERSION"),
+            })
+            .to_string(),
+        ),
+        _ => ("404 Not Found", String::new()),
+    };
+    format!(
+        "HTTP/1.0 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+async fn probe_health(port: u16) -> Result<String, String> {
+    let mut stream = time::timeout(
+        Duration::from_secs(2),
+        TcpStream::connect(("127.0.0.1", port)),
+    )
+    .await
+    .map_err(|_| "connect timed out".to_string())?
+    .map_err(|e| e.to_string())?;
+    let request = format!("GET /api/health HTTP/1.0\r\nHost: 127.0.0.1:{port}\r\n\r\n");
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut response = Vec::new();
+    time::timeout(
+        Duration::from_secs(2),
+        stream.take(64 * 1024).read_to_end(&mut response),
+    )
+    .await
+    .map_err(|_| "read timed out".to_string())?
+    .map_err(|e| e.to_string())?;
+    parse_health(&String::from_utf8_lossy(&response))
+}
+
+/// Parse the candidate's `/api/health` response into its version
+fn parse_health(response: &str) -> Result<String, String> {
+    #[derive(Deserialize)]
+    struct Health {
+        status: String,
+        version: String,
+    }
+
+    let status_line = response.lines().next().unwrap_or_default();
+    if status_line.split_whitespace().nth(1) != Some("200") {
+        return Err(format!("Unexpected response: {status_line}"));
+    }
+    let body = response
+        .split_once("\r\n\r\n")
+        .map(|(_, body)| body)
+        .unwrap_or_default();
+    let health: Health =
+        serde_json::from_str(body.trim()).map_err(|e| format!("Invalid health response: {e}"))?;
+    if health.status == "healthy" {
+        Ok(health.version)
+    } else {
+        Err(format!("Candidate reports {}", health.status))
+    }
+}
+
+/// Watchdog: restart onto the new binary, then roll back if the new agent
+/// has not confirmed in time. Arguments: exe, backup, marker, timeout.
+#[cfg(unix)]
+const WATCHDOG_SCRIPT: &str = r#"
+sleep 2
+__RESTART__
+sleep "$4"
+if [ -f "$3" ]; then
+  cp -f "$2" "$1.rollback" && mv -f "$1.rollback" "$1"
+  rm -f "$3"
+  __RESTART__
+fi
+"#;
+
+#[cfg(target_os = "macos")]
+const RESTART_COMMAND: &str = "launchctl kickstart -k system/com.nanolink.agent";
+
+#[cfg(all(unix, not(target_os = "macos")))]
+const RESTART_COMMAND: &str = "systemctl restart nanolink-agent";
+
+/// Swap in the staged binary and hand over to the watchdog
+#[cfg(unix)]
+pub(super) async fn promote(
+    staged: &Path,
+    exe: &Path,
+    rollout: &PendingRollout,
+    config: &UpdateConfig,
+) -> Result<(), String> {
+    use std::fs;
+
+    let backup = backup_path(exe);
+    let marker = marker_path(exe);
+    fs::copy(exe, &backup).map_err(|e| format!("Failed to create backup: {e}"))?;
+    // rename() replaces the running binary atomically
+    fs::rename(staged, exe).map_err(|e| format!("Failed to replace binary: {e}"))?;
+    let restore = |reason: String| {
+        if let Err(e) = fs::copy(&backup, exe) {
+            warn!("Failed to restore backup after failed promotion: {e}");
+        }
+        let _ = fs::remove_file(&marker);
+        reason
+    };
+
+    let json = serde_json::to_vec(rollout).map_err(|e| e.to_string())?;
+    fs::write(&marker, json).map_err(|e| restore(format!("Failed to write marker: {e}")))?;
+
+    let script = WATCHDOG_SCRIPT.replace("__RESTART__", RESTART_COMMAND);
+    let timeout = config
+        .rollback_timeout_seconds
+        .max(CONFIRM_AFTER.as_secs() * 2);
+    let args = [
+        exe.as_os_str(),
+        backup.as_os_str(),
+        marker.as_os_str(),
+        std::ffi::OsStr::new(&timeout.to_string()),
+    ]
+    .map(std::ffi::OsStr::to_os_string);
+
+    // Under systemd the watchdog must live outside the agent's cgroup or the
+    // restart would kill it too
+    let mut command = if which("systemd-run") {
+        let mut command = Command::new("systemd-run");
+        command.args(["--collect", "--quiet", "/bin/sh", "-c", &script, "sh"]);
+        command
+    } else {
+        let mut command = Command::new("/bin/sh");
+        command.args(["-c", &script, "sh"]).process_group(0);
+        command
+    };
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    command
+        .spawn()
+        .map_err(|e| restore(format!("Failed to start rollout watchdog: {e}")))?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn which(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+/// Swap in the staged binary and hand over to the watchdog. The running
+/// binary can't be replaced on Windows, so the watchdog stops the service
+/// first.
+#[cfg(windows)]
+pub(super) async fn promote(
+    staged: &Path,
+    exe: &Path,
+    rollout: &PendingRollout,
+    config: &UpdateConfig,
+) -> Result<(), String> {
+    use std::fs;
+
+    let backup = backup_path(exe);
+    let marker = marker_path(exe);
+    let paths = [staged, exe, backup.as_path(), marker.as_path()]
+        .map(|p| super::update::escape_path(p))
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+    let timeout = config
+        .rollback_timeout_seconds
+        .max(CONFIRM_AFTER.as_secs() * 2);
+
+    let script = format!(
+        r#"@echo off
+setlocal EnableDelayedExpansion
+set "SRC={}"
+set "DST={}"
+set "BAK={}"
+set "MARKER={}"
+timeout /t 2 /nobreak >nul
+net stop NanoLinkAgent
+copy /Y "!DST!" "!BAK!"
+copy /Y "!SRC!" "!DST!"
+if errorlevel 1 (
+    del /F "!MARKER!"
+    net start NanoLinkAgent
+    exit /b 1
+)
+del /F "!SRC!"
+net start NanoLinkAgent
+timeout /t {timeout} /nobreak >nul
+if exist "!MARKER!" (
+    net stop NanoLinkAgent
+    copy /Y "!BAK!" "!DST!"
+    del /F "!MARKER!"
+    net start NanoLinkAgent
+)
+del /F "%~f0"
+"#,
+        paths[0], paths[1], paths[2], paths[3]
+    );
+    let script_path = std::env::temp_dir().join("nanolink-rollout.bat");
+    fs::write(&script_path, script).map_err(|e| format!("Failed to create rollout script: {e}"))?;
+    let json = serde_json::to_vec(rollout).map_err(|e| e.to_string())?;
+    fs::write(&marker, json).map_err(|e| format!("Failed to write marker: {e}"))?;
+
+    let script_path = super::update::escape_path(&script_path)?;
+    if let Err(e) = std::process::Command::new("cmd")
+        .args(["/C", "start", "/MIN", "", &script_path])
+        .spawn()
+    {
+        let _ = fs::remove_file(&marker);
+        return Err(format!("Failed to start rollout watchdog: {e}"));
+    }
+    Ok(())
+}
+
+/// Called by a freshly started agent: confirm a pending rollout once the
+/// agent has stayed up long enough
+pub async fn confirm_rollout() {
+    let Ok(exe) = std::env::current_exe() else {
+        return;
+    };
+    if pending(&exe).is_none() {
+        return;
+    }
+    time::sleep(CONFIRM_AFTER).await;
+    if let Some(rollout) = pending(&exe) {
+        if let Err(e) = std::fs::remove_file(marker_path(&exe)) {
+            warn!("Failed to confirm update: {e}");
+            return;
+        }
+        let _ = std::fs::remove_file(backup_path(&exe));
+        info!(
+            "[AUDIT] Update {} -> {} confirmed",
+            rollout.from_version, rollout.to_version
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_health_response() {
+        let request = b"GET /api/health HTTP/1.0\r\nHost: 127.0.0.1:1\r\n\r\n";
+        assert_eq!(
+            parse_health(&health_response(request)).unwrap(),
+            env!("CARGO_PKG_VERSION")
+        );
+        assert!(parse_health(&health_response(b"GET /api/status HTTP/1.0\r\n\r\n")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_serve_health() {
+        let port = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().port()
+        };
+        tokio::spawn(serve_health(port));
+        let mut version = Err(String::new());
+        for _ in 0..50 {
+            version = probe_health(port).await;
+            if version.is_ok() {
+                break;
+            }
+            time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(version.unwrap(), env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn test_parse_health() {
+        let ok = "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\r\n{\"status\":\"healthy\",\"version\":\"0.5.0\"}";
+        assert_eq!(parse_health(ok).unwrap(), "0.5.0");
+
+        let degraded = "HTTP/1.1 200 OK\r\n\r\n{\"status\":\"degraded\",\"version\":\"0.5.0\"}";
+        assert!(parse_health(degraded).is_err());
+        assert!(parse_health("HTTP/1.1 404 Not Found\r\n\r\n").is_err());
+    }
+}
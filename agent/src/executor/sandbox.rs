@@ -0,0 +1,287 @@
+//! Sandboxing of executor subprocesses
+//!
+//! Shell commands, scripts and package manager runs can each be given a
+//! `sandbox` section. On Unix the child runs as a dedicated low-privilege
+//! user; on Linux it can also be placed in a transient systemd scope with
+//! CPU, memory and task limits, so a runaway script is throttled or killed
+//! by the kernel instead of starving the host. On Windows the limits are
+//! applied through a Job Object.
+
+use std::ffi::OsStr;
+use std::io;
+use std::process::{Child, Command, Output};
+
+#[cfg(unix)]
+use tracing::warn;
+
+use crate::config::SandboxConfig;
+
+/// Wraps executor subprocesses according to a [`SandboxConfig`]
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    config: SandboxConfig,
+}
+
+impl Sandbox {
+    pub fn new(config: SandboxConfig) -> Self {
+        #[cfg(unix)]
+        if config.has_limits() && !(cfg!(target_os = "linux") && config.systemd_scope) {
+            warn!("Sandbox limits need systemd_scope on Linux; ignoring them");
+        }
+        Self { config }
+    }
+
+    /// Command running `program` in the sandbox; append arguments as usual
+    pub fn command(&self, program: impl AsRef<OsStr>) -> Command {
+        #[cfg(target_os = "linux")]
+        if self.config.systemd_scope {
+            let mut cmd = Command::new("systemd-run");
+            cmd.args(systemd_run_args(&self.config)).arg(program);
+            return cmd;
+        }
+
+        #[allow(unused_mut)]
+        let mut cmd = Command::new(program);
+        #[cfg(unix)]
+        if let Some(user) = &self.config.user {
+            run_as(&mut cmd, user);
+        }
+        cmd
+    }
+
+    /// Start `cmd`, built with [`Sandbox::command`]
+    pub fn spawn(&self, cmd: &mut Command) -> io::Result<Child> {
+        #[cfg(unix)]
+        if let Some(user) = &self.config.user {
+            // Fail with a clear message instead of running as the agent
+            lookup_user(user)?;
+        }
+
+        let child = cmd.spawn()?;
+
+        #[cfg(windows)]
+        if self.config.has_limits() {
+            if let Err(e) = job::confine(&child, &self.config) {
+                let mut child = child;
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(e);
+            }
+        }
+
+        Ok(child)
+    }
+
+    /// Run `cmd` to completion and collect its output
+    pub fn output(&self, cmd: &mut Command) -> io::Result<Output> {
+        use std::process::Stdio;
+
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        self.spawn(cmd)?.wait_with_output()
+    }
+}
+
+/// `systemd-run` arguments up to and including `--`
+#[cfg(target_os = "linux")]
+fn systemd_run_args(config: &SandboxConfig) -> Vec<String> {
+    let mut args = vec![
+        "--scope".to_string(),
+        "--quiet".to_string(),
+        "--collect".to_string(),
+    ];
+    if let Some(quota) = config.cpu_quota_percent {
+        args.push(format!("--property=CPUQuota={quota}%"));
+    }
+    if let Some(memory) = config.memory_max_mb {
+        args.push(format!("--property=MemoryMax={memory}M"));
+        // Don't let the scope swap its way around the limit
+        args.push("--property=MemorySwapMax=0".to_string());
+    }
+    if let Some(tasks) = config.max_tasks {
+        args.push(format!("--property=TasksMax={tasks}"));
+    }
+    if let Some(user) = &config.user {
+        args.push(format!("--uid={user}"));
+    }
+    args.push("--".to_string());
+    args
+}
+
+#[cfg(unix)]
+fn lookup_user(name: &str) -> io::Result<nix::unistd::User> {
+    nix::unistd::User::from_name(name)
+        .map_err(io::Error::from)?
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Sandbox user '{name}' does not exist"),
+            )
+        })
+}
+
+/// Drop to `user` in the child; the agent has to run as root for this
+#[cfg(unix)]
+fn run_as(cmd: &mut Command, user: &str) {
+    use std::os::unix::process::CommandExt;
+
+    // A missing user is reported by `Sandbox::spawn`
+    if let Ok(user) = lookup_user(user) {
+        cmd.uid(user.uid.as_raw())
+            .gid(user.gid.as_raw())
+            .env("HOME", &user.dir)
+            .env("USER", &user.name)
+            .env("LOGNAME", &user.name);
+    }
+}
+
+#[cfg(windows)]
+mod job {
+    use std::io;
+    use std::os::windows::io::AsRawHandle;
+    use std::process::Child;
+    use std::ptr;
+
+    use winapi::shared::minwindef::{DWORD, LPVOID};
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::jobapi2::{
+        AssignProcessToJobObject, CreateJobObjectW, SetInformationJobObject,
+    };
+    use winapi::um::winnt::{
+        HANDLE, JOB_OBJECT_CPU_RATE_CONTROL_ENABLE, JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP,
+        JOB_OBJECT_LIMIT_ACTIVE_PROCESS, JOB_OBJECT_LIMIT_JOB_MEMORY,
+        JOBOBJECT_CPU_RATE_CONTROL_INFORMATION, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+        JobObjectCpuRateControlInformation, JobObjectExtendedLimitInformation,
+    };
+
+    use crate::config::SandboxConfig;
+
+    /// Put `child` in a new Job Object carrying the configured limits
+    ///
+    /// The job handle is closed right away; the job and its limits live on
+    /// for as long as a process in it does.
+    pub fn confine(child: &Child, config: &SandboxConfig) -> io::Result<()> {
+        // SAFETY: plain Win32 calls on a handle owned by this function and
+        // the child's process handle, which outlives the call
+        unsafe {
+            let job = CreateJobObjectW(ptr::null_mut(), ptr::null());
+            if job.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            let result = configure(job, config).and_then(|()| {
+                if AssignProcessToJobObject(job, child.as_raw_handle() as _) == 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(())
+                }
+            });
+            CloseHandle(job);
+            result
+        }
+    }
+
+    fn configure(job: HANDLE, config: &SandboxConfig) -> io::Result<()> {
+        let mut limits: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { std::mem::zeroed() };
+        if let Some(memory) = config.memory_max_mb {
+            limits.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_JOB_MEMORY;
+            limits.JobMemoryLimit = (memory as usize).saturating_mul(1024 * 1024);
+        }
+        if let Some(tasks) = config.max_tasks {
+            limits.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_ACTIVE_PROCESS;
+            limits.BasicLimitInformation.ActiveProcessLimit = tasks;
+        }
+        if limits.BasicLimitInformation.LimitFlags != 0 {
+            let ok = unsafe {
+                SetInformationJobObject(
+                    job,
+                    JobObjectExtendedLimitInformation,
+                    &mut limits as *mut _ as LPVOID,
+                    std::mem::size_of_val(&limits) as DWORD,
+                )
+            };
+            if ok == 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        if let Some(quota) = config.cpu_quota_percent {
+            let mut rate: JOBOBJECT_CPU_RATE_CONTROL_INFORMATION = unsafe { std::mem::zeroed() };
+            rate.ControlFlags =
+                JOB_OBJECT_CPU_RATE_CONTROL_ENABLE | JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP;
+            // Percent of the whole machine in 1/100ths of a percent; the
+            // config counts one CPU as 100%
+            let cpus = std::thread::available_parallelism().map_or(1, |n| n.get()) as u32;
+            let rate_value = (quota.saturating_mul(100) / cpus).clamp(1, 10_000);
+            unsafe { *rate.u.CpuRate_mut() = rate_value };
+            let ok = unsafe {
+                SetInformationJobObject(
+                    job,
+                    JobObjectCpuRateControlInformation,
+                    &mut rate as *mut _ as LPVOID,
+                    std::mem::size_of_val(&rate) as DWORD,
+                )
+            };
+            if ok == 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_sandbox_runs_program_directly() {
+        let sandbox = Sandbox::new(SandboxConfig::default());
+        let cmd = sandbox.command("sh");
+        assert_eq!(cmd.get_program(), "sh");
+        assert_eq!(cmd.get_args().count(), 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_systemd_scope_args() {
+        let sandbox = Sandbox::new(SandboxConfig {
+            user: Some("nanolink-exec".to_string()),
+            systemd_scope: true,
+            cpu_quota_percent: Some(50),
+            memory_max_mb: Some(256),
+            max_tasks: Some(64),
+        });
+        let mut cmd = sandbox.command("sh");
+        cmd.args(["-c", "true"]);
+        assert_eq!(cmd.get_program(), "systemd-run");
+        let args: Vec<_> = cmd.get_args().map(|a| a.to_str().unwrap()).collect();
+        assert_eq!(
+            args,
+            [
+                "--scope",
+                "--quiet",
+                "--collect",
+                "--property=CPUQuota=50%",
+                "--property=MemoryMax=256M",
+                "--property=MemorySwapMax=0",
+                "--property=TasksMax=64",
+                "--uid=nanolink-exec",
+                "--",
+                "sh",
+                "-c",
+                "true",
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_missing_user_is_refused() {
+        let sandbox = Sandbox::new(SandboxConfig {
+            user: Some("nanolink-no-such-user".to_string()),
+            ..Default::default()
+        });
+        let mut cmd = sandbox.command("true");
+        let err = sandbox.spawn(&mut cmd).unwrap_err();
+        assert!(err.to_string().contains("nanolink-no-such-user"));
+    }
+}
@@ -2,7 +2,6 @@ use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::{info, warn};
@@ -10,9 +9,12 @@ use tracing::{info, warn};
 use crate::config::Config;
 use crate::proto::{CommandResult, ScriptInfo};
 
+use super::Sandbox;
+
 /// Script executor with security controls
 pub struct ScriptExecutor {
     config: Arc<Config>,
+    sandbox: Sandbox,
 }
 
 /// Dangerous characters that could be used for injection
@@ -23,7 +25,10 @@ const DANGEROUS_CHARS: &[char] = &[
 impl ScriptExecutor {
     /// Create a new script executor
     pub fn new(config: Arc<Config>) -> Self {
-        Self { config }
+        Self {
+            sandbox: Sandbox::new(config.scripts.sandbox.clone()),
+            config,
+        }
     }
 
     /// List available scripts in the scripts directory
@@ -422,10 +427,10 @@ impl ScriptExecutor {
         use std::io::Read;
         use std::process::Stdio;
 
-        let mut cmd = Command::new(script_path);
+        let mut cmd = self.sandbox.command(script_path);
         cmd.args(args).stdout(Stdio::piped()).stderr(Stdio::piped());
 
-        let mut child = match cmd.spawn() {
+        let mut child = match self.sandbox.spawn(&mut cmd) {
             Ok(c) => c,
             Err(e) => {
                 return (
@@ -513,13 +518,13 @@ impl ScriptExecutor {
             }
         };
 
-        let mut cmd = Command::new(program);
+        let mut cmd = self.sandbox.command(program);
         cmd.args(&script_args)
             .args(args)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
 
-        let mut child = match cmd.spawn() {
+        let mut child = match self.sandbox.spawn(&mut cmd) {
             Ok(c) => c,
             Err(e) => {
                 return (String::new(), false, format!("Failed to spawn script: {e}"));
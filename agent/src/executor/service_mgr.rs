@@ -1,16 +1,38 @@
+use std::collections::{BTreeSet, HashMap};
 use std::process::Command;
+use std::sync::Arc;
 use tracing::info;
 
+use crate::config::Config;
+#[cfg(unix)]
+use crate::privsep::{HelperClient, Operation};
 use crate::proto::CommandResult;
+#[cfg(target_os = "windows")]
+use crate::proto::WindowsServiceInfo;
 use crate::security::validation::validate_service_name;
 
 /// Service management executor
-pub struct ServiceExecutor;
+pub struct ServiceExecutor {
+    /// Privileged helper that starts, stops and restarts services
+    #[cfg(unix)]
+    helper: Option<HelperClient>,
+}
 
 impl ServiceExecutor {
     /// Create a new service executor
-    pub fn new() -> Self {
-        Self
+    pub fn new(config: Arc<Config>) -> Self {
+        #[cfg(not(unix))]
+        let _ = config;
+        Self {
+            #[cfg(unix)]
+            helper: HelperClient::from_config(&config.privsep),
+        }
+    }
+
+    /// Service executor that always acts itself (the privileged helper)
+    #[cfg(unix)]
+    pub fn local() -> Self {
+        Self { helper: None }
     }
 
     /// Helper to create an error CommandResult
@@ -48,6 +70,118 @@ impl ServiceExecutor {
             .await
     }
 
+    /// Restart a service followed by the services that depend on it.
+    ///
+    /// Dependents are active services that (transitively) `Requires=` or
+    /// `BindsTo=` the target; they are restarted in `After=` order. The plan
+    /// is reported first; with `dry_run=true` nothing is restarted. Execution
+    /// stops at the first failed step.
+    pub async fn restart_with_dependents(
+        &self,
+        service_name: &str,
+        params: &HashMap<String, String>,
+    ) -> CommandResult {
+        if let Err(e) = validate_service_name(service_name) {
+            return Self::error_result(e);
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            let unit = if service_name.contains('.') {
+                service_name.to_string()
+            } else {
+                format!("{service_name}.service")
+            };
+            let plan = match systemd_units().map(|units| restart_plan(&unit, &units)) {
+                Ok(plan) => plan,
+                Err(e) => return Self::error_result(e),
+            };
+
+            let mut output = format!("Restart plan: {}\n", plan.join(" -> "));
+            if params.get("dry_run").is_some_and(|v| v == "true") {
+                return CommandResult {
+                    success: true,
+                    output,
+                    ..Default::default()
+                };
+            }
+
+            info!(
+                "[AUDIT] Service restart with dependents: {}",
+                plan.join(", ")
+            );
+            let mut failed = None;
+            for (i, unit) in plan.iter().enumerate() {
+                if failed.is_some() {
+                    output.push_str(&format!("{}. {unit}: skipped\n", i + 1));
+                    continue;
+                }
+                let result = self.run_action(unit, ServiceAction::Restart).await;
+                if result.success {
+                    output.push_str(&format!("{}. {unit}: restarted\n", i + 1));
+                } else {
+                    tracing::warn!(
+                        "[AUDIT] Restart of {} failed: {}",
+                        unit,
+                        result.error.trim()
+                    );
+                    output.push_str(&format!(
+                        "{}. {unit}: failed: {}\n",
+                        i + 1,
+                        result.error.trim()
+                    ));
+                    failed = Some(unit.clone());
+                }
+            }
+
+            CommandResult {
+                success: failed.is_none(),
+                output,
+                error: failed
+                    .map(|unit| format!("Restart of {unit} failed"))
+                    .unwrap_or_default(),
+                ..Default::default()
+            }
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = params;
+            Self::error_result("Dependency-aware restart requires systemd".to_string())
+        }
+    }
+
+    /// List all services with their state, start type, logon account,
+    /// binary path and recovery (failure) actions. Windows only.
+    ///
+    /// Params: `filter` (case-insensitive substring of the service or
+    /// display name).
+    pub async fn service_inventory(&self, params: &HashMap<String, String>) -> CommandResult {
+        #[cfg(target_os = "windows")]
+        {
+            let filter = params
+                .get("filter")
+                .map(|f| f.to_lowercase())
+                .filter(|f| !f.is_empty());
+            match tokio::task::spawn_blocking(move || windows_inventory(filter.as_deref())).await {
+                Ok(Ok(services)) => CommandResult {
+                    success: true,
+                    output: format!("{} services", services.len()),
+                    services,
+                    ..Default::default()
+                },
+                Ok(Err(e)) => Self::error_result(e),
+                Err(e) => Self::error_result(format!("Service inventory task failed: {e}")),
+            }
+        }
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            let _ = params;
+            Self::error_result("Service inventory is only available on Windows".to_string())
+        }
+    }
+
     /// Execute a service command
     async fn execute_service_command(
         &self,
@@ -60,6 +194,26 @@ impl ServiceExecutor {
         }
 
         info!("[AUDIT] Service {:?}: {}", action, service_name);
+        self.run_action(service_name, action).await
+    }
+
+    /// Perform a validated action, through the helper if there is one
+    async fn run_action(&self, service_name: &str, action: ServiceAction) -> CommandResult {
+        #[cfg(unix)]
+        if let Some(helper) = &self.helper {
+            let service = service_name.to_string();
+            let operation = match action {
+                ServiceAction::Start => Some(Operation::ServiceStart { service }),
+                ServiceAction::Stop => Some(Operation::ServiceStop { service }),
+                ServiceAction::Restart => Some(Operation::ServiceRestart { service }),
+                // Reading the status needs no privileges
+                ServiceAction::Status => None,
+            };
+            if let Some(operation) = operation {
+                return helper.call(&operation).await;
+            }
+        }
+
         #[cfg(target_os = "linux")]
         {
             self.execute_systemctl(service_name, action)
@@ -197,10 +351,121 @@ impl ServiceExecutor {
     }
 }
 
-impl Default for ServiceExecutor {
-    fn default() -> Self {
-        Self::new()
+/// Query every installed service through the service control manager
+#[cfg(target_os = "windows")]
+fn windows_inventory(filter: Option<&str>) -> Result<Vec<WindowsServiceInfo>, String> {
+    use crate::proto::ServiceRecoveryAction;
+    use crate::utils::safe_command::exec_with_timeout;
+    use std::time::Duration;
+    use windows_service::service::{
+        ServiceAccess, ServiceActionType, ServiceFailureResetPeriod, ServiceStartType, ServiceState,
+    };
+    use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+
+    let mut cmd = Command::new("powershell");
+    cmd.args([
+        "-NoProfile",
+        "-Command",
+        "Get-Service | Select-Object -ExpandProperty Name",
+    ]);
+    let output = exec_with_timeout(cmd, Duration::from_secs(30))
+        .filter(|output| output.status.success())
+        .ok_or_else(|| "Failed to enumerate services".to_string())?;
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+        .map_err(|e| format!("Failed to connect to the service control manager: {e}"))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut services = Vec::new();
+    for name in stdout.lines().map(str::trim).filter(|n| !n.is_empty()) {
+        let mut info = WindowsServiceInfo {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let service = match manager.open_service(
+            name,
+            ServiceAccess::QUERY_CONFIG | ServiceAccess::QUERY_STATUS,
+        ) {
+            Ok(service) => service,
+            Err(e) => {
+                info.error = format!("Failed to open service: {e}");
+                services.push(info);
+                continue;
+            }
+        };
+
+        match service.query_config() {
+            Ok(config) => {
+                info.display_name = config.display_name.to_string_lossy().to_string();
+                info.start_type = match config.start_type {
+                    ServiceStartType::AutoStart => "auto",
+                    ServiceStartType::OnDemand => "manual",
+                    ServiceStartType::Disabled => "disabled",
+                    ServiceStartType::SystemStart => "system",
+                    ServiceStartType::BootStart => "boot",
+                }
+                .to_string();
+                info.account = config
+                    .account_name
+                    .map(|a| a.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                info.binary_path = config.executable_path.to_string_lossy().to_string();
+                info.dependencies = config
+                    .dependencies
+                    .iter()
+                    .map(|d| d.to_system_identifier().to_string_lossy().to_string())
+                    .collect();
+            }
+            Err(e) => info.error = format!("Failed to query config: {e}"),
+        }
+
+        if let Ok(status) = service.query_status() {
+            info.state = match status.current_state {
+                ServiceState::Stopped => "stopped",
+                ServiceState::StartPending => "start_pending",
+                ServiceState::StopPending => "stop_pending",
+                ServiceState::Running => "running",
+                ServiceState::ContinuePending => "continue_pending",
+                ServiceState::PausePending => "pause_pending",
+                ServiceState::Paused => "paused",
+            }
+            .to_string();
+        }
+
+        if let Ok(failure) = service.get_failure_actions() {
+            info.reset_period_seconds = match failure.reset_period {
+                ServiceFailureResetPeriod::Never => -1,
+                ServiceFailureResetPeriod::After(period) => period.as_secs() as i64,
+            };
+            info.recovery_command = failure
+                .command
+                .map(|c| c.to_string_lossy().to_string())
+                .unwrap_or_default();
+            info.recovery_actions = failure
+                .actions
+                .unwrap_or_default()
+                .iter()
+                .map(|action| ServiceRecoveryAction {
+                    action: match action.action_type {
+                        ServiceActionType::None => "none",
+                        ServiceActionType::Reboot => "reboot",
+                        ServiceActionType::Restart => "restart",
+                        ServiceActionType::RunCommand => "run_command",
+                    }
+                    .to_string(),
+                    delay_ms: action.delay.as_millis() as u64,
+                })
+                .collect();
+        }
+
+        if let Some(filter) = filter
+            && !info.name.to_lowercase().contains(filter)
+            && !info.display_name.to_lowercase().contains(filter)
+        {
+            continue;
+        }
+        services.push(info);
     }
+    Ok(services)
 }
 
 /// Service action types
@@ -211,3 +476,144 @@ enum ServiceAction {
     Restart,
     Status,
 }
+
+/// Dependency properties of a systemd unit
+#[derive(Debug, Default, Clone, PartialEq)]
+struct UnitDeps {
+    /// Requires= and BindsTo=
+    requires: BTreeSet<String>,
+    after: BTreeSet<String>,
+}
+
+/// Dependencies of all active services
+#[cfg(target_os = "linux")]
+fn systemd_units() -> Result<HashMap<String, UnitDeps>, String> {
+    let output = Command::new("systemctl")
+        .args([
+            "list-units",
+            "--type=service",
+            "--state=active",
+            "--plain",
+            "--no-legend",
+        ])
+        .output()
+        .map_err(|e| format!("Failed to execute systemctl: {e}"))?;
+    let units: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(String::from)
+        .collect();
+
+    let output = Command::new("systemctl")
+        .args([
+            "show", "-p", "Id", "-p", "Requires", "-p", "BindsTo", "-p", "After",
+        ])
+        .args(&units)
+        .output()
+        .map_err(|e| format!("Failed to execute systemctl: {e}"))?;
+    Ok(parse_systemctl_show(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// Parse `systemctl show -p Id -p Requires -p BindsTo -p After` output,
+/// one blank-line separated block per unit
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn parse_systemctl_show(output: &str) -> HashMap<String, UnitDeps> {
+    let mut units = HashMap::new();
+    for block in output.split("\n\n") {
+        let mut id = None;
+        let mut deps = UnitDeps::default();
+        for line in block.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let values = value.split_whitespace().map(String::from);
+            match key {
+                "Id" => id = Some(value.to_string()),
+                "Requires" | "BindsTo" => deps.requires.extend(values),
+                "After" => deps.after.extend(values),
+                _ => {}
+            }
+        }
+        if let Some(id) = id {
+            units.insert(id, deps);
+        }
+    }
+    units
+}
+
+/// Restart order for `target` and the units requiring it: the target
+/// first, then dependents once everything they are ordered `After=` is done
+#[cfg_attr(not(any(test, target_os = "linux")), allow(dead_code))]
+fn restart_plan(target: &str, units: &HashMap<String, UnitDeps>) -> Vec<String> {
+    // Transitive dependents
+    let mut affected = BTreeSet::from([target.to_string()]);
+    loop {
+        let before = affected.len();
+        for (unit, deps) in units {
+            if deps.requires.iter().any(|r| affected.contains(r)) {
+                affected.insert(unit.clone());
+            }
+        }
+        if affected.len() == before {
+            break;
+        }
+    }
+
+    let mut plan = vec![target.to_string()];
+    let mut pending: BTreeSet<String> = affected.into_iter().filter(|u| u != target).collect();
+    while !pending.is_empty() {
+        let ready: Vec<String> = pending
+            .iter()
+            .filter(|unit| {
+                units.get(*unit).is_none_or(|deps| {
+                    deps.after
+                        .iter()
+                        .all(|a| !pending.contains(a) || a == *unit)
+                })
+            })
+            .cloned()
+            .collect();
+        // An ordering cycle: restart the rest by name
+        let ready = if ready.is_empty() {
+            pending.iter().cloned().collect()
+        } else {
+            ready
+        };
+        for unit in ready {
+            pending.remove(&unit);
+            plan.push(unit);
+        }
+    }
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart_plan() {
+        let output = "Id=postgresql.service\nRequires=system.slice\nBindsTo=\nAfter=network.target\n\n\
+                      Id=app.service\nRequires=postgresql.service\nBindsTo=\nAfter=postgresql.service network.target\n\n\
+                      Id=worker.service\nRequires=\nBindsTo=app.service\nAfter=app.service cache.service\n\n\
+                      Id=cache.service\nRequires=postgresql.service\nBindsTo=\nAfter=postgresql.service\n\n\
+                      Id=sshd.service\nRequires=\nBindsTo=\nAfter=network.target\n";
+        let units = parse_systemctl_show(output);
+        assert_eq!(units.len(), 5);
+        assert!(units["worker.service"].requires.contains("app.service"));
+
+        let plan = restart_plan("postgresql.service", &units);
+        assert_eq!(
+            plan,
+            [
+                "postgresql.service",
+                "app.service",
+                "cache.service",
+                "worker.service"
+            ]
+        );
+        assert_eq!(restart_plan("sshd.service", &units), ["sshd.service"]);
+    }
+}
@@ -1,4 +1,3 @@
-use std::process::Command;
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::{info, warn};
@@ -7,10 +6,13 @@ use crate::config::Config;
 use crate::proto::CommandResult;
 use crate::security::PermissionChecker;
 
+use super::Sandbox;
+
 /// Shell command executor with security controls
 pub struct ShellExecutor {
     config: Arc<Config>,
     permission_checker: PermissionChecker,
+    sandbox: Sandbox,
 }
 
 impl ShellExecutor {
@@ -18,6 +20,7 @@ impl ShellExecutor {
     pub fn new(config: Arc<Config>) -> Self {
         Self {
             permission_checker: PermissionChecker::new(config.clone()),
+            sandbox: Sandbox::new(config.shell.sandbox.clone()),
             config,
         }
     }
@@ -67,12 +70,12 @@ impl ShellExecutor {
         use std::io::Read;
         use std::process::Stdio;
 
-        let mut child = match Command::new("sh")
-            .args(["-c", command])
+        let mut cmd = self.sandbox.command("sh");
+        cmd.args(["-c", command])
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-        {
+            .stderr(Stdio::piped());
+
+        let mut child = match self.sandbox.spawn(&mut cmd) {
             Ok(child) => child,
             Err(e) => {
                 return CommandResult {
@@ -145,12 +148,12 @@ impl ShellExecutor {
         use std::io::Read;
         use std::process::Stdio;
 
-        let mut child = match Command::new("cmd")
-            .args(["/C", command])
+        let mut cmd = self.sandbox.command("cmd");
+        cmd.args(["/C", command])
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-        {
+            .stderr(Stdio::piped());
+
+        let mut child = match self.sandbox.spawn(&mut cmd) {
             Ok(child) => child,
             Err(e) => {
                 return CommandResult {
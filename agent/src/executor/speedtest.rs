@@ -0,0 +1,370 @@
+//! Network throughput probe
+//!
+//! Measures download/upload throughput and latency on demand, for verifying
+//! circuits at remote sites:
+//!
+//! - `iperf3` client against `speedtest.iperf3_server` when configured and
+//!   the binary is installed
+//! - otherwise `curl` against `speedtest.http_download_url` (and the
+//!   optional `http_upload_url`)
+//!
+//! Probes are capped at `speedtest.max_mbps` and at most one runs per
+//! `speedtest.min_interval_seconds`, so a probe can't saturate the link the
+//! agent itself depends on.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use tokio::net::TcpStream;
+use tracing::{info, warn};
+
+use crate::config::{Config, SpeedTestConfig};
+use crate::proto::{CommandResult, SpeedTestResult};
+use crate::utils::async_command::{
+    CommandResult as ExecResult, CommandTimeout, command_exists, run_command_async,
+};
+
+/// Start of the last probe, shared by all connections
+static LAST_RUN: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Connection attempts used for the latency median
+const LATENCY_SAMPLES: usize = 5;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Time allowed on top of the probe duration for setup and teardown
+const COMMAND_GRACE: Duration = Duration::from_secs(15);
+
+/// Throughput probe executor
+pub struct SpeedTestExecutor {
+    config: Arc<Config>,
+}
+
+impl SpeedTestExecutor {
+    /// Create a new speedtest executor
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
+    }
+
+    /// Helper to create an error CommandResult
+    fn error_result(error: String) -> CommandResult {
+        CommandResult {
+            command_id: String::new(),
+            success: false,
+            output: String::new(),
+            error,
+            ..Default::default()
+        }
+    }
+
+    /// Run a throughput probe.
+    ///
+    /// Params: `method` (`iperf3` or `http`, default: iperf3 when available)
+    /// and `duration_seconds` (per direction, capped at
+    /// `speedtest.duration_seconds`).
+    pub async fn run(&self, params: &HashMap<String, String>) -> CommandResult {
+        let cfg = &self.config.speedtest;
+
+        let duration = match params.get("duration_seconds") {
+            Some(value) => match value.parse::<u64>() {
+                Ok(d) if d > 0 => d.min(cfg.duration_seconds),
+                _ => return Self::error_result(format!("Invalid duration_seconds: {value}")),
+            },
+            None => cfg.duration_seconds,
+        };
+
+        let method = match params.get("method").map(String::as_str) {
+            Some("iperf3") => Method::Iperf3,
+            Some("http") => Method::Http,
+            Some(other) => return Self::error_result(format!("Unknown method: {other}")),
+            None if cfg.iperf3_server.is_some() && command_exists("iperf3").await => Method::Iperf3,
+            None => Method::Http,
+        };
+        let configured = match method {
+            Method::Iperf3 => cfg.iperf3_server.is_some(),
+            Method::Http => cfg.http_download_url.is_some(),
+        };
+        if !configured {
+            return Self::error_result(format!("No {method:?} speedtest server is configured"));
+        }
+
+        {
+            let mut last_run = LAST_RUN.lock();
+            if let Some(last) = *last_run {
+                let wait =
+                    Duration::from_secs(cfg.min_interval_seconds).saturating_sub(last.elapsed());
+                if !wait.is_zero() {
+                    return Self::error_result(format!(
+                        "Speedtest rate limited, retry in {} seconds",
+                        wait.as_secs().max(1)
+                    ));
+                }
+            }
+            *last_run = Some(Instant::now());
+        }
+
+        info!(
+            "[AUDIT] Speedtest started: method={:?}, duration={}s, max_mbps={}",
+            method, duration, cfg.max_mbps
+        );
+
+        let result = match method {
+            Method::Iperf3 => run_iperf3(cfg, duration).await,
+            Method::Http => run_http(cfg, duration).await,
+        };
+
+        match result {
+            Ok(result) => {
+                info!(
+                    "[AUDIT] Speedtest finished: down={:.1} Mbps, up={:.1} Mbps, latency={:.1} ms",
+                    result.download_mbps, result.upload_mbps, result.latency_ms
+                );
+                CommandResult {
+                    success: true,
+                    output: format!(
+                        "Download {:.1} Mbps, upload {:.1} Mbps, latency {:.1} ms ({})",
+                        result.download_mbps, result.upload_mbps, result.latency_ms, result.method
+                    ),
+                    speedtest_result: Some(result),
+                    ..Default::default()
+                }
+            }
+            Err(e) => {
+                warn!("[AUDIT] Speedtest failed: {}", e);
+                Self::error_result(e)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Method {
+    Iperf3,
+    Http,
+}
+
+async fn run_iperf3(cfg: &SpeedTestConfig, duration: u64) -> Result<SpeedTestResult, String> {
+    let server = cfg
+        .iperf3_server
+        .as_deref()
+        .ok_or("speedtest.iperf3_server is not configured")?;
+    let (host, port) = split_host_port(server, 5201);
+
+    let latency_ms = measure_latency(host, port).await?;
+
+    let port = port.to_string();
+    let duration_arg = duration.to_string();
+    let bitrate = format!("{}M", cfg.max_mbps);
+    let mut args = vec![
+        "-c",
+        host,
+        "-p",
+        &port,
+        "-t",
+        &duration_arg,
+        "-J",
+        // No per-interval reports, which keeps the JSON small
+        "-i",
+        "0",
+    ];
+    if cfg.max_mbps > 0 {
+        args.extend(["-b", &bitrate]);
+    }
+
+    let upload = iperf3_pass(&args, duration).await?;
+    args.push("-R");
+    let download = iperf3_pass(&args, duration).await?;
+
+    Ok(SpeedTestResult {
+        method: "iperf3".to_string(),
+        server: server.to_string(),
+        download_mbps: download,
+        upload_mbps: upload,
+        latency_ms,
+        duration_seconds: duration as u32,
+    })
+}
+
+/// One iperf3 run, returning Mbps received by the far end
+async fn iperf3_pass(args: &[&str], duration: u64) -> Result<f64, String> {
+    let timeout = Duration::from_secs(duration) + COMMAND_GRACE;
+    match run_command_async("iperf3", args, CommandTimeout::Custom(timeout)).await {
+        // iperf3 reports errors in the JSON document as well
+        ExecResult::Success(output) | ExecResult::Failed(_, output) => parse_iperf3(&output),
+        ExecResult::NotFound => Err("iperf3 is not installed".to_string()),
+        ExecResult::Timeout => Err("iperf3 timed out".to_string()),
+        ExecResult::Error(e) => Err(format!("iperf3 failed: {e}")),
+    }
+}
+
+/// Extract the received bitrate from `iperf3 -J` output
+fn parse_iperf3(output: &str) -> Result<f64, String> {
+    let json: serde_json::Value =
+        serde_json::from_str(output).map_err(|e| format!("Invalid iperf3 output: {e}"))?;
+    if let Some(error) = json.get("error").and_then(|e| e.as_str()) {
+        return Err(format!("iperf3: {error}"));
+    }
+    json.pointer("/end/sum_received/bits_per_second")
+        .and_then(|v| v.as_f64())
+        .map(|bps| bps / 1_000_000.0)
+        .ok_or_else(|| "iperf3 output has no received bitrate".to_string())
+}
+
+async fn run_http(cfg: &SpeedTestConfig, duration: u64) -> Result<SpeedTestResult, String> {
+    let download_url = cfg
+        .http_download_url
+        .as_deref()
+        .ok_or("Neither speedtest.iperf3_server nor speedtest.http_download_url is configured")?;
+    let (host, port) = url_host_port(download_url)?;
+
+    let latency_ms = measure_latency(host, port).await?;
+
+    let null = if cfg!(windows) { "NUL" } else { "/dev/null" };
+    let download_mbps = curl_pass(
+        cfg,
+        duration,
+        &["-o", null, "-w", "%{speed_download}", download_url],
+    )
+    .await?;
+
+    let upload_mbps = match &cfg.http_upload_url {
+        Some(url) => {
+            let path =
+                std::env::temp_dir().join(format!("nanolink-speedtest-{}", uuid::Uuid::new_v4()));
+            std::fs::write(&path, vec![0u8; cfg.upload_bytes as usize])
+                .map_err(|e| format!("Failed to create upload payload: {e}"))?;
+            let payload = format!("@{}", path.display());
+            let result = curl_pass(
+                cfg,
+                duration,
+                &[
+                    "-o",
+                    null,
+                    "-w",
+                    "%{speed_upload}",
+                    "--data-binary",
+                    &payload,
+                    url,
+                ],
+            )
+            .await;
+            let _ = std::fs::remove_file(&path);
+            result?
+        }
+        None => 0.0,
+    };
+
+    Ok(SpeedTestResult {
+        method: "http".to_string(),
+        server: host.to_string(),
+        download_mbps,
+        upload_mbps,
+        latency_ms,
+        duration_seconds: duration as u32,
+    })
+}
+
+/// One curl transfer, stopped after `duration`, returning Mbps
+async fn curl_pass(cfg: &SpeedTestConfig, duration: u64, args: &[&str]) -> Result<f64, String> {
+    let max_time = duration.to_string();
+    // --limit-rate takes bytes per second
+    let limit = (cfg.max_mbps * 125_000).to_string();
+    let mut full_args = vec!["-sS", "--max-time", &max_time];
+    if cfg.max_mbps > 0 {
+        full_args.extend(["--limit-rate", &limit]);
+    }
+    full_args.extend(args);
+
+    let timeout = Duration::from_secs(duration) + COMMAND_GRACE;
+    match run_command_async("curl", &full_args, CommandTimeout::Custom(timeout)).await {
+        // Exit code 28 is the expected --max-time cutoff; the average
+        // speed is still written
+        ExecResult::Success(output) | ExecResult::Failed(28, output) => output
+            .trim()
+            .parse::<f64>()
+            .map(|bytes_per_sec| bytes_per_sec * 8.0 / 1_000_000.0)
+            .map_err(|_| format!("Unexpected curl output: {}", output.trim())),
+        ExecResult::Failed(code, _) => Err(format!("curl exited with {code}")),
+        ExecResult::NotFound => Err("curl is not installed".to_string()),
+        ExecResult::Timeout => Err("curl timed out".to_string()),
+        ExecResult::Error(e) => Err(format!("curl failed: {e}")),
+    }
+}
+
+/// Median TCP connect time to the probe server
+async fn measure_latency(host: &str, port: u16) -> Result<f64, String> {
+    let mut samples = Vec::with_capacity(LATENCY_SAMPLES);
+    for _ in 0..LATENCY_SAMPLES {
+        let start = Instant::now();
+        match tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect((host, port))).await {
+            Ok(Ok(_)) => samples.push(start.elapsed().as_secs_f64() * 1000.0),
+            Ok(Err(e)) => return Err(format!("Cannot connect to {host}:{port}: {e}")),
+            Err(_) => return Err(format!("Connection to {host}:{port} timed out")),
+        }
+    }
+    samples.sort_by(f64::total_cmp);
+    Ok(samples[samples.len() / 2])
+}
+
+/// Split `host[:port]`, accepting bracketed IPv6 addresses
+fn split_host_port(address: &str, default_port: u16) -> (&str, u16) {
+    if let Some(rest) = address.strip_prefix('[') {
+        if let Some((host, tail)) = rest.split_once(']') {
+            let port = tail.strip_prefix(':').and_then(|p| p.parse().ok());
+            return (host, port.unwrap_or(default_port));
+        }
+    }
+    match address.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => (host, port.parse().unwrap_or(default_port)),
+        _ => (address, default_port),
+    }
+}
+
+/// Host and port of an http(s) URL
+fn url_host_port(url: &str) -> Result<(&str, u16), String> {
+    let (default_port, rest) = if let Some(rest) = url.strip_prefix("https://") {
+        (443, rest)
+    } else if let Some(rest) = url.strip_prefix("http://") {
+        (80, rest)
+    } else {
+        return Err(format!("Unsupported speedtest URL: {url}"));
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let authority = authority.rsplit('@').next().unwrap_or_default();
+    Ok(split_host_port(authority, default_port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_iperf3() {
+        let output = r#"{"start":{},"end":{"sum_sent":{"bits_per_second":9.6e7},"sum_received":{"bits_per_second":9.4e7}}}"#;
+        assert_eq!(parse_iperf3(output), Ok(94.0));
+
+        let output =
+            r#"{"start":{},"intervals":[],"end":{},"error":"unable to connect to server"}"#;
+        assert_eq!(
+            parse_iperf3(output),
+            Err("iperf3: unable to connect to server".to_string())
+        );
+    }
+
+    #[test]
+    fn test_url_host_port() {
+        assert_eq!(
+            url_host_port("https://speed.example.com/100MB.bin"),
+            Ok(("speed.example.com", 443))
+        );
+        assert_eq!(
+            url_host_port("http://[2001:db8::1]:8080/upload"),
+            Ok(("2001:db8::1", 8080))
+        );
+        assert_eq!(split_host_port("10.0.0.9", 5201), ("10.0.0.9", 5201));
+        assert_eq!(split_host_port("10.0.0.9:5202", 5201), ("10.0.0.9", 5202));
+        assert!(url_host_port("ftp://example.com").is_err());
+    }
+}
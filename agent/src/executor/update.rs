@@ -3,6 +3,8 @@ use std::env;
 use std::path::{Path, PathBuf};
 use tracing::{info, warn};
 
+use super::bundle::{Bundle, FileKind};
+use super::rollout::{self, PendingRollout};
 use crate::config::{UpdateConfig, UpdateSource};
 use crate::proto::{CommandResult, UpdateInfo};
 
@@ -22,7 +24,7 @@ fn is_safe_url(url: &str) -> bool {
 }
 
 /// Escape path for safe use in shell commands
-fn escape_path(path: &Path) -> Result<String, String> {
+pub(super) fn escape_path(path: &Path) -> Result<String, String> {
     let s = path
         .to_str()
         .ok_or_else(|| "Path contains invalid UTF-8".to_string())?;
@@ -197,7 +199,16 @@ impl UpdateExecutor {
         CommandResult {
             command_id: String::new(),
             success: true,
-            output: format!("Agent version: {AGENT_VERSION}"),
+            output: match env::current_exe()
+                .ok()
+                .and_then(|exe| rollout::pending(&exe))
+            {
+                Some(pending) => format!(
+                    "Agent version: {AGENT_VERSION} (update from {} awaiting confirmation)",
+                    pending.from_version
+                ),
+                None => format!("Agent version: {AGENT_VERSION}"),
+            },
             error: String::new(),
             update_info: Some(UpdateInfo {
                 current_version: AGENT_VERSION.to_string(),
@@ -473,6 +484,12 @@ impl UpdateExecutor {
             Err(e) => return Self::error_result(format!("Failed to get current exe path: {e}")),
         };
 
+        if self.config.blue_green {
+            return self
+                .apply_blue_green(&update_path, &current_exe, params)
+                .await;
+        }
+
         // Create backup of current binary
         let backup_path = current_exe.with_extension("bak");
 
@@ -496,6 +513,160 @@ impl UpdateExecutor {
         }
     }
 
+    /// Apply an offline update bundle: install its data files and, if it
+    /// carries a newer agent for this platform (or `force`), update the agent
+    pub async fn apply_bundle(&self, path: &Path, force: bool) -> CommandResult {
+        info!("[AUDIT] ApplyBundle requested: {}", path.display());
+
+        let bundle = match Bundle::open(path, &self.config.bundle_public_keys) {
+            Ok(bundle) => bundle,
+            Err(e) => return Self::error_result(format!("Rejected update bundle: {e}")),
+        };
+        let manifest = &bundle.manifest;
+        let mut output = format!(
+            "Bundle {} (created {}) verified\n",
+            manifest.version,
+            if manifest.created.is_empty() {
+                "unknown"
+            } else {
+                &manifest.created
+            }
+        );
+
+        let data_dir = Path::new(&self.config.data_dir);
+        for entry in manifest.files.iter().filter(|f| f.kind == FileKind::Data) {
+            let dest = data_dir.join(&entry.name);
+            let tmp = data_dir.join(format!(".{}.tmp", entry.name));
+            let write = std::fs::create_dir_all(data_dir)
+                .and_then(|_| std::fs::write(&tmp, bundle.file(&entry.name)))
+                .and_then(|_| std::fs::rename(&tmp, &dest));
+            if let Err(e) = write {
+                return Self::error_result(format!(
+                    "{output}Failed to install {}: {e}",
+                    dest.display()
+                ));
+            }
+            info!("[AUDIT] Installed {} from update bundle", dest.display());
+            output.push_str(&format!("Installed {}\n", dest.display()));
+        }
+
+        let platform = Self::get_platform_identifier().unwrap_or_default();
+        let agent = manifest
+            .files
+            .iter()
+            .find(|f| f.kind == FileKind::Agent && f.platform == platform);
+        let Some(agent) = agent else {
+            output.push_str(&format!("No agent binary for {platform} in bundle"));
+            return CommandResult {
+                success: true,
+                output,
+                ..Default::default()
+            };
+        };
+        if !force && !self.is_newer_version(&manifest.version, AGENT_VERSION) {
+            output.push_str(&format!(
+                "Agent {AGENT_VERSION} is not older than {}, skipping binary",
+                manifest.version
+            ));
+            return CommandResult {
+                success: true,
+                output,
+                ..Default::default()
+            };
+        }
+
+        let update_path = env::temp_dir().join("nanolink-agent-update");
+        #[cfg(windows)]
+        let update_path = update_path.with_extension("exe");
+        if let Err(e) = std::fs::write(&update_path, bundle.file(&agent.name)) {
+            return Self::error_result(format!("{output}Failed to extract agent binary: {e}"));
+        }
+        let params = HashMap::from([
+            ("path".to_string(), update_path.display().to_string()),
+            ("checksum".to_string(), agent.sha256.clone()),
+            ("version".to_string(), manifest.version.clone()),
+        ]);
+        let mut result = self.apply_update(&params).await;
+        result.output = format!("{output}{}", result.output);
+        result
+    }
+
+    /// Verify the update as a candidate next to the running agent, then
+    /// promote it with automatic rollback
+    async fn apply_blue_green(
+        &self,
+        update_path: &Path,
+        current_exe: &Path,
+        params: &HashMap<String, String>,
+    ) -> CommandResult {
+        if let Some(pending) = rollout::pending(current_exe) {
+            return Self::error_result(format!(
+                "Update {} -> {} is still awaiting confirmation",
+                pending.from_version, pending.to_version
+            ));
+        }
+
+        let staged = match rollout::stage(update_path, current_exe) {
+            Ok(path) => path,
+            Err(e) => return Self::error_result(e),
+        };
+        let cleanup = || {
+            if let Err(e) = std::fs::remove_file(&staged) {
+                warn!("Failed to clean up staged binary: {e}");
+            }
+        };
+
+        let version = match rollout::verify_candidate(&staged, &self.config).await {
+            Ok(version) => version,
+            Err(e) => {
+                cleanup();
+                warn!("[AUDIT] Update candidate rejected: {}", e);
+                return Self::error_result(format!("Update candidate failed health check: {e}"));
+            }
+        };
+        if let Some(expected) = params.get("version") {
+            if expected.trim_start_matches('v') != version {
+                cleanup();
+                return Self::error_result(format!(
+                    "Update candidate reports version {version}, expected {expected}"
+                ));
+            }
+        }
+        info!("Update candidate {} is healthy, promoting", version);
+
+        let pending = PendingRollout {
+            from_version: AGENT_VERSION.to_string(),
+            to_version: version.clone(),
+            promoted_at: chrono::Utc::now().to_rfc3339(),
+        };
+        if let Err(e) = rollout::promote(&staged, current_exe, &pending, &self.config).await {
+            cleanup();
+            return Self::error_result(e);
+        }
+        if let Err(e) = std::fs::remove_file(update_path) {
+            warn!("Failed to clean up downloaded file: {e}");
+        }
+        info!(
+            "[AUDIT] Update {} -> {} promoted, rollback in {}s unless confirmed",
+            AGENT_VERSION, version, self.config.rollback_timeout_seconds
+        );
+
+        CommandResult {
+            success: true,
+            output: format!(
+                "Candidate {version} passed health check. Agent will restart on the new binary \
+                 and roll back to {AGENT_VERSION} if it does not stay up."
+            ),
+            update_info: Some(UpdateInfo {
+                current_version: AGENT_VERSION.to_string(),
+                latest_version: version,
+                update_available: false,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
     #[cfg(unix)]
     async fn apply_update_unix(
         &self,
@@ -737,6 +908,7 @@ del /F "%~f0"
             ("macos", "x86_64") => Some("macos-x86_64"),
             ("macos", "aarch64") => Some("macos-aarch64"),
             ("windows", "x86_64") => Some("windows-x86_64"),
+            ("windows", "aarch64") => Some("windows-aarch64"),
             _ => None,
         }
     }
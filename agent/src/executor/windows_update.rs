@@ -0,0 +1,297 @@
+//! Windows Update backend
+//!
+//! winget and Chocolatey only cover applications. OS patches are queried and
+//! installed through the Windows Update Agent COM API (`Microsoft.Update.Session`)
+//! from PowerShell, which honors WSUS policy when one is configured. Scripts
+//! print a single JSON object that is parsed here.
+
+use serde::Deserialize;
+
+use crate::proto::{PackageInfo, WindowsUpdateStatus};
+
+/// Pending updates, last install/search time, pending reboot and WSUS server
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub(super) const STATUS_SCRIPT: &str = r#"
+$ErrorActionPreference = 'Stop'
+try {
+  $session = New-Object -ComObject Microsoft.Update.Session
+  $result = $session.CreateUpdateSearcher().Search("IsInstalled=0 and IsHidden=0 and Type='Software'")
+  $updates = @(foreach ($u in $result.Updates) {
+    [pscustomobject]@{
+      title = [string]$u.Title
+      kb = (@($u.KBArticleIDs | ForEach-Object { "KB$_" }) -join ',')
+      size = [int64]$u.MaxDownloadSize
+      severity = [string]$u.MsrcSeverity
+      categories = (@($u.Categories | ForEach-Object { $_.Name }) -join ', ')
+      optional = -not $u.AutoSelectOnWebSites
+      reboot = ($u.InstallationBehavior.RebootBehavior -ne 0)
+    }
+  })
+  $results = (New-Object -ComObject Microsoft.Update.AutoUpdate).Results
+  $format = { param($d) if ($d -and $d.Year -gt 1601) { $d.ToUniversalTime().ToString('o') } else { '' } }
+  $policy = Get-ItemProperty 'HKLM:\SOFTWARE\Policies\Microsoft\Windows\WindowsUpdate' -ErrorAction SilentlyContinue
+  $au = Get-ItemProperty 'HKLM:\SOFTWARE\Policies\Microsoft\Windows\WindowsUpdate\AU' -ErrorAction SilentlyContinue
+  [pscustomobject]@{
+    updates = $updates
+    last_install = (& $format $results.LastInstallationSuccessDate)
+    last_search = (& $format $results.LastSearchSuccessDate)
+    reboot_pending = (Test-Path 'HKLM:\SOFTWARE\Microsoft\Windows\CurrentVersion\WindowsUpdate\Auto Update\RebootRequired') -or (Test-Path 'HKLM:\SOFTWARE\Microsoft\Windows\CurrentVersion\Component Based Servicing\RebootPending')
+    wsus_server = if ($au -and $au.UseWUServer -eq 1) { [string]$policy.WUServer } else { '' }
+  } | ConvertTo-Json -Depth 4 -Compress
+} catch {
+  [pscustomobject]@{ error = $_.Exception.Message } | ConvertTo-Json -Compress
+  exit 1
+}
+"#;
+
+/// Download and install updates. `__KBS__` is replaced by the validated KB
+/// list; an empty list selects all important (non-optional) updates.
+#[cfg_attr(not(any(test, target_os = "windows")), allow(dead_code))]
+const INSTALL_SCRIPT: &str = r#"
+$ErrorActionPreference = 'Stop'
+try {
+  $kbs = @(__KBS__)
+  $session = New-Object -ComObject Microsoft.Update.Session
+  $result = $session.CreateUpdateSearcher().Search("IsInstalled=0 and IsHidden=0 and Type='Software'")
+  $selected = New-Object -ComObject Microsoft.Update.UpdateColl
+  foreach ($u in $result.Updates) {
+    $ids = @($u.KBArticleIDs | ForEach-Object { "KB$_" })
+    if (($kbs.Count -eq 0 -and $u.AutoSelectOnWebSites) -or ($ids | Where-Object { $kbs -contains $_ })) {
+      if (-not $u.EulaAccepted) { $u.AcceptEula() }
+      [void]$selected.Add($u)
+    }
+  }
+  $titles = @(foreach ($u in $selected) { [string]$u.Title })
+  if ($selected.Count -eq 0) {
+    [pscustomobject]@{ result_code = 2; reboot_required = $false; titles = $titles } | ConvertTo-Json -Compress
+    exit 0
+  }
+  $downloader = $session.CreateUpdateDownloader()
+  $downloader.Updates = $selected
+  [void]$downloader.Download()
+  $installer = $session.CreateUpdateInstaller()
+  $installer.Updates = $selected
+  $r = $installer.Install()
+  [pscustomobject]@{ result_code = [int]$r.ResultCode; reboot_required = [bool]$r.RebootRequired; titles = $titles } | ConvertTo-Json -Compress
+} catch {
+  [pscustomobject]@{ error = $_.Exception.Message } | ConvertTo-Json -Compress
+  exit 1
+}
+"#;
+
+#[derive(Debug, Deserialize)]
+struct StatusOutput {
+    #[serde(default)]
+    updates: Vec<UpdateOutput>,
+    #[serde(default)]
+    last_install: String,
+    #[serde(default)]
+    last_search: String,
+    #[serde(default)]
+    reboot_pending: bool,
+    #[serde(default)]
+    wsus_server: String,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateOutput {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    kb: String,
+    #[serde(default)]
+    size: i64,
+    #[serde(default)]
+    severity: String,
+    #[serde(default)]
+    categories: String,
+    #[serde(default)]
+    optional: bool,
+    #[serde(default)]
+    reboot: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct InstallOutput {
+    #[serde(default)]
+    result_code: i32,
+    #[serde(default)]
+    reboot_required: bool,
+    #[serde(default)]
+    titles: Vec<String>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// Outcome of an install run
+#[derive(Debug)]
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub(super) struct InstallReport {
+    pub success: bool,
+    pub reboot_required: bool,
+    pub summary: String,
+}
+
+/// Parse the status script output into pending updates and overall state
+#[cfg_attr(not(any(test, target_os = "windows")), allow(dead_code))]
+pub(super) fn parse_status(json: &str) -> Result<(Vec<PackageInfo>, WindowsUpdateStatus), String> {
+    let output: StatusOutput = serde_json::from_str(json.trim())
+        .map_err(|e| format!("Invalid Windows Update output: {e}"))?;
+    if let Some(error) = output.error {
+        return Err(format!("Windows Update search failed: {error}"));
+    }
+
+    let packages: Vec<PackageInfo> = output
+        .updates
+        .into_iter()
+        .map(|update| {
+            let mut description = update.categories;
+            if !update.severity.is_empty() {
+                description = format!("{description} ({})", update.severity);
+            }
+            if update.reboot {
+                description.push_str(", requires reboot");
+            }
+            PackageInfo {
+                name: update.title,
+                version: update.kb,
+                description,
+                installed_size: update.size,
+                update_available: true,
+                repository: if update.optional {
+                    "optional".to_string()
+                } else {
+                    "important".to_string()
+                },
+                package_manager: "windows-update".to_string(),
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    let status = WindowsUpdateStatus {
+        pending_count: packages.len() as u32,
+        important_count: packages
+            .iter()
+            .filter(|p| p.repository == "important")
+            .count() as u32,
+        last_install_time: output.last_install,
+        last_search_time: output.last_search,
+        reboot_pending: output.reboot_pending,
+        wsus_server: output.wsus_server,
+    };
+    Ok((packages, status))
+}
+
+/// Install script for the given KB articles (all important updates if empty)
+#[cfg_attr(not(any(test, target_os = "windows")), allow(dead_code))]
+pub(super) fn install_script(kbs: &[String]) -> String {
+    let list = kbs
+        .iter()
+        .map(|kb| format!("'{kb}'"))
+        .collect::<Vec<_>>()
+        .join(",");
+    INSTALL_SCRIPT.replace("__KBS__", &list)
+}
+
+/// Parse a comma-separated `kb` parameter; only `KB<digits>` is accepted
+pub(super) fn parse_kb_list(value: &str) -> Result<Vec<String>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|kb| !kb.is_empty())
+        .map(|kb| {
+            let digits = kb
+                .strip_prefix("KB")
+                .or_else(|| kb.strip_prefix("kb"))
+                .unwrap_or(kb);
+            if !digits.is_empty()
+                && digits.len() <= 10
+                && digits.chars().all(|c| c.is_ascii_digit())
+            {
+                Ok(format!("KB{digits}"))
+            } else {
+                Err(format!("Invalid KB article: {kb}"))
+            }
+        })
+        .collect()
+}
+
+/// Parse the install script output
+#[cfg_attr(not(any(test, target_os = "windows")), allow(dead_code))]
+pub(super) fn parse_install(json: &str) -> Result<InstallReport, String> {
+    let output: InstallOutput = serde_json::from_str(json.trim())
+        .map_err(|e| format!("Invalid Windows Update output: {e}"))?;
+    if let Some(error) = output.error {
+        return Err(format!("Windows Update install failed: {error}"));
+    }
+
+    // OperationResultCode: 2 succeeded, 3 succeeded with errors, 4 failed, 5 aborted
+    let outcome = match output.result_code {
+        2 => "succeeded",
+        3 => "succeeded with errors",
+        4 => "failed",
+        5 => "aborted",
+        _ => "did not complete",
+    };
+    let summary = if output.titles.is_empty() {
+        "No matching updates to install".to_string()
+    } else {
+        format!(
+            "Installation of {} updates {}{}:\n{}",
+            output.titles.len(),
+            outcome,
+            if output.reboot_required {
+                ", reboot required"
+            } else {
+                ""
+            },
+            output.titles.join("\n")
+        )
+    };
+    Ok(InstallReport {
+        success: matches!(output.result_code, 2 | 3),
+        reboot_required: output.reboot_required,
+        summary,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_status() {
+        let json = r#"{"updates":[{"title":"2026-10 Cumulative Update (KB5031356)","kb":"KB5031356","size":734003200,"severity":"Critical","categories":"Security Updates","optional":false,"reboot":true},{"title":"Defender definitions","kb":"KB2267602","size":1000,"severity":"","categories":"Definition Updates","optional":true,"reboot":false}],"last_install":"2026-10-01T03:00:00.0000000Z","last_search":"2026-10-15T03:00:00.0000000Z","reboot_pending":true,"wsus_server":"http://wsus.corp:8530"}"#;
+        let (packages, status) = parse_status(json).unwrap();
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].version, "KB5031356");
+        assert_eq!(
+            packages[0].description,
+            "Security Updates (Critical), requires reboot"
+        );
+        assert_eq!(packages[1].repository, "optional");
+        assert_eq!(status.pending_count, 2);
+        assert_eq!(status.important_count, 1);
+        assert!(status.reboot_pending);
+        assert_eq!(status.wsus_server, "http://wsus.corp:8530");
+
+        assert!(parse_status(r#"{"error":"0x8024402C"}"#).is_err());
+    }
+
+    #[test]
+    fn test_kb_list_and_install() {
+        assert_eq!(
+            parse_kb_list("KB5031356, 2267602").unwrap(),
+            vec!["KB5031356", "KB2267602"]
+        );
+        assert!(parse_kb_list("KB1'; Remove-Item C:\\").is_err());
+        assert!(install_script(&["KB1".to_string()]).contains("$kbs = @('KB1')"));
+
+        let report =
+            parse_install(r#"{"result_code":2,"reboot_required":true,"titles":["KB1"]}"#).unwrap();
+        assert!(report.success && report.reboot_required);
+    }
+}
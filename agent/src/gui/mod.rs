@@ -1,8 +1,11 @@
-//! GUI module for NanoLink Agent configuration wizard
+//! GUI module for NanoLink Agent configuration
 //!
-//! This module provides a graphical user interface for configuring
-//! the NanoLink Agent when no configuration file is found.
+//! Provides the first-run configuration wizard, launched when no
+//! configuration file is found, and a settings window (`nanolink-agent gui`)
+//! for checking and editing an installed agent.
 
+mod settings;
 mod wizard;
 
+pub use settings::run_settings;
 pub use wizard::run_wizard;
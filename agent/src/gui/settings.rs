@@ -0,0 +1,303 @@
+//! Settings window for an installed agent
+//!
+//! Opened with `nanolink-agent gui` at any time after setup. Shows whether
+//! the agent service is running and, through the local management API, the
+//! state of each server connection. Servers and tokens can be edited and
+//! tested with the same form as the wizard; saving rewrites the config file
+//! and can restart the service so the change takes effect.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use eframe::egui;
+use parking_lot::Mutex;
+use serde::Deserialize;
+
+use super::wizard::{ServerForm, TestStatus, server_editor};
+use crate::config::{Config, ManagementConfig};
+use crate::management::client::ApiClient;
+
+/// How often the status panel is refreshed
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// One entry of `/api/connection/status`
+#[derive(Debug, Clone, Deserialize)]
+struct ConnectionState {
+    server: String,
+    connected: bool,
+    last_error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConnectionStatusResponse {
+    servers: Vec<ConnectionState>,
+}
+
+/// Latest view of the running agent
+#[derive(Debug, Clone, Default)]
+struct AgentStatus {
+    service_running: bool,
+    /// Server connections, or why they are unavailable
+    connections: Option<Result<Vec<ConnectionState>, String>>,
+}
+
+struct SettingsApp {
+    config_path: PathBuf,
+    config: Config,
+    servers: Vec<ServerForm>,
+    selected_server: usize,
+    skip_connection_test: bool,
+    /// Shared with the status polling thread
+    status: Arc<Mutex<AgentStatus>>,
+    /// Result of the last save, and whether it succeeded
+    message: Option<(bool, String)>,
+}
+
+impl SettingsApp {
+    fn new(cc: &eframe::CreationContext<'_>, config_path: PathBuf, config: Config) -> Self {
+        let status = Arc::new(Mutex::new(AgentStatus::default()));
+        poll_status(
+            cc.egui_ctx.clone(),
+            config.management.clone(),
+            status.clone(),
+        );
+        Self {
+            servers: config
+                .servers
+                .iter()
+                .map(ServerForm::from_server_config)
+                .collect(),
+            selected_server: 0,
+            skip_connection_test: false,
+            status,
+            message: None,
+            config_path,
+            config,
+        }
+    }
+
+    fn save(&mut self) -> Result<(), String> {
+        if self.servers.is_empty() {
+            return Err("At least one server must be configured".to_string());
+        }
+        let servers = self
+            .servers
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                s.to_server_config()
+                    .map_err(|e| format!("Server {} ({}): {}", i + 1, s.label(), e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if !self.skip_connection_test {
+            let untested: Vec<String> = self
+                .servers
+                .iter()
+                .filter(|s| !matches!(s.status(), TestStatus::Passed(_)))
+                .map(|s| s.label())
+                .collect();
+            if !untested.is_empty() {
+                return Err(format!(
+                    "Connection test has not passed for: {}",
+                    untested.join(", ")
+                ));
+            }
+        }
+
+        self.config.servers = servers;
+        self.config
+            .save(&self.config_path)
+            .map_err(|e| format!("{e:#}"))
+    }
+
+    fn show_status(&self, ui: &mut egui::Ui) {
+        let status = self.status.lock().clone();
+
+        egui::Grid::new("agent_status_grid")
+            .num_columns(2)
+            .spacing([20.0, 6.0])
+            .show(ui, |ui| {
+                ui.label("Config file:");
+                ui.label(self.config_path.display().to_string());
+                ui.end_row();
+
+                ui.label("Service:");
+                if status.service_running {
+                    ui.colored_label(egui::Color32::GREEN, "Running");
+                } else {
+                    ui.colored_label(egui::Color32::YELLOW, "Not running");
+                }
+                ui.end_row();
+            });
+
+        ui.add_space(6.0);
+        match &status.connections {
+            None => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Checking connections...");
+                });
+            }
+            Some(Err(reason)) => {
+                ui.label(egui::RichText::new(reason).color(egui::Color32::GRAY));
+            }
+            Some(Ok(connections)) if connections.is_empty() => {
+                ui.label(egui::RichText::new("No server connections").color(egui::Color32::GRAY));
+            }
+            Some(Ok(connections)) => {
+                for conn in connections {
+                    ui.horizontal(|ui| {
+                        if conn.connected {
+                            ui.colored_label(egui::Color32::GREEN, "● Connected");
+                        } else {
+                            ui.colored_label(egui::Color32::RED, "● Disconnected");
+                        }
+                        ui.label(&conn.server);
+                        if let Some(error) = conn.last_error.as_deref().filter(|_| !conn.connected)
+                        {
+                            ui.label(egui::RichText::new(error).color(egui::Color32::GRAY));
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    fn show_actions(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(
+            &mut self.skip_connection_test,
+            "Save without a passing connection test",
+        );
+        ui.add_space(6.0);
+
+        ui.horizontal(|ui| {
+            if ui.button("  Save  ").clicked() {
+                self.message = Some(match self.save() {
+                    Ok(()) => (
+                        true,
+                        "Saved. Restart the service to apply the changes.".to_string(),
+                    ),
+                    Err(e) => (false, e),
+                });
+            }
+
+            if ui
+                .button(egui::RichText::new("  Save & Restart Service  ").strong())
+                .clicked()
+            {
+                self.message = Some(
+                    match self.save().and_then(|_| crate::platform::restart_service()) {
+                        Ok(()) => (true, "Saved and restarted the service.".to_string()),
+                        Err(e) => (false, e),
+                    },
+                );
+            }
+
+            if ui.button("  Close  ").clicked() {
+                ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+        });
+
+        if let Some((ok, message)) = &self.message {
+            ui.add_space(6.0);
+            let color = if *ok {
+                egui::Color32::GREEN
+            } else {
+                egui::Color32::RED
+            };
+            ui.colored_label(color, message);
+        }
+    }
+}
+
+impl eframe::App for SettingsApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        ctx.set_visuals(egui::Visuals::dark());
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                ui.add_space(10.0);
+                ui.heading(
+                    egui::RichText::new("NanoLink Agent Settings")
+                        .size(22.0)
+                        .strong(),
+                );
+                ui.label(
+                    egui::RichText::new(format!("Version {}", env!("CARGO_PKG_VERSION")))
+                        .color(egui::Color32::GRAY),
+                );
+                ui.add_space(10.0);
+            });
+
+            egui::ScrollArea::vertical()
+                .max_height(ui.available_height() - 90.0)
+                .show(ui, |ui| {
+                    ui.label(egui::RichText::new("Status").size(16.0).strong());
+                    ui.add_space(6.0);
+                    self.show_status(ui);
+
+                    ui.add_space(10.0);
+                    ui.separator();
+                    ui.label(egui::RichText::new("Servers").size(16.0).strong());
+                    ui.add_space(6.0);
+                    server_editor(ui, ctx, &mut self.servers, &mut self.selected_server);
+                });
+
+            ui.separator();
+            self.show_actions(ui);
+        });
+    }
+}
+
+/// Refresh `status` in the background for as long as the window is open
+fn poll_status(ctx: egui::Context, management: ManagementConfig, status: Arc<Mutex<AgentStatus>>) {
+    std::thread::spawn(move || {
+        loop {
+            let current = AgentStatus {
+                service_running: crate::platform::is_service_running(),
+                connections: Some(connection_status(&management)),
+            };
+            *status.lock() = current;
+            ctx.request_repaint();
+            std::thread::sleep(REFRESH_INTERVAL);
+        }
+    });
+}
+
+/// Server connections as reported by the management API of the running agent
+fn connection_status(management: &ManagementConfig) -> Result<Vec<ConnectionState>, String> {
+    if !management.enabled {
+        return Err("Enable the management API to see connection status".to_string());
+    }
+    ApiClient::local(management)?
+        .get::<ConnectionStatusResponse>("/api/connection/status")
+        .map(|r| r.servers)
+}
+
+/// Open the settings window; falls back to the wizard without a config file
+pub fn run_settings(config_path: Option<PathBuf>) -> anyhow::Result<()> {
+    let Some(config_path) = config_path else {
+        return super::run_wizard();
+    };
+    let config = Config::load(&config_path)?;
+
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_inner_size([640.0, 640.0])
+            .with_min_inner_size([520.0, 480.0])
+            .with_title("NanoLink Agent - Settings"),
+        centered: true,
+        ..Default::default()
+    };
+
+    eframe::run_native(
+        "NanoLink Agent Settings",
+        options,
+        Box::new(|cc| Ok(Box::new(SettingsApp::new(cc, config_path, config)))),
+    )
+    .map_err(|e| anyhow::anyhow!("Failed to run settings window: {}", e))?;
+
+    Ok(())
+}
@@ -1,8 +1,15 @@
 //! Configuration wizard for NanoLink Agent
+//!
+//! Walks the user through server connections, collector intervals and the
+//! management API, and verifies every server with a live gRPC auth check
+//! before the configuration is written.
 
-use crate::config::{Config, ServerConfig};
+use crate::config::{Config, DEFAULT_GRPC_PORT, ServerConfig};
+use crate::connection::grpc::GrpcClient;
 use eframe::egui;
+use parking_lot::Mutex;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 /// Permission levels for server connections
 const PERMISSION_LEVELS: &[(&str, u8)] = &[
@@ -12,39 +19,124 @@ const PERMISSION_LEVELS: &[(&str, u8)] = &[
     ("SYSTEM_ADMIN (3) - Full control", 3),
 ];
 
-/// Wizard state
-#[derive(Default)]
-struct WizardState {
-    // Server configuration
+/// Minimum accepted collector interval in milliseconds
+const MIN_INTERVAL_MS: u64 = 100;
+
+/// Wizard pages, in navigation order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum WizardStep {
+    #[default]
+    Welcome,
+    Servers,
+    Collector,
+    Management,
+    Review,
+}
+
+impl WizardStep {
+    const PAGES: [WizardStep; 4] = [
+        WizardStep::Servers,
+        WizardStep::Collector,
+        WizardStep::Management,
+        WizardStep::Review,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            WizardStep::Welcome => "Welcome",
+            WizardStep::Servers => "Servers",
+            WizardStep::Collector => "Collector",
+            WizardStep::Management => "Management API",
+            WizardStep::Review => "Review & Save",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            WizardStep::Welcome => WizardStep::Servers,
+            WizardStep::Servers => WizardStep::Collector,
+            WizardStep::Collector => WizardStep::Management,
+            WizardStep::Management | WizardStep::Review => WizardStep::Review,
+        }
+    }
+
+    fn prev(self) -> Self {
+        match self {
+            WizardStep::Welcome | WizardStep::Servers => WizardStep::Welcome,
+            WizardStep::Collector => WizardStep::Servers,
+            WizardStep::Management => WizardStep::Collector,
+            WizardStep::Review => WizardStep::Management,
+        }
+    }
+}
+
+/// Result of the live connection test for a server
+#[derive(Debug, Clone, Default)]
+pub(super) enum TestStatus {
+    #[default]
+    Untested,
+    Running,
+    Passed(String),
+    Failed(String),
+}
+
+/// Editable form for a single server entry
+pub(super) struct ServerForm {
     host: String,
     port: String,
     token: String,
     permission: usize,
     tls_enabled: bool,
     tls_verify: bool,
-
-    // UI state
-    current_step: usize,
-    error_message: Option<String>,
     show_token: bool,
-
-    // Result
-    config_saved: bool,
-    config_path: Option<PathBuf>,
+    /// Shared with the background test thread
+    test_status: Arc<Mutex<TestStatus>>,
+    /// Entry being edited, whose settings without a form field are kept
+    original: Option<ServerConfig>,
 }
 
-impl WizardState {
-    fn new() -> Self {
+impl Default for ServerForm {
+    fn default() -> Self {
         Self {
             host: String::new(),
-            port: "39100".to_string(),
+            port: DEFAULT_GRPC_PORT.to_string(),
+            token: String::new(),
             permission: 0,
+            tls_enabled: false,
             tls_verify: true,
+            show_token: false,
+            test_status: Arc::new(Mutex::new(TestStatus::Untested)),
+            original: None,
+        }
+    }
+}
+
+impl ServerForm {
+    pub(super) fn from_server_config(server: &ServerConfig) -> Self {
+        Self {
+            host: server.host.clone(),
+            port: server.port.to_string(),
+            token: server.token.clone(),
+            permission: PERMISSION_LEVELS
+                .iter()
+                .position(|(_, level)| *level == server.permission)
+                .unwrap_or(0),
+            tls_enabled: server.tls_enabled,
+            tls_verify: server.tls_verify,
+            original: Some(server.clone()),
             ..Default::default()
         }
     }
 
-    fn validate_server_config(&self) -> Result<(), String> {
+    pub(super) fn label(&self) -> String {
+        if self.host.trim().is_empty() {
+            "(new server)".to_string()
+        } else {
+            format!("{}:{}", self.host.trim(), self.port.trim())
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
         if self.host.trim().is_empty() {
             return Err("Server host is required".to_string());
         }
@@ -70,21 +162,256 @@ impl WizardState {
         Ok(())
     }
 
-    fn save_config(&mut self) -> Result<PathBuf, String> {
-        self.validate_server_config()?;
-
-        let server = ServerConfig {
-            host: self.host.trim().to_string(),
-            port: self.port.trim().parse().unwrap(),
+    pub(super) fn to_server_config(&self) -> Result<ServerConfig, String> {
+        self.validate()?;
+
+        let host = self.host.trim().to_string();
+        let port = self.port.trim().parse().unwrap();
+        // A pinned certificate only belongs to the same endpoint
+        let same_endpoint = self
+            .original
+            .as_ref()
+            .filter(|o| o.host == host && o.port == port);
+        Ok(ServerConfig {
+            host,
+            port,
             token: self.token.clone(),
-            management_token: None,
+            management_token: self
+                .original
+                .as_ref()
+                .and_then(|o| o.management_token.clone()),
             permission: PERMISSION_LEVELS[self.permission].1,
             tls_enabled: self.tls_enabled,
             tls_verify: self.tls_verify,
+            tls_pin: same_endpoint.and_then(|o| o.tls_pin.clone()),
+            srv: self.original.as_ref().and_then(|o| o.srv.clone()),
+            role: self.original.as_ref().and_then(|o| o.role),
+            bandwidth_cap: self.original.as_ref().and_then(|o| o.bandwidth_cap.clone()),
+            reporting_schedule: self
+                .original
+                .as_ref()
+                .and_then(|o| o.reporting_schedule.clone()),
+        })
+    }
+
+    pub(super) fn status(&self) -> TestStatus {
+        self.test_status.lock().clone()
+    }
+
+    /// Any edit invalidates a previous test result
+    fn reset_test(&self) {
+        *self.test_status.lock() = TestStatus::Untested;
+    }
+
+    /// Run the gRPC connect + authenticate check on a background thread
+    fn start_test(&self, ctx: &egui::Context) {
+        let server = match self.to_server_config() {
+            Ok(s) => s,
+            Err(e) => {
+                *self.test_status.lock() = TestStatus::Failed(e);
+                return;
+            }
         };
 
+        *self.test_status.lock() = TestStatus::Running;
+
+        let status = self.test_status.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let result = tokio::runtime::Runtime::new()
+                .map_err(|e| format!("Failed to create runtime: {e}"))
+                .and_then(|rt| {
+                    rt.block_on(GrpcClient::test_server_connection(
+                        &server,
+                        server.permission,
+                    ))
+                    .map_err(|e| e.to_string())
+                });
+
+            *status.lock() = match result {
+                Ok(detail) => TestStatus::Passed(detail),
+                Err(e) => TestStatus::Failed(e),
+            };
+            ctx.request_repaint();
+        });
+    }
+}
+
+/// Wizard state
+#[derive(Default)]
+struct WizardState {
+    // Server configuration
+    servers: Vec<ServerForm>,
+    selected_server: usize,
+
+    // Collector intervals (milliseconds)
+    realtime_interval_ms: String,
+    disk_usage_interval_ms: String,
+    session_interval_ms: String,
+    ip_check_interval_ms: String,
+
+    // Management API
+    management_enabled: bool,
+    management_port: String,
+    management_bind_address: String,
+    management_api_token: String,
+
+    // UI state
+    current_step: WizardStep,
+    error_message: Option<String>,
+    skip_connection_test: bool,
+
+    // Result
+    config_saved: bool,
+    config_path: Option<PathBuf>,
+}
+
+impl WizardState {
+    fn new() -> Self {
+        let defaults = Config::sample();
+
+        Self {
+            servers: vec![ServerForm::default()],
+            realtime_interval_ms: defaults.collector.realtime_interval_ms.to_string(),
+            disk_usage_interval_ms: defaults.collector.disk_usage_interval_ms.to_string(),
+            session_interval_ms: defaults.collector.session_interval_ms.to_string(),
+            ip_check_interval_ms: defaults.collector.ip_check_interval_ms.to_string(),
+            management_enabled: defaults.management.enabled,
+            management_port: defaults.management.port.to_string(),
+            management_bind_address: defaults.management.bind_address,
+            management_api_token: String::new(),
+            ..Default::default()
+        }
+    }
+
+    fn validate_servers(&self) -> Result<(), String> {
+        if self.servers.is_empty() {
+            return Err("At least one server must be configured".to_string());
+        }
+
+        for (i, server) in self.servers.iter().enumerate() {
+            server
+                .validate()
+                .map_err(|e| format!("Server {} ({}): {}", i + 1, server.label(), e))?;
+        }
+
+        Ok(())
+    }
+
+    fn parse_interval(name: &str, value: &str) -> Result<u64, String> {
+        let ms: u64 = value
+            .trim()
+            .parse()
+            .map_err(|_| format!("{name} must be a number of milliseconds"))?;
+
+        if ms < MIN_INTERVAL_MS {
+            return Err(format!("{name} must be at least {MIN_INTERVAL_MS}ms"));
+        }
+
+        Ok(ms)
+    }
+
+    fn validate_collector(&self) -> Result<(), String> {
+        Self::parse_interval("Realtime interval", &self.realtime_interval_ms)?;
+        Self::parse_interval("Disk usage interval", &self.disk_usage_interval_ms)?;
+        Self::parse_interval("Session interval", &self.session_interval_ms)?;
+        Self::parse_interval("IP check interval", &self.ip_check_interval_ms)?;
+        Ok(())
+    }
+
+    fn validate_management(&self) -> Result<(), String> {
+        if !self.management_enabled {
+            return Ok(());
+        }
+
+        let port: u16 = self
+            .management_port
+            .trim()
+            .parse()
+            .map_err(|_| "Management port must be a valid number (1-65535)".to_string())?;
+
+        if port == 0 {
+            return Err("Management port must be greater than 0".to_string());
+        }
+
+        if self
+            .management_bind_address
+            .trim()
+            .parse::<std::net::IpAddr>()
+            .is_err()
+        {
+            return Err("Bind address must be an IP address".to_string());
+        }
+
+        // Config::load disables the management API when no token is set
+        if self.management_api_token.trim().is_empty() {
+            return Err("An API token is required when the management API is enabled".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Validate the page the user is leaving
+    fn validate_step(&self, step: WizardStep) -> Result<(), String> {
+        match step {
+            WizardStep::Servers => self.validate_servers(),
+            WizardStep::Collector => self.validate_collector(),
+            WizardStep::Management => self.validate_management(),
+            WizardStep::Welcome | WizardStep::Review => Ok(()),
+        }
+    }
+
+    fn untested_servers(&self) -> Vec<String> {
+        self.servers
+            .iter()
+            .filter(|s| !matches!(s.status(), TestStatus::Passed(_)))
+            .map(|s| s.label())
+            .collect()
+    }
+
+    fn build_config(&self) -> Result<Config, String> {
+        self.validate_servers()?;
+        self.validate_collector()?;
+        self.validate_management()?;
+
         let mut config = Config::sample();
-        config.servers = vec![server];
+        config.servers = self
+            .servers
+            .iter()
+            .map(ServerForm::to_server_config)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        config.collector.realtime_interval_ms =
+            Self::parse_interval("Realtime interval", &self.realtime_interval_ms)?;
+        config.collector.disk_usage_interval_ms =
+            Self::parse_interval("Disk usage interval", &self.disk_usage_interval_ms)?;
+        config.collector.session_interval_ms =
+            Self::parse_interval("Session interval", &self.session_interval_ms)?;
+        config.collector.ip_check_interval_ms =
+            Self::parse_interval("IP check interval", &self.ip_check_interval_ms)?;
+
+        config.management.enabled = self.management_enabled;
+        if self.management_enabled {
+            config.management.port = self.management_port.trim().parse().unwrap();
+            config.management.bind_address = self.management_bind_address.trim().to_string();
+            config.management.api_token = Some(self.management_api_token.trim().to_string());
+        }
+
+        Ok(config)
+    }
+
+    fn save_config(&mut self) -> Result<PathBuf, String> {
+        let config = self.build_config()?;
+
+        if !self.skip_connection_test {
+            let untested = self.untested_servers();
+            if !untested.is_empty() {
+                return Err(format!(
+                    "Connection test has not passed for: {}",
+                    untested.join(", ")
+                ));
+            }
+        }
 
         // Determine config path
         let config_path = if let Ok(exe_path) = std::env::current_exe() {
@@ -143,18 +470,42 @@ impl eframe::App for WizardApp {
                         .color(egui::Color32::GRAY),
                 );
 
-                ui.add_space(30.0);
+                ui.add_space(20.0);
             });
 
             if self.state.config_saved {
                 self.show_success_page(ui);
-            } else {
-                match self.state.current_step {
-                    0 => self.show_welcome_page(ui),
-                    1 => self.show_server_config_page(ui),
-                    _ => {}
-                }
+                return;
+            }
+
+            if self.state.current_step == WizardStep::Welcome {
+                self.show_welcome_page(ui);
+                return;
+            }
+
+            self.show_step_tabs(ui);
+            ui.separator();
+            ui.add_space(10.0);
+
+            egui::ScrollArea::vertical()
+                .max_height(ui.available_height() - 60.0)
+                .show(ui, |ui| match self.state.current_step {
+                    WizardStep::Servers => self.show_servers_page(ui, ctx),
+                    WizardStep::Collector => self.show_collector_page(ui),
+                    WizardStep::Management => self.show_management_page(ui),
+                    WizardStep::Review => self.show_review_page(ui),
+                    WizardStep::Welcome => {}
+                });
+
+            ui.add_space(10.0);
+
+            // Error message
+            if let Some(error) = &self.state.error_message {
+                ui.colored_label(egui::Color32::RED, error);
+                ui.add_space(10.0);
             }
+
+            self.show_navigation(ui);
         });
     }
 }
@@ -177,7 +528,7 @@ impl WizardApp {
                 .button(egui::RichText::new("  Start Configuration  ").size(16.0))
                 .clicked()
             {
-                self.state.current_step = 1;
+                self.state.current_step = WizardStep::Servers;
             }
 
             ui.add_space(20.0);
@@ -188,7 +539,70 @@ impl WizardApp {
         });
     }
 
-    fn show_server_config_page(&mut self, ui: &mut egui::Ui) {
+    /// Page tabs; jumping forward is only allowed when the current page validates
+    fn show_step_tabs(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            for (i, step) in WizardStep::PAGES.iter().enumerate() {
+                let selected = self.state.current_step == *step;
+                let label = format!("{}. {}", i + 1, step.title());
+                if ui.selectable_label(selected, label).clicked() && !selected {
+                    self.go_to(*step);
+                }
+            }
+        });
+    }
+
+    fn go_to(&mut self, step: WizardStep) {
+        let current_pos = WizardStep::PAGES
+            .iter()
+            .position(|s| *s == self.state.current_step);
+        let target_pos = WizardStep::PAGES.iter().position(|s| *s == step);
+
+        if target_pos > current_pos {
+            if let Err(e) = self.state.validate_step(self.state.current_step) {
+                self.state.error_message = Some(e);
+                return;
+            }
+        }
+
+        self.state.current_step = step;
+        self.state.error_message = None;
+    }
+
+    fn show_navigation(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.add_space(ui.available_width() / 2.0 - 100.0);
+
+            if ui.button("  Back  ").clicked() {
+                self.state.current_step = self.state.current_step.prev();
+                self.state.error_message = None;
+            }
+
+            ui.add_space(20.0);
+
+            if self.state.current_step == WizardStep::Review {
+                if ui
+                    .button(egui::RichText::new("  Save & Start Agent  ").strong())
+                    .clicked()
+                {
+                    match self.state.save_config() {
+                        Ok(path) => {
+                            self.state.config_path = Some(path);
+                            self.state.config_saved = true;
+                            self.state.error_message = None;
+                        }
+                        Err(e) => {
+                            self.state.error_message = Some(e);
+                        }
+                    }
+                }
+            } else if ui.button("  Next  ").clicked() {
+                self.go_to(self.state.current_step.next());
+            }
+        });
+    }
+
+    fn show_servers_page(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
         ui.vertical_centered(|ui| {
             ui.label(
                 egui::RichText::new("Server Configuration")
@@ -196,122 +610,167 @@ impl WizardApp {
                     .strong(),
             );
             ui.add_space(10.0);
-            ui.label("Enter the NanoLink server details:");
+            ui.label("Add one or more NanoLink servers to report to:");
+        });
+
+        ui.add_space(10.0);
+
+        server_editor(
+            ui,
+            ctx,
+            &mut self.state.servers,
+            &mut self.state.selected_server,
+        );
+    }
+
+    fn show_collector_page(&mut self, ui: &mut egui::Ui) {
+        ui.vertical_centered(|ui| {
+            ui.label(
+                egui::RichText::new("Collector Intervals")
+                    .size(18.0)
+                    .strong(),
+            );
+            ui.add_space(10.0);
+            ui.label("How often metrics are collected (milliseconds):");
         });
 
         ui.add_space(20.0);
 
-        // Form
-        egui::Grid::new("server_config_grid")
+        egui::Grid::new("collector_config_grid")
             .num_columns(2)
             .spacing([20.0, 10.0])
             .show(ui, |ui| {
-                // Host
-                ui.label("Server Host:");
+                ui.label("Realtime (CPU/memory/IO):");
                 ui.add(
-                    egui::TextEdit::singleline(&mut self.state.host)
-                        .hint_text("e.g., 192.168.1.100 or server.example.com")
-                        .desired_width(300.0),
+                    egui::TextEdit::singleline(&mut self.state.realtime_interval_ms)
+                        .desired_width(120.0),
                 );
                 ui.end_row();
 
-                // Port
-                ui.label("Port:");
+                ui.label("Disk usage:");
                 ui.add(
-                    egui::TextEdit::singleline(&mut self.state.port)
-                        .hint_text("39100")
-                        .desired_width(100.0),
+                    egui::TextEdit::singleline(&mut self.state.disk_usage_interval_ms)
+                        .desired_width(120.0),
                 );
                 ui.end_row();
 
-                // Token
-                ui.label("Auth Token:");
-                ui.horizontal(|ui| {
-                    if self.state.show_token {
-                        ui.add(
-                            egui::TextEdit::singleline(&mut self.state.token)
-                                .hint_text("Enter your authentication token")
-                                .desired_width(260.0),
-                        );
-                    } else {
-                        ui.add(
-                            egui::TextEdit::singleline(&mut self.state.token)
-                                .hint_text("Enter your authentication token")
-                                .password(true)
-                                .desired_width(260.0),
-                        );
-                    }
-                    if ui
-                        .small_button(if self.state.show_token {
-                            "Hide"
-                        } else {
-                            "Show"
-                        })
-                        .clicked()
-                    {
-                        self.state.show_token = !self.state.show_token;
-                    }
-                });
-                ui.end_row();
-
-                // Permission
-                ui.label("Permission Level:");
-                egui::ComboBox::from_id_salt("permission_combo")
-                    .selected_text(PERMISSION_LEVELS[self.state.permission].0)
-                    .width(300.0)
-                    .show_ui(ui, |ui| {
-                        for (i, (label, _)) in PERMISSION_LEVELS.iter().enumerate() {
-                            ui.selectable_value(&mut self.state.permission, i, *label);
-                        }
-                    });
+                ui.label("User sessions:");
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.state.session_interval_ms)
+                        .desired_width(120.0),
+                );
                 ui.end_row();
 
-                // TLS Options
-                ui.label("TLS:");
-                ui.horizontal(|ui| {
-                    ui.checkbox(&mut self.state.tls_enabled, "Enable TLS");
-                    if self.state.tls_enabled {
-                        ui.checkbox(&mut self.state.tls_verify, "Verify Certificate");
-                    }
-                });
+                ui.label("IP address check:");
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.state.ip_check_interval_ms)
+                        .desired_width(120.0),
+                );
                 ui.end_row();
             });
+    }
+
+    fn show_management_page(&mut self, ui: &mut egui::Ui) {
+        ui.vertical_centered(|ui| {
+            ui.label(egui::RichText::new("Management API").size(18.0).strong());
+            ui.add_space(10.0);
+            ui.label("Local HTTP API used by servers to manage this agent:");
+        });
 
         ui.add_space(20.0);
 
-        // Error message
-        if let Some(error) = &self.state.error_message {
-            ui.colored_label(egui::Color32::RED, error);
-            ui.add_space(10.0);
-        }
+        ui.checkbox(&mut self.state.management_enabled, "Enable management API");
+
+        ui.add_space(10.0);
+
+        ui.add_enabled_ui(self.state.management_enabled, |ui| {
+            egui::Grid::new("management_config_grid")
+                .num_columns(2)
+                .spacing([20.0, 10.0])
+                .show(ui, |ui| {
+                    ui.label("Port:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.state.management_port)
+                            .desired_width(100.0),
+                    );
+                    ui.end_row();
+
+                    ui.label("Bind Address:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.state.management_bind_address)
+                            .hint_text("127.0.0.1")
+                            .desired_width(200.0),
+                    );
+                    ui.end_row();
+
+                    ui.label("API Token:");
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.state.management_api_token)
+                                .desired_width(260.0),
+                        );
+                        if ui.small_button("Generate").clicked() {
+                            self.state.management_api_token =
+                                crate::management::token::generate_secure_token(Some("mgmt"));
+                        }
+                    });
+                    ui.end_row();
+                });
+        });
+    }
 
-        // Buttons
-        ui.horizontal(|ui| {
-            ui.add_space(ui.available_width() / 2.0 - 100.0);
+    fn show_review_page(&mut self, ui: &mut egui::Ui) {
+        ui.vertical_centered(|ui| {
+            ui.label(egui::RichText::new("Review").size(18.0).strong());
+        });
 
-            if ui.button("  Back  ").clicked() {
-                self.state.current_step = 0;
-                self.state.error_message = None;
-            }
+        ui.add_space(15.0);
+
+        ui.label(egui::RichText::new("Servers").strong());
+        for server in &self.state.servers {
+            let (color, detail) = match server.status() {
+                TestStatus::Passed(d) => (egui::Color32::GREEN, format!("passed ({d})")),
+                TestStatus::Failed(e) => (egui::Color32::RED, format!("failed: {e}")),
+                TestStatus::Running => (egui::Color32::YELLOW, "running".to_string()),
+                TestStatus::Untested => (egui::Color32::GRAY, "not tested".to_string()),
+            };
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "  {} — {}",
+                    server.label(),
+                    PERMISSION_LEVELS[server.permission].0
+                ));
+                ui.colored_label(color, detail);
+            });
+        }
 
-            ui.add_space(20.0);
+        ui.add_space(10.0);
+        ui.label(egui::RichText::new("Collector").strong());
+        ui.label(format!(
+            "  realtime {}ms, disk {}ms, sessions {}ms, ip check {}ms",
+            self.state.realtime_interval_ms.trim(),
+            self.state.disk_usage_interval_ms.trim(),
+            self.state.session_interval_ms.trim(),
+            self.state.ip_check_interval_ms.trim()
+        ));
+
+        ui.add_space(10.0);
+        ui.label(egui::RichText::new("Management API").strong());
+        if self.state.management_enabled {
+            ui.label(format!(
+                "  enabled on {}:{}",
+                self.state.management_bind_address.trim(),
+                self.state.management_port.trim()
+            ));
+        } else {
+            ui.label("  disabled");
+        }
 
-            if ui
-                .button(egui::RichText::new("  Save & Start Agent  ").strong())
-                .clicked()
-            {
-                match self.state.save_config() {
-                    Ok(path) => {
-                        self.state.config_path = Some(path);
-                        self.state.config_saved = true;
-                        self.state.error_message = None;
-                    }
-                    Err(e) => {
-                        self.state.error_message = Some(e);
-                    }
-                }
-            }
-        });
+        ui.add_space(15.0);
+        ui.checkbox(
+            &mut self.state.skip_connection_test,
+            "Save without a passing connection test",
+        );
     }
 
     fn show_success_page(&mut self, ui: &mut egui::Ui) {
@@ -356,12 +815,169 @@ impl WizardApp {
     }
 }
 
+/// Server list with add/remove and the form for the selected server,
+/// shared by the wizard and the settings window
+pub(super) fn server_editor(
+    ui: &mut egui::Ui,
+    ctx: &egui::Context,
+    servers: &mut Vec<ServerForm>,
+    selected: &mut usize,
+) {
+    // Server list
+    let mut remove_index = None;
+    ui.horizontal_wrapped(|ui| {
+        for (i, server) in servers.iter().enumerate() {
+            let marker = match server.status() {
+                TestStatus::Passed(_) => "✔ ",
+                TestStatus::Failed(_) => "✘ ",
+                TestStatus::Running => "… ",
+                TestStatus::Untested => "",
+            };
+            let label = format!("{marker}{}", server.label());
+            if ui.selectable_label(*selected == i, label).clicked() {
+                *selected = i;
+            }
+        }
+
+        if ui.button("+ Add").clicked() {
+            servers.push(ServerForm::default());
+            *selected = servers.len() - 1;
+        }
+
+        if servers.len() > 1 && ui.button("− Remove").clicked() {
+            remove_index = Some(*selected);
+        }
+    });
+
+    if let Some(i) = remove_index {
+        servers.remove(i);
+        *selected = (*selected).min(servers.len() - 1);
+    }
+
+    ui.add_space(15.0);
+
+    let Some(server) = servers.get_mut(*selected) else {
+        return;
+    };
+
+    let mut changed = false;
+
+    // Form
+    egui::Grid::new("server_config_grid")
+        .num_columns(2)
+        .spacing([20.0, 10.0])
+        .show(ui, |ui| {
+            // Host
+            ui.label("Server Host:");
+            changed |= ui
+                .add(
+                    egui::TextEdit::singleline(&mut server.host)
+                        .hint_text("e.g., 192.168.1.100 or server.example.com")
+                        .desired_width(300.0),
+                )
+                .changed();
+            ui.end_row();
+
+            // Port
+            ui.label("Port:");
+            changed |= ui
+                .add(
+                    egui::TextEdit::singleline(&mut server.port)
+                        .hint_text("39100")
+                        .desired_width(100.0),
+                )
+                .changed();
+            ui.end_row();
+
+            // Token
+            ui.label("Auth Token:");
+            ui.horizontal(|ui| {
+                changed |= ui
+                    .add(
+                        egui::TextEdit::singleline(&mut server.token)
+                            .hint_text("Enter your authentication token")
+                            .password(!server.show_token)
+                            .desired_width(260.0),
+                    )
+                    .changed();
+                if ui
+                    .small_button(if server.show_token { "Hide" } else { "Show" })
+                    .clicked()
+                {
+                    server.show_token = !server.show_token;
+                }
+            });
+            ui.end_row();
+
+            // Permission
+            ui.label("Permission Level:");
+            let before = server.permission;
+            egui::ComboBox::from_id_salt("permission_combo")
+                .selected_text(PERMISSION_LEVELS[server.permission].0)
+                .width(300.0)
+                .show_ui(ui, |ui| {
+                    for (i, (label, _)) in PERMISSION_LEVELS.iter().enumerate() {
+                        ui.selectable_value(&mut server.permission, i, *label);
+                    }
+                });
+            changed |= before != server.permission;
+            ui.end_row();
+
+            // TLS Options
+            ui.label("TLS:");
+            ui.horizontal(|ui| {
+                changed |= ui.checkbox(&mut server.tls_enabled, "Enable TLS").changed();
+                if server.tls_enabled {
+                    changed |= ui
+                        .checkbox(&mut server.tls_verify, "Verify Certificate")
+                        .changed();
+                }
+            });
+            ui.end_row();
+        });
+
+    if changed {
+        server.reset_test();
+    }
+
+    ui.add_space(15.0);
+
+    // Connection test
+    ui.horizontal(|ui| {
+        let status = server.status();
+        let running = matches!(status, TestStatus::Running);
+
+        if ui
+            .add_enabled(!running, egui::Button::new("Test Connection"))
+            .clicked()
+        {
+            server.start_test(ctx);
+        }
+
+        match status {
+            TestStatus::Untested => {
+                ui.label(egui::RichText::new("Not tested").color(egui::Color32::GRAY));
+            }
+            TestStatus::Running => {
+                ui.spinner();
+                ui.label("Connecting and authenticating...");
+            }
+            TestStatus::Passed(detail) => {
+                ui.colored_label(egui::Color32::GREEN, format!("Authenticated ({detail})"));
+            }
+            TestStatus::Failed(e) => {
+                ui.colored_label(egui::Color32::RED, e);
+            }
+        }
+    });
+}
+
 /// Run the configuration wizard
 pub fn run_wizard() -> anyhow::Result<()> {
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size([500.0, 450.0])
-            .with_min_inner_size([400.0, 350.0])
+            .with_inner_size([640.0, 600.0])
+            .with_min_inner_size([520.0, 480.0])
             .with_title("NanoLink Agent - Configuration Wizard"),
         centered: true,
         ..Default::default()
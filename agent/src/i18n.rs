@@ -333,6 +333,8 @@ pub fn t(key: &str, lang: Lang) -> &'static str {
         ("metrics.ports", Lang::En) => "Listening Ports",
         ("metrics.press_q", Lang::Zh) => "按 q 返回, 方向键切换",
         ("metrics.press_q", Lang::En) => "Press q to return, arrow keys to navigate",
+        ("metrics.read_only_help", Lang::Zh) => "只读模式, 方向键切换",
+        ("metrics.read_only_help", Lang::En) => "Read-only view, arrow keys to navigate",
         ("metrics.refreshing", Lang::Zh) => "刷新中...",
         ("metrics.refreshing", Lang::En) => "Refreshing...",
         ("metrics.no_gpu", Lang::Zh) => "未检测到 GPU",
@@ -2,20 +2,27 @@ mod buffer;
 mod collector;
 mod config;
 mod connection;
+mod drift;
 mod executor;
 #[cfg(feature = "gui")]
 mod gui;
 mod i18n;
 mod management;
+mod notify;
 mod platform;
+#[cfg(all(unix, feature = "executors"))]
+mod privsep;
+mod profile;
+#[cfg(feature = "relay")]
+mod relay;
 mod security;
+mod silence;
+mod simulate;
+mod telemetry;
 mod tui;
 mod utils;
 
-#[allow(clippy::large_enum_variant)]
-pub mod proto {
-    include!(concat!(env!("OUT_DIR"), "/nanolink.rs"));
-}
+pub use nanolink_client::proto;
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
@@ -29,6 +36,9 @@ use crate::buffer::RingBuffer;
 use crate::collector::MetricsCollector;
 use crate::config::Config;
 use crate::connection::ConnectionManager;
+#[cfg(feature = "executors")]
+use crate::executor::MaintenanceExecutor;
+#[cfg(feature = "management")]
 use crate::management::ManagementServer;
 
 /// Default config file search paths (in order of priority)
@@ -63,6 +73,10 @@ struct Args {
     #[arg(long)]
     generate_config: bool,
 
+    /// Serve only the health check on this localhost port (update candidate)
+    #[arg(long, hide = true)]
+    candidate_port: Option<u16>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -91,6 +105,168 @@ enum Commands {
     },
     /// Show agent status and configuration
     Status,
+    /// Pair a desktop app by QR code: shows the code and waits for the app
+    #[cfg(feature = "management")]
+    Pair {
+        /// Permission level (0-3) granted to the app
+        #[arg(long, default_value = "1")]
+        permission: u8,
+        /// How long the code stays valid, e.g. 5m
+        #[arg(long, default_value = "5m")]
+        ttl: String,
+        /// Address the app connects to (default: this host's primary address)
+        #[arg(long)]
+        host: Option<String>,
+    },
+    /// Register with a server using a one-time enrollment code
+    Enroll {
+        /// Enrollment code issued by the server
+        #[arg(long)]
+        code: String,
+        /// Server address (supports host:port format, default port 39100)
+        #[arg(long)]
+        server: String,
+        /// Expected server certificate fingerprint (sha256:<hex>), for servers
+        /// without a certificate from a trusted CA
+        #[arg(long)]
+        fingerprint: Option<String>,
+        /// Install and start the agent service afterwards
+        #[arg(long)]
+        install_service: bool,
+    },
+    /// Save one full metrics collection to a file (for tickets and offline analysis)
+    Snapshot {
+        /// Output format
+        #[arg(long, value_parser = ["json", "csv"], default_value = "json")]
+        format: String,
+        /// Output file (default: nanolink-snapshot-<hostname>-<time>.<format>)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Export buffered metrics history from the running agent to CSV or Parquet
+    Export {
+        /// Output format (parquet needs a build with the `parquet` feature)
+        #[arg(long, value_parser = ["csv", "parquet"], default_value = "csv")]
+        format: String,
+        /// Columns to include, as comma-separated patterns (e.g. timestamp,cpu.*,disks.*.used)
+        #[arg(long)]
+        columns: Option<String>,
+        /// Only samples at or after this time (RFC 3339, e.g. 2026-01-31T08:00:00Z)
+        #[arg(long)]
+        from: Option<String>,
+        /// Only samples at or before this time (RFC 3339)
+        #[arg(long)]
+        to: Option<String>,
+        /// Gzip the output
+        #[arg(long)]
+        gzip: bool,
+        /// Output file (default: nanolink-history-<time>.<format>[.gz])
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Management API of a remote agent (host:port) instead of the local one
+        #[arg(long, requires = "token")]
+        remote: Option<String>,
+        /// Management API token of the remote agent
+        #[arg(long)]
+        token: Option<String>,
+    },
+    /// Send a metrics stream recorded with `agent.record_stream` to a server
+    Replay {
+        /// Recording file
+        file: PathBuf,
+        /// Speed relative to the recording, e.g. 10 to replay ten times faster
+        #[arg(long, default_value = "1.0")]
+        speed: f64,
+        /// Play the recording this many times
+        #[arg(long, default_value = "1")]
+        repeat: u32,
+        /// Send the recorded timestamps instead of moving them to the replay time
+        #[arg(long)]
+        keep_timestamps: bool,
+        /// Configured server to send to (host[:port], default: the first one)
+        #[arg(long)]
+        server: Option<String>,
+    },
+    /// Load test: connect many simulated agents with synthetic metrics to a server
+    Simulate {
+        /// Number of simulated agents
+        #[arg(long, default_value = "100")]
+        hosts: u32,
+        /// Configured server to connect to (host[:port], default: the first one)
+        #[arg(long)]
+        server: Option<String>,
+        /// Hostname prefix (sim-0001, ...); agent IDs are derived from the hostnames
+        #[arg(long, default_value = "sim")]
+        prefix: String,
+        /// Realtime metrics interval in milliseconds
+        #[arg(long, default_value = "1000")]
+        interval_ms: u64,
+        /// Reconnect each agent after about this long, e.g. 5m (default: stay connected)
+        #[arg(long)]
+        churn: Option<String>,
+        /// Spread the first connections over this long, e.g. 1m (default: all at once)
+        #[arg(long)]
+        ramp: Option<String>,
+        /// Stop after this long, e.g. 30m (default: until Ctrl+C)
+        #[arg(long)]
+        duration: Option<String>,
+        /// CPU cores per agent
+        #[arg(long, default_value = "8")]
+        cores: u32,
+        /// Disks per agent
+        #[arg(long, default_value = "2")]
+        disks: u32,
+        /// Network interfaces per agent
+        #[arg(long, default_value = "2")]
+        interfaces: u32,
+    },
+    /// Maintenance mode: silence alerts, optionally refuse server commands
+    Silence {
+        #[command(subcommand)]
+        action: SilenceAction,
+        /// Management API of a remote agent (host:port) instead of the local one
+        #[arg(long, global = true, requires = "token")]
+        remote: Option<String>,
+        /// Management API token of the remote agent
+        #[arg(long, global = true)]
+        token: Option<String>,
+    },
+    /// Realtime metrics viewer, for this host or a remote agent
+    Tui {
+        /// Kiosk mode: display only, q/Esc don't exit (Ctrl+C does)
+        #[arg(long)]
+        read_only: bool,
+        /// Management API of a remote agent (host:port) to display instead of this host
+        #[arg(long, requires = "token")]
+        remote: Option<String>,
+        /// Management API token of the remote agent
+        #[arg(long)]
+        token: Option<String>,
+    },
+    /// Open the settings window (connection status, servers and tokens)
+    #[cfg(feature = "gui")]
+    Gui,
+    /// Update from a signed offline bundle (for networks without internet access)
+    Update {
+        /// Bundle file (.tar or .tar.gz with manifest.json and manifest.sig)
+        #[arg(long)]
+        bundle: PathBuf,
+        /// Install the agent binary even if it is not newer
+        #[arg(long)]
+        force: bool,
+    },
+    /// Generate an AppArmor profile or SELinux policy module for the enabled features
+    SecurityProfile {
+        /// Policy format
+        #[arg(long, value_parser = ["apparmor", "selinux"], default_value = "apparmor")]
+        format: String,
+        /// Directory to write the policy files to
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+    },
+    /// Privileged helper for an agent running unprivileged (privsep.enabled); run as root
+    #[cfg(all(unix, feature = "executors"))]
+    Helper,
 }
 
 /// Windows Service actions
@@ -166,6 +342,59 @@ enum ServerAction {
         #[arg(long)]
         tls_verify: Option<bool>,
     },
+    /// Pin (or re-pin) the TLS certificate of a configured server
+    Pin {
+        /// Server hostname (supports host:port format)
+        #[arg(long)]
+        host: String,
+        /// Server port
+        #[arg(long, default_value = "39100")]
+        port: u16,
+        /// Expected fingerprint (sha256:<hex> or openssl colon format); fetched from the server if omitted
+        #[arg(long, conflicts_with = "clear")]
+        fingerprint: Option<String>,
+        /// Remove the pin; the next successful connection pins again (trust on first use)
+        #[arg(long)]
+        clear: bool,
+    },
+    /// Test connection and authentication (all configured servers if host not provided)
+    Test {
+        /// Server hostname (supports host:port format); need not be configured if --token is given
+        #[arg(long)]
+        host: Option<String>,
+        /// Server port
+        #[arg(long, default_value = "39100")]
+        port: u16,
+        /// Authentication token (overrides the configured token)
+        #[arg(long)]
+        token: Option<String>,
+        /// Enable TLS
+        #[arg(long)]
+        tls_enabled: Option<bool>,
+        /// Enable TLS certificate verification
+        #[arg(long)]
+        tls_verify: Option<bool>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SilenceAction {
+    /// Suppress alerts for a while
+    Start {
+        /// How long, e.g. 30m, 2h, 1d, 1h30m
+        #[arg(long, default_value = "1h")]
+        duration: String,
+        /// Shown to servers and in notifications
+        #[arg(long, default_value = "")]
+        reason: String,
+        /// Also refuse commands from servers that change the host
+        #[arg(long)]
+        reject_commands: bool,
+    },
+    /// End the silence early
+    Stop,
+    /// Show whether the host is silenced
+    Status,
 }
 
 /// Permission level options for interactive selection
@@ -291,6 +520,12 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
+    // Started by a blue/green update to verify the new binary
+    if let Some(port) = args.candidate_port {
+        let rt = tokio::runtime::Runtime::new()?;
+        return rt.block_on(run_candidate(get_config_path(&args), port));
+    }
+
     // Handle subcommands - need async runtime
     if let Some(command) = &args.command {
         let rt = tokio::runtime::Runtime::new()?;
@@ -390,6 +625,315 @@ async fn handle_command(command: &Commands, args: &Args) -> Result<()> {
             return Ok(());
         }
 
+        #[cfg(feature = "management")]
+        Commands::Pair {
+            permission,
+            ttl,
+            host,
+        } => {
+            return handle_pair(args, *permission, ttl, host.as_deref()).await;
+        }
+
+        Commands::Enroll {
+            code,
+            server,
+            fingerprint,
+            install_service,
+        } => {
+            return handle_enroll(args, code, server, fingerprint.as_deref(), *install_service)
+                .await;
+        }
+
+        Commands::Update { bundle, force } => {
+            use crate::executor::UpdateExecutor;
+
+            let config = match get_config_path(args) {
+                Some(path) => {
+                    let config = Config::load(&path)?;
+                    crate::executor::set_config_path(path);
+                    config
+                }
+                None => Config::sample(),
+            };
+            let result = UpdateExecutor::new(config.update)
+                .apply_bundle(bundle, *force)
+                .await;
+            println!("{}", result.output.trim_end());
+            if !result.success {
+                anyhow::bail!(result.error);
+            }
+            return Ok(());
+        }
+
+        #[cfg(all(unix, feature = "executors"))]
+        Commands::Helper => {
+            let path = get_config_path(args)
+                .ok_or_else(|| anyhow::anyhow!("No configuration file found"))?;
+            let config = Config::load(&path)?;
+            if !nix::unistd::geteuid().is_root() {
+                anyhow::bail!("The privileged helper has to run as root");
+            }
+            return privsep::helper::run(std::sync::Arc::new(config)).await;
+        }
+
+        Commands::SecurityProfile { format, output } => {
+            use security::confinement::{Format, generate};
+
+            let (config, config_path) = match get_config_path(args) {
+                Some(path) => (Config::load(&path)?, std::fs::canonicalize(&path)?),
+                None => (
+                    Config::sample(),
+                    PathBuf::from("/etc/nanolink/nanolink.yaml"),
+                ),
+            };
+            let binary = std::env::current_exe()?;
+            let format = match format.as_str() {
+                "selinux" => Format::SeLinux,
+                _ => Format::AppArmor,
+            };
+            std::fs::create_dir_all(output)?;
+            for file in generate(&config, &config_path, &binary, format) {
+                let path = output.join(&file.name);
+                std::fs::write(&path, file.contents)?;
+                println!("Wrote {}", path.display());
+            }
+            match format {
+                Format::AppArmor => println!(
+                    "Install with: cp nanolink-agent /etc/apparmor.d/ && apparmor_parser -r /etc/apparmor.d/nanolink-agent"
+                ),
+                Format::SeLinux => println!(
+                    "Build with: make -f /usr/share/selinux/devel/Makefile nanolink_agent.pp && semodule -i nanolink_agent.pp"
+                ),
+            }
+            return Ok(());
+        }
+
+        Commands::Snapshot { format, output } => {
+            let config = match get_config_path(args) {
+                Some(path) => Config::load(&path)?,
+                None => Config::sample(),
+            };
+            let metrics =
+                tokio::task::spawn_blocking(move || collector::collect_once(Arc::new(config)))
+                    .await?;
+            let contents = match format.as_str() {
+                "csv" => utils::export::to_csv(&metrics)?,
+                _ => utils::export::to_json(&metrics)?,
+            };
+            let output = output.clone().unwrap_or_else(|| {
+                PathBuf::from(format!(
+                    "nanolink-snapshot-{}-{}.{}",
+                    metrics.hostname,
+                    chrono::Local::now().format("%Y%m%d-%H%M%S"),
+                    format
+                ))
+            });
+            std::fs::write(&output, contents)?;
+            println!("Snapshot saved to {}", output.display());
+            return Ok(());
+        }
+
+        Commands::Export {
+            format,
+            columns,
+            from,
+            to,
+            gzip,
+            output,
+            remote,
+            token,
+        } => {
+            use crate::management::client::ApiClient;
+            use crate::utils::http::encode_query;
+
+            let client = match (remote, token) {
+                (Some(remote), Some(token)) => ApiClient::new(remote, token),
+                _ => {
+                    let path = get_config_path(args)
+                        .ok_or_else(|| anyhow::anyhow!("No configuration file found"))?;
+                    ApiClient::local(&Config::load(&path)?.management)
+                }
+            }
+            .map_err(|e| anyhow::anyhow!(e))?
+            .with_timeout(std::time::Duration::from_secs(60));
+
+            let mut query = format!("format={format}&gzip={gzip}");
+            if let Some(columns) = columns {
+                query.push_str(&format!("&columns={}", encode_query(columns)));
+            }
+            for (name, time) in [("from", from), ("to", to)] {
+                if let Some(time) = time {
+                    let millis = chrono::DateTime::parse_from_rfc3339(time)
+                        .map_err(|e| anyhow::anyhow!("Invalid --{name} time {time}: {e}"))?
+                        .timestamp_millis();
+                    query.push_str(&format!("&{name}={}", millis.max(0)));
+                }
+            }
+
+            let data = tokio::task::spawn_blocking(move || {
+                client.download(&format!("/api/export?{query}"))
+            })
+            .await?
+            .map_err(|e| anyhow::anyhow!(e))?;
+            let output = output.clone().unwrap_or_else(|| {
+                PathBuf::from(format!(
+                    "nanolink-history-{}.{}{}",
+                    chrono::Local::now().format("%Y%m%d-%H%M%S"),
+                    format,
+                    if *gzip { ".gz" } else { "" }
+                ))
+            });
+            std::fs::write(&output, data)?;
+            println!("History saved to {}", output.display());
+            return Ok(());
+        }
+
+        Commands::Replay {
+            file,
+            speed,
+            repeat,
+            keep_timestamps,
+            server,
+        } => {
+            return handle_replay(
+                args,
+                file,
+                *speed,
+                *repeat,
+                *keep_timestamps,
+                server.as_deref(),
+            )
+            .await;
+        }
+
+        Commands::Simulate {
+            hosts,
+            server,
+            prefix,
+            interval_ms,
+            churn,
+            ramp,
+            duration,
+            cores,
+            disks,
+            interfaces,
+        } => {
+            let parse = |value: &Option<String>| {
+                value
+                    .as_deref()
+                    .map(crate::silence::parse_duration)
+                    .transpose()
+                    .map_err(|e| anyhow::anyhow!(e))
+            };
+            let options = simulate::SimulateOptions {
+                hosts: *hosts,
+                prefix: prefix.clone(),
+                interval: std::time::Duration::from_millis((*interval_ms).max(100)),
+                churn: parse(churn)?.unwrap_or_default(),
+                ramp: parse(ramp)?.unwrap_or_default(),
+                cores: *cores,
+                disks: *disks,
+                interfaces: *interfaces,
+            };
+            return handle_simulate(args, server.as_deref(), options, parse(duration)?).await;
+        }
+
+        Commands::Silence {
+            action,
+            remote,
+            token,
+        } => {
+            use crate::management::SilenceRequest;
+            use crate::management::client::ApiClient;
+            use crate::silence::Silence;
+
+            let client = match (remote, token) {
+                (Some(remote), Some(token)) => ApiClient::new(remote, token),
+                _ => {
+                    let path = get_config_path(args)
+                        .ok_or_else(|| anyhow::anyhow!("No configuration file found"))?;
+                    ApiClient::local(&Config::load(&path)?.management)
+                }
+            }
+            .map_err(|e| anyhow::anyhow!(e))?;
+
+            let (silence, ended) = match action {
+                SilenceAction::Start {
+                    duration,
+                    reason,
+                    reject_commands,
+                } => {
+                    let request = SilenceRequest {
+                        duration: duration.clone(),
+                        reason: reason.clone(),
+                        reject_commands: *reject_commands,
+                    };
+                    let silence = tokio::task::spawn_blocking(move || {
+                        client.post::<_, Silence>("/api/silence/start", &request)
+                    })
+                    .await?;
+                    (Some(silence.map_err(|e| anyhow::anyhow!(e))?), false)
+                }
+                SilenceAction::Stop => {
+                    let ended = tokio::task::spawn_blocking(move || {
+                        client.post::<_, Option<Silence>>("/api/silence/stop", &())
+                    })
+                    .await?;
+                    (ended.map_err(|e| anyhow::anyhow!(e))?, true)
+                }
+                SilenceAction::Status => {
+                    let silence = tokio::task::spawn_blocking(move || {
+                        client.get::<Option<Silence>>("/api/silence")
+                    })
+                    .await?;
+                    (silence.map_err(|e| anyhow::anyhow!(e))?, false)
+                }
+            };
+            match (silence, ended) {
+                (Some(silence), true) => println!("Silence ended ({})", silence.reason),
+                (None, true) => println!("Host was not silenced"),
+                (Some(silence), false) => {
+                    println!(
+                        "Silenced until {} by {}",
+                        silence.until.with_timezone(&chrono::Local).to_rfc3339(),
+                        silence.started_by
+                    );
+                    if !silence.reason.is_empty() {
+                        println!("  Reason: {}", silence.reason);
+                    }
+                    if silence.reject_commands {
+                        println!("  Commands that change the host are refused");
+                    }
+                }
+                (None, false) => println!("Host is not silenced"),
+            }
+            return Ok(());
+        }
+
+        Commands::Tui {
+            read_only,
+            remote,
+            token,
+        } => {
+            let lang = get_config_path(args)
+                .and_then(|path| Config::load(&path).ok())
+                .and_then(|config| config.agent.language.as_deref().and_then(Lang::from_str))
+                .unwrap_or_else(detect_language);
+            let source = match (remote, token) {
+                (Some(remote), Some(token)) => tui::Source::Remote(
+                    management::client::ApiClient::new(remote, token)
+                        .map_err(|e| anyhow::anyhow!(e))?,
+                ),
+                _ => tui::Source::Local(Box::new(tui::LocalSource::new())),
+            };
+            return tui::run_viewer(lang, source, *read_only);
+        }
+
+        #[cfg(feature = "gui")]
+        Commands::Gui => {
+            return gui::run_settings(get_config_path(args));
+        }
+
         Commands::Status => {
             println!("NanoLink Agent v{}", env!("CARGO_PKG_VERSION"));
             println!();
@@ -400,6 +944,16 @@ async fn handle_command(command: &Commands, args: &Args) -> Result<()> {
 
                     match Config::load(&config_path) {
                         Ok(config) => {
+                            if !config.sources().is_empty() {
+                                println!("Merged from (later files win):");
+                                for file in config.sources() {
+                                    println!("  {}", file.display());
+                                }
+                            }
+                            println!(
+                                "Agent ID: {}",
+                                config.agent.agent_id.as_deref().unwrap_or("(not set)")
+                            );
                             println!();
                             println!("Configured servers:");
                             if config.servers.is_empty() {
@@ -450,6 +1004,34 @@ async fn handle_command(command: &Commands, args: &Args) -> Result<()> {
             return Ok(());
         }
 
+        Commands::Server {
+            action:
+                ServerAction::Test {
+                    host,
+                    port,
+                    token,
+                    tls_enabled,
+                    tls_verify,
+                },
+        } => {
+            // Ad-hoc tests (--host + --token) work without a config file
+            let config = match get_config_path(args) {
+                Some(path) => Some(Config::load(&path)?),
+                None => None,
+            };
+            connection::tls::init(config.as_ref().is_some_and(|c| c.security.fips_enabled()));
+
+            handle_server_test(
+                config.as_ref(),
+                host.clone(),
+                *port,
+                token.clone(),
+                *tls_enabled,
+                *tls_verify,
+            )
+            .await?;
+        }
+
         Commands::Server { action } => {
             let config_path = match get_config_path(args) {
                 Some(path) => path,
@@ -460,6 +1042,7 @@ async fn handle_command(command: &Commands, args: &Args) -> Result<()> {
             };
 
             let mut config = Config::load(&config_path)?;
+            connection::tls::init(config.security.fips_enabled());
 
             match action {
                 ServerAction::Add {
@@ -497,6 +1080,9 @@ async fn handle_command(command: &Commands, args: &Args) -> Result<()> {
                             "     TLS: {}, Verify: {}",
                             server.tls_enabled, server.tls_verify
                         );
+                        if let Some(pin) = &server.tls_pin {
+                            println!("     Pinned certificate: {pin}");
+                        }
                     }
                 }
                 ServerAction::Update {
@@ -518,6 +1104,23 @@ async fn handle_command(command: &Commands, args: &Args) -> Result<()> {
                         *tls_verify,
                     )?;
                 }
+                ServerAction::Pin {
+                    host,
+                    port,
+                    fingerprint,
+                    clear,
+                } => {
+                    handle_server_pin(
+                        &mut config,
+                        &config_path,
+                        host,
+                        *port,
+                        fingerprint.as_deref(),
+                        *clear,
+                    )
+                    .await?;
+                }
+                ServerAction::Test { .. } => unreachable!("handled above"),
             }
         }
     }
@@ -618,6 +1221,11 @@ fn handle_server_add(
         permission: final_permission,
         tls_enabled: final_tls_enabled,
         tls_verify: final_tls_verify,
+        tls_pin: None,
+        srv: None,
+        role: None,
+        bandwidth_cap: None,
+        reporting_schedule: None,
     });
 
     save_config(config, config_path)?;
@@ -815,24 +1423,567 @@ fn handle_server_update(
     Ok(())
 }
 
-fn permission_name(level: u8) -> &'static str {
-    match level {
-        0 => "READ_ONLY",
-        1 => "BASIC_WRITE",
-        2 => "SERVICE_CONTROL",
-        3 => "SYSTEM_ADMIN",
-        _ => "UNKNOWN",
+/// Handle server test command: one-shot connect + authenticate
+async fn handle_server_test(
+    config: Option<&Config>,
+    host: Option<String>,
+    default_port: u16,
+    token: Option<String>,
+    tls_enabled: Option<bool>,
+    tls_verify: Option<bool>,
+) -> Result<()> {
+    use crate::config::ServerConfig;
+    use crate::connection::grpc::GrpcClient;
+
+    let configured = config.map(|c| c.servers.as_slice()).unwrap_or_default();
+
+    let mut targets: Vec<ServerConfig> = match &host {
+        Some(h) => {
+            let (final_host, final_port) = parse_host_port(h, default_port);
+            let existing = configured
+                .iter()
+                .find(|s| s.host == final_host && s.port == final_port)
+                .cloned();
+
+            match (existing, token) {
+                (Some(mut s), t) => {
+                    if let Some(t) = t {
+                        s.token = t;
+                    }
+                    vec![s]
+                }
+                (None, Some(t)) => vec![ServerConfig {
+                    host: final_host,
+                    port: final_port,
+                    token: t,
+                    management_token: None,
+                    permission: 0,
+                    tls_enabled: false,
+                    tls_verify: true,
+                    tls_pin: None,
+                    srv: None,
+                    role: None,
+                    bandwidth_cap: None,
+                    reporting_schedule: None,
+                }],
+                (None, None) => anyhow::bail!(
+                    "Server {final_host}:{final_port} is not configured. Pass --token to test it ad hoc."
+                ),
+            }
+        }
+        None => {
+            if configured.is_empty() {
+                anyhow::bail!("No servers configured. Use --host and --token to test a server.");
+            }
+            configured.to_vec()
+        }
+    };
+
+    for s in &mut targets {
+        if let Some(te) = tls_enabled {
+            s.tls_enabled = te;
+        }
+        if let Some(tv) = tls_verify {
+            s.tls_verify = tv;
+        }
     }
+
+    let mut failed = 0;
+    for server in &targets {
+        println!("Testing {}:{}...", server.host, server.port);
+
+        match GrpcClient::probe_server(server).await {
+            Ok(probe) => {
+                println!("  ✓ Connected: {}", probe.url);
+                let verification = if probe.tls_verify {
+                    "certificate verified"
+                } else {
+                    "verification disabled"
+                };
+                match &probe.tls_session {
+                    Some((version, cipher_suite)) => {
+                        println!("    TLS: {version}, {cipher_suite} ({verification})")
+                    }
+                    None if probe.tls_enabled => println!("    TLS: enabled ({verification})"),
+                    None => println!("    TLS: disabled"),
+                }
+                if let Some(fp) = &probe.tls_fingerprint {
+                    let pin_state = match &server.tls_pin {
+                        Some(_) => "matches pin",
+                        None => "not pinned yet",
+                    };
+                    println!("    Certificate: {fp} ({pin_state})");
+                }
+                println!(
+                    "    Permission: {} ({}), configured: {} ({})",
+                    probe.granted_permission,
+                    permission_name(probe.granted_permission as u8),
+                    server.permission,
+                    permission_name(server.permission)
+                );
+                println!(
+                    "    RTT: connect {:.1}ms, authenticate {:.1}ms",
+                    probe.connect_rtt.as_secs_f64() * 1000.0,
+                    probe.auth_rtt.as_secs_f64() * 1000.0
+                );
+            }
+            Err(e) => {
+                failed += 1;
+                println!("  ✗ Failed: {e:#}");
+            }
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!(
+            "{failed}/{} server(s) failed the connection test",
+            targets.len()
+        );
+    }
+
+    Ok(())
 }
 
-fn save_config(config: &Config, path: &Path) -> Result<()> {
-    let content = if path.extension().is_some_and(|e| e == "toml") {
-        toml::to_string_pretty(config)?
+/// Handle server pin command
+async fn handle_server_pin(
+    config: &mut Config,
+    config_path: &Path,
+    host: &str,
+    default_port: u16,
+    fingerprint: Option<&str>,
+    clear: bool,
+) -> Result<()> {
+    use crate::connection::grpc::GrpcClient;
+    use crate::connection::tls;
+
+    let (final_host, final_port) = parse_host_port(host, default_port);
+
+    let server = config
+        .servers
+        .iter_mut()
+        .find(|s| s.host == final_host && s.port == final_port)
+        .ok_or_else(|| anyhow::anyhow!("Server {final_host}:{final_port} not found."))?;
+
+    if !server.tls_enabled {
+        anyhow::bail!("TLS is not enabled for {final_host}:{final_port}; nothing to pin.");
+    }
+
+    let previous = server.tls_pin.take();
+
+    if clear {
+        save_config(config, config_path)?;
+        println!("Certificate pin for {final_host}:{final_port} cleared.");
+        println!("The next successful connection will pin the server certificate again.");
+        return Ok(());
+    }
+
+    let new_pin = match fingerprint {
+        Some(fp) => tls::normalize_fingerprint(fp)
+            .ok_or_else(|| anyhow::anyhow!("Invalid SHA-256 fingerprint: {fp}"))?,
+        None => {
+            // Fetch the current certificate; authentication must still succeed
+            println!("Connecting to {final_host}:{final_port} to fetch its certificate...");
+            GrpcClient::probe_server(server)
+                .await?
+                .tls_fingerprint
+                .ok_or_else(|| anyhow::anyhow!("Server did not present a certificate"))?
+        }
+    };
+
+    if let Some(old) = &previous {
+        println!("  Previous: {old}");
+    }
+    println!("  Pinned:   {new_pin}");
+
+    server.tls_pin = Some(new_pin);
+    save_config(config, config_path)?;
+    println!("Server {final_host}:{final_port} pinned successfully.");
+    println!("Restart the agent to apply changes.");
+    Ok(())
+}
+
+/// A configured server by host[:port], or the first one
+fn find_server(config: &Config, server: Option<&str>) -> Result<crate::config::ServerConfig> {
+    let server = match server {
+        Some(server) => {
+            let (host, port) = parse_host_port(server, crate::config::DEFAULT_GRPC_PORT);
+            config
+                .servers
+                .iter()
+                .find(|s| s.host == host && s.port == port)
+                .ok_or_else(|| anyhow::anyhow!("Server {host}:{port} not found."))?
+        }
+        None => config
+            .servers
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("No servers configured"))?,
+    };
+    Ok(server.clone())
+}
+
+/// Connect simulated agents to a configured server and print fleet counters
+/// until `duration` runs out or Ctrl+C
+async fn handle_simulate(
+    args: &Args,
+    server: Option<&str>,
+    options: simulate::SimulateOptions,
+    duration: Option<std::time::Duration>,
+) -> Result<()> {
+    let config_path =
+        get_config_path(args).ok_or_else(|| anyhow::anyhow!("No configuration file found"))?;
+    let config = Arc::new(Config::load(&config_path)?);
+    connection::tls::init(config.security.fips_enabled());
+    let target = find_server(&config, server)?;
+
+    println!(
+        "Simulating {} agents against {} (Ctrl+C to stop)",
+        options.hosts,
+        target.address()
+    );
+    let options = Arc::new(options);
+    let stats = Arc::new(simulate::SimulateStats::default());
+    let mut hosts = tokio::task::JoinSet::new();
+    for index in 1..=options.hosts {
+        hosts.spawn(simulate::run_host(
+            index,
+            target.clone(),
+            config.clone(),
+            options.clone(),
+            stats.clone(),
+        ));
+    }
+
+    let report = async {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_secs(10));
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            println!("{}", stats.summary(options.hosts));
+        }
+    };
+    let limit = async {
+        match duration {
+            Some(duration) => tokio::time::sleep(duration).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        _ = report => {}
+        _ = limit => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    hosts.abort_all();
+    println!("Finished: {}", stats.summary(options.hosts));
+    Ok(())
+}
+
+/// Send a stream recording to a configured server, authenticating as the
+/// recorded agent
+async fn handle_replay(
+    args: &Args,
+    file: &Path,
+    speed: f64,
+    repeat: u32,
+    keep_timestamps: bool,
+    server: Option<&str>,
+) -> Result<()> {
+    use crate::connection::grpc::GrpcClient;
+    use crate::connection::{replay, tls};
+    use crate::proto::metrics_stream_request::Request;
+
+    if !speed.is_finite() || speed <= 0.0 {
+        anyhow::bail!("--speed must be greater than 0");
+    }
+    let config_path =
+        get_config_path(args).ok_or_else(|| anyhow::anyhow!("No configuration file found"))?;
+    let mut config = Config::load(&config_path)?;
+    tls::init(config.security.fips_enabled());
+    utils::at_rest::init(&config.security.at_rest).map_err(|e| anyhow::anyhow!(e))?;
+
+    let target = find_server(&config, server)?;
+
+    let recording = replay::read(file)?;
+    let recorded_agent = recording
+        .iter()
+        .find_map(|r| match r.request.as_ref()?.request {
+            Some(Request::AgentInit(ref init)) => Some(init.clone()),
+            _ => None,
+        });
+    if let Some(init) = recorded_agent {
+        config.agent.agent_id = Some(init.agent_id);
+        config.agent.hostname = Some(init.hostname);
+    }
+    let pass = recording
+        .last()
+        .map(|r| replay::due(r.offset_ms, speed))
+        .unwrap_or_default();
+    println!(
+        "Replaying {} messages ({}s per pass) to {}",
+        recording.len(),
+        pass.as_secs(),
+        target.address()
+    );
+
+    let mut client = GrpcClient::connect(&target, &Arc::new(config)).await?;
+    let auth = client.authenticate().await?;
+    if !auth.success {
+        anyhow::bail!("Authentication failed: {}", auth.error_message);
+    }
+    let sent = client
+        .replay(Arc::new(recording), speed, repeat, !keep_timestamps)
+        .await?;
+    println!("Replay finished, {sent} messages sent");
+    Ok(())
+}
+
+/// Exchange an enrollment code for a token and write the server to the config
+/// (created if missing)
+async fn handle_enroll(
+    args: &Args,
+    code: &str,
+    server: &str,
+    fingerprint: Option<&str>,
+    install_service: bool,
+) -> Result<()> {
+    use crate::config::ServerConfig;
+    use crate::connection::grpc::GrpcClient;
+    use crate::connection::tls;
+
+    let (host, port) = parse_host_port(server, 39100);
+    let config_path = get_config_path(args).unwrap_or_else(default_config_path);
+    let mut config = if config_path.exists() {
+        Config::load(&config_path)?
     } else {
-        serde_yaml::to_string(config)?
+        let mut config = Config::sample();
+        config.servers.clear();
+        config.agent.agent_id = Some(crate::utils::machine_id::stable_agent_id().0);
+        config
+    };
+    tls::init(config.security.fips_enabled());
+
+    // Without a pin the certificate must chain to a trusted CA
+    let pin = fingerprint
+        .map(|fp| {
+            tls::normalize_fingerprint(fp)
+                .ok_or_else(|| anyhow::anyhow!("Invalid SHA-256 fingerprint: {fp}"))
+        })
+        .transpose()?;
+    let mut server_config = ServerConfig {
+        host: host.clone(),
+        port,
+        token: String::new(),
+        management_token: None,
+        permission: 0,
+        tls_enabled: true,
+        tls_verify: pin.is_none(),
+        tls_pin: pin,
+        srv: None,
+        role: None,
+        bandwidth_cap: None,
+        reporting_schedule: None,
     };
 
-    std::fs::write(path, content)?;
+    println!("Enrolling with {host}:{port}...");
+    let agent_id = config.agent.agent_id.clone().unwrap_or_default();
+    let (response, tls_fingerprint) = GrpcClient::enroll(&server_config, code, &agent_id).await?;
+    if !response.success {
+        anyhow::bail!("Enrollment rejected: {}", response.error_message);
+    }
+    server_config.token = response.token;
+    server_config.permission = response.permission_level.clamp(0, 3) as u8;
+    server_config.tls_pin = Some(tls_fingerprint);
+    let permission = server_config.permission;
+
+    match config
+        .servers
+        .iter_mut()
+        .find(|s| s.host == host && s.port == port)
+    {
+        Some(existing) => *existing = server_config,
+        None => config.servers.push(server_config),
+    }
+    // The profile is served by the first server
+    let serves_profile = config.servers[0].host == host && config.servers[0].port == port;
+    if !response.profile_public_keys.is_empty() && serves_profile {
+        config.profile.enabled = true;
+        config.profile.public_keys = response.profile_public_keys;
+    }
+
+    if let Some(dir) = config_path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    // The token grants access to the server
+    config.save_private(&config_path)?;
+    println!(
+        "Enrolled with {host}:{port} ({})",
+        permission_name(permission)
+    );
+    println!("Configuration written to {}", config_path.display());
+
+    if install_service {
+        // The service may start in another directory
+        let config_path = std::fs::canonicalize(&config_path)?;
+        install_agent_service(&config_path)
+            .and_then(|_| restart_agent_service())
+            .map_err(|e| anyhow::anyhow!("Failed to install the service: {e}"))?;
+        println!("Agent service installed and started.");
+    } else {
+        println!("Run the agent: nanolink-agent -c {}", config_path.display());
+    }
+    Ok(())
+}
+
+/// Offer a one-time pairing code to a desktop app and wait until it is used
+#[cfg(feature = "management")]
+async fn handle_pair(args: &Args, permission: u8, ttl: &str, host: Option<&str>) -> Result<()> {
+    use crate::management::pairing::{self, Pending};
+    use rustls::pki_types::{CertificateDer, pem::PemObject};
+
+    if permission > 3 {
+        anyhow::bail!("Permission level must be 0-3");
+    }
+    let ttl = crate::silence::parse_duration(ttl).map_err(|e| anyhow::anyhow!(e))?;
+    let config_path =
+        get_config_path(args).ok_or_else(|| anyhow::anyhow!("No configuration file found"))?;
+    let config = Config::load(&config_path)?;
+    let management = &config.management;
+    if !management.enabled {
+        anyhow::bail!("The management API is disabled; the app pairs through it");
+    }
+
+    let host = match (host, management.bind_address.parse::<std::net::IpAddr>()) {
+        (Some(host), _) => host.to_string(),
+        (None, Ok(ip)) if ip.is_loopback() => anyhow::bail!(
+            "The management API only listens on {ip}. Set management.bind_address so the app \
+             can reach it, or pass --host for a tunnel."
+        ),
+        (None, Ok(ip)) if ip.is_unspecified() => pairing::primary_address()
+            .ok_or_else(|| anyhow::anyhow!("Could not determine this host's address; pass --host"))?
+            .to_string(),
+        (None, _) => management.bind_address.clone(),
+    };
+    let tls_fingerprint = if management.tls_enabled {
+        let path = management
+            .tls_cert
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("management.tls_cert is not set"))?;
+        let cert = CertificateDer::from_pem_file(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {path}: {e}"))?;
+        Some(connection::tls::fingerprint(cert.as_ref()))
+    } else {
+        None
+    };
+
+    let secret = pairing::random_secret(10).map_err(|e| anyhow::anyhow!(e))?;
+    let pending = Pending {
+        secret_sha256: pairing::sha256_hex(&secret),
+        permission,
+        expires_at: chrono::Utc::now() + ttl,
+        failed_attempts: 0,
+        claimed_by: None,
+    };
+    pairing::store(&pending)?;
+
+    let uri = pairing::pairing_uri(
+        &host,
+        management.port,
+        &secret,
+        &config.get_hostname(),
+        tls_fingerprint.as_deref(),
+    );
+    println!(
+        "{}",
+        pairing::render_qr(&uri).map_err(|e| anyhow::anyhow!(e))?
+    );
+    println!("Scan with the NanoLink desktop app, or add the agent there manually:");
+    println!("  Address: {host}:{}", management.port);
+    println!("  Code:    {secret}");
+    if tls_fingerprint.is_none() {
+        println!("  Note: the management API doesn't use TLS, the app's token travels unencrypted");
+    }
+    println!(
+        "Waiting for the app (code valid until {})...",
+        pending
+            .expires_at
+            .with_timezone(&chrono::Local)
+            .format("%H:%M:%S")
+    );
+
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(std::time::Duration::from_secs(1)) => {}
+            _ = tokio::signal::ctrl_c() => {
+                pairing::clear();
+                anyhow::bail!("Pairing cancelled");
+            }
+        }
+        match pairing::load() {
+            Some(p) if p.secret_sha256 != pending.secret_sha256 => {
+                anyhow::bail!("Another pairing was started")
+            }
+            Some(Pending {
+                claimed_by: Some(app),
+                ..
+            }) => {
+                pairing::clear();
+                println!("Paired with {app} ({})", permission_name(permission));
+                return Ok(());
+            }
+            Some(p) if p.expires_at <= chrono::Utc::now() => {
+                pairing::clear();
+                anyhow::bail!("The pairing code expired");
+            }
+            Some(_) => {}
+            None => anyhow::bail!("Pairing cancelled after too many wrong codes"),
+        }
+    }
+}
+
+/// Where a new configuration is written when none exists
+fn default_config_path() -> PathBuf {
+    #[cfg(windows)]
+    {
+        std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join("nanolink.yaml")))
+            .unwrap_or_else(|| PathBuf::from("nanolink.yaml"))
+    }
+    #[cfg(not(windows))]
+    {
+        PathBuf::from("/etc/nanolink/nanolink.yaml")
+    }
+}
+
+/// Install the agent service for `config_path` (platform-specific)
+fn install_agent_service(config_path: &Path) -> Result<(), String> {
+    #[cfg(target_os = "linux")]
+    {
+        install_systemd_service(Some(config_path))
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        install_launchd_service(Some(config_path))
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        crate::platform::install_service(Some(config_path.to_path_buf()))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    {
+        let _ = config_path;
+        Err("Service installation not supported on this platform".to_string())
+    }
+}
+
+fn permission_name(level: u8) -> &'static str {
+    nanolink_core::PermissionLevel::new(level).map_or("UNKNOWN", |p| p.as_str())
+}
+
+fn save_config(config: &Config, path: &Path) -> Result<()> {
+    std::fs::write(path, config.to_file_content(path)?)?;
     Ok(())
 }
 
@@ -1366,6 +2517,11 @@ fn interactive_add_server(config_path: &Path, lang: Lang) -> Result<()> {
         permission,
         tls_enabled,
         tls_verify,
+        tls_pin: None,
+        srv: None,
+        role: None,
+        bandwidth_cap: None,
+        reporting_schedule: None,
     });
 
     save_config(&config, config_path)?;
@@ -1434,6 +2590,10 @@ fn interactive_show_status(args: &Args, lang: Lang) -> Result<()> {
 
             match Config::load(&config_path) {
                 Ok(config) => {
+                    println!(
+                        "Agent ID: {}",
+                        config.agent.agent_id.as_deref().unwrap_or("-")
+                    );
                     println!();
                     println!("{}:", t("server.configured_servers", lang));
                     if config.servers.is_empty() {
@@ -1535,7 +2695,11 @@ fn interactive_check_update(args: &Args, lang: Lang) -> Result<()> {
 
     // Load config to get update settings
     let config = match get_config_path(args) {
-        Some(path) => Config::load(&path)?,
+        Some(path) => {
+            let config = Config::load(&path)?;
+            crate::executor::set_config_path(path);
+            config
+        }
         None => Config::sample(),
     };
 
@@ -1680,6 +2844,13 @@ fn interactive_check_update(args: &Args, lang: Lang) -> Result<()> {
 
     println!("✓ {}", t("update.success", lang));
     println!();
+
+    // Blue/green updates restart the service themselves
+    if config.update.blue_green {
+        println!("{}", apply_result.output);
+        return Ok(());
+    }
+
     println!("{}", t("update.restart_required", lang));
     println!();
 
@@ -2664,11 +3835,9 @@ fn interactive_realtime_metrics_legacy(lang: Lang) -> Result<()> {
                         KeyCode::Up => {
                             scroll_offset = scroll_offset.saturating_sub(1);
                         }
-                        KeyCode::Down => {
-                            // Limit scroll to max_scroll
-                            if scroll_offset < max_scroll {
-                                scroll_offset += 1;
-                            }
+                        // Limit scroll to max_scroll
+                        KeyCode::Down if scroll_offset < max_scroll => {
+                            scroll_offset += 1;
                         }
                         KeyCode::Tab => {
                             current_tab = (current_tab + 1) % tabs.len();
@@ -2690,55 +3859,27 @@ fn interactive_realtime_metrics_legacy(lang: Lang) -> Result<()> {
 fn get_listening_ports_legacy() -> Vec<(String, String, String, String)> {
     let mut ports = Vec::new();
 
-    #[cfg(target_os = "linux")]
+    #[cfg(any(target_os = "linux", target_os = "windows"))]
     {
         use std::process::Command;
-        if let Ok(output) = Command::new("ss").args(["-tulnp"]).output() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            for line in stdout.lines().skip(1) {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 5 {
-                    let proto = parts[0].to_string();
-                    let addr = parts[4].to_string();
-                    let process_info = parts.get(6).unwrap_or(&"").to_string();
-
-                    // Extract PID and process name from users:(("name",pid=123,fd=4))
-                    let (pid, name) = if let Some(start) = process_info.find("pid=") {
-                        let pid_part = &process_info[start + 4..];
-                        let pid: String = pid_part.chars().take_while(|c| c.is_numeric()).collect();
-                        let name = process_info
-                            .split("((\"")
-                            .nth(1)
-                            .and_then(|s| s.split("\"").next())
-                            .unwrap_or("-")
-                            .to_string();
-                        (pid, name)
-                    } else {
-                        ("-".to_string(), "-".to_string())
-                    };
-
-                    ports.push((pid, proto, addr, name));
-                }
-            }
-        }
-    }
-
-    #[cfg(target_os = "windows")]
-    {
-        use std::process::Command;
-        if let Ok(output) = Command::new("netstat").args(["-ano"]).output() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            for line in stdout.lines() {
-                if line.contains("LISTENING") {
-                    let parts: Vec<&str> = line.split_whitespace().collect();
-                    if parts.len() >= 5 {
-                        let proto = parts[0].to_string();
-                        let addr = parts[1].to_string();
-                        let pid = parts[4].to_string();
-                        ports.push((pid, proto, addr, "-".to_string()));
-                    }
-                }
-            }
+        #[cfg(target_os = "linux")]
+        let parsed = Command::new("ss").args(["-tulnp"]).output().map(|output| {
+            nanolink_parse::ss::listening_ports(&String::from_utf8_lossy(&output.stdout))
+        });
+        #[cfg(target_os = "windows")]
+        let parsed = Command::new("netstat")
+            .args(["-ano"])
+            .output()
+            .map(|output| {
+                nanolink_parse::netstat::listening_ports(&String::from_utf8_lossy(&output.stdout))
+            });
+
+        for port in parsed.unwrap_or_default() {
+            let pid = port
+                .pid
+                .map_or_else(|| "-".to_string(), |pid| pid.to_string());
+            let name = port.process.unwrap_or_else(|| "-".to_string());
+            ports.push((pid, port.protocol, port.address, name));
         }
     }
 
@@ -2808,14 +3949,14 @@ fn interactive_service_management(args: &Args, lang: Lang) -> Result<()> {
                 }
                 #[cfg(target_os = "linux")]
                 {
-                    match install_systemd_service(args) {
+                    match install_systemd_service(get_config_path(args).as_deref()) {
                         Ok(_) => println!("✓ {}", t("service.installed", lang)),
                         Err(e) => println!("✗ {}: {}", t("service.error", lang), e),
                     }
                 }
                 #[cfg(target_os = "macos")]
                 {
-                    match install_launchd_service(args) {
+                    match install_launchd_service(get_config_path(args).as_deref()) {
                         Ok(_) => println!("✓ {}", t("service.installed", lang)),
                         Err(e) => println!("✗ {}: {}", t("service.error", lang), e),
                     }
@@ -2999,7 +4140,7 @@ fn is_root() -> bool {
 }
 
 #[cfg(target_os = "linux")]
-fn install_systemd_service(args: &Args) -> Result<(), String> {
+fn install_systemd_service(config_path: Option<&Path>) -> Result<(), String> {
     use std::fs;
 
     // Check root permission
@@ -3011,9 +4152,9 @@ fn install_systemd_service(args: &Args) -> Result<(), String> {
     let exe_escaped = validate_systemd_path(&exe_path)
         .ok_or_else(|| "Invalid characters in executable path".to_string())?;
 
-    let config_arg = match get_config_path(args) {
+    let config_arg = match config_path {
         Some(p) => {
-            let config_escaped = validate_systemd_path(&p)
+            let config_escaped = validate_systemd_path(p)
                 .ok_or_else(|| "Invalid characters in config path".to_string())?;
             format!(" -c {}", config_escaped)
         }
@@ -3079,7 +4220,7 @@ fn uninstall_systemd_service() -> Result<(), String> {
 }
 
 #[cfg(target_os = "macos")]
-fn install_launchd_service(args: &Args) -> Result<(), String> {
+fn install_launchd_service(config_path: Option<&Path>) -> Result<(), String> {
     use std::fs;
 
     // Check root permission
@@ -3091,7 +4232,7 @@ fn install_launchd_service(args: &Args) -> Result<(), String> {
     let exe_escaped = escape_xml(&exe_path.to_string_lossy());
 
     let mut args_xml = String::from("        <string>-f</string>\n");
-    if let Some(p) = get_config_path(args) {
+    if let Some(p) = config_path {
         let config_escaped = escape_xml(&p.to_string_lossy());
         args_xml.push_str(&format!(
             "        <string>-c</string>\n        <string>{}</string>\n",
@@ -3435,6 +4576,27 @@ fn interactive_export_config(args: &Args, lang: Lang) -> Result<()> {
     Ok(())
 }
 
+/// Run as an update candidate: load the config like the real agent would,
+/// then serve the health check until stopped
+async fn run_candidate(config_path: Option<PathBuf>, port: u16) -> Result<()> {
+    let config_path = config_path.ok_or_else(|| anyhow::anyhow!("No configuration file found"))?;
+    let config = Config::load(&config_path)?;
+    connection::tls::init(config.security.fips_enabled());
+    connection::tls::self_check();
+
+    info!(
+        "Update candidate serving health check on 127.0.0.1:{}",
+        port
+    );
+    tokio::time::timeout(
+        crate::executor::CANDIDATE_LIFETIME,
+        executor::serve_health(port),
+    )
+    .await
+    .unwrap_or(Ok(()))?;
+    Ok(())
+}
+
 /// Run the agent (public for Windows service support)
 pub async fn run_agent(config_path: PathBuf) -> Result<()> {
     info!("NanoLink Agent v{} starting...", env!("CARGO_PKG_VERSION"));
@@ -3442,14 +4604,47 @@ pub async fn run_agent(config_path: PathBuf) -> Result<()> {
     // Load configuration
     let config = Config::load(&config_path)?;
     info!("Configuration loaded from {:?}", config_path);
+    if !config.sources().is_empty() {
+        info!("Configuration merged from {:?}", config.sources());
+    }
+    crate::executor::set_config_path(config_path.clone());
+
+    // Confirm a blue/green update once this binary has stayed up
+    tokio::spawn(crate::executor::confirm_rollout());
+
+    // Select crypto provider (FIPS mode) and log the TLS self-check
+    connection::tls::init(config.security.fips_enabled());
+    connection::tls::self_check();
+
+    // Select metrics timestamp clock
+    utils::clock::init(config.agent.clock);
+
+    // Load the key for encrypted state before anything is persisted
+    utils::at_rest::init(&config.security.at_rest).map_err(|e| anyhow::anyhow!(e))?;
+
+    // Deny syscalls the agent never needs
+    #[cfg(target_os = "linux")]
+    security::seccomp::apply(&config)
+        .map_err(|e| anyhow::anyhow!("Failed to apply the seccomp filter: {e}"))?;
+    #[cfg(not(target_os = "linux"))]
+    if config.security.hardening.seccomp {
+        tracing::warn!("security.hardening.seccomp is only supported on Linux");
+    }
+
+    // Scrub personal data from outgoing metrics
+    connection::privacy::init(
+        &config.privacy,
+        config.agent.agent_id.as_deref().unwrap_or_default(),
+    );
 
     // Create shared state with RwLock for runtime updates
     let management_enabled = config.management.enabled;
     let management_port = config.management.port;
     let buffer_capacity = config.buffer.capacity;
+    let buffer_max_bytes = config.buffer.max_bytes;
 
     let config = Arc::new(RwLock::new(config));
-    let ring_buffer = Arc::new(RingBuffer::new(buffer_capacity));
+    let ring_buffer = Arc::new(RingBuffer::new(buffer_capacity).with_max_bytes(buffer_max_bytes));
 
     // Create shutdown channel
     let (shutdown_tx, _) = broadcast::channel::<()>(1);
@@ -3458,18 +4653,19 @@ pub async fn run_agent(config_path: PathBuf) -> Result<()> {
     let connection_manager = {
         let config_guard = config.read().await;
         ConnectionManager::new(Arc::new((*config_guard).clone()), ring_buffer.clone())
+            .with_config_path(config_path.clone())
     };
-    let connection_signal_tx = connection_manager.get_signal_sender();
     let connection_status = connection_manager.get_status();
 
     // Start management API if enabled (with connection control)
+    #[cfg(feature = "management")]
     let management_handle = if management_enabled {
         let (management_server, _event_rx) = ManagementServer::new_with_connection_control(
             config.clone(),
             config_path.clone(),
             management_port,
-            connection_signal_tx,
-            connection_status,
+            connection_manager.get_signal_sender(),
+            connection_status.clone(),
             ring_buffer.clone(),
         );
 
@@ -3485,6 +4681,10 @@ pub async fn run_agent(config_path: PathBuf) -> Result<()> {
     } else {
         None
     };
+    #[cfg(not(feature = "management"))]
+    if management_enabled {
+        tracing::warn!("management.enabled is set but this build has no management API");
+    }
 
     // Start metrics collector (needs read-only config access)
     let collector = {
@@ -3504,6 +4704,116 @@ pub async fn run_agent(config_path: PathBuf) -> Result<()> {
         })
     };
 
+    // Start syslog listener if enabled
+    let syslog_handle = {
+        let config_guard = config.read().await;
+        config_guard.syslog.enabled.then(|| {
+            let syslog_config = Arc::new((*config_guard).clone());
+            let mut shutdown_rx = shutdown_tx.subscribe();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = collector::syslog::run(syslog_config) => {},
+                    _ = shutdown_rx.recv() => {
+                        info!("Syslog listener shutting down");
+                    }
+                }
+            })
+        })
+    };
+
+    // Start StatsD listener if enabled
+    let statsd_handle = {
+        let config_guard = config.read().await;
+        config_guard.statsd.enabled.then(|| {
+            let statsd_config = Arc::new((*config_guard).clone());
+            let mut shutdown_rx = shutdown_tx.subscribe();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = collector::statsd::run(statsd_config) => {},
+                    _ = shutdown_rx.recv() => {
+                        info!("StatsD listener shutting down");
+                    }
+                }
+            })
+        })
+    };
+
+    // Start the relay for downstream agents if enabled
+    #[cfg(feature = "relay")]
+    let relay_handle = {
+        let config_guard = config.read().await;
+        config_guard.relay.enabled.then(|| {
+            let relay_config = Arc::new((*config_guard).clone());
+            let mut shutdown_rx = shutdown_tx.subscribe();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = relay::run(relay_config) => {},
+                    _ = shutdown_rx.recv() => {
+                        info!("Relay shutting down");
+                    }
+                }
+            })
+        })
+    };
+    #[cfg(not(feature = "relay"))]
+    if config.read().await.relay.enabled {
+        tracing::warn!("relay.enabled is set but this build has no relay support");
+    }
+
+    // Start maintenance window scheduler if enabled
+    #[cfg(feature = "executors")]
+    let maintenance_handle = {
+        let config_guard = config.read().await;
+        config_guard.maintenance.enabled.then(|| {
+            let scheduler = MaintenanceExecutor::new(Arc::new((*config_guard).clone()));
+            let mut shutdown_rx = shutdown_tx.subscribe();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = scheduler.run_scheduler() => {},
+                    _ = shutdown_rx.recv() => {
+                        info!("Maintenance scheduler shutting down");
+                    }
+                }
+            })
+        })
+    };
+
+    // Start alert and connection notifications if enabled
+    let notify_handle = {
+        let config_guard = config.read().await;
+        config_guard.notifications.enabled.then(|| {
+            let notify_config = Arc::new((*config_guard).clone());
+            let connection_status = connection_status.clone();
+            let mut shutdown_rx = shutdown_tx.subscribe();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = notify::run(notify_config, connection_status) => {},
+                    _ = shutdown_rx.recv() => {
+                        info!("Notifications shutting down");
+                    }
+                }
+            })
+        })
+    };
+
+    // Refresh the central config profile from its URL if enabled
+    let profile_handle = {
+        let config_guard = config.read().await;
+        (config_guard.profile.enabled && config_guard.profile.url.is_some()).then(|| {
+            let profile_config = Arc::new((*config_guard).clone());
+            let config_path = config_path.clone();
+            let mut shutdown_rx = shutdown_tx.subscribe();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = profile::run(profile_config, config_path) => {},
+                    _ = shutdown_rx.recv() => {
+                        info!("Config profile refresh shutting down");
+                    }
+                }
+            })
+        })
+    };
+
     // Start connection manager (already created above)
     let connection_handle = {
         let mut shutdown_rx = shutdown_tx.subscribe();
@@ -3518,7 +4828,7 @@ pub async fn run_agent(config_path: PathBuf) -> Result<()> {
     };
 
     info!("NanoLink Agent started successfully");
-    if management_enabled {
+    if cfg!(feature = "management") && management_enabled {
         info!("  Management API: http://localhost:{}/api", management_port);
     }
 
@@ -3534,9 +4844,30 @@ pub async fn run_agent(config_path: PathBuf) -> Result<()> {
 
     // Wait for tasks to complete
     let _ = tokio::join!(collector_handle, connection_handle);
+    #[cfg(feature = "management")]
     if let Some(handle) = management_handle {
         let _ = handle.await;
     }
+    if let Some(handle) = syslog_handle {
+        let _ = handle.await;
+    }
+    if let Some(handle) = statsd_handle {
+        let _ = handle.await;
+    }
+    #[cfg(feature = "relay")]
+    if let Some(handle) = relay_handle {
+        let _ = handle.await;
+    }
+    #[cfg(feature = "executors")]
+    if let Some(handle) = maintenance_handle {
+        let _ = handle.await;
+    }
+    if let Some(handle) = notify_handle {
+        let _ = handle.await;
+    }
+    if let Some(handle) = profile_handle {
+        let _ = handle.await;
+    }
 
     info!("NanoLink Agent stopped");
     Ok(())
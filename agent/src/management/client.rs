@@ -0,0 +1,130 @@
+//! Blocking client for a running agent's management API
+//!
+//! Used by the settings window, the remote TUI and the export and silence
+//! commands. Plain HTTP only; reach a
+//! TLS-enabled or remote API through an SSH tunnel.
+
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::time::Duration;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::config::ManagementConfig;
+use crate::utils::http;
+
+const API_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Largest response accepted (history exports can be large)
+const MAX_RESPONSE_BYTES: u64 = 1024 * 1024 * 1024;
+
+pub struct ApiClient {
+    addr: SocketAddr,
+    token: String,
+    timeout: Duration,
+}
+
+impl ApiClient {
+    /// Client for `host:port` authenticating with `token`
+    pub fn new(address: &str, token: &str) -> Result<Self, String> {
+        let addr = address
+            .to_socket_addrs()
+            .map_err(|e| format!("Invalid management address {address}: {e}"))?
+            .next()
+            .ok_or_else(|| format!("{address} did not resolve"))?;
+        Ok(Self {
+            addr,
+            token: token.to_string(),
+            timeout: API_TIMEOUT,
+        })
+    }
+
+    /// Set the connect and read timeout (default 3s)
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Client for the API of the agent on this host, as configured
+    pub fn local(management: &ManagementConfig) -> Result<Self, String> {
+        if !management.enabled {
+            return Err("The management API is disabled".to_string());
+        }
+        if management.tls_enabled {
+            return Err(
+                "The management API uses TLS, which this client doesn't support".to_string(),
+            );
+        }
+        let token = management
+            .api_token
+            .as_deref()
+            .ok_or("The management API has no token")?;
+        // A wildcard bind is reachable on loopback
+        let host = match management.bind_address.as_str() {
+            "0.0.0.0" => "127.0.0.1".to_string(),
+            "::" => "[::1]".to_string(),
+            other if other.contains(':') => format!("[{other}]"),
+            other => other.to_string(),
+        };
+        Self::new(&format!("{host}:{}", management.port), token)
+    }
+
+    /// GET `path` and decode the JSON response
+    pub fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, String> {
+        serde_json::from_slice(&self.download(path)?)
+            .map_err(|e| format!("Unexpected response from the agent: {e}"))
+    }
+
+    /// POST `body` as JSON to `path` and decode the JSON response
+    pub fn post<B: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, String> {
+        let body = serde_json::to_vec(body).map_err(|e| e.to_string())?;
+        let bearer = format!("Bearer {}", self.token);
+        let response = http::request_with_body(
+            "POST",
+            self.addr,
+            &self.addr.to_string(),
+            path,
+            &[
+                ("Authorization", &bearer),
+                ("Content-Type", "application/json"),
+            ],
+            &body,
+            self.timeout,
+        );
+        serde_json::from_slice(&self.check(response)?)
+            .map_err(|e| format!("Unexpected response from the agent: {e}"))
+    }
+
+    /// GET `path` and return the raw response body
+    pub fn download(&self, path: &str) -> Result<Vec<u8>, String> {
+        let bearer = format!("Bearer {}", self.token);
+        let response = http::request_with_limit(
+            "GET",
+            self.addr,
+            &self.addr.to_string(),
+            path,
+            &[("Authorization", &bearer)],
+            self.timeout,
+            MAX_RESPONSE_BYTES,
+        );
+        self.check(response)
+    }
+
+    /// Body of a successful response, or the reason it failed
+    fn check(&self, response: std::io::Result<http::Response>) -> Result<Vec<u8>, String> {
+        let response =
+            response.map_err(|e| format!("Agent is not reachable on {}: {}", self.addr, e))?;
+        match response.status {
+            200 => Ok(response.body),
+            401 | 403 => Err("Management API token was rejected".to_string()),
+            status => match response.text().trim() {
+                "" => Err(format!("Agent returned HTTP {status}")),
+                reason => Err(format!("Agent returned HTTP {status}: {reason}")),
+            },
+        }
+    }
+}
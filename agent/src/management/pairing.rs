@@ -0,0 +1,235 @@
+//! Pairing desktop apps by QR code
+//!
+//! `nanolink-agent pair` creates a one-time secret, shows it in a QR code
+//! together with the address of the management API, and waits. The desktop
+//! app scans the code and claims the pairing with `POST /api/pair/claim`,
+//! receiving a management token of its own. Only a hash of that token is
+//! kept, in `management.paired_clients`.
+//!
+//! The pending pairing lives in `/var/lib/nanolink/pairing.json`
+//! (`%ProgramData%\nanolink` on Windows), written by the command and
+//! consumed by the running agent. It holds a hash of the secret, expires
+//! after a few minutes, can be claimed once and is dropped after a few wrong
+//! guesses.
+
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::PairedClient;
+
+/// Wrong secrets accepted before the pairing is cancelled
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Serializes claims within the agent
+static CLAIM: Mutex<()> = Mutex::new(());
+
+/// A pairing waiting to be claimed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pending {
+    pub secret_sha256: String,
+    pub permission: u8,
+    pub expires_at: DateTime<Utc>,
+    #[serde(default)]
+    pub failed_attempts: u32,
+    /// Name of the app that claimed it
+    #[serde(default)]
+    pub claimed_by: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClaimRequest {
+    pub secret: String,
+    /// How the agent lists the app, e.g. the desktop's hostname
+    #[serde(default)]
+    pub name: String,
+}
+
+/// What the app keeps
+#[derive(Debug, Serialize)]
+pub struct ClaimResponse {
+    pub token: String,
+    pub permission: u8,
+    pub agent_id: String,
+    pub hostname: String,
+}
+
+pub fn pending_file() -> PathBuf {
+    #[cfg(windows)]
+    {
+        let base = std::env::var("ProgramData").unwrap_or_else(|_| "C:\\ProgramData".to_string());
+        PathBuf::from(base).join("nanolink").join("pairing.json")
+    }
+    #[cfg(unix)]
+    {
+        PathBuf::from("/var/lib/nanolink/pairing.json")
+    }
+}
+
+pub fn sha256_hex(data: &str) -> String {
+    Sha256::digest(data.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// A random secret of `bytes` bytes, hex encoded
+pub fn random_secret(bytes: usize) -> Result<String, String> {
+    let mut secret = vec![0u8; bytes];
+    SystemRandom::new()
+        .fill(&mut secret)
+        .map_err(|_| "No random source available".to_string())?;
+    Ok(secret.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+pub fn load() -> Option<Pending> {
+    let json = std::fs::read(pending_file()).ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+/// Write the pending pairing, readable by its owner only
+pub fn store(pending: &Pending) -> std::io::Result<()> {
+    let path = pending_file();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("tmp");
+    std::fs::write(
+        &tmp,
+        serde_json::to_vec(pending).map_err(std::io::Error::other)?,
+    )?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600))?;
+    }
+    std::fs::rename(&tmp, &path)
+}
+
+pub fn clear() {
+    let _ = std::fs::remove_file(pending_file());
+}
+
+/// Check `secret` against the pending pairing and mark it claimed by `name`.
+/// Returns the permission to grant.
+pub fn claim(secret: &str, name: &str) -> Result<u8, String> {
+    let _guard = CLAIM.lock();
+    let mut pending = load()
+        .filter(|p| p.claimed_by.is_none() && p.expires_at > Utc::now())
+        .ok_or("No pairing in progress")?;
+
+    let matches: bool = subtle::ConstantTimeEq::ct_eq(
+        sha256_hex(secret).as_bytes(),
+        pending.secret_sha256.as_bytes(),
+    )
+    .into();
+    if !matches {
+        pending.failed_attempts += 1;
+        if pending.failed_attempts >= MAX_ATTEMPTS {
+            clear();
+            return Err("Invalid pairing code, pairing cancelled".to_string());
+        }
+        store(&pending).map_err(|e| e.to_string())?;
+        return Err("Invalid pairing code".to_string());
+    }
+
+    pending.claimed_by = Some(name.to_string());
+    store(&pending).map_err(|e| e.to_string())?;
+    Ok(pending.permission)
+}
+
+/// The paired app presenting `token`
+pub fn find_client<'a>(clients: &'a [PairedClient], token: &str) -> Option<&'a PairedClient> {
+    let hash = sha256_hex(token);
+    clients
+        .iter()
+        .find(|c| subtle::ConstantTimeEq::ct_eq(hash.as_bytes(), c.token_sha256.as_bytes()).into())
+}
+
+/// Link the desktop app reads from the QR code
+pub fn pairing_uri(
+    host: &str,
+    port: u16,
+    secret: &str,
+    name: &str,
+    tls_fingerprint: Option<&str>,
+) -> String {
+    let mut uri = format!(
+        "nanolink://pair?host={}&port={port}&secret={secret}&name={}",
+        encode(host),
+        encode(name)
+    );
+    if let Some(fingerprint) = tls_fingerprint {
+        uri.push_str(&format!("&tls=1&fp={}", encode(fingerprint)));
+    }
+    uri
+}
+
+/// Percent-encode a query value
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Address of the interface with the default route
+pub fn primary_address() -> Option<std::net::IpAddr> {
+    // Connecting a UDP socket sends nothing, it only picks the route
+    let socket = std::net::UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("192.0.2.1:9").ok()?;
+    Some(socket.local_addr().ok()?.ip())
+}
+
+/// `data` as a QR code drawn with block characters, light on dark
+pub fn render_qr(data: &str) -> Result<String, String> {
+    use qrcode::render::unicode::Dense1x2;
+
+    let code = qrcode::QrCode::new(data.as_bytes()).map_err(|e| e.to_string())?;
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .quiet_zone(true)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_client() {
+        let clients = vec![PairedClient {
+            name: "laptop".to_string(),
+            token_sha256: sha256_hex("pair_abc"),
+            permission: 1,
+            paired_at: String::new(),
+        }];
+        assert_eq!(find_client(&clients, "pair_abc").unwrap().name, "laptop");
+        assert!(find_client(&clients, "pair_abd").is_none());
+    }
+
+    #[test]
+    fn test_pairing_uri() {
+        assert_eq!(
+            pairing_uri("10.0.0.5", 9101, "ab12", "web 01", None),
+            "nanolink://pair?host=10.0.0.5&port=9101&secret=ab12&name=web%2001"
+        );
+        assert!(
+            pairing_uri("::1", 9101, "ab12", "web", Some("sha256:00ff"))
+                .ends_with("host=%3A%3A1&port=9101&secret=ab12&name=web&tls=1&fp=sha256%3A00ff")
+        );
+        assert!(render_qr("nanolink://pair").unwrap().contains('█'));
+        assert_eq!(random_secret(10).unwrap().len(), 20);
+    }
+}
@@ -0,0 +1,1797 @@
+//! HTTP server of the management API
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{ConnectInfo, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{RwLock, broadcast};
+use tracing::{error, info, warn};
+
+use super::{SilenceRequest, audit, pairing, rate_limit, token};
+use crate::buffer::RingBuffer;
+use crate::config::{Config, DEFAULT_GRPC_PORT, PairedClient, ServerConfig, ServerRole};
+use crate::connection::{ConnectionSignal, ConnectionStatus};
+use crate::silence;
+use crate::telemetry::{TelemetrySnapshot, telemetry};
+use crate::tui::{LocalSource, Snapshot};
+use crate::utils::export;
+
+/// Server change event for dynamic server management
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub enum ServerEvent {
+    /// Add a new server
+    Add(ServerConfig),
+    /// Update an existing server (by host:port)
+    Update(ServerConfig),
+    /// Remove a server by host:port
+    Remove(String, u16),
+}
+
+/// Management API state
+pub struct ManagementState {
+    /// Current configuration
+    config: Arc<RwLock<Config>>,
+    /// Path to configuration file
+    config_path: PathBuf,
+    /// Channel to notify about server changes
+    event_tx: broadcast::Sender<ServerEvent>,
+    /// Channel to send connection control signals
+    connection_signal_tx: Option<broadcast::Sender<ConnectionSignal>>,
+    /// Connection status for each server
+    connection_status: Option<Arc<RwLock<Vec<ConnectionStatus>>>>,
+    /// Ring buffer reference for buffer stats
+    buffer: Option<Arc<RingBuffer>>,
+    /// Host snapshots for remote TUI viewers, created on first request
+    snapshot_source: Arc<parking_lot::Mutex<Option<LocalSource>>>,
+}
+
+/// Management API server
+pub struct ManagementServer {
+    state: Arc<ManagementState>,
+    port: u16,
+}
+
+impl ManagementServer {
+    /// Create a new management server
+    #[allow(dead_code)]
+    pub fn new(
+        config: Arc<RwLock<Config>>,
+        config_path: PathBuf,
+        port: u16,
+    ) -> (Self, broadcast::Receiver<ServerEvent>) {
+        let (event_tx, event_rx) = broadcast::channel(16);
+
+        let state = Arc::new(ManagementState {
+            config,
+            config_path,
+            event_tx,
+            connection_signal_tx: None,
+            connection_status: None,
+            buffer: None,
+            snapshot_source: Arc::default(),
+        });
+
+        (Self { state, port }, event_rx)
+    }
+
+    /// Create a new management server with connection control capabilities
+    pub fn new_with_connection_control(
+        config: Arc<RwLock<Config>>,
+        config_path: PathBuf,
+        port: u16,
+        connection_signal_tx: broadcast::Sender<ConnectionSignal>,
+        connection_status: Arc<RwLock<Vec<ConnectionStatus>>>,
+        buffer: Arc<RingBuffer>,
+    ) -> (Self, broadcast::Receiver<ServerEvent>) {
+        let (event_tx, event_rx) = broadcast::channel(16);
+
+        let state = Arc::new(ManagementState {
+            config,
+            config_path,
+            event_tx,
+            connection_signal_tx: Some(connection_signal_tx),
+            connection_status: Some(connection_status),
+            buffer: Some(buffer),
+            snapshot_source: Arc::default(),
+        });
+
+        (Self { state, port }, event_rx)
+    }
+
+    /// Run the management server
+    pub async fn run(self) {
+        // Get config for middleware setup
+        let (rate_limit_config, audit_config) = {
+            let config = self.state.config.read().await;
+            (
+                config.management.rate_limit.clone(),
+                config.management.audit.clone(),
+            )
+        };
+
+        // Create middleware states
+        let auth_state = self.state.clone();
+        let rate_limit_state = Arc::new(rate_limit::RateLimitState::new(rate_limit_config.clone()));
+        let audit_state = Arc::new(audit::AuditState::new(audit_config.clone()));
+
+        // Start background cleanup task for rate limit buckets
+        if rate_limit_config.enabled {
+            let cleanup_state = rate_limit_state.clone();
+            tokio::spawn(async move {
+                rate_limit::cleanup_old_buckets(cleanup_state).await;
+            });
+        }
+
+        // Protected routes (require authentication based on permission level)
+        let protected_routes = Router::new()
+            .route("/api/config", get(get_config))
+            .route("/api/servers", get(list_servers))
+            .route("/api/servers", post(add_server))
+            .route("/api/servers", delete(remove_server))
+            .route("/api/servers/update", post(update_server))
+            .route("/api/connection/status", get(connection_status))
+            .route("/api/telemetry", get(agent_telemetry))
+            .route("/api/snapshot", get(host_snapshot))
+            .route("/api/connection/reconnect", post(trigger_reconnect))
+            .route("/api/buffer/status", get(buffer_status))
+            .route("/api/export", get(export_history))
+            .route("/api/silence", get(silence_status))
+            .route("/api/silence/start", post(silence_start))
+            .route("/api/silence/stop", post(silence_stop))
+            .route("/api/token/rotate", post(rotate_token))
+            .layer(middleware::from_fn_with_state(
+                auth_state.clone(),
+                auth_middleware,
+            ));
+
+        // All routes with rate limiting layer
+        let rate_limited_routes = Router::new()
+            .route("/api/health", get(health))
+            .route("/api/health/live", get(health))
+            .route("/api/health/ready", get(readiness))
+            .route("/api/status", get(status))
+            .route("/api/pair/claim", post(pair_claim))
+            .route("/api/proto/descriptor", get(proto_descriptor))
+            .merge(protected_routes)
+            .layer(middleware::from_fn_with_state(
+                rate_limit_state,
+                rate_limit::rate_limit_middleware,
+            ))
+            .with_state(self.state.clone());
+
+        // Apply audit logging to all routes (outermost layer)
+        let app = rate_limited_routes.layer(middleware::from_fn_with_state(
+            audit_state,
+            audit::audit_middleware,
+        ));
+
+        // Get TLS config
+        let (bind_addr, tls_enabled, tls_cert, tls_key) = {
+            let config = self.state.config.read().await;
+            (
+                config.management.bind_address.clone(),
+                config.management.tls_enabled,
+                config.management.tls_cert.clone(),
+                config.management.tls_key.clone(),
+            )
+        };
+
+        let addr: SocketAddr = format!("{}:{}", bind_addr, self.port)
+            .parse()
+            .unwrap_or_else(|_| SocketAddr::from(([127, 0, 0, 1], self.port)));
+
+        // Start server with or without TLS
+        if tls_enabled {
+            if let (Some(cert_path), Some(key_path)) = (tls_cert, tls_key) {
+                match axum_server::tls_rustls::RustlsConfig::from_pem_file(&cert_path, &key_path)
+                    .await
+                {
+                    Ok(tls_config) => {
+                        info!("Management API listening on https://{} (TLS enabled)", addr);
+                        if let Err(e) = axum_server::bind_rustls(addr, tls_config)
+                            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                            .await
+                        {
+                            error!("Management API TLS error: {}", e);
+                        }
+                        return;
+                    }
+                    Err(e) => {
+                        error!(
+                            "Failed to load TLS certificates from {} / {}: {}. Falling back to HTTP.",
+                            cert_path, key_path, e
+                        );
+                    }
+                }
+            } else {
+                warn!("TLS enabled but cert/key paths not configured. Falling back to HTTP.");
+            }
+        }
+
+        // Plain HTTP fallback
+        if crate::connection::tls::fips_mode() {
+            error!("Management API not started: FIPS mode forbids plaintext HTTP");
+            return;
+        }
+        info!("Management API listening on http://{}", addr);
+
+        match tokio::net::TcpListener::bind(addr).await {
+            Ok(listener) => {
+                if let Err(e) = axum::serve(
+                    listener,
+                    app.into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .await
+                {
+                    error!("Management API error: {}", e);
+                }
+            }
+            Err(e) => {
+                error!("Failed to bind Management API to {}: {}", addr, e);
+            }
+        }
+    }
+}
+
+/// Endpoints `management.api_token` is accepted for: connection status for
+/// the settings window, host snapshots for the TUI viewer, history export
+/// and the `silence` command
+const LOCAL_TOKEN_PATHS: &[&str] = &[
+    "/api/connection/status",
+    "/api/snapshot",
+    "/api/export",
+    "/api/silence",
+    "/api/silence/start",
+    "/api/silence/stop",
+];
+
+/// Permission level of `management.api_token`
+const LOCAL_TOKEN_PERMISSION: u8 = 2;
+
+/// Authentication middleware - validates Token + IP + Permission
+/// 1. Extract Bearer token from Authorization header
+/// 2. Find matching server by management_token
+/// 3. Verify source IP matches server host
+/// 4. Check permission level for requested endpoint
+async fn auth_middleware(
+    State(state): State<Arc<ManagementState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    request: axum::extract::Request,
+    next: Next,
+) -> Result<Response, (StatusCode, Json<ApiResponse>)> {
+    let config = state.config.read().await;
+    let source_ip = addr.ip();
+    let path = request.uri().path();
+
+    // Get required permission for this endpoint
+    let required_permission = get_required_permission(path);
+
+    // Public endpoints (permission 0) - no auth required
+    if required_permission == 0 {
+        return Ok(next.run(request).await);
+    }
+
+    // Extract Authorization header
+    let auth_header = headers.get("Authorization").and_then(|v| v.to_str().ok());
+
+    let token = match auth_header {
+        Some(header) if header.starts_with("Bearer ") => &header[7..],
+        _ => {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                Json(ApiResponse {
+                    success: false,
+                    message: "Missing or invalid Authorization header. Use: Authorization: Bearer <token>".to_string(),
+                }),
+            ));
+        }
+    };
+
+    // The agent's own API token is for local tools
+    if is_local_api_token(&config.management, token, source_ip) {
+        if !local_token_allowed(path, required_permission) {
+            warn!(
+                "Management API: local API token used for {} from {}",
+                path, source_ip
+            );
+            return Err((
+                StatusCode::FORBIDDEN,
+                Json(ApiResponse {
+                    success: false,
+                    message: format!("The local API token is not accepted for {path}"),
+                }),
+            ));
+        }
+        drop(config);
+        return Ok(next.run(request).await);
+    }
+
+    // Desktop apps paired by QR code
+    if let Some(client) = pairing::find_client(&config.management.paired_clients, token) {
+        if client.permission < required_permission {
+            return Err((
+                StatusCode::FORBIDDEN,
+                Json(ApiResponse {
+                    success: false,
+                    message: format!(
+                        "Insufficient permission: level {} required, you have {}",
+                        required_permission, client.permission
+                    ),
+                }),
+            ));
+        }
+        drop(config);
+        return Ok(next.run(request).await);
+    }
+
+    // Find server with matching management_token
+    let matching_server = config.servers.iter().find(|s| {
+        s.management_token.as_ref().is_some_and(|t| {
+            // Use constant-time comparison to prevent timing attacks
+            subtle::ConstantTimeEq::ct_eq(token.as_bytes(), t.as_bytes()).into()
+        })
+    });
+
+    let server = match matching_server {
+        Some(s) => s,
+        None => {
+            warn!("Management API: invalid token attempted from {}", source_ip);
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                Json(ApiResponse {
+                    success: false,
+                    message: "Invalid management token".to_string(),
+                }),
+            ));
+        }
+    };
+
+    // Verify source IP matches server host
+    if !verify_source_ip(&server.host, source_ip).await {
+        warn!(
+            "Management API: IP mismatch - token for {} used from {}",
+            server.host, source_ip
+        );
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ApiResponse {
+                success: false,
+                message: format!(
+                    "Source IP {} does not match server {}",
+                    source_ip, server.host
+                ),
+            }),
+        ));
+    }
+
+    // Check permission level
+    if server.permission < required_permission {
+        warn!(
+            "Management API: insufficient permission - {} has {} but needs {}",
+            server.host, server.permission, required_permission
+        );
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ApiResponse {
+                success: false,
+                message: format!(
+                    "Insufficient permission: level {} required, you have {}",
+                    required_permission, server.permission
+                ),
+            }),
+        ));
+    }
+
+    Ok(next.run(request).await)
+}
+
+/// Whether `token` is `management.api_token`, presented from this host
+fn is_local_api_token(
+    management: &crate::config::ManagementConfig,
+    token: &str,
+    source_ip: std::net::IpAddr,
+) -> bool {
+    source_ip.is_loopback()
+        && management
+            .api_token
+            .as_ref()
+            .is_some_and(|t| subtle::ConstantTimeEq::ct_eq(token.as_bytes(), t.as_bytes()).into())
+}
+
+/// Whether `management.api_token` may call `path`: only the endpoints local
+/// tools use, and no higher than `LOCAL_TOKEN_PERMISSION`
+fn local_token_allowed(path: &str, required_permission: u8) -> bool {
+    LOCAL_TOKEN_PATHS.contains(&path) && required_permission <= LOCAL_TOKEN_PERMISSION
+}
+
+/// Get required permission level for endpoint
+fn get_required_permission(path: &str) -> u8 {
+    match path {
+        // Public endpoints (permission 0)
+        "/api/health" | "/api/status" | "/api/pair/claim" | "/api/proto/descriptor" => 0,
+
+        // Basic read (permission 1)
+        "/api/config"
+        | "/api/connection/status"
+        | "/api/servers"
+        | "/api/telemetry"
+        | "/api/snapshot"
+        | "/api/silence" => 1,
+
+        // Service control (permission 2)
+        "/api/connection/reconnect"
+        | "/api/logs"
+        | "/api/buffer/status"
+        | "/api/export"
+        | "/api/silence/start"
+        | "/api/silence/stop" => 2,
+
+        // System admin (permission 3)
+        "/api/shell" | "/api/restart" | "/api/token/rotate" | "/api/servers/update" => 3,
+
+        // Default: require highest permission for unknown endpoints
+        _ => 3,
+    }
+}
+
+/// Verify that source IP matches server host (DNS resolution)
+async fn verify_source_ip(host: &str, source_ip: std::net::IpAddr) -> bool {
+    use std::net::ToSocketAddrs;
+
+    // A server on a Unix socket runs on this host
+    if host.starts_with(nanolink_client::unix::SCHEME) {
+        return source_ip.is_loopback();
+    }
+
+    // Try to resolve host to IP addresses
+    let resolved = format!("{host}:0").to_socket_addrs();
+
+    match resolved {
+        Ok(addrs) => {
+            for addr in addrs {
+                if addr.ip() == source_ip {
+                    return true;
+                }
+            }
+            false
+        }
+        Err(_) => {
+            // If resolution fails, try direct comparison (host might be an IP)
+            if let Ok(host_ip) = host.parse::<std::net::IpAddr>() {
+                host_ip == source_ip
+            } else {
+                false
+            }
+        }
+    }
+}
+
+/// SECURITY: Validate host input to prevent injection and DoS attacks
+fn validate_host(host: &str) -> Result<(), String> {
+    // Length check (prevent DoS via oversized input)
+    if host.is_empty() {
+        return Err("Host cannot be empty".to_string());
+    }
+    if host.len() > 253 {
+        // Max DNS hostname length
+        return Err("Host too long (max 253 characters)".to_string());
+    }
+
+    // Check for dangerous patterns
+    let host_lower = host.to_lowercase();
+
+    // Block localhost variants (unless explicitly allowing local management)
+    // This is a security decision - remove if local servers should be allowed
+    // if host_lower == "localhost" || host_lower == "127.0.0.1" || host_lower == "::1" {
+    //     return Err("Localhost addresses are not allowed".to_string());
+    // }
+
+    // Block metadata service IPs (cloud security)
+    if host_lower.starts_with("169.254.") || host_lower == "169.254.169.254" {
+        return Err("Metadata service addresses are not allowed".to_string());
+    }
+
+    // Check for valid characters (hostname or IP)
+    let valid_chars = host.chars().all(|c| {
+        c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == ':' || c == '[' || c == ']'
+    });
+    if !valid_chars {
+        return Err("Host contains invalid characters".to_string());
+    }
+
+    Ok(())
+}
+
+// Request/Response types
+
+#[derive(Debug, Serialize)]
+struct HealthResponse {
+    status: String,
+    version: String,
+}
+
+/// Readiness: whether the agent is doing its job, not just running
+#[derive(Debug, Serialize)]
+struct ReadinessResponse {
+    status: String,
+    version: String,
+    checks: ReadinessChecks,
+}
+
+#[derive(Debug, Serialize)]
+struct ReadinessChecks {
+    servers: ServersCheck,
+    collector: CollectorCheck,
+    buffer: BufferCheck,
+}
+
+#[derive(Debug, Serialize)]
+struct ServersCheck {
+    ok: bool,
+    connected: usize,
+    configured: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct CollectorCheck {
+    ok: bool,
+    /// Seconds since the latest sample, absent before the first one
+    last_sample_age_seconds: Option<u64>,
+    stale_after_seconds: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct BufferCheck {
+    ok: bool,
+    usage_percent: f64,
+}
+
+impl ReadinessResponse {
+    fn new(servers: ServersCheck, collector: CollectorCheck, buffer: BufferCheck) -> Self {
+        let ready = servers.ok && collector.ok && buffer.ok;
+        Self {
+            status: if ready { "ready" } else { "not_ready" }.to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            checks: ReadinessChecks {
+                servers,
+                collector,
+                buffer,
+            },
+        }
+    }
+
+    fn status_code(&self) -> StatusCode {
+        if self.status == "ready" {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ServerInfo {
+    host: String,
+    port: u16,
+    permission: u8,
+    tls_enabled: bool,
+    tls_verify: bool,
+    connected: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct AddServerRequest {
+    host: String,
+    #[serde(default = "default_grpc_port")]
+    port: u16,
+    token: String,
+    #[serde(default)]
+    permission: u8,
+    #[serde(default)]
+    tls_enabled: bool,
+    #[serde(default = "default_true")]
+    tls_verify: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_grpc_port() -> u16 {
+    DEFAULT_GRPC_PORT
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoveServerQuery {
+    host: String,
+    #[serde(default = "default_grpc_port")]
+    port: u16,
+}
+
+#[derive(Debug, Serialize)]
+struct ApiResponse {
+    success: bool,
+    message: String,
+}
+
+// Handlers
+
+async fn health() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "healthy".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    })
+}
+
+/// Ready when a server is connected, the collector produced a sample within
+/// a few intervals and the buffer isn't full while nothing drains it.
+async fn readiness(
+    State(state): State<Arc<ManagementState>>,
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let (configured, interval_ms) = {
+        let config = state.config.read().await;
+        let collector = &config.collector;
+        (
+            config.servers.len(),
+            collector
+                .cpu_interval_ms
+                .max(collector.realtime_interval_ms),
+        )
+    };
+    let connected = match &state.connection_status {
+        Some(status) => status.read().await.iter().filter(|s| s.connected).count(),
+        None => 0,
+    };
+
+    let stale_after = (interval_ms.saturating_mul(3) / 1000).max(10);
+    let age = telemetry().last_sample_age().map(|age| age.as_secs());
+    let usage_percent = state.buffer.as_ref().map_or(0.0, |b| b.usage_percent());
+
+    let response = ReadinessResponse::new(
+        ServersCheck {
+            ok: connected > 0,
+            connected,
+            configured,
+        },
+        CollectorCheck {
+            ok: age.is_some_and(|age| age <= stale_after),
+            last_sample_age_seconds: age,
+            stale_after_seconds: stale_after,
+        },
+        BufferCheck {
+            ok: usage_percent < 100.0 || connected > 0,
+            usage_percent,
+        },
+    );
+    (response.status_code(), Json(response))
+}
+
+#[derive(Debug, Serialize)]
+struct StatusResponse {
+    status: String,
+    version: String,
+    uptime_seconds: u64,
+    hostname: Option<String>,
+    agent_id: Option<String>,
+    /// Connection state and role of each server
+    servers: Vec<ConnectionStatusInfo>,
+}
+
+async fn status(State(state): State<Arc<ManagementState>>) -> Json<StatusResponse> {
+    let config = state.config.read().await;
+    let hostname = config.agent.hostname.clone();
+    let agent_id = config.agent.agent_id.clone();
+
+    // Calculate uptime (approximate since we don't track start time)
+    let uptime = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let servers = match &state.connection_status {
+        Some(status) => status.read().await.iter().map(Into::into).collect(),
+        None => Vec::new(),
+    };
+
+    Json(StatusResponse {
+        status: "running".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime_seconds: uptime,
+        hostname,
+        agent_id,
+        servers,
+    })
+}
+
+async fn get_config(State(state): State<Arc<ManagementState>>) -> Json<serde_json::Value> {
+    let config = state.config.read().await;
+
+    // Return config without tokens
+    Json(serde_json::json!({
+        "agent": config.agent,
+        "collector": config.collector,
+        "buffer": config.buffer,
+        "shell": {
+            "enabled": config.shell.enabled,
+            "timeout_seconds": config.shell.timeout_seconds,
+        },
+        "logging": config.logging,
+        "server_count": config.servers.len(),
+    }))
+}
+
+async fn list_servers(State(state): State<Arc<ManagementState>>) -> Json<Vec<ServerInfo>> {
+    let config = state.config.read().await;
+
+    let servers: Vec<ServerInfo> = config
+        .servers
+        .iter()
+        .map(|s| ServerInfo {
+            host: s.host.clone(),
+            port: s.port,
+            permission: s.permission,
+            tls_enabled: s.tls_enabled,
+            tls_verify: s.tls_verify,
+            connected: false, // TODO: Track actual connection state
+        })
+        .collect();
+
+    Json(servers)
+}
+
+async fn add_server(
+    State(state): State<Arc<ManagementState>>,
+    Json(req): Json<AddServerRequest>,
+) -> (StatusCode, Json<ApiResponse>) {
+    // SECURITY: Validate host input
+    if let Err(msg) = validate_host(&req.host) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse {
+                success: false,
+                message: msg,
+            }),
+        );
+    }
+
+    // Validate permission
+    if req.permission > 3 {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse {
+                success: false,
+                message: "Permission must be 0-3".to_string(),
+            }),
+        );
+    }
+
+    let server_config = ServerConfig {
+        host: req.host.clone(),
+        port: req.port,
+        token: req.token,
+        management_token: None,
+        permission: req.permission,
+        tls_enabled: req.tls_enabled,
+        tls_verify: req.tls_verify,
+        tls_pin: None,
+        srv: None,
+        role: None,
+        bandwidth_cap: None,
+        reporting_schedule: None,
+    };
+
+    // Check if server already exists
+    {
+        let config = state.config.read().await;
+        if config
+            .servers
+            .iter()
+            .any(|s| s.host == req.host && s.port == req.port)
+        {
+            return (
+                StatusCode::CONFLICT,
+                Json(ApiResponse {
+                    success: false,
+                    message: "Server already exists. Use update endpoint to modify.".to_string(),
+                }),
+            );
+        }
+    }
+
+    // Add server to config
+    {
+        let mut config = state.config.write().await;
+        config.servers.push(server_config.clone());
+
+        // Save to file
+        if let Err(e) = save_config(&config, &state.config_path) {
+            error!("Failed to save config: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse {
+                    success: false,
+                    message: format!("Failed to save config: {e}"),
+                }),
+            );
+        }
+    }
+
+    // Notify about the new server
+    let _ = state.event_tx.send(ServerEvent::Add(server_config));
+
+    info!("Added server: {}:{}", req.host, req.port);
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse {
+            success: true,
+            message: format!("Server {}:{} added successfully", req.host, req.port),
+        }),
+    )
+}
+
+async fn update_server(
+    State(state): State<Arc<ManagementState>>,
+    Json(req): Json<AddServerRequest>,
+) -> (StatusCode, Json<ApiResponse>) {
+    // SECURITY: Validate host input
+    if let Err(msg) = validate_host(&req.host) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse {
+                success: false,
+                message: msg,
+            }),
+        );
+    }
+
+    // Update server in config
+    {
+        let mut config = state.config.write().await;
+        let found = config
+            .servers
+            .iter_mut()
+            .find(|s| s.host == req.host && s.port == req.port);
+
+        match found {
+            Some(server) => {
+                // SECURITY: Preserve existing management_token and certificate pin
+                let existing_mgmt_token = server.management_token.clone();
+                let existing_tls_pin = server.tls_pin.clone();
+                let existing_srv = server.srv.clone();
+                let existing_role = server.role;
+                let existing_bandwidth_cap = server.bandwidth_cap.clone();
+                let existing_reporting_schedule = server.reporting_schedule.clone();
+
+                // Log permission changes as security events
+                if server.permission != req.permission {
+                    warn!(
+                        "SECURITY: Permission change for {}:{} from {} to {}",
+                        req.host, req.port, server.permission, req.permission
+                    );
+                }
+
+                *server = ServerConfig {
+                    host: req.host.clone(),
+                    port: req.port,
+                    token: req.token.clone(),
+                    management_token: existing_mgmt_token,
+                    permission: req.permission,
+                    tls_enabled: req.tls_enabled,
+                    tls_verify: req.tls_verify,
+                    tls_pin: existing_tls_pin,
+                    srv: existing_srv,
+                    role: existing_role,
+                    bandwidth_cap: existing_bandwidth_cap,
+                    reporting_schedule: existing_reporting_schedule,
+                };
+            }
+            None => {
+                return (
+                    StatusCode::NOT_FOUND,
+                    Json(ApiResponse {
+                        success: false,
+                        message: "Server not found".to_string(),
+                    }),
+                );
+            }
+        }
+
+        // Save to file
+        if let Err(e) = save_config(&config, &state.config_path) {
+            error!("Failed to save config: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse {
+                    success: false,
+                    message: format!("Failed to save config: {e}"),
+                }),
+            );
+        }
+    }
+
+    // Notify about the update
+    let _ = state.event_tx.send(ServerEvent::Update(ServerConfig {
+        host: req.host.clone(),
+        port: req.port,
+        token: req.token,
+        management_token: None, // Event doesn't need actual token
+        permission: req.permission,
+        tls_enabled: req.tls_enabled,
+        tls_verify: req.tls_verify,
+        tls_pin: None,
+        srv: None,
+        role: None,
+        bandwidth_cap: None,
+        reporting_schedule: None,
+    }));
+
+    info!("Updated server: {}:{}", req.host, req.port);
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse {
+            success: true,
+            message: format!("Server {}:{} updated successfully", req.host, req.port),
+        }),
+    )
+}
+
+async fn remove_server(
+    State(state): State<Arc<ManagementState>>,
+    Query(query): Query<RemoveServerQuery>,
+) -> (StatusCode, Json<ApiResponse>) {
+    // Remove server from config
+    {
+        let mut config = state.config.write().await;
+        let original_len = config.servers.len();
+        config
+            .servers
+            .retain(|s| !(s.host == query.host && s.port == query.port));
+
+        if config.servers.len() == original_len {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse {
+                    success: false,
+                    message: "Server not found".to_string(),
+                }),
+            );
+        }
+
+        // Don't allow removing all servers
+        if config.servers.is_empty() {
+            // Restore the removed server
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse {
+                    success: false,
+                    message: "Cannot remove the last server".to_string(),
+                }),
+            );
+        }
+
+        // Save to file
+        if let Err(e) = save_config(&config, &state.config_path) {
+            error!("Failed to save config: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse {
+                    success: false,
+                    message: format!("Failed to save config: {e}"),
+                }),
+            );
+        }
+    }
+
+    // Notify about the removal
+    let _ = state
+        .event_tx
+        .send(ServerEvent::Remove(query.host.clone(), query.port));
+
+    info!("Removed server: {}:{}", query.host, query.port);
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse {
+            success: true,
+            message: format!("Server {}:{} removed successfully", query.host, query.port),
+        }),
+    )
+}
+
+/// Save configuration to file (atomic write, owner read/write only)
+fn save_config(config: &Config, path: &Path) -> anyhow::Result<()> {
+    config.save_private(path)
+}
+
+// Connection control handlers
+
+#[derive(Debug, Serialize)]
+struct ConnectionStatusResponse {
+    servers: Vec<ConnectionStatusInfo>,
+}
+
+#[derive(Debug, Serialize)]
+struct ConnectionStatusInfo {
+    server: String,
+    connected: bool,
+    last_error: Option<String>,
+    reconnect_delay_secs: u64,
+    connection_attempts: u32,
+    role: Option<ServerRole>,
+    idle: bool,
+}
+
+impl From<&ConnectionStatus> for ConnectionStatusInfo {
+    fn from(s: &ConnectionStatus) -> Self {
+        Self {
+            server: s.server.clone(),
+            connected: s.connected,
+            last_error: s.last_error.clone(),
+            reconnect_delay_secs: s.reconnect_delay_secs,
+            connection_attempts: s.connection_attempts,
+            role: s.role,
+            idle: s.idle,
+        }
+    }
+}
+
+async fn connection_status(
+    State(state): State<Arc<ManagementState>>,
+) -> (StatusCode, Json<ConnectionStatusResponse>) {
+    match &state.connection_status {
+        Some(status) => {
+            let status_guard = status.read().await;
+            let servers: Vec<ConnectionStatusInfo> = status_guard.iter().map(Into::into).collect();
+            (StatusCode::OK, Json(ConnectionStatusResponse { servers }))
+        }
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ConnectionStatusResponse { servers: vec![] }),
+        ),
+    }
+}
+
+/// The compiled protocol, for `grpcurl -protoset` and generated clients
+async fn proto_descriptor() -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "application/octet-stream"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"nanolink.protoset\"",
+            ),
+        ],
+        crate::proto::FILE_DESCRIPTOR_SET,
+    )
+        .into_response()
+}
+
+async fn agent_telemetry() -> Json<TelemetrySnapshot> {
+    Json(telemetry().snapshot())
+}
+
+/// What `nanolink-agent tui --remote` displays
+async fn host_snapshot(
+    State(state): State<Arc<ManagementState>>,
+) -> Result<Json<Snapshot>, StatusCode> {
+    let source = state.snapshot_source.clone();
+    // Refreshing every process and listing ports blocks for a while
+    tokio::task::spawn_blocking(move || {
+        source
+            .lock()
+            .get_or_insert_with(LocalSource::new)
+            .snapshot()
+    })
+    .await
+    .map(Json)
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn trigger_reconnect(
+    State(state): State<Arc<ManagementState>>,
+) -> (StatusCode, Json<ApiResponse>) {
+    match &state.connection_signal_tx {
+        Some(tx) => match tx.send(ConnectionSignal::ImmediateReconnect) {
+            Ok(receivers) => {
+                info!(
+                    "Triggered immediate reconnect, {} receivers notified",
+                    receivers
+                );
+                (
+                    StatusCode::OK,
+                    Json(ApiResponse {
+                        success: true,
+                        message: format!(
+                            "Immediate reconnect triggered, {receivers} connections notified"
+                        ),
+                    }),
+                )
+            }
+            Err(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse {
+                    success: false,
+                    message: "Failed to send reconnect signal (no active receivers)".to_string(),
+                }),
+            ),
+        },
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ApiResponse {
+                success: false,
+                message: "Connection control not available".to_string(),
+            }),
+        ),
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct BufferStatusResponse {
+    capacity: usize,
+    current_size: usize,
+    current_bytes: usize,
+    max_bytes: Option<usize>,
+    usage_percent: f64,
+    oldest_timestamp: Option<u64>,
+    newest_timestamp: Option<u64>,
+    newest_sequence: Option<u64>,
+    last_sync_sequence: u64,
+    unsynced_count: usize,
+    data_compensation_enabled: bool,
+}
+
+async fn buffer_status(
+    State(state): State<Arc<ManagementState>>,
+) -> (StatusCode, Json<BufferStatusResponse>) {
+    let config = state.config.read().await;
+
+    match &state.buffer {
+        Some(buffer) => (
+            StatusCode::OK,
+            Json(BufferStatusResponse {
+                capacity: buffer.capacity(),
+                current_size: buffer.len(),
+                current_bytes: buffer.current_bytes(),
+                max_bytes: buffer.max_bytes(),
+                usage_percent: buffer.usage_percent(),
+                oldest_timestamp: buffer.oldest_timestamp(),
+                newest_timestamp: buffer.newest_timestamp(),
+                newest_sequence: buffer.newest_sequence(),
+                last_sync_sequence: buffer.get_last_sync_sequence(),
+                unsynced_count: buffer.unsynced_count(),
+                data_compensation_enabled: config.buffer.data_compensation,
+            }),
+        ),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(BufferStatusResponse {
+                capacity: 0,
+                current_size: 0,
+                current_bytes: 0,
+                max_bytes: config.buffer.max_bytes,
+                usage_percent: 0.0,
+                oldest_timestamp: None,
+                newest_timestamp: None,
+                newest_sequence: None,
+                last_sync_sequence: 0,
+                unsynced_count: 0,
+                data_compensation_enabled: config.buffer.data_compensation,
+            }),
+        ),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportQuery {
+    /// `csv` or `parquet`
+    #[serde(default = "default_export_format")]
+    format: String,
+    /// Comma-separated column patterns (e.g. `timestamp,cpu.*`)
+    columns: Option<String>,
+    /// Sample time range, Unix milliseconds (inclusive)
+    from: Option<u64>,
+    to: Option<u64>,
+    #[serde(default)]
+    gzip: bool,
+}
+
+fn default_export_format() -> String {
+    "csv".to_string()
+}
+
+/// Buffered samples as a CSV or Parquet table
+async fn export_history(
+    State(state): State<Arc<ManagementState>>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let Some(buffer) = &state.buffer else {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Buffer not available".to_string(),
+        ));
+    };
+    let samples = buffer.get_all();
+    let content_type = match (query.format.as_str(), query.gzip) {
+        (_, true) => "application/gzip",
+        ("parquet", false) => "application/vnd.apache.parquet",
+        _ => "text/csv; charset=utf-8",
+    };
+
+    // Flattening every sample is CPU-bound
+    let data = tokio::task::spawn_blocking(move || {
+        let samples: Vec<&crate::proto::Metrics> = samples
+            .iter()
+            .map(|m| m.as_ref())
+            .filter(|m| query.from.is_none_or(|from| m.timestamp >= from))
+            .filter(|m| query.to.is_none_or(|to| m.timestamp <= to))
+            .collect();
+        let columns: Vec<String> = query
+            .columns
+            .iter()
+            .flat_map(|c| c.split(','))
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .collect();
+        let table = export::Table::from_records(&samples, &columns)?;
+        export::encode(&table, &query.format, query.gzip)
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(|e| (StatusCode::BAD_REQUEST, format!("{e:#}")))?;
+
+    Ok(([(header::CONTENT_TYPE, content_type)], data).into_response())
+}
+
+/// Current silence, `null` when the host isn't silenced
+async fn silence_status() -> Json<Option<silence::Silence>> {
+    Json(silence::current())
+}
+
+async fn silence_start(
+    Json(req): Json<SilenceRequest>,
+) -> Result<Json<silence::Silence>, (StatusCode, String)> {
+    silence::parse_duration(&req.duration)
+        .and_then(|duration| {
+            silence::start(duration, &req.reason, req.reject_commands, "management")
+        })
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+/// End the silence, returning it (`null` if there was none)
+async fn silence_stop() -> Json<Option<silence::Silence>> {
+    Json(silence::stop("management"))
+}
+
+/// Exchange a pairing code for a management token of the app's own
+async fn pair_claim(
+    State(state): State<Arc<ManagementState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(req): Json<pairing::ClaimRequest>,
+) -> Result<Json<pairing::ClaimResponse>, (StatusCode, String)> {
+    let name = match req.name.trim() {
+        "" => addr.ip().to_string(),
+        name => name.chars().take(64).collect(),
+    };
+    let claimed_by = name.clone();
+    let permission = tokio::task::spawn_blocking(move || pairing::claim(&req.secret, &claimed_by))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| {
+            warn!("Pairing from {} rejected: {}", addr.ip(), e);
+            (StatusCode::FORBIDDEN, e)
+        })?;
+
+    let token = token::generate_secure_token(Some("pair"));
+    let mut config = state.config.write().await;
+    config.management.paired_clients.push(PairedClient {
+        name: name.clone(),
+        token_sha256: pairing::sha256_hex(&token),
+        permission,
+        paired_at: chrono::Utc::now().to_rfc3339(),
+    });
+    if let Err(e) = save_config(&config, &state.config_path) {
+        error!("Failed to save config after pairing: {}", e);
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to save config: {e}"),
+        ));
+    }
+    info!(
+        "Paired {} from {} with permission {}",
+        name,
+        addr.ip(),
+        permission
+    );
+
+    Ok(Json(pairing::ClaimResponse {
+        token,
+        permission,
+        agent_id: config.agent.agent_id.clone().unwrap_or_default(),
+        hostname: config.get_hostname(),
+    }))
+}
+
+// Token rotation types and handler
+
+#[derive(Debug, Deserialize)]
+struct RotateTokenRequest {
+    /// Server host to rotate token for (must match requesting server)
+    server_host: String,
+    /// Server port
+    #[serde(default = "default_grpc_port")]
+    server_port: u16,
+}
+
+#[derive(Debug, Serialize)]
+struct RotateTokenResponse {
+    success: bool,
+    message: String,
+    /// New token (only returned on success)
+    new_token: Option<String>,
+    /// When the old token expires (immediate = now)
+    old_token_expires_at: Option<String>,
+}
+
+/// Rotate management token for a server
+/// This endpoint requires permission level 3 (SYSTEM_ADMIN)
+async fn rotate_token(
+    State(state): State<Arc<ManagementState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<RotateTokenRequest>,
+) -> (StatusCode, Json<RotateTokenResponse>) {
+    // Extract current token from auth header to identify the calling server
+    let auth_header = headers.get("Authorization").and_then(|v| v.to_str().ok());
+    let current_token = match auth_header {
+        Some(header) if header.starts_with("Bearer ") => &header[7..],
+        _ => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(RotateTokenResponse {
+                    success: false,
+                    message: "Missing Authorization header".to_string(),
+                    new_token: None,
+                    old_token_expires_at: None,
+                }),
+            );
+        }
+    };
+
+    let source_ip = addr.ip();
+
+    // Find the server making the request and verify it matches the requested server
+    let mut config = state.config.write().await;
+
+    // SECURITY: Use constant-time comparison to prevent timing attacks
+    let server_idx = config.servers.iter().position(|s| {
+        s.host == req.server_host
+            && s.port == req.server_port
+            && s.management_token.as_ref().is_some_and(|t| {
+                subtle::ConstantTimeEq::ct_eq(current_token.as_bytes(), t.as_bytes()).into()
+            })
+    });
+
+    let idx = match server_idx {
+        Some(i) => i,
+        None => {
+            warn!(
+                "Token rotation failed: server {}:{} not found or token mismatch from {}",
+                req.server_host, req.server_port, source_ip
+            );
+            return (
+                StatusCode::FORBIDDEN,
+                Json(RotateTokenResponse {
+                    success: false,
+                    message: "Server not found or token mismatch".to_string(),
+                    new_token: None,
+                    old_token_expires_at: None,
+                }),
+            );
+        }
+    };
+
+    // SECURITY: Verify source IP matches the server host
+    if !verify_source_ip(&config.servers[idx].host, source_ip).await {
+        warn!(
+            "Token rotation failed: IP mismatch - token for {} used from {}",
+            config.servers[idx].host, source_ip
+        );
+        return (
+            StatusCode::FORBIDDEN,
+            Json(RotateTokenResponse {
+                success: false,
+                message: format!(
+                    "Source IP {} does not match server {}",
+                    source_ip, config.servers[idx].host
+                ),
+                new_token: None,
+                old_token_expires_at: None,
+            }),
+        );
+    }
+
+    // Verify permission level
+    if config.servers[idx].permission < 3 {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(RotateTokenResponse {
+                success: false,
+                message: "Token rotation requires permission level 3".to_string(),
+                new_token: None,
+                old_token_expires_at: None,
+            }),
+        );
+    }
+
+    // Generate new token
+    let new_token = token::generate_secure_token(Some("mgmt"));
+    let now = chrono::Utc::now();
+    let expires_at = now.to_rfc3339();
+
+    // Update the server's management token
+    config.servers[idx].management_token = Some(new_token.clone());
+
+    // Save config to file
+    if let Err(e) = save_config(&config, &state.config_path) {
+        error!("Failed to save config after token rotation: {}", e);
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(RotateTokenResponse {
+                success: false,
+                message: format!("Failed to save config: {e}"),
+                new_token: None,
+                old_token_expires_at: None,
+            }),
+        );
+    }
+
+    info!(
+        "Management token rotated for server {}:{} from {}",
+        req.server_host, req.server_port, source_ip
+    );
+
+    (
+        StatusCode::OK,
+        Json(RotateTokenResponse {
+            success: true,
+            message: format!(
+                "Token rotated for {}:{}. Old token invalidated immediately.",
+                req.server_host, req.server_port
+            ),
+            new_token: Some(new_token),
+            old_token_expires_at: Some(expires_at),
+        }),
+    )
+}
+
+/// Generate and assign management token for a server if needed
+/// Called when permission level is set to >= 1
+#[allow(dead_code)]
+pub fn ensure_management_token(server: &mut ServerConfig) -> Option<String> {
+    if server.permission >= 1 && server.management_token.is_none() {
+        let new_token = token::generate_secure_token(Some("mgmt"));
+        server.management_token = Some(new_token.clone());
+        Some(new_token)
+    } else {
+        None
+    }
+}
+
+/// Remove management token when permission is downgraded to 0
+#[allow(dead_code)]
+pub fn clear_management_token_if_needed(server: &mut ServerConfig) -> bool {
+    if server.permission == 0 && server.management_token.is_some() {
+        server.management_token = None;
+        true
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn servers(connected: usize) -> ServersCheck {
+        ServersCheck {
+            ok: connected > 0,
+            connected,
+            configured: 2,
+        }
+    }
+
+    fn collector(ok: bool) -> CollectorCheck {
+        CollectorCheck {
+            ok,
+            last_sample_age_seconds: Some(1),
+            stale_after_seconds: 10,
+        }
+    }
+
+    #[test]
+    fn test_local_api_token() {
+        let management = crate::config::ManagementConfig {
+            api_token: Some("local-secret".to_string()),
+            ..Default::default()
+        };
+        let loopback = "127.0.0.1".parse().unwrap();
+        assert!(is_local_api_token(&management, "local-secret", loopback));
+        assert!(is_local_api_token(
+            &management,
+            "local-secret",
+            "::1".parse().unwrap()
+        ));
+        assert!(!is_local_api_token(&management, "wrong", loopback));
+        assert!(!is_local_api_token(
+            &management,
+            "local-secret",
+            "10.0.0.5".parse().unwrap()
+        ));
+        let unset = crate::config::ManagementConfig::default();
+        assert!(!is_local_api_token(&unset, "", loopback));
+
+        let allowed = |path| local_token_allowed(path, get_required_permission(path));
+        assert!(allowed("/api/connection/status"));
+        assert!(allowed("/api/snapshot"));
+        assert!(allowed("/api/export"));
+        assert!(allowed("/api/silence"));
+        assert!(allowed("/api/silence/start"));
+        assert!(allowed("/api/silence/stop"));
+        for path in [
+            "/api/config",
+            "/api/servers",
+            "/api/connection/reconnect",
+            "/api/shell",
+            "/api/token/rotate",
+        ] {
+            assert!(!allowed(path), "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_local_token_rejected_for_admin_endpoints() {
+        use axum::body::Body;
+        use tower::ServiceExt;
+
+        let mut config = Config::sample();
+        config.management.api_token = Some("local-secret".to_string());
+        let (server, _events) =
+            ManagementServer::new(Arc::new(RwLock::new(config)), PathBuf::new(), 0);
+        let app = Router::new()
+            .route("/api/shell", post(|| async {}))
+            .route("/api/export", get(|| async {}))
+            .layer(middleware::from_fn_with_state(
+                server.state.clone(),
+                auth_middleware,
+            ))
+            .with_state(server.state);
+        let request = |method: &str, path: &str| {
+            let mut request = axum::http::Request::builder()
+                .method(method)
+                .uri(path)
+                .header("Authorization", "Bearer local-secret")
+                .body(Body::empty())
+                .unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 50000))));
+            request
+        };
+
+        let shell = app.clone().oneshot(request("POST", "/api/shell")).await;
+        assert_eq!(shell.unwrap().status(), StatusCode::FORBIDDEN);
+        let export = app.oneshot(request("GET", "/api/export")).await;
+        assert_eq!(export.unwrap().status(), StatusCode::OK);
+    }
+
+    /// What `nanolink-agent silence` does without `--remote`
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_local_silence_command() {
+        use crate::management::client::ApiClient;
+
+        let mut config = Config::sample();
+        config.management.api_token = Some("local-secret".to_string());
+        let (server, _events) =
+            ManagementServer::new(Arc::new(RwLock::new(config)), PathBuf::new(), 0);
+        let app = Router::new()
+            .route("/api/silence", get(silence_status))
+            .route("/api/silence/start", post(silence_start))
+            .route("/api/silence/stop", post(silence_stop))
+            .route("/api/shell", post(|| async {}))
+            .layer(middleware::from_fn_with_state(
+                server.state.clone(),
+                auth_middleware,
+            ))
+            .with_state(server.state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let management = crate::config::ManagementConfig {
+            enabled: true,
+            port: listener.local_addr().unwrap().port(),
+            bind_address: "127.0.0.1".to_string(),
+            api_token: Some("local-secret".to_string()),
+            ..Default::default()
+        };
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+        });
+
+        tokio::task::spawn_blocking(move || {
+            let client = ApiClient::local(&management).unwrap();
+            let request = SilenceRequest {
+                duration: "2h".to_string(),
+                reason: "patching".to_string(),
+                reject_commands: false,
+            };
+            let started: silence::Silence = client.post("/api/silence/start", &request).unwrap();
+            assert_eq!(started.reason, "patching");
+            let status: Option<silence::Silence> = client.get("/api/silence").unwrap();
+            assert_eq!(status, Some(started.clone()));
+            let ended: Option<silence::Silence> = client.post("/api/silence/stop", &()).unwrap();
+            assert_eq!(ended, Some(started));
+            assert!(client.post::<_, ()>("/api/shell", &()).is_err());
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_save_keeps_overlays_out_of_main_file() {
+        use axum::body::Body;
+        use tower::ServiceExt;
+
+        let dir = std::env::temp_dir().join(format!("nanolink-mgmt-save-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("conf.d")).unwrap();
+        let path = dir.join("nanolink.yaml");
+        std::fs::write(
+            &path,
+            "agent: {agent_id: test}\nservers: [{host: 10.0.0.1, token: a}]\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("conf.d/50-host.yaml"), "logging: {level: debug}\n").unwrap();
+
+        let config = Config::load(&path).unwrap();
+        let (server, _events) =
+            ManagementServer::new(Arc::new(RwLock::new(config)), path.clone(), 0);
+        let app = Router::new()
+            .route("/api/servers", post(add_server))
+            .with_state(server.state);
+        let request = axum::http::Request::post("/api/servers")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"host": "10.0.0.2", "token": "b"}"#))
+            .unwrap();
+        assert_eq!(app.oneshot(request).await.unwrap().status(), StatusCode::OK);
+
+        let main: serde_yaml::Value =
+            serde_yaml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(main["servers"].as_sequence().unwrap().len(), 2);
+        assert!(main.get("logging").is_none());
+        assert_eq!(Config::load(&path).unwrap().logging.level, "debug");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_save_config_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("nanolink-mgmt-mode-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("nanolink.yaml");
+        std::fs::write(&path, "").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        // A stale temp file doesn't lend its permissions
+        std::fs::write(path.with_extension("tmp"), "").unwrap();
+
+        let mut config = Config::sample();
+        config.servers[0].token = "secret".to_string();
+        save_config(&config, &path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(Config::load(&path).unwrap().servers[0].token, "secret");
+        assert!(!path.with_extension("tmp").exists());
+
+        assert!(save_config(&config, &dir.join("missing/nanolink.yaml")).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_save_refuses_servers_from_drop_in() {
+        use axum::body::Body;
+        use tower::ServiceExt;
+
+        let dir = std::env::temp_dir().join(format!("nanolink-mgmt-dropin-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("conf.d")).unwrap();
+        let path = dir.join("nanolink.yaml");
+        std::fs::write(&path, "agent: {agent_id: test}\nlogging: {level: info}\n").unwrap();
+        let drop_in = dir.join("conf.d/50-servers.yaml");
+        std::fs::write(&drop_in, "servers: [{host: 10.0.0.1, token: a}]\n").unwrap();
+
+        let config = Config::load(&path).unwrap();
+        let main = std::fs::read_to_string(&path).unwrap();
+        let (server, _events) =
+            ManagementServer::new(Arc::new(RwLock::new(config)), path.clone(), 0);
+        let app = Router::new()
+            .route("/api/servers", post(add_server))
+            .with_state(server.state);
+        let request = axum::http::Request::post("/api/servers")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"host": "10.0.0.2", "token": "b"}"#))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let message = String::from_utf8_lossy(&body);
+        assert!(message.contains("50-servers.yaml"), "{message}");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), main);
+
+        // Other changes still save, and the drop-in's servers survive a reload
+        let mut config = Config::load(&path).unwrap();
+        config.logging.level = "debug".to_string();
+        save_config(&config, &path).unwrap();
+        let reloaded = Config::load(&path).unwrap();
+        assert_eq!(reloaded.logging.level, "debug");
+        assert_eq!(reloaded.servers.len(), 1);
+        assert_eq!(reloaded.servers[0].host, "10.0.0.1");
+        let main: serde_yaml::Value =
+            serde_yaml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(main.get("servers").is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_readiness_status() {
+        let buffer = || BufferCheck {
+            ok: true,
+            usage_percent: 12.0,
+        };
+        let ready = ReadinessResponse::new(servers(1), collector(true), buffer());
+        assert_eq!(ready.status, "ready");
+        assert_eq!(ready.status_code(), StatusCode::OK);
+
+        for response in [
+            ReadinessResponse::new(servers(0), collector(true), buffer()),
+            ReadinessResponse::new(servers(1), collector(false), buffer()),
+        ] {
+            assert_eq!(response.status, "not_ready");
+            assert_eq!(response.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+        }
+
+        let json = serde_json::to_value(&ready).unwrap();
+        assert_eq!(json["checks"]["servers"]["connected"], 1);
+        assert_eq!(json["checks"]["collector"]["stale_after_seconds"], 10);
+    }
+
+    #[test]
+    fn test_proto_descriptor() {
+        use prost::Message;
+
+        let set =
+            prost_types::FileDescriptorSet::decode(crate::proto::FILE_DESCRIPTOR_SET).unwrap();
+        let file = set.file.iter().find(|f| f.package() == "nanolink").unwrap();
+        assert!(file.service.iter().any(|s| s.name() == "NanoLinkService"));
+        assert_eq!(get_required_permission("/api/proto/descriptor"), 0);
+    }
+}
@@ -12,6 +12,7 @@ pub fn generate_management_token() -> String {
 }
 
 /// Generate a cryptographically secure token with specified length
+#[cfg_attr(not(feature = "management"), allow(dead_code))]
 pub fn generate_secure_token(prefix: Option<&str>) -> String {
     let token = Uuid::new_v4().to_string().replace("-", "");
     match prefix {
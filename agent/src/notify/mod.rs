@@ -0,0 +1,588 @@
+//! Notifications: alerts and connection events pushed to outside tools
+//!
+//! With `notifications.enabled`, events published by the alert rules,
+//! anomaly detection and disk forecast (see [`crate::collector::syslog`]) are
+//! forwarded to every configured webhook, and open and resolve incidents in
+//! PagerDuty and Opsgenie. When `connection_events` is on, a
+//! server that stays disconnected for `connection_grace_secs` raises a
+//! "lost" notification, followed by "restored" once it reconnects.
+//!
+//! Incidents are keyed by host and rule (or metric, mount point, server), so
+//! repeated firings update one incident and the resolution closes it.
+//!
+//! Each destination has its own queue and worker, so a slow or failing
+//! endpoint doesn't hold up the others. Failed deliveries are retried with
+//! exponential backoff; beyond `max_per_minute` notifications are dropped and
+//! the count is reported with the next one that goes out.
+
+mod opsgenie;
+mod pagerduty;
+mod webhook;
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::sync::{RwLock, broadcast, mpsc};
+use tracing::{debug, info, warn};
+
+use crate::collector::syslog;
+use crate::config::Config;
+use crate::connection::ConnectionStatus;
+use crate::connection::privacy;
+use crate::proto::LogEntry;
+use crate::silence;
+use crate::utils::http;
+
+/// Notifications queued per destination before new ones are dropped
+const QUEUE_CAPACITY: usize = 256;
+
+/// How often connection status is checked
+const CONNECTION_POLL: Duration = Duration::from_secs(5);
+
+/// First retry delay, doubled after each attempt
+const RETRY_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Longest wait between retries
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Request timeout per attempt
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Metadata naming what an event is about, in order of preference
+const SUBJECT_KEYS: [&str; 4] = ["rule", "metric", "mount_point", "server"];
+
+/// One event as delivered to a destination
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Notification {
+    /// Event source: "alerts", "anomaly", "disk_forecast" or "connection"
+    pub source: String,
+    /// "firing"/"resolved" for alerts, "lost"/"restored" for connections
+    pub state: String,
+    /// info, warning, error or critical
+    pub level: String,
+    pub title: String,
+    pub message: String,
+    pub hostname: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    /// RFC 3339
+    pub timestamp: String,
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl Notification {
+    fn from_event(source: &str, entry: &LogEntry, host: &Host) -> Self {
+        let state = entry
+            .metadata
+            .get("state")
+            .cloned()
+            .unwrap_or_else(|| "firing".to_string());
+        let subject = SUBJECT_KEYS
+            .iter()
+            .find_map(|key| entry.metadata.get(*key))
+            .map_or(source, String::as_str);
+        Self {
+            source: source.to_string(),
+            title: format!(
+                "[{}] {} on {}",
+                state.to_uppercase(),
+                subject,
+                host.hostname
+            ),
+            state,
+            level: entry.level.clone(),
+            message: entry.message.clone(),
+            hostname: host.hostname.clone(),
+            agent_id: host.agent_id.clone(),
+            timestamp: entry.timestamp.clone(),
+            metadata: entry
+                .metadata
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        }
+    }
+
+    fn connection(server: &str, lost: bool, detail: &str, host: &Host) -> Self {
+        let (state, level) = if lost {
+            ("lost", "error")
+        } else {
+            ("restored", "info")
+        };
+        let message = if lost {
+            format!("Connection to {server} lost: {detail}")
+        } else {
+            format!("Connection to {server} restored")
+        };
+        Self {
+            source: "connection".to_string(),
+            state: state.to_string(),
+            level: level.to_string(),
+            title: format!(
+                "[{}] {} connection on {}",
+                state.to_uppercase(),
+                server,
+                host.hostname
+            ),
+            message,
+            hostname: host.hostname.clone(),
+            agent_id: host.agent_id.clone(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            metadata: BTreeMap::from([
+                ("server".to_string(), server.to_string()),
+                ("state".to_string(), state.to_string()),
+            ]),
+        }
+    }
+
+    /// Whether this clears an earlier notification
+    pub fn is_recovery(&self) -> bool {
+        matches!(self.state.as_str(), "resolved" | "restored")
+    }
+
+    /// Stable key shared by the firing and resolved notifications of one
+    /// condition on one host
+    pub fn dedup_key(&self) -> String {
+        let subject = SUBJECT_KEYS
+            .iter()
+            .find_map(|key| self.metadata.get(*key))
+            .map_or(self.source.as_str(), String::as_str);
+        format!("nanolink/{}/{}/{}", self.hostname, self.source, subject)
+    }
+}
+
+/// Numeric order of levels; unknown levels rank as info
+pub fn level_rank(level: &str) -> u8 {
+    match level.to_ascii_lowercase().as_str() {
+        "warning" | "warn" => 1,
+        "error" => 2,
+        "critical" | "fatal" | "emergency" => 3,
+        _ => 0,
+    }
+}
+
+/// Why a delivery attempt failed
+#[derive(Debug)]
+pub enum SendError {
+    /// Network error, throttling or server error: worth another attempt
+    Retry(String),
+    /// Rejected request: retrying won't help
+    Fatal(String),
+}
+
+/// POST a JSON body, classifying failures for retry
+fn post_json(url: &str, headers: &[(&str, &str)], body: &str) -> Result<(), SendError> {
+    let mut all = vec![("Content-Type", "application/json")];
+    all.extend_from_slice(headers);
+    let response = http::send("POST", url, &all, body.as_bytes(), TIMEOUT)
+        .map_err(|e| SendError::Retry(e.to_string()))?;
+    match response.status {
+        200..=299 => Ok(()),
+        429 | 500..=599 => Err(SendError::Retry(format!("HTTP {}", response.status))),
+        status => Err(SendError::Fatal(format!(
+            "HTTP {status}: {}",
+            response.text().chars().take(200).collect::<String>()
+        ))),
+    }
+}
+
+/// Cut `s` to `max_chars`, marking the cut with an ellipsis
+fn truncate(s: &str, max_chars: usize) -> String {
+    if s.chars().count() <= max_chars {
+        s.to_string()
+    } else {
+        let mut out: String = s.chars().take(max_chars - 1).collect();
+        out.push('…');
+        out
+    }
+}
+
+/// Incidents opened by an incident provider, so only conditions that were
+/// raised there get resolved
+#[derive(Default)]
+struct OpenIncidents(Mutex<HashSet<String>>);
+
+impl OpenIncidents {
+    /// Whether `notification` should go out: at or above `min_level` to open
+    /// an incident, or a recovery closing one that was opened
+    fn accepts(&self, notification: &Notification, min_level: &str) -> bool {
+        let key = notification.dedup_key();
+        if notification.is_recovery() {
+            self.0.lock().remove(&key)
+        } else if level_rank(&notification.level) >= level_rank(min_level) {
+            self.0.lock().insert(key);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// A notification destination
+trait Sink: Send + Sync + 'static {
+    fn name(&self) -> &str;
+
+    /// Whether this destination wants the notification at all
+    fn accepts(&self, notification: &Notification) -> bool;
+
+    /// Deliver once (blocking)
+    fn send(&self, notification: &Notification) -> Result<(), SendError>;
+}
+
+/// Identity reported in every notification
+struct Host {
+    hostname: String,
+    agent_id: Option<String>,
+}
+
+/// Sliding one-minute window of sent notifications
+struct RateLimiter {
+    max_per_minute: u32,
+    sent: VecDeque<Instant>,
+    suppressed: u64,
+}
+
+impl RateLimiter {
+    fn new(max_per_minute: u32) -> Self {
+        Self {
+            max_per_minute,
+            sent: VecDeque::new(),
+            suppressed: 0,
+        }
+    }
+
+    /// Whether a notification may go out now; if so, returns how many were
+    /// suppressed since the last one that did
+    fn allow(&mut self, now: Instant) -> Option<u64> {
+        while self
+            .sent
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= Duration::from_secs(60))
+        {
+            self.sent.pop_front();
+        }
+        if self.max_per_minute > 0 && self.sent.len() >= self.max_per_minute as usize {
+            self.suppressed += 1;
+            return None;
+        }
+        self.sent.push_back(now);
+        Some(std::mem::take(&mut self.suppressed))
+    }
+}
+
+/// Start a worker for `sink`, returning its queue
+fn spawn_worker(
+    sink: Arc<dyn Sink>,
+    max_per_minute: u32,
+    retries: u32,
+) -> mpsc::Sender<Notification> {
+    let (tx, mut rx) = mpsc::channel::<Notification>(QUEUE_CAPACITY);
+    tokio::spawn(async move {
+        let mut limiter = RateLimiter::new(max_per_minute);
+        while let Some(mut notification) = rx.recv().await {
+            let Some(suppressed) = limiter.allow(Instant::now()) else {
+                debug!("Notification to {} rate limited", sink.name());
+                continue;
+            };
+            if suppressed > 0 {
+                notification.message.push_str(&format!(
+                    "\n({suppressed} earlier notifications suppressed by rate limit)"
+                ));
+                notification
+                    .metadata
+                    .insert("suppressed".to_string(), suppressed.to_string());
+            }
+            deliver(&sink, notification, retries).await;
+        }
+    });
+    tx
+}
+
+async fn deliver(sink: &Arc<dyn Sink>, notification: Notification, retries: u32) {
+    let notification = Arc::new(notification);
+    let mut backoff = RETRY_BACKOFF;
+    for attempt in 0..=retries {
+        let (sink_ref, n) = (sink.clone(), notification.clone());
+        let result = tokio::task::spawn_blocking(move || sink_ref.send(&n))
+            .await
+            .unwrap_or_else(|e| Err(SendError::Fatal(e.to_string())));
+        match result {
+            Ok(()) => {
+                debug!("Notification sent to {}", sink.name());
+                return;
+            }
+            Err(SendError::Retry(e)) if attempt < retries => {
+                debug!(
+                    "Notification to {} failed ({}), retrying in {:?}",
+                    sink.name(),
+                    e,
+                    backoff
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_RETRY_BACKOFF);
+            }
+            Err(SendError::Retry(e)) | Err(SendError::Fatal(e)) => {
+                warn!("Notification to {} failed: {}", sink.name(), e);
+                return;
+            }
+        }
+    }
+}
+
+/// Per-server state for connection events
+struct LinkState {
+    /// Since when the server has been disconnected
+    down_since: Option<Instant>,
+    /// Whether "lost" was sent for the current outage
+    notified: bool,
+}
+
+/// Compare connection status with the previous poll and return the events
+/// due: "lost" once a server has been down for `grace`, "restored" when a
+/// server reported lost reconnects. While `silenced`, "lost" is held back
+/// until the silence ends. An idle standby is not down.
+fn connection_events(
+    links: &mut HashMap<String, LinkState>,
+    status: &[ConnectionStatus],
+    grace: Duration,
+    now: Instant,
+    started: Instant,
+    silenced: bool,
+) -> Vec<(String, bool, String)> {
+    let mut events = Vec::new();
+    for server in status {
+        if server.idle {
+            if links
+                .remove(&server.server)
+                .is_some_and(|link| link.notified)
+            {
+                events.push((server.server.clone(), false, String::new()));
+            }
+            continue;
+        }
+        // A server not seen connected yet has been down since startup
+        let link = links.entry(server.server.clone()).or_insert(LinkState {
+            down_since: Some(started),
+            notified: false,
+        });
+        if server.connected {
+            if std::mem::take(&mut link.notified) {
+                events.push((server.server.clone(), false, String::new()));
+            }
+            link.down_since = None;
+            continue;
+        }
+        let since = *link.down_since.get_or_insert(now);
+        if !silenced && !link.notified && now.duration_since(since) >= grace {
+            link.notified = true;
+            let detail = server
+                .last_error
+                .clone()
+                .unwrap_or_else(|| "not connected".to_string());
+            events.push((server.server.clone(), true, detail));
+        }
+    }
+    events
+}
+
+/// Forward events and connection changes to the configured destinations
+/// until the task is aborted
+pub async fn run(config: Arc<Config>, connection_status: Arc<RwLock<Vec<ConnectionStatus>>>) {
+    let cfg = &config.notifications;
+    let webhooks = cfg
+        .webhooks
+        .iter()
+        .map(|c| Arc::new(webhook::Webhook::new(c.clone())) as Arc<dyn Sink>);
+    let pagerduty = cfg
+        .pagerduty
+        .iter()
+        .map(|c| Arc::new(pagerduty::PagerDuty::new(c.clone())) as Arc<dyn Sink>);
+    let opsgenie = cfg
+        .opsgenie
+        .iter()
+        .map(|c| Arc::new(opsgenie::Opsgenie::new(c.clone())) as Arc<dyn Sink>);
+    let sinks: Vec<(Arc<dyn Sink>, mpsc::Sender<Notification>)> = webhooks
+        .chain(pagerduty)
+        .chain(opsgenie)
+        .map(|sink| {
+            let tx = spawn_worker(sink.clone(), cfg.max_per_minute, cfg.retries);
+            (sink, tx)
+        })
+        .collect();
+    if sinks.is_empty() {
+        warn!("Notifications enabled but no destinations configured");
+        return;
+    }
+    info!("Notifications enabled ({} destinations)", sinks.len());
+
+    let host = Host {
+        hostname: privacy::hostname(
+            hostname::get()
+                .map(|h| h.to_string_lossy().to_string())
+                .unwrap_or_else(|_| "unknown".to_string()),
+        ),
+        agent_id: config.agent.agent_id.clone(),
+    };
+    let dispatch = |notification: Notification| {
+        for (sink, tx) in &sinks {
+            if sink.accepts(&notification) && tx.try_send(notification.clone()).is_err() {
+                warn!("Notification queue for {} full, dropping", sink.name());
+            }
+        }
+    };
+
+    let mut events = syslog::subscribe();
+    let mut poll = tokio::time::interval(CONNECTION_POLL);
+    let grace = Duration::from_secs(cfg.connection_grace_secs);
+    let started = Instant::now();
+    let mut links = HashMap::new();
+    loop {
+        tokio::select! {
+            batch = events.recv() => match batch {
+                Ok(batch) => {
+                    if cfg.sources.contains(&batch.source) {
+                        for entry in &batch.entries {
+                            dispatch(Notification::from_event(&batch.source, entry, &host));
+                        }
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("Notifications lagged, {} event batches skipped", n);
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            _ = poll.tick(), if cfg.connection_events => {
+                let status = connection_status.read().await.clone();
+                for (server, lost, detail) in
+                    connection_events(
+                        &mut links,
+                        &status,
+                        grace,
+                        Instant::now(),
+                        started,
+                        silence::is_active(),
+                    )
+                {
+                    dispatch(Notification::connection(&server, lost, &detail, &host));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host() -> Host {
+        Host {
+            hostname: "web-01".to_string(),
+            agent_id: None,
+        }
+    }
+
+    #[test]
+    fn test_from_event() {
+        let entry = LogEntry {
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            level: "critical".to_string(),
+            source: "rules".to_string(),
+            message: "CPU above 90% (firing)".to_string(),
+            metadata: HashMap::from([
+                ("rule".to_string(), "cpu_high".to_string()),
+                ("state".to_string(), "firing".to_string()),
+            ]),
+        };
+        let n = Notification::from_event("alerts", &entry, &host());
+        assert_eq!(n.title, "[FIRING] cpu_high on web-01");
+        assert_eq!(n.level, "critical");
+        assert!(!n.is_recovery());
+        assert_eq!(n.dedup_key(), "nanolink/web-01/alerts/cpu_high");
+
+        let resolved = Notification::connection("srv:39100", false, "", &host());
+        let lost = Notification::connection("srv:39100", true, "refused", &host());
+        assert_eq!(resolved.dedup_key(), lost.dedup_key());
+    }
+
+    #[test]
+    fn test_open_incidents() {
+        let incidents = OpenIncidents::default();
+        let lost = Notification::connection("srv:39100", true, "refused", &host());
+        let restored = Notification::connection("srv:39100", false, "", &host());
+        assert!(!incidents.accepts(&restored, "error"));
+        assert!(!incidents.accepts(&lost, "critical"));
+        assert!(!incidents.accepts(&restored, "critical"));
+        assert!(incidents.accepts(&lost, "error"));
+        assert!(incidents.accepts(&restored, "error"));
+        assert!(!incidents.accepts(&restored, "error"));
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new(2);
+        assert_eq!(limiter.allow(start), Some(0));
+        assert_eq!(limiter.allow(start), Some(0));
+        assert_eq!(limiter.allow(start), None);
+        assert_eq!(limiter.allow(start + Duration::from_secs(30)), None);
+        assert_eq!(limiter.allow(start + Duration::from_secs(60)), Some(2));
+    }
+
+    #[test]
+    fn test_connection_events() {
+        let status = |connected| {
+            vec![ConnectionStatus {
+                server: "srv:39100".to_string(),
+                connected,
+                last_error: Some("refused".to_string()),
+                reconnect_delay_secs: 1,
+                connection_attempts: 1,
+                role: None,
+                idle: false,
+            }]
+        };
+        let grace = Duration::from_secs(60);
+        let t0 = Instant::now();
+        let mut links = HashMap::new();
+
+        assert!(connection_events(&mut links, &status(true), grace, t0, t0, false).is_empty());
+        let t1 = t0 + Duration::from_secs(10);
+        assert!(connection_events(&mut links, &status(false), grace, t1, t0, false).is_empty());
+        // Down since the first failed poll, not since startup
+        let t2 = t1 + Duration::from_secs(59);
+        assert!(connection_events(&mut links, &status(false), grace, t2, t0, false).is_empty());
+        let t3 = t1 + Duration::from_secs(60);
+        let events = connection_events(&mut links, &status(false), grace, t3, t0, false);
+        assert_eq!(
+            events,
+            vec![("srv:39100".to_string(), true, "refused".to_string())]
+        );
+        assert!(connection_events(&mut links, &status(false), grace, t3, t0, false).is_empty());
+        let events = connection_events(&mut links, &status(true), grace, t3, t0, false);
+        assert_eq!(
+            events,
+            vec![("srv:39100".to_string(), false, String::new())]
+        );
+
+        // Silenced: held back, then raised once the silence is over
+        let t4 = t3 + Duration::from_secs(120);
+        assert!(connection_events(&mut links, &status(false), grace, t3, t0, true).is_empty());
+        assert!(connection_events(&mut links, &status(false), grace, t4, t0, true).is_empty());
+        assert_eq!(
+            connection_events(&mut links, &status(false), grace, t4, t0, false).len(),
+            1
+        );
+
+        // A standby going idle is no longer down
+        let mut idle = status(false);
+        idle[0].idle = true;
+        assert_eq!(
+            connection_events(&mut links, &idle, grace, t4, t0, false),
+            vec![("srv:39100".to_string(), false, String::new())]
+        );
+        assert!(connection_events(&mut links, &idle, grace, t4 + grace * 10, t0, false).is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+//! Opsgenie destination (Alert API)
+//!
+//! Firing events create an alert whose alias is the notification's dedup
+//! key, so Opsgenie counts repeats on the open alert; resolutions close it by
+//! alias.
+
+use serde_json::{Value, json};
+
+use super::{Notification, OpenIncidents, SendError, Sink, level_rank, post_json, truncate};
+use crate::config::{OpsgenieConfig, OpsgenieRegion};
+use crate::utils::http::encode_query;
+
+pub struct Opsgenie {
+    config: OpsgenieConfig,
+    name: String,
+    open: OpenIncidents,
+}
+
+impl Opsgenie {
+    pub fn new(config: OpsgenieConfig) -> Self {
+        let name = if config.name.is_empty() {
+            "opsgenie".to_string()
+        } else {
+            config.name.clone()
+        };
+        Self {
+            config,
+            name,
+            open: OpenIncidents::default(),
+        }
+    }
+}
+
+impl Sink for Opsgenie {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn accepts(&self, notification: &Notification) -> bool {
+        self.open.accepts(notification, &self.config.min_level)
+    }
+
+    fn send(&self, notification: &Notification) -> Result<(), SendError> {
+        let (url, body) = request(&self.config, notification);
+        let auth = format!("GenieKey {}", self.config.api_key);
+        post_json(&url, &[("Authorization", &auth)], &body.to_string())
+    }
+}
+
+/// URL and body creating or closing the alert for `n`
+fn request(config: &OpsgenieConfig, n: &Notification) -> (String, Value) {
+    let base = match config.region {
+        OpsgenieRegion::Us => "https://api.opsgenie.com/v2/alerts",
+        OpsgenieRegion::Eu => "https://api.eu.opsgenie.com/v2/alerts",
+    };
+    // Aliases are limited to 512 characters
+    let alias = truncate(&n.dedup_key(), 512);
+    if n.is_recovery() {
+        return (
+            format!("{base}/{}/close?identifierType=alias", encode_query(&alias)),
+            json!({"source": "NanoLink", "note": n.message}),
+        );
+    }
+    let mut tags = config.tags.clone();
+    tags.push(n.source.clone());
+    (
+        base.to_string(),
+        json!({
+            "message": truncate(&format!("{}: {}", n.hostname, n.message), 130),
+            "alias": alias,
+            "description": truncate(&n.message, 15000),
+            "priority": priority(&n.level),
+            "source": "NanoLink",
+            "entity": n.hostname,
+            "tags": tags,
+            "details": n.metadata,
+        }),
+    )
+}
+
+/// Opsgenie priority for a notification level
+fn priority(level: &str) -> &'static str {
+    match level_rank(level) {
+        3 => "P1",
+        2 => "P2",
+        1 => "P3",
+        _ => "P5",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_request() {
+        let config = OpsgenieConfig {
+            name: String::new(),
+            api_key: "key".to_string(),
+            min_level: "critical".to_string(),
+            region: OpsgenieRegion::Eu,
+            tags: vec!["prod".to_string()],
+        };
+        let mut n = Notification {
+            source: "disk_forecast".to_string(),
+            state: "firing".to_string(),
+            level: "warning".to_string(),
+            title: "[FIRING] / on web-01".to_string(),
+            message: "/ full in 2.5 days".to_string(),
+            hostname: "web-01".to_string(),
+            agent_id: None,
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            metadata: BTreeMap::from([("mount_point".to_string(), "/".to_string())]),
+        };
+        let (url, body) = request(&config, &n);
+        assert_eq!(url, "https://api.eu.opsgenie.com/v2/alerts");
+        assert_eq!(body["alias"], "nanolink/web-01/disk_forecast//");
+        assert_eq!(body["priority"], "P3");
+        assert_eq!(body["tags"], json!(["prod", "disk_forecast"]));
+
+        n.state = "resolved".to_string();
+        let (url, _) = request(&config, &n);
+        assert_eq!(
+            url,
+            "https://api.eu.opsgenie.com/v2/alerts/nanolink%2Fweb-01%2Fdisk_forecast%2F%2F/close?identifierType=alias"
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! PagerDuty destination (Events API v2)
+//!
+//! Firing events trigger an incident and resolutions resolve it, both under
+//! the notification's dedup key so PagerDuty groups repeats into one
+//! incident.
+
+use serde_json::{Value, json};
+
+use super::{Notification, OpenIncidents, SendError, Sink, level_rank, post_json, truncate};
+use crate::config::PagerDutyConfig;
+
+pub struct PagerDuty {
+    config: PagerDutyConfig,
+    name: String,
+    open: OpenIncidents,
+}
+
+impl PagerDuty {
+    pub fn new(config: PagerDutyConfig) -> Self {
+        let name = if config.name.is_empty() {
+            "pagerduty".to_string()
+        } else {
+            config.name.clone()
+        };
+        Self {
+            config,
+            name,
+            open: OpenIncidents::default(),
+        }
+    }
+}
+
+impl Sink for PagerDuty {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn accepts(&self, notification: &Notification) -> bool {
+        self.open.accepts(notification, &self.config.min_level)
+    }
+
+    fn send(&self, notification: &Notification) -> Result<(), SendError> {
+        let body = event(&self.config.routing_key, notification);
+        post_json(&self.config.url, &[], &body.to_string())
+    }
+}
+
+fn event(routing_key: &str, n: &Notification) -> Value {
+    if n.is_recovery() {
+        return json!({
+            "routing_key": routing_key,
+            "event_action": "resolve",
+            "dedup_key": n.dedup_key(),
+        });
+    }
+    json!({
+        "routing_key": routing_key,
+        "event_action": "trigger",
+        "dedup_key": n.dedup_key(),
+        "payload": {
+            "summary": truncate(&format!("{}: {}", n.hostname, n.message), 1024),
+            "source": n.hostname,
+            "severity": severity(&n.level),
+            "timestamp": n.timestamp,
+            "class": n.source,
+            "custom_details": n.metadata,
+        },
+        "client": "NanoLink",
+    })
+}
+
+/// PagerDuty severity for a notification level
+fn severity(level: &str) -> &'static str {
+    match level_rank(level) {
+        3 => "critical",
+        2 => "error",
+        1 => "warning",
+        _ => "info",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_event() {
+        let mut n = Notification {
+            source: "alerts".to_string(),
+            state: "firing".to_string(),
+            level: "critical".to_string(),
+            title: "[FIRING] cpu_high on web-01".to_string(),
+            message: "CPU above 90%".to_string(),
+            hostname: "web-01".to_string(),
+            agent_id: None,
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            metadata: BTreeMap::from([("rule".to_string(), "cpu_high".to_string())]),
+        };
+        let trigger = event("key", &n);
+        assert_eq!(trigger["event_action"], "trigger");
+        assert_eq!(trigger["dedup_key"], "nanolink/web-01/alerts/cpu_high");
+        assert_eq!(trigger["payload"]["severity"], "critical");
+        assert_eq!(trigger["payload"]["summary"], "web-01: CPU above 90%");
+
+        n.state = "resolved".to_string();
+        n.level = "info".to_string();
+        let resolve = event("key", &n);
+        assert_eq!(resolve["event_action"], "resolve");
+        assert_eq!(resolve["dedup_key"], trigger["dedup_key"]);
+        assert!(resolve.get("payload").is_none());
+    }
+}
@@ -0,0 +1,221 @@
+//! Webhook destination: Slack, Discord, Teams or generic JSON
+
+use serde_json::{Value, json};
+
+use super::{Notification, SendError, Sink, level_rank, post_json, truncate};
+use crate::config::{WebhookConfig, WebhookFormat};
+
+pub struct Webhook {
+    config: WebhookConfig,
+    name: String,
+}
+
+impl Webhook {
+    pub fn new(config: WebhookConfig) -> Self {
+        let name = if config.name.is_empty() {
+            // Avoid logging the URL: for most services it is the secret
+            format!("{:?} webhook", config.format).to_lowercase()
+        } else {
+            config.name.clone()
+        };
+        Self { config, name }
+    }
+}
+
+impl Sink for Webhook {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn accepts(&self, notification: &Notification) -> bool {
+        notification.is_recovery()
+            || level_rank(&notification.level) >= level_rank(&self.config.min_level)
+    }
+
+    fn send(&self, notification: &Notification) -> Result<(), SendError> {
+        let body = payload(&self.config, notification).map_err(SendError::Fatal)?;
+        let headers: Vec<_> = self
+            .config
+            .headers
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        post_json(&self.config.url, &headers, &body)
+    }
+}
+
+/// Request body for `notification` in the webhook's format
+fn payload(config: &WebhookConfig, n: &Notification) -> Result<String, String> {
+    let fields = n
+        .metadata
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .filter(|(k, _)| *k != "state")
+        .chain([("host", n.hostname.as_str())]);
+    let value = match config.format {
+        WebhookFormat::Generic => match &config.template {
+            Some(template) => return render_template(template, n),
+            None => serde_json::to_value(n).map_err(|e| e.to_string())?,
+        },
+        WebhookFormat::Slack => json!({
+            "text": n.title,
+            "attachments": [{
+                "color": format!("#{:06x}", color(n)),
+                "text": n.message,
+                "fields": fields
+                    .map(|(k, v)| json!({"title": k, "value": v, "short": true}))
+                    .collect::<Vec<_>>(),
+                "footer": "NanoLink",
+                "ts": chrono::DateTime::parse_from_rfc3339(&n.timestamp)
+                    .map(|t| t.timestamp())
+                    .unwrap_or_default(),
+            }],
+        }),
+        WebhookFormat::Discord => json!({
+            "embeds": [{
+                "title": truncate(&n.title, 256),
+                "description": truncate(&n.message, 4096),
+                "color": color(n),
+                "fields": fields
+                    .take(25)
+                    .map(|(k, v)| json!({"name": k, "value": truncate(v, 1024), "inline": true}))
+                    .collect::<Vec<_>>(),
+                "timestamp": n.timestamp,
+                "footer": {"text": "NanoLink"},
+            }],
+        }),
+        WebhookFormat::Teams => json!({
+            "@type": "MessageCard",
+            "@context": "https://schema.org/extensions",
+            "summary": n.title,
+            "themeColor": format!("{:06X}", color(n)),
+            "title": n.title,
+            "text": n.message,
+            "sections": [{
+                "facts": fields
+                    .map(|(k, v)| json!({"name": k, "value": v}))
+                    .collect::<Vec<_>>(),
+            }],
+        }),
+    };
+    Ok(Value::to_string(&value))
+}
+
+/// Render a custom body; `tojson` quotes values for embedding in JSON
+#[cfg(feature = "templates")]
+fn render_template(template: &str, n: &Notification) -> Result<String, String> {
+    let mut env = minijinja::Environment::new();
+    env.add_filter("tojson", |value: minijinja::Value| {
+        serde_json::to_string(&value).map_err(|e| {
+            minijinja::Error::new(minijinja::ErrorKind::InvalidOperation, e.to_string())
+        })
+    });
+    env.render_str(template, n)
+        .map_err(|e| format!("Template error: {e}"))
+}
+
+#[cfg(not(feature = "templates"))]
+fn render_template(_template: &str, _n: &Notification) -> Result<String, String> {
+    Err("Custom webhook templates need the `templates` feature".to_string())
+}
+
+/// Red while firing (orange for warnings), green on recovery
+fn color(n: &Notification) -> u32 {
+    if n.is_recovery() {
+        0x2eb67d
+    } else if level_rank(&n.level) >= 2 {
+        0xd63232
+    } else if level_rank(&n.level) == 1 {
+        0xe8a317
+    } else {
+        0x439fe0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn notification(state: &str, level: &str) -> Notification {
+        Notification {
+            source: "alerts".to_string(),
+            state: state.to_string(),
+            level: level.to_string(),
+            title: "[FIRING] cpu_high on web-01".to_string(),
+            message: "CPU \"high\"".to_string(),
+            hostname: "web-01".to_string(),
+            agent_id: None,
+            timestamp: "2026-01-01T00:00:00+00:00".to_string(),
+            metadata: BTreeMap::from([
+                ("rule".to_string(), "cpu_high".to_string()),
+                ("state".to_string(), state.to_string()),
+            ]),
+        }
+    }
+
+    fn webhook(format: WebhookFormat) -> WebhookConfig {
+        WebhookConfig {
+            name: String::new(),
+            url: "https://example.com/hook".to_string(),
+            format,
+            template: None,
+            headers: Default::default(),
+            min_level: "warning".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_payload_formats() {
+        let n = notification("firing", "critical");
+        let body = |format| -> Value {
+            serde_json::from_str(&payload(&webhook(format), &n).unwrap()).unwrap()
+        };
+
+        let slack = body(WebhookFormat::Slack);
+        assert_eq!(slack["text"], "[FIRING] cpu_high on web-01");
+        assert_eq!(slack["attachments"][0]["color"], "#d63232");
+        assert_eq!(slack["attachments"][0]["ts"], 1767225600);
+        assert_eq!(slack["attachments"][0]["fields"][0]["title"], "rule");
+
+        let discord = body(WebhookFormat::Discord);
+        assert_eq!(discord["embeds"][0]["color"], 0xd63232);
+        assert_eq!(discord["embeds"][0]["description"], "CPU \"high\"");
+
+        let teams = body(WebhookFormat::Teams);
+        assert_eq!(teams["@type"], "MessageCard");
+        assert_eq!(teams["themeColor"], "D63232");
+        assert_eq!(teams["sections"][0]["facts"][1]["value"], "web-01");
+
+        let generic = body(WebhookFormat::Generic);
+        assert_eq!(generic["state"], "firing");
+        assert_eq!(generic["metadata"]["rule"], "cpu_high");
+    }
+
+    #[cfg(feature = "templates")]
+    #[test]
+    fn test_template() {
+        let mut config = webhook(WebhookFormat::Generic);
+        config.template = Some(
+            r#"{"summary": {{ title|tojson }}, "text": {{ message|tojson }}, "rule": "{{ metadata.rule }}"}"#
+                .to_string(),
+        );
+        let body: Value =
+            serde_json::from_str(&payload(&config, &notification("firing", "warning")).unwrap())
+                .unwrap();
+        assert_eq!(body["text"], "CPU \"high\"");
+        assert_eq!(body["rule"], "cpu_high");
+
+        config.template = Some("{{ nope(".to_string());
+        assert!(payload(&config, &notification("firing", "warning")).is_err());
+    }
+
+    #[test]
+    fn test_min_level() {
+        let sink = Webhook::new(webhook(WebhookFormat::Slack));
+        assert_eq!(sink.name(), "slack webhook");
+        assert!(sink.accepts(&notification("firing", "critical")));
+        assert!(!sink.accepts(&notification("firing", "info")));
+        assert!(sink.accepts(&notification("resolved", "info")));
+    }
+}
@@ -1,18 +1,6 @@
 /// Linux-specific implementations
 use std::process::Command;
 
-/// Install as systemd service
-pub fn install_service() -> Result<(), String> {
-    // TODO: Implement systemd service installation
-    Err("Not implemented".to_string())
-}
-
-/// Uninstall systemd service
-pub fn uninstall_service() -> Result<(), String> {
-    // TODO: Implement systemd service uninstallation
-    Err("Not implemented".to_string())
-}
-
 /// Restart the nanolink-agent systemd service
 pub fn restart_service() -> Result<(), String> {
     let output = Command::new("systemctl")
@@ -31,6 +19,7 @@ pub fn restart_service() -> Result<(), String> {
 }
 
 /// Check if the agent service is running
+#[cfg(feature = "gui")]
 pub fn is_service_running() -> bool {
     Command::new("systemctl")
         .args(["is-active", "--quiet", "nanolink-agent"])
@@ -1,6 +1,6 @@
-/// Platform-specific implementations
-///
-/// This module contains platform-specific code for Windows, Linux, and macOS.
+//! Platform-specific implementations
+//!
+//! This module contains platform-specific code for Windows, Linux, and macOS.
 
 #[cfg(target_os = "linux")]
 mod linux;
@@ -19,8 +19,8 @@ pub use macos::*;
 
 #[cfg(target_os = "windows")]
 pub use windows::{
-    install_service, query_service_status, restart_service, run_as_service, start_service,
-    stop_service, uninstall_service,
+    install_service, is_service_running, query_service_status, restart_service, run_as_service,
+    start_service, stop_service, uninstall_service,
 };
 
 /// Get the current platform name
@@ -0,0 +1,240 @@
+//! Privileged helper (`nanolink-agent helper`)
+//!
+//! Runs as root next to an unprivileged agent. The listening socket comes
+//! from systemd socket activation (nanolink-helper.socket) or is created at
+//! `privsep.socket_path`, owned by the agent user's group with mode 0660.
+
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::FromRawFd;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tracing::{info, warn};
+
+use super::{MAX_LINE, Operation, Reply};
+use crate::config::{Config, PrivsepConfig};
+use crate::executor::{PowerAction, PowerManager, ServiceExecutor};
+use crate::proto::CommandResult;
+use crate::security::validation::validate_service_name;
+
+/// First file descriptor passed by systemd (SD_LISTEN_FDS_START)
+const LISTEN_FDS_START: i32 = 3;
+
+/// Serve requests until the process is stopped
+pub async fn run(config: Arc<Config>) -> Result<()> {
+    let privsep = &config.privsep;
+    let agent = nix::unistd::User::from_name(&privsep.agent_user)?
+        .with_context(|| format!("privsep.agent_user '{}' does not exist", privsep.agent_user))?;
+    let agent_uid = agent.uid.as_raw();
+
+    let listener = match activated_listener()? {
+        Some(listener) => {
+            info!("Privileged helper started by socket activation");
+            listener
+        }
+        None => {
+            let listener = bind(privsep, agent.gid)?;
+            info!("Privileged helper listening on {}", privsep.socket_path);
+            listener
+        }
+    };
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let config = config.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve(stream, &config, agent_uid).await {
+                warn!("Privileged helper connection failed: {}", e);
+            }
+        });
+    }
+}
+
+/// Listener passed by systemd, if the helper was socket activated
+fn activated_listener() -> Result<Option<UnixListener>> {
+    let for_us = std::env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        == Some(std::process::id());
+    let fds = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|n| n.parse::<u32>().ok())
+        .unwrap_or(0);
+    if !for_us || fds == 0 {
+        return Ok(None);
+    }
+
+    // SAFETY: systemd hands over the listening socket as the first passed
+    // descriptor and nothing else in the process owns it
+    let listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(LISTEN_FDS_START) };
+    listener.set_nonblocking(true)?;
+    Ok(Some(UnixListener::from_std(listener)?))
+}
+
+/// Create the socket, reachable only by root and the agent user's group
+fn bind(privsep: &PrivsepConfig, group: nix::unistd::Gid) -> Result<UnixListener> {
+    let path = Path::new(&privsep.socket_path);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // A stale socket from an earlier run
+    let _ = std::fs::remove_file(path);
+
+    let listener =
+        UnixListener::bind(path).with_context(|| format!("Failed to bind {}", path.display()))?;
+    nix::unistd::chown(path, None, Some(group))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o660))?;
+    Ok(listener)
+}
+
+/// Answer one request
+async fn serve(stream: UnixStream, config: &Arc<Config>, agent_uid: u32) -> std::io::Result<()> {
+    let uid = stream.peer_cred()?.uid();
+    let (read, mut write) = stream.into_split();
+
+    let reply = if uid != agent_uid && uid != 0 {
+        warn!("[AUDIT] Helper: refused connection from uid {}", uid);
+        Reply::error(format!("uid {uid} may not use the privileged helper"))
+    } else {
+        let mut line = String::new();
+        BufReader::new(read.take(MAX_LINE))
+            .read_line(&mut line)
+            .await?;
+        handle(&line, config).await
+    };
+
+    let mut reply = serde_json::to_string(&reply)?;
+    reply.push('\n');
+    write.write_all(reply.as_bytes()).await
+}
+
+/// Check and perform the request in `line`
+async fn handle(line: &str, config: &Arc<Config>) -> Reply {
+    let operation: Operation = match serde_json::from_str(line) {
+        Ok(operation) => operation,
+        Err(e) => return Reply::error(format!("Invalid request: {e}")),
+    };
+    if let Err(e) = authorize(&operation, &config.privsep) {
+        warn!("[AUDIT] Helper: denied {:?}: {}", operation, e);
+        return Reply::error(e);
+    }
+    info!("[AUDIT] Helper: {:?}", operation);
+    execute(operation, config).await
+}
+
+/// Per-operation checks against the helper's own config
+fn authorize(operation: &Operation, privsep: &PrivsepConfig) -> Result<(), String> {
+    let name = operation.name();
+    if !privsep.allowed_operations.iter().any(|op| op == name) {
+        return Err(format!("Operation {name} is not allowed"));
+    }
+
+    match operation {
+        Operation::ServiceStart { service }
+        | Operation::ServiceStop { service }
+        | Operation::ServiceRestart { service } => {
+            validate_service_name(service)?;
+            let allowed = privsep.allowed_services.is_empty()
+                || privsep.allowed_services.iter().any(|pattern| {
+                    glob::Pattern::new(pattern).is_ok_and(|pattern| pattern.matches(service))
+                });
+            if !allowed {
+                return Err(format!(
+                    "Service {service} is not in privsep.allowed_services"
+                ));
+            }
+            Ok(())
+        }
+        Operation::Reboot { .. } | Operation::Shutdown { .. } | Operation::PowerCancel => Ok(()),
+    }
+}
+
+/// Run the operation locally; delay limits and message sanitizing come
+/// from the helper's `power` settings
+async fn execute(operation: Operation, config: &Arc<Config>) -> Reply {
+    let services = ServiceExecutor::local();
+    let power = PowerManager::local(config.clone());
+
+    let result = match operation {
+        Operation::ServiceStart { service } => services.start_service(&service).await,
+        Operation::ServiceStop { service } => services.stop_service(&service).await,
+        Operation::ServiceRestart { service } => services.restart_service(&service).await,
+        Operation::Reboot {
+            delay_seconds,
+            message,
+        } => schedule(&power, PowerAction::Reboot, delay_seconds, message).await,
+        Operation::Shutdown {
+            delay_seconds,
+            message,
+        } => schedule(&power, PowerAction::Shutdown, delay_seconds, message).await,
+        Operation::PowerCancel => power.cancel().await,
+    };
+    result.into()
+}
+
+async fn schedule(
+    power: &PowerManager,
+    action: PowerAction,
+    delay_seconds: u64,
+    message: String,
+) -> CommandResult {
+    let params = HashMap::from([
+        ("delay_seconds".to_string(), delay_seconds.to_string()),
+        ("message".to_string(), message),
+    ]);
+    power.schedule(action, &params).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn restart(service: &str) -> Operation {
+        Operation::ServiceRestart {
+            service: service.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_authorize() {
+        let mut privsep = PrivsepConfig::default();
+        assert!(authorize(&restart("nginx"), &privsep).is_ok());
+        assert!(authorize(&restart("nginx; reboot"), &privsep).is_err());
+
+        privsep.allowed_services = vec!["nginx*".to_string(), "php-fpm".to_string()];
+        assert!(authorize(&restart("nginx.service"), &privsep).is_ok());
+        assert!(authorize(&restart("sshd"), &privsep).is_err());
+
+        privsep.allowed_operations = vec!["service_restart".to_string()];
+        assert!(authorize(&restart("php-fpm"), &privsep).is_ok());
+        assert!(authorize(&Operation::PowerCancel, &privsep).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_serve_checks_request() {
+        let mut config = Config::sample();
+        config.privsep.allowed_operations = vec!["service_start".to_string()];
+        let config = Arc::new(config);
+        let uid = nix::unistd::getuid().as_raw();
+
+        let (client, server) = UnixStream::pair().unwrap();
+        let serving = tokio::spawn(async move { serve(server, &config, uid).await });
+        let reply = super::super::exchange(client, &restart("nginx"))
+            .await
+            .unwrap();
+        serving.await.unwrap().unwrap();
+        assert!(!reply.success);
+        assert_eq!(reply.error, "Operation service_restart is not allowed");
+
+        assert!(
+            handle("not json", &Arc::new(Config::sample()))
+                .await
+                .error
+                .starts_with("Invalid request")
+        );
+    }
+}
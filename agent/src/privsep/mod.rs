@@ -0,0 +1,163 @@
+//! Privilege separation
+//!
+//! With `privsep.enabled` the agent runs unprivileged and hands the few
+//! operations that need root (service control, reboot and shutdown) to
+//! `nanolink-agent helper` over a local Unix socket. Each connection carries
+//! one JSON request line and gets one JSON reply line back. The helper checks
+//! the caller's uid and every request against its own config before acting.
+
+pub mod helper;
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+
+use crate::config::PrivsepConfig;
+use crate::proto::CommandResult;
+
+/// Service restarts and shutdown scheduling can take a while
+const HELPER_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Longest request or reply line
+const MAX_LINE: u64 = 1024 * 1024;
+
+/// A privileged operation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    ServiceStart { service: String },
+    ServiceStop { service: String },
+    ServiceRestart { service: String },
+    Reboot { delay_seconds: u64, message: String },
+    Shutdown { delay_seconds: u64, message: String },
+    PowerCancel,
+}
+
+impl Operation {
+    /// Name used in `privsep.allowed_operations`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ServiceStart { .. } => "service_start",
+            Self::ServiceStop { .. } => "service_stop",
+            Self::ServiceRestart { .. } => "service_restart",
+            Self::Reboot { .. } => "reboot",
+            Self::Shutdown { .. } => "shutdown",
+            Self::PowerCancel => "power_cancel",
+        }
+    }
+}
+
+/// Outcome of an operation
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reply {
+    pub success: bool,
+    #[serde(default)]
+    pub output: String,
+    #[serde(default)]
+    pub error: String,
+}
+
+impl Reply {
+    fn error(error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            output: String::new(),
+            error: error.into(),
+        }
+    }
+}
+
+impl From<CommandResult> for Reply {
+    fn from(result: CommandResult) -> Self {
+        Self {
+            success: result.success,
+            output: result.output,
+            error: result.error,
+        }
+    }
+}
+
+impl From<Reply> for CommandResult {
+    fn from(reply: Reply) -> Self {
+        CommandResult {
+            success: reply.success,
+            output: reply.output,
+            error: reply.error,
+            ..Default::default()
+        }
+    }
+}
+
+/// Connection to the privileged helper
+#[derive(Debug, Clone)]
+pub struct HelperClient {
+    socket: PathBuf,
+}
+
+impl HelperClient {
+    /// Client for the configured helper, if privilege separation is on
+    pub fn from_config(config: &PrivsepConfig) -> Option<Self> {
+        config.enabled.then(|| Self {
+            socket: PathBuf::from(&config.socket_path),
+        })
+    }
+
+    /// Ask the helper to perform `operation`
+    pub async fn call(&self, operation: &Operation) -> CommandResult {
+        match tokio::time::timeout(HELPER_TIMEOUT, self.request(operation)).await {
+            Ok(Ok(reply)) => reply.into(),
+            Ok(Err(e)) => Reply::error(format!(
+                "Privileged helper at {} failed: {e}",
+                self.socket.display()
+            ))
+            .into(),
+            Err(_) => Reply::error(format!(
+                "Privileged helper did not answer within {}s",
+                HELPER_TIMEOUT.as_secs()
+            ))
+            .into(),
+        }
+    }
+
+    async fn request(&self, operation: &Operation) -> std::io::Result<Reply> {
+        let stream = UnixStream::connect(&self.socket).await?;
+        exchange(stream, operation).await
+    }
+}
+
+/// Send one request over `stream` and read the reply
+async fn exchange(stream: UnixStream, operation: &Operation) -> std::io::Result<Reply> {
+    let (read, mut write) = stream.into_split();
+    let mut request = serde_json::to_string(operation)?;
+    request.push('\n');
+    write.write_all(request.as_bytes()).await?;
+    write.shutdown().await?;
+
+    let mut line = String::new();
+    BufReader::new(read.take(MAX_LINE))
+        .read_line(&mut line)
+        .await?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operation_wire_format() {
+        let op = Operation::ServiceRestart {
+            service: "nginx".to_string(),
+        };
+        let json = serde_json::to_string(&op).unwrap();
+        assert_eq!(json, r#"{"op":"service_restart","service":"nginx"}"#);
+        assert_eq!(serde_json::from_str::<Operation>(&json).unwrap(), op);
+        assert_eq!(
+            serde_json::from_str::<Operation>(r#"{"op":"power_cancel"}"#).unwrap(),
+            Operation::PowerCancel
+        );
+    }
+}
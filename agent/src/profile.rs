@@ -0,0 +1,361 @@
+//! Central config profile
+//!
+//! With `profile.enabled`, the agent fetches a YAML config fragment at start
+//! and every `refresh_interval_secs`: from `profile.url` or, without one,
+//! from the first configured server (`GetConfigProfile`). A profile must
+//! carry a detached Ed25519 signature (base64) from one of
+//! `profile.public_keys`; next to a URL it is read from `signature_url`,
+//! by default the profile URL plus `.sig`.
+//!
+//! The signed content carries a top-level `serial` (a positive integer) that
+//! must grow with every profile the signer issues: a profile is refused
+//! unless its serial is higher than the cached one's, so an old signed
+//! profile can't be replayed to roll back a change.
+//!
+//! A verified profile is cached in `/var/lib/nanolink/profile.yaml`
+//! (`%ProgramData%\nanolink` on Windows) with its signature, and merged with
+//! the local config on every start: below the local files or, with
+//! `override_local`, above them. The cache is verified again when loaded,
+//! and a profile can't set the `profile` or `include` sections.
+//!
+//! A new profile only replaces the cached one if the config it produces
+//! loads. With `restart_on_change` the agent service is then restarted so it
+//! takes effect.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde_yaml::Value;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+
+use crate::config::{Config, ProfileConfig};
+use crate::proto::ConfigProfile;
+use crate::utils::http;
+
+/// Sections a profile may not set, so it can't redirect or re-key itself
+const RESERVED: [&str; 2] = ["profile", "include"];
+
+/// Key of the profile serial, removed before merging
+const SERIAL: &str = "serial";
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Shortest refresh interval honored
+const MIN_REFRESH: Duration = Duration::from_secs(60);
+
+fn cache_dir() -> PathBuf {
+    #[cfg(windows)]
+    {
+        let base = std::env::var("ProgramData").unwrap_or_else(|_| "C:\\ProgramData".to_string());
+        PathBuf::from(base).join("nanolink")
+    }
+    #[cfg(unix)]
+    {
+        PathBuf::from("/var/lib/nanolink")
+    }
+}
+
+/// Where the verified profile is cached
+pub fn cache_file() -> PathBuf {
+    cache_dir().join("profile.yaml")
+}
+
+fn signature_file() -> PathBuf {
+    cache_dir().join("profile.sig")
+}
+
+/// The cached profile as a config layer, if it still verifies
+pub fn cached(config: &ProfileConfig) -> Option<Value> {
+    let profile = read_cache()?;
+    verify(&profile, &config.public_keys)
+        .inspect_err(|e| eprintln!("Warning: Ignoring cached config profile: {e}"))
+        .ok()
+        .map(|(_, value)| value)
+}
+
+/// Check the signature and parse a profile into its serial and a config
+/// layer
+fn verify(profile: &ConfigProfile, public_keys: &[String]) -> Result<(u64, Value), String> {
+    if public_keys.is_empty() {
+        return Err("No profile.public_keys configured, refusing unsigned profile".to_string());
+    }
+    let signature = BASE64
+        .decode(profile.signature.trim())
+        .map_err(|e| format!("Invalid signature encoding: {e}"))?;
+    let trusted = public_keys.iter().any(|key| {
+        BASE64.decode(key.trim()).is_ok_and(|key| {
+            ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, key)
+                .verify(&profile.content, &signature)
+                .is_ok()
+        })
+    });
+    if !trusted {
+        return Err("Profile signature does not match any trusted key".to_string());
+    }
+
+    let value: Value =
+        serde_yaml::from_slice(&profile.content).map_err(|e| format!("Invalid profile: {e}"))?;
+    let Value::Mapping(mut map) = value else {
+        return Err("Profile must be a mapping of config sections".to_string());
+    };
+    if let Some(key) = RESERVED.iter().find(|key| map.contains_key(**key)) {
+        return Err(format!("Profile may not set the {key} section"));
+    }
+    let serial = match map.remove(SERIAL) {
+        Some(serial) => serial
+            .as_u64()
+            .filter(|&serial| serial > 0)
+            .ok_or("Profile serial must be a positive integer")?,
+        None => return Err("Profile has no serial".to_string()),
+    };
+    Ok((serial, Value::Mapping(map)))
+}
+
+fn read_cache() -> Option<ConfigProfile> {
+    Some(ConfigProfile {
+        content: std::fs::read(cache_file()).ok()?,
+        signature: std::fs::read_to_string(signature_file()).ok()?,
+        not_modified: false,
+    })
+}
+
+/// Replace the cache with `profile`, or remove it
+fn write_cache(profile: Option<&ConfigProfile>) -> std::io::Result<()> {
+    let Some(profile) = profile else {
+        let _ = std::fs::remove_file(signature_file());
+        return match std::fs::remove_file(cache_file()) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    };
+    std::fs::create_dir_all(cache_dir())?;
+    for (path, data) in [
+        (cache_file(), profile.content.as_slice()),
+        (signature_file(), profile.signature.as_bytes()),
+    ] {
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, data)?;
+        std::fs::rename(&tmp, &path)?;
+    }
+    Ok(())
+}
+
+/// Refuse a profile that isn't newer than the cached one
+fn check_serial(serial: u64, cached: Option<u64>) -> Result<(), String> {
+    match cached {
+        Some(cached) if serial <= cached => Err(format!(
+            "Profile serial {serial} is not newer than the cached profile's {cached}, \
+             refusing a rollback"
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Verify a fetched profile and cache it if the resulting config loads.
+/// Returns whether it differs from the cached one.
+fn apply(config: &Config, config_path: &Path, profile: ConfigProfile) -> Result<bool, String> {
+    let (serial, _) = verify(&profile, &config.profile.public_keys)?;
+    let previous = read_cache();
+    if previous
+        .as_ref()
+        .is_some_and(|p| p.content == profile.content)
+    {
+        return Ok(false);
+    }
+    let cached = previous
+        .as_ref()
+        .and_then(|p| verify(p, &config.profile.public_keys).ok());
+    check_serial(serial, cached.map(|(serial, _)| serial))?;
+
+    write_cache(Some(&profile)).map_err(|e| format!("Failed to cache profile: {e}"))?;
+    if let Err(e) = Config::load(config_path) {
+        if let Err(e) = write_cache(previous.as_ref()) {
+            warn!("Failed to restore the previous config profile: {}", e);
+        }
+        return Err(format!("Resulting configuration is invalid: {e:#}"));
+    }
+    Ok(true)
+}
+
+/// `{agent_id}` and `{hostname}` in a profile URL
+fn expand_url(url: &str, config: &Config) -> String {
+    url.replace(
+        "{agent_id}",
+        config.agent.agent_id.as_deref().unwrap_or_default(),
+    )
+    .replace("{hostname}", &config.get_hostname())
+}
+
+/// Download the profile and its signature from `profile.url`
+fn fetch_url(config: &Config, url: &str) -> Result<ConfigProfile, String> {
+    let url = expand_url(url, config);
+    let signature_url = match &config.profile.signature_url {
+        Some(signature_url) => expand_url(signature_url, config),
+        None => format!("{url}.sig"),
+    };
+    let headers: Vec<_> = config
+        .profile
+        .headers
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect();
+    let get = |url: &str| {
+        let response =
+            http::send("GET", url, &headers, &[], TIMEOUT).map_err(|e| format!("{url}: {e}"))?;
+        match response.status {
+            200 => Ok(response.body),
+            status => Err(format!("{url}: HTTP {status}")),
+        }
+    };
+    Ok(ConfigProfile {
+        content: get(&url)?,
+        signature: String::from_utf8_lossy(&get(&signature_url)?).into_owned(),
+        not_modified: false,
+    })
+}
+
+/// Fetch the profile now and every `refresh_interval_secs`. `fetch` gets
+/// the digest of the cached profile.
+pub async fn refresh<F, Fut>(config: Arc<Config>, config_path: PathBuf, mut fetch: F)
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<ConfigProfile, String>>,
+{
+    let period = Duration::from_secs(config.profile.refresh_interval_secs).max(MIN_REFRESH);
+    let mut interval = tokio::time::interval(period);
+    loop {
+        interval.tick().await;
+        let current = read_cache()
+            .map(|p| sha256_hex(&p.content))
+            .unwrap_or_default();
+        let profile = match fetch(current).await {
+            Ok(profile) if profile.not_modified => {
+                debug!("Config profile is up to date");
+                continue;
+            }
+            Ok(profile) => profile,
+            Err(e) => {
+                warn!("Failed to fetch config profile: {}", e);
+                continue;
+            }
+        };
+
+        let (apply_config, apply_path) = (config.clone(), config_path.clone());
+        let applied =
+            tokio::task::spawn_blocking(move || apply(&apply_config, &apply_path, profile)).await;
+        match applied {
+            Ok(Ok(true)) => {}
+            Ok(Ok(false)) => {
+                debug!("Config profile is up to date");
+                continue;
+            }
+            Ok(Err(e)) => {
+                warn!("Config profile rejected: {}", e);
+                continue;
+            }
+            Err(e) => {
+                warn!("Config profile task failed: {}", e);
+                continue;
+            }
+        }
+
+        if !config.profile.restart_on_change {
+            info!("New config profile received, it applies on the next start");
+            continue;
+        }
+        info!("New config profile received, restarting the agent");
+        let restarted = tokio::task::spawn_blocking(crate::restart_agent_service)
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|result| result);
+        if let Err(e) = restarted {
+            warn!("Restart the agent to apply the profile: {}", e);
+        }
+    }
+}
+
+/// Refresh the profile from `profile.url`
+pub async fn run(config: Arc<Config>, config_path: PathBuf) {
+    let Some(url) = config.profile.url.clone() else {
+        return;
+    };
+    let fetch_config = config.clone();
+    refresh(config, config_path, move |_| {
+        let (config, url) = (fetch_config.clone(), url.clone());
+        async move {
+            tokio::task::spawn_blocking(move || fetch_url(&config, &url))
+                .await
+                .map_err(|e| e.to_string())?
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    #[test]
+    fn test_verify() {
+        let rng = ring::rand::SystemRandom::new();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let key = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let keys = [BASE64.encode(key.public_key().as_ref())];
+        let signed = |content: &str| ConfigProfile {
+            content: content.as_bytes().to_vec(),
+            signature: BASE64.encode(key.sign(content.as_bytes()).as_ref()),
+            not_modified: false,
+        };
+
+        let profile = signed("serial: 7\nlogging:\n  level: warn\n");
+        let (serial, value) = verify(&profile, &keys).unwrap();
+        assert_eq!(serial, 7);
+        assert_eq!(value["logging"]["level"], Value::from("warn"));
+        assert!(value.get(SERIAL).is_none());
+
+        // Tampered content, no trusted keys, reserved sections, not a mapping
+        let mut tampered = profile.clone();
+        tampered.content = b"serial: 7\nlogging:\n  level: off\n".to_vec();
+        assert!(verify(&tampered, &keys).is_err());
+        assert!(verify(&profile, &[]).is_err());
+        assert!(verify(&signed("serial: 8\nprofile: {enabled: false}"), &keys).is_err());
+        assert!(verify(&signed("serial: 8\ninclude: [/tmp/x.yaml]"), &keys).is_err());
+        assert!(verify(&signed("- servers"), &keys).is_err());
+
+        // No serial, or not a positive integer
+        assert!(verify(&signed("logging: {level: warn}"), &keys).is_err());
+        assert!(verify(&signed("serial: 0"), &keys).is_err());
+        assert!(verify(&signed("serial: \"7\""), &keys).is_err());
+    }
+
+    #[test]
+    fn test_check_serial() {
+        assert!(check_serial(1, None).is_ok());
+        assert!(check_serial(8, Some(7)).is_ok());
+        assert!(check_serial(7, Some(7)).is_err());
+        assert!(check_serial(6, Some(7)).is_err());
+    }
+
+    #[test]
+    fn test_expand_url() {
+        let mut config = Config::sample();
+        config.agent.agent_id = Some("abc".to_string());
+        config.agent.hostname = Some("web-01".to_string());
+        assert_eq!(
+            expand_url("https://cfg/{hostname}/{agent_id}.yaml", &config),
+            "https://cfg/web-01/abc.yaml"
+        );
+    }
+}
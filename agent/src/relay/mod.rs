@@ -0,0 +1,411 @@
+//! Store-and-forward relay (`relay`)
+//!
+//! Agents at a site with a single uplink, such as a ship or a remote
+//! station, report to one agent there instead of the central server. That
+//! agent serves the server side of `NanoLinkService` to them, keeps each
+//! agent's stream messages in a bounded in-memory store, and forwards them to
+//! its own primary server whenever the uplink is up. Upstream, every
+//! downstream agent appears under its own identity, authenticated with the
+//! relay's server token.
+//!
+//! Forwarding is one way: commands and data requests from upstream are not
+//! passed down, and downstream agents get read-only access.
+
+mod service;
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use nanolink_client::protocol::downgrade;
+use nanolink_client::{AgentIdentity, Client, ConnectOptions, Server};
+use parking_lot::Mutex;
+use prost::Message;
+use tokio::sync::Notify;
+use tonic::transport::{Identity, ServerTlsConfig};
+use tracing::{info, warn};
+
+use crate::config::{Config, ServerConfig, ServerRole};
+use crate::connection::bandwidth::Meter;
+use crate::proto::nano_link_service_server::NanoLinkServiceServer;
+use crate::proto::{
+    CommandResult, MetricsStreamRequest, metrics_stream_request, metrics_stream_response,
+};
+
+/// Serve downstream agents until the listener fails
+pub async fn run(config: Arc<Config>) {
+    if let Err(e) = serve(config).await {
+        warn!("Relay stopped: {:#}", e);
+    }
+}
+
+async fn serve(config: Arc<Config>) -> Result<()> {
+    let relay = &config.relay;
+    let addr: SocketAddr = relay
+        .listen
+        .parse()
+        .with_context(|| format!("Invalid relay.listen '{}'", relay.listen))?;
+    let max_message_size = config.agent.max_message_size;
+
+    let mut builder = tonic::transport::Server::builder();
+    if let (Some(cert), Some(key)) = (&relay.tls_cert, &relay.tls_key) {
+        let cert = std::fs::read(cert).with_context(|| format!("Failed to read {cert}"))?;
+        let key = std::fs::read(key).with_context(|| format!("Failed to read {key}"))?;
+        builder =
+            builder.tls_config(ServerTlsConfig::new().identity(Identity::from_pem(cert, key)))?;
+    }
+
+    info!(
+        "Relay listening on {} ({}, up to {} agents)",
+        addr,
+        if relay.tls_cert.is_some() {
+            "TLS"
+        } else {
+            "plain gRPC"
+        },
+        relay.max_agents
+    );
+    let service =
+        NanoLinkServiceServer::new(service::RelayService::new(Relay::new(config.clone())))
+            .max_decoding_message_size(max_message_size)
+            .max_encoding_message_size(max_message_size);
+    builder
+        .add_service(service)
+        .serve(addr)
+        .await
+        .context("Relay server failed")
+}
+
+/// Server the relay forwards to: the primary, or the first server
+fn upstream(config: &Config) -> Option<&ServerConfig> {
+    config
+        .servers
+        .iter()
+        .find(|s| s.role == Some(ServerRole::Primary))
+        .or_else(|| config.servers.first())
+}
+
+/// Downstream agents and what they sent
+pub struct Relay {
+    config: Arc<Config>,
+    agents: Mutex<HashMap<String, Arc<Downstream>>>,
+}
+
+impl Relay {
+    fn new(config: Arc<Config>) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            agents: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Register a stream from `identity`, starting its forwarder on first
+    /// contact. `None` once `relay.max_agents` other agents are known.
+    fn attach(self: &Arc<Self>, identity: AgentIdentity) -> Option<Arc<Downstream>> {
+        let mut agents = self.agents.lock();
+        if let Some(agent) = agents.get(&identity.agent_id) {
+            agent.streams.fetch_add(1, Ordering::SeqCst);
+            return Some(agent.clone());
+        }
+        if agents.len() >= self.config.relay.max_agents {
+            return None;
+        }
+
+        let agent = Arc::new(Downstream::new(
+            identity.clone(),
+            self.config.relay.buffer_capacity,
+        ));
+        agent.streams.fetch_add(1, Ordering::SeqCst);
+        agents.insert(identity.agent_id, agent.clone());
+        tokio::spawn(forward(self.config.clone(), agent.clone()));
+        Some(agent)
+    }
+}
+
+/// One downstream agent
+pub struct Downstream {
+    identity: AgentIdentity,
+    queue: Mutex<VecDeque<MetricsStreamRequest>>,
+    capacity: usize,
+    /// Latest static info (all its chunks), sent again on every upstream
+    /// stream as the agent only sends it when it connects
+    static_info: Mutex<Vec<MetricsStreamRequest>>,
+    /// Open streams from the agent
+    streams: AtomicUsize,
+    dropped: AtomicU64,
+    notify: Notify,
+}
+
+impl Downstream {
+    fn new(identity: AgentIdentity, capacity: usize) -> Self {
+        Self {
+            identity,
+            queue: Mutex::new(VecDeque::new()),
+            capacity: capacity.max(1),
+            static_info: Mutex::new(Vec::new()),
+            streams: AtomicUsize::new(0),
+            dropped: AtomicU64::new(0),
+            notify: Notify::new(),
+        }
+    }
+
+    /// Store a message for forwarding. Only the latest heartbeat is kept;
+    /// when full, the oldest message is dropped.
+    fn push(&self, request: MetricsStreamRequest) {
+        if let Some(metrics_stream_request::Request::StaticInfo(info)) = &request.request {
+            let mut static_info = self.static_info.lock();
+            if info.chunk.is_none_or(|chunk| chunk.index == 0) {
+                static_info.clear();
+            }
+            static_info.push(request.clone());
+        }
+        let mut queue = self.queue.lock();
+        if is_heartbeat(&request) {
+            queue.retain(|queued| !is_heartbeat(queued));
+        }
+        if queue.len() >= self.capacity {
+            queue.pop_front();
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        queue.push_back(request);
+        drop(queue);
+        self.notify.notify_one();
+    }
+
+    /// Static info for a new upstream stream, unless newer static info is
+    /// still waiting in the store
+    fn static_info(&self) -> Vec<MetricsStreamRequest> {
+        let queued = self.queue.lock().iter().any(|request| {
+            matches!(
+                request.request,
+                Some(metrics_stream_request::Request::StaticInfo(_))
+            )
+        });
+        if queued {
+            Vec::new()
+        } else {
+            self.static_info.lock().clone()
+        }
+    }
+
+    fn detach(&self) {
+        self.streams.fetch_sub(1, Ordering::SeqCst);
+        self.notify.notify_one();
+    }
+
+    /// Wait until there is something to forward
+    async fn pending(&self) {
+        while self.queue.lock().is_empty() {
+            self.notify.notified().await;
+        }
+    }
+
+    /// Next message to forward; `None` once the store is empty and the
+    /// agent has disconnected
+    async fn next(&self) -> Option<MetricsStreamRequest> {
+        loop {
+            if let Some(request) = self.queue.lock().pop_front() {
+                return Some(request);
+            }
+            if self.streams.load(Ordering::SeqCst) == 0 {
+                return None;
+            }
+            self.notify.notified().await;
+        }
+    }
+}
+
+fn is_heartbeat(request: &MetricsStreamRequest) -> bool {
+    matches!(
+        request.request,
+        Some(metrics_stream_request::Request::Heartbeat(_))
+    )
+}
+
+/// Forward what `agent` sends for as long as the relay runs
+async fn forward(config: Arc<Config>, agent: Arc<Downstream>) {
+    let initial_delay = config.agent.reconnect_delay;
+    let mut delay = initial_delay;
+    loop {
+        agent.pending().await;
+        let Some(server) = upstream(&config) else {
+            return;
+        };
+        match forward_once(&config, server, &agent).await {
+            Ok(()) => delay = initial_delay,
+            Err(e) => {
+                warn!(
+                    "Relay uplink for {} failed, retrying in {}s: {:#}",
+                    agent.identity.hostname, delay, e
+                );
+                tokio::time::sleep(Duration::from_secs(delay)).await;
+                delay = (delay * 2).min(config.agent.max_reconnect_delay);
+            }
+        }
+    }
+}
+
+/// Stream the stored messages upstream until the store runs dry after the
+/// agent disconnected. Messages leave the store only when the stream takes
+/// them, so little is lost when the uplink breaks.
+async fn forward_once(
+    config: &Config,
+    server: &ServerConfig,
+    agent: &Arc<Downstream>,
+) -> Result<()> {
+    let token = server.resolve_token().map_err(|e| anyhow::anyhow!(e))?;
+    let options = ConnectOptions {
+        connect_timeout: Duration::from_secs(15),
+        keepalive: true,
+        max_message_size: config.agent.max_message_size,
+    };
+    let mut client = Client::connect(&Server::from(server), &options).await?;
+    let auth = client
+        .authenticate(agent.identity.auth_request(token))
+        .await?;
+    if !auth.success {
+        anyhow::bail!("Authentication failed: {}", auth.error_message);
+    }
+
+    let protocol = client.protocol_version();
+    let init = MetricsStreamRequest {
+        request: Some(metrics_stream_request::Request::AgentInit(
+            agent.identity.agent_init(),
+        )),
+    };
+    // Results of refused commands go back on the same stream
+    let (refusals_tx, mut refusals_rx) = tokio::sync::mpsc::unbounded_channel();
+    let source = agent.clone();
+    let static_info = agent.static_info();
+    // The uplink counts against the server's bandwidth cap like a direct one
+    let meter = Meter::new(server);
+    let requests = async_stream::stream! {
+        meter.record(init.encoded_len());
+        yield init;
+        for request in static_info {
+            meter.record(request.encoded_len());
+            yield request;
+        }
+        loop {
+            let request = tokio::select! {
+                request = source.next() => match request {
+                    Some(request) => request,
+                    None => break,
+                },
+                Some(refusal) = refusals_rx.recv() => refusal,
+            };
+            if !meter.allows(&request) {
+                continue;
+            }
+            if let Some(request) = request.request.and_then(|r| downgrade(r, protocol)) {
+                let request = MetricsStreamRequest { request: Some(request) };
+                meter.record(request.encoded_len());
+                yield request;
+            }
+        }
+    };
+
+    let mut responses = client.stream_metrics(requests).await?;
+    info!(
+        "Relay forwarding {} ({}) to {}",
+        agent.identity.hostname,
+        agent.identity.agent_id,
+        server.address()
+    );
+    while let Some(response) = responses.message().await? {
+        if let Some(metrics_stream_response::Response::Command(cmd)) = response.response {
+            let result = CommandResult {
+                command_id: cmd.command_id,
+                success: false,
+                error: "Commands are not passed through the relay".to_string(),
+                ..Default::default()
+            };
+            let _ = refusals_tx.send(MetricsStreamRequest {
+                request: Some(metrics_stream_request::Request::CommandResult(result)),
+            });
+        }
+    }
+
+    let dropped = agent.dropped.swap(0, Ordering::Relaxed);
+    if dropped > 0 {
+        warn!(
+            "Relay dropped {} messages from {} while the uplink was down",
+            dropped, agent.identity.hostname
+        );
+    }
+    info!("Relay stream for {} closed", agent.identity.hostname);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::{Heartbeat, RealtimeMetrics};
+
+    fn realtime(timestamp: u64) -> MetricsStreamRequest {
+        MetricsStreamRequest {
+            request: Some(metrics_stream_request::Request::Realtime(RealtimeMetrics {
+                timestamp,
+                ..Default::default()
+            })),
+        }
+    }
+
+    fn heartbeat(timestamp: u64) -> MetricsStreamRequest {
+        MetricsStreamRequest {
+            request: Some(metrics_stream_request::Request::Heartbeat(Heartbeat {
+                timestamp,
+                ..Default::default()
+            })),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_store_keeps_newest() {
+        let agent = Downstream::new(AgentIdentity::new("a1", "ship-01", "0.4.1"), 3);
+        agent.streams.fetch_add(1, Ordering::SeqCst);
+        agent.push(heartbeat(1));
+        agent.push(realtime(2));
+        agent.push(heartbeat(3));
+        agent.push(realtime(4));
+        agent.push(realtime(5));
+        assert_eq!(agent.dropped.load(Ordering::Relaxed), 1);
+
+        agent.pending().await;
+        let mut forwarded = Vec::new();
+        agent.detach();
+        while let Some(request) = agent.next().await {
+            forwarded.push(request);
+        }
+        // The first heartbeat was superseded, the oldest sample dropped
+        assert_eq!(forwarded, [heartbeat(3), realtime(4), realtime(5)]);
+    }
+
+    #[test]
+    fn test_static_info_is_resent() {
+        let agent = Downstream::new(AgentIdentity::new("a1", "ship-01", "0.4.1"), 10);
+        let static_info = MetricsStreamRequest {
+            request: Some(metrics_stream_request::Request::StaticInfo(
+                Default::default(),
+            )),
+        };
+        agent.push(static_info.clone());
+        assert!(agent.static_info().is_empty());
+
+        agent.queue.lock().clear();
+        assert_eq!(agent.static_info(), [static_info]);
+    }
+
+    #[test]
+    fn test_upstream_prefers_primary() {
+        let mut config = Config::sample();
+        let mut primary = config.servers[0].clone();
+        primary.host = "primary.example.com".to_string();
+        primary.role = Some(ServerRole::Primary);
+        config.servers.push(primary);
+        assert_eq!(upstream(&config).unwrap().host, "primary.example.com");
+    }
+}
@@ -0,0 +1,425 @@
+//! Server side of `NanoLinkService` for downstream agents
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use nanolink_client::AgentIdentity;
+use nanolink_client::protocol::{PROTOCOL_VERSION, SESSION_METADATA};
+use parking_lot::Mutex;
+use ring::rand::{SecureRandom, SystemRandom};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::metadata::MetadataMap;
+use tonic::{Request, Response, Status, Streaming};
+use tracing::{debug, info, warn};
+
+use super::Relay;
+use crate::proto::nano_link_service_server::NanoLinkService;
+use crate::proto::{
+    AgentInfoRequest, AgentInfoResponse, AuthRequest, AuthResponse, Command, CommandResult,
+    ConfigProfile, ConfigProfileRequest, EnrollRequest, EnrollResponse, HeartbeatAck,
+    HeartbeatRequest, HeartbeatResponse, Metrics, MetricsAck, MetricsStreamRequest,
+    MetricsStreamResponse, MetricsSyncRequest, MetricsSyncResponse, metrics_stream_request,
+    metrics_stream_response,
+};
+
+/// Responses buffered per downstream stream
+const STREAM_BUFFER: usize = 16;
+
+/// How long a session token can be used to open the metrics stream
+const SESSION_TTL: Duration = Duration::from_secs(60);
+
+/// An authentication no stream has claimed yet
+struct Session {
+    identity: AgentIdentity,
+    issued: Instant,
+}
+
+pub struct RelayService {
+    relay: Arc<Relay>,
+    /// Unclaimed sessions by token. A token opens one metrics stream.
+    sessions: Mutex<HashMap<String, Session>>,
+    rng: SystemRandom,
+}
+
+impl RelayService {
+    pub fn new(relay: Arc<Relay>) -> Self {
+        Self {
+            relay,
+            sessions: Mutex::new(HashMap::new()),
+            rng: SystemRandom::new(),
+        }
+    }
+
+    fn token_accepted(&self, token: &str) -> bool {
+        self.relay.config.relay.tokens.iter().any(|accepted| {
+            !accepted.is_empty()
+                && subtle::ConstantTimeEq::ct_eq(token.as_bytes(), accepted.as_bytes()).into()
+        })
+    }
+
+    /// Start a session for an authenticated agent, returning its token
+    fn open_session(&self, identity: AgentIdentity) -> Result<String, Status> {
+        let mut bytes = [0u8; 32];
+        self.rng
+            .fill(&mut bytes)
+            .map_err(|_| Status::internal("No randomness available"))?;
+        let token: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+        let mut sessions = self.sessions.lock();
+        sessions.retain(|_, session| session.issued.elapsed() < SESSION_TTL);
+        sessions.insert(
+            token.clone(),
+            Session {
+                identity,
+                issued: Instant::now(),
+            },
+        );
+        Ok(token)
+    }
+
+    /// Take the session whose token a stream presents
+    fn claim_session(&self, metadata: &MetadataMap) -> Result<AgentIdentity, Status> {
+        let token = metadata
+            .get(SESSION_METADATA)
+            .and_then(|token| token.to_str().ok())
+            .ok_or_else(|| Status::unauthenticated("Agent has not authenticated"))?;
+        self.sessions
+            .lock()
+            .remove(token)
+            .filter(|session| session.issued.elapsed() < SESSION_TTL)
+            .map(|session| session.identity)
+            .ok_or_else(|| Status::unauthenticated("Session expired or already in use"))
+    }
+}
+
+#[tonic::async_trait]
+impl NanoLinkService for RelayService {
+    async fn authenticate(
+        &self,
+        request: Request<AuthRequest>,
+    ) -> Result<Response<AuthResponse>, Status> {
+        let peer = request.remote_addr();
+        let request = request.into_inner();
+        if !self.token_accepted(&request.token) {
+            warn!(
+                "[AUDIT] Relay: rejected token from {} ({:?})",
+                request.hostname, peer
+            );
+            return Ok(Response::new(AuthResponse {
+                success: false,
+                error_message: "Invalid token".to_string(),
+                ..Default::default()
+            }));
+        }
+
+        info!(
+            "Relay: authenticated {} ({})",
+            request.hostname, request.agent_id
+        );
+        let identity = AgentIdentity {
+            agent_id: request.agent_id.clone(),
+            hostname: request.hostname,
+            agent_version: request.agent_version,
+            os: request.os,
+            arch: request.arch,
+            capabilities: request.capabilities,
+        };
+        let session_token = self.open_session(identity)?;
+
+        // Commands are not passed down, so read-only is all there is
+        Ok(Response::new(AuthResponse {
+            success: true,
+            permission_level: 0,
+            max_message_size: self.relay.config.agent.max_message_size as u32,
+            protocol_version: PROTOCOL_VERSION,
+            session_token,
+            ..Default::default()
+        }))
+    }
+
+    type StreamMetricsStream = ReceiverStream<Result<MetricsStreamResponse, Status>>;
+
+    async fn stream_metrics(
+        &self,
+        request: Request<Streaming<MetricsStreamRequest>>,
+    ) -> Result<Response<Self::StreamMetricsStream>, Status> {
+        let identity = self.claim_session(request.metadata())?;
+        let mut inbound = request.into_inner();
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        let relay = self.relay.clone();
+
+        // Agents send AgentInit once the response headers arrive, so the
+        // stream is answered first
+        tokio::spawn(async move {
+            let first = match inbound.message().await {
+                Ok(Some(message)) => message,
+                Ok(None) => return,
+                Err(e) => {
+                    debug!("Relay stream failed: {e}");
+                    return;
+                }
+            };
+            // Legacy streams send no AgentInit
+            let first = match &first.request {
+                Some(metrics_stream_request::Request::AgentInit(init))
+                    if init.agent_id != identity.agent_id =>
+                {
+                    warn!(
+                        "[AUDIT] Relay: {} authenticated as {} but streams as {}",
+                        identity.hostname, identity.agent_id, init.agent_id
+                    );
+                    let status = Status::permission_denied("AgentInit does not match the session");
+                    let _ = tx.send(Err(status)).await;
+                    return;
+                }
+                Some(metrics_stream_request::Request::AgentInit(_)) => None,
+                _ => Some(first),
+            };
+            let Some(agent) = relay.attach(identity.clone()) else {
+                warn!(
+                    "Relay: refused {}, relay.max_agents reached",
+                    identity.hostname
+                );
+                let status = Status::resource_exhausted("Relay has no room for more agents");
+                let _ = tx.send(Err(status)).await;
+                return;
+            };
+            info!(
+                "Relay: stream opened by {} ({})",
+                identity.hostname, identity.agent_id
+            );
+
+            if let Some(first) = first {
+                agent.push(first);
+            }
+            loop {
+                let message = match inbound.message().await {
+                    Ok(Some(message)) => message,
+                    Ok(None) => break,
+                    Err(e) => {
+                        debug!("Relay stream failed: {e}");
+                        break;
+                    }
+                };
+                let ack = match &message.request {
+                    Some(metrics_stream_request::Request::AgentInit(_)) => continue,
+                    Some(metrics_stream_request::Request::Heartbeat(heartbeat)) => {
+                        Some(HeartbeatAck {
+                            timestamp: heartbeat.timestamp,
+                        })
+                    }
+                    _ => None,
+                };
+                agent.push(message);
+                if let Some(ack) = ack {
+                    let response = MetricsStreamResponse {
+                        response: Some(metrics_stream_response::Response::HeartbeatAck(ack)),
+                    };
+                    if tx.send(Ok(response)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            agent.detach();
+            info!("Relay: stream closed by {}", identity.hostname);
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    type StreamCommandsStream = ReceiverStream<Result<MetricsStreamResponse, Status>>;
+
+    async fn stream_commands(
+        &self,
+        _request: Request<Streaming<MetricsStreamRequest>>,
+    ) -> Result<Response<Self::StreamCommandsStream>, Status> {
+        Err(Status::unimplemented(
+            "Commands are not passed through the relay",
+        ))
+    }
+
+    async fn report_metrics(
+        &self,
+        _request: Request<Metrics>,
+    ) -> Result<Response<MetricsAck>, Status> {
+        Err(Status::unimplemented("Use the metrics stream"))
+    }
+
+    async fn execute_command(
+        &self,
+        _request: Request<Command>,
+    ) -> Result<Response<CommandResult>, Status> {
+        Err(Status::unimplemented(
+            "Commands are not passed through the relay",
+        ))
+    }
+
+    async fn heartbeat(
+        &self,
+        _request: Request<HeartbeatRequest>,
+    ) -> Result<Response<HeartbeatResponse>, Status> {
+        Ok(Response::new(HeartbeatResponse {
+            server_timestamp: chrono::Utc::now().timestamp_millis() as u64,
+            config_changed: false,
+        }))
+    }
+
+    async fn sync_metrics(
+        &self,
+        _request: Request<MetricsSyncRequest>,
+    ) -> Result<Response<MetricsSyncResponse>, Status> {
+        Err(Status::unimplemented("Use the metrics stream"))
+    }
+
+    async fn get_agent_info(
+        &self,
+        _request: Request<AgentInfoRequest>,
+    ) -> Result<Response<AgentInfoResponse>, Status> {
+        Err(Status::unimplemented("Not available through the relay"))
+    }
+
+    async fn get_config_profile(
+        &self,
+        _request: Request<ConfigProfileRequest>,
+    ) -> Result<Response<ConfigProfile>, Status> {
+        Err(Status::not_found("The relay has no config profile"))
+    }
+
+    async fn enroll(
+        &self,
+        _request: Request<EnrollRequest>,
+    ) -> Result<Response<EnrollResponse>, Status> {
+        Err(Status::unimplemented("Enroll with the central server"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[tokio::test]
+    async fn test_authenticate_checks_token() {
+        let mut config = Config::sample();
+        config.relay.tokens = vec!["site-token".to_string(), String::new()];
+        let service = RelayService::new(Relay::new(Arc::new(config)));
+        let identity = AgentIdentity::new("a1", "ship-01", "0.4.1");
+
+        for (token, accepted) in [("site-token", true), ("other", false), ("", false)] {
+            let response = service
+                .authenticate(Request::new(identity.auth_request(token)))
+                .await
+                .unwrap()
+                .into_inner();
+            assert_eq!(response.success, accepted, "{token}");
+            assert_eq!(!response.session_token.is_empty(), accepted, "{token}");
+        }
+    }
+
+    #[test]
+    fn test_session_is_single_use_and_expires() {
+        let service = RelayService::new(Relay::new(Arc::new(Config::sample())));
+        let metadata = |token: &str| {
+            let mut metadata = MetadataMap::new();
+            metadata.insert(SESSION_METADATA, token.parse().unwrap());
+            metadata
+        };
+
+        let token = service
+            .open_session(AgentIdentity::new("a1", "ship-01", "0.4.1"))
+            .unwrap();
+        assert!(service.claim_session(&MetadataMap::new()).is_err());
+        assert!(service.claim_session(&metadata("guess")).is_err());
+        assert_eq!(
+            service.claim_session(&metadata(&token)).unwrap().agent_id,
+            "a1"
+        );
+        assert!(service.claim_session(&metadata(&token)).is_err());
+
+        let token = service
+            .open_session(AgentIdentity::new("a2", "ship-02", "0.4.1"))
+            .unwrap();
+        service.sessions.lock().get_mut(&token).unwrap().issued -= SESSION_TTL;
+        assert!(service.claim_session(&metadata(&token)).is_err());
+        assert!(service.sessions.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stream_requires_session() {
+        use crate::proto::Heartbeat;
+        use crate::proto::nano_link_service_server::NanoLinkServiceServer;
+        use nanolink_client::{Client, ConnectOptions, Server};
+        use tokio_stream::StreamExt;
+
+        let mut config = Config::sample();
+        config.relay.tokens = vec!["site-token".to_string()];
+        let service = RelayService::new(Relay::new(Arc::new(config)));
+        let incoming =
+            tonic::transport::server::TcpIncoming::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let server = Server {
+            host: "127.0.0.1".to_string(),
+            port: incoming.local_addr().unwrap().port(),
+            ..Default::default()
+        };
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(NanoLinkServiceServer::new(service))
+                .serve_with_incoming(incoming),
+        );
+
+        let identity = AgentIdentity::new("a1", "ship-01", "0.4.1");
+        let stream = |agent_id: &str| {
+            let init = MetricsStreamRequest {
+                request: Some(metrics_stream_request::Request::AgentInit(
+                    AgentIdentity::new(agent_id, "ship-01", "0.4.1").agent_init(),
+                )),
+            };
+            let heartbeat = MetricsStreamRequest {
+                request: Some(metrics_stream_request::Request::Heartbeat(Heartbeat {
+                    timestamp: 42,
+                    ..Default::default()
+                })),
+            };
+            tokio_stream::iter([init, heartbeat]).chain(tokio_stream::pending())
+        };
+
+        // Claiming an agent ID without authenticating
+        let mut client = Client::connect(&server, &ConnectOptions::default())
+            .await
+            .unwrap();
+        let err = client.stream_metrics(stream("a1")).await.unwrap_err();
+        let status = err.downcast_ref::<Status>().unwrap();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+        assert!(
+            client
+                .authenticate(identity.auth_request("site-token"))
+                .await
+                .unwrap()
+                .success
+        );
+        let mut responses = client.stream_metrics(stream("a1")).await.unwrap();
+        let ack = responses.message().await.unwrap().unwrap();
+        assert!(matches!(
+            ack.response,
+            Some(metrics_stream_response::Response::HeartbeatAck(
+                HeartbeatAck { timestamp: 42 }
+            ))
+        ));
+
+        // The session opened one stream
+        let err = client.stream_metrics(stream("a1")).await.unwrap_err();
+        let status = err.downcast_ref::<Status>().unwrap();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+        // A session can't stream as another agent
+        client
+            .authenticate(identity.auth_request("site-token"))
+            .await
+            .unwrap();
+        let mut responses = client.stream_metrics(stream("a2")).await.unwrap();
+        let status = responses.message().await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    }
+}
@@ -3,6 +3,7 @@ use std::sync::Arc;
 use subtle::ConstantTimeEq;
 
 use crate::config::Config;
+pub use nanolink_core::PermissionLevel;
 
 /// 常量时间字符串比较，防止时序攻击
 #[allow(dead_code)]
@@ -13,37 +14,6 @@ fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
     a.ct_eq(b).into()
 }
 
-/// Permission levels
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub enum PermissionLevel {
-    /// Read-only access - can only view metrics
-    ReadOnly = 0,
-    /// Basic write - can download logs, clear temp files
-    BasicWrite = 1,
-    /// Service control - can restart services, containers, kill processes
-    ServiceControl = 2,
-    /// System admin - full access including reboot and shell commands
-    SystemAdmin = 3,
-}
-
-impl From<u8> for PermissionLevel {
-    fn from(level: u8) -> Self {
-        match level {
-            0 => PermissionLevel::ReadOnly,
-            1 => PermissionLevel::BasicWrite,
-            2 => PermissionLevel::ServiceControl,
-            3 => PermissionLevel::SystemAdmin,
-            _ => PermissionLevel::ReadOnly,
-        }
-    }
-}
-
-impl From<i32> for PermissionLevel {
-    fn from(level: i32) -> Self {
-        Self::from(level.clamp(0, 3) as u8)
-    }
-}
-
 /// Authenticator for validating tokens
 #[allow(dead_code)]
 pub struct Authenticator {
@@ -0,0 +1,251 @@
+//! Capability discovery and negotiation
+//!
+//! During authentication the agent advertises which executors and collectors
+//! are enabled, and the server replies with the subset the token is allowed to
+//! use. The negotiated intersection is enforced locally before any command
+//! reaches an executor.
+
+use std::collections::BTreeSet;
+
+use crate::config::Config;
+use crate::proto::CommandType;
+
+/// Process listing and termination
+pub const CAP_PROCESS: &str = "process";
+/// Service start/stop/status
+pub const CAP_SERVICE: &str = "service";
+/// File tail/download/upload/truncate
+pub const CAP_FILE: &str = "file";
+/// Docker container operations
+pub const CAP_DOCKER: &str = "docker";
+/// Arbitrary shell commands
+pub const CAP_SHELL: &str = "shell";
+/// Agent self-update
+pub const CAP_UPDATE: &str = "update";
+/// Log queries
+pub const CAP_LOGS: &str = "logs";
+/// Predefined script execution
+pub const CAP_SCRIPTS: &str = "scripts";
+/// Config file management
+pub const CAP_CONFIG: &str = "config";
+/// Package management
+pub const CAP_PACKAGES: &str = "packages";
+/// Reboot and other system-level operations
+pub const CAP_SYSTEM: &str = "system";
+/// Health and connectivity checks
+pub const CAP_HEALTH: &str = "health";
+
+/// Map a command type to the capability it requires
+pub fn capability_for(command_type: CommandType) -> Option<&'static str> {
+    match command_type {
+        CommandType::ProcessList
+        | CommandType::ProcessKill
+        | CommandType::ProcessTree
+        | CommandType::ProcessKillTree => Some(CAP_PROCESS),
+
+        CommandType::ServiceStart
+        | CommandType::ServiceStop
+        | CommandType::ServiceRestart
+        | CommandType::ServiceStatus
+        | CommandType::ServiceRestartWithDependents
+        | CommandType::ServiceInventory
+        | CommandType::PrintQueueClear => Some(CAP_SERVICE),
+
+        CommandType::FileTail
+        | CommandType::FileDownload
+        | CommandType::FileUpload
+        | CommandType::FileTruncate
+        | CommandType::FileCleanup => Some(CAP_FILE),
+
+        CommandType::DockerList
+        | CommandType::DockerStart
+        | CommandType::DockerStop
+        | CommandType::DockerRestart
+        | CommandType::DockerLogs => Some(CAP_DOCKER),
+
+        CommandType::SystemReboot
+        | CommandType::SystemShutdown
+        | CommandType::SystemPowerCancel
+        | CommandType::SilenceStart
+        | CommandType::SilenceStop
+        | CommandType::AccountAudit => Some(CAP_SYSTEM),
+        CommandType::ShellExecute => Some(CAP_SHELL),
+
+        CommandType::AgentCheckUpdate
+        | CommandType::AgentDownloadUpdate
+        | CommandType::AgentApplyUpdate
+        | CommandType::AgentGetVersion => Some(CAP_UPDATE),
+
+        CommandType::ServiceLogs
+        | CommandType::SystemLogs
+        | CommandType::AuditLogs
+        | CommandType::LogStream
+        | CommandType::SessionRecordings
+        | CommandType::SessionExport => Some(CAP_LOGS),
+
+        CommandType::PackageList
+        | CommandType::PackageCheckUpdates
+        | CommandType::PackageUpdate
+        | CommandType::SystemUpdate
+        | CommandType::WindowsUpdateStatus
+        | CommandType::WindowsUpdateInstall
+        | CommandType::MaintenanceStatus
+        | CommandType::MaintenanceCancel => Some(CAP_PACKAGES),
+
+        CommandType::ScriptList | CommandType::ScriptExecute | CommandType::ScriptUpload => {
+            Some(CAP_SCRIPTS)
+        }
+
+        CommandType::ConfigRead
+        | CommandType::ConfigWrite
+        | CommandType::ConfigValidate
+        | CommandType::ConfigRollback
+        | CommandType::ConfigListBackups
+        | CommandType::ConfigRender
+        | CommandType::ConfigDiff => Some(CAP_CONFIG),
+
+        CommandType::HealthCheck
+        | CommandType::ConnectivityTest
+        | CommandType::NetworkSpeedtest => Some(CAP_HEALTH),
+
+        _ => None,
+    }
+}
+
+/// Capabilities this agent has enabled, as advertised in the auth request
+pub fn local_capabilities(config: &Config) -> Vec<String> {
+    let mut caps = vec![
+        CAP_PROCESS,
+        CAP_SERVICE,
+        CAP_FILE,
+        CAP_DOCKER,
+        CAP_SYSTEM,
+        CAP_UPDATE,
+        CAP_LOGS,
+        CAP_HEALTH,
+    ];
+
+    if config.shell.enabled {
+        caps.push(CAP_SHELL);
+    }
+    if config.scripts.enabled {
+        caps.push(CAP_SCRIPTS);
+    }
+    if config.config_management.enabled {
+        caps.push(CAP_CONFIG);
+    }
+    if config.package_management.enabled {
+        caps.push(CAP_PACKAGES);
+    }
+
+    // Collector capabilities
+    caps.push("metrics");
+    if config.collector.enable_layered_metrics {
+        caps.push("metrics.layered");
+    }
+    if config.collector.enable_disk_io {
+        caps.push("metrics.disk_io");
+    }
+    if config.collector.enable_network {
+        caps.push("metrics.network");
+    }
+
+    caps.into_iter().map(String::from).collect()
+}
+
+/// Negotiated capability set for a server session
+#[derive(Debug, Clone, Default)]
+pub struct CapabilitySet {
+    allowed: BTreeSet<String>,
+}
+
+impl CapabilitySet {
+    /// Intersect local capabilities with the server's allowed set.
+    ///
+    /// Servers that predate capability negotiation send an empty list; in that
+    /// case everything enabled locally stays allowed.
+    pub fn negotiate(local: &[String], server_allowed: &[String]) -> Self {
+        let allowed = if server_allowed.is_empty() {
+            local.iter().cloned().collect()
+        } else {
+            local
+                .iter()
+                .filter(|c| server_allowed.contains(c))
+                .cloned()
+                .collect()
+        };
+
+        Self { allowed }
+    }
+
+    /// Check whether a capability was negotiated
+    pub fn contains(&self, capability: &str) -> bool {
+        self.allowed.contains(capability)
+    }
+
+    /// Check whether a command is covered by the negotiated capabilities
+    pub fn check_command(&self, command_type: CommandType) -> Result<(), String> {
+        match capability_for(command_type) {
+            Some(cap) if self.contains(cap) => Ok(()),
+            Some(cap) => Err(format!(
+                "Capability '{cap}' is not enabled for this session (disabled on the agent or not granted to this token)"
+            )),
+            // Unknown commands are left to the permission checker
+            None => Ok(()),
+        }
+    }
+
+    /// Negotiated capabilities, sorted
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.allowed.iter().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn caps(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_negotiate_intersection() {
+        let set = CapabilitySet::negotiate(
+            &caps(&["process", "shell", "docker"]),
+            &caps(&["process", "docker", "packages"]),
+        );
+
+        assert!(set.contains("process"));
+        assert!(set.contains("docker"));
+        assert!(!set.contains("shell"));
+        assert!(!set.contains("packages"));
+    }
+
+    #[test]
+    fn test_negotiate_legacy_server() {
+        let set = CapabilitySet::negotiate(&caps(&["process", "shell"]), &[]);
+
+        assert!(set.contains("process"));
+        assert!(set.contains("shell"));
+    }
+
+    #[test]
+    fn test_check_command() {
+        let set = CapabilitySet::negotiate(&caps(&["process"]), &caps(&["process", "shell"]));
+
+        assert!(set.check_command(CommandType::ProcessKill).is_ok());
+        assert!(set.check_command(CommandType::ShellExecute).is_err());
+        assert!(set.check_command(CommandType::Unspecified).is_ok());
+    }
+
+    #[test]
+    fn test_local_capabilities_respect_config() {
+        let mut config = Config::sample();
+        config.shell.enabled = false;
+        assert!(!local_capabilities(&config).contains(&CAP_SHELL.to_string()));
+
+        config.shell.enabled = true;
+        assert!(local_capabilities(&config).contains(&CAP_SHELL.to_string()));
+    }
+}
@@ -0,0 +1,367 @@
+//! AppArmor profile and SELinux policy generation
+//! (`nanolink-agent security-profile`)
+//!
+//! The policy only grants what the features enabled in the config need:
+//! reading the config and /proc, writing agent state, listening on the
+//! configured ports, and running service managers, package managers or
+//! arbitrary commands when those executors are on. Commands started through
+//! the remote shell or scripts run unconfined; the policy can't predict
+//! what they touch.
+
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::config::Config;
+
+/// Policy flavour
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    AppArmor,
+    SeLinux,
+}
+
+/// A generated policy file
+#[derive(Debug)]
+pub struct PolicyFile {
+    pub name: String,
+    pub contents: String,
+}
+
+/// Features that change what the policy allows
+struct Features {
+    runs_commands: bool,
+    packages: bool,
+    services: bool,
+    helper: bool,
+    config_files: bool,
+    logs: bool,
+    listens_tcp: bool,
+    listens_udp: bool,
+}
+
+impl Features {
+    fn of(config: &Config) -> Self {
+        let executors = cfg!(feature = "executors");
+        Self {
+            runs_commands: executors && (config.shell.enabled || config.scripts.enabled),
+            packages: executors && config.package_management.enabled,
+            services: executors && !config.privsep.enabled,
+            helper: executors && config.privsep.enabled,
+            config_files: executors && config.config_management.enabled,
+            logs: executors,
+            listens_tcp: config.management.enabled
+                || config.syslog.enabled && !config.syslog.tcp_listen.is_empty(),
+            listens_udp: config.statsd.enabled
+                || config.syslog.enabled && !config.syslog.udp_listen.is_empty(),
+        }
+    }
+}
+
+/// Paths the agent writes to; relative ones depend on the service's working
+/// directory and are left out
+fn writable_paths(config: &Config) -> Vec<String> {
+    let mut paths = vec![
+        config.update.data_dir.clone(),
+        config.maintenance.state_file.clone(),
+        config.logging.audit_file.clone(),
+    ];
+    paths.extend(config.logging.file.clone());
+    if config.recording.enabled {
+        paths.push(config.recording.dir.clone());
+    }
+    if config.config_management.enabled {
+        paths.push(config.config_management.backup_dir.clone());
+    }
+    paths.retain(|path| Path::new(path).is_absolute());
+    paths
+}
+
+/// Generate the policy for `config`, loaded from `config_path` and run as
+/// `binary`
+pub fn generate(
+    config: &Config,
+    config_path: &Path,
+    binary: &Path,
+    format: Format,
+) -> Vec<PolicyFile> {
+    let features = Features::of(config);
+    match format {
+        Format::AppArmor => vec![apparmor(config, &features, config_path, binary)],
+        Format::SeLinux => selinux(config, &features, config_path, binary),
+    }
+}
+
+/// AppArmor rule covering a file or, for a directory, everything below it
+fn apparmor_path(path: &str, perms: &str) -> String {
+    if Path::new(path).extension().is_some() {
+        format!("  {path} {perms},\n")
+    } else {
+        let dir = path.trim_end_matches('/');
+        format!("  {dir}/ {perms},\n  {dir}/** {perms},\n")
+    }
+}
+
+fn apparmor(config: &Config, features: &Features, config_path: &Path, binary: &Path) -> PolicyFile {
+    let binary = binary.display().to_string();
+    let config_dir = config_path
+        .parent()
+        .map_or("/etc/nanolink".to_string(), |dir| dir.display().to_string());
+
+    let mut p = String::new();
+    let _ = writeln!(p, "# AppArmor profile for nanolink-agent, generated by");
+    let _ = writeln!(
+        p,
+        "# `nanolink-agent security-profile` from {}",
+        config_path.display()
+    );
+    p.push_str("abi <abi/3.0>,\ninclude <tunables/global>\n\n");
+    let _ = writeln!(p, "profile nanolink-agent {binary} {{");
+    p.push_str("  include <abstractions/base>\n");
+    p.push_str("  include <abstractions/nameservice>\n");
+    p.push_str("  include <abstractions/ssl_certs>\n\n");
+
+    p.push_str("  # Metrics\n");
+    p.push_str("  capability dac_read_search,\n  capability sys_ptrace,\n");
+    p.push_str("  ptrace (read),\n");
+    p.push_str("  @{PROC}/** r,\n  /sys/** r,\n  /dev/ r,\n  /run/systemd/** r,\n");
+    p.push_str("  /etc/machine-id r,\n  /etc/os-release r,\n  /usr/lib/os-release r,\n\n");
+
+    p.push_str("  # Connections\n");
+    p.push_str("  network inet stream,\n  network inet6 stream,\n");
+    p.push_str("  network inet dgram,\n  network inet6 dgram,\n");
+    p.push_str("  network unix stream,\n  network netlink raw,\n");
+    if features.listens_tcp || features.listens_udp {
+        p.push_str("  capability net_bind_service,\n");
+    }
+    p.push('\n');
+
+    p.push_str("  # Agent files\n");
+    let _ = writeln!(p, "  {binary} mrix,");
+    p.push_str(&apparmor_path(&config_dir, "r"));
+    for path in writable_paths(config) {
+        p.push_str(&apparmor_path(&path, "rwk"));
+    }
+    if config.collector.wasm.enabled {
+        p.push_str(&apparmor_path(&config.collector.wasm.dir, "r"));
+    }
+    // Self-update stages the new binary next to the old one
+    let _ = writeln!(p, "  {binary}.* rw,");
+
+    if features.logs {
+        p.push_str("\n  # Log reading commands\n  /var/log/** r,\n");
+        p.push_str("  /{,usr/}bin/journalctl Ux,\n");
+    }
+    if features.services {
+        p.push_str("\n  # Service control and power commands\n");
+        p.push_str("  /{,usr/}bin/systemctl Ux,\n  /{,usr/}{,s}bin/shutdown Ux,\n");
+        p.push_str("  /{,usr/}{,s}bin/service Ux,\n");
+    }
+    if features.helper {
+        p.push_str("\n  # Privileged helper\n");
+        let _ = writeln!(p, "  {} rw,", config.privsep.socket_path);
+    }
+    if features.packages {
+        p.push_str("\n  # Package managers\n");
+        p.push_str("  /usr/bin/{apt,apt-get,dpkg,dnf,yum,rpm,zypper,pacman,apk} Ux,\n");
+    }
+    if features.config_files {
+        p.push_str("\n  # Managed config files\n");
+        for pattern in &config.config_management.allowed_configs {
+            let _ = writeln!(p, "  {pattern} rw,");
+        }
+    }
+    if features.runs_commands {
+        p.push_str("\n  # Remote shell and scripts: commands run unconfined\n");
+        p.push_str(&apparmor_path(&config.scripts.scripts_dir, "r"));
+        p.push_str("  /{,usr/}{,local/}{,s}bin/* Ux,\n");
+    }
+    p.push_str("}\n");
+
+    PolicyFile {
+        name: "nanolink-agent".to_string(),
+        contents: p,
+    }
+}
+
+fn selinux(
+    config: &Config,
+    features: &Features,
+    config_path: &Path,
+    binary: &Path,
+) -> Vec<PolicyFile> {
+    let mut te = String::new();
+    te.push_str("policy_module(nanolink_agent, 1.0.0)\n\n");
+    let _ = writeln!(
+        te,
+        "# Generated by `nanolink-agent security-profile` from {}",
+        config_path.display()
+    );
+    te.push_str("# Load in permissive mode first: semanage permissive -a nanolink_agent_t\n\n");
+    te.push_str("type nanolink_agent_t;\ntype nanolink_agent_exec_t;\n");
+    te.push_str("init_daemon_domain(nanolink_agent_t, nanolink_agent_exec_t)\n\n");
+    te.push_str("type nanolink_agent_conf_t;\nfiles_config_file(nanolink_agent_conf_t)\n");
+    te.push_str("type nanolink_agent_var_lib_t;\nfiles_type(nanolink_agent_var_lib_t)\n");
+    te.push_str("type nanolink_agent_log_t;\nlogging_log_file(nanolink_agent_log_t)\n\n");
+
+    te.push_str("# Agent files\n");
+    te.push_str(
+        "read_files_pattern(nanolink_agent_t, nanolink_agent_conf_t, nanolink_agent_conf_t)\n",
+    );
+    te.push_str("manage_dirs_pattern(nanolink_agent_t, nanolink_agent_var_lib_t, nanolink_agent_var_lib_t)\n");
+    te.push_str("manage_files_pattern(nanolink_agent_t, nanolink_agent_var_lib_t, nanolink_agent_var_lib_t)\n");
+    te.push_str(
+        "manage_files_pattern(nanolink_agent_t, nanolink_agent_log_t, nanolink_agent_log_t)\n",
+    );
+    te.push_str("logging_log_filetrans(nanolink_agent_t, nanolink_agent_log_t, file)\n\n");
+
+    te.push_str("# Metrics\n");
+    te.push_str("allow nanolink_agent_t self:capability { dac_read_search sys_ptrace };\n");
+    te.push_str("kernel_read_system_state(nanolink_agent_t)\n");
+    te.push_str("kernel_read_network_state(nanolink_agent_t)\n");
+    te.push_str("dev_read_sysfs(nanolink_agent_t)\n");
+    te.push_str("domain_read_all_domains_state(nanolink_agent_t)\n");
+    te.push_str("fs_getattr_all_fs(nanolink_agent_t)\n");
+    te.push_str("files_read_etc_files(nanolink_agent_t)\n\n");
+
+    te.push_str("# Connections\n");
+    te.push_str("allow nanolink_agent_t self:tcp_socket create_stream_socket_perms;\n");
+    te.push_str("allow nanolink_agent_t self:udp_socket create_socket_perms;\n");
+    te.push_str("allow nanolink_agent_t self:netlink_route_socket r_netlink_socket_perms;\n");
+    te.push_str("sysnet_dns_name_resolve(nanolink_agent_t)\n");
+    te.push_str("corenet_tcp_connect_all_ports(nanolink_agent_t)\n");
+    te.push_str("miscfiles_read_generic_certs(nanolink_agent_t)\n");
+    if features.listens_tcp {
+        te.push_str("corenet_tcp_bind_generic_node(nanolink_agent_t)\n");
+        te.push_str("corenet_tcp_bind_all_ports(nanolink_agent_t)\n");
+    }
+    if features.listens_udp {
+        te.push_str("corenet_udp_bind_generic_node(nanolink_agent_t)\n");
+        te.push_str("corenet_udp_bind_all_ports(nanolink_agent_t)\n");
+    }
+
+    if features.logs {
+        te.push_str("\n# Log reading commands\nlogging_read_all_logs(nanolink_agent_t)\n");
+        te.push_str("corecmd_exec_bin(nanolink_agent_t)\n");
+    }
+    if features.services {
+        te.push_str("\n# Service control and power commands\n");
+        te.push_str("optional_policy(`\n\tsystemd_exec_systemctl(nanolink_agent_t)\n");
+        te.push_str("\tinit_manage_all_units(nanolink_agent_t)\n");
+        te.push_str("\tsystemd_start_power_units(nanolink_agent_t)\n')\n");
+    }
+    if features.helper {
+        te.push_str("\n# Privileged helper\n");
+        te.push_str("allow nanolink_agent_t self:unix_stream_socket create_stream_socket_perms;\n");
+        te.push_str("optional_policy(`\n\tinit_stream_connect(nanolink_agent_t)\n");
+        te.push_str("\tunconfined_stream_connect(nanolink_agent_t)\n')\n");
+    }
+    if features.packages {
+        te.push_str("\n# Package managers\n");
+        te.push_str("optional_policy(`\n\trpm_domtrans(nanolink_agent_t)\n')\n");
+        te.push_str("optional_policy(`\n\tapt_domtrans(nanolink_agent_t)\n')\n");
+    }
+    if features.config_files {
+        te.push_str("\n# Managed config files\nfiles_manage_etc_files(nanolink_agent_t)\n");
+    }
+    if features.runs_commands {
+        te.push_str("\n# Remote shell and scripts: commands run unconfined\n");
+        te.push_str("optional_policy(`\n\tunconfined_domtrans(nanolink_agent_t)\n')\n");
+        te.push_str("corecmd_exec_shell(nanolink_agent_t)\n");
+    }
+
+    let config_dir = config_path
+        .parent()
+        .map_or("/etc/nanolink".to_string(), |dir| dir.display().to_string());
+    let mut fc = String::new();
+    let _ = writeln!(
+        fc,
+        "{}\t--\tgen_context(system_u:object_r:nanolink_agent_exec_t,s0)",
+        binary.display()
+    );
+    let _ = writeln!(
+        fc,
+        "{config_dir}(/.*)?\tgen_context(system_u:object_r:nanolink_agent_conf_t,s0)"
+    );
+    for path in writable_paths(config) {
+        let (label, pattern) = if path == config.logging.audit_file
+            || config.logging.file.as_deref() == Some(path.as_str())
+        {
+            ("nanolink_agent_log_t", format!("{path}\t--"))
+        } else if Path::new(&path).extension().is_some() {
+            ("nanolink_agent_var_lib_t", format!("{path}\t--"))
+        } else {
+            ("nanolink_agent_var_lib_t", format!("{path}(/.*)?"))
+        };
+        let _ = writeln!(fc, "{pattern}\tgen_context(system_u:object_r:{label},s0)");
+    }
+
+    vec![
+        PolicyFile {
+            name: "nanolink_agent.te".to_string(),
+            contents: te,
+        },
+        PolicyFile {
+            name: "nanolink_agent.fc".to_string(),
+            contents: fc,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate_for(config: &Config, format: Format) -> Vec<PolicyFile> {
+        generate(
+            config,
+            Path::new("/etc/nanolink/nanolink.yaml"),
+            Path::new("/usr/local/bin/nanolink-agent"),
+            format,
+        )
+    }
+
+    #[test]
+    fn test_apparmor_follows_features() {
+        let mut config = Config::sample();
+        config.shell.enabled = false;
+        config.scripts.enabled = false;
+        config.package_management.enabled = false;
+        config.management.enabled = false;
+
+        let profile = &generate_for(&config, Format::AppArmor)[0].contents;
+        assert!(profile.contains("profile nanolink-agent /usr/local/bin/nanolink-agent {"));
+        assert!(profile.contains("  /etc/nanolink/** r,"));
+        assert!(profile.contains("  /var/lib/nanolink/maintenance.json rwk,"));
+        assert!(!profile.contains("net_bind_service"));
+        assert!(!profile.contains("/{,usr/}{,local/}{,s}bin/* Ux"));
+
+        config.management.enabled = true;
+        config.shell.enabled = true;
+        let profile = &generate_for(&config, Format::AppArmor)[0].contents;
+        assert!(profile.contains("capability net_bind_service,"));
+        assert_eq!(
+            profile.contains("/{,usr/}{,local/}{,s}bin/* Ux"),
+            cfg!(feature = "executors")
+        );
+        assert!(profile.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_selinux_module() {
+        let config = Config::sample();
+        let files = generate_for(&config, Format::SeLinux);
+        assert_eq!(files[0].name, "nanolink_agent.te");
+        assert!(
+            files[0]
+                .contents
+                .starts_with("policy_module(nanolink_agent, 1.0.0)")
+        );
+        assert_eq!(files[1].name, "nanolink_agent.fc");
+        assert!(files[1].contents.contains(
+            "/usr/local/bin/nanolink-agent\t--\tgen_context(system_u:object_r:nanolink_agent_exec_t,s0)"
+        ));
+        assert!(files[1].contents.contains(
+            "/var/lib/nanolink/data(/.*)?\tgen_context(system_u:object_r:nanolink_agent_var_lib_t,s0)"
+        ));
+    }
+}
@@ -1,5 +1,12 @@
 mod auth;
+pub mod capability;
+pub mod confinement;
+// Shell command and target checks are only used by the executors
+#[cfg_attr(not(feature = "executors"), allow(dead_code))]
 mod permission;
+#[cfg(target_os = "linux")]
+pub mod seccomp;
+#[cfg_attr(not(feature = "executors"), allow(dead_code))]
 pub mod validation;
 
 pub use permission::PermissionChecker;
@@ -37,7 +37,9 @@ impl PermissionChecker {
         match command_type {
             // Read-only operations (level 0)
             CommandType::ProcessList => 0,
+            CommandType::ProcessTree => 0,
             CommandType::ServiceStatus => 0,
+            CommandType::ServiceInventory => 0,
             CommandType::DockerList => 0,
             CommandType::FileTail => 0,
 
@@ -48,9 +50,11 @@ impl PermissionChecker {
 
             // Service control operations (level 2)
             CommandType::ProcessKill => 2,
+            CommandType::ProcessKillTree => 2,
             CommandType::ServiceStart => 2,
             CommandType::ServiceStop => 2,
             CommandType::ServiceRestart => 2,
+            CommandType::ServiceRestartWithDependents => 2,
             CommandType::DockerStart => 2,
             CommandType::DockerStop => 2,
             CommandType::DockerRestart => 2,
@@ -58,7 +62,10 @@ impl PermissionChecker {
 
             // System admin operations (level 3)
             CommandType::SystemReboot => 3,
+            CommandType::SystemShutdown => 3,
+            CommandType::SystemPowerCancel => 3,
             CommandType::ShellExecute => 3,
+            CommandType::FileCleanup => 3,
 
             // Agent update operations (level 3 - SYSTEM_ADMIN required)
             CommandType::AgentCheckUpdate => 3,
@@ -76,11 +83,19 @@ impl PermissionChecker {
             CommandType::PackageList => 0, // Read-only, all levels
             CommandType::PackageCheckUpdates => 0, // Read-only, all levels
             CommandType::PackageUpdate => 3, // SYSTEM_ADMIN only
+            CommandType::WindowsUpdateStatus => 0, // Read-only, all levels
+            CommandType::WindowsUpdateInstall => 3, // SYSTEM_ADMIN only
+            CommandType::MaintenanceStatus => 0, // Read-only, all levels
+            CommandType::MaintenanceCancel => 3, // SYSTEM_ADMIN only
+            CommandType::SilenceStart => 2, // SERVICE_CONTROL
+            CommandType::SilenceStop => 2, // SERVICE_CONTROL
             CommandType::SystemUpdate => 3, // SYSTEM_ADMIN only
 
             // Script execution commands
             CommandType::ScriptList => 0,    // Read-only, all levels
             CommandType::ScriptExecute => 2, // SERVICE_CONTROL for whitelisted scripts
+            CommandType::SessionRecordings => 3, // SYSTEM_ADMIN only (recorded output)
+            CommandType::SessionExport => 3, // SYSTEM_ADMIN only
             CommandType::ScriptUpload => 3,  // SYSTEM_ADMIN only
 
             // Config management commands
@@ -89,10 +104,19 @@ impl PermissionChecker {
             CommandType::ConfigValidate => 0, // All levels can validate
             CommandType::ConfigRollback => 2, // SERVICE_CONTROL
             CommandType::ConfigListBackups => 0, // Read-only
+            CommandType::ConfigRender => 2, // SERVICE_CONTROL, may restart a service
+            CommandType::ConfigDiff => 0, // Read-only, sanitized
 
             // Health check commands
             CommandType::HealthCheck => 0,      // All levels
             CommandType::ConnectivityTest => 0, // All levels
+            CommandType::NetworkSpeedtest => 1, // Consumes bandwidth, rate limited
+
+            // Printing
+            CommandType::PrintQueueClear => 2, // SERVICE_CONTROL, like a spooler restart
+
+            // Security audit
+            CommandType::AccountAudit => 2, // SERVICE_CONTROL, like AUDIT_LOGS
 
             // Unknown commands require highest level
             _ => 3,
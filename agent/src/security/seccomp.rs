@@ -0,0 +1,147 @@
+//! Seccomp filter for the agent process (`security.hardening.seccomp`)
+//!
+//! This is a deny list, not an allow list. The syscalls made by collectors,
+//! shell commands and package managers can't be listed up front, but the
+//! agent never needs to load kernel modules, kexec, attach BPF programs or
+//! set the clock. The filter covers every thread and is inherited by child
+//! processes, so a compromised agent can't reach those syscalls through a
+//! command either.
+
+use std::collections::BTreeMap;
+
+use seccompiler::{BpfProgram, SeccompFilter, TargetArch};
+use tracing::info;
+
+use crate::config::{Config, SeccompAction};
+
+/// Never needed by the agent or anything it runs
+const ALWAYS_DENIED: &[i64] = &[
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+    libc::SYS_kexec_load,
+    libc::SYS_bpf,
+    libc::SYS_perf_event_open,
+    libc::SYS_userfaultfd,
+    libc::SYS_keyctl,
+    libc::SYS_add_key,
+    libc::SYS_request_key,
+    libc::SYS_acct,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+    libc::SYS_pivot_root,
+    libc::SYS_open_by_handle_at,
+    libc::SYS_settimeofday,
+    libc::SYS_clock_settime,
+    libc::SYS_clock_adjtime,
+    libc::SYS_adjtimex,
+    // Rebooting goes through the init system (`shutdown -r`)
+    libc::SYS_reboot,
+];
+
+/// Debuggers such as strace or gdb, run through the remote shell
+const DEBUG: &[i64] = &[
+    libc::SYS_ptrace,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+];
+
+/// Commands and package installs (snaps, loop images) may mount
+const MOUNT: &[i64] = &[libc::SYS_mount, libc::SYS_umount2];
+
+/// Syscalls denied for the features enabled in `config`
+fn denied_syscalls(config: &Config) -> Vec<i64> {
+    let mut denied = ALWAYS_DENIED.to_vec();
+    #[cfg(not(target_arch = "riscv64"))]
+    denied.push(libc::SYS_kexec_file_load);
+
+    let runs_commands =
+        cfg!(feature = "executors") && (config.shell.enabled || config.scripts.enabled);
+    if !runs_commands {
+        denied.extend_from_slice(DEBUG);
+    }
+    if !(runs_commands || cfg!(feature = "executors") && config.package_management.enabled) {
+        denied.extend_from_slice(MOUNT);
+    }
+    denied
+}
+
+/// Compile the filter for this machine's architecture
+fn compile(config: &Config) -> Result<BpfProgram, String> {
+    let arch = TargetArch::try_from(std::env::consts::ARCH).map_err(|e| e.to_string())?;
+    let denied_action = match config.security.hardening.seccomp_action {
+        SeccompAction::Errno => seccompiler::SeccompAction::Errno(libc::EPERM as u32),
+        SeccompAction::Log => seccompiler::SeccompAction::Log,
+        SeccompAction::Kill => seccompiler::SeccompAction::KillProcess,
+    };
+    let rules: BTreeMap<_, _> = denied_syscalls(config)
+        .into_iter()
+        .map(|syscall| (syscall, Vec::new()))
+        .collect();
+
+    let filter = SeccompFilter::new(
+        rules,
+        seccompiler::SeccompAction::Allow,
+        denied_action,
+        arch,
+    )
+    .map_err(|e| e.to_string())?;
+    BpfProgram::try_from(filter).map_err(|e| e.to_string())
+}
+
+/// Install the filter on all threads of the process if enabled
+///
+/// Also sets `no_new_privs`, so setuid programs such as `sudo` no longer
+/// gain privileges when run by the agent.
+pub fn apply(config: &Config) -> Result<(), String> {
+    if !config.security.hardening.seccomp {
+        return Ok(());
+    }
+    let program = compile(config)?;
+    seccompiler::apply_filter_all_threads(&program).map_err(|e| e.to_string())?;
+    info!(
+        "Seccomp filter active: {} syscalls denied ({:?})",
+        denied_syscalls(config).len(),
+        config.security.hardening.seccomp_action
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_denied_syscalls_follow_features() {
+        let mut config = Config::sample();
+        config.shell.enabled = false;
+        config.scripts.enabled = false;
+        config.package_management.enabled = false;
+        let denied = denied_syscalls(&config);
+        assert!(denied.contains(&libc::SYS_init_module));
+        assert!(denied.contains(&libc::SYS_ptrace));
+        assert!(denied.contains(&libc::SYS_mount));
+        assert!(!denied.contains(&libc::SYS_openat));
+
+        #[cfg(feature = "executors")]
+        {
+            config.package_management.enabled = true;
+            let denied = denied_syscalls(&config);
+            assert!(denied.contains(&libc::SYS_ptrace));
+            assert!(!denied.contains(&libc::SYS_mount));
+        }
+    }
+
+    #[test]
+    fn test_filter_compiles() {
+        let mut config = Config::sample();
+        for action in [
+            SeccompAction::Errno,
+            SeccompAction::Log,
+            SeccompAction::Kill,
+        ] {
+            config.security.hardening.seccomp_action = action;
+            assert!(!compile(&config).unwrap().is_empty());
+        }
+    }
+}
@@ -106,6 +106,40 @@ pub fn validate_process_name(name: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Validates a printer (print queue) name.
+///
+/// Windows printer names may contain spaces, parentheses and a
+/// `\\server\` prefix, so only characters that could break out of an
+/// argument or a quoted PowerShell string are refused.
+pub fn validate_printer_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Printer name cannot be empty".to_string());
+    }
+    if name.len() > 255 {
+        return Err("Printer name is too long".to_string());
+    }
+    if name.starts_with('-') {
+        return Err("Printer name cannot start with '-'".to_string());
+    }
+
+    const DANGEROUS_CHARS: &[char] = &[';', '|', '&', '$', '`', '<', '>', '"', '\'', '%'];
+
+    for c in name.chars() {
+        if c.is_control() || DANGEROUS_CHARS.contains(&c) {
+            warn!(
+                "[SECURITY] Blocked printer name with dangerous character: {}",
+                name.escape_debug()
+            );
+            return Err(format!(
+                "Printer name contains forbidden character: '{}'",
+                c.escape_debug()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
 /// Check if a PID is a protected system process
 /// Returns Err if the process should not be killed
 pub fn validate_pid_killable(pid: u32) -> Result<(), String> {
@@ -157,6 +191,19 @@ mod tests {
         assert!(validate_service_name("foo bar").is_err());
     }
 
+    #[test]
+    fn test_printer_name_validation() {
+        assert!(validate_printer_name("HP_LaserJet_M404").is_ok());
+        assert!(validate_printer_name("HP LaserJet (Copy 1)").is_ok());
+        assert!(validate_printer_name("\\\\print01\\Floor 2").is_ok());
+
+        assert!(validate_printer_name("").is_err());
+        assert!(validate_printer_name("-a").is_err());
+        assert!(validate_printer_name("x'; Remove-Item C:\\").is_err());
+        assert!(validate_printer_name("x$(id)").is_err());
+        assert!(validate_printer_name("x\ny").is_err());
+    }
+
     #[test]
     fn test_pid_protection() {
         assert!(validate_pid_killable(0).is_err());
@@ -0,0 +1,360 @@
+//! Silence (maintenance mode)
+//!
+//! `nanolink-agent silence start --duration 2h --reason patching`, the
+//! management API or a SILENCE_START command puts the host under maintenance
+//! until the duration runs out or the silence is stopped. While silenced:
+//!
+//! - alert rule, anomaly and disk forecast events that fire are dropped;
+//!   resolutions still go out so nothing is left open,
+//! - connection-lost notifications are not sent,
+//! - with `reject_commands`, mutating commands from servers are refused.
+//!
+//! The silence is reported in every heartbeat, and start and end are
+//! published as `silence` events. It is kept in
+//! `/var/lib/nanolink/silence.json` (`%ProgramData%\nanolink` on Windows)
+//! so a reboot during patching doesn't end it.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::collector::syslog;
+use crate::proto::{CommandResult, CommandType, LogBatch, LogEntry, SilenceState};
+
+/// Longest silence accepted
+const MAX_DURATION: Duration = Duration::from_secs(30 * 86_400);
+
+/// Event sources whose firing events a silence drops
+pub const ALERT_SOURCES: [&str; 3] = ["alerts", "anomaly", "disk_forecast"];
+
+static STATE: OnceLock<Mutex<Option<Silence>>> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Silence {
+    pub reason: String,
+    pub started_at: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub reject_commands: bool,
+    /// "management" (CLI, local API) or the server address
+    pub started_by: String,
+}
+
+impl Silence {
+    pub fn to_proto(&self) -> SilenceState {
+        SilenceState {
+            active: true,
+            reason: self.reason.clone(),
+            started_at: self.started_at.to_rfc3339(),
+            until: self.until.to_rfc3339(),
+            reject_commands: self.reject_commands,
+            started_by: self.started_by.clone(),
+        }
+    }
+}
+
+fn state_file() -> PathBuf {
+    #[cfg(windows)]
+    {
+        let base = std::env::var("ProgramData").unwrap_or_else(|_| "C:\\ProgramData".to_string());
+        PathBuf::from(base).join("nanolink").join("silence.json")
+    }
+    #[cfg(unix)]
+    {
+        PathBuf::from("/var/lib/nanolink/silence.json")
+    }
+}
+
+fn state() -> &'static Mutex<Option<Silence>> {
+    STATE.get_or_init(|| {
+        let silence = (!cfg!(test))
+            .then(|| std::fs::read(state_file()).ok())
+            .flatten()
+            .and_then(|json| serde_json::from_slice(&json).ok());
+        Mutex::new(silence)
+    })
+}
+
+fn save(silence: Option<&Silence>) {
+    if cfg!(test) {
+        return;
+    }
+    let path = state_file();
+    let written = match silence {
+        Some(silence) => serde_json::to_vec_pretty(silence)
+            .map_err(std::io::Error::other)
+            .and_then(|json| {
+                path.parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .and_then(|()| std::fs::write(&path, json))
+            }),
+        None => match std::fs::remove_file(&path) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            other => other,
+        },
+    };
+    if let Err(e) = written {
+        warn!("Failed to save silence to {}: {}", path.display(), e);
+    }
+}
+
+/// The silence in effect, ending it if it has run out
+pub fn current() -> Option<Silence> {
+    let ended = {
+        let mut state = state().lock();
+        if state.as_ref().is_none_or(|s| s.until > Utc::now()) {
+            return state.clone();
+        }
+        state.take()
+    };
+    save(None);
+    if let Some(silence) = ended {
+        info!("Silence ended (expired): {}", silence.reason);
+        publish(&silence, "ended", "expired");
+    }
+    None
+}
+
+/// Whether the host is silenced
+pub fn is_active() -> bool {
+    current().is_some()
+}
+
+/// Silence the host for `duration`, replacing any current silence
+pub fn start(
+    duration: Duration,
+    reason: &str,
+    reject_commands: bool,
+    started_by: &str,
+) -> Result<Silence, String> {
+    if duration.is_zero() || duration > MAX_DURATION {
+        return Err(format!(
+            "Duration must be between 1s and {} days",
+            MAX_DURATION.as_secs() / 86_400
+        ));
+    }
+    let now = Utc::now();
+    let silence = Silence {
+        reason: reason.to_string(),
+        started_at: now,
+        until: now + chrono::Duration::from_std(duration).map_err(|e| e.to_string())?,
+        reject_commands,
+        started_by: started_by.to_string(),
+    };
+    *state().lock() = Some(silence.clone());
+    save(Some(&silence));
+    info!(
+        "[AUDIT] Silence started by {} until {} ({}){}",
+        started_by,
+        silence.until.to_rfc3339(),
+        reason,
+        if reject_commands {
+            ", rejecting mutating commands"
+        } else {
+            ""
+        }
+    );
+    publish(&silence, "started", started_by);
+    Ok(silence)
+}
+
+/// End the current silence, returning it
+pub fn stop(stopped_by: &str) -> Option<Silence> {
+    let ended = state().lock().take();
+    if let Some(silence) = &ended {
+        save(None);
+        info!("[AUDIT] Silence stopped by {}", stopped_by);
+        publish(silence, "ended", stopped_by);
+    }
+    ended
+}
+
+fn publish(silence: &Silence, state: &str, by: &str) {
+    let message = match state {
+        "started" => format!(
+            "Host silenced until {}: {}",
+            silence.until.to_rfc3339(),
+            silence.reason
+        ),
+        _ => format!("Silence ended: {}", silence.reason),
+    };
+    syslog::publish(LogBatch {
+        source: "silence".to_string(),
+        entries: vec![LogEntry {
+            timestamp: Utc::now().to_rfc3339(),
+            level: "info".to_string(),
+            source: "silence".to_string(),
+            message,
+            metadata: HashMap::from([
+                ("state".to_string(), state.to_string()),
+                ("reason".to_string(), silence.reason.clone()),
+                ("until".to_string(), silence.until.to_rfc3339()),
+                ("by".to_string(), by.to_string()),
+            ]),
+        }],
+        dropped: 0,
+    });
+}
+
+/// Drop firing alert events from `batch` while silenced
+pub fn filter_batch(batch: &mut LogBatch) {
+    if ALERT_SOURCES.contains(&batch.source.as_str()) && is_active() {
+        batch
+            .entries
+            .retain(|e| e.metadata.get("state").is_some_and(|s| s == "resolved"));
+    }
+}
+
+/// Why `command_type` is refused, if the silence rejects it
+pub fn rejection(command_type: CommandType) -> Option<String> {
+    if !is_mutating(command_type) {
+        return None;
+    }
+    current().filter(|s| s.reject_commands).map(|s| {
+        format!(
+            "Host is silenced until {}: {}",
+            s.until.to_rfc3339(),
+            s.reason
+        )
+    })
+}
+
+/// Commands that change the host (silence commands excepted)
+fn is_mutating(command_type: CommandType) -> bool {
+    matches!(
+        command_type,
+        CommandType::ProcessKill
+            | CommandType::ProcessKillTree
+            | CommandType::ServiceStart
+            | CommandType::ServiceStop
+            | CommandType::ServiceRestart
+            | CommandType::ServiceRestartWithDependents
+            | CommandType::FileUpload
+            | CommandType::FileTruncate
+            | CommandType::FileCleanup
+            | CommandType::DockerStart
+            | CommandType::DockerStop
+            | CommandType::DockerRestart
+            | CommandType::SystemReboot
+            | CommandType::SystemShutdown
+            | CommandType::ShellExecute
+            | CommandType::AgentDownloadUpdate
+            | CommandType::AgentApplyUpdate
+            | CommandType::PackageUpdate
+            | CommandType::SystemUpdate
+            | CommandType::WindowsUpdateInstall
+            | CommandType::ScriptExecute
+            | CommandType::ScriptUpload
+            | CommandType::ConfigWrite
+            | CommandType::ConfigRollback
+            | CommandType::ConfigRender
+            | CommandType::PrintQueueClear
+    )
+}
+
+/// Parse a duration like `90s`, `30m`, `2h`, `1d` or `1h30m`; a bare
+/// number is minutes
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    if let Ok(minutes) = input.parse::<u64>() {
+        return Ok(Duration::from_secs(minutes * 60));
+    }
+    let invalid = || format!("Invalid duration: {input} (e.g. 30m, 2h, 1d, 1h30m)");
+    let mut total = 0u64;
+    let mut number = String::new();
+    for c in input.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86_400,
+            _ => return Err(invalid()),
+        };
+        let value: u64 = std::mem::take(&mut number).parse().map_err(|_| invalid())?;
+        total = value
+            .checked_mul(unit)
+            .and_then(|v| total.checked_add(v))
+            .ok_or_else(invalid)?;
+    }
+    if !number.is_empty() || total == 0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(total))
+}
+
+/// SILENCE_START. Params: `duration` (default 1h), `reason`,
+/// `reject_commands` ("true" to refuse mutating commands)
+pub fn start_command(params: &HashMap<String, String>, server: &str) -> CommandResult {
+    let started =
+        parse_duration(params.get("duration").map_or("1h", String::as_str)).and_then(|duration| {
+            start(
+                duration,
+                params.get("reason").map_or("", String::as_str),
+                params.get("reject_commands").is_some_and(|v| v == "true"),
+                server,
+            )
+        });
+    match started {
+        Ok(silence) => CommandResult {
+            success: true,
+            output: format!("Silenced until {}", silence.until.to_rfc3339()),
+            silence: Some(silence.to_proto()),
+            ..Default::default()
+        },
+        Err(error) => CommandResult {
+            success: false,
+            error,
+            ..Default::default()
+        },
+    }
+}
+
+/// SILENCE_STOP
+pub fn stop_command(server: &str) -> CommandResult {
+    let output = match stop(server) {
+        Some(silence) => format!("Silence ended: {}", silence.reason),
+        None => "Host was not silenced".to_string(),
+    };
+    CommandResult {
+        success: true,
+        output,
+        silence: Some(SilenceState::default()),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(86_400)));
+        assert_eq!(parse_duration("45"), Ok(Duration::from_secs(2700)));
+        assert!(parse_duration("2x").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("1h30").is_err());
+        assert!(parse_duration("0m").is_err());
+    }
+
+    #[test]
+    fn test_mutating_commands() {
+        assert!(is_mutating(CommandType::ServiceRestart));
+        assert!(is_mutating(CommandType::ShellExecute));
+        assert!(!is_mutating(CommandType::ProcessList));
+        assert!(!is_mutating(CommandType::SystemPowerCancel));
+        assert!(!is_mutating(CommandType::SilenceStop));
+    }
+}
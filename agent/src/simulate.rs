@@ -0,0 +1,390 @@
+//! Load test with simulated agents
+//!
+//! `nanolink-agent simulate --hosts 500` connects that many virtual agents to
+//! a configured server, each with its own agent ID and hostname. They send
+//! static info once per connection, then synthetic realtime metrics and
+//! heartbeats, so server capacity can be checked without a fleet. With
+//! `churn` each agent drops its connection after a random time around that
+//! value and reconnects; with no `ramp` all agents connect at once, like a
+//! fleet reconnecting after a server restart.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use nanolink_client::AgentIdentity;
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+use tokio::time;
+use tracing::debug;
+
+use crate::config::{Config, ServerConfig};
+use crate::connection::grpc::GrpcClient;
+use crate::proto::{
+    CpuStaticInfo, DiskIo, DiskStaticInfo, Heartbeat, MemoryStaticInfo, MetricsStreamRequest,
+    NetworkIo, NetworkStaticInfo, RealtimeMetrics, StaticInfo, metrics_stream_request::Request,
+};
+
+/// What the simulated fleet looks like
+#[derive(Debug, Clone)]
+pub struct SimulateOptions {
+    pub hosts: u32,
+    /// Prefix of hostnames and agent IDs, so several simulators can share a server
+    pub prefix: String,
+    /// Realtime metrics interval
+    pub interval: Duration,
+    /// Mean connection lifetime before an agent reconnects; zero keeps agents connected
+    pub churn: Duration,
+    /// Initial connections are spread over this time
+    pub ramp: Duration,
+    /// Metric cardinality per host
+    pub cores: u32,
+    pub disks: u32,
+    pub interfaces: u32,
+}
+
+/// Fleet counters, printed while the simulation runs
+#[derive(Debug, Default)]
+pub struct SimulateStats {
+    pub connected: AtomicU64,
+    pub connects: AtomicU64,
+    pub failures: AtomicU64,
+    pub messages: AtomicU64,
+}
+
+impl SimulateStats {
+    pub fn summary(&self, hosts: u32) -> String {
+        format!(
+            "connected {}/{}, connects {}, failures {}, messages {}",
+            self.connected.load(Ordering::Relaxed),
+            hosts,
+            self.connects.load(Ordering::Relaxed),
+            self.failures.load(Ordering::Relaxed),
+            self.messages.load(Ordering::Relaxed)
+        )
+    }
+}
+
+/// SplitMix64, enough to vary synthetic metrics between hosts and samples
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in [low, high)
+    fn range(&mut self, low: f64, high: f64) -> f64 {
+        low + (high - low) * self.unit()
+    }
+}
+
+/// One simulated host. Its metrics wander from sample to sample.
+struct VirtualHost {
+    identity: AgentIdentity,
+    options: Arc<SimulateOptions>,
+    rng: Rng,
+    memory_total: u64,
+    cpu: f64,
+    memory: f64,
+    sequence: u64,
+}
+
+impl VirtualHost {
+    fn new(index: u32, options: Arc<SimulateOptions>) -> Self {
+        let name = format!("{}-{:04}", options.prefix, index);
+        let mut rng = Rng(u64::from(index));
+        let memory_total = (4u64 << 30) << (rng.next() % 4);
+        Self {
+            identity: AgentIdentity::new(agent_id(&name), name, env!("CARGO_PKG_VERSION")),
+            options,
+            cpu: rng.range(5.0, 60.0),
+            memory: rng.range(0.2, 0.8),
+            rng,
+            memory_total,
+            sequence: 0,
+        }
+    }
+
+    fn static_info(&self) -> StaticInfo {
+        let options = &self.options;
+        StaticInfo {
+            timestamp: now_ms(),
+            cpu: Some(CpuStaticInfo {
+                model: "Simulated CPU".to_string(),
+                vendor: "NanoLink".to_string(),
+                physical_cores: options.cores,
+                logical_cores: options.cores,
+                architecture: self.identity.arch.clone(),
+                frequency_max_mhz: 3000,
+                ..Default::default()
+            }),
+            memory: Some(MemoryStaticInfo {
+                total: self.memory_total,
+                ..Default::default()
+            }),
+            disks: (0..options.disks)
+                .map(|i| DiskStaticInfo {
+                    device: disk_name(i),
+                    mount_point: if i == 0 {
+                        "/".to_string()
+                    } else {
+                        format!("/data{i}")
+                    },
+                    fs_type: "ext4".to_string(),
+                    disk_type: "SSD".to_string(),
+                    total_bytes: 256 << 30,
+                    ..Default::default()
+                })
+                .collect(),
+            networks: (0..options.interfaces)
+                .map(|i| NetworkStaticInfo {
+                    interface: format!("eth{i}"),
+                    speed_mbps: 1000,
+                    interface_type: "ethernet".to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            agent_version: self.identity.agent_version.clone(),
+            agent_id: self.identity.agent_id.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn realtime(&mut self) -> RealtimeMetrics {
+        self.cpu = (self.cpu + self.rng.range(-5.0, 5.0)).clamp(0.0, 100.0);
+        self.memory = (self.memory + self.rng.range(-0.01, 0.01)).clamp(0.05, 0.95);
+        self.sequence += 1;
+        let cpu = self.cpu;
+        let rng = &mut self.rng;
+        RealtimeMetrics {
+            timestamp: now_ms(),
+            cpu_usage_percent: cpu,
+            cpu_per_core: (0..self.options.cores)
+                .map(|_| (cpu + rng.range(-10.0, 10.0)).clamp(0.0, 100.0))
+                .collect(),
+            memory_used: (self.memory_total as f64 * self.memory) as u64,
+            disk_io: (0..self.options.disks)
+                .map(|i| DiskIo {
+                    device: disk_name(i),
+                    read_bytes_sec: (rng.unit() * 50e6) as u64,
+                    write_bytes_sec: (rng.unit() * 20e6) as u64,
+                    read_iops: rng.next() % 2000,
+                    write_iops: rng.next() % 1000,
+                })
+                .collect(),
+            network_io: (0..self.options.interfaces)
+                .map(|i| NetworkIo {
+                    interface: format!("eth{i}"),
+                    rx_bytes_sec: (rng.unit() * 10e6) as u64,
+                    tx_bytes_sec: (rng.unit() * 5e6) as u64,
+                    rx_packets_sec: rng.next() % 10_000,
+                    tx_packets_sec: rng.next() % 5_000,
+                    is_up: true,
+                })
+                .collect(),
+            load_average: vec![cpu / 100.0 * f64::from(self.options.cores); 3],
+            agent_id: self.identity.agent_id.clone(),
+            sequence: self.sequence,
+            ..Default::default()
+        }
+    }
+
+    /// How long the next connection lasts; `None` without churn
+    fn lifetime(&mut self) -> Option<Duration> {
+        let churn = self.options.churn;
+        (!churn.is_zero()).then(|| churn.mul_f64(self.rng.range(0.5, 1.5)))
+    }
+}
+
+/// Stable UUID-formatted agent ID for a simulated hostname
+fn agent_id(name: &str) -> String {
+    let digest = Sha256::digest(format!("nanolink-simulate:{name}").as_bytes());
+    let hex: String = digest[..16].iter().map(|b| format!("{b:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+fn disk_name(index: u32) -> String {
+    format!("sd{}", char::from(b'a' + (index % 26) as u8))
+}
+
+fn now_ms() -> u64 {
+    chrono::Utc::now().timestamp_millis().max(0) as u64
+}
+
+/// Run one simulated host until the task is dropped
+pub async fn run_host(
+    index: u32,
+    server: ServerConfig,
+    config: Arc<Config>,
+    options: Arc<SimulateOptions>,
+    stats: Arc<SimulateStats>,
+) {
+    let mut host = VirtualHost::new(index, options.clone());
+    let mut config = (*config).clone();
+    config.agent.agent_id = Some(host.identity.agent_id.clone());
+    config.agent.hostname = Some(host.identity.hostname.clone());
+    let config = Arc::new(config);
+
+    if options.hosts > 0 {
+        time::sleep(
+            options
+                .ramp
+                .mul_f64(f64::from(index) / f64::from(options.hosts)),
+        )
+        .await;
+    }
+
+    loop {
+        stats.connects.fetch_add(1, Ordering::Relaxed);
+        let session = async {
+            let mut client = GrpcClient::connect(&server, &config).await?;
+            let auth = client.authenticate().await?;
+            if !auth.success {
+                anyhow::bail!("Authentication failed: {}", auth.error_message);
+            }
+            stats.connected.fetch_add(1, Ordering::Relaxed);
+            let (samples_tx, mut samples_rx) = mpsc::channel(1);
+            let heartbeat_interval = Duration::from_secs(config.agent.heartbeat_interval.max(1));
+            let lifetime = host.lifetime();
+            let stream_stats = stats.clone();
+            let stream = client.send_stream(move |tx| {
+                async move {
+                    let deadline = lifetime.map(|lifetime| time::Instant::now() + lifetime);
+                    // AgentInit has to go first, so no heartbeat right away
+                    let mut heartbeat = time::interval_at(
+                        time::Instant::now() + heartbeat_interval,
+                        heartbeat_interval,
+                    );
+                    loop {
+                        let request = tokio::select! {
+                            Some(request) = samples_rx.recv() => request,
+                            _ = heartbeat.tick() => Request::Heartbeat(Heartbeat {
+                                timestamp: now_ms(),
+                                ..Default::default()
+                            }),
+                            _ = sleep_until(deadline) => return,
+                        };
+                        let request = MetricsStreamRequest {
+                            request: Some(request),
+                        };
+                        if tx.send(request).await.is_err() {
+                            return;
+                        }
+                        stream_stats.messages.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            });
+            // Samples are generated here, as the stream task can't borrow the host
+            let samples = async {
+                let _ = samples_tx
+                    .send(Request::AgentInit(host.identity.agent_init()))
+                    .await;
+                let _ = samples_tx
+                    .send(Request::StaticInfo(host.static_info()))
+                    .await;
+                let mut ticker = time::interval(options.interval);
+                loop {
+                    ticker.tick().await;
+                    if samples_tx
+                        .send(Request::Realtime(host.realtime()))
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            };
+            let result = tokio::select! {
+                result = stream => result,
+                _ = samples => Ok(()),
+            };
+            stats.connected.fetch_sub(1, Ordering::Relaxed);
+            result
+        };
+        if let Err(e) = session.await {
+            stats.failures.fetch_add(1, Ordering::Relaxed);
+            debug!("Simulated host {} failed: {:#}", index, e);
+        }
+
+        // Reconnect after a random delay, so churn doesn't synchronize hosts
+        let delay = config.agent.reconnect_delay.max(1) as f64;
+        time::sleep(Duration::from_secs_f64(host.rng.range(0.0, delay))).await;
+    }
+}
+
+/// Sleep until `deadline`, forever without one
+async fn sleep_until(deadline: Option<time::Instant>) {
+    match deadline {
+        Some(deadline) => time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> Arc<SimulateOptions> {
+        Arc::new(SimulateOptions {
+            hosts: 10,
+            prefix: "sim".to_string(),
+            interval: Duration::from_secs(1),
+            churn: Duration::from_secs(60),
+            ramp: Duration::ZERO,
+            cores: 4,
+            disks: 3,
+            interfaces: 2,
+        })
+    }
+
+    #[test]
+    fn test_virtual_host_identity() {
+        let a = VirtualHost::new(7, options());
+        let b = VirtualHost::new(7, options());
+        assert_eq!(a.identity.hostname, "sim-0007");
+        assert_eq!(a.identity.agent_id, b.identity.agent_id);
+        assert_eq!(a.identity.agent_id.len(), 36);
+        assert_ne!(
+            a.identity.agent_id,
+            VirtualHost::new(8, options()).identity.agent_id
+        );
+    }
+
+    #[test]
+    fn test_virtual_host_metrics() {
+        let mut host = VirtualHost::new(1, options());
+        let info = host.static_info();
+        assert_eq!(info.disks.len(), 3);
+        assert_eq!(info.networks.len(), 2);
+
+        for sequence in 1..=100 {
+            let realtime = host.realtime();
+            assert_eq!(realtime.sequence, sequence);
+            assert_eq!(realtime.cpu_per_core.len(), 4);
+            assert_eq!(realtime.disk_io.len(), 3);
+            assert_eq!(realtime.network_io.len(), 2);
+            assert!((0.0..=100.0).contains(&realtime.cpu_usage_percent));
+            assert!(realtime.memory_used < host.memory_total);
+        }
+
+        let lifetime = host.lifetime().unwrap();
+        assert!(lifetime >= Duration::from_secs(30) && lifetime < Duration::from_secs(90));
+    }
+}
@@ -0,0 +1,269 @@
+//! Self-telemetry for the metrics pipeline
+//!
+//! Process-wide counters describing the agent itself: samples collected and
+//! dropped, buffer evictions, per-server traffic, bandwidth use and
+//! reconnects, and commands handled by type. Exposed at `/api/telemetry` and sent to servers
+//! periodically as an `AgentTelemetry` message.
+
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::proto;
+
+/// Per-server counters
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ServerTelemetry {
+    pub bytes_sent: u64,
+    pub messages_sent: u64,
+    pub connection_attempts: u64,
+    pub reconnects: u64,
+    /// Messages waiting in the send queue
+    pub queue_depth: u64,
+    /// Messages the send queue dropped while the server was slow
+    pub queue_dropped: u64,
+    /// Sent since local midnight, kept across restarts
+    pub bytes_today: u64,
+    /// Sent since the monthly cap last reset
+    pub bytes_this_month: u64,
+    /// 0 = no daily cap
+    pub daily_cap_bytes: u64,
+    /// 0 = no monthly cap
+    pub monthly_cap_bytes: u64,
+    /// "full", "reduced" or "capped"
+    pub bandwidth_mode: String,
+}
+
+/// Point-in-time copy of all counters
+#[derive(Debug, Clone, Serialize)]
+pub struct TelemetrySnapshot {
+    pub uptime_seconds: u64,
+    pub samples_collected: u64,
+    pub samples_dropped: u64,
+    pub buffer_evictions: u64,
+    pub servers: BTreeMap<String, ServerTelemetry>,
+    pub command_counts: BTreeMap<String, u64>,
+}
+
+/// Agent self-telemetry counters
+pub struct Telemetry {
+    started: Instant,
+    samples_collected: AtomicU64,
+    /// Milliseconds after `started` of the latest sample, 0 if none yet
+    last_sample_ms: AtomicU64,
+    samples_dropped: AtomicU64,
+    buffer_evictions: AtomicU64,
+    servers: Mutex<BTreeMap<String, ServerTelemetry>>,
+    command_counts: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Telemetry {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            samples_collected: AtomicU64::new(0),
+            last_sample_ms: AtomicU64::new(0),
+            samples_dropped: AtomicU64::new(0),
+            buffer_evictions: AtomicU64::new(0),
+            servers: Mutex::new(BTreeMap::new()),
+            command_counts: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// A metrics sample was collected
+    pub fn record_sample(&self) {
+        self.samples_collected.fetch_add(1, Ordering::Relaxed);
+        let elapsed = self.started.elapsed().as_millis() as u64;
+        self.last_sample_ms.store(elapsed.max(1), Ordering::Relaxed);
+    }
+
+    /// Time since the latest sample, `None` before the first one
+    #[cfg_attr(not(feature = "management"), allow(dead_code))]
+    pub fn last_sample_age(&self) -> Option<Duration> {
+        match self.last_sample_ms.load(Ordering::Relaxed) {
+            0 => None,
+            at => Some(
+                self.started
+                    .elapsed()
+                    .saturating_sub(Duration::from_millis(at)),
+            ),
+        }
+    }
+
+    /// A metrics sample failed to collect or could not be delivered
+    pub fn record_dropped(&self) {
+        self.samples_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The ring buffer discarded its oldest entry to make room
+    pub fn record_eviction(&self) {
+        self.buffer_evictions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A message of `bytes` encoded size was handed to the stream for `server`
+    pub fn record_sent(&self, server: &str, bytes: usize) {
+        let mut servers = self.servers.lock();
+        let entry = servers.entry(server.to_string()).or_default();
+        entry.bytes_sent += bytes as u64;
+        entry.messages_sent += 1;
+    }
+
+    /// A connection attempt was made; `reconnect` if the server was connected before
+    pub fn record_connection_attempt(&self, server: &str, reconnect: bool) {
+        let mut servers = self.servers.lock();
+        let entry = servers.entry(server.to_string()).or_default();
+        entry.connection_attempts += 1;
+        if reconnect {
+            entry.reconnects += 1;
+        }
+    }
+
+    /// The send queue for `server` now holds `depth` messages
+    pub fn record_queue_depth(&self, server: &str, depth: usize) {
+        let mut servers = self.servers.lock();
+        servers.entry(server.to_string()).or_default().queue_depth = depth as u64;
+    }
+
+    /// The send queue for `server` dropped a message
+    pub fn record_queue_drop(&self, server: &str) {
+        let mut servers = self.servers.lock();
+        servers.entry(server.to_string()).or_default().queue_dropped += 1;
+    }
+
+    /// Bytes sent to `server` today and this month, against its daily and
+    /// monthly caps (0 = none)
+    pub fn record_bandwidth(
+        &self,
+        server: &str,
+        today: u64,
+        this_month: u64,
+        [daily_cap, monthly_cap]: [u64; 2],
+        mode: &str,
+    ) {
+        let mut servers = self.servers.lock();
+        let entry = servers.entry(server.to_string()).or_default();
+        entry.bytes_today = today;
+        entry.bytes_this_month = this_month;
+        entry.daily_cap_bytes = daily_cap;
+        entry.monthly_cap_bytes = monthly_cap;
+        if entry.bandwidth_mode != mode {
+            entry.bandwidth_mode = mode.to_string();
+        }
+    }
+
+    /// A command of the given type was received
+    pub fn record_command(&self, command_type: &str) {
+        *self
+            .command_counts
+            .lock()
+            .entry(command_type.to_string())
+            .or_default() += 1;
+    }
+
+    /// Seconds since the agent started
+    pub fn uptime_seconds(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+
+    /// Copy all counters
+    pub fn snapshot(&self) -> TelemetrySnapshot {
+        TelemetrySnapshot {
+            uptime_seconds: self.uptime_seconds(),
+            samples_collected: self.samples_collected.load(Ordering::Relaxed),
+            samples_dropped: self.samples_dropped.load(Ordering::Relaxed),
+            buffer_evictions: self.buffer_evictions.load(Ordering::Relaxed),
+            servers: self.servers.lock().clone(),
+            command_counts: self.command_counts.lock().clone(),
+        }
+    }
+}
+
+impl TelemetrySnapshot {
+    /// Convert to the wire message
+    pub fn to_proto(&self, agent_id: &str) -> proto::AgentTelemetry {
+        proto::AgentTelemetry {
+            timestamp: chrono::Utc::now().timestamp_millis() as u64,
+            agent_id: agent_id.to_string(),
+            uptime_seconds: self.uptime_seconds,
+            samples_collected: self.samples_collected,
+            samples_dropped: self.samples_dropped,
+            buffer_evictions: self.buffer_evictions,
+            servers: self
+                .servers
+                .iter()
+                .map(|(server, s)| proto::ServerTelemetry {
+                    server: server.clone(),
+                    bytes_sent: s.bytes_sent,
+                    messages_sent: s.messages_sent,
+                    connection_attempts: s.connection_attempts,
+                    reconnects: s.reconnects,
+                    queue_depth: s.queue_depth,
+                    queue_dropped: s.queue_dropped,
+                    bytes_today: s.bytes_today,
+                    bytes_this_month: s.bytes_this_month,
+                    daily_cap_bytes: s.daily_cap_bytes,
+                    monthly_cap_bytes: s.monthly_cap_bytes,
+                    bandwidth_mode: s.bandwidth_mode.clone(),
+                })
+                .collect(),
+            command_counts: self.command_counts.clone().into_iter().collect(),
+        }
+    }
+}
+
+/// Global telemetry instance
+pub fn telemetry() -> &'static Telemetry {
+    static TELEMETRY: OnceLock<Telemetry> = OnceLock::new();
+    TELEMETRY.get_or_init(Telemetry::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters() {
+        let t = Telemetry::new();
+        t.record_sample();
+        t.record_sample();
+        t.record_dropped();
+        t.record_eviction();
+        t.record_sent("a:1", 100);
+        t.record_sent("a:1", 50);
+        t.record_connection_attempt("a:1", false);
+        t.record_connection_attempt("a:1", true);
+        t.record_queue_depth("a:1", 5);
+        t.record_queue_drop("a:1");
+        t.record_bandwidth("a:1", 150, 400, [0, 500_000_000], "full");
+        t.record_command("PROCESS_LIST");
+        t.record_command("PROCESS_LIST");
+
+        assert!(t.last_sample_age().unwrap() < Duration::from_secs(1));
+
+        let snap = t.snapshot();
+        assert_eq!(snap.samples_collected, 2);
+        assert_eq!(snap.samples_dropped, 1);
+        assert_eq!(snap.buffer_evictions, 1);
+
+        let server = &snap.servers["a:1"];
+        assert_eq!(server.bytes_sent, 150);
+        assert_eq!(server.messages_sent, 2);
+        assert_eq!(server.connection_attempts, 2);
+        assert_eq!(server.reconnects, 1);
+        assert_eq!(server.queue_depth, 5);
+        assert_eq!(server.queue_dropped, 1);
+        assert_eq!((server.bytes_today, server.bytes_this_month), (150, 400));
+        assert_eq!(server.daily_cap_bytes, 0);
+        assert_eq!(server.monthly_cap_bytes, 500_000_000);
+        assert_eq!(server.bandwidth_mode, "full");
+        assert_eq!(snap.command_counts["PROCESS_LIST"], 2);
+
+        let msg = snap.to_proto("id");
+        assert_eq!(msg.servers.len(), 1);
+        assert_eq!(msg.command_counts["PROCESS_LIST"], 2);
+    }
+}
@@ -0,0 +1,166 @@
+//! Metrics viewer
+//!
+//! The viewer shows this host or, through its management API, a remote
+//! agent. On a terminal it is the full-screen ratatui view (the `tui`
+//! feature). When output isn't a terminal (a pipe, a serial console without
+//! raw mode) or the agent was built without the viewer, one plain-text
+//! snapshot is printed instead.
+
+mod snapshot;
+#[cfg(feature = "tui")]
+mod view;
+
+pub use snapshot::{LocalSource, Snapshot, Source};
+
+use std::fmt::Write;
+
+use anyhow::Result;
+
+use crate::i18n::{Lang, t};
+
+/// Processes listed in the plain-text snapshot
+const TEXT_PROCESSES: usize = 10;
+
+/// Run the interactive realtime metrics viewer on this host
+pub fn interactive_realtime_metrics(lang: Lang) -> Result<()> {
+    run_viewer(lang, Source::Local(Box::new(LocalSource::new())), false)
+}
+
+/// Run the metrics viewer on `source`; `read_only` locks it for kiosk use
+pub fn run_viewer(lang: Lang, mut source: Source, read_only: bool) -> Result<()> {
+    #[cfg(feature = "tui")]
+    if atty::is(atty::Stream::Stdout) && atty::is(atty::Stream::Stdin) {
+        return view::run(lang, source, read_only);
+    }
+    let _ = read_only;
+
+    // CPU usage needs two samples
+    if let Source::Local(local) = &mut source {
+        local.snapshot();
+        std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+    }
+    let snapshot = source.snapshot().map_err(anyhow::Error::msg)?;
+    print!("{}", render_text(&snapshot, lang));
+    Ok(())
+}
+
+/// Plain-text rendering of a snapshot
+fn render_text(snapshot: &Snapshot, lang: Lang) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "{}", snapshot.hostname);
+
+    let _ = write!(
+        out,
+        "\n{}: {:.1}% ({} cores)",
+        t("metrics.cpu_overview", lang),
+        snapshot.cpu_usage,
+        snapshot.cores.len()
+    );
+    if let Some([one, five, fifteen]) = snapshot.load_average {
+        let _ = write!(out, ", load {one:.2} {five:.2} {fifteen:.2}");
+    }
+    let (used, used_unit) = format_bytes(snapshot.memory_used);
+    let (total, total_unit) = format_bytes(snapshot.memory_total);
+    let _ = writeln!(
+        out,
+        "\n{}: {used:.1} {used_unit} / {total:.1} {total_unit}",
+        t("metrics.memory", lang)
+    );
+
+    if !snapshot.disks.is_empty() {
+        let _ = writeln!(out);
+        for disk in &snapshot.disks {
+            let (used, used_unit) = format_bytes(disk.used);
+            let (total, total_unit) = format_bytes(disk.total);
+            let _ = writeln!(
+                out,
+                "{:<24} {used:>7.1} {used_unit:<2} / {total:>7.1} {total_unit:<2} {}",
+                disk.mount_point, disk.file_system
+            );
+        }
+    }
+
+    if !snapshot.gpus.is_empty() {
+        let _ = writeln!(out, "\n{}", t("metrics.gpu", lang));
+        for gpu in &snapshot.gpus {
+            let _ = writeln!(
+                out,
+                "{:<3} {:<32} {:>5.1}% {:>5.1}°C",
+                gpu.index, gpu.name, gpu.usage_percent, gpu.temperature
+            );
+        }
+    }
+
+    let _ = writeln!(out, "\n{}", t("metrics.processes", lang));
+    let _ = writeln!(out, "{:>8} {:>6}  {:>10}  NAME", "PID", "CPU%", "MEMORY");
+    for process in snapshot.processes.iter().take(TEXT_PROCESSES) {
+        let (memory, unit) = format_bytes(process.memory);
+        let _ = writeln!(
+            out,
+            "{:>8} {:>6.1}  {:>7.1} {:<2}  {}",
+            process.pid, process.cpu_usage, memory, unit, process.name
+        );
+    }
+    out
+}
+
+fn format_bytes(bytes: u64) -> (f64, &'static str) {
+    const KB: f64 = 1024.0;
+    const MB: f64 = KB * 1024.0;
+    const GB: f64 = MB * 1024.0;
+    const TB: f64 = GB * 1024.0;
+
+    let bytes_f = bytes as f64;
+
+    if bytes_f >= TB {
+        (bytes_f / TB, "TB")
+    } else if bytes_f >= GB {
+        (bytes_f / GB, "GB")
+    } else if bytes_f >= MB {
+        (bytes_f / MB, "MB")
+    } else if bytes_f >= KB {
+        (bytes_f / KB, "KB")
+    } else {
+        (bytes_f, "B")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nanolink_core::snapshot::{DiskRow, ProcessRow};
+
+    #[test]
+    fn test_render_text() {
+        let snapshot = Snapshot {
+            hostname: "edge-01".to_string(),
+            cpu_usage: 12.5,
+            cores: vec![10.0, 15.0],
+            load_average: Some([0.5, 0.25, 0.1]),
+            memory_total: 2 * 1024 * 1024 * 1024,
+            memory_used: 512 * 1024 * 1024,
+            disks: vec![DiskRow {
+                mount_point: "/".to_string(),
+                file_system: "ext4".to_string(),
+                used: 1024 * 1024 * 1024,
+                total: 8 * 1024 * 1024 * 1024,
+            }],
+            processes: (1..=12)
+                .map(|pid| ProcessRow {
+                    pid,
+                    cpu_usage: 1.0,
+                    memory: 4096,
+                    name: format!("proc-{pid}"),
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let text = render_text(&snapshot, Lang::En);
+        assert!(text.starts_with("edge-01\n"));
+        assert!(text.contains("CPU Overview: 12.5% (2 cores), load 0.50 0.25 0.10"));
+        assert!(text.contains("Memory: 512.0 MB / 2.0 GB"));
+        assert!(text.contains("/ ") && text.contains("ext4"));
+        assert!(text.contains("proc-10") && !text.contains("proc-11"));
+        assert!(!text.contains("GPU"));
+    }
+}
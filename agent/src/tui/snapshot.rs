@@ -0,0 +1,194 @@
+//! Data shown by the metrics viewer
+//!
+//! The viewer renders a [`Snapshot`], taken either from this host or from
+//! a running agent's management API (`GET /api/snapshot`). The second way
+//! lets a NOC screen show a host it has no shell on.
+
+use sysinfo::{Disks, Networks, System};
+
+use crate::collector::GpuCollector;
+use crate::management::client::ApiClient;
+pub use nanolink_core::snapshot::{DiskRow, GpuRow, NetworkRow, PortRow, ProcessRow, Snapshot};
+
+/// Most processes included, busiest first
+const MAX_PROCESSES: usize = 200;
+
+/// Where snapshots come from
+pub enum Source {
+    Local(Box<LocalSource>),
+    Remote(ApiClient),
+}
+
+impl Source {
+    /// Take a fresh snapshot, or say why none is available
+    pub fn snapshot(&mut self) -> Result<Snapshot, String> {
+        match self {
+            Source::Local(local) => Ok(local.snapshot()),
+            Source::Remote(client) => client.get("/api/snapshot"),
+        }
+    }
+}
+
+/// Snapshots of this host
+pub struct LocalSource {
+    system: System,
+    disks: Disks,
+    networks: Networks,
+    /// Kept so GPU availability isn't re-checked for every snapshot
+    gpu_collector: GpuCollector,
+}
+
+impl LocalSource {
+    pub fn new() -> Self {
+        Self {
+            system: System::new_all(),
+            disks: Disks::new_with_refreshed_list(),
+            networks: Networks::new_with_refreshed_list(),
+            gpu_collector: GpuCollector::new(),
+        }
+    }
+
+    pub fn snapshot(&mut self) -> Snapshot {
+        self.system.refresh_all();
+        self.disks.refresh(false);
+        self.networks.refresh(false);
+
+        let mut processes: Vec<ProcessRow> = self
+            .system
+            .processes()
+            .iter()
+            .map(|(pid, proc)| ProcessRow {
+                pid: pid.as_u32(),
+                cpu_usage: proc.cpu_usage(),
+                memory: proc.memory(),
+                name: proc.name().to_string_lossy().into_owned(),
+            })
+            .collect();
+        processes.sort_by(|a, b| b.cpu_usage.total_cmp(&a.cpu_usage));
+        processes.truncate(MAX_PROCESSES);
+
+        #[cfg(unix)]
+        let load_average = {
+            let load = System::load_average();
+            Some([load.one, load.five, load.fifteen])
+        };
+        #[cfg(not(unix))]
+        let load_average = None;
+
+        Snapshot {
+            hostname: System::host_name().unwrap_or_default(),
+            cpu_usage: f64::from(self.system.global_cpu_usage()),
+            cores: self.system.cpus().iter().map(|c| c.cpu_usage()).collect(),
+            load_average,
+            memory_total: self.system.total_memory(),
+            memory_used: self.system.used_memory(),
+            swap_total: self.system.total_swap(),
+            swap_used: self.system.used_swap(),
+            disks: self
+                .disks
+                .list()
+                .iter()
+                .map(|d| DiskRow {
+                    mount_point: d.mount_point().display().to_string(),
+                    file_system: d.file_system().to_string_lossy().into_owned(),
+                    used: d.total_space().saturating_sub(d.available_space()),
+                    total: d.total_space(),
+                })
+                .collect(),
+            networks: self
+                .networks
+                .list()
+                .iter()
+                .map(|(name, data)| NetworkRow {
+                    name: name.clone(),
+                    received: data.received(),
+                    transmitted: data.transmitted(),
+                })
+                .collect(),
+            gpus: self
+                .gpu_collector
+                .collect()
+                .into_iter()
+                .map(|g| GpuRow {
+                    index: g.index,
+                    name: g.name,
+                    usage_percent: g.usage_percent,
+                    memory_total: g.memory_total,
+                    memory_used: g.memory_used,
+                    temperature: g.temperature,
+                    power_watts: g.power_watts,
+                    power_limit_watts: g.power_limit_watts,
+                })
+                .collect(),
+            processes,
+            ports: listening_ports(),
+        }
+    }
+}
+
+/// Listening ports (platform-specific)
+fn listening_ports() -> Vec<PortRow> {
+    let mut ports = Vec::new();
+
+    #[cfg(target_os = "linux")]
+    {
+        use std::process::Command;
+        if let Ok(output) = Command::new("ss").args(["-tulnp"]).output() {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            ports.extend(
+                nanolink_parse::ss::listening_ports(&stdout)
+                    .into_iter()
+                    .map(port_row),
+            );
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::process::Command;
+        if let Ok(output) = Command::new("netstat").args(["-ano"]).output() {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            ports.extend(
+                nanolink_parse::netstat::listening_ports(&stdout)
+                    .into_iter()
+                    .map(port_row),
+            );
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        use std::process::Command;
+        if let Ok(output) = Command::new("lsof")
+            .args(["-iTCP", "-sTCP:LISTEN", "-n", "-P"])
+            .output()
+        {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            for line in stdout.lines().skip(1) {
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                if parts.len() >= 9 {
+                    ports.push(PortRow {
+                        pid: parts[1].to_string(),
+                        protocol: "TCP".to_string(),
+                        address: parts.get(8).unwrap_or(&"").to_string(),
+                        process: parts[0].to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    ports
+}
+
+#[cfg_attr(not(any(target_os = "linux", target_os = "windows")), allow(dead_code))]
+fn port_row(port: nanolink_parse::ListeningPort) -> PortRow {
+    PortRow {
+        pid: port
+            .pid
+            .map_or_else(|| "-".to_string(), |pid| pid.to_string()),
+        protocol: port.protocol,
+        address: port.address,
+        process: port.process.unwrap_or_else(|| "-".to_string()),
+    }
+}
@@ -1,14 +1,21 @@
-//! Terminal User Interface module using ratatui
+//! Full-screen metrics viewer using ratatui
 //!
 //! Provides a responsive, efficient metrics viewer with:
 //! - Responsive layouts that adapt to terminal size
 //! - Double-buffered rendering (only updates changed content)
 //! - Constraint-based positioning
+//!
+//! In read-only (kiosk) mode it only displays: the quit keys are ignored so
+//! an SSH session forced into `nanolink-agent tui --read-only` can't fall
+//! back to the interactive menu or a shell; Ctrl+C ends it.
 
+use super::{Snapshot, Source, format_bytes};
 use crate::i18n::{Lang, t};
 use anyhow::Result;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+    },
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
@@ -22,9 +29,6 @@ use ratatui::{
 };
 use std::io;
 use std::time::Duration;
-use sysinfo::{Disks, Networks, System};
-
-use crate::collector::GpuCollector;
 
 /// App state for the TUI
 struct App<'a> {
@@ -32,31 +36,35 @@ struct App<'a> {
     current_tab: usize,
     scroll_offset: usize,
     lang: Lang,
-    system: System,
-    disks: Disks,
-    networks: Networks,
-    /// Cached GPU collector to avoid re-checking availability on every frame
-    gpu_collector: GpuCollector,
+    source: Source,
+    snapshot: Snapshot,
+    /// Why the last refresh failed; the previous snapshot stays on screen
+    error: Option<String>,
+    read_only: bool,
 }
 
 impl<'a> App<'a> {
-    fn new(lang: Lang, tabs: Vec<&'a str>) -> Self {
+    fn new(lang: Lang, tabs: Vec<&'a str>, source: Source, read_only: bool) -> Self {
         Self {
             tabs,
             current_tab: 0,
             scroll_offset: 0,
             lang,
-            system: System::new_all(),
-            disks: Disks::new_with_refreshed_list(),
-            networks: Networks::new_with_refreshed_list(),
-            gpu_collector: GpuCollector::new(),
+            source,
+            snapshot: Snapshot::default(),
+            error: None,
+            read_only,
         }
     }
 
     fn refresh_data(&mut self) {
-        self.system.refresh_all();
-        self.disks.refresh(false);
-        self.networks.refresh(false);
+        match self.source.snapshot() {
+            Ok(snapshot) => {
+                self.snapshot = snapshot;
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e),
+        }
     }
 
     fn next_tab(&mut self) {
@@ -80,8 +88,8 @@ impl<'a> App<'a> {
     }
 }
 
-/// Run the interactive realtime metrics viewer using ratatui
-pub fn interactive_realtime_metrics(lang: Lang) -> Result<()> {
+/// Run the viewer on `source`; `read_only` locks it for kiosk use
+pub fn run(lang: Lang, source: Source, read_only: bool) -> Result<()> {
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -100,7 +108,7 @@ pub fn interactive_realtime_metrics(lang: Lang) -> Result<()> {
         t("metrics.processes", lang),
         t("metrics.ports", lang),
     ];
-    let mut app = App::new(lang, tabs);
+    let mut app = App::new(lang, tabs, source, read_only);
 
     // Main loop
     let result = run_app(&mut terminal, &mut app);
@@ -129,7 +137,11 @@ fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, app: &mut A
             if let Event::Key(key) = event::read()? {
                 if key.kind == KeyEventKind::Press {
                     match key.code {
-                        KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                        // Raw mode swallows SIGINT, so Ctrl+C is handled here
+                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            return Ok(());
+                        }
+                        KeyCode::Char('q') | KeyCode::Esc if !app.read_only => return Ok(()),
                         KeyCode::Left | KeyCode::Char('h') => app.prev_tab(),
                         KeyCode::Right | KeyCode::Char('l') | KeyCode::Tab => app.next_tab(),
                         KeyCode::Up | KeyCode::Char('k') => app.scroll_up(),
@@ -147,9 +159,9 @@ fn run_app<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, app: &mut A
 
 fn get_max_scroll(app: &App) -> usize {
     match app.current_tab {
-        1 => app.system.cpus().len().saturating_sub(16), // CPU Cores
-        6 => app.system.processes().len().saturating_sub(15), // Processes
-        7 => 50,                                         // Ports (estimate)
+        1 => app.snapshot.cores.len().saturating_sub(16), // CPU Cores
+        6 => app.snapshot.processes.len().saturating_sub(15), // Processes
+        7 => app.snapshot.ports.len().saturating_sub(15), // Ports
         _ => 0,
     }
 }
@@ -170,11 +182,29 @@ fn ui(f: &mut Frame, app: &App) {
     // Render tabs
     let titles: Vec<Line> = app.tabs.iter().map(|t| Line::from(*t)).collect();
 
+    let mut title = vec![Span::raw(format!(" {} ", t("metrics.title", app.lang)))];
+    if !app.snapshot.hostname.is_empty() {
+        title.push(Span::styled(
+            format!("- {} ", app.snapshot.hostname),
+            Style::default().fg(Color::Cyan),
+        ));
+    }
+    if app.read_only {
+        title.push(Span::styled(
+            " READ-ONLY ",
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ));
+        title.push(Span::raw(" "));
+    }
+
     let tabs = Tabs::new(titles)
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title(format!(" {} ", t("metrics.title", app.lang))),
+                .title(Line::from(title)),
         )
         .select(app.current_tab)
         .style(Style::default().fg(Color::White))
@@ -185,9 +215,18 @@ fn ui(f: &mut Frame, app: &App) {
         );
     f.render_widget(tabs, chunks[0]);
 
-    // Render help line
-    let help =
-        Paragraph::new(t("metrics.press_q", app.lang)).style(Style::default().fg(Color::DarkGray));
+    // Render help line, or why the data is stale
+    let help = match &app.error {
+        Some(error) => Paragraph::new(error.as_str()).style(Style::default().fg(Color::Red)),
+        None => {
+            let key = if app.read_only {
+                "metrics.read_only_help"
+            } else {
+                "metrics.press_q"
+            };
+            Paragraph::new(t(key, app.lang)).style(Style::default().fg(Color::DarkGray))
+        }
+    };
     f.render_widget(help, chunks[1]);
 
     // Render tab content
@@ -205,8 +244,8 @@ fn ui(f: &mut Frame, app: &App) {
 }
 
 fn render_cpu_overview(f: &mut Frame, app: &App, area: Rect) {
-    let cpu_usage = app.system.global_cpu_usage() as f64;
-    let cores = app.system.cpus().len();
+    let cpu_usage = app.snapshot.cpu_usage;
+    let cores = app.snapshot.cores.len();
 
     let block = Block::default()
         .borders(Borders::ALL)
@@ -266,22 +305,14 @@ fn render_cpu_overview(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(gauge, chunks[2]);
 
     // Load average (Unix only)
-    #[cfg(unix)]
-    {
-        let load = System::load_average();
+    if let Some([one, five, fifteen]) = app.snapshot.load_average {
         let load_text = Paragraph::new(Line::from(vec![
             Span::raw("Load Average: "),
-            Span::styled(format!("{:.2}", load.one), Style::default().fg(Color::Cyan)),
+            Span::styled(format!("{one:.2}"), Style::default().fg(Color::Cyan)),
             Span::raw("  "),
-            Span::styled(
-                format!("{:.2}", load.five),
-                Style::default().fg(Color::Cyan),
-            ),
+            Span::styled(format!("{five:.2}"), Style::default().fg(Color::Cyan)),
             Span::raw("  "),
-            Span::styled(
-                format!("{:.2}", load.fifteen),
-                Style::default().fg(Color::Cyan),
-            ),
+            Span::styled(format!("{fifteen:.2}"), Style::default().fg(Color::Cyan)),
             Span::raw("  (1m / 5m / 15m)"),
         ]));
         f.render_widget(load_text, chunks[3]);
@@ -289,7 +320,7 @@ fn render_cpu_overview(f: &mut Frame, app: &App, area: Rect) {
 }
 
 fn render_cpu_cores(f: &mut Frame, app: &App, area: Rect) {
-    let cpus = app.system.cpus();
+    let cpus = &app.snapshot.cores;
     let visible_rows = (area.height.saturating_sub(4)) as usize; // Account for borders and header
     let start = app
         .scroll_offset
@@ -305,8 +336,7 @@ fn render_cpu_cores(f: &mut Frame, app: &App, area: Rect) {
         .enumerate()
         .skip(start)
         .take(end - start)
-        .map(|(i, cpu)| {
-            let usage = cpu.cpu_usage();
+        .map(|(i, &usage)| {
             let color = if usage > 90.0 {
                 Color::Red
             } else if usage > 70.0 {
@@ -354,10 +384,10 @@ fn render_cpu_cores(f: &mut Frame, app: &App, area: Rect) {
 }
 
 fn render_memory(f: &mut Frame, app: &App, area: Rect) {
-    let total = app.system.total_memory();
-    let used = app.system.used_memory();
-    let swap_total = app.system.total_swap();
-    let swap_used = app.system.used_swap();
+    let total = app.snapshot.memory_total;
+    let used = app.snapshot.memory_used;
+    let swap_total = app.snapshot.swap_total;
+    let swap_used = app.snapshot.swap_used;
 
     let mem_percent = if total > 0 {
         (used as f64 / total as f64) * 100.0
@@ -455,18 +485,17 @@ fn render_memory(f: &mut Frame, app: &App, area: Rect) {
 }
 
 fn render_disk(f: &mut Frame, app: &App, area: Rect) {
-    let disks: Vec<_> = app.disks.list().iter().collect();
-
     let header = Row::new(vec!["Mount", "FileSystem", "Used", "Total", "Usage"])
         .style(Style::default().add_modifier(Modifier::BOLD))
         .height(1);
 
-    let rows: Vec<Row> = disks
+    let rows: Vec<Row> = app
+        .snapshot
+        .disks
         .iter()
         .map(|disk| {
-            let total = disk.total_space();
-            let available = disk.available_space();
-            let used = total - available;
+            let total = disk.total;
+            let used = disk.used;
             let percent = if total > 0 {
                 (used as f64 / total as f64) * 100.0
             } else {
@@ -482,8 +511,8 @@ fn render_disk(f: &mut Frame, app: &App, area: Rect) {
             };
 
             Row::new(vec![
-                truncate_string(&disk.mount_point().display().to_string(), 15),
-                truncate_string(&disk.file_system().to_string_lossy(), 10),
+                truncate_string(&disk.mount_point, 15),
+                truncate_string(&disk.file_system, 10),
                 format!("{:.1} GB", used as f64 / 1024.0 / 1024.0 / 1024.0),
                 format!("{:.1} GB", total as f64 / 1024.0 / 1024.0 / 1024.0),
                 format!("{:.1}%", percent),
@@ -518,18 +547,15 @@ fn render_network(f: &mut Frame, app: &App, area: Rect) {
         .height(1);
 
     let rows: Vec<Row> = app
+        .snapshot
         .networks
-        .list()
         .iter()
-        .map(|(name, data)| {
-            let rx = data.received();
-            let tx = data.transmitted();
-
-            let (rx_val, rx_unit) = format_bytes(rx);
-            let (tx_val, tx_unit) = format_bytes(tx);
+        .map(|net| {
+            let (rx_val, rx_unit) = format_bytes(net.received);
+            let (tx_val, tx_unit) = format_bytes(net.transmitted);
 
             Row::new(vec![
-                truncate_string(name, 18),
+                truncate_string(&net.name, 18),
                 format!("{:>8.2} {}", rx_val, rx_unit),
                 format!("{:>8.2} {}", tx_val, tx_unit),
             ])
@@ -555,8 +581,7 @@ fn render_network(f: &mut Frame, app: &App, area: Rect) {
 }
 
 fn render_gpu(f: &mut Frame, app: &App, area: Rect) {
-    // Use cached collector from App state (avoids re-checking GPU availability every frame)
-    let gpus = app.gpu_collector.collect();
+    let gpus = &app.snapshot.gpus;
 
     let block = Block::default()
         .borders(Borders::ALL)
@@ -684,14 +709,10 @@ fn render_gpu(f: &mut Frame, app: &App, area: Rect) {
 }
 
 fn render_processes(f: &mut Frame, app: &App, area: Rect) {
-    let mut procs: Vec<_> = app.system.processes().iter().collect();
-    procs.sort_by(|a, b| {
-        b.1.cpu_usage()
-            .partial_cmp(&a.1.cpu_usage())
-            .unwrap_or(std::cmp::Ordering::Equal)
-    });
-
-    let total_mem = app.system.total_memory() as f64;
+    // Already sorted busiest first
+    let procs = &app.snapshot.processes;
+
+    let total_mem = app.snapshot.memory_total as f64;
     let visible_rows = (area.height.saturating_sub(4)) as usize;
     let start = app
         .scroll_offset
@@ -706,27 +727,27 @@ fn render_processes(f: &mut Frame, app: &App, area: Rect) {
         .iter()
         .skip(start)
         .take(end - start)
-        .map(|(pid, proc)| {
+        .map(|proc| {
             let mem_percent = if total_mem > 0.0 {
-                (proc.memory() as f64 / total_mem) * 100.0
+                (proc.memory as f64 / total_mem) * 100.0
             } else {
                 0.0
             };
 
-            let cpu_color = if proc.cpu_usage() > 50.0 {
+            let cpu_color = if proc.cpu_usage > 50.0 {
                 Color::Red
-            } else if proc.cpu_usage() > 10.0 {
+            } else if proc.cpu_usage > 10.0 {
                 Color::Yellow
             } else {
                 Color::White
             };
 
             Row::new(vec![
-                format!("{:>7}", pid.as_u32()),
-                format!("{:>6.1}%", proc.cpu_usage()),
+                format!("{:>7}", proc.pid),
+                format!("{:>6.1}%", proc.cpu_usage),
                 format!("{:>6.1}%", mem_percent),
-                format!("{:>10.1}", proc.memory() as f64 / 1024.0 / 1024.0),
-                truncate_string(&proc.name().to_string_lossy(), 24),
+                format!("{:>10.1}", proc.memory as f64 / 1024.0 / 1024.0),
+                truncate_string(&proc.name, 24),
             ])
             .style(Style::default().fg(cpu_color))
         })
@@ -760,7 +781,7 @@ fn render_processes(f: &mut Frame, app: &App, area: Rect) {
 }
 
 fn render_ports(f: &mut Frame, app: &App, area: Rect) {
-    let ports = get_listening_ports();
+    let ports = &app.snapshot.ports;
     let visible_rows = (area.height.saturating_sub(4)) as usize;
     let start = app
         .scroll_offset
@@ -775,18 +796,18 @@ fn render_ports(f: &mut Frame, app: &App, area: Rect) {
         .iter()
         .skip(start)
         .take(end - start)
-        .map(|(pid, proto, addr, name)| {
-            let proto_color = if proto.contains("TCP") {
+        .map(|port| {
+            let proto_color = if port.protocol.contains("TCP") {
                 Color::Green
             } else {
                 Color::Cyan
             };
 
             Row::new(vec![
-                pid.clone(),
-                proto.clone(),
-                truncate_string(addr, 22),
-                truncate_string(name, 18),
+                port.pid.clone(),
+                port.protocol.clone(),
+                truncate_string(&port.address, 22),
+                truncate_string(&port.process, 18),
             ])
             .style(Style::default().fg(proto_color))
         })
@@ -829,102 +850,3 @@ fn truncate_string(s: &str, max_len: usize) -> String {
         s[..max_len].to_string()
     }
 }
-
-fn format_bytes(bytes: u64) -> (f64, &'static str) {
-    const KB: f64 = 1024.0;
-    const MB: f64 = KB * 1024.0;
-    const GB: f64 = MB * 1024.0;
-    const TB: f64 = GB * 1024.0;
-
-    let bytes_f = bytes as f64;
-
-    if bytes_f >= TB {
-        (bytes_f / TB, "TB")
-    } else if bytes_f >= GB {
-        (bytes_f / GB, "GB")
-    } else if bytes_f >= MB {
-        (bytes_f / MB, "MB")
-    } else if bytes_f >= KB {
-        (bytes_f / KB, "KB")
-    } else {
-        (bytes_f, "B")
-    }
-}
-
-/// Get listening ports (platform-specific)
-fn get_listening_ports() -> Vec<(String, String, String, String)> {
-    let mut ports = Vec::new();
-
-    #[cfg(target_os = "linux")]
-    {
-        use std::process::Command;
-        if let Ok(output) = Command::new("ss").args(["-tulnp"]).output() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            for line in stdout.lines().skip(1) {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 5 {
-                    let proto = parts[0].to_string();
-                    let addr = parts[4].to_string();
-                    let process_info = parts.get(6).unwrap_or(&"").to_string();
-
-                    let (pid, name) = if let Some(start) = process_info.find("pid=") {
-                        let pid_part = &process_info[start + 4..];
-                        let pid: String = pid_part.chars().take_while(|c| c.is_numeric()).collect();
-                        let name = process_info
-                            .split("((\"")
-                            .nth(1)
-                            .and_then(|s| s.split('"').next())
-                            .unwrap_or("-")
-                            .to_string();
-                        (pid, name)
-                    } else {
-                        ("-".to_string(), "-".to_string())
-                    };
-
-                    ports.push((pid, proto, addr, name));
-                }
-            }
-        }
-    }
-
-    #[cfg(target_os = "windows")]
-    {
-        use std::process::Command;
-        if let Ok(output) = Command::new("netstat").args(["-ano"]).output() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            for line in stdout.lines() {
-                if line.contains("LISTENING") {
-                    let parts: Vec<&str> = line.split_whitespace().collect();
-                    if parts.len() >= 5 {
-                        let proto = parts[0].to_string();
-                        let addr = parts[1].to_string();
-                        let pid = parts[4].to_string();
-                        ports.push((pid, proto, addr, "-".to_string()));
-                    }
-                }
-            }
-        }
-    }
-
-    #[cfg(target_os = "macos")]
-    {
-        use std::process::Command;
-        if let Ok(output) = Command::new("lsof")
-            .args(["-iTCP", "-sTCP:LISTEN", "-n", "-P"])
-            .output()
-        {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            for line in stdout.lines().skip(1) {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 9 {
-                    let name = parts[0].to_string();
-                    let pid = parts[1].to_string();
-                    let addr = parts.get(8).unwrap_or(&"").to_string();
-                    ports.push((pid, "TCP".to_string(), addr, name));
-                }
-            }
-        }
-    }
-
-    ports
-}
@@ -0,0 +1,224 @@
+//! Encryption of persisted agent state
+//!
+//! With `security.at_rest.enabled`, files the agent writes (session
+//! recordings and their chain, the maintenance queue, stream recordings) are
+//! sealed with AES-256-GCM. Sealed data starts with [`MAGIC`] followed by a
+//! random nonce; data without the prefix is returned unchanged by [`open`],
+//! so stores written before encryption was enabled stay readable.
+
+use std::process::Command;
+use std::sync::OnceLock;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::config::AtRestConfig;
+
+const MAGIC: &[u8] = b"NLENC1";
+
+static CIPHER: OnceLock<Option<Cipher>> = OnceLock::new();
+
+/// AES-256-GCM with a per-message random nonce
+pub struct Cipher {
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl Cipher {
+    /// Cipher for a 32-byte key
+    pub fn new(key: &[u8]) -> Result<Self, String> {
+        let key = UnboundKey::new(&AES_256_GCM, key)
+            .map_err(|_| "At-rest key must be 32 bytes".to_string())?;
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// Encrypt `data`; `purpose` is bound as associated data so a file can't
+    /// be swapped in for another kind
+    pub fn seal(&self, purpose: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| "No randomness available".to_string())?;
+        let mut buffer = data.to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(purpose.as_bytes()),
+                &mut buffer,
+            )
+            .map_err(|_| "Encryption failed".to_string())?;
+
+        let mut sealed = Vec::with_capacity(MAGIC.len() + NONCE_LEN + buffer.len());
+        sealed.extend_from_slice(MAGIC);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&buffer);
+        Ok(sealed)
+    }
+
+    /// Decrypt sealed data; anything else is returned as is
+    pub fn open(&self, purpose: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+        let Some(rest) = data.strip_prefix(MAGIC) else {
+            return Ok(data.to_vec());
+        };
+        if rest.len() < NONCE_LEN {
+            return Err("Encrypted data is truncated".to_string());
+        }
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce)
+            .map_err(|_| "Encrypted data is truncated".to_string())?;
+        let mut buffer = ciphertext.to_vec();
+        let plain = self
+            .key
+            .open_in_place(nonce, Aad::from(purpose.as_bytes()), &mut buffer)
+            .map_err(|_| "Decryption failed (wrong key or tampered data)".to_string())?;
+        Ok(plain.to_vec())
+    }
+}
+
+/// Load the key once at startup. Fails if encryption is enabled but no key
+/// can be found, rather than silently writing plaintext.
+pub fn init(config: &AtRestConfig) -> Result<(), String> {
+    let cipher = if config.enabled {
+        Some(Cipher::new(&load_key(config)?)?)
+    } else {
+        None
+    };
+    let _ = CIPHER.set(cipher);
+    Ok(())
+}
+
+fn load_key(config: &AtRestConfig) -> Result<Vec<u8>, String> {
+    let encoded = if let Some(path) = &config.key_file {
+        std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read at-rest key file {path}: {e}"))?
+    } else if config.keychain {
+        keychain_key()?
+    } else if let Some(key) = &config.key {
+        key.clone()
+    } else {
+        return Err("security.at_rest is enabled but no key is configured".to_string());
+    };
+    BASE64
+        .decode(encoded.trim())
+        .map_err(|e| format!("Invalid at-rest key: {e}"))
+}
+
+fn keychain_key() -> Result<String, String> {
+    if cfg!(windows) {
+        return Err("Keychain lookup is not supported on Windows, use key_file".to_string());
+    }
+    let output = if cfg!(target_os = "macos") {
+        Command::new("security")
+            .args([
+                "find-generic-password",
+                "-s",
+                "nanolink",
+                "-a",
+                "at-rest",
+                "-w",
+            ])
+            .output()
+    } else {
+        Command::new("secret-tool")
+            .args(["lookup", "service", "nanolink", "account", "at-rest"])
+            .output()
+    }
+    .map_err(|e| format!("Failed to query keychain: {e}"))?;
+    if output.status.success() && !output.stdout.is_empty() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err("At-rest key not found in keychain".to_string())
+    }
+}
+
+/// The configured cipher, if encryption is enabled
+pub fn cipher() -> Option<&'static Cipher> {
+    CIPHER.get().and_then(Option::as_ref)
+}
+
+/// Seal with the configured key, or pass through when disabled
+pub fn seal(purpose: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+    seal_with(cipher(), purpose, data)
+}
+
+/// Open data written by [`seal`]
+pub fn open(purpose: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+    open_with(cipher(), purpose, data)
+}
+
+/// [`seal`] with a given cipher
+pub fn seal_with(cipher: Option<&Cipher>, purpose: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+    match cipher {
+        Some(cipher) => cipher.seal(purpose, data),
+        None => Ok(data.to_vec()),
+    }
+}
+
+/// [`open`] with a given cipher
+pub fn open_with(cipher: Option<&Cipher>, purpose: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+    match cipher {
+        Some(cipher) => cipher.open(purpose, data),
+        None if data.starts_with(MAGIC) => {
+            Err("Data is encrypted but security.at_rest is not enabled".to_string())
+        }
+        None => Ok(data.to_vec()),
+    }
+}
+
+/// Seal one line of an append-only text file (base64, no newlines)
+pub fn seal_line(purpose: &str, line: &str) -> Result<String, String> {
+    match cipher() {
+        Some(cipher) => Ok(BASE64.encode(cipher.seal(purpose, line.as_bytes())?)),
+        None => Ok(line.to_string()),
+    }
+}
+
+/// Open a line written by [`seal_line`]; plaintext JSON lines pass through
+pub fn open_line(purpose: &str, line: &str) -> Result<String, String> {
+    if line.starts_with('{') {
+        return Ok(line.to_string());
+    }
+    let data = BASE64
+        .decode(line.trim())
+        .map_err(|e| format!("Invalid encrypted line: {e}"))?;
+    let plain = open(purpose, &data)?;
+    String::from_utf8(plain).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let cipher = Cipher::new(&[7u8; 32]).unwrap();
+        let sealed = cipher.seal("recording", b"root@db01 10.0.0.5").unwrap();
+        assert!(sealed.starts_with(MAGIC));
+        assert!(!sealed.windows(4).any(|w| w == b"db01"));
+        assert_eq!(
+            cipher.open("recording", &sealed).unwrap(),
+            b"root@db01 10.0.0.5"
+        );
+
+        // Wrong purpose, wrong key, tampering
+        assert!(cipher.open("maintenance", &sealed).is_err());
+        assert!(
+            Cipher::new(&[8u8; 32])
+                .unwrap()
+                .open("recording", &sealed)
+                .is_err()
+        );
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(cipher.open("recording", &tampered).is_err());
+
+        // Plaintext written before encryption was enabled
+        assert_eq!(cipher.open("recording", b"{}").unwrap(), b"{}");
+        assert!(Cipher::new(&[0u8; 16]).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+//! Metrics timestamps and sequence numbers
+//!
+//! Wall-clock timestamps are for display only; NTP steps can move them
+//! backwards. Ordering, buffering and backfill use the per-process sequence
+//! number, which only ever increases. In `monotonic` clock mode timestamps
+//! are also clamped so they never go backwards.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+/// How metrics timestamps are produced
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClockMode {
+    /// Raw system wall clock
+    #[default]
+    System,
+    /// Wall clock clamped to never go backwards (holds until the clock catches up after a step back)
+    Monotonic,
+}
+
+/// Clock mode, last timestamp and sequence counter
+struct Clock {
+    monotonic: AtomicBool,
+    last_timestamp: AtomicU64,
+    sequence: AtomicU64,
+}
+
+impl Clock {
+    const fn new() -> Self {
+        Self {
+            monotonic: AtomicBool::new(false),
+            last_timestamp: AtomicU64::new(0),
+            sequence: AtomicU64::new(0),
+        }
+    }
+
+    fn set_mode(&self, mode: ClockMode) {
+        self.monotonic
+            .store(mode == ClockMode::Monotonic, Ordering::Relaxed);
+    }
+
+    fn now_millis(&self) -> u64 {
+        let now = wall_clock_millis();
+        let previous = self.last_timestamp.fetch_max(now, Ordering::Relaxed);
+        if self.monotonic.load(Ordering::Relaxed) {
+            now.max(previous)
+        } else {
+            now
+        }
+    }
+
+    fn next_sequence(&self) -> u64 {
+        self.sequence.fetch_add(1, Ordering::Relaxed) + 1
+    }
+}
+
+/// The process clock
+static CLOCK: Clock = Clock::new();
+
+/// Select the clock mode. Call once at startup.
+pub fn init(mode: ClockMode) {
+    CLOCK.set_mode(mode);
+}
+
+fn wall_clock_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Current timestamp in milliseconds since the Unix epoch
+pub fn now_millis() -> u64 {
+    CLOCK.now_millis()
+}
+
+/// Next metrics sequence number.
+///
+/// Starts at 1 and increases by one per sample for the lifetime of the
+/// process; a lower value than previously seen means the agent restarted.
+pub fn next_sequence() -> u64 {
+    CLOCK.next_sequence()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_strictly_increases() {
+        let clock = Clock::new();
+        assert_eq!(clock.next_sequence(), 1);
+        assert_eq!(clock.next_sequence(), 2);
+    }
+
+    #[test]
+    fn test_monotonic_timestamps_hold_after_step_back() {
+        let clock = Clock::new();
+        let ahead = wall_clock_millis() + 60_000;
+        clock.last_timestamp.store(ahead, Ordering::Relaxed);
+        assert!(clock.now_millis() < ahead);
+
+        clock.set_mode(ClockMode::Monotonic);
+        assert!(clock.now_millis() >= ahead);
+    }
+}
@@ -0,0 +1,221 @@
+//! Minimal cron expressions
+//!
+//! Standard five-field expressions (`minute hour day-of-month month
+//! day-of-week`) with `*`, lists, ranges, steps, month/weekday names and the
+//! `@hourly`/`@daily`/`@weekly`/`@monthly` shorthands. As in cron, when both
+//! day fields are restricted a time matches if either one does. Times are
+//! naive local times with minute resolution.
+
+use std::str::FromStr;
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+
+/// Searches for the next match give up after this long
+const MAX_SEARCH_DAYS: i64 = 4 * 366;
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A parsed cron expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        let expanded = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("Cron expression needs 5 fields: {expr}"));
+        };
+
+        let mut weekdays = parse_field(weekday, 0, 7, &WEEKDAYS)?;
+        // 7 is Sunday as well
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59, &[])?,
+            hours: parse_field(hour, 0, 23, &[])?,
+            days: parse_field(day, 1, 31, &[])?,
+            months: parse_field(month, 1, 12, &MONTHS)?,
+            weekdays,
+            days_restricted: day != "*",
+            weekdays_restricted: weekday != "*",
+        })
+    }
+}
+
+impl CronSchedule {
+    /// Whether `time` (truncated to the minute) matches
+    pub fn matches(&self, time: &NaiveDateTime) -> bool {
+        self.date_matches(time.date())
+            && bit(self.hours, time.hour())
+            && bit(self.minutes, time.minute())
+    }
+
+    /// First matching time strictly after `time`
+    pub fn next_after(&self, time: &NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = truncate(time) + Duration::minutes(1);
+        let mut date = start.date();
+        let mut from = Some(start.time());
+
+        for _ in 0..MAX_SEARCH_DAYS {
+            if self.date_matches(date) {
+                let (from_hour, from_minute) = from.map_or((0, 0), |t| (t.hour(), t.minute()));
+                for hour in (from_hour..24).filter(|h| bit(self.hours, *h)) {
+                    let first_minute = if hour == from_hour { from_minute } else { 0 };
+                    if let Some(minute) = (first_minute..60).find(|m| bit(self.minutes, *m)) {
+                        return date.and_hms_opt(hour, minute, 0);
+                    }
+                }
+            }
+            date = date.succ_opt()?;
+            from = None;
+        }
+        None
+    }
+
+    /// Latest matching time at or before `time`, looking back at most
+    /// `within` minutes
+    pub fn last_at_or_before(&self, time: &NaiveDateTime, within: u32) -> Option<NaiveDateTime> {
+        let time = truncate(time);
+        (0..=i64::from(within))
+            .map(|back| time - Duration::minutes(back))
+            .find(|candidate| self.matches(candidate))
+    }
+
+    fn date_matches(&self, date: NaiveDate) -> bool {
+        if !bit(self.months, date.month()) {
+            return false;
+        }
+        let day = bit(self.days, date.day());
+        let weekday = bit(self.weekdays, date.weekday().num_days_from_sunday());
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+fn bit(mask: u64, value: u32) -> bool {
+    mask & (1 << value) != 0
+}
+
+fn truncate(time: &NaiveDateTime) -> NaiveDateTime {
+    time.date()
+        .and_time(NaiveTime::from_hms_opt(time.hour(), time.minute(), 0).unwrap_or(NaiveTime::MIN))
+}
+
+/// Parse one field into a bit mask
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |s: &str| -> Result<u32, String> {
+        let lower = s.to_ascii_lowercase();
+        let parsed = match names.iter().position(|n| *n == lower) {
+            // Month names start at 1, weekday names at 0
+            Some(i) => i as u32 + min,
+            None => s.parse().map_err(|_| format!("Invalid cron value: {s}"))?,
+        };
+        if (min..=max).contains(&parsed) {
+            Ok(parsed)
+        } else {
+            Err(format!("Cron value {parsed} out of range {min}-{max}"))
+        }
+    };
+
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("Invalid cron step: {part}"))?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (value(start)?, value(end)?)
+        } else {
+            let start = value(range)?;
+            // `5/15` means every 15 starting at 5
+            (start, if step > 1 { max } else { start })
+        };
+        if start > end {
+            return Err(format!("Invalid cron range: {part}"));
+        }
+        for v in (start..=end).step_by(step as usize) {
+            mask |= 1 << v;
+        }
+    }
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_match() {
+        let cron: CronSchedule = "30 2 * * sat,sun".parse().unwrap();
+        // 2026-10-17 is a Saturday
+        assert!(cron.matches(&at("2026-10-17 02:30")));
+        assert!(!cron.matches(&at("2026-10-16 02:30")));
+
+        let every: CronSchedule = "*/15 9-17 * * 1-5".parse().unwrap();
+        assert!(every.matches(&at("2026-10-16 09:45")));
+        assert!(!every.matches(&at("2026-10-16 09:50")));
+
+        assert!("61 * * * *".parse::<CronSchedule>().is_err());
+        assert!("* * *".parse::<CronSchedule>().is_err());
+    }
+
+    #[test]
+    fn test_next_and_last() {
+        let cron: CronSchedule = "0 3 * * 0".parse().unwrap();
+        assert_eq!(
+            cron.next_after(&at("2026-10-16 12:00")),
+            Some(at("2026-10-18 03:00"))
+        );
+        assert_eq!(
+            cron.next_after(&at("2026-10-18 03:00")),
+            Some(at("2026-10-25 03:00"))
+        );
+        assert_eq!(
+            cron.last_at_or_before(&at("2026-10-18 04:59"), 120),
+            Some(at("2026-10-18 03:00"))
+        );
+        assert_eq!(cron.last_at_or_before(&at("2026-10-18 05:01"), 120), None);
+
+        // Either day field matches when both are restricted
+        let either: CronSchedule = "0 0 1 * mon".parse().unwrap();
+        assert_eq!(
+            either.next_after(&at("2026-10-16 00:00")),
+            Some(at("2026-10-19 00:00"))
+        );
+    }
+}
@@ -0,0 +1,384 @@
+//! File export of collected metrics
+//!
+//! Used by `nanolink-agent snapshot` and the history export
+//! (`nanolink-agent export`, `GET /api/export`). Metrics are flattened to
+//! one value per field, with list entries indexed (`disks.0.used`). A single
+//! snapshot is written as `metric,value` rows; history is written as a table
+//! with one row per sample and one column per field, as CSV or Parquet.
+
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Pretty-printed JSON of `data`
+pub fn to_json<T: Serialize>(data: &T) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(data)
+}
+
+/// `metric,value` CSV of every leaf field of `data`
+pub fn to_csv<T: Serialize>(data: &T) -> serde_json::Result<String> {
+    let mut fields = Vec::new();
+    flatten(serde_json::to_value(data)?, String::new(), &mut fields);
+
+    let mut csv = String::from("metric,value\n");
+    for (metric, value) in fields {
+        csv.push_str(&escape(&metric));
+        csv.push(',');
+        csv.push_str(&escape(&cell(&value)));
+        csv.push('\n');
+    }
+    Ok(csv)
+}
+
+/// Gzip-compress `data`
+pub fn gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+/// Encode `table` as `format` (`csv` or `parquet`), gzipped if asked
+#[cfg_attr(not(feature = "management"), allow(dead_code))]
+pub fn encode(table: &Table, format: &str, gzip: bool) -> Result<Vec<u8>> {
+    let data = match format {
+        "csv" => table.to_csv().into_bytes(),
+        #[cfg(feature = "parquet")]
+        "parquet" => table.to_parquet()?,
+        #[cfg(not(feature = "parquet"))]
+        "parquet" => anyhow::bail!("This agent was built without Parquet support"),
+        other => anyhow::bail!("Unknown export format {other}"),
+    };
+    if gzip {
+        Ok(self::gzip(&data)?)
+    } else {
+        Ok(data)
+    }
+}
+
+/// Records flattened into rows with a common set of columns
+pub struct Table {
+    columns: Vec<String>,
+    /// One cell per column; `None` where a record lacks the field
+    rows: Vec<Vec<Option<Value>>>,
+}
+
+impl Table {
+    /// Flatten `records`, keeping the columns matching any of `columns`
+    /// (glob patterns such as `cpu.*` or `disks.*.used`; all when empty),
+    /// in the order of the patterns
+    #[cfg_attr(not(feature = "management"), allow(dead_code))]
+    pub fn from_records<T: Serialize>(records: &[T], columns: &[String]) -> Result<Self> {
+        let patterns = columns
+            .iter()
+            .map(|c| glob::Pattern::new(c).with_context(|| format!("Invalid column pattern {c}")))
+            .collect::<Result<Vec<_>>>()?;
+        // Index of the first pattern matching a column
+        let rank = |name: &str| {
+            if patterns.is_empty() {
+                Some(0)
+            } else {
+                patterns.iter().position(|p| p.matches(name))
+            }
+        };
+
+        let mut names: Vec<String> = Vec::new();
+        let mut index = std::collections::HashMap::new();
+        let mut rows = Vec::with_capacity(records.len());
+        for record in records {
+            let mut fields = Vec::new();
+            flatten(serde_json::to_value(record)?, String::new(), &mut fields);
+
+            let mut row = vec![None; names.len()];
+            for (name, value) in fields {
+                if rank(&name).is_none() {
+                    continue;
+                }
+                let column = *index.entry(name.clone()).or_insert_with(|| {
+                    names.push(name);
+                    names.len() - 1
+                });
+                if column >= row.len() {
+                    row.resize(column + 1, None);
+                }
+                row[column] = Some(value);
+            }
+            rows.push(row);
+        }
+        // Columns first seen in later records are missing from earlier rows
+        for row in &mut rows {
+            row.resize(names.len(), None);
+        }
+
+        let mut order: Vec<usize> = (0..names.len()).collect();
+        order.sort_by_key(|&i| rank(&names[i]));
+        Ok(Self {
+            columns: order.iter().map(|&i| names[i].clone()).collect(),
+            rows: rows
+                .into_iter()
+                .map(|row| order.iter().map(|&i| row[i].clone()).collect())
+                .collect(),
+        })
+    }
+
+    /// CSV with a header row
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        let header: Vec<String> = self.columns.iter().map(|c| escape(c)).collect();
+        csv.push_str(&header.join(","));
+        csv.push('\n');
+        for row in &self.rows {
+            let cells: Vec<String> = row
+                .iter()
+                .map(|v| v.as_ref().map(|v| escape(&cell(v))).unwrap_or_default())
+                .collect();
+            csv.push_str(&cells.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Parquet file with one optional column per field
+    ///
+    /// Column types follow the values: INT64 when every value is an integer,
+    /// DOUBLE for other numbers, BOOLEAN, and UTF-8 strings for the rest.
+    #[cfg(feature = "parquet")]
+    pub fn to_parquet(&self) -> Result<Vec<u8>> {
+        use std::sync::Arc;
+
+        use parquet::basic::{LogicalType, Repetition, Type as PhysicalType};
+        use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int64Type};
+        use parquet::file::properties::WriterProperties;
+        use parquet::file::writer::SerializedFileWriter;
+        use parquet::schema::types::Type;
+
+        let kinds: Vec<ColumnKind> = (0..self.columns.len())
+            .map(|i| ColumnKind::of(self.rows.iter().filter_map(|r| r[i].as_ref())))
+            .collect();
+
+        let fields = self
+            .columns
+            .iter()
+            .zip(&kinds)
+            .map(|(name, kind)| {
+                let (physical, logical) = match kind {
+                    ColumnKind::Int => (PhysicalType::INT64, None),
+                    ColumnKind::Float => (PhysicalType::DOUBLE, None),
+                    ColumnKind::Bool => (PhysicalType::BOOLEAN, None),
+                    ColumnKind::Text => (PhysicalType::BYTE_ARRAY, Some(LogicalType::String)),
+                };
+                Type::primitive_type_builder(name, physical)
+                    .with_repetition(Repetition::OPTIONAL)
+                    .with_logical_type(logical)
+                    .build()
+                    .map(Arc::new)
+            })
+            .collect::<parquet::errors::Result<Vec<_>>>()?;
+        let schema = Arc::new(
+            Type::group_type_builder("metrics")
+                .with_fields(fields)
+                .build()?,
+        );
+
+        let props = Arc::new(WriterProperties::builder().build());
+        let mut writer = SerializedFileWriter::new(Vec::new(), schema, props)?;
+        let mut row_group = writer.next_row_group()?;
+        for (i, kind) in kinds.iter().enumerate() {
+            let cells: Vec<&Value> = self.rows.iter().filter_map(|r| r[i].as_ref()).collect();
+            let levels: Vec<i16> = self.rows.iter().map(|r| r[i].is_some() as i16).collect();
+            let mut column = row_group
+                .next_column()?
+                .context("Parquet schema has fewer columns than the table")?;
+            match kind {
+                ColumnKind::Int => {
+                    let values: Vec<i64> = cells.iter().filter_map(|v| v.as_i64()).collect();
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(&values, Some(&levels), None)?;
+                }
+                ColumnKind::Float => {
+                    let values: Vec<f64> = cells.iter().filter_map(|v| v.as_f64()).collect();
+                    column
+                        .typed::<DoubleType>()
+                        .write_batch(&values, Some(&levels), None)?;
+                }
+                ColumnKind::Bool => {
+                    let values: Vec<bool> = cells.iter().filter_map(|v| v.as_bool()).collect();
+                    column
+                        .typed::<BoolType>()
+                        .write_batch(&values, Some(&levels), None)?;
+                }
+                ColumnKind::Text => {
+                    let values: Vec<ByteArray> = cells
+                        .iter()
+                        .map(|v| ByteArray::from(cell(v).as_str()))
+                        .collect();
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(&values, Some(&levels), None)?;
+                }
+            }
+            column.close()?;
+        }
+        row_group.close()?;
+        Ok(writer.into_inner()?)
+    }
+}
+
+/// Narrowest Parquet type holding every value of a column
+#[cfg(feature = "parquet")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColumnKind {
+    Int,
+    Float,
+    Bool,
+    Text,
+}
+
+#[cfg(feature = "parquet")]
+impl ColumnKind {
+    fn of<'a>(mut values: impl Iterator<Item = &'a Value> + Clone) -> Self {
+        if values.clone().all(Value::is_boolean) {
+            ColumnKind::Bool
+        } else if values.clone().all(|v| v.is_i64()) {
+            ColumnKind::Int
+        } else if values.all(Value::is_number) {
+            ColumnKind::Float
+        } else {
+            ColumnKind::Text
+        }
+    }
+}
+
+/// Collect the leaf fields of `value` as dotted paths
+fn flatten(value: Value, path: String, fields: &mut Vec<(String, Value)>) {
+    let child = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{path}.{key}")
+        }
+    };
+    match value {
+        // Unset optional fields
+        Value::Null => {}
+        Value::Object(map) => {
+            for (key, value) in map {
+                let path = child(&key);
+                flatten(value, path, fields);
+            }
+        }
+        Value::Array(items) => {
+            for (i, value) in items.into_iter().enumerate() {
+                let path = child(&i.to_string());
+                flatten(value, path, fields);
+            }
+        }
+        leaf => fields.push((path, leaf)),
+    }
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Quote a CSV field when it needs it (RFC 4180)
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_csv_flattens_fields() {
+        let data = serde_json::json!({
+            "hostname": "web, 1",
+            "cpu": { "usage_percent": 12.5, "model": null },
+            "disks": [{ "used": 10 }, { "used": 20 }],
+        });
+        let csv = to_csv(&data).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "metric,value");
+        assert!(lines.contains(&"cpu.usage_percent,12.5"));
+        assert!(lines.contains(&"disks.0.used,10"));
+        assert!(lines.contains(&"disks.1.used,20"));
+        assert!(lines.contains(&"hostname,\"web, 1\""));
+        assert!(!csv.contains("cpu.model"));
+    }
+
+    #[test]
+    fn test_table_selects_columns() {
+        let records = vec![
+            serde_json::json!({ "timestamp": 1, "cpu": { "usage_percent": 5.0 } }),
+            serde_json::json!({
+                "timestamp": 2,
+                "cpu": { "usage_percent": 7.5 },
+                "disks": [{ "used": 3, "mount_point": "/" }],
+            }),
+        ];
+
+        let all = Table::from_records(&records, &[]).unwrap();
+        assert_eq!(
+            all.to_csv(),
+            "cpu.usage_percent,timestamp,disks.0.mount_point,disks.0.used\n\
+             5.0,1,,\n\
+             7.5,2,/,3\n"
+        );
+
+        let columns = ["timestamp".to_string(), "*.us*".to_string()];
+        let selected = Table::from_records(&records, &columns).unwrap();
+        assert_eq!(
+            selected.to_csv(),
+            "timestamp,cpu.usage_percent,disks.0.used\n1,5.0,\n2,7.5,3\n"
+        );
+
+        assert!(Table::from_records(&records, &["[".to_string()]).is_err());
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_table_to_parquet() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let records = vec![
+            serde_json::json!({ "timestamp": 1, "host": "a", "ok": true }),
+            serde_json::json!({ "timestamp": 2, "ok": false, "load": 0.5 }),
+        ];
+        let data = Table::from_records(&records, &[])
+            .unwrap()
+            .to_parquet()
+            .unwrap();
+        let path =
+            std::env::temp_dir().join(format!("nanolink-export-{}.parquet", std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(metadata.file_metadata().num_rows(), 2);
+        assert_eq!(metadata.file_metadata().schema_descr().num_columns(), 4);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_gzip() {
+        use std::io::Read;
+
+        let compressed = gzip(b"metric,value\n").unwrap();
+        let mut out = String::new();
+        flate2::read::GzDecoder::new(compressed.as_slice())
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, "metric,value\n");
+    }
+}
@@ -0,0 +1,335 @@
+//! Minimal blocking HTTP/1.1 client
+//!
+//! For the few HTTP calls made outside the async runtime: cloud instance
+//! metadata, the local management API (settings window, remote TUI) and
+//! webhook delivery. One request per connection, no redirects; TLS only for
+//! `https://` URLs passed to [`send`].
+
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustls::pki_types::ServerName;
+
+/// Largest response accepted
+const MAX_RESPONSE_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Status code and decoded body of a response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// Body as text (lossy)
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+/// Send one request to `addr` and read the whole response
+pub fn request(
+    method: &str,
+    addr: SocketAddr,
+    host: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    timeout: Duration,
+) -> io::Result<Response> {
+    request_with_limit(
+        method,
+        addr,
+        host,
+        path,
+        headers,
+        timeout,
+        MAX_RESPONSE_BYTES,
+    )
+}
+
+/// [`request`] accepting responses of up to `max_bytes`
+pub fn request_with_limit(
+    method: &str,
+    addr: SocketAddr,
+    host: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    timeout: Duration,
+    max_bytes: u64,
+) -> io::Result<Response> {
+    let mut stream = connect(addr, timeout)?;
+    exchange(&mut stream, method, host, path, headers, &[], max_bytes)
+}
+
+/// [`request`] with a request body
+pub fn request_with_body(
+    method: &str,
+    addr: SocketAddr,
+    host: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+    timeout: Duration,
+) -> io::Result<Response> {
+    let mut stream = connect(addr, timeout)?;
+    exchange(
+        &mut stream,
+        method,
+        host,
+        path,
+        headers,
+        body,
+        MAX_RESPONSE_BYTES,
+    )
+}
+
+/// Send one request with a body to an `http://` or `https://` URL.
+///
+/// HTTPS verifies the server against the system and bundled roots.
+pub fn send(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+    timeout: Duration,
+) -> io::Result<Response> {
+    let url = Url::parse(url)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid URL"))?;
+    let addr = (url.host.as_str(), url.port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "host not found"))?;
+    let mut stream = connect(addr, timeout)?;
+    let host = url.host_header();
+
+    if !url.tls {
+        return exchange(
+            &mut stream,
+            method,
+            &host,
+            &url.path,
+            headers,
+            body,
+            MAX_RESPONSE_BYTES,
+        );
+    }
+
+    let config =
+        crate::connection::tls::web_client_config().map_err(|e| io::Error::other(e.to_string()))?;
+    let name = ServerName::try_from(url.host.clone())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let conn = rustls::ClientConnection::new(Arc::new(config), name)
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    let mut tls = rustls::StreamOwned::new(conn, stream);
+    exchange(
+        &mut tls,
+        method,
+        &host,
+        &url.path,
+        headers,
+        body,
+        MAX_RESPONSE_BYTES,
+    )
+}
+
+fn connect(addr: SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+    let stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    Ok(stream)
+}
+
+fn exchange<S: Read + Write>(
+    stream: &mut S,
+    method: &str,
+    host: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+    max_bytes: u64,
+) -> io::Result<Response> {
+    let mut request = format!(
+        "{method} {path} HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\nContent-Length: {}\r\n",
+        body.len()
+    );
+    for (name, value) in headers {
+        request.push_str(&format!("{name}: {value}\r\n"));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes())?;
+    stream.write_all(body)?;
+    stream.flush()?;
+
+    let mut response = Vec::new();
+    match stream.take(max_bytes).read_to_end(&mut response) {
+        Ok(_) => {}
+        // Servers commonly close TLS connections without close_notify
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof && !response.is_empty() => {}
+        Err(e) => return Err(e),
+    }
+    parse_response(&response)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed HTTP response"))
+}
+
+/// Parts of an `http://` or `https://` URL needed to make a request
+#[derive(Debug, PartialEq, Eq)]
+struct Url {
+    tls: bool,
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Url {
+    fn parse(url: &str) -> Option<Self> {
+        let (scheme, rest) = url.split_once("://")?;
+        let tls = match scheme.to_ascii_lowercase().as_str() {
+            "http" => false,
+            "https" => true,
+            _ => return None,
+        };
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(i) if rest[i..].starts_with('/') => (&rest[..i], rest[i..].to_string()),
+            Some(i) => (&rest[..i], format!("/{}", &rest[i..])),
+            None => (rest, "/".to_string()),
+        };
+        let default_port = if tls { 443 } else { 80 };
+        let (host, port) = if let Some(bracketed) = authority.strip_prefix('[') {
+            let (host, rest) = bracketed.split_once(']')?;
+            let port = match rest.strip_prefix(':') {
+                Some(port) => port.parse().ok()?,
+                None if rest.is_empty() => default_port,
+                None => return None,
+            };
+            (host, port)
+        } else {
+            match authority.rsplit_once(':') {
+                Some((host, port)) => (host, port.parse().ok()?),
+                None => (authority, default_port),
+            }
+        };
+        if host.is_empty() || authority.contains('@') {
+            return None;
+        }
+        Some(Self {
+            tls,
+            host: host.to_string(),
+            port,
+            path,
+        })
+    }
+
+    fn host_header(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        if self.port == if self.tls { 443 } else { 80 } {
+            host
+        } else {
+            format!("{host}:{}", self.port)
+        }
+    }
+}
+
+/// Percent-encode `value` for use in a query string
+pub fn encode_query(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~*,".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+fn parse_response(response: &[u8]) -> Option<Response> {
+    let split = response.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let head = std::str::from_utf8(&response[..split]).ok()?;
+    let body = &response[split + 4..];
+    let mut lines = head.lines();
+    let status = lines.next()?.split_whitespace().nth(1)?.parse().ok()?;
+    let chunked = lines.any(|l| {
+        l.split_once(':').is_some_and(|(k, v)| {
+            k.trim().eq_ignore_ascii_case("transfer-encoding") && v.trim() == "chunked"
+        })
+    });
+    let body = if chunked {
+        decode_chunked(body)?
+    } else {
+        body.to_vec()
+    };
+    Some(Response { status, body })
+}
+
+fn decode_chunked(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    loop {
+        let line_end = body.windows(2).position(|w| w == b"\r\n")?;
+        let size = std::str::from_utf8(&body[..line_end]).ok()?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        if size == 0 {
+            return Some(out);
+        }
+        let rest = &body[line_end + 2..];
+        out.extend_from_slice(rest.get(..size)?);
+        body = rest.get(size..)?.strip_prefix(b"\r\n")?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        let plain = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\ntoken";
+        assert_eq!(
+            parse_response(plain),
+            Some(Response {
+                status: 200,
+                body: b"token".to_vec()
+            })
+        );
+        let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\n{\"a\"\r\n3\r\n:1}\r\n0\r\n\r\n";
+        assert_eq!(parse_response(chunked).unwrap().text(), "{\"a\":1}");
+        assert_eq!(
+            parse_response(b"HTTP/1.1 401 Unauthorized\r\n\r\n")
+                .unwrap()
+                .status,
+            401
+        );
+        assert!(parse_response(b"garbage").is_none());
+    }
+
+    #[test]
+    fn test_url_parse() {
+        let url = Url::parse("https://hooks.slack.com/services/T0/B0/x?y=1").unwrap();
+        assert!(url.tls);
+        assert_eq!(url.host, "hooks.slack.com");
+        assert_eq!(url.port, 443);
+        assert_eq!(url.path, "/services/T0/B0/x?y=1");
+        assert_eq!(url.host_header(), "hooks.slack.com");
+
+        let url = Url::parse("http://[::1]:8080").unwrap();
+        assert_eq!((url.host.as_str(), url.port), ("::1", 8080));
+        assert_eq!(url.path, "/");
+        assert_eq!(url.host_header(), "[::1]:8080");
+
+        assert_eq!(Url::parse("http://example.com?a=1").unwrap().path, "/?a=1");
+        assert!(Url::parse("ftp://example.com/").is_none());
+        assert!(Url::parse("https://user:pw@example.com/").is_none());
+        assert!(Url::parse("http://example.com:port/").is_none());
+    }
+
+    #[test]
+    fn test_encode_query() {
+        assert_eq!(encode_query("cpu.*,disks.*.used"), "cpu.*,disks.*.used");
+        assert_eq!(encode_query("a b&c+d/é"), "a%20b%26c%2Bd%2F%C3%A9");
+    }
+}
@@ -0,0 +1,113 @@
+//! Stable machine identity
+//!
+//! Derives the persistent agent ID from the OS machine ID or firmware UUID so
+//! that reinstalling the agent (or losing its config) keeps the same identity.
+//! The raw machine ID is hashed with an agent-specific namespace and never
+//! sent to servers.
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use super::safe_command::run_command;
+use sha2::{Digest, Sha256};
+
+/// Namespace mixed into the hash so the agent ID can't be correlated with the
+/// machine ID used by other software
+const AGENT_ID_NAMESPACE: &[u8] = b"nanolink-agent/v1:";
+
+/// Read the raw, platform-specific machine identifier
+#[cfg(target_os = "linux")]
+fn read_machine_id() -> Option<String> {
+    [
+        "/etc/machine-id",
+        "/var/lib/dbus/machine-id",
+        "/sys/class/dmi/id/product_uuid",
+    ]
+    .iter()
+    .filter_map(|path| std::fs::read_to_string(path).ok())
+    .map(|s| s.trim().to_string())
+    .find(|s| is_usable(s))
+}
+
+#[cfg(target_os = "macos")]
+fn read_machine_id() -> Option<String> {
+    let output = run_command("ioreg", &["-rd1", "-c", "IOPlatformExpertDevice"])?;
+    output
+        .lines()
+        .find(|line| line.contains("IOPlatformUUID"))
+        .and_then(|line| line.split('"').nth(3))
+        .map(|s| s.to_string())
+        .filter(|s| is_usable(s))
+}
+
+#[cfg(target_os = "windows")]
+fn read_machine_id() -> Option<String> {
+    let output = run_command(
+        "reg",
+        &[
+            "query",
+            r"HKLM\SOFTWARE\Microsoft\Cryptography",
+            "/v",
+            "MachineGuid",
+        ],
+    )?;
+    output
+        .lines()
+        .find(|line| line.contains("MachineGuid"))
+        .and_then(|line| line.split_whitespace().last())
+        .map(|s| s.to_string())
+        .filter(|s| is_usable(s))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn read_machine_id() -> Option<String> {
+    None
+}
+
+/// Reject empty and placeholder IDs (some VMs/firmware report all zeros or all Fs)
+fn is_usable(id: &str) -> bool {
+    let hex: String = id.chars().filter(|c| c.is_ascii_hexdigit()).collect();
+    !hex.is_empty() && hex.chars().any(|c| c != '0') && hex.chars().any(|c| !"fF".contains(c))
+}
+
+/// Derive a UUID from a machine ID
+fn derive_agent_id(machine_id: &str) -> uuid::Uuid {
+    let mut hasher = Sha256::new();
+    hasher.update(AGENT_ID_NAMESPACE);
+    hasher.update(machine_id.trim().to_ascii_lowercase().as_bytes());
+    let digest = hasher.finalize();
+
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    uuid::Builder::from_custom_bytes(bytes).into_uuid()
+}
+
+/// Generate a stable agent ID.
+///
+/// Derived from the machine ID when available, otherwise a random UUID.
+/// Returns the ID and whether it is machine-derived.
+pub fn stable_agent_id() -> (String, bool) {
+    match read_machine_id() {
+        Some(machine_id) => (derive_agent_id(&machine_id).to_string(), true),
+        None => (uuid::Uuid::new_v4().to_string(), false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_agent_id_is_stable() {
+        let a = derive_agent_id("4c4c4544-0042-3510-8052-b4c04f4e3132");
+        let b = derive_agent_id("4C4C4544-0042-3510-8052-B4C04F4E3132\n");
+        assert_eq!(a, b);
+        assert_ne!(a, derive_agent_id("4c4c4544-0042-3510-8052-b4c04f4e3133"));
+    }
+
+    #[test]
+    fn test_is_usable() {
+        assert!(is_usable("0b8f1c7e2a6d4e8f9a3b5c7d1e2f3a4b"));
+        assert!(!is_usable(""));
+        assert!(!is_usable("00000000-0000-0000-0000-000000000000"));
+        assert!(!is_usable("FFFFFFFF-FFFF-FFFF-FFFF-FFFFFFFFFFFF"));
+    }
+}
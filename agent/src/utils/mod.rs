@@ -1,4 +1,16 @@
 //! Utility modules for NanoLink Agent
 
 pub mod async_command;
+// Sealing and tar writing are only used by the executors, and most of the
+// cron schedules
+#[cfg_attr(not(feature = "executors"), allow(dead_code))]
+pub mod at_rest;
+pub mod clock;
+#[cfg_attr(not(feature = "executors"), allow(dead_code))]
+pub mod cron;
+pub mod export;
+pub mod http;
+pub mod machine_id;
 pub mod safe_command;
+#[cfg_attr(not(feature = "executors"), allow(dead_code))]
+pub mod tar;
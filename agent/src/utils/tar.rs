@@ -0,0 +1,92 @@
+//! Minimal ustar archives
+//!
+//! Only regular files are supported, which is all update bundles and
+//! recording exports need.
+
+use std::collections::HashMap;
+
+/// Regular files of a ustar archive by name
+pub fn read(tar: &[u8]) -> Result<HashMap<String, Vec<u8>>, String> {
+    let mut files = HashMap::new();
+    let mut offset = 0;
+    while offset + 512 <= tar.len() {
+        let header = &tar[offset..offset + 512];
+        if header.iter().all(|b| *b == 0) {
+            break;
+        }
+        let field = |range: std::ops::Range<usize>| {
+            let raw = &header[range];
+            let end = raw.iter().position(|b| *b == 0).unwrap_or(raw.len());
+            String::from_utf8_lossy(&raw[..end]).into_owned()
+        };
+        let size = u64::from_str_radix(field(124..136).trim(), 8)
+            .map_err(|_| "Corrupt tar header".to_string())? as usize;
+        let mut name = field(0..100);
+        let prefix = field(345..500);
+        if header[257..262] == *b"ustar" && !prefix.is_empty() {
+            name = format!("{prefix}/{name}");
+        }
+
+        let start = offset + 512;
+        let end = start
+            .checked_add(size)
+            .filter(|end| *end <= tar.len())
+            .ok_or_else(|| "Truncated tar archive".to_string())?;
+        if matches!(header[156], b'0' | 0) {
+            let name = name.trim_start_matches("./").to_string();
+            files.insert(name, tar[start..end].to_vec());
+        }
+        offset = start + size.div_ceil(512) * 512;
+    }
+    Ok(files)
+}
+
+/// Append a regular file (name up to 100 bytes) to an archive
+pub fn append(tar: &mut Vec<u8>, name: &str, data: &[u8], mtime: u64) -> Result<(), String> {
+    if name.len() > 100 {
+        return Err(format!("File name too long for tar: {name}"));
+    }
+    let mut header = [0u8; 512];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[100..107].copy_from_slice(b"0000644");
+    header[108..115].copy_from_slice(b"0000000");
+    header[116..123].copy_from_slice(b"0000000");
+    header[124..135].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+    header[136..147].copy_from_slice(format!("{mtime:011o}").as_bytes());
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    // Checksum is computed with its own field set to spaces
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|b| u32::from(*b)).sum();
+    header[148..155].copy_from_slice(format!("{checksum:06o}\0").as_bytes());
+
+    tar.extend_from_slice(&header);
+    tar.extend_from_slice(data);
+    tar.resize(tar.len().div_ceil(512) * 512, 0);
+    Ok(())
+}
+
+/// Terminate an archive
+pub fn finish(tar: &mut Vec<u8>) {
+    tar.extend_from_slice(&[0u8; 1024]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut tar = Vec::new();
+        append(&mut tar, "chain.jsonl", b"{}\n", 0).unwrap();
+        append(&mut tar, "a.cast", &[b'x'; 700], 0).unwrap();
+        finish(&mut tar);
+        assert_eq!(tar.len() % 512, 0);
+
+        let files = read(&tar).unwrap();
+        assert_eq!(files["chain.jsonl"], b"{}\n");
+        assert_eq!(files["a.cast"].len(), 700);
+        assert!(read(&tar[..1600]).is_err());
+    }
+}
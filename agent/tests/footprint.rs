@@ -0,0 +1,94 @@
+//! Footprint of the lite build
+//!
+//! The lite profile targets IoT gateways: a binary under 5 MB and under
+//! 15 MB resident while collecting and streaming. Only the resident target
+//! is met. The x86_64 Linux binary is about 6.0 MiB, most of it TLS, gRPC
+//! (h2, tonic) and the YAML config parser, so `test_binary_size` fails
+//! until the binary gets under 5 MB.
+//! Sizes only mean something for an optimized build, so CI runs
+//!
+//! ```text
+//! cargo test --release --no-default-features --features lite --test footprint
+//! ```
+
+#![cfg(feature = "lite")]
+
+use std::path::Path;
+
+const MAX_BINARY_SIZE: u64 = 5 * 1024 * 1024;
+const MAX_RESIDENT: u64 = 15 * 1024 * 1024;
+
+fn agent() -> &'static Path {
+    Path::new(env!("CARGO_BIN_EXE_nanolink-agent"))
+}
+
+#[test]
+fn test_binary_size() {
+    if cfg!(debug_assertions) {
+        eprintln!("Skipped: binary size is only checked in release builds");
+        return;
+    }
+    let size = std::fs::metadata(agent()).unwrap().len();
+    assert!(
+        size <= MAX_BINARY_SIZE,
+        "Lite binary is {size} bytes, limit is {MAX_BINARY_SIZE}"
+    );
+}
+
+/// Peak resident memory of the agent collecting for a while, with its
+/// server unreachable so it also runs the reconnect and buffering path
+#[cfg(target_os = "linux")]
+#[test]
+fn test_resident_memory() {
+    use std::process::{Command, Stdio};
+    use std::time::Duration;
+
+    if cfg!(debug_assertions) {
+        eprintln!("Skipped: resident memory is only checked in release builds");
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("nanolink-footprint-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("nanolink.yaml");
+    std::fs::write(
+        &config,
+        "servers:\n  - host: 127.0.0.1\n    port: 9\n    token: footprint\n    tls_enabled: false\n\
+         collector:\n  realtime_interval_ms: 1000\n",
+    )
+    .unwrap();
+
+    let mut child = Command::new(agent())
+        .arg("--config")
+        .arg(&config)
+        .arg("--foreground")
+        .current_dir(&dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(Duration::from_secs(15));
+    let status = std::fs::read_to_string(format!("/proc/{}/status", child.id()));
+    let _ = child.kill();
+    let _ = child.wait();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let status = status.expect("agent exited early");
+    // Peak resident set size in kB
+    let peak = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))
+        .and_then(|value| {
+            value
+                .trim()
+                .trim_end_matches("kB")
+                .trim()
+                .parse::<u64>()
+                .ok()
+        })
+        .unwrap()
+        * 1024;
+    assert!(
+        peak <= MAX_RESIDENT,
+        "Lite agent peaked at {peak} bytes resident, limit is {MAX_RESIDENT}"
+    );
+}
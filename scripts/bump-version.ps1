@@ -107,6 +107,10 @@ function Main {
     # Files to update
     $files = @(
         "agent/Cargo.toml",
+        "agent/crates/nanolink-client/Cargo.toml",
+        "agent/crates/nanolink-core/Cargo.toml",
+        "agent/crates/nanolink-parse/Cargo.toml",
+        "agent/crates/nanolink-mockserver/Cargo.toml",
         "agent/src/main.rs",
         "sdk/java/pom.xml",
         "sdk/go/nanolink/version.go",
@@ -108,6 +108,10 @@ main() {
 
     # Update each file
     update_file "agent/Cargo.toml" "$CURRENT_VERSION" "$NEW_VERSION"
+    update_file "agent/crates/nanolink-client/Cargo.toml" "$CURRENT_VERSION" "$NEW_VERSION"
+    update_file "agent/crates/nanolink-core/Cargo.toml" "$CURRENT_VERSION" "$NEW_VERSION"
+    update_file "agent/crates/nanolink-parse/Cargo.toml" "$CURRENT_VERSION" "$NEW_VERSION"
+    update_file "agent/crates/nanolink-mockserver/Cargo.toml" "$CURRENT_VERSION" "$NEW_VERSION"
     update_file "agent/src/main.rs" "$CURRENT_VERSION" "$NEW_VERSION"
     update_file "sdk/java/pom.xml" "$CURRENT_VERSION" "$NEW_VERSION"
     update_file "sdk/go/nanolink/version.go" "$CURRENT_VERSION" "$NEW_VERSION"